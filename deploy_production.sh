@@ -59,7 +59,7 @@ case $choice in
     1)
         echo ""
         echo -e "${CYAN}Applying migrations...${NC}"
-        wrangler d1 execute $DB_NAME --file=./migrations/001_init_schema_d1.sql
+        wrangler d1 execute $DB_NAME --file=./migrations/sqlite/001_init_schema.sql
         echo -e "${GREEN}✓${NC} Migrations applied"
         ;;
     2)
@@ -132,7 +132,7 @@ case $choice in
         # Step 1: Migrations
         echo ""
         echo -e "${CYAN}[1/4] Applying migrations...${NC}"
-        wrangler d1 execute $DB_NAME --file=./migrations/001_init_schema_d1.sql
+        wrangler d1 execute $DB_NAME --file=./migrations/sqlite/001_init_schema.sql
         echo -e "${GREEN}✓${NC} Migrations applied"
         
         # Step 2: Verify
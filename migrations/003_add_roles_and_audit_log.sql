@@ -0,0 +1,16 @@
+-- Migration 003: Admin roles and audit log
+-- Adds a role column used to gate `qhub admin` and a table recording every admin action
+
+ALTER TABLE qhub.users ADD COLUMN IF NOT EXISTS role TEXT NOT NULL DEFAULT 'user';
+
+CREATE TABLE IF NOT EXISTS qhub.audit_log (
+    id TEXT PRIMARY KEY,
+    actor TEXT NOT NULL,
+    action TEXT NOT NULL,
+    target TEXT,
+    details TEXT,
+    created_at BIGINT NOT NULL DEFAULT EXTRACT(EPOCH FROM NOW())::BIGINT
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_log_created_at ON qhub.audit_log(created_at);
+CREATE INDEX IF NOT EXISTS idx_audit_log_actor ON qhub.audit_log(actor);
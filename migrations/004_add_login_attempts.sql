@@ -0,0 +1,13 @@
+-- Migration 004: Login attempt history
+-- Records each login attempt for rate limiting and auditing; pruned by `qhub db maintain`
+
+CREATE TABLE IF NOT EXISTS qhub.login_attempts (
+    id TEXT PRIMARY KEY,
+    email TEXT NOT NULL,
+    ip_address TEXT,
+    success BOOLEAN NOT NULL DEFAULT FALSE,
+    attempted_at BIGINT NOT NULL DEFAULT EXTRACT(EPOCH FROM NOW())::BIGINT
+);
+
+CREATE INDEX IF NOT EXISTS idx_login_attempts_email ON qhub.login_attempts(email, attempted_at);
+CREATE INDEX IF NOT EXISTS idx_login_attempts_attempted_at ON qhub.login_attempts(attempted_at);
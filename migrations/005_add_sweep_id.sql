@@ -0,0 +1,6 @@
+-- Migration 005: Parameter sweeps
+-- Each point of a `qhub sweep` is a job; sweep_id groups them
+
+ALTER TABLE qhub.quantum_jobs ADD COLUMN IF NOT EXISTS sweep_id TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_quantum_jobs_sweep_id ON qhub.quantum_jobs(sweep_id);
@@ -0,0 +1,6 @@
+-- Migration 004: Parameter sweeps (SQLite / D1)
+-- Each point of a `qhub sweep` is a job; sweep_id groups them
+
+ALTER TABLE quantum_jobs ADD COLUMN sweep_id TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_quantum_jobs_sweep_id ON quantum_jobs(sweep_id);
@@ -3,6 +3,9 @@ use serde::{Deserialize, Serialize};
 use std::time::Duration;
 use thiserror::Error;
 
+use super::http;
+use crate::config::settings::NetworkConfig;
+
 /// API client errors
 #[derive(Error, Debug)]
 pub enum ApiError {
@@ -43,6 +46,13 @@ pub struct RegisterRequest {
     pub email: String,
     pub password: String,
     pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UpdateProfileRequest {
+    pub display_name: String,
 }
 
 #[derive(Debug, Serialize)]
@@ -52,6 +62,7 @@ pub struct LoginRequest {
 }
 
 #[derive(Debug, Deserialize)]
+#[allow(dead_code)]
 pub struct AuthResponse {
     pub token: String,
     pub user: User,
@@ -59,29 +70,52 @@ pub struct AuthResponse {
 }
 
 #[derive(Debug, Deserialize, Clone)]
+#[allow(dead_code)]
 pub struct User {
     pub id: String,
     pub email: String,
     pub username: Option<String>,
+    /// Older servers don't send it
+    #[serde(default)]
+    pub display_name: Option<String>,
     pub tier: String,
+    /// Unix seconds of the login before this one; older servers don't send it
+    #[serde(default)]
+    pub last_login_at: Option<i64>,
+}
+
+impl User {
+    /// Name to show: display name, then username, then email
+    pub fn name(&self) -> &str {
+        self.display_name.as_deref().or(self.username.as_deref()).unwrap_or(&self.email)
+    }
 }
 
 /// AI chat request/response types
 #[derive(Debug, Serialize)]
+#[allow(dead_code)]
 pub struct ChatRequest {
     pub message: String,
     pub conversation_id: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
+#[allow(dead_code)]
 pub struct ChatResponse {
     pub response: String,
     pub conversation_id: String,
     pub tokens_used: i32,
 }
 
+/// Link to an uploaded conversation or code block
+#[derive(Debug, Deserialize)]
+pub struct ShareResponse {
+    pub url: String,
+}
+
 /// API health check response
 #[derive(Debug, Deserialize)]
+#[allow(dead_code)]
 pub struct HealthResponse {
     pub status: String,
 }
@@ -96,8 +130,8 @@ pub struct ApiClient {
 
 impl ApiClient {
     /// Create a new API client with enterprise defaults
-    pub fn new(base_url: String) -> Result<Self, ApiError> {
-        let client = Client::builder()
+    pub fn new(base_url: String, network: &NetworkConfig) -> Result<Self, ApiError> {
+        let client = http::client_builder(network)
             .timeout(Duration::from_secs(30))
             .connect_timeout(Duration::from_secs(10))
             .pool_max_idle_per_host(10)
@@ -121,6 +155,11 @@ impl ApiClient {
     pub fn clear_token(&mut self) {
         self.token = None;
     }
+
+    /// The session token requests are sent with
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
     
     /// Build full URL from endpoint
     fn url(&self, endpoint: &str) -> String {
@@ -173,6 +212,7 @@ impl ApiClient {
     }
     
     /// Health check endpoint
+    #[allow(dead_code)]
     pub async fn health(&self) -> Result<HealthResponse, ApiError> {
         let response = self.client
             .get(self.url("/health"))
@@ -247,7 +287,60 @@ impl ApiClient {
         Ok(verify_resp.user)
     }
     
+    /// Change the logged-in user's profile
+    pub async fn update_profile(&self, req: &UpdateProfileRequest) -> Result<User, ApiError> {
+        let token = self.token.as_ref()
+            .ok_or_else(|| ApiError::Unauthorized("No token set".to_string()))?;
+        
+        let response = self.client
+            .patch(self.url("/auth/profile"))
+            .bearer_auth(token)
+            .json(req)
+            .send()
+            .await?;
+        
+        #[derive(Deserialize)]
+        struct ProfileResponse {
+            user: User,
+        }
+        
+        let profile_resp: ProfileResponse = self.handle_response(response).await?;
+        Ok(profile_resp.user)
+    }
+    
+    /// Upload anonymous usage counters. Sent without the auth token so they
+    /// can't be tied to an account.
+    pub async fn upload_telemetry<T: Serialize>(&self, batch: &T) -> Result<(), ApiError> {
+        let response = self.client
+            .post(self.url("/telemetry"))
+            .json(batch)
+            .send()
+            .await?;
+        
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(ApiError::ServerError(format!("Unexpected status: {}", response.status())))
+        }
+    }
+    
+    /// Publish a conversation or code block and get a link to it
+    pub async fn share<T: Serialize>(&self, req: &T) -> Result<ShareResponse, ApiError> {
+        let token = self.token.as_ref()
+            .ok_or_else(|| ApiError::Unauthorized("No token set".to_string()))?;
+        
+        let response = self.client
+            .post(self.url("/share"))
+            .bearer_auth(token)
+            .json(req)
+            .send()
+            .await?;
+        
+        self.handle_response(response).await
+    }
+    
     /// Send AI chat message
+    #[allow(dead_code)]
     pub async fn chat(&self, req: ChatRequest) -> Result<ChatResponse, ApiError> {
         let token = self.token.as_ref()
             .ok_or_else(|| ApiError::Unauthorized("No token set".to_string()))?;
@@ -269,13 +362,13 @@ mod tests {
     
     #[test]
     fn test_client_creation() {
-        let client = ApiClient::new("http://localhost:8787".to_string());
+        let client = ApiClient::new("http://localhost:8787".to_string(), &NetworkConfig::default());
         assert!(client.is_ok());
     }
     
     #[test]
     fn test_url_building() {
-        let client = ApiClient::new("http://localhost:8787".to_string()).unwrap();
+        let client = ApiClient::new("http://localhost:8787".to_string(), &NetworkConfig::default()).unwrap();
         assert_eq!(client.url("/health"), "http://localhost:8787/health");
         assert_eq!(client.url("/auth/login"), "http://localhost:8787/auth/login");
     }
@@ -1,15 +1,34 @@
 use anyhow::Result;
+use reqwest::header::HeaderMap;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-const CLOUDFLARE_GATEWAY_URL: &str = 
-    "https://gateway.ai.cloudflare.com/v1/2d4b81ed42312401410d8ab4cd8c5dcf/northstars-industries/compat/chat/completions";
+use super::request_log::{self, RequestLog};
+use super::response_cache;
+use super::{http, models};
+use crate::config::settings::{AiConfig, NetworkConfig};
+use crate::config::Config;
+use crate::diagnostics;
+
+/// QHub's hosted Cloudflare AI Gateway (OpenAI-compatible), used unless `ai.base_url` is set
+pub const DEFAULT_BASE_URL: &str =
+    "https://gateway.ai.cloudflare.com/v1/2d4b81ed42312401410d8ab4cd8c5dcf/northstars-industries/compat";
 
 #[derive(Debug, Clone)]
 pub struct DeepSeekClient {
     client: Client,
-    api_key: String,
+    /// None when nothing is configured and the endpoint is not QHub's own
+    api_key: Option<String>,
+    /// Full chat completions URL derived from `ai.base_url`
+    endpoint: String,
+    provider: String,
+    model: String,
+    max_tokens: u32,
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+    /// Set when `ai.log_requests` is on
+    log: Option<RequestLog>,
 }
 
 #[derive(Debug, Serialize)]
@@ -17,9 +36,77 @@ struct ChatRequest {
     model: String,
     messages: Vec<ChatMessage>,
     stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+}
+
+/// Overrides for a session (/set) or one request (/regen's hotter second
+/// opinion). Unset fields fall back to the client's configuration.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Sampling {
+    pub model: Option<String>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub max_tokens: Option<u32>,
+}
+
+/// Generation parameters /set can change
+pub const PARAMETERS: [&str; 3] = ["temperature", "top_p", "max_tokens"];
+
+/// The range OpenAI-compatible APIs accept for each parameter
+pub fn check_parameter(name: &str, value: f64) -> Result<(), String> {
+    let valid = match name {
+        "temperature" => (0.0..=2.0).contains(&value),
+        "top_p" => value > 0.0 && value <= 1.0,
+        "max_tokens" => value >= 1.0 && value <= u32::MAX as f64 && value.fract() == 0.0,
+        _ => return Err(format!("Unknown parameter '{}'. Try one of: {}", name, PARAMETERS.join(", "))),
+    };
+    if valid {
+        return Ok(());
+    }
+    Err(match name {
+        "temperature" => format!("temperature must be between 0 and 2, not {}", value),
+        "top_p" => format!("top_p must be above 0 and at most 1, not {}", value),
+        _ => format!("max_tokens must be a whole number of at least 1, not {}", value),
+    })
+}
+
+impl Sampling {
+    /// Set `name` from /set's text; `default` clears the override
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let value = match value {
+            "default" | "reset" => None,
+            text => {
+                let number: f64 = text.parse().map_err(|_| format!("'{}' is not a number", text))?;
+                check_parameter(name, number)?;
+                Some(number)
+            }
+        };
+        match name {
+            "temperature" => self.temperature = value.map(|v| v as f32),
+            "top_p" => self.top_p = value.map(|v| v as f32),
+            "max_tokens" => self.max_tokens = value.map(|v| v as u32),
+            _ => check_parameter(name, 0.0)?,
+        }
+        Ok(())
+    }
+
+    /// Session overrides with `request`'s on top
+    pub fn and(&self, request: &Sampling) -> Sampling {
+        Sampling {
+            model: request.model.clone().or_else(|| self.model.clone()),
+            temperature: request.temperature.or(self.temperature),
+            top_p: request.top_p.or(self.top_p),
+            max_tokens: request.max_tokens.or(self.max_tokens),
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct ChatMessage {
     pub role: String,
     pub content: String,
@@ -28,44 +115,184 @@ pub struct ChatMessage {
 #[derive(Debug, Deserialize)]
 struct ChatResponse {
     choices: Vec<Choice>,
+    #[serde(default)]
+    usage: Option<TokenUsage>,
+}
+
+/// Token counts reported by the API for one completion
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    #[serde(default)]
+    pub prompt_tokens: u64,
+    #[serde(default)]
+    pub completion_tokens: u64,
+    #[serde(default)]
+    pub total_tokens: u64,
+}
+
+/// Assistant reply plus the usage the API reported for it, if any
+#[derive(Debug, Clone)]
+pub struct ChatReply {
+    pub content: String,
+    pub usage: Option<TokenUsage>,
+    /// Cut off at the token limit (`finish_reason: "length"`)
+    pub truncated: bool,
+}
+
+/// The provider answered with HTTP 200 but nothing to show. Returned as the
+/// error of `chat_with_usage`; the prompt can be sent again unchanged.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum NoReply {
+    #[error("the AI returned an empty response")]
+    Empty,
+    #[error("the AI declined to answer: {0}")]
+    Refused(String),
+}
+
+/// The provider refused a request with HTTP 429. Returned as the error of
+/// `chat_with_usage` so callers can wait instead of failing.
+#[derive(Debug, Clone, Copy, PartialEq, thiserror::Error)]
+#[error("API error 429: rate limited by the AI provider")]
+pub struct RateLimited {
+    /// From the Retry-After header, when the provider sent one
+    pub retry_after: Option<Duration>,
+    /// Requests left in the current window, when reported
+    pub remaining: Option<u64>,
+}
+
+impl RateLimited {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let number = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<u64>().ok())
+        };
+        Self {
+            // Only the delta-seconds form; an HTTP date falls back to backoff
+            retry_after: number("retry-after").map(Duration::from_secs),
+            remaining: number("x-ratelimit-remaining-requests").or_else(|| number("x-ratelimit-remaining")),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 struct Choice {
     message: ResponseMessage,
+    #[serde(default)]
+    finish_reason: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 struct ResponseMessage {
-    content: String,
+    /// Null when the model refused or was filtered
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    refusal: Option<String>,
+}
+
+/// One `data:` event of a streamed completion
+#[derive(Debug, Deserialize)]
+struct StreamChunk {
+    #[serde(default)]
+    choices: Vec<StreamChoice>,
+    #[serde(default)]
+    usage: Option<TokenUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamChoice {
+    #[serde(default)]
+    delta: Option<ResponseMessage>,
+    /// Some proxies send whole messages as events instead of deltas
+    #[serde(default)]
+    message: Option<ResponseMessage>,
+    #[serde(default)]
+    finish_reason: Option<String>,
 }
 
 impl DeepSeekClient {
-    pub fn new(api_key: String) -> Self {
-        let client = Client::builder()
+    pub fn new(api_key: String, ai: &AiConfig, network: &NetworkConfig) -> Self {
+        let client = http::client_builder(network)
             .timeout(Duration::from_secs(120))
             .connect_timeout(Duration::from_secs(10))
             .pool_idle_timeout(Duration::from_secs(90))
             .build()
             .unwrap_or_else(|_| Client::new());
-        
+        let log = if ai.log_requests { request_log::log_path().ok() } else { None };
+
         Self {
             client,
-            api_key,
+            log: log.map(|path| RequestLog::new(path, &api_key)),
+            api_key: Some(api_key),
+            endpoint: chat_completions_url(&ai.base_url),
+            provider: ai.provider.clone(),
+            model: ai.model.clone(),
+            max_tokens: ai.max_tokens,
+            temperature: ai.temperature,
+            top_p: ai.top_p,
+        }
+    }
+
+    /// Temperature requests use unless overridden; providers default to 1.0
+    pub fn temperature(&self) -> f32 {
+        self.temperature.unwrap_or(1.0)
+    }
+
+    fn request(&self, messages: Vec<ChatMessage>, sampling: &Sampling) -> ChatRequest {
+        let mut request = ChatRequest {
+            model: sampling.model.clone().unwrap_or_else(|| self.model.clone()),
+            messages,
+            stream: false,
+            max_tokens: Some(sampling.max_tokens.unwrap_or(self.max_tokens)),
+            temperature: sampling.temperature.or(self.temperature),
+            top_p: sampling.top_p.or(self.top_p),
+        };
+        drop_unsupported(&self.provider, &mut request);
+        request
+    }
+
+    /// What `ai.cache` files a reply to `messages` under
+    pub fn cache_key(&self, messages: &[ChatMessage], sampling: &Sampling) -> String {
+        response_cache::key(&self.endpoint, &self.request(messages.to_vec(), sampling))
+    }
+
+    /// Client for the configured key, falling back to the built-in shared key
+    pub fn from_config(config: &Config) -> Self {
+        Self::for_key(config.get_ai_api_key(), &config.ai, &config.network)
+    }
+
+    /// The shared key only ever goes to QHub's own gateway; any other
+    /// endpoint without a key of its own gets a client that refuses to send
+    fn for_key(key: Option<String>, ai: &AiConfig, network: &NetworkConfig) -> Self {
+        match key {
+            Some(key) => Self::new(key, ai, network),
+            None if chat_completions_url(&ai.base_url) == chat_completions_url(DEFAULT_BASE_URL) => {
+                Self::new("75pX0slf0zE2EF6Kf0H-MjauYQosat8-wzqXP0eF".to_string(), ai, network)
+            }
+            None => Self { api_key: None, ..Self::new(String::new(), ai, network) },
         }
     }
 
-    pub fn from_env() -> Option<Self> {
-        std::env::var("CLOUDFLARE_AI_TOKEN")
-            .ok()
-            .map(|key| Self::new(key))
+    /// The key to send, or an error before anything is sent
+    fn key(&self) -> Result<&str> {
+        self.api_key.as_deref().ok_or_else(|| {
+            anyhow::anyhow!(
+                "No API key configured for {}. Set ai.api_key or use /keys set ai.",
+                self.endpoint
+            )
+        })
     }
 
-    pub fn with_default_key() -> Self {
-        Self::new("75pX0slf0zE2EF6Kf0H-MjauYQosat8-wzqXP0eF".to_string())
+    pub async fn chat_with_usage(&self, messages: Vec<ChatMessage>) -> Result<ChatReply> {
+        self.chat_with(messages, &Sampling::default()).await
     }
 
-    pub async fn chat(&self, messages: Vec<ChatMessage>) -> Result<String> {
+    /// `chat_with_usage` with `sampling` overriding the configured model and
+    /// parameters
+    pub async fn chat_with(&self, messages: Vec<ChatMessage>, sampling: &Sampling) -> Result<ChatReply> {
+        let key = self.key()?;
         // Retry logic with exponential backoff
         let max_retries = 3;
         let mut attempt = 0;
@@ -73,15 +300,22 @@ impl DeepSeekClient {
         loop {
             attempt += 1;
             
-            let request = ChatRequest {
-                model: "deepseek/deepseek-chat".to_string(),
-                messages: messages.clone(),
-                stream: false,
+            let request = self.request(messages.clone(), sampling);
+            let started = Instant::now();
+            let log = |status: Option<u16>, response: &str, error: Option<String>, usage: Option<TokenUsage>| {
+                if let Some(log) = &self.log {
+                    let mut entry = request_log::Entry::new(&self.endpoint, attempt, &request, started.elapsed());
+                    entry.status = status;
+                    entry.response = response.to_string();
+                    entry.error = error;
+                    entry.usage = usage;
+                    log.record(entry);
+                }
             };
 
             let result = self.client
-                .post(CLOUDFLARE_GATEWAY_URL)
-                .header("Authorization", format!("Bearer {}", self.api_key))
+                .post(&self.endpoint)
+                .header("Authorization", format!("Bearer {}", key))
                 .header("Content-Type", "application/json")
                 .header("User-Agent", "qhub-cli/0.1.0")
                 .json(&request)
@@ -93,42 +327,117 @@ impl DeepSeekClient {
                     let status = response.status();
                     
                     if status.is_success() {
-                        let chat_response: ChatResponse = response.json().await?;
-                        return chat_response
-                            .choices
-                            .first()
-                            .map(|c| c.message.content.clone())
-                            .ok_or_else(|| anyhow::anyhow!("No response from AI"));
+                        let content_type = response
+                            .headers()
+                            .get(reqwest::header::CONTENT_TYPE)
+                            .and_then(|v| v.to_str().ok())
+                            .unwrap_or_default()
+                            .to_string();
+                        let body = response.text().await?;
+                        let reply = parse_reply(&content_type, &body);
+                        log(Some(status.as_u16()), &body, None, reply.as_ref().ok().and_then(|r| r.usage));
+                        return reply;
                     }
                     
-                    // Handle rate limiting with retry
-                    if status.as_u16() == 429 && attempt < max_retries {
-                        let backoff = Duration::from_secs(2u64.pow(attempt));
-                        tokio::time::sleep(backoff).await;
-                        continue;
+                    // Short backoff for a bare 429; a provider that says when to
+                    // come back is left to the caller, which can show the wait
+                    if status.as_u16() == 429 {
+                        log(Some(429), "", None, None);
+                        let limited = RateLimited::from_headers(response.headers());
+                        if limited.retry_after.is_none() && attempt < max_retries {
+                            let backoff = Duration::from_secs(2u64.pow(attempt));
+                            tokio::time::sleep(backoff).await;
+                            continue;
+                        }
+                        return Err(limited.into());
                     }
                     
                     // Handle other errors
                     let text = response.text().await.unwrap_or_default();
+                    log(Some(status.as_u16()), &text, None, None);
                     anyhow::bail!("API error {}: {}", status, text);
                 }
                 Err(e) if attempt < max_retries && e.is_timeout() => {
+                    log(None, "", Some(e.to_string()), None);
                     // Retry on timeout
                     let backoff = Duration::from_secs(2u64.pow(attempt));
                     tokio::time::sleep(backoff).await;
                     continue;
                 }
                 Err(e) => {
+                    log(None, "", Some(e.to_string()), None);
                     return Err(e.into());
                 }
             }
         }
     }
 
-    pub fn get_system_prompt() -> ChatMessage {
-        ChatMessage {
-            role: "system".to_string(),
-            content: r#"You are QHub, an AI assistant specialized in quantum computing. 
+    /// The provider's model list from `GET {base_url}/models`.
+    /// `NoModelsEndpoint` when it has none.
+    pub async fn list_models(&self) -> Result<Vec<String>> {
+        let key = self.key()?;
+        let base = self.endpoint.trim_end_matches("/chat/completions");
+        let response = self
+            .client
+            .get(format!("{}/models", base))
+            .header("Authorization", format!("Bearer {}", key))
+            .header("User-Agent", "qhub-cli/0.1.0")
+            .send()
+            .await?;
+        let status = response.status();
+        if matches!(status.as_u16(), 404 | 405 | 501) {
+            return Err(models::NoModelsEndpoint(base.to_string()).into());
+        }
+        if !status.is_success() {
+            anyhow::bail!("API error {}: {}", status, response.text().await.unwrap_or_default());
+        }
+        models::parse(&response.text().await?)
+    }
+
+    /// Check the API key with the smallest possible completion request
+    pub async fn verify_key(&self) -> Result<()> {
+        let key = self.key()?;
+        let request = ChatRequest {
+            model: self.model.clone(),
+            messages: vec![ChatMessage {
+                role: "user".to_string(),
+                content: "ping".to_string(),
+            }],
+            stream: false,
+            max_tokens: Some(1),
+            temperature: None,
+            top_p: None,
+        };
+
+        let response = self.client
+            .post(&self.endpoint)
+            .header("Authorization", format!("Bearer {}", key))
+            .header("Content-Type", "application/json")
+            .header("User-Agent", "qhub-cli/0.1.0")
+            .json(&request)
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            anyhow::bail!("API error {}: {}", status, text);
+        }
+        Ok(())
+    }
+
+    /// The built-in system prompt, followed by the config's own
+    /// instructions (e.g. a project's) when it has any
+    pub fn get_system_prompt(ai: &AiConfig) -> ChatMessage {
+        let content = match ai.system_prompt.as_deref().map(str::trim) {
+            Some(extra) if !extra.is_empty() => format!("{}\n\n{}", SYSTEM_PROMPT, extra),
+            _ => SYSTEM_PROMPT.to_string(),
+        };
+        ChatMessage { role: "system".to_string(), content }
+    }
+}
+
+const SYSTEM_PROMPT: &str = r#"You are QHub, an AI assistant specialized in quantum computing. 
 You help users design and implement quantum algorithms and circuits.
 
 When a user describes a computation they want to perform:
@@ -137,7 +446,324 @@ When a user describes a computation they want to perform:
 3. Explain the expected output/results
 
 Keep responses concise but informative. Use code blocks with ```python for code.
-Focus on practical, runnable quantum circuits for IBM Quantum backends."#.to_string(),
+Focus on practical, runnable quantum circuits for IBM Quantum backends."#;
+
+/// Make `history[0]` the system prompt. Trimming, /clear and imports all
+/// rely on it being there; a prompt found further down is moved back to the
+/// front, a missing one re-added, and one with other instructions (from a
+/// session resumed in another project) replaced. Returns true if anything
+/// changed.
+pub fn ensure_system_prompt(history: &mut Vec<ChatMessage>, prompt: &ChatMessage) -> bool {
+    if history.first() == Some(prompt) {
+        return false;
+    }
+    history.retain(|m| !(m.role == "system" && m.content.starts_with(SYSTEM_PROMPT)));
+    history.insert(0, prompt.clone());
+    true
+}
+
+/// A completion body as the gateway sent it. We ask for `stream: false`, but
+/// some proxies in front of the gateway answer with an event stream anyway;
+/// that is collapsed into one reply.
+fn parse_reply(content_type: &str, body: &str) -> Result<ChatReply> {
+    let body = body.trim_start_matches('\u{feff}').trim();
+    if content_type.starts_with("text/event-stream") || body.starts_with("data:") {
+        return parse_event_stream(body);
+    }
+    let chat_response: ChatResponse = serde_json::from_str(body)
+        .map_err(|e| anyhow::anyhow!("Unreadable AI response ({}): {}", e, snippet(body)))?;
+    let Some(choice) = chat_response.choices.into_iter().next() else {
+        return Err(NoReply::Empty.into());
+    };
+    let message = choice.message;
+    let content = message.content.unwrap_or_default();
+    reply(content, message.refusal, choice.finish_reason, chat_response.usage)
+}
+
+/// Leave out parameters the model would reject rather than fail the
+/// request: reasoning models take no sampling settings, and Anthropic takes
+/// temperature or top_p but not both
+fn drop_unsupported(provider: &str, request: &mut ChatRequest) {
+    let name = request.model.rsplit('/').next().unwrap_or(&request.model).to_ascii_lowercase();
+    let reasoning = name.contains("reasoner") || ["o1", "o3", "o4"].iter().any(|p| name.starts_with(p));
+    if reasoning {
+        for (parameter, value) in [("temperature", request.temperature.take()), ("top_p", request.top_p.take())] {
+            if value.is_some() {
+                diagnostics::debug(format!("{} does not take {}; left out of the request", request.model, parameter));
+            }
+        }
+    } else if provider == "anthropic" && request.temperature.is_some() && request.top_p.take().is_some() {
+        diagnostics::debug("Anthropic takes temperature or top_p, not both; top_p left out of the request");
+    }
+}
+
+/// Join the content of every `data:` event up to `[DONE]`
+fn parse_event_stream(body: &str) -> Result<ChatReply> {
+    let mut content = String::new();
+    let mut refusal: Option<String> = None;
+    let mut finish_reason = None;
+    let mut usage = None;
+    for line in body.lines() {
+        // Comments, event names and ids carry nothing we need
+        let Some(data) = line.strip_prefix("data:") else { continue };
+        let data = data.trim();
+        if data == "[DONE]" {
+            break;
+        }
+        if data.is_empty() {
+            continue;
+        }
+        let chunk: StreamChunk = serde_json::from_str(data)
+            .map_err(|e| anyhow::anyhow!("Unreadable AI stream event ({}): {}", e, snippet(data)))?;
+        for choice in chunk.choices {
+            if let Some(part) = choice.delta.or(choice.message) {
+                content.push_str(part.content.as_deref().unwrap_or_default());
+                if let Some(text) = part.refusal {
+                    refusal.get_or_insert_with(String::new).push_str(&text);
+                }
+            }
+            finish_reason = choice.finish_reason.or(finish_reason);
         }
+        usage = chunk.usage.or(usage);
+    }
+    reply(content, refusal, finish_reason, usage)
+}
+
+/// Sort a finished completion into a reply or the reason there is none
+fn reply(content: String, refusal: Option<String>, finish_reason: Option<String>, usage: Option<TokenUsage>) -> Result<ChatReply> {
+    if let Some(reason) = refusal.filter(|r| !r.trim().is_empty()) {
+        return Err(NoReply::Refused(reason.trim().to_string()).into());
+    }
+    if finish_reason.as_deref() == Some("content_filter") {
+        return Err(NoReply::Refused("blocked by the provider's content filter".to_string()).into());
+    }
+    if content.trim().is_empty() {
+        return Err(NoReply::Empty.into());
+    }
+    let truncated = finish_reason.as_deref() == Some("length");
+    Ok(ChatReply { content, usage, truncated })
+}
+
+/// The start of a body for error messages
+fn snippet(body: &str) -> String {
+    const MAX: usize = 120;
+    match body.char_indices().nth(MAX) {
+        Some((end, _)) => format!("{}…", &body[..end]),
+        None => body.to_string(),
+    }
+}
+
+/// OpenAI-compatible chat endpoint under a base URL such as `http://localhost:8000/v1`
+fn chat_completions_url(base_url: &str) -> String {
+    format!("{}/chat/completions", base_url.trim_end_matches('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn body(ai: &AiConfig, sampling: &Sampling) -> Value {
+        let client = DeepSeekClient::new("key".to_string(), ai, &NetworkConfig::default());
+        serde_json::to_value(client.request(Vec::new(), sampling)).unwrap()
+    }
+
+    fn close(value: &Value, expected: f64) -> bool {
+        value.as_f64().is_some_and(|v| (v - expected).abs() < 1e-6)
+    }
+
+    #[test]
+    fn test_request_carries_the_configured_parameters() {
+        let ai = AiConfig { temperature: Some(0.2), top_p: Some(0.9), max_tokens: 2048, ..AiConfig::default() };
+        let sent = body(&ai, &Sampling::default());
+        assert_eq!(sent["model"], ai.model.as_str());
+        assert_eq!(sent["max_tokens"], 2048);
+        assert!(close(&sent["temperature"], 0.2) && close(&sent["top_p"], 0.9));
+
+        // Unset parameters are left to the provider
+        let sent = body(&AiConfig::default(), &Sampling::default());
+        assert!(sent.get("temperature").is_none() && sent.get("top_p").is_none());
+        assert_eq!(sent["max_tokens"], 4096);
+    }
+
+    #[test]
+    fn test_sampling_overrides_the_configured_values() {
+        let ai = AiConfig { temperature: Some(0.2), ..AiConfig::default() };
+        let mut session = Sampling::default();
+        session.set("max_tokens", "512").unwrap();
+        session.set("temperature", "0.5").unwrap();
+        let regen = Sampling { model: Some("gpt-4o".to_string()), temperature: Some(1.3), ..Sampling::default() };
+
+        let sent = body(&ai, &session);
+        assert!(close(&sent["temperature"], 0.5));
+        assert_eq!(sent["max_tokens"], 512);
+        let sent = body(&ai, &session.and(&regen));
+        assert_eq!(sent["model"], "gpt-4o");
+        assert!(close(&sent["temperature"], 1.3));
+        assert_eq!(sent["max_tokens"], 512);
+
+        session.set("temperature", "default").unwrap();
+        assert!(close(&body(&ai, &session)["temperature"], 0.2));
+    }
+
+    #[test]
+    fn test_set_validates_ranges() {
+        let mut sampling = Sampling::default();
+        assert!(sampling.set("temperature", "2.5").unwrap_err().contains("between 0 and 2"));
+        assert!(sampling.set("top_p", "0").is_err());
+        assert!(sampling.set("max_tokens", "1.5").is_err());
+        assert!(sampling.set("max_tokens", "lots").unwrap_err().contains("not a number"));
+        assert!(sampling.set("seed", "1").unwrap_err().contains("Unknown parameter"));
+        assert_eq!(sampling, Sampling::default());
+    }
+
+    #[test]
+    fn test_unsupported_parameters_are_left_out() {
+        let ai = AiConfig { temperature: Some(0.2), top_p: Some(0.9), ..AiConfig::default() };
+        let reasoner = Sampling { model: Some("deepseek/deepseek-reasoner".to_string()), ..Sampling::default() };
+        let sent = body(&ai, &reasoner);
+        assert!(sent.get("temperature").is_none() && sent.get("top_p").is_none());
+        assert_eq!(sent["max_tokens"], 4096);
+
+        let anthropic = AiConfig { provider: "anthropic".to_string(), ..ai };
+        let sent = body(&anthropic, &Sampling::default());
+        assert!(close(&sent["temperature"], 0.2));
+        assert!(sent.get("top_p").is_none());
+    }
+
+    #[test]
+    fn test_rate_limit_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after", "42".parse().unwrap());
+        headers.insert("x-ratelimit-remaining-requests", "0".parse().unwrap());
+        assert_eq!(
+            RateLimited::from_headers(&headers),
+            RateLimited { retry_after: Some(Duration::from_secs(42)), remaining: Some(0) }
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after", "Wed, 21 Oct 2026 07:28:00 GMT".parse().unwrap());
+        assert_eq!(
+            RateLimited::from_headers(&headers),
+            RateLimited { retry_after: None, remaining: None }
+        );
+    }
+
+    #[test]
+    fn test_ensure_system_prompt() {
+        let prompt = DeepSeekClient::get_system_prompt(&AiConfig::default());
+        let user = ChatMessage { role: "user".to_string(), content: "Bell state?".to_string() };
+
+        let mut history = Vec::new();
+        assert!(ensure_system_prompt(&mut history, &prompt));
+        assert_eq!(history, vec![prompt.clone()]);
+        assert!(!ensure_system_prompt(&mut history, &prompt));
+
+        // Misplaced by a bad import: moved, not duplicated
+        let mut history = vec![user.clone(), prompt.clone()];
+        assert!(ensure_system_prompt(&mut history, &prompt));
+        assert_eq!(history, vec![prompt.clone(), user.clone()]);
+
+        // Resumed in a project with its own instructions: replaced
+        let ai = AiConfig { system_prompt: Some("Use the VQE ansatz in ansatz.qasm".to_string()), ..AiConfig::default() };
+        let project_prompt = DeepSeekClient::get_system_prompt(&ai);
+        assert!(project_prompt.content.ends_with("\n\nUse the VQE ansatz in ansatz.qasm"));
+        assert!(ensure_system_prompt(&mut history, &project_prompt));
+        assert_eq!(history, vec![project_prompt, user]);
+    }
+
+    #[test]
+    fn test_parse_reply_json() {
+        let body = r#"{"choices":[{"message":{"role":"assistant","content":"Hello"}}],"usage":{"total_tokens":12}}"#;
+        let reply = parse_reply("application/json; charset=utf-8", body).unwrap();
+        assert_eq!(reply.content, "Hello");
+        assert_eq!(reply.usage.unwrap().total_tokens, 12);
+
+        assert!(!reply.truncated);
+
+        let error = parse_reply("application/json", "<html>502</html>").unwrap_err().to_string();
+        assert!(error.contains("Unreadable AI response"), "{}", error);
+    }
+
+    fn no_reply(body: &str) -> Option<NoReply> {
+        parse_reply("application/json", body).unwrap_err().downcast::<NoReply>().ok()
+    }
+
+    #[test]
+    fn test_parse_reply_empty_choices() {
+        let body = r#"{"id":"chatcmpl-91","object":"chat.completion","created":1760000000,"model":"deepseek-chat","choices":[],"usage":{"prompt_tokens":412,"completion_tokens":0,"total_tokens":412}}"#;
+        assert_eq!(no_reply(body), Some(NoReply::Empty));
+
+        let body = r#"{"choices":[{"index":0,"message":{"role":"assistant","content":"  \n"},"finish_reason":"stop"}]}"#;
+        assert_eq!(no_reply(body), Some(NoReply::Empty));
+    }
+
+    #[test]
+    fn test_parse_reply_refusal() {
+        let body = r#"{"choices":[{"index":0,"message":{"role":"assistant","content":null},"finish_reason":"content_filter"}],"usage":{"total_tokens":120}}"#;
+        assert_eq!(
+            no_reply(body),
+            Some(NoReply::Refused("blocked by the provider's content filter".to_string()))
+        );
+
+        let body = r#"{"choices":[{"index":0,"message":{"role":"assistant","content":null,"refusal":"I can't help with that."},"finish_reason":"stop"}]}"#;
+        assert_eq!(no_reply(body), Some(NoReply::Refused("I can't help with that.".to_string())));
+    }
+
+    #[test]
+    fn test_parse_reply_truncated() {
+        let body = r#"{"choices":[{"index":0,"message":{"role":"assistant","content":"```python\nfrom qiskit import"},"finish_reason":"length"}],"usage":{"total_tokens":4096}}"#;
+        let reply = parse_reply("application/json", body).unwrap();
+        assert!(reply.truncated);
+        assert_eq!(reply.content, "```python\nfrom qiskit import");
+
+        let body = "data: {\"choices\":[{\"delta\":{\"content\":\"Grover\"}}]}\n\n\
+data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"length\"}]}\n\ndata: [DONE]\n";
+        assert!(parse_reply("text/event-stream", body).unwrap().truncated);
+    }
+
+    #[test]
+    fn test_parse_reply_event_stream() {
+        let body = "\
+: keep-alive\n\
+data: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\n\n\
+data: {\"choices\":[{\"delta\":{\"content\":\"Bell \"}}]}\n\n\
+data: {\"choices\":[{\"delta\":{\"content\":\"state\"}}]}\n\n\
+data: {\"choices\":[],\"usage\":{\"total_tokens\":30}}\n\n\
+data: [DONE]\n\n";
+        let reply = parse_reply("text/event-stream", body).unwrap();
+        assert_eq!(reply.content, "Bell state");
+        assert_eq!(reply.usage.unwrap().total_tokens, 30);
+
+        // Recognised by its shape when the content type is wrong
+        let body = "data: {\"choices\":[{\"message\":{\"content\":\"Hi\"}}]}\ndata: [DONE]";
+        assert_eq!(parse_reply("application/json", body).unwrap().content, "Hi");
+
+        assert!(parse_reply("text/event-stream", "data: [DONE]\n").is_err());
+    }
+
+    #[test]
+    fn test_chat_completions_url() {
+        assert_eq!(
+            chat_completions_url(DEFAULT_BASE_URL),
+            format!("{}/chat/completions", DEFAULT_BASE_URL)
+        );
+        assert_eq!(
+            chat_completions_url("http://localhost:8000/v1/"),
+            "http://localhost:8000/v1/chat/completions"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_shared_key_stays_with_the_default_gateway() {
+        let network = NetworkConfig::default();
+        assert!(DeepSeekClient::for_key(None, &AiConfig::default(), &network).key().is_ok());
+
+        let ai = AiConfig { base_url: "http://localhost:8000/v1".to_string(), ..AiConfig::default() };
+        let client = DeepSeekClient::for_key(None, &ai, &network);
+        let error = client.chat_with_usage(Vec::new()).await.unwrap_err().to_string();
+        assert!(error.starts_with("No API key configured for http://localhost:8000/v1/chat/completions"), "{}", error);
+        assert!(client.list_models().await.is_err());
+        assert_eq!(DeepSeekClient::for_key(Some("mine".to_string()), &ai, &network).key().unwrap(), "mine");
     }
 }
@@ -0,0 +1,76 @@
+// Shared reqwest setup for every outgoing HTTP client
+//
+// reqwest already reads HTTP_PROXY/HTTPS_PROXY/NO_PROXY; this adds the
+// `[network]` overrides (explicit proxy, extra CA certificates, and the opt-in
+// to skip certificate verification).
+
+use anyhow::{Context, Result};
+use reqwest::{Certificate, ClientBuilder, NoProxy, Proxy};
+use std::path::Path;
+
+use crate::config::settings::NetworkConfig;
+
+/// A client builder with the proxy and TLS settings applied.
+/// Values were validated when the config was loaded; any that fail here are skipped.
+pub fn client_builder(network: &NetworkConfig) -> ClientBuilder {
+    let mut builder = reqwest::Client::builder();
+
+    if let Some(proxy) = network.proxy.as_deref().and_then(|url| Proxy::all(url).ok()) {
+        // An explicit proxy turns off reqwest's env lookup, so keep NO_PROXY working
+        builder = builder.proxy(proxy.no_proxy(NoProxy::from_env()));
+    }
+
+    if let Some(certs) = network.ca_bundle.as_deref().and_then(|path| load_ca_bundle(path).ok()) {
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+
+    if network.danger_accept_invalid_certs {
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+
+    builder
+}
+
+/// Read every certificate from a PEM bundle
+pub fn load_ca_bundle(path: &Path) -> Result<Vec<Certificate>> {
+    let pem = std::fs::read(path)
+        .with_context(|| format!("Failed to read CA bundle {}", path.display()))?;
+    let certs = Certificate::from_pem_bundle(&pem)
+        .with_context(|| format!("Invalid PEM in CA bundle {}", path.display()))?;
+    if certs.is_empty() {
+        anyhow::bail!("CA bundle {} contains no certificates", path.display());
+    }
+    Ok(certs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_accepts_network_settings() {
+        let network = NetworkConfig {
+            proxy: Some("http://proxy.corp.example:3128".to_string()),
+            ca_bundle: None,
+            danger_accept_invalid_certs: true,
+        };
+        assert!(client_builder(&network).build().is_ok());
+    }
+
+    #[test]
+    fn test_ca_bundle_errors_are_clear() {
+        let dir = std::env::temp_dir().join(format!("qhub-ca-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let missing = load_ca_bundle(&dir.join("missing.pem")).unwrap_err();
+        assert!(missing.to_string().contains("Failed to read CA bundle"));
+
+        let empty = dir.join("empty.pem");
+        std::fs::write(&empty, "not a certificate").unwrap();
+        assert!(load_ca_bundle(&empty).is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
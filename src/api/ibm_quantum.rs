@@ -1 +1,222 @@
-// IBM Quantum client - to be implemented in Phase 6
+// IBM Quantum client
+use anyhow::Result;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use super::http;
+use super::quantum_error::QuantumError;
+use crate::config::settings::NetworkConfig;
+use crate::config::tiers::TierLimits;
+use crate::quantum::calibration::BackendInfo;
+use crate::quantum::estimate::BackendProfile;
+use crate::quantum::job::{JobProgress, JobStatus};
+use crate::quantum::select::{self, Recommendation, Requirements};
+
+const IBM_AUTH_URL: &str = "https://auth.quantum-computing.ibm.com/api";
+const IBM_RUNTIME_URL: &str = "https://api.quantum-computing.ibm.com/runtime";
+
+/// Backends looked up per fetch; each costs two requests
+const MAX_BACKENDS: usize = 12;
+
+#[derive(Debug, Clone)]
+pub struct IbmQuantumClient {
+    client: Client,
+    token: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LoginRequest<'a> {
+    api_token: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct LoginResponse {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct BackendList {
+    devices: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BackendStatusResponse {
+    #[serde(default)]
+    state: bool,
+    pending_jobs: Option<u32>,
+    #[serde(default)]
+    status: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JobResponse {
+    status: String,
+}
+
+/// Only some backends report queue positions
+#[derive(Debug, Default, Deserialize)]
+struct JobMetrics {
+    #[serde(default)]
+    position_in_queue: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BackendConfiguration {
+    n_qubits: Option<u32>,
+}
+
+/// One backend the account can use, as of the last fetch
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackendStatus {
+    pub name: String,
+    pub qubits: Option<u32>,
+    pub pending_jobs: Option<u32>,
+    pub operational: bool,
+}
+
+impl IbmQuantumClient {
+    pub fn new(token: String, network: &NetworkConfig) -> Self {
+        let client = http::client_builder(network)
+            .timeout(Duration::from_secs(30))
+            .connect_timeout(Duration::from_secs(10))
+            .pool_idle_timeout(Duration::from_secs(90))
+            .build()
+            .unwrap_or_else(|_| Client::new());
+
+        Self { client, token }
+    }
+
+    /// Exchange the API token for a short-lived access token
+    pub async fn login(&self) -> Result<String> {
+        let response = self.client
+            .post(format!("{}/users/loginWithToken", IBM_AUTH_URL))
+            .header("User-Agent", "qhub-cli/0.1.0")
+            .json(&LoginRequest { api_token: &self.token })
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(QuantumError::from_response(status.as_u16(), &text).into());
+        }
+
+        let login: LoginResponse = response.json().await?;
+        Ok(login.id)
+    }
+
+    /// Check that the API token is accepted by IBM Quantum
+    pub async fn verify_token(&self) -> Result<()> {
+        self.login().await.map(|_| ())
+    }
+
+    /// Backends available to this account with their size and queue depth
+    pub async fn backends(&self) -> Result<Vec<BackendStatus>> {
+        let access = self.login().await?;
+        let list: BackendList = self.get(&access, "/backends").await?;
+
+        let mut backends = Vec::new();
+        for name in list.devices.into_iter().take(MAX_BACKENDS) {
+            let status: BackendStatusResponse =
+                self.get(&access, &format!("/backends/{}/status", name)).await?;
+            // Simulators and some retired devices have no configuration
+            let qubits = self
+                .get::<BackendConfiguration>(&access, &format!("/backends/{}/configuration", name))
+                .await
+                .ok()
+                .and_then(|c| c.n_qubits);
+            backends.push(BackendStatus {
+                name,
+                qubits,
+                pending_jobs: status.pending_jobs,
+                operational: status.state,
+            });
+        }
+        Ok(backends)
+    }
+
+    /// Gate timings and queue depth of one backend, for estimates
+    pub async fn backend_profile(&self, name: &str) -> Result<BackendProfile> {
+        let access = self.login().await?;
+        let status: BackendStatusResponse = self.get(&access, &format!("/backends/{}/status", name)).await?;
+        let properties: serde_json::Value = self.get(&access, &format!("/backends/{}/properties", name)).await?;
+        let configuration: serde_json::Value = self
+            .get(&access, &format!("/backends/{}/configuration", name))
+            .await
+            .unwrap_or_default();
+        Ok(BackendProfile::from_ibm(name, &properties, &configuration, status.pending_jobs))
+    }
+
+    /// Configuration, calibration and queue of one backend, for /backend-info
+    pub async fn backend_info(&self, name: &str) -> Result<BackendInfo> {
+        let access = self.login().await?;
+        let status: BackendStatusResponse = self.get(&access, &format!("/backends/{}/status", name)).await?;
+        // Simulators have no calibration
+        let properties: serde_json::Value = self
+            .get(&access, &format!("/backends/{}/properties", name))
+            .await
+            .unwrap_or_default();
+        let configuration: serde_json::Value = self
+            .get(&access, &format!("/backends/{}/configuration", name))
+            .await
+            .unwrap_or_default();
+        Ok(BackendInfo::from_ibm(name, &properties, &configuration, status.state, status.status, status.pending_jobs))
+    }
+
+    /// Status of a job, with its queue position while queued when the
+    /// backend reports one
+    pub async fn job_progress(&self, id: &str) -> Result<JobProgress> {
+        let access = self.login().await?;
+        let job: JobResponse = self.get(&access, &format!("/jobs/{}", id)).await?;
+        let status = JobStatus::parse(&job.status);
+        let position = if status == JobStatus::Queued {
+            self.get::<JobMetrics>(&access, &format!("/jobs/{}/metrics", id))
+                .await
+                .unwrap_or_default()
+                .position_in_queue
+        } else {
+            None
+        };
+        Ok(JobProgress { status, position })
+    }
+
+    /// Recommend a backend for a circuit needing `requirements`, from
+    /// `backends` when already fetched. Error rates are looked up for the
+    /// shortlist only, as each costs a request.
+    pub async fn recommend(
+        &self,
+        requirements: &Requirements,
+        limits: &TierLimits,
+        backends: Option<Vec<BackendStatus>>,
+    ) -> Result<Recommendation> {
+        let backends = match backends {
+            Some(backends) => backends,
+            None => self.backends().await?,
+        };
+        let mut errors = std::collections::HashMap::new();
+        for backend in select::candidates(requirements, &backends, limits).into_iter().take(select::SHORTLIST) {
+            if let Some(error) = self.backend_profile(&backend.name).await.ok().and_then(|p| p.two_qubit_error) {
+                errors.insert(backend.name.clone(), error);
+            }
+        }
+        select::select_backend(requirements, &backends, &errors, limits).map_err(anyhow::Error::msg)
+    }
+
+    async fn get<T: serde::de::DeserializeOwned>(&self, access: &str, path: &str) -> Result<T> {
+        let response = self.client
+            .get(format!("{}{}", IBM_RUNTIME_URL, path))
+            .header("User-Agent", "qhub-cli/0.1.0")
+            .bearer_auth(access)
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(QuantumError::from_response(status.as_u16(), &text).into());
+        }
+        Ok(response.json().await?)
+    }
+}
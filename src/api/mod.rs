@@ -1,9 +1,17 @@
 pub mod client;
+pub mod http;
 pub mod deepseek;
+pub mod models;
+pub mod tokens;
+pub mod transcript;
+pub mod request_log;
+pub mod response_cache;
 pub mod ibm_quantum;
+pub mod quantum_error;
 pub mod backend;
 
+#[allow(unused_imports)]
 pub use client::{
     ApiClient, ApiError, AuthResponse, ChatRequest, ChatResponse, LoginRequest, RegisterRequest,
-    User,
+    UpdateProfileRequest, User,
 };
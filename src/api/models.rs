@@ -0,0 +1,167 @@
+// Models the AI provider offers
+//
+// OpenAI-compatible APIs list their models at `GET {base_url}/models`.
+// `/models refresh` fetches that list and caches it in
+// ~/.qhub/cache/models.json along with the base URL it came from, so /model
+// can check and complete names without a request. Until a list is fetched,
+// or when the provider has no such endpoint, a built-in list of common
+// models stands in; it cannot be complete, so names missing from it are
+// allowed with a note rather than refused.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use crate::config::Config;
+
+/// Common models, used until the provider's own list is fetched
+pub const KNOWN_MODELS: &[&str] = &[
+    "deepseek/deepseek-chat",
+    "deepseek/deepseek-reasoner",
+    "openai/gpt-4o",
+    "openai/gpt-4o-mini",
+    "openai/gpt-4-turbo",
+    "openai/gpt-3.5-turbo",
+    "anthropic/claude-3-5-sonnet",
+    "anthropic/claude-3-haiku",
+];
+
+/// The provider answered, but has no models endpoint
+#[derive(Debug, Error)]
+#[error("{0} does not list its models")]
+pub struct NoModelsEndpoint(pub String);
+
+/// A fetched model list as cached on disk
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ModelList {
+    pub base_url: String,
+    /// Unix time in seconds
+    pub fetched_at: i64,
+    pub models: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ModelsResponse {
+    data: Vec<ModelEntry>,
+}
+
+#[derive(Deserialize)]
+struct ModelEntry {
+    id: String,
+}
+
+pub fn cache_path() -> Result<PathBuf> {
+    Ok(Config::cache_dir()?.join("models.json"))
+}
+
+/// `{"data": [{"id": ...}, ...]}`, sorted and without duplicates
+pub fn parse(body: &str) -> Result<Vec<String>> {
+    let response: ModelsResponse = serde_json::from_str(body).context("The models list was not in the expected format")?;
+    let mut models: Vec<String> = response.data.into_iter().map(|m| m.id).filter(|id| !id.is_empty()).collect();
+    models.sort();
+    models.dedup();
+    Ok(models)
+}
+
+/// The cached list, if it was fetched from `base_url`
+pub fn load_cached(path: &Path, base_url: &str) -> Option<ModelList> {
+    let list: ModelList = serde_json::from_str(&fs::read_to_string(path).ok()?).ok()?;
+    (list.base_url == base_url).then_some(list)
+}
+
+pub fn save(path: &Path, list: &ModelList) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).context("Failed to create cache directory")?;
+    }
+    fs::write(path, serde_json::to_string(list)?).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Model names match with or without the provider prefix,
+/// e.g. `deepseek-chat` and `deepseek/deepseek-chat`
+pub fn same_model(a: &str, b: &str) -> bool {
+    let bare = |name: &str| name.rsplit('/').next().unwrap_or(name).to_ascii_lowercase();
+    a.eq_ignore_ascii_case(b) || bare(a) == bare(b)
+}
+
+/// The models /model checks against: the provider's, once fetched
+#[derive(Debug, Clone, PartialEq)]
+pub enum Catalog {
+    Builtin,
+    Provider(ModelList),
+}
+
+impl Catalog {
+    pub fn models(&self) -> Vec<&str> {
+        match self {
+            Catalog::Builtin => KNOWN_MODELS.to_vec(),
+            Catalog::Provider(list) => list.models.iter().map(String::as_str).collect(),
+        }
+    }
+
+    /// The listed name `model` refers to
+    pub fn find(&self, model: &str) -> Option<&str> {
+        self.models().into_iter().find(|known| same_model(known, model))
+    }
+
+    /// Names starting with `prefix`, with or without their provider prefix
+    pub fn complete(&self, prefix: &str) -> Vec<&str> {
+        let prefix = prefix.to_ascii_lowercase();
+        self.models()
+            .into_iter()
+            .filter(|m| {
+                let name = m.to_ascii_lowercase();
+                name.starts_with(&prefix) || name.rsplit('/').next().is_some_and(|bare| bare.starts_with(&prefix))
+            })
+            .collect()
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Catalog::Builtin => "built-in list; /models refresh asks the provider".to_string(),
+            Catalog::Provider(list) => match DateTime::from_timestamp(list.fetched_at, 0) {
+                Some(at) => format!("from the provider, fetched {}", at.with_timezone(&Local).format("%Y-%m-%d %H:%M")),
+                None => "from the provider".to_string(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let body = r#"{"object":"list","data":[{"id":"gpt-4o","object":"model"},{"id":"deepseek-chat"},{"id":"gpt-4o"}]}"#;
+        assert_eq!(parse(body).unwrap(), ["deepseek-chat", "gpt-4o"]);
+        assert!(parse("<html>Not Found</html>").is_err());
+    }
+
+    #[test]
+    fn test_catalog_matching() {
+        let builtin = Catalog::Builtin;
+        assert_eq!(builtin.find("deepseek-chat"), Some("deepseek/deepseek-chat"));
+        assert_eq!(builtin.find("GPT-4o"), Some("openai/gpt-4o"));
+        assert_eq!(builtin.find("llama-3"), None);
+        assert_eq!(builtin.complete("gpt-4o"), ["openai/gpt-4o", "openai/gpt-4o-mini"]);
+        assert_eq!(builtin.complete("deepseek/deepseek-r"), ["deepseek/deepseek-reasoner"]);
+
+        let list = ModelList { base_url: "https://example.com/v1".to_string(), fetched_at: 1_760_000_000, models: vec!["llama-3".to_string()] };
+        let provider = Catalog::Provider(list);
+        assert_eq!(provider.find("llama-3"), Some("llama-3"));
+        assert_eq!(provider.find("gpt-4o"), None);
+    }
+
+    #[test]
+    fn test_cache_is_per_base_url() {
+        let path = std::env::temp_dir().join(format!("qhub-models-{}.json", uuid::Uuid::new_v4()));
+        let list = ModelList { base_url: "https://example.com/v1".to_string(), fetched_at: 1_760_000_000, models: vec!["a".to_string()] };
+        save(&path, &list).unwrap();
+        assert_eq!(load_cached(&path, "https://example.com/v1"), Some(list));
+        assert_eq!(load_cached(&path, "https://other.example.com/v1"), None);
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,147 @@
+// Errors from the quantum provider
+//
+// IBM Quantum answers failures with JSON in one of two shapes: the runtime
+// API's `{"errors": [{"code", "message", ...}]}` and the auth API's
+// `{"error": {"message", "code", ...}}`. `QuantumError::from_response` sorts
+// them into the cases a user can act on; anything it doesn't recognise keeps
+// the response body verbatim so nothing is lost.
+
+use regex::Regex;
+use serde_json::Value;
+use thiserror::Error;
+
+#[derive(Debug, Clone, Error, PartialEq)]
+pub enum QuantumError {
+    #[error("IBM Quantum rejected the API token")]
+    AuthInvalid,
+    #[error("backend {} is offline", backend.as_deref().unwrap_or("(unnamed)"))]
+    BackendOffline {
+        backend: Option<String>,
+        /// When the provider says it is back, as it wrote it
+        until: Option<String>,
+    },
+    #[error("limit of pending jobs reached")]
+    QueueLimitReached,
+    #[error("circuit needs {qubits} qubits but the backend has {max}")]
+    CircuitTooLarge { qubits: u32, max: u32 },
+    #[error("transpilation failed: {0}")]
+    TranspileFailed(String),
+    #[error("IBM Quantum error: {0}")]
+    ProviderInternal(String),
+}
+
+impl QuantumError {
+    /// Classify a failed response from its status code and body
+    pub fn from_response(status: u16, body: &str) -> Self {
+        let message = error_message(body).unwrap_or_default();
+        let lower = message.to_lowercase();
+
+        if status == 401 || status == 403 || ["login failed", "invalid token", "unauthorized"]
+            .iter()
+            .any(|p| lower.contains(p))
+        {
+            return QuantumError::AuthInvalid;
+        }
+        if lower.contains("qubit")
+            && ["maximum", "greater than", "exceeds", "larger than"].iter().any(|p| lower.contains(p))
+        {
+            let number = |pattern: &str| {
+                Regex::new(pattern)
+                    .expect("valid regex")
+                    .captures(&message)
+                    .and_then(|c| c[1].parse().ok())
+            };
+            let qubits = number(r"(?i)qubits?\D*?(\d+)");
+            let max = number(r"(?i)(?:maximum|than|exceeds)\D*?(\d+)");
+            if let (Some(qubits), Some(max)) = (qubits, max) {
+                return QuantumError::CircuitTooLarge { qubits, max };
+            }
+        }
+        if ["pending jobs", "queue limit", "maximum number of jobs"].iter().any(|p| lower.contains(p)) {
+            return QuantumError::QueueLimitReached;
+        }
+        if ["offline", "not available", "unavailable", "maintenance", "paused"].iter().any(|p| lower.contains(p)) {
+            let capture = |pattern: &str| {
+                Regex::new(pattern)
+                    .expect("valid regex")
+                    .captures(&message)
+                    .map(|c| c[1].trim_end_matches('.').to_string())
+            };
+            return QuantumError::BackendOffline {
+                backend: capture(r"\b(ibm[a-z]*_[a-z0-9_]+)"),
+                until: capture(r"(?i)\buntil ([^,;]+?)(?:\.?$|[,;])"),
+            };
+        }
+        if lower.contains("transpil") || lower.contains("not supported by the target") {
+            return QuantumError::TranspileFailed(message);
+        }
+        QuantumError::ProviderInternal(if body.trim().is_empty() {
+            format!("HTTP {}", status)
+        } else {
+            body.trim().to_string()
+        })
+    }
+}
+
+/// The human-readable message in either IBM error shape
+fn error_message(body: &str) -> Option<String> {
+    let json: Value = serde_json::from_str(body).ok()?;
+    let message = json
+        .pointer("/errors/0/message")
+        .or_else(|| json.pointer("/error/message"))
+        .or_else(|| json.get("message"))
+        .or_else(|| json.get("detail"))?;
+    message.as_str().map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> &'static str {
+        match name {
+            "auth_invalid" => include_str!("fixtures/ibm/auth_invalid.json"),
+            "backend_offline" => include_str!("fixtures/ibm/backend_offline.json"),
+            "queue_limit" => include_str!("fixtures/ibm/queue_limit.json"),
+            "circuit_too_large" => include_str!("fixtures/ibm/circuit_too_large.json"),
+            "transpile_failed" => include_str!("fixtures/ibm/transpile_failed.json"),
+            "internal" => include_str!("fixtures/ibm/internal.json"),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_parses_captured_responses() {
+        assert_eq!(QuantumError::from_response(401, fixture("auth_invalid")), QuantumError::AuthInvalid);
+        assert_eq!(
+            QuantumError::from_response(400, fixture("backend_offline")),
+            QuantumError::BackendOffline {
+                backend: Some("ibm_kyoto".into()),
+                until: Some("2026-03-11 14:00 UTC".into()),
+            }
+        );
+        assert_eq!(QuantumError::from_response(429, fixture("queue_limit")), QuantumError::QueueLimitReached);
+        assert_eq!(
+            QuantumError::from_response(400, fixture("circuit_too_large")),
+            QuantumError::CircuitTooLarge { qubits: 156, max: 127 }
+        );
+        assert!(matches!(
+            QuantumError::from_response(400, fixture("transpile_failed")),
+            QuantumError::TranspileFailed(detail) if detail.contains("'rccx'")
+        ));
+    }
+
+    #[test]
+    fn test_unknown_payloads_keep_raw_text() {
+        let raw = fixture("internal");
+        assert_eq!(
+            QuantumError::from_response(500, raw),
+            QuantumError::ProviderInternal(raw.trim().to_string())
+        );
+        assert_eq!(
+            QuantumError::from_response(502, "<html>Bad gateway</html>"),
+            QuantumError::ProviderInternal("<html>Bad gateway</html>".into())
+        );
+        assert_eq!(QuantumError::from_response(503, ""), QuantumError::ProviderInternal("HTTP 503".into()));
+    }
+}
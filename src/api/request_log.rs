@@ -0,0 +1,170 @@
+// Raw AI requests and responses, for debugging prompts (ai.log_requests)
+//
+// Off unless turned on, since conversations can be sensitive. Each attempt
+// the AI client makes is appended to ~/.qhub/cache/ai_requests.jsonl as one
+// JSON object: the request body exactly as sent, the raw response body, the
+// HTTP status, how long it took and the token usage the provider reported.
+// The API key travels in a header that is not logged, and is replaced
+// wherever it turns up in the bodies too. Past `MAX_BYTES` the file moves to
+// ai_requests.1.jsonl, replacing the one before, so the log stays bounded.
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::deepseek::TokenUsage;
+use crate::config::Config;
+use crate::diagnostics;
+
+const FILE_NAME: &str = "ai_requests.jsonl";
+const PREVIOUS_FILE_NAME: &str = "ai_requests.1.jsonl";
+
+/// Size at which the log is rotated
+pub const MAX_BYTES: u64 = 5 * 1024 * 1024;
+
+const REDACTED: &str = "[REDACTED]";
+
+pub fn log_path() -> Result<PathBuf> {
+    Ok(Config::cache_dir()?.join(FILE_NAME))
+}
+
+/// One request attempt and what came back
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Entry {
+    /// RFC 3339, local time
+    pub timestamp: String,
+    pub endpoint: String,
+    /// 1 for the first try, counting up through retries
+    pub attempt: u32,
+    pub elapsed_ms: u64,
+    pub request: Value,
+    /// None when no response arrived
+    pub status: Option<u16>,
+    pub response: String,
+    /// Transport failure, when there was no response
+    pub error: Option<String>,
+    pub usage: Option<TokenUsage>,
+}
+
+impl Entry {
+    pub fn new(endpoint: &str, attempt: u32, request: &impl Serialize, elapsed: Duration) -> Self {
+        Self {
+            timestamp: Local::now().to_rfc3339(),
+            endpoint: endpoint.to_string(),
+            attempt,
+            elapsed_ms: elapsed.as_millis() as u64,
+            request: serde_json::to_value(request).unwrap_or(Value::Null),
+            status: None,
+            response: String::new(),
+            error: None,
+            usage: None,
+        }
+    }
+}
+
+/// Where a client logs its requests; cheap to clone with the client
+#[derive(Debug, Clone)]
+pub struct RequestLog {
+    path: PathBuf,
+    /// Replaced in everything written
+    secret: String,
+    /// Set after a failed write, so only the first one is reported
+    failing: Arc<AtomicBool>,
+}
+
+impl RequestLog {
+    pub fn new(path: PathBuf, secret: &str) -> Self {
+        Self { path, secret: secret.to_string(), failing: Arc::new(AtomicBool::new(false)) }
+    }
+
+    /// Append `entry` on a blocking worker so the request is not held up
+    pub fn record(&self, entry: Entry) {
+        let log = self.clone();
+        tokio::task::spawn_blocking(move || match append(&log.path, &entry, &log.secret) {
+            Ok(()) => log.failing.store(false, Ordering::SeqCst),
+            Err(e) => {
+                if !log.failing.swap(true, Ordering::SeqCst) {
+                    diagnostics::warn(format!("Could not write the AI request log: {:#}", e));
+                }
+            }
+        });
+    }
+}
+
+/// Write `entry` as one line with `secret` blanked out, rotating first when
+/// the file is full
+pub fn append(path: &Path, entry: &Entry, secret: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).context("Failed to create cache directory")?;
+    }
+    if fs::metadata(path).is_ok_and(|m| m.len() >= MAX_BYTES) {
+        let previous = path.with_file_name(PREVIOUS_FILE_NAME);
+        fs::rename(path, &previous).with_context(|| format!("Failed to rotate {}", path.display()))?;
+    }
+
+    let mut line = serde_json::to_string(entry)?;
+    if !secret.is_empty() {
+        // Serialized strings escape the same way on both sides, so the key
+        // is found wherever it appears
+        let escaped = serde_json::to_string(secret)?;
+        line = line.replace(escaped.trim_matches('"'), REDACTED);
+    }
+    line.push('\n');
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    file.write_all(line.as_bytes()).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn temp_log() -> PathBuf {
+        std::env::temp_dir().join(format!("qhub-request-log-{}", uuid::Uuid::new_v4())).join(FILE_NAME)
+    }
+
+    #[test]
+    fn test_entries_are_redacted() {
+        let path = temp_log();
+        let request = json!({"model": "deepseek-chat", "messages": [{"role": "user", "content": "my key is sk-secret-1"}]});
+        let mut entry = Entry::new("https://example.com/chat/completions", 1, &request, Duration::from_millis(1250));
+        entry.status = Some(200);
+        entry.response = r#"{"choices":[{"message":{"content":"Never share sk-secret-1"}}]}"#.to_string();
+        entry.usage = Some(TokenUsage { prompt_tokens: 10, completion_tokens: 5, total_tokens: 15 });
+        append(&path, &entry, "sk-secret-1").unwrap();
+
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(!text.contains("sk-secret-1"), "{}", text);
+        let logged: Value = serde_json::from_str(text.trim()).unwrap();
+        assert_eq!(logged["request"]["messages"][0]["content"], "my key is [REDACTED]");
+        assert_eq!(logged["elapsed_ms"], 1250);
+        assert_eq!(logged["status"], 200);
+        assert_eq!(logged["usage"]["total_tokens"], 15);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn test_log_rotates() {
+        let path = temp_log();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, vec![b'x'; MAX_BYTES as usize]).unwrap();
+        let entry = Entry::new("https://example.com", 2, &json!({}), Duration::ZERO);
+        append(&path, &entry, "").unwrap();
+
+        let previous = path.with_file_name(PREVIOUS_FILE_NAME);
+        assert_eq!(fs::metadata(&previous).unwrap().len(), MAX_BYTES);
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 1);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,240 @@
+// Earlier replies, for answering a repeated request at once (ai.cache)
+//
+// Demos ask the same things again and again. With `ai.cache` on, each reply
+// is kept under a hash of the exact request - endpoint, model, parameters and
+// the whole conversation - so only an identical request gets it back; any
+// change to the history or a /set parameter is a miss. "memory" keeps
+// replies for this run, "disk" also writes them to ~/.qhub/cache/ai/, one
+// JSON file per request, and drops the least recently used files once the
+// directory grows past `MAX_DISK_BYTES`. Streamed replies are stored as the
+// text they add up to. `/cache clear` empties both.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::config::settings::CacheMode;
+use crate::config::Config;
+use crate::diagnostics;
+
+/// Replies kept in memory; the least recently used goes first
+const MAX_MEMORY_ENTRIES: usize = 200;
+
+/// Size of ~/.qhub/cache/ai before old replies are dropped
+const MAX_DISK_BYTES: u64 = 20 * 1024 * 1024;
+
+pub fn cache_dir() -> Result<PathBuf> {
+    Ok(Config::cache_dir()?.join("ai"))
+}
+
+/// A reply as it is cached
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cached {
+    pub content: String,
+    pub truncated: bool,
+}
+
+/// Hex SHA-256 of `request` as sent to `endpoint`
+pub fn key(endpoint: &str, request: &impl Serialize) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(endpoint.as_bytes());
+    hasher.update([0]);
+    hasher.update(serde_json::to_vec(request).unwrap_or_default());
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub struct ResponseCache {
+    mode: CacheMode,
+    /// Reply and when it was last used
+    memory: HashMap<String, (Cached, u64)>,
+    clock: u64,
+    /// Where "disk" mode writes; None when there is no home directory
+    dir: Option<PathBuf>,
+    max_disk_bytes: u64,
+}
+
+impl ResponseCache {
+    pub fn new(mode: CacheMode, dir: Option<PathBuf>) -> Self {
+        Self { mode, memory: HashMap::new(), clock: 0, dir, max_disk_bytes: MAX_DISK_BYTES }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.mode != CacheMode::Off
+    }
+
+    /// The reply cached for `key`, marking it recently used
+    pub fn get(&mut self, key: &str) -> Option<Cached> {
+        if !self.is_enabled() {
+            return None;
+        }
+        self.clock += 1;
+        if let Some((cached, used)) = self.memory.get_mut(key) {
+            *used = self.clock;
+            return Some(cached.clone());
+        }
+        let path = self.disk_path(key)?;
+        let cached: Cached = serde_json::from_str(&fs::read_to_string(&path).ok()?).ok()?;
+        // The modification time is the file's last use
+        if let Ok(file) = File::options().write(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        self.remember(key, cached.clone());
+        Some(cached)
+    }
+
+    pub fn put(&mut self, key: &str, cached: Cached) {
+        if !self.is_enabled() {
+            return;
+        }
+        if let Some(path) = self.disk_path(key) {
+            if let Err(e) = write(&path, &cached).and_then(|_| evict(path.parent().unwrap_or(&path), self.max_disk_bytes)) {
+                diagnostics::debug(format!("Reply not cached on disk: {:#}", e));
+            }
+        }
+        self.clock += 1;
+        self.remember(key, cached);
+    }
+
+    /// Forget every reply, on disk too whatever the mode; the number dropped
+    pub fn clear(&mut self) -> Result<usize> {
+        let mut keys: Vec<String> = self.memory.drain().map(|(key, _)| key).collect();
+        if let Some(dir) = self.dir.as_ref().filter(|d| d.exists()) {
+            for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+                let path = entry?.path();
+                if path.extension().is_some_and(|e| e == "json") {
+                    fs::remove_file(&path).with_context(|| format!("Failed to remove {}", path.display()))?;
+                    keys.extend(path.file_stem().map(|s| s.to_string_lossy().into_owned()));
+                }
+            }
+        }
+        keys.sort();
+        keys.dedup();
+        Ok(keys.len())
+    }
+
+    fn remember(&mut self, key: &str, cached: Cached) {
+        self.memory.insert(key.to_string(), (cached, self.clock));
+        if self.memory.len() > MAX_MEMORY_ENTRIES {
+            if let Some(oldest) = self.memory.iter().min_by_key(|(_, (_, used))| *used).map(|(key, _)| key.clone()) {
+                self.memory.remove(&oldest);
+            }
+        }
+    }
+
+    fn disk_path(&self, key: &str) -> Option<PathBuf> {
+        match self.mode {
+            CacheMode::Disk => self.dir.as_ref().map(|dir| dir.join(format!("{}.json", key))),
+            _ => None,
+        }
+    }
+}
+
+fn write(path: &Path, cached: &Cached) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).context("Failed to create the reply cache directory")?;
+    }
+    fs::write(path, serde_json::to_string(cached)?).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Remove the least recently used replies until `dir` is within `max_bytes`
+fn evict(dir: &Path, max_bytes: u64) -> Result<()> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_file() {
+            files.push((metadata.modified()?, metadata.len(), entry.path()));
+        }
+    }
+    let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+    files.sort_by_key(|(modified, _, _)| *modified);
+    for (_, len, path) in files {
+        if total <= max_bytes {
+            break;
+        }
+        fs::remove_file(&path).with_context(|| format!("Failed to remove {}", path.display()))?;
+        total -= len;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::time::Duration;
+
+    fn reply(content: &str) -> Cached {
+        Cached { content: content.to_string(), truncated: false }
+    }
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("qhub-reply-cache-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn test_key_is_stable_and_covers_the_request() {
+        let request = json!({"model": "deepseek-chat", "messages": [{"role": "user", "content": "Bell state?"}]});
+        let first = key("https://example.com/chat/completions", &request);
+        assert_eq!(first, key("https://example.com/chat/completions", &request.clone()));
+        assert_eq!(first, "af4095e6c0ab8f1a274ed049f0bc3cef437d62231708f119c2031c07398d9817");
+
+        let other_model = json!({"model": "deepseek-reasoner", "messages": request["messages"]});
+        assert_ne!(first, key("https://example.com/chat/completions", &other_model));
+        assert_ne!(first, key("https://other.example.com/chat/completions", &request));
+    }
+
+    #[test]
+    fn test_memory_cache() {
+        let mut cache = ResponseCache::new(CacheMode::Memory, None);
+        assert_eq!(cache.get("a"), None);
+        cache.put("a", reply("Here is a Bell state"));
+        assert_eq!(cache.get("a"), Some(reply("Here is a Bell state")));
+
+        let mut off = ResponseCache::new(CacheMode::Off, None);
+        off.put("a", reply("x"));
+        assert_eq!(off.get("a"), None);
+    }
+
+    #[test]
+    fn test_memory_evicts_least_recently_used() {
+        let mut cache = ResponseCache::new(CacheMode::Memory, None);
+        for i in 0..MAX_MEMORY_ENTRIES {
+            cache.put(&i.to_string(), reply("x"));
+        }
+        // Using the oldest makes the second oldest the one to go
+        assert!(cache.get("0").is_some());
+        cache.put("new", reply("y"));
+        assert!(cache.get("0").is_some());
+        assert!(cache.get("1").is_none());
+        assert_eq!(cache.memory.len(), MAX_MEMORY_ENTRIES);
+    }
+
+    #[test]
+    fn test_disk_cache_survives_restart_and_evicts() {
+        let dir = temp_dir();
+        let mut cache = ResponseCache::new(CacheMode::Disk, Some(dir.clone()));
+        cache.max_disk_bytes = 300;
+        let content = "q".repeat(60);
+        for key in ["a", "b", "c"] {
+            cache.put(key, reply(&content));
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        // A fresh run reads them back; reading "a" makes it the newest
+        let mut restarted = ResponseCache::new(CacheMode::Disk, Some(dir.clone()));
+        restarted.max_disk_bytes = 300;
+        assert_eq!(restarted.get("a"), Some(reply(&content)));
+        std::thread::sleep(Duration::from_millis(20));
+        restarted.put("d", reply(&content));
+        assert!(!dir.join("b.json").exists());
+        assert!(dir.join("a.json").exists() && dir.join("d.json").exists());
+
+        assert_eq!(restarted.clear().unwrap(), 3);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        let _ = fs::remove_dir_all(dir);
+    }
+}
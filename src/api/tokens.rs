@@ -0,0 +1,130 @@
+// Approximate token counts for chat context
+//
+// No tokenizer ships with qhub, so counts are estimated: ASCII text and code
+// average about four characters per token with BPE tokenizers, while other
+// scripts (CJK in particular) come close to one token per character. Each
+// message also pays a few tokens for its role and separators. The estimate
+// errs high so trimmed requests stay under the real limit.
+
+use super::deepseek::ChatMessage;
+
+const ASCII_CHARS_PER_TOKEN: usize = 4;
+const MESSAGE_OVERHEAD: usize = 4;
+
+/// Context window used for models not listed in `context_window`
+pub const DEFAULT_CONTEXT_WINDOW: usize = 8_192;
+
+pub fn estimate_text(text: &str) -> usize {
+    let ascii = text.chars().filter(|c| c.is_ascii()).count();
+    let other = text.chars().count() - ascii;
+    ascii.div_ceil(ASCII_CHARS_PER_TOKEN) + other
+}
+
+pub fn estimate_message(message: &ChatMessage) -> usize {
+    MESSAGE_OVERHEAD + estimate_text(&message.content)
+}
+
+pub fn estimate_messages(messages: &[ChatMessage]) -> usize {
+    messages.iter().map(estimate_message).sum()
+}
+
+/// Context window of a known model, in tokens
+pub fn context_window(model: &str) -> usize {
+    let model = model.to_lowercase();
+    if model.contains("deepseek") {
+        64_000
+    } else if model.contains("claude") {
+        200_000
+    } else if model.contains("gpt-4o") || model.contains("gpt-4-turbo") {
+        128_000
+    } else if model.contains("gpt-3.5") {
+        16_385
+    } else {
+        DEFAULT_CONTEXT_WINDOW
+    }
+}
+
+/// Drop the oldest messages until the history fits in `budget` tokens.
+/// A leading system prompt and the newest message are always kept.
+/// Returns how many messages were removed.
+pub fn trim_to_budget(history: &mut Vec<ChatMessage>, budget: usize) -> usize {
+    let first = usize::from(history.first().is_some_and(|m| m.role == "system"));
+    let mut total = estimate_messages(history);
+    // The newest message is never dropped; saturating so an empty history is a no-op
+    let newest = history.len().saturating_sub(1);
+    let mut end = first;
+    while total > budget && end < newest {
+        total -= estimate_message(&history[end]);
+        end += 1;
+    }
+    history.drain(first..end);
+    end - first
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: &str, content: &str) -> ChatMessage {
+        ChatMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_estimate_text() {
+        assert_eq!(estimate_text(""), 0);
+        assert_eq!(estimate_text("qc.h(0)"), 2);
+        assert_eq!(estimate_text("量子"), 2);
+        assert_eq!(estimate_message(&message("user", "abcd")), MESSAGE_OVERHEAD + 1);
+    }
+
+    #[test]
+    fn test_context_window() {
+        assert_eq!(context_window("deepseek/deepseek-chat"), 64_000);
+        assert_eq!(context_window("gpt-4o-mini"), 128_000);
+        assert_eq!(context_window("my-local-model"), DEFAULT_CONTEXT_WINDOW);
+    }
+
+    #[test]
+    fn test_trim_keeps_system_prompt_and_newest() {
+        let long = "x".repeat(400); // 100 tokens + overhead
+        let mut history = vec![
+            message("system", "You are QHub"),
+            message("user", &long),
+            message("assistant", &long),
+            message("user", "short"),
+        ];
+
+        // Fits: nothing is removed
+        assert_eq!(trim_to_budget(&mut history, 1_000), 0);
+        assert_eq!(history.len(), 4);
+
+        // Room for the system prompt, one long message and the newest
+        assert_eq!(trim_to_budget(&mut history, 120), 1);
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0].role, "system");
+        assert_eq!(history[1].role, "assistant");
+
+        // Even an impossible budget keeps the prompt and the question
+        assert_eq!(trim_to_budget(&mut history, 0), 1);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].content, "short");
+    }
+
+    #[test]
+    fn test_trim_short_histories() {
+        let mut history = Vec::new();
+        assert_eq!(trim_to_budget(&mut history, 0), 0);
+
+        let mut history = vec![message("system", "You are QHub")];
+        assert_eq!(trim_to_budget(&mut history, 0), 0);
+        assert_eq!(history.len(), 1);
+
+        // No system prompt: the newest message still survives
+        let mut history = vec![message("user", "first"), message("user", "second")];
+        assert_eq!(trim_to_budget(&mut history, 0), 1);
+        assert_eq!(history[0].content, "second");
+    }
+}
@@ -0,0 +1,261 @@
+// Append-only transcript of AI exchanges (ai.transcript = true)
+//
+// Each prompt/response pair is one JSON line in ~/.qhub/files/transcript.jsonl,
+// kept independently of chat history and sessions so a good answer survives
+// /clear. The file is archived as transcript-YYYY-MM.jsonl when a new month
+// starts. Writes happen on a blocking worker so a slow or full disk never stalls
+// the UI; the first failure is reported once and later ones stay quiet until a
+// write succeeds again.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+
+use crate::config::Config;
+
+const FILE_NAME: &str = "transcript.jsonl";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// RFC 3339, local time
+    pub timestamp: String,
+    pub model: String,
+    pub prompt: String,
+    pub response: String,
+}
+
+impl Entry {
+    pub fn new(model: &str, prompt: &str, response: &str) -> Self {
+        Self {
+            timestamp: Local::now().to_rfc3339(),
+            model: model.to_string(),
+            prompt: prompt.to_string(),
+            response: response.to_string(),
+        }
+    }
+}
+
+pub fn transcript_dir() -> Result<PathBuf> {
+    Config::files_dir()
+}
+
+/// Handle for queueing entries; the writer stops once it is dropped
+pub struct Transcript {
+    tx: mpsc::UnboundedSender<Entry>,
+    errors: mpsc::UnboundedReceiver<String>,
+}
+
+impl Transcript {
+    /// Start the writer for `dir`. Must be called inside the tokio runtime.
+    pub fn start(dir: PathBuf) -> Self {
+        let (tx, mut rx) = mpsc::unbounded_channel::<Entry>();
+        let (error_tx, errors) = mpsc::unbounded_channel();
+        tokio::task::spawn_blocking(move || {
+            let mut failing = false;
+            while let Some(entry) = rx.blocking_recv() {
+                match append(&dir, &entry, Local::now()) {
+                    Ok(()) => failing = false,
+                    Err(e) if !failing => {
+                        failing = true;
+                        let _ = error_tx.send(format!("{:#}", e));
+                    }
+                    Err(_) => {}
+                }
+            }
+        });
+        Self { tx, errors }
+    }
+
+    pub fn record(&self, entry: Entry) {
+        let _ = self.tx.send(entry);
+    }
+
+    /// A write failure not yet shown to the user
+    pub fn take_error(&mut self) -> Option<String> {
+        self.errors.try_recv().ok()
+    }
+}
+
+/// Append one entry, archiving last month's file first
+pub fn append(dir: &Path, entry: &Entry, now: DateTime<Local>) -> Result<()> {
+    fs::create_dir_all(dir).context("Failed to create files directory")?;
+    let path = dir.join(FILE_NAME);
+    rotate(dir, &path, now)?;
+
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    file.write_all(line.as_bytes())
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn rotate(dir: &Path, path: &Path, now: DateTime<Local>) -> Result<()> {
+    let Ok(modified) = fs::metadata(path).and_then(|m| m.modified()) else {
+        return Ok(());
+    };
+    let month = DateTime::<Local>::from(modified).format("%Y-%m").to_string();
+    if month == now.format("%Y-%m").to_string() {
+        return Ok(());
+    }
+    let mut archive = dir.join(format!("transcript-{}.jsonl", month));
+    let mut n = 1;
+    while archive.exists() {
+        archive = dir.join(format!("transcript-{}.{}.jsonl", month, n));
+        n += 1;
+    }
+    fs::rename(path, &archive).with_context(|| format!("Failed to archive {}", path.display()))
+}
+
+/// The current transcript and its archives, oldest first
+pub fn files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut archives: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| {
+                p.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with("transcript-") && n.ends_with(".jsonl"))
+            })
+            .collect(),
+        Err(_) => return Ok(Vec::new()),
+    };
+    // Names sort by month
+    archives.sort();
+    let current = dir.join(FILE_NAME);
+    if current.exists() {
+        archives.push(current);
+    }
+    Ok(archives)
+}
+
+/// A plain substring (case-insensitive) unless `regex` is set
+pub fn matcher(pattern: &str, regex: bool) -> Result<Regex> {
+    let pattern = if regex { pattern.to_string() } else { regex::escape(pattern) };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(true)
+        .build()
+        .with_context(|| format!("Invalid pattern: {}", pattern))
+}
+
+/// A matching line of an entry with the lines around it
+#[derive(Debug, PartialEq)]
+pub struct Hit<'a> {
+    /// "prompt" or "response"
+    pub field: &'static str,
+    /// (line number from 1, text, is the match)
+    pub lines: Vec<(usize, &'a str, bool)>,
+}
+
+pub fn search<'a>(entry: &'a Entry, matcher: &Regex, context: usize) -> Vec<Hit<'a>> {
+    let mut hits = Vec::new();
+    for (field, text) in [("prompt", &entry.prompt), ("response", &entry.response)] {
+        let lines: Vec<&str> = text.lines().collect();
+        let matched: Vec<usize> = (0..lines.len()).filter(|&i| matcher.is_match(lines[i])).collect();
+        let mut i = 0;
+        while i < matched.len() {
+            // Overlapping context windows are merged into one hit
+            let start = matched[i].saturating_sub(context);
+            let mut end = (matched[i] + context).min(lines.len() - 1);
+            while i + 1 < matched.len() && matched[i + 1].saturating_sub(context) <= end + 1 {
+                i += 1;
+                end = (matched[i] + context).min(lines.len() - 1);
+            }
+            hits.push(Hit {
+                field,
+                lines: (start..=end)
+                    .map(|n| (n + 1, lines[n], matched.contains(&n)))
+                    .collect(),
+            });
+            i += 1;
+        }
+    }
+    hits
+}
+
+/// `qhub transcript grep`
+pub fn execute_grep(pattern: &str, regex: bool, context: usize) -> Result<()> {
+    let matcher = matcher(pattern, regex)?;
+    let dir = transcript_dir()?;
+    let files = files(&dir)?;
+    if files.is_empty() {
+        println!("No transcript yet. Set `transcript = true` under [ai] in config.toml to start one.");
+        return Ok(());
+    }
+
+    let mut total = 0;
+    for path in files {
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        // A line cut short by a full disk is skipped, not fatal
+        for entry in content.lines().filter_map(|l| serde_json::from_str::<Entry>(l).ok()) {
+            for hit in search(&entry, &matcher, context) {
+                let when = DateTime::parse_from_rfc3339(&entry.timestamp)
+                    .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_else(|_| entry.timestamp.clone());
+                println!("{} {} ({})", when, hit.field, entry.model);
+                for (number, text, is_match) in hit.lines {
+                    let marker = if is_match { '>' } else { ' ' };
+                    println!("{} {:>4}  {}", marker, number, text);
+                }
+                println!();
+                total += 1;
+            }
+        }
+    }
+    println!("{} match{}", total, if total == 1 { "" } else { "es" });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Datelike, TimeZone};
+
+    #[test]
+    fn test_search_merges_context() {
+        let entry = Entry::new(
+            "deepseek-chat",
+            "Explain Grover",
+            "intro\nGrover's algorithm\nuses\namplitude\namplification\n\nqc.h(0)",
+        );
+        let hits = search(&entry, &matcher("grover", false).unwrap(), 1);
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].field, "prompt");
+        assert_eq!(hits[1].lines, vec![(1, "intro", false), (2, "Grover's algorithm", true), (3, "uses", false)]);
+
+        // Metacharacters are literal unless --regex is given
+        assert_eq!(search(&entry, &matcher("qc.h(0)", false).unwrap(), 0).len(), 1);
+        assert_eq!(search(&entry, &matcher("^amp", true).unwrap(), 1)[0].lines.len(), 4);
+        assert!(matcher("(", true).is_err());
+    }
+
+    #[test]
+    fn test_append_rotates_monthly() {
+        let dir = std::env::temp_dir().join(format!("qhub-transcript-{}", uuid::Uuid::new_v4()));
+        let entry = Entry::new("m", "p", "r");
+
+        append(&dir, &entry, Local::now()).unwrap();
+        append(&dir, &entry, Local::now()).unwrap();
+        assert_eq!(fs::read_to_string(dir.join(FILE_NAME)).unwrap().lines().count(), 2);
+
+        // The file was last written this month; a write next year archives it
+        let next_year = Local.with_ymd_and_hms(Local::now().year() + 1, 1, 15, 12, 0, 0).unwrap();
+        append(&dir, &entry, next_year).unwrap();
+        let files = files(&dir).unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(fs::read_to_string(&files[0]).unwrap().lines().count(), 2);
+        assert_eq!(files[1], dir.join(FILE_NAME));
+        assert_eq!(fs::read_to_string(&files[1]).unwrap().lines().count(), 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -2,6 +2,5 @@ pub mod credentials;
 pub mod oauth;
 pub mod service;
 
-pub use credentials::*;
-pub use oauth::*;
+#[allow(unused_imports)]
 pub use service::*;
@@ -8,13 +8,64 @@ use chrono::{Duration, Utc};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use sqlx::PgPool;
 use uuid::Uuid;
 
-use crate::db::{AuthResponse, CreateUserRequest, LoginRequest, User, UserSession};
+use crate::db::pool::with_pool;
+use crate::diagnostics;
+use crate::db::{AuthResponse, CreateUserRequest, DatabasePool, LoginRequest, UpdateProfileRequest, User, UserSession};
 
+#[cfg_attr(not(test), allow(dead_code))]
 const TOKEN_EXPIRY_HOURS: i64 = 24;
 
+/// Longest display name, in characters
+pub const MAX_DISPLAY_NAME: usize = 64;
+
+/// The display name with surrounding whitespace trimmed, or why it can't be used
+pub fn validate_display_name(name: &str) -> Result<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        anyhow::bail!("Display name cannot be empty");
+    }
+    if name.chars().count() > MAX_DISPLAY_NAME {
+        anyhow::bail!("Display name must be at most {} characters", MAX_DISPLAY_NAME);
+    }
+    if name.chars().any(char::is_control) {
+        anyhow::bail!("Display name cannot contain control characters");
+    }
+    Ok(name.to_string())
+}
+
+/// Signing secret used when JWT_SECRET is unset. It is in the source, so
+/// anyone can forge tokens signed with it.
+pub const DEFAULT_JWT_SECRET: &str = "development-secret-key-change-in-production";
+
+/// JWT_SECRET, unless it is unset, empty or the default
+pub fn configured_jwt_secret() -> Option<String> {
+    std::env::var("JWT_SECRET")
+        .ok()
+        .filter(|secret| !secret.is_empty() && secret != DEFAULT_JWT_SECRET)
+}
+
+/// QHUB_ENV=production, or a build with the `production` feature
+pub fn is_production() -> bool {
+    cfg!(feature = "production") || std::env::var("QHUB_ENV").is_ok_and(|env| env.eq_ignore_ascii_case("production"))
+}
+
+/// The secret to sign with. Outside production the default is allowed, with
+/// a warning; in production it is refused.
+fn signing_secret(configured: Option<String>, production: bool) -> Result<String> {
+    match configured {
+        Some(secret) => Ok(secret),
+        None if production => anyhow::bail!(
+            "JWT_SECRET must be set to a private value in production (generate one with `openssl rand -base64 32`)"
+        ),
+        None => {
+            diagnostics::warn("Using the default JWT secret. Set JWT_SECRET in production!");
+            Ok(DEFAULT_JWT_SECRET.to_string())
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,      // User ID
@@ -25,23 +76,18 @@ pub struct Claims {
 }
 
 pub struct AuthService {
-    pool: PgPool,
+    db: DatabasePool,
     jwt_secret: String,
 }
 
 impl AuthService {
-    pub fn new(pool: PgPool) -> Result<Self> {
-        let jwt_secret = std::env::var("JWT_SECRET")
-            .unwrap_or_else(|_| "development-secret-key-change-in-production".to_string());
-        
-        if jwt_secret == "development-secret-key-change-in-production" {
-            eprintln!("⚠️  WARNING: Using default JWT secret. Set JWT_SECRET in production!");
-        }
-
-        Ok(Self { pool, jwt_secret })
+    pub fn new(db: DatabasePool) -> Result<Self> {
+        let jwt_secret = signing_secret(configured_jwt_secret(), is_production())?;
+        Ok(Self { db, jwt_secret })
     }
 
     /// Hash a password using Argon2
+    #[cfg_attr(not(test), allow(dead_code))]
     pub fn hash_password(&self, password: &str) -> Result<String> {
         let salt = SaltString::generate(&mut OsRng);
         let argon2 = Argon2::default();
@@ -55,6 +101,7 @@ impl AuthService {
     }
 
     /// Verify a password against a hash
+    #[cfg_attr(not(test), allow(dead_code))]
     pub fn verify_password(&self, password: &str, hash: &str) -> Result<bool> {
         let parsed_hash = PasswordHash::new(hash)
             .map_err(|e| anyhow::anyhow!("Invalid password hash: {}", e))?;
@@ -65,6 +112,7 @@ impl AuthService {
     }
 
     /// Generate a JWT token
+    #[cfg_attr(not(test), allow(dead_code))]
     pub fn generate_token(&self, user: &User) -> Result<(String, i64)> {
         let expiry_hours = std::env::var("TOKEN_EXPIRY_HOURS")
             .ok()
@@ -111,7 +159,56 @@ impl AuthService {
         general_purpose::STANDARD.encode(hasher.finalize())
     }
 
+    /// Columns selected into `User`
+    const USER_COLUMNS: &'static str = "id, email, username, display_name, password_hash, \
+        tier, created_at, updated_at, last_login_at, is_active, email_verified";
+
+    async fn find_user(&self, column: &str, value: &str) -> Result<Option<User>> {
+        let sql = format!(
+            "SELECT {} FROM {} WHERE {} = $1",
+            Self::USER_COLUMNS,
+            self.db.table("users"),
+            column
+        );
+        let user = with_pool!(&self.db, pool => {
+            sqlx::query_as::<_, User>(&sql).bind(value).fetch_optional(pool).await?
+        });
+        Ok(user)
+    }
+
+    /// Issue a token for `user` and record its session
+    #[cfg_attr(not(test), allow(dead_code))]
+    async fn create_session(&self, user: User, now: i64) -> Result<AuthResponse> {
+        let (token, exp) = self.generate_token(&user)?;
+        let token_hash = self.hash_token(&token);
+
+        let sql = format!(
+            "INSERT INTO {} (id, user_id, token_hash, expires_at, created_at, last_active_at)
+             VALUES ($1, $2, $3, $4, $5, $6)",
+            self.db.table("user_sessions")
+        );
+        let session_id = Uuid::new_v4().to_string();
+        with_pool!(&self.db, pool => {
+            sqlx::query(&sql)
+                .bind(&session_id)
+                .bind(&user.id)
+                .bind(&token_hash)
+                .bind(exp)
+                .bind(now)
+                .bind(now)
+                .execute(pool)
+                .await?;
+        });
+
+        Ok(AuthResponse {
+            token,
+            user,
+            expires_at: exp,
+        })
+    }
+
     /// Register a new user
+    #[cfg_attr(not(test), allow(dead_code))]
     pub async fn register(&self, req: CreateUserRequest) -> Result<AuthResponse> {
         // Validate email format
         if !req.email.contains('@') {
@@ -119,14 +216,12 @@ impl AuthService {
         }
 
         // Check if user already exists
-        let existing = sqlx::query!("SELECT id FROM qhub.users WHERE email = $1", req.email)
-            .fetch_optional(&self.pool)
-            .await?;
-
-        if existing.is_some() {
+        if self.find_user("email", &req.email).await?.is_some() {
             anyhow::bail!("Email already registered");
         }
 
+        let display_name = req.display_name.as_deref().map(validate_display_name).transpose()?;
+
         // Hash password
         let password_hash = self.hash_password(&req.password)?;
 
@@ -134,81 +229,67 @@ impl AuthService {
         let user_id = Uuid::new_v4().to_string();
         let now = Utc::now().timestamp();
 
-        sqlx::query!(
-            r#"
-            INSERT INTO qhub.users (id, email, username, password_hash, tier, created_at, updated_at)
-            VALUES ($1, $2, $3, $4, $5, $6, $7)
-            "#,
-            user_id,
-            req.email,
-            req.username,
-            password_hash,
-            "free",
-            now,
-            now
-        )
-        .execute(&self.pool)
-        .await
-        .context("Failed to create user")?;
+        let sql = format!(
+            "INSERT INTO {} (id, email, username, display_name, password_hash, tier, created_at, updated_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+            self.db.table("users")
+        );
+        with_pool!(&self.db, pool => {
+            sqlx::query(&sql)
+                .bind(&user_id)
+                .bind(&req.email)
+                .bind(&req.username)
+                .bind(&display_name)
+                .bind(&password_hash)
+                .bind("free")
+                .bind(now)
+                .bind(now)
+                .execute(pool)
+                .await
+                .context("Failed to create user")?;
+        });
 
         // Fetch the created user
-        let user = sqlx::query_as!(
-            User,
-            r#"
-            SELECT id, email, username, display_name, password_hash,
-                   tier, created_at, updated_at, last_login_at,
-                   is_active as "is_active!", email_verified as "email_verified!"
-            FROM qhub.users WHERE id = $1
-            "#,
-            user_id
-        )
-        .fetch_one(&self.pool)
-        .await?;
-
-        // Generate token
-        let (token, exp) = self.generate_token(&user)?;
-        let token_hash = self.hash_token(&token);
+        let user = self
+            .find_user("id", &user_id)
+            .await?
+            .context("Created user not found")?;
 
-        // Create session
-        let session_id = Uuid::new_v4().to_string();
-        sqlx::query!(
-            r#"
-            INSERT INTO qhub.user_sessions (id, user_id, token_hash, expires_at, created_at, last_active_at)
-            VALUES ($1, $2, $3, $4, $5, $6)
-            "#,
-            session_id,
-            user.id,
-            token_hash,
-            exp,
-            now,
-            now
-        )
-        .execute(&self.pool)
-        .await?;
+        self.create_session(user, now).await
+    }
 
-        Ok(AuthResponse {
-            token,
-            user,
-            expires_at: exp,
-        })
+    /// Change the profile of an active user
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub async fn update_profile(&self, user_id: &str, req: UpdateProfileRequest) -> Result<User> {
+        let display_name = validate_display_name(&req.display_name)?;
+        let sql = format!(
+            "UPDATE {} SET display_name = $1, updated_at = $2 WHERE id = $3 AND is_active = true",
+            self.db.table("users")
+        );
+        let updated = with_pool!(&self.db, pool => {
+            sqlx::query(&sql)
+                .bind(&display_name)
+                .bind(Utc::now().timestamp())
+                .bind(user_id)
+                .execute(pool)
+                .await
+                .context("Failed to update profile")?
+                .rows_affected()
+        });
+        if updated == 0 {
+            anyhow::bail!("User not found");
+        }
+        self.find_user("id", user_id).await?.context("Updated user not found")
     }
 
     /// Login a user
+    #[cfg_attr(not(test), allow(dead_code))]
     pub async fn login(&self, req: LoginRequest) -> Result<AuthResponse> {
         // Fetch user
-        let user = sqlx::query_as!(
-            User,
-            r#"
-            SELECT id, email, username, display_name, password_hash,
-                   tier, created_at, updated_at, last_login_at,
-                   is_active as "is_active!", email_verified as "email_verified!"
-            FROM qhub.users WHERE email = $1
-            "#,
-            req.email
-        )
-        .fetch_optional(&self.pool)
-        .await?
-        .ok_or_else(|| anyhow::anyhow!("Invalid email or password"))?;
+        let user = self
+            .find_user("email", &req.email)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Invalid email or password"))?;
 
         // Check if user is active
         if !user.is_active {
@@ -225,36 +306,12 @@ impl AuthService {
 
         // Update last login
         let now = Utc::now().timestamp();
-        sqlx::query!("UPDATE qhub.users SET last_login_at = $1 WHERE id = $2", now, user.id)
-            .execute(&self.pool)
-            .await?;
-
-        // Generate token
-        let (token, exp) = self.generate_token(&user)?;
-        let token_hash = self.hash_token(&token);
+        let sql = format!("UPDATE {} SET last_login_at = $1 WHERE id = $2", self.db.table("users"));
+        with_pool!(&self.db, pool => {
+            sqlx::query(&sql).bind(now).bind(&user.id).execute(pool).await?;
+        });
 
-        // Create session
-        let session_id = Uuid::new_v4().to_string();
-        sqlx::query!(
-            r#"
-            INSERT INTO qhub.user_sessions (id, user_id, token_hash, expires_at, created_at, last_active_at)
-            VALUES ($1, $2, $3, $4, $5, $6)
-            "#,
-            session_id,
-            user.id,
-            token_hash,
-            exp,
-            now,
-            now
-        )
-        .execute(&self.pool)
-        .await?;
-
-        Ok(AuthResponse {
-            token,
-            user,
-            expires_at: exp,
-        })
+        self.create_session(user, now).await
     }
 
     /// Verify a session token
@@ -264,64 +321,160 @@ impl AuthService {
         let now = Utc::now().timestamp();
 
         // Check if session exists and is valid
-        let session = sqlx::query_as!(
-            UserSession,
-            r#"
-            SELECT id, user_id, token_hash, device_info, ip_address,
-                   expires_at, created_at, last_active_at
-            FROM qhub.user_sessions WHERE token_hash = $1 AND expires_at > $2
-            "#,
-            token_hash,
-            now
-        )
-        .fetch_optional(&self.pool)
-        .await?
+        let sql = format!(
+            "SELECT id, user_id, token_hash, device_info, ip_address,
+                    expires_at, created_at, last_active_at
+             FROM {} WHERE token_hash = $1 AND expires_at > $2",
+            self.db.table("user_sessions")
+        );
+        let session = with_pool!(&self.db, pool => {
+            sqlx::query_as::<_, UserSession>(&sql)
+                .bind(&token_hash)
+                .bind(now)
+                .fetch_optional(pool)
+                .await?
+        })
         .ok_or_else(|| anyhow::anyhow!("Invalid or expired session"))?;
 
         // Update last active
-        sqlx::query!(
-            "UPDATE qhub.user_sessions SET last_active_at = $1 WHERE id = $2",
-            now,
-            session.id
-        )
-        .execute(&self.pool)
-        .await?;
+        let sql = format!(
+            "UPDATE {} SET last_active_at = $1 WHERE id = $2",
+            self.db.table("user_sessions")
+        );
+        with_pool!(&self.db, pool => {
+            sqlx::query(&sql).bind(now).bind(&session.id).execute(pool).await?;
+        });
 
         // Fetch user
-        let user = sqlx::query_as!(
-            User,
-            r#"
-            SELECT id, email, username, display_name, password_hash,
-                   tier, created_at, updated_at, last_login_at,
-                   is_active as "is_active!", email_verified as "email_verified!"
-            FROM qhub.users WHERE id = $1
-            "#,
-            claims.sub
-        )
-        .fetch_one(&self.pool)
-        .await?;
+        let user = self
+            .find_user("id", &claims.sub)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Invalid or expired session"))?;
 
         Ok(user)
     }
 
     /// Logout (invalidate session)
+    #[cfg_attr(not(test), allow(dead_code))]
     pub async fn logout(&self, token: &str) -> Result<()> {
         let token_hash = self.hash_token(token);
-        
-        sqlx::query!("DELETE FROM qhub.user_sessions WHERE token_hash = $1", token_hash)
-            .execute(&self.pool)
-            .await?;
+
+        let sql = format!("DELETE FROM {} WHERE token_hash = $1", self.db.table("user_sessions"));
+        with_pool!(&self.db, pool => {
+            sqlx::query(&sql).bind(&token_hash).execute(pool).await?;
+        });
 
         Ok(())
     }
 
     /// Clean up expired sessions
+    #[allow(dead_code)]
     pub async fn cleanup_expired_sessions(&self) -> Result<u64> {
-        let now = Utc::now().timestamp();
-        let result = sqlx::query!("DELETE FROM qhub.user_sessions WHERE expires_at < $1", now)
-            .execute(&self.pool)
-            .await?;
+        self.db.purge_expired_sessions().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn register_request(email: &str) -> CreateUserRequest {
+        CreateUserRequest {
+            email: email.to_string(),
+            password: "correct horse".to_string(),
+            username: None,
+            display_name: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_display_name() {
+        assert_eq!(validate_display_name("  Ada Lovelace ").unwrap(), "Ada Lovelace");
+        assert_eq!(validate_display_name("量子 ✨").unwrap(), "量子 ✨");
+        assert!(validate_display_name("   ").is_err());
+        assert!(validate_display_name("Ada\u{1b}[31m").is_err());
+        assert!(validate_display_name("Ada\nLovelace").is_err());
+        assert!(validate_display_name(&"a".repeat(MAX_DISPLAY_NAME)).is_ok());
+        assert!(validate_display_name(&"a".repeat(MAX_DISPLAY_NAME + 1)).is_err());
+    }
+
+    #[test]
+    fn test_default_secret_refused_in_production() {
+        assert_eq!(signing_secret(Some("s3cret".into()), true).unwrap(), "s3cret");
+        assert_eq!(signing_secret(None, false).unwrap(), DEFAULT_JWT_SECRET);
+        assert!(signing_secret(None, true).unwrap_err().to_string().contains("JWT_SECRET must be set"));
+    }
+
+    /// register -> login -> verify_session -> logout against one backend
+    async fn auth_round_trip(db: DatabasePool) {
+        let auth = AuthService::new(db).unwrap();
+        let email = format!("{}@example.com", Uuid::new_v4());
+
+        let registered = auth.register(register_request(&email)).await.unwrap();
+        assert_eq!(registered.user.email, email);
+        assert_eq!(registered.user.tier, "free");
+        assert!(registered.user.is_active);
+        assert!(Uuid::parse_str(&registered.user.id).is_ok());
+        assert_eq!(registered.user.name(), email);
+        assert!(auth.register(register_request(&email)).await.is_err());
+
+        let named = CreateUserRequest {
+            display_name: Some(" Ada ".to_string()),
+            ..register_request(&format!("{}@example.com", Uuid::new_v4()))
+        };
+        assert_eq!(auth.register(named).await.unwrap().user.name(), "Ada");
+        let bad_name = CreateUserRequest {
+            display_name: Some("\u{7}".to_string()),
+            ..register_request(&format!("{}@example.com", Uuid::new_v4()))
+        };
+        assert!(auth.register(bad_name).await.is_err());
+
+        let update = |name: &str| UpdateProfileRequest { display_name: name.to_string() };
+        let user = auth.update_profile(&registered.user.id, update("Ada L.")).await.unwrap();
+        assert_eq!(user.display_name.as_deref(), Some("Ada L."));
+        assert!(user.updated_at >= registered.user.updated_at);
+        assert!(auth.update_profile(&registered.user.id, update("")).await.is_err());
+        assert!(auth.update_profile("missing", update("Eve")).await.is_err());
+
+        let bad_password = LoginRequest { email: email.clone(), password: "wrong".to_string() };
+        assert!(auth.login(bad_password).await.is_err());
+
+        let login = LoginRequest { email: email.clone(), password: "correct horse".to_string() };
+        let session = auth.login(login).await.unwrap();
+        assert!(session.expires_at > Utc::now().timestamp());
+
+        let user = auth.verify_session(&session.token).await.unwrap();
+        assert_eq!(user.id, registered.user.id);
+        assert!(user.last_login_at.is_some());
+        assert!(user.created_at > 0);
+
+        auth.logout(&session.token).await.unwrap();
+        assert!(auth.verify_session(&session.token).await.is_err());
+        // The registration session is independent of the logged-out one
+        assert!(auth.verify_session(&registered.token).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_auth_round_trip_sqlite() {
+        let dir = std::env::temp_dir().join(format!("qhub-auth-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = DatabasePool::from_url(&format!("sqlite://{}/qhub.db?mode=rwc", dir.display()), &Default::default())
+            .await
+            .unwrap();
+
+        auth_round_trip(db).await;
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Run with `cargo test -- --ignored` and QHUB_TEST_POSTGRES_URL
+    /// pointing at a disposable database
+    #[tokio::test]
+    #[ignore = "needs QHUB_TEST_POSTGRES_URL"]
+    async fn test_auth_round_trip_postgres() {
+        let url = std::env::var("QHUB_TEST_POSTGRES_URL").expect("QHUB_TEST_POSTGRES_URL is not set");
+        let db = DatabasePool::from_url(&url, &Default::default()).await.unwrap();
 
-        Ok(result.rows_affected())
+        auth_round_trip(db).await;
     }
 }
@@ -0,0 +1,251 @@
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+
+use super::args::{AdminCommand, AdminSessionsCommand, AdminUsersCommand};
+use crate::auth::AuthService;
+use crate::config::{Config, ConfigLocator};
+use crate::db::DatabasePool;
+use crate::tui::format;
+
+/// Actor recorded in the audit log when the admin token is used
+const ADMIN_TOKEN_ACTOR: &str = "admin-token";
+
+pub async fn execute_admin(
+    locator: &ConfigLocator,
+    command: AdminCommand,
+    admin_token: Option<String>,
+    json: bool,
+) -> Result<()> {
+    let config = Config::load(locator).unwrap_or_default();
+    let db = DatabasePool::new(&config.db).await?;
+    let actor = authorize(&db, &config, admin_token.as_deref()).await?;
+
+    match command {
+        AdminCommand::Users { command: AdminUsersCommand::List } => {
+            let users = db.list_users().await?;
+            db.record_audit(&actor, "users.list", None, None).await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&users)?);
+            } else {
+                let rows = users
+                    .iter()
+                    .map(|u| {
+                        vec![
+                            u.email.clone(),
+                            u.username.clone().unwrap_or_default(),
+                            u.tier.clone(),
+                            u.role.clone(),
+                            if u.is_active { "yes" } else { "no" }.to_string(),
+                            format_timestamp(Some(u.created_at)),
+                            format_age(u.last_login_at),
+                        ]
+                    })
+                    .collect::<Vec<_>>();
+                print_table(
+                    &["EMAIL", "USERNAME", "TIER", "ROLE", "ACTIVE", "CREATED", "LAST LOGIN"],
+                    &rows,
+                );
+            }
+        }
+        AdminCommand::Users { command: AdminUsersCommand::SetTier { email, tier } } => {
+            if !db.set_user_tier(&email, &tier).await? {
+                anyhow::bail!("No user with email {}", email);
+            }
+            db.record_audit(&actor, "users.set_tier", Some(&email), Some(&tier)).await?;
+            print_result(json, "users.set_tier", &email, &format!("tier set to {}", tier))?;
+        }
+        AdminCommand::Users { command: AdminUsersCommand::Deactivate { email, yes } } => {
+            require_confirmation(yes, &format!("deactivate {}", email))?;
+            if !db.deactivate_user(&email).await? {
+                anyhow::bail!("No user with email {}", email);
+            }
+            db.record_audit(&actor, "users.deactivate", Some(&email), None).await?;
+            print_result(json, "users.deactivate", &email, "deactivated, sessions revoked")?;
+        }
+        AdminCommand::Sessions { command: AdminSessionsCommand::PurgeExpired { yes } } => {
+            require_confirmation(yes, "purge expired sessions")?;
+            let purged = db.purge_expired_sessions().await?;
+            db.record_audit(&actor, "sessions.purge_expired", None, Some(&purged.to_string()))
+                .await?;
+            if json {
+                println!("{}", serde_json::json!({ "action": "sessions.purge_expired", "purged": purged }));
+            } else {
+                println!("Purged {} expired session(s)", purged);
+            }
+        }
+        AdminCommand::Stats => {
+            let stats = db.admin_stats().await?;
+            db.record_audit(&actor, "stats", None, None).await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&stats)?);
+            } else {
+                print_table(
+                    &["METRIC", "VALUE"],
+                    &[
+                        vec!["users (total)".to_string(), stats.users_total.to_string()],
+                        vec!["users (active)".to_string(), stats.users_active.to_string()],
+                    ],
+                );
+                println!();
+                let jobs = stats.jobs_by_status
+                    .iter()
+                    .map(|(status, count)| vec![status.clone(), count.to_string()])
+                    .collect::<Vec<_>>();
+                print_table(&["JOB STATUS", "COUNT"], &jobs);
+                println!();
+                let usage = stats.usage_totals
+                    .iter()
+                    .map(|(resource, total)| vec![resource.clone(), total.to_string()])
+                    .collect::<Vec<_>>();
+                print_table(&["RESOURCE", "TOTAL"], &usage);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolve the acting admin, refusing (and auditing the attempt) otherwise.
+/// The email in config.toml is only a claim: the active account's session
+/// token has to be valid, and the user it belongs to is the one checked.
+async fn authorize(db: &DatabasePool, config: &Config, admin_token: Option<&str>) -> Result<String> {
+    if let (Some(given), Ok(expected)) = (admin_token, std::env::var("QHUB_ADMIN_TOKEN")) {
+        if !expected.is_empty() && tokens_match(given, &expected) {
+            return Ok(ADMIN_TOKEN_ACTOR.to_string());
+        }
+    }
+
+    let account = config.user();
+    let mut actor = account.map(|u| u.email.clone());
+    if let Some(token) = account.and_then(|a| config.session_token(a)) {
+        if let Ok(user) = AuthService::new(db.clone())?.verify_session(&token).await {
+            if db.user_role(&user.email).await?.as_deref() == Some("admin") {
+                return Ok(user.email);
+            }
+            actor = Some(user.email);
+        }
+    }
+
+    let actor = actor.unwrap_or_else(|| "anonymous".to_string());
+    db.record_audit(&actor, "denied", None, None).await?;
+    anyhow::bail!(
+        "Admin access required. Log in as a user with the admin role, \
+         or pass --admin-token matching QHUB_ADMIN_TOKEN."
+    )
+}
+
+/// Compare the digests, so the time taken says nothing about how much of
+/// `given` was right
+fn tokens_match(given: &str, expected: &str) -> bool {
+    let (given, expected) = (Sha256::digest(given), Sha256::digest(expected));
+    given.iter().zip(expected.iter()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn require_confirmation(yes: bool, action: &str) -> Result<()> {
+    if !yes {
+        anyhow::bail!("Refusing to {} without --yes", action);
+    }
+    Ok(())
+}
+
+fn print_result(json: bool, action: &str, target: &str, message: &str) -> Result<()> {
+    if json {
+        println!(
+            "{}",
+            serde_json::json!({ "action": action, "target": target, "result": message })
+        );
+    } else {
+        println!("✓ {}: {}", target, message);
+    }
+    Ok(())
+}
+
+/// A stored time in the local timezone
+fn format_timestamp(ts: Option<i64>) -> String {
+    ts.and_then(format::local)
+        .map(|dt| dt.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+/// "2 hours ago", for times people compare with now
+fn format_age(ts: Option<i64>) -> String {
+    ts.and_then(format::local)
+        .map(|dt| format::ago(&dt, &chrono::Local::now()))
+        .unwrap_or_else(|| "never".to_string())
+}
+
+/// Print rows as a left-aligned table with a header
+fn print_table(headers: &[&str], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (i, cell) in row.iter().enumerate() {
+            widths[i] = widths[i].max(cell.chars().count());
+        }
+    }
+
+    let format_row = |cells: Vec<&str>| {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    println!("{}", format_row(headers.to_vec()));
+    for row in rows {
+        println!("{}", format_row(row.iter().map(String::as_str).collect()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::CreateUserRequest;
+
+    #[test]
+    fn test_tokens_match() {
+        assert!(tokens_match("s3cret-token", "s3cret-token"));
+        assert!(!tokens_match("s3cret-tokeN", "s3cret-token"));
+        assert!(!tokens_match("s3cret", "s3cret-token"));
+        assert!(!tokens_match("", "s3cret-token"));
+    }
+
+    #[tokio::test]
+    async fn test_admin_needs_a_valid_session() {
+        let path = std::env::temp_dir().join(format!("qhub-admin-cli-{}.db", uuid::Uuid::new_v4()));
+        let db = DatabasePool::from_url(&format!("sqlite://{}?mode=rwc", path.display()), &Default::default())
+            .await
+            .unwrap();
+        let auth = AuthService::new(db.clone()).unwrap();
+        let request = |email: &str| CreateUserRequest {
+            email: email.to_string(),
+            username: None,
+            password: "correct horse".to_string(),
+            display_name: None,
+        };
+        let admin = auth.register(request("root@example.com")).await.unwrap();
+        let user = auth.register(request("ada@example.com")).await.unwrap();
+        sqlx::query("UPDATE users SET role = 'admin' WHERE email = 'root@example.com'")
+            .execute(db.sqlite().unwrap())
+            .await
+            .unwrap();
+
+        let signed_in = |email: &str, token: &str| {
+            let mut config = Config::default();
+            config.add_account(email.to_string(), token.to_string(), "free".to_string()).unwrap();
+            config
+        };
+        // Writing an admin's email into config.toml is not enough
+        let forged = signed_in("root@example.com", "not-a-session");
+        assert!(authorize(&db, &forged, None).await.is_err());
+        let borrowed = signed_in("root@example.com", &user.token);
+        assert!(authorize(&db, &borrowed, None).await.is_err());
+
+        let config = signed_in("root@example.com", &admin.token);
+        assert_eq!(authorize(&db, &config, None).await.unwrap(), "root@example.com");
+        let _ = std::fs::remove_file(path);
+    }
+}
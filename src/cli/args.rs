@@ -1,4 +1,9 @@
 use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+use crate::config::settings::CountOrder;
+use crate::quantum::export::ExportFormat;
+use crate::quantum::sweep::ParamRange;
 
 #[derive(Parser, Debug)]
 #[command(name = "qhub")]
@@ -7,13 +12,273 @@ use clap::{Parser, Subcommand};
 pub struct Args {
     #[command(subcommand)]
     pub command: Option<Command>,
+
+    /// Skip the interactive first-run setup wizard
+    #[arg(long, global = true)]
+    pub skip_setup: bool,
+
+    /// Config file to use instead of ~/.qhub/config.toml (default: $QHUB_CONFIG)
+    #[arg(long, value_name = "PATH", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Continue the most recent saved session
+    #[arg(long, conflicts_with = "new")]
+    pub resume: bool,
+
+    /// Open a saved session by id, e.g. 20260314-093000
+    #[arg(long, value_name = "ID", conflicts_with_all = ["new", "resume"])]
+    pub session: Option<String>,
+
+    /// Start a fresh session even with ui.autoresume on
+    #[arg(long)]
+    pub new: bool,
+
+    /// Plain text output for screen readers, as with ui.accessible
+    #[arg(long)]
+    pub accessible: bool,
+
+    /// Start with a one-line ready notice instead of the welcome, as with
+    /// ui.show_welcome = false
+    #[arg(long)]
+    pub quiet: bool,
 }
 
 #[derive(Subcommand, Debug, Clone)]
 pub enum Command {
-    /// Run a .qqb file
+    /// Run an OpenQASM (.qasm) or Qiskit Python (.py) program
+    #[command(alias = "rr")]
     Run {
         /// Path to the quantum program
         file: String,
+        /// Use the local simulator, with no network access or login
+        #[arg(long)]
+        offline: bool,
+    },
+    /// Run a parameterized OpenQASM circuit over a grid of parameter values
+    Sweep {
+        /// OpenQASM program using bare names as gate parameters, e.g. `rz(theta) q[0];`
+        file: String,
+        /// NAME=START:STOP:POINTS; repeat for a grid over several parameters
+        #[arg(long = "param", required = true)]
+        params: Vec<ParamRange>,
+        /// Shots per point
+        #[arg(long, default_value_t = 1024)]
+        shots: usize,
+        /// Where the points run; only `simulator` is supported so far
+        #[arg(long, default_value = "simulator")]
+        backend: String,
+        /// Also write how often this outcome was measured, e.g. 00
+        #[arg(long)]
+        select: Option<String>,
+        /// CSV file for the results (default: <file>-sweep.csv)
+        #[arg(long)]
+        output: Option<String>,
+    },
+    /// Run every circuit in a directory and write a summary of the results
+    Batch {
+        /// Directory with .qasm (and .qqb) files; subdirectories are not searched
+        dir: String,
+        /// Where the circuits run; only `simulator` is supported so far
+        #[arg(long, default_value = "simulator")]
+        backend: String,
+        /// Shots per circuit
+        #[arg(long, default_value_t = 1024)]
+        shots: usize,
+        /// Circuits run at once (default: number of CPUs)
+        #[arg(long)]
+        concurrency: Option<usize>,
+        /// Summary file, JSON if it ends in .json and CSV otherwise (default: <dir>-batch.csv)
+        #[arg(long)]
+        output: Option<String>,
+    },
+    /// Predict QPU time, cost and queue wait before running on hardware
+    Estimate {
+        /// OpenQASM program
+        file: String,
+        /// IBM Quantum backend (default: default_backend under [quantum])
+        #[arg(long)]
+        backend: Option<String>,
+        #[arg(long, default_value_t = 4096)]
+        shots: usize,
+    },
+    /// Database maintenance for operators (requires the admin role)
+    Admin {
+        /// Admin token, accepted when it matches QHUB_ADMIN_TOKEN
+        #[arg(long)]
+        admin_token: Option<String>,
+        /// Print JSON instead of tables
+        #[arg(long, global = true)]
+        json: bool,
+        #[command(subcommand)]
+        command: AdminCommand,
+    },
+    /// Serve newline-delimited JSON-RPC for editor plugins (stdin/stdout by default)
+    Serve {
+        /// Speak JSON-RPC on stdin/stdout (the default)
+        #[arg(long)]
+        stdio: bool,
+        /// Listen on a Unix socket instead; connections share chat sessions
+        #[arg(long, value_name = "PATH", conflicts_with = "stdio")]
+        socket: Option<String>,
+    },
+    /// Database housekeeping
+    Db {
+        #[command(subcommand)]
+        command: DbCommand,
+    },
+    /// Inspect the effective configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// IBM Quantum account setup
+    Quantum {
+        #[command(subcommand)]
+        command: QuantumCommand,
+    },
+    /// Quantum job results (needs DATABASE_URL)
+    Jobs {
+        #[command(subcommand)]
+        command: JobsCommand,
+    },
+    /// Check this machine for exposed credentials and files
+    Security {
+        #[command(subcommand)]
+        command: SecurityCommand,
+    },
+    /// Search the AI transcript (enable with `transcript = true` under [ai])
+    Transcript {
+        #[command(subcommand)]
+        command: TranscriptCommand,
+    },
+    /// Update qhub to the latest release
+    Update {
+        /// Only report whether a newer release is available
+        #[arg(long)]
+        check: bool,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum TranscriptCommand {
+    /// Print prompts and responses containing a pattern
+    Grep {
+        /// Text to find (case-insensitive)
+        pattern: String,
+        /// Treat the pattern as a regular expression
+        #[arg(long)]
+        regex: bool,
+        /// Lines of context around each match
+        #[arg(short = 'C', long, default_value_t = 2)]
+        context: usize,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ConfigCommand {
+    /// Every setting with its value and the file (or variable) it came from
+    List,
+    /// Report every problem in the config files, exiting with 1 if there are any
+    Validate,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum SecurityCommand {
+    /// List risky states by severity, exiting with 1 if any high-severity ones remain
+    Audit {
+        /// Apply the fixes that are safe to make automatically
+        #[arg(long)]
+        fix: bool,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum QuantumCommand {
+    /// Use the IBM Quantum account saved by Qiskit (~/.qiskit/qiskit-ibm.json)
+    ImportQiskit {
+        /// Account file to read instead of Qiskit's default
+        #[arg(long)]
+        file: Option<String>,
+        /// Name of the saved account to import; asked for when there are several
+        #[arg(long)]
+        account: Option<String>,
+        /// Show what would be imported without checking or saving anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum JobsCommand {
+    /// Compare the measurement distributions of two jobs (or exported .json files)
+    Compare {
+        job_a: String,
+        job_b: String,
+        /// Print the metrics as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Write a job's measurement counts to a CSV or JSON file
+    Export {
+        id: String,
+        /// csv or json; by default from the --out extension
+        #[arg(long)]
+        format: Option<ExportFormat>,
+        /// lexicographic or count; quantum.export_order by default
+        #[arg(long)]
+        order: Option<CountOrder>,
+        #[arg(long, value_name = "PATH")]
+        out: String,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum DbCommand {
+    /// Run one maintenance pass (expired sessions, old audit rows, vacuum, cache cleanup)
+    Maintain,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum AdminCommand {
+    /// Manage user accounts
+    Users {
+        #[command(subcommand)]
+        command: AdminUsersCommand,
+    },
+    /// Manage login sessions
+    Sessions {
+        #[command(subcommand)]
+        command: AdminSessionsCommand,
+    },
+    /// Show user, job and usage totals
+    Stats,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum AdminUsersCommand {
+    /// List all users
+    List,
+    /// Change a user's subscription tier
+    SetTier {
+        email: String,
+        /// free, pro or enterprise
+        tier: String,
+    },
+    /// Deactivate a user and revoke their sessions
+    Deactivate {
+        email: String,
+        /// Confirm the destructive action
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum AdminSessionsCommand {
+    /// Delete all expired sessions
+    PurgeExpired {
+        /// Confirm the destructive action
+        #[arg(long)]
+        yes: bool,
     },
 }
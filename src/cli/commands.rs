@@ -1,9 +1,719 @@
-pub use super::args::Command;
+pub use super::args::{Command, ConfigCommand, DbCommand, JobsCommand, QuantumCommand, SecurityCommand, TranscriptCommand};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::api::ibm_quantum::IbmQuantumClient;
+use crate::config::audit::{self, Scope, Severity};
+use crate::config::qiskit::{self, QiskitAccount};
+use crate::config::secrets::ApiKeyKind;
+use crate::config::tiers::{self, TierLimits};
+use crate::config::settings::CountOrder;
+use crate::config::{Config, ConfigLocator};
+use crate::db::jobs::NewJob;
+use crate::db::{maintenance, DatabasePool};
+use crate::quantum::estimate::{self, Estimate};
+use crate::quantum::batch::{self, Entry, Status};
+use crate::quantum::select::{self, Recommendation, Requirements};
+use crate::quantum::sweep::{self, ParamRange};
+use crate::quantum::program::{self, Kind};
+use crate::quantum::export::{self, ExportFormat};
+use crate::quantum::{compare, qasm, simulator};
+
+use super::interrupt::Interrupt;
+use super::run::{RunOutcome, RunStatus};
+
+/// Run `file` and return what happened; main.rs prints the report. Only the
+/// hardware preflight (backend choice and cost estimate) talks to the user,
+/// and Ctrl+C during it ends the run as `Interrupted`. Once submission is
+/// implemented, the job id has to be printed before waiting on the job, so
+/// an interrupted run can still be cancelled with it.
+pub async fn execute_run(locator: &ConfigLocator, file: &str, offline: bool) -> Result<RunOutcome> {
+    let mut outcome = RunOutcome::new(file, simulator::DEFAULT_SHOTS);
+
+    // --offline never reads the config or credentials, so it works right after install
+    let simulate = offline
+        || Config::load(locator).is_ok_and(|config| config.quantum.provider == "simulator");
+
+    // OpenQASM and Qiskit Python are parsed up front so problems surface
+    // before execution
+    let source = std::fs::read_to_string(file)
+        .with_context(|| format!("Failed to read {}", file))?;
+    let started = Instant::now();
+    let (kind, circuit) = program::parse(file, &source)?;
+    outcome.timings.parse = started.elapsed();
+    outcome.circuit = Some(match kind {
+        Kind::Python => format!("Qiskit program as {}", circuit.summary()),
+        _ => circuit.summary(),
+    });
+
+    if simulate {
+        let outcome = run_locally(outcome, &circuit)?;
+        return Ok(if offline { outcome } else { store_run(locator, source, outcome).await });
+    }
+
+    let config = Config::load(locator)?;
+    let limits = tier_limits(locator);
+    let (interrupt, watcher) = Interrupt::watch("Cancelling the run…");
+    let outcome = preflight(outcome, &config, &circuit, &limits, &interrupt).await;
+    watcher.abort();
+    Ok(store_run(locator, source, outcome?).await)
+}
+
+/// Keep the counts of a completed run as a job, so that `/compare` and
+/// `qhub jobs compare` take its id. Skipped when not logged in or without
+/// a database; a failure to store only warns, the run still counts.
+async fn store_run(locator: &ConfigLocator, source: String, mut outcome: RunOutcome) -> RunOutcome {
+    if outcome.status != RunStatus::Completed {
+        return outcome;
+    }
+    let Ok((db, email)) = job_store(locator).await else {
+        return outcome;
+    };
+    let name = Path::new(&outcome.file)
+        .file_name()
+        .map_or_else(|| outcome.file.clone(), |n| n.to_string_lossy().into_owned());
+    let backend = outcome.backend.clone().unwrap_or_else(|| "simulator".to_string());
+    let job = NewJob { name, circuit_code: source };
+    match db.record_run(&email, &backend, job, &outcome.counts).await {
+        Ok(id) => outcome.job_id = Some(id),
+        Err(e) => eprintln!("Could not store the run: {:#}", e),
+    }
+    outcome
+}
+
+/// Choose a backend and confirm the estimate, stopping early on Ctrl+C
+async fn preflight(
+    mut outcome: RunOutcome,
+    config: &Config,
+    circuit: &qasm::Circuit,
+    limits: &TierLimits,
+    interrupt: &Interrupt,
+) -> Result<RunOutcome> {
+    let backend = match &config.quantum.default_backend {
+        Some(backend) => {
+            limits.check_hardware(backend)?;
+            backend.clone()
+        }
+        // No backend configured: pick one for this circuit
+        None => {
+            let Some(recommendation) = interrupt.run(recommend_backend(config, circuit, limits)).await else {
+                outcome.status = RunStatus::Interrupted;
+                return Ok(outcome);
+            };
+            let recommendation = recommendation?;
+            println!("{}", recommendation.report());
+            match recommendation.backend {
+                Some(backend) => backend,
+                None => return run_locally(outcome, circuit),
+            }
+        }
+    };
+    outcome.backend = Some(backend.clone());
+
+    // Hardware shots cost queue time and money: show what to expect first
+    if config.quantum.confirm_hardware {
+        let Some(estimate) = interrupt.run(hardware_estimate(config, circuit, &backend, outcome.shots)).await else {
+            outcome.status = RunStatus::Interrupted;
+            return Ok(outcome);
+        };
+        let summary = match estimate {
+            Ok(estimate) => {
+                print_assumptions(&estimate);
+                estimate.summary()
+            }
+            Err(e) => format!("No estimate for {} ({})", backend, e),
+        };
+        if !confirm(&format!("{} — proceed? (y/n) ", summary))? {
+            outcome.status = RunStatus::Cancelled;
+            return Ok(outcome);
+        }
+    }
 
-pub async fn execute_run(file: &str) -> Result<()> {
-    println!("Running quantum program: {}", file);
     // TODO: Implement quantum program execution
+    Ok(outcome)
+}
+
+/// `qhub config list`
+pub fn execute_config_list(locator: &ConfigLocator) -> Result<()> {
+    let config = Config::load(locator)?;
+    println!("Config file: {}", config.path()?.display());
+    if let Some(project) = &config.layers.project {
+        println!("Project file: {}", project.display());
+    }
+    let values = config.effective_values()?;
+    let width = values.iter().map(|(key, value, _)| key.len() + value.len() + 3).max().unwrap_or(0).min(48);
+    for (key, value, source) in values {
+        println!("{:<width$}  # {}", format!("{} = {}", key, value), source, width = width);
+    }
+    Ok(())
+}
+
+/// `qhub config validate`: every problem with the config files, one per
+/// line, for checking edits before starting qhub
+pub fn execute_config_validate(locator: &ConfigLocator) -> Result<()> {
+    let problems = Config::check(locator)?;
+    if problems.is_empty() {
+        println!("{} is valid.", locator.path()?.display());
+        return Ok(());
+    }
+    for problem in &problems {
+        println!("{}", problem);
+    }
+    std::process::exit(1);
+}
+
+/// `qhub security audit`: every finding with its remedy, applying the
+/// automatic fixes with `fix`
+pub fn execute_security_audit(locator: &ConfigLocator, fix: bool) -> Result<()> {
+    let mut config = Config::load(locator)?;
+    let scope = Scope::current()?;
+    let findings = audit::audit(&config, &scope);
+    if findings.is_empty() {
+        println!("No problems found.");
+        return Ok(());
+    }
+
+    let mut remaining_high = 0;
+    for finding in &findings {
+        println!("{:<7} {}", finding.severity, finding.problem);
+        let fixed = match (&finding.fix, fix) {
+            (Some(action), true) => match audit::apply(action, &mut config, &scope) {
+                Ok(done) => {
+                    println!("        fixed: {}", done);
+                    true
+                }
+                Err(e) => {
+                    println!("        could not fix: {:#}", e);
+                    false
+                }
+            },
+            (Some(_), false) => {
+                println!("        fix: {} (--fix)", finding.remedy);
+                false
+            }
+            (None, _) => {
+                println!("        to do: {}", finding.remedy);
+                false
+            }
+        };
+        if finding.severity == Severity::High && !fixed {
+            remaining_high += 1;
+        }
+    }
+
+    let fixable = findings.iter().filter(|f| f.fix.is_some()).count();
+    println!();
+    println!(
+        "{} finding(s), {} high severity.{}",
+        findings.len(),
+        findings.iter().filter(|f| f.severity == Severity::High).count(),
+        if !fix && fixable > 0 { format!(" --fix applies {} of the fixes.", fixable) } else { String::new() }
+    );
+    if remaining_high > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// `qhub quantum import-qiskit`: take over the IBM Quantum account Qiskit
+/// saved, after checking the token still works. The token is never printed.
+pub async fn execute_import_qiskit(locator: &ConfigLocator, file: Option<&str>, account: Option<&str>, dry_run: bool) -> Result<()> {
+    let path = match file {
+        Some(file) => std::path::PathBuf::from(file),
+        None => qiskit::default_path().context("no home directory to find ~/.qiskit in")?,
+    };
+    let accounts = qiskit::load(&path)?;
+    if accounts.is_empty() {
+        anyhow::bail!("no Qiskit accounts with a token in {}", path.display());
+    }
+    let chosen = match (account, qiskit::select(&accounts, account)) {
+        (_, Ok(chosen)) => chosen,
+        (Some(_), Err(e)) => anyhow::bail!(e),
+        (None, Err(_)) => choose_account(&accounts)?,
+    };
+
+    let mut config = Config::load(locator)?;
+    let destination = if config.security.use_keychain { "the keychain" } else { "config.toml" };
+    println!("Account: {}", chosen.describe());
+    if dry_run {
+        println!("Would check the token with IBM Quantum and save it to {}.", destination);
+        println!("Would set provider = \"ibm\", channel = \"{}\"{} under [quantum].",
+            chosen.channel,
+            chosen.instance.as_deref().map(|i| format!(", instance = \"{}\"", i)).unwrap_or_default()
+        );
+        println!("Dry run: nothing was changed.");
+        return Ok(());
+    }
+
+    println!("Checking the token with IBM Quantum…");
+    let client = IbmQuantumClient::new(chosen.token.clone(), &config.network);
+    let backends = client.backends().await.context("IBM Quantum did not accept the token")?;
+
+    config.quantum.provider = "ibm".to_string();
+    config.quantum.channel = Some(chosen.channel.clone());
+    config.quantum.instance = chosen.instance.clone();
+    let source = config.set_api_key(ApiKeyKind::Quantum, chosen.token.clone())?;
+    println!("✓ Imported {} into the {}", chosen.name, source.label());
+
+    if backends.is_empty() {
+        println!("The account has no backends available.");
+    } else {
+        println!("Backends available to this account:");
+        for backend in &backends {
+            let qubits = backend.qubits.map(|q| format!("{} qubits", q)).unwrap_or_else(|| "simulator".to_string());
+            let queue = backend.pending_jobs.map(|n| format!(", {} queued", n)).unwrap_or_default();
+            let state = if backend.operational { "" } else { ", offline" };
+            println!("  {:<20} {}{}{}", backend.name, qubits, queue, state);
+        }
+    }
+    Ok(())
+}
+
+/// Ask which of several saved accounts to import; Enter takes the default
+fn choose_account(accounts: &[QiskitAccount]) -> Result<&QiskitAccount> {
+    println!("Qiskit has several saved accounts:");
+    for (i, account) in accounts.iter().enumerate() {
+        let default = if account.is_default { " [default]" } else { "" };
+        println!("  {}. {}{}", i + 1, account.describe(), default);
+    }
+    print!("Import which one? (1-{}) ", accounts.len());
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    let answer = answer.trim();
+    if answer.is_empty() {
+        return qiskit::select(accounts, None).map_err(anyhow::Error::msg);
+    }
+    match answer.parse::<usize>() {
+        Ok(n) if (1..=accounts.len()).contains(&n) => Ok(&accounts[n - 1]),
+        _ => qiskit::select(accounts, Some(answer)).map_err(anyhow::Error::msg),
+    }
+}
+
+/// `qhub estimate`: the preflight numbers `qhub run` asks about
+pub async fn execute_estimate(locator: &ConfigLocator, file: &str, backend: Option<&str>, shots: usize) -> Result<()> {
+    let source = std::fs::read_to_string(file).with_context(|| format!("Failed to read {}", file))?;
+    let circuit = qasm::parse(&source)?;
+    let config = Config::load(locator)?;
+    let Some(backend) = backend.or(config.quantum.default_backend.as_deref()) else {
+        anyhow::bail!("no backend given: pass --backend or set default_backend under [quantum]");
+    };
+    if backend == "simulator" {
+        println!("The local simulator is free and has no queue; nothing to estimate.");
+        return Ok(());
+    }
+
+    let estimate = hardware_estimate(&config, &circuit, backend, shots).await?;
+    println!("{}", estimate.report());
+    Ok(())
+}
+
+fn run_locally(mut outcome: RunOutcome, circuit: &qasm::Circuit) -> Result<RunOutcome> {
+    let started = Instant::now();
+    outcome.counts = simulator::run(circuit, outcome.shots, &mut rand::thread_rng())?;
+    outcome.timings.execution = started.elapsed();
+    outcome.backend = Some("simulator".to_string());
+    outcome.status = RunStatus::Completed;
+    Ok(outcome)
+}
+
+/// The backend `select_backend` picks for `circuit`. Tiers without hardware
+/// get the local simulator without asking IBM Quantum.
+async fn recommend_backend(config: &Config, circuit: &qasm::Circuit, limits: &TierLimits) -> Result<Recommendation> {
+    let requirements = Requirements::of(circuit);
+    if !limits.hardware {
+        return select::select_backend(&requirements, &[], &HashMap::new(), limits).map_err(anyhow::Error::msg);
+    }
+    let Some(key) = config.get_quantum_api_key() else {
+        anyhow::bail!(
+            "no backend to run on: set quantum.default_backend, or an IBM Quantum token (/keys set quantum) so one can be chosen"
+        );
+    };
+    IbmQuantumClient::new(key, &config.network).recommend(&requirements, limits, None).await
+}
+
+async fn hardware_estimate(config: &Config, circuit: &qasm::Circuit, backend: &str, shots: usize) -> Result<Estimate> {
+    let Some(key) = config.get_quantum_api_key() else {
+        anyhow::bail!("estimates read backend timings from IBM Quantum; set a token with /keys set quantum");
+    };
+    let profile = IbmQuantumClient::new(key, &config.network).backend_profile(backend).await?;
+    Ok(estimate::estimate(circuit, &profile, shots, config.quantum.pricing.get(backend)))
+}
+
+fn print_assumptions(estimate: &Estimate) {
+    println!("Assumptions:");
+    for assumption in &estimate.assumptions {
+        println!("  - {}", assumption);
+    }
+}
+
+/// Limits of the logged-in account's tier; Free when logged out
+fn tier_limits(locator: &ConfigLocator) -> TierLimits {
+    Config::load(locator)
+        .ok()
+        .and_then(|config| config.user().map(|user| tiers::limits(&user.tier)))
+        .unwrap_or(tiers::FREE)
+}
+
+/// Ask a yes/no question on the terminal; anything but y/yes is a no
+fn confirm(question: &str) -> Result<bool> {
+    print!("{}", question);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// `qhub sweep`: simulate every point of the grid, a few at a time. Points
+/// are stored as jobs when a database and account are configured; Ctrl+C
+/// skips the points not yet started, keeps the finished ones and exits 130.
+pub async fn execute_sweep(
+    locator: &ConfigLocator,
+    file: &str,
+    ranges: &[ParamRange],
+    shots: usize,
+    backend: &str,
+    select: Option<&str>,
+    output: Option<&str>,
+) -> Result<()> {
+    let limits = tier_limits(locator);
+    if backend != "simulator" {
+        limits.check_hardware(backend)?;
+        anyhow::bail!(
+            "sweeps on {} need job submission to IBM Quantum, which qhub doesn't support yet; use --backend simulator",
+            backend
+        );
+    }
+    limits.check_shots(shots)?;
+    let source = std::fs::read_to_string(file).with_context(|| format!("Failed to read {}", file))?;
+    let circuit = qasm::parse(&source)?;
+    let points = sweep::plan(&circuit, ranges).map_err(anyhow::Error::msg)?;
+    // Catch unsupported gates before anything is stored
+    simulator::run(&sweep::bind(&circuit, &points[0]), 1, &mut rand::thread_rng())?;
+
+    let sweep_id = uuid::Uuid::new_v4().to_string();
+    let (db, job_ids) = match store_sweep(locator, &sweep_id, &source, &points).await {
+        Ok((db, ids)) => {
+            println!("Sweep {}: {} points, stored as jobs (/sweep status {})", sweep_id, points.len(), sweep_id);
+            (Some(db), ids)
+        }
+        Err(e) => {
+            println!("Sweep of {} points (not stored: {})", points.len(), e);
+            (None, Vec::new())
+        }
+    };
+
+    let (interrupt, watcher) = Interrupt::watch("Cancelling: waiting for running points to finish…");
+
+    let slots = Arc::new(Semaphore::new(std::thread::available_parallelism().map_or(4, |n| n.get())));
+    let mut running = JoinSet::new();
+    let mut results = vec![None; points.len()];
+    for (index, point) in points.iter().enumerate() {
+        let Some(slot) = interrupt.run(slots.clone().acquire_owned()).await else {
+            break;
+        };
+        let slot = slot?;
+        let bound = sweep::bind(&circuit, point);
+        running.spawn_blocking(move || {
+            let _slot = slot;
+            (index, simulator::run(&bound, shots, &mut rand::thread_rng()))
+        });
+    }
+    while let Some(joined) = running.join_next().await {
+        let (index, counts) = joined?;
+        if let (Some(db), Some(id)) = (&db, job_ids.get(index)) {
+            let stored = match &counts {
+                Ok(counts) => db.finish_job(id, Ok(&sweep::point_result(&points[index], counts))).await,
+                Err(e) => db.finish_job(id, Err(&e.to_string())).await,
+            };
+            if let Err(e) = stored {
+                eprintln!("Could not store point {}: {}", index + 1, e);
+            }
+        }
+        match counts {
+            Ok(counts) => results[index] = Some(counts),
+            Err(e) => eprintln!("Point {} ({}) failed: {}", index + 1, sweep::label(&points[index]), e),
+        }
+    }
+    let cancelled = interrupt.is_cancelled();
+    if let (true, Some(db)) = (cancelled, &db) {
+        db.cancel_pending_sweep_jobs(&sweep_id).await?;
+    }
+    let finished: Vec<(sweep::Point, BTreeMap<String, usize>)> = points
+        .into_iter()
+        .zip(results)
+        .filter_map(|(point, counts)| Some((point, counts?)))
+        .collect();
+    let output = output.map(str::to_string).unwrap_or_else(|| {
+        let stem = Path::new(file).file_stem().and_then(|s| s.to_str()).unwrap_or("circuit");
+        format!("{}-sweep.csv", stem)
+    });
+    interrupt.write_file(&output, sweep::csv(&finished, select))?;
+
+    let metric: Vec<f64> = finished.iter().map(|(_, counts)| sweep::expectation(counts)).collect();
+    println!(
+        "{} {} points, {} shots each → {}",
+        if cancelled { "Cancelled after" } else { "✓ Finished" },
+        finished.len(),
+        shots,
+        output
+    );
+    if !metric.is_empty() {
+        println!("⟨Z⟩ {}", sweep::sparkline(&metric));
+    }
+    watcher.abort();
+    interrupt.exit_if_cancelled();
+    Ok(())
+}
+
+/// Pending jobs for every point, under the logged-in account
+async fn store_sweep(
+    locator: &ConfigLocator,
+    sweep_id: &str,
+    source: &str,
+    points: &[sweep::Point],
+) -> Result<(DatabasePool, Vec<String>)> {
+    let config = Config::load(locator)?;
+    let Some(user) = config.user() else {
+        anyhow::bail!("not logged in");
+    };
+    let db = DatabasePool::new(&config.db).await?;
+    let jobs: Vec<NewJob> = points
+        .iter()
+        .enumerate()
+        .map(|(i, point)| NewJob {
+            // Zero-padded so sorting by name keeps point order
+            name: format!("point {:04}: {}", i, sweep::label(point)),
+            circuit_code: source.to_string(),
+        })
+        .collect();
+    let ids = db.create_sweep_jobs(sweep_id, &user.email, "simulator", &jobs).await?;
+    Ok((db, ids))
+}
+
+/// `qhub batch`: simulate every circuit in `dir`, `concurrency` at a time,
+/// and write a summary. Each run is stored as a job when a database and
+/// account are configured; Ctrl+C skips the circuits not yet started and
+/// exits 130 once the summary is written.
+pub async fn execute_batch(
+    locator: &ConfigLocator,
+    dir: &str,
+    backend: &str,
+    shots: usize,
+    concurrency: Option<usize>,
+    output: Option<&str>,
+) -> Result<()> {
+    let limits = tier_limits(locator);
+    if backend != "simulator" {
+        limits.check_hardware(backend)?;
+        anyhow::bail!(
+            "batches on {} need job submission to IBM Quantum, which qhub doesn't support yet; use --backend simulator",
+            backend
+        );
+    }
+    limits.check_shots(shots)?;
+    let files = batch::discover(Path::new(dir))?;
+    if files.is_empty() {
+        anyhow::bail!("no .qasm or .qqb files in {}", dir);
+    }
+    let concurrency = concurrency
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(4, |n| n.get()))
+        .max(1);
+    let store = match job_store(locator).await {
+        Ok(store) => {
+            println!("Batch of {} circuits, stored as jobs", files.len());
+            Some(store)
+        }
+        Err(e) => {
+            println!("Batch of {} circuits (not stored: {})", files.len(), e);
+            None
+        }
+    };
+
+    let (interrupt, watcher) = Interrupt::watch("Cancelling: waiting for running circuits to finish…");
+
+    let names: Vec<String> = files
+        .iter()
+        .map(|path| path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned()))
+        .collect();
+    // Circuits never started stay cancelled
+    let mut entries: Vec<Entry> = names.iter().map(|name| Entry::new(name, Status::Cancelled)).collect();
+    let total = files.len();
+    let mut done = 0;
+    let mut report = |index: usize, entry: Entry| {
+        done += 1;
+        println!("[{}/{}] {}", done, total, entry.progress());
+        entries[index] = entry;
+    };
+
+    let slots = Arc::new(Semaphore::new(concurrency));
+    let mut running = JoinSet::new();
+    for (index, path) in files.iter().enumerate() {
+        let name = &names[index];
+        if path.extension().is_some_and(|e| e == "qqb") {
+            let error = Some("the .qqb format is not supported yet".to_string());
+            report(index, Entry { error, ..Entry::new(name, Status::Skipped) });
+            continue;
+        }
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => {
+                report(index, Entry::failed(name, e));
+                continue;
+            }
+        };
+        let circuit = match qasm::parse(&source) {
+            Ok(circuit) => circuit,
+            Err(e) => {
+                report(index, Entry::failed(name, e));
+                continue;
+            }
+        };
+        let Some(slot) = interrupt.run(slots.clone().acquire_owned()).await else {
+            break;
+        };
+        let slot = slot?;
+        let job_id = match &store {
+            Some((db, email)) => {
+                let job = NewJob { name: name.clone(), circuit_code: source };
+                match db.create_jobs(None, email, backend, std::slice::from_ref(&job)).await {
+                    Ok(ids) => ids.into_iter().next(),
+                    Err(e) => {
+                        eprintln!("Could not store {}: {}", name, e);
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+        running.spawn_blocking(move || {
+            let _slot = slot;
+            (index, job_id, simulator::run(&circuit, shots, &mut rand::thread_rng()))
+        });
+        // Report whatever finished while this one was being started
+        while let Some(joined) = running.try_join_next() {
+            let (index, job_id, counts) = joined?;
+            report(index, finish_batch_job(&names[index], job_id, counts, shots, store.as_ref()).await);
+        }
+    }
+    while let Some(joined) = running.join_next().await {
+        let (index, job_id, counts) = joined?;
+        report(index, finish_batch_job(&names[index], job_id, counts, shots, store.as_ref()).await);
+    }
+
+    let output = output.map(str::to_string).unwrap_or_else(|| {
+        let stem = Path::new(dir).file_name().and_then(|s| s.to_str()).unwrap_or("circuits");
+        format!("{}-batch.csv", stem)
+    });
+    let summary = if output.ends_with(".json") { batch::json(&entries)? } else { batch::csv(&entries) };
+    interrupt.write_file(&output, summary)?;
+    println!("{} → {}", batch::totals(&entries), output);
+    watcher.abort();
+    interrupt.exit_if_cancelled();
+    Ok(())
+}
+
+/// Store the outcome of one batch circuit and build its summary entry
+async fn finish_batch_job(
+    name: &str,
+    job_id: Option<String>,
+    counts: Result<BTreeMap<String, usize>, simulator::SimulatorError>,
+    shots: usize,
+    store: Option<&(DatabasePool, String)>,
+) -> Entry {
+    if let (Some((db, _)), Some(id)) = (store, &job_id) {
+        let stored = match &counts {
+            Ok(counts) => db.finish_job(id, Ok(&serde_json::json!({ "counts": counts }))).await,
+            Err(e) => db.finish_job(id, Err(&e.to_string())).await,
+        };
+        if let Err(e) = stored {
+            eprintln!("Could not store the result of {}: {}", name, e);
+        }
+    }
+    let mut entry = match counts {
+        Ok(counts) => Entry::completed(name, shots, &counts),
+        Err(e) => Entry::failed(name, e),
+    };
+    entry.job_id = job_id;
+    entry
+}
+
+/// The database and the logged-in account jobs are stored under
+async fn job_store(locator: &ConfigLocator) -> Result<(DatabasePool, String)> {
+    let config = Config::load(locator)?;
+    let Some(email) = config.user().map(|u| u.email.clone()) else {
+        anyhow::bail!("not logged in");
+    };
+    let db = DatabasePool::new(&config.db).await?;
+    Ok((db, email))
+}
+
+/// `qhub jobs compare`: distance and per-outcome deltas between two results,
+/// each a job id or a file from `qhub jobs export`
+pub async fn execute_compare(locator: &ConfigLocator, job_a: &str, job_b: &str, json: bool) -> Result<()> {
+    let (a, b) = if export::is_counts_file(job_a) && export::is_counts_file(job_b) {
+        (export::read_counts(job_a)?, export::read_counts(job_b)?)
+    } else {
+        let config = Config::load(locator)?;
+        let db = DatabasePool::new(&config.db).await?;
+        // Scoped to the logged-in user when there is one
+        let email = config.user().map(|u| u.email.as_str());
+        (db.counts_for(job_a, email).await?, db.counts_for(job_b, email).await?)
+    };
+    let comparison = compare::compare(&a, &b);
+
+    if json {
+        let report = serde_json::json!({
+            "job_a": job_a,
+            "job_b": job_b,
+            "comparison": comparison,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!("{}", compare::render(&comparison, job_a, job_b));
+    }
+    Ok(())
+}
+
+/// `qhub jobs export`: a job's counts as CSV or JSON
+pub async fn execute_export(
+    locator: &ConfigLocator,
+    id: &str,
+    format: Option<ExportFormat>,
+    order: Option<CountOrder>,
+    out: &str,
+) -> Result<()> {
+    let config = Config::load(locator)?;
+    let (db, email) = job_store(locator).await?;
+    let (counts, source) = db.job_export(id, &email).await?;
+    let options = export::Options {
+        format: format.unwrap_or_else(|| ExportFormat::for_path(out)),
+        order: order.unwrap_or(config.quantum.export_order),
+        separator: config.quantum.register_separator.clone(),
+    };
+    std::fs::write(out, export::render(&counts, &source, &options)?).with_context(|| format!("Failed to write {}", out))?;
+    println!("✓ {} outcomes, {} shots → {}", counts.len(), counts.values().sum::<u64>(), out);
+    Ok(())
+}
+
+/// One maintenance pass, intended for cron
+pub async fn execute_maintain(locator: &ConfigLocator) -> Result<()> {
+    let config = Config::load(locator)?;
+    let db = DatabasePool::new(&config.db).await?;
+
+    let report = db
+        .run_maintenance(&config.db.maintenance, &maintenance::results_dir()?)
+        .await?;
+    let summary = report.summary();
+    maintenance::log(&summary);
+    println!("✓ Maintenance complete: {}", summary);
+
     Ok(())
 }
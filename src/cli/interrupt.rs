@@ -0,0 +1,154 @@
+// Ctrl+C in CLI commands
+//
+// Without a handler Ctrl+C kills the process wherever it is, which can leave
+// an output file half written. Commands that wait on the network or run for
+// a while install an `Interrupt` instead: the first Ctrl+C cancels - waits
+// end early through `run`, loops stop starting new work - and the command
+// winds down, keeping what finished, then exits with 130 like a shell would.
+// A second Ctrl+C exits at once, removing any output file still being
+// written. Output goes through `write_file`, which writes beside the target
+// and renames, so a file is either complete or absent.
+
+use anyhow::{Context, Result};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+/// Exit status after Ctrl+C, as for a process killed by SIGINT
+pub const EXIT_CODE: i32 = 130;
+
+#[derive(Debug, Clone)]
+pub struct Interrupt {
+    cancelled: Arc<watch::Sender<bool>>,
+    /// Output files being written, removed on a forced exit
+    partial: Arc<Mutex<Vec<PathBuf>>>,
+}
+
+impl Default for Interrupt {
+    fn default() -> Self {
+        Self { cancelled: Arc::new(watch::channel(false).0), partial: Arc::default() }
+    }
+}
+
+impl Interrupt {
+    /// Handle Ctrl+C until the returned task is aborted, printing `message`
+    /// on the first
+    pub fn watch(message: &'static str) -> (Self, JoinHandle<()>) {
+        let interrupt = Self::default();
+        let watcher = tokio::spawn({
+            let interrupt = interrupt.clone();
+            async move {
+                if tokio::signal::ctrl_c().await.is_err() {
+                    return;
+                }
+                interrupt.cancel();
+                eprintln!("\n{} (Ctrl+C again to quit now)", message);
+                if tokio::signal::ctrl_c().await.is_ok() {
+                    interrupt.remove_partial_files();
+                    eprintln!("Interrupted.");
+                    std::process::exit(EXIT_CODE);
+                }
+            }
+        });
+        (interrupt, watcher)
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.send_replace(true);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.cancelled.borrow()
+    }
+
+    /// Wait for `future`, or None if cancelled first
+    pub async fn run<T>(&self, future: impl Future<Output = T>) -> Option<T> {
+        let mut cancelled = self.cancelled.subscribe();
+        tokio::select! {
+            output = future => Some(output),
+            _ = cancelled.wait_for(|c| *c) => None,
+        }
+    }
+
+    /// Write `contents` to `path` so that an interruption never leaves part
+    /// of it behind
+    pub fn write_file(&self, path: &str, contents: impl AsRef<[u8]>) -> Result<()> {
+        let part = part_path(Path::new(path));
+        self.partial.lock().unwrap().push(part.clone());
+        let written = std::fs::write(&part, contents)
+            .and_then(|_| std::fs::rename(&part, path))
+            .with_context(|| format!("Failed to write {}", path));
+        if written.is_err() {
+            let _ = std::fs::remove_file(&part);
+        }
+        self.partial.lock().unwrap().retain(|p| p != &part);
+        written
+    }
+
+    /// Exit with 130 if Ctrl+C was pressed; for the end of a command that
+    /// kept what finished
+    pub fn exit_if_cancelled(&self) {
+        if self.is_cancelled() {
+            std::process::exit(EXIT_CODE);
+        }
+    }
+
+    fn remove_partial_files(&self) {
+        for path in self.partial.lock().unwrap().drain(..) {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Where `path` is written before it is renamed into place
+fn part_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_cancel_ends_the_wait() {
+        let interrupt = Interrupt::default();
+        assert_eq!(interrupt.run(async { 7 }).await, Some(7));
+
+        let waiting = tokio::spawn({
+            let interrupt = interrupt.clone();
+            async move { interrupt.run(tokio::time::sleep(Duration::from_secs(60))).await }
+        });
+        interrupt.cancel();
+        assert_eq!(tokio::time::timeout(Duration::from_secs(5), waiting).await.unwrap().unwrap(), None);
+        // Later waits end at once
+        assert!(interrupt.is_cancelled());
+        assert_eq!(interrupt.run(std::future::pending::<()>()).await, None);
+    }
+
+    #[test]
+    fn test_files_are_written_whole_or_not_at_all() {
+        let dir = std::env::temp_dir().join(format!("qhub-interrupt-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bell-sweep.csv");
+        let interrupt = Interrupt::default();
+        interrupt.write_file(path.to_str().unwrap(), "theta,p00\n").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "theta,p00\n");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        // A forced exit mid-write removes the part file
+        let part = part_path(&path);
+        std::fs::write(&part, "theta").unwrap();
+        interrupt.partial.lock().unwrap().push(part.clone());
+        interrupt.remove_partial_files();
+        assert!(!part.exists());
+
+        let missing = dir.join("missing").join("out.csv");
+        assert!(interrupt.write_file(missing.to_str().unwrap(), "x").is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
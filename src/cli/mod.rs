@@ -1,5 +1,10 @@
+pub mod admin;
 pub mod commands;
 pub mod args;
+pub mod interrupt;
+pub mod run;
+pub mod serve;
+pub mod update;
 
 pub use args::Args;
-pub use commands::Command;
+pub use commands::{Command, ConfigCommand, DbCommand, JobsCommand, QuantumCommand, SecurityCommand, TranscriptCommand};
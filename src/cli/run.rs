@@ -0,0 +1,173 @@
+// What `qhub run` produced
+//
+// `execute_run` returns a RunOutcome rather than printing, so a run can be
+// checked in tests and reused by other commands; main.rs prints `report()`,
+// which is the text `qhub run` has always shown. Counts stay empty unless
+// the program actually ran.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::time::Duration;
+
+/// Outcomes listed after a simulation; the rest are summed up
+const MAX_OUTCOMES: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunStatus {
+    /// The program ran and `counts` holds its results
+    Completed,
+    /// The hardware estimate was declined
+    Cancelled,
+    /// Ctrl+C before the program was submitted
+    Interrupted,
+    /// Checked, but not run: qhub cannot submit to hardware yet
+    NotSubmitted,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Timings {
+    pub parse: Duration,
+    pub execution: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunOutcome {
+    pub file: String,
+    /// The job the counts were stored as, when logged in with a database
+    pub job_id: Option<String>,
+    /// "simulator" or a hardware backend; None until one is chosen
+    pub backend: Option<String>,
+    pub shots: usize,
+    pub counts: BTreeMap<String, usize>,
+    /// The parsed circuit's summary
+    pub circuit: Option<String>,
+    pub timings: Timings,
+    pub status: RunStatus,
+}
+
+impl RunOutcome {
+    pub fn new(file: &str, shots: usize) -> Self {
+        Self {
+            file: file.to_string(),
+            job_id: None,
+            backend: None,
+            shots,
+            counts: BTreeMap::new(),
+            circuit: None,
+            timings: Timings::default(),
+            status: RunStatus::NotSubmitted,
+        }
+    }
+
+    /// The text `qhub run` prints
+    pub fn report(&self) -> String {
+        let mut report = format!("Running quantum program: {}\n", self.file);
+        if let Some(circuit) = &self.circuit {
+            let _ = writeln!(report, "Parsed {} ({})", circuit, millis(self.timings.parse));
+        }
+        match self.status {
+            RunStatus::Completed => {
+                let backend = match self.backend.as_deref() {
+                    None | Some("simulator") => "Local simulator".to_string(),
+                    Some(backend) => backend.to_string(),
+                };
+                let _ = writeln!(report, "{}, {} shots ({}):", backend, self.shots, millis(self.timings.execution));
+                report.push_str(&format_counts(&self.counts, self.shots));
+            }
+            RunStatus::Cancelled => report.push_str("Cancelled.\n"),
+            RunStatus::Interrupted => report.push_str("Interrupted.\n"),
+            RunStatus::NotSubmitted => {}
+        }
+        if let Some(id) = &self.job_id {
+            let _ = writeln!(report, "Stored as job {}; `qhub jobs compare` and /compare take its id", id);
+        }
+        report
+    }
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.1} ms", duration.as_secs_f64() * 1000.0)
+}
+
+/// Most frequent outcomes first, with a bar scaled to the shot count
+fn format_counts(counts: &BTreeMap<String, usize>, shots: usize) -> String {
+    let mut sorted: Vec<(&String, &usize)> = counts.iter().collect();
+    sorted.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    let mut text = String::new();
+    for (bits, count) in sorted.iter().take(MAX_OUTCOMES) {
+        let share = **count as f64 / shots.max(1) as f64;
+        let _ = writeln!(
+            text,
+            "  {}  {:>6}  {:5.1}%  {}",
+            bits,
+            count,
+            share * 100.0,
+            "█".repeat((share * 40.0).round() as usize)
+        );
+    }
+    if sorted.len() > MAX_OUTCOMES {
+        let rest: usize = sorted[MAX_OUTCOMES..].iter().map(|(_, c)| **c).sum();
+        let _ = writeln!(text, "  … {} more outcomes ({} shots)", sorted.len() - MAX_OUTCOMES, rest);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::commands::execute_run;
+    use crate::config::ConfigLocator;
+
+    #[test]
+    fn test_report() {
+        let mut outcome = RunOutcome::new("bell.qasm", 100);
+        assert_eq!(outcome.report(), "Running quantum program: bell.qasm\n");
+
+        outcome.circuit = Some("OpenQASM 2.0 circuit".to_string());
+        outcome.status = RunStatus::Cancelled;
+        assert!(outcome.report().ends_with("Parsed OpenQASM 2.0 circuit (0.0 ms)\nCancelled.\n"));
+
+        outcome.status = RunStatus::Completed;
+        outcome.backend = Some("simulator".to_string());
+        outcome.counts = BTreeMap::from([("00".to_string(), 25), ("11".to_string(), 75)]);
+        let report = outcome.report();
+        assert!(report.contains("Local simulator, 100 shots (0.0 ms):\n  11      75   75.0%  "), "{}", report);
+        // Most frequent first
+        assert!(report.find("  11 ").unwrap() < report.find("  00 ").unwrap());
+    }
+
+    #[test]
+    fn test_many_outcomes_are_summed_up() {
+        let counts: BTreeMap<String, usize> = (0..20).map(|i| (format!("{:05b}", i), 1)).collect();
+        let text = format_counts(&counts, 20);
+        assert_eq!(text.lines().count(), MAX_OUTCOMES + 1);
+        assert!(text.ends_with("  … 4 more outcomes (4 shots)\n"));
+    }
+
+    #[tokio::test]
+    async fn test_offline_run() {
+        let path = std::env::temp_dir().join(format!("qhub-run-{}.qasm", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "OPENQASM 2.0;\nqreg q[2];\ncreg c[2];\nh q[0];\ncx q[0],q[1];\nmeasure q -> c;\n").unwrap();
+        let outcome = execute_run(&ConfigLocator::default(), path.to_str().unwrap(), true).await.unwrap();
+        assert_eq!(outcome.status, RunStatus::Completed);
+        assert_eq!(outcome.backend.as_deref(), Some("simulator"));
+        assert_eq!(outcome.counts.values().sum::<usize>(), outcome.shots);
+        assert!(outcome.counts.keys().all(|bits| bits == "00" || bits == "11"), "{:?}", outcome.counts);
+        assert!(outcome.circuit.is_some_and(|c| c.contains("2 qubit(s)")));
+
+        let _ = std::fs::remove_file(path);
+
+        let path = std::env::temp_dir().join(format!("qhub-run-{}.py", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "from qiskit import QuantumCircuit\nqc = QuantumCircuit(1, 1)\nqc.x(0)\nqc.measure(0, 0)\n").unwrap();
+        let outcome = execute_run(&ConfigLocator::default(), path.to_str().unwrap(), true).await.unwrap();
+        assert_eq!(outcome.counts, BTreeMap::from([("1".to_string(), outcome.shots)]));
+        assert!(outcome.circuit.is_some_and(|c| c.starts_with("Qiskit program as OpenQASM 2.0 circuit")));
+        let _ = std::fs::remove_file(path);
+
+        let path = std::env::temp_dir().join(format!("qhub-run-{}.qqb", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "").unwrap();
+        let qqb = execute_run(&ConfigLocator::default(), path.to_str().unwrap(), true).await.unwrap_err();
+        assert_eq!(qqb.to_string(), "the .qqb format is not supported yet");
+        let _ = std::fs::remove_file(path);
+    }
+}
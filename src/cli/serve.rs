@@ -0,0 +1,909 @@
+// Newline-delimited JSON-RPC for editor integration
+//
+// One request per line in, one response per line out - see EDITOR_PROTOCOL.md.
+// Requests run concurrently and answer as they finish, so clients match
+// responses by id; chat turns of one session still run one at a time. The
+// same server speaks on stdin/stdout or, with `--socket`, on a Unix socket
+// whose connections share its sessions. Nothing else may be written to stdout
+// in stdio mode; diagnostics go to stderr.
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Component, Path};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+
+use crate::api::deepseek::{self, ChatMessage, DeepSeekClient, NoReply, RateLimited};
+use crate::api::ibm_quantum::IbmQuantumClient;
+use crate::api::tokens;
+use crate::api::quantum_error::QuantumError;
+use crate::api::ApiError;
+use crate::config::tiers::{self, Restricted, TierLimits};
+use crate::config::{Config, ConfigLocator};
+use crate::db::manager::{DbError, PoolManager};
+use crate::diagnostics;
+use crate::quantum::qasm::{self, QasmError};
+use crate::quantum::simulator::{self, SimulatorError};
+
+// Standard JSON-RPC 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+// Server errors, one per case a client can act on differently
+const SERVER_ERROR: i64 = -32000;
+const AUTH_REQUIRED: i64 = -32001;
+const RATE_LIMITED: i64 = -32002;
+const NOT_FOUND: i64 = -32003;
+const BACKEND_UNAVAILABLE: i64 = -32004;
+const CIRCUIT_REJECTED: i64 = -32005;
+const TIER_RESTRICTED: i64 = -32006;
+const DB_UNAVAILABLE: i64 = -32007;
+
+/// Chat session used when a request names none
+const DEFAULT_SESSION: &str = "default";
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatParams {
+    #[serde(alias = "message")]
+    prompt: String,
+    #[serde(default)]
+    session_id: Option<String>,
+    /// Start a fresh conversation before sending
+    #[serde(default)]
+    reset: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct SaveParams {
+    name: String,
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RunParams {
+    file: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RunCircuitParams {
+    qasm: String,
+    /// `simulator` or an IBM backend; default_backend when left out
+    #[serde(default)]
+    backend: Option<String>,
+    #[serde(default)]
+    shots: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JobStatusParams {
+    id: String,
+}
+
+#[derive(Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+    data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self { code, message: message.into(), data: None }
+    }
+
+    /// Tag the error with a machine-readable `kind` and any extra fields
+    fn kind(mut self, kind: &str, extra: Value) -> Self {
+        let mut data = json!({ "kind": kind });
+        if let (Some(data), Value::Object(extra)) = (data.as_object_mut(), extra) {
+            data.extend(extra);
+        }
+        self.data = Some(data);
+        self
+    }
+
+    /// Map an internal error to the code a client can act on. Errors are
+    /// matched anywhere in the context chain; the message keeps all of it.
+    fn from_error(error: &anyhow::Error) -> Self {
+        let message = format!("{:#}", error);
+        for cause in error.chain() {
+            if let Some(limited) = cause.downcast_ref::<RateLimited>() {
+                return RpcError::new(RATE_LIMITED, message).kind(
+                    "ai_rate_limited",
+                    json!({
+                        "retry_after_secs": limited.retry_after.map(|d| d.as_secs()),
+                        "remaining": limited.remaining,
+                    }),
+                );
+            }
+            if cause.downcast_ref::<NoReply>().is_some() {
+                return RpcError::new(SERVER_ERROR, message).kind("ai_no_reply", json!({}));
+            }
+            if let Some(error) = cause.downcast_ref::<QuantumError>() {
+                return Self::from_quantum(error, message);
+            }
+            if let Some(error) = cause.downcast_ref::<ApiError>() {
+                let code = match error {
+                    ApiError::Unauthorized(_) => AUTH_REQUIRED,
+                    ApiError::NotFound(_) => NOT_FOUND,
+                    ApiError::Validation(_) => INVALID_PARAMS,
+                    ApiError::RateLimit => RATE_LIMITED,
+                    _ => SERVER_ERROR,
+                };
+                return RpcError::new(code, message).kind("api", json!({}));
+            }
+            if cause.downcast_ref::<QasmError>().is_some() {
+                return RpcError::new(INVALID_PARAMS, message).kind("qasm", json!({}));
+            }
+            if cause.downcast_ref::<SimulatorError>().is_some() {
+                return RpcError::new(CIRCUIT_REJECTED, message).kind("simulator", json!({}));
+            }
+            if cause.downcast_ref::<Restricted>().is_some() {
+                return RpcError::new(TIER_RESTRICTED, message).kind("tier", json!({}));
+            }
+            if cause.downcast_ref::<DbError>().is_some() {
+                return RpcError::new(DB_UNAVAILABLE, message).kind("db_reconnecting", json!({}));
+            }
+        }
+        RpcError::new(SERVER_ERROR, message)
+    }
+
+    fn from_quantum(error: &QuantumError, message: String) -> Self {
+        match error {
+            QuantumError::AuthInvalid => RpcError::new(AUTH_REQUIRED, message).kind("quantum_auth", json!({})),
+            QuantumError::BackendOffline { backend, until } => RpcError::new(BACKEND_UNAVAILABLE, message)
+                .kind("backend_offline", json!({ "backend": backend, "until": until })),
+            QuantumError::QueueLimitReached => RpcError::new(RATE_LIMITED, message).kind("queue_limit", json!({})),
+            QuantumError::CircuitTooLarge { qubits, max } => RpcError::new(CIRCUIT_REJECTED, message)
+                .kind("circuit_too_large", json!({ "qubits": qubits, "max": max })),
+            QuantumError::TranspileFailed(_) => {
+                RpcError::new(CIRCUIT_REJECTED, message).kind("transpile_failed", json!({}))
+            }
+            QuantumError::ProviderInternal(_) => {
+                RpcError::new(SERVER_ERROR, message).kind("quantum_provider", json!({}))
+            }
+        }
+    }
+}
+
+impl From<anyhow::Error> for RpcError {
+    fn from(error: anyhow::Error) -> Self {
+        Self::from_error(&error)
+    }
+}
+
+type History = Arc<tokio::sync::Mutex<Vec<ChatMessage>>>;
+
+/// State shared by every request and, with `--socket`, every connection
+pub struct Server {
+    config: Config,
+    ai_client: DeepSeekClient,
+    /// Conversations by session id
+    sessions: Mutex<HashMap<String, History>>,
+    /// DATABASE_URL, for job_status
+    db: Option<PoolManager>,
+}
+
+impl Server {
+    pub fn new(config: Config, db: Option<PoolManager>) -> Self {
+        let ai_client = DeepSeekClient::from_config(&config);
+        Self {
+            config,
+            ai_client,
+            sessions: Mutex::new(HashMap::new()),
+            db,
+        }
+    }
+
+    /// Handle one raw input line. Returns None for blank lines.
+    pub async fn handle_line(&self, line: &str) -> Option<Value> {
+        let line = line.trim();
+        if line.is_empty() {
+            return None;
+        }
+
+        let raw: Value = match serde_json::from_str(line) {
+            Ok(value) => value,
+            Err(e) => {
+                return Some(error_response(Value::Null, RpcError::new(PARSE_ERROR, format!("Parse error: {}", e))))
+            }
+        };
+        let id = raw.get("id").cloned().unwrap_or(Value::Null);
+        let request: Request = match serde_json::from_value(raw) {
+            Ok(request) => request,
+            Err(e) => {
+                return Some(error_response(id, RpcError::new(INVALID_REQUEST, format!("Invalid request: {}", e))))
+            }
+        };
+
+        let id = request.id.clone();
+        Some(match self.dispatch(request).await {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(e) => error_response(id, e),
+        })
+    }
+
+    async fn dispatch(&self, request: Request) -> Result<Value, RpcError> {
+        match request.method.as_str() {
+            "chat" => self.chat(parse_params(request.params)?).await,
+            "run_circuit" => self.run_circuit(parse_params(request.params)?).await,
+            "list_backends" => self.list_backends().await,
+            "job_status" => self.job_status(parse_params(request.params)?).await,
+            "auth.status" | "status" => Ok(self.status()),
+            "save" => self.save(parse_params(request.params)?),
+            "run" => self.run(parse_params(request.params)?).await,
+            other => Err(RpcError::new(METHOD_NOT_FOUND, format!("Unknown method '{}'", other))),
+        }
+    }
+
+    fn session(&self, id: &str) -> History {
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions
+            .entry(id.to_string())
+            .or_insert_with(|| Arc::new(tokio::sync::Mutex::new(Vec::new())))
+            .clone()
+    }
+
+    async fn chat(&self, params: ChatParams) -> Result<Value, RpcError> {
+        // Only the hosted gateway is limited by tier, as in the TUI
+        if self.config.ai.base_url == deepseek::DEFAULT_BASE_URL {
+            self.limits().check_model(&self.config.ai.model).map_err(anyhow::Error::from)?;
+        }
+        let session_id = params.session_id.unwrap_or_else(|| DEFAULT_SESSION.to_string());
+        let history = self.session(&session_id);
+        // Held across the request: a session's turns must not interleave
+        let mut history = history.lock().await;
+        deepseek::ensure_system_prompt(&mut history, &DeepSeekClient::get_system_prompt(&self.config.ai));
+        if params.reset {
+            history.truncate(1);
+        }
+        history.push(ChatMessage {
+            role: "user".to_string(),
+            content: params.prompt,
+        });
+        // Drop the oldest turns once the session outgrows the model's context
+        tokens::trim_to_budget(&mut history, self.context_budget());
+
+        match self.ai_client.chat_with_usage(history.clone()).await {
+            Ok(reply) => {
+                history.push(ChatMessage {
+                    role: "assistant".to_string(),
+                    content: reply.content.clone(),
+                });
+                Ok(json!({
+                    "content": reply.content,
+                    "session_id": session_id,
+                    "truncated": reply.truncated,
+                    "total_tokens": reply.usage.map(|u| u.total_tokens),
+                }))
+            }
+            Err(e) => {
+                // Keep history consistent so the request can simply be retried
+                history.pop();
+                Err(RpcError::from(e.context("AI service error")))
+            }
+        }
+    }
+
+    /// Tokens of history sent with each chat, leaving room for the reply
+    fn context_budget(&self) -> usize {
+        let ai = &self.config.ai;
+        let window = ai.context_window.unwrap_or_else(|| tokens::context_window(&ai.model));
+        window.saturating_sub(ai.max_tokens as usize)
+    }
+
+    fn limits(&self) -> TierLimits {
+        self.config.user().map_or(tiers::FREE, |user| tiers::limits(&user.tier))
+    }
+
+    async fn run_circuit(&self, params: RunCircuitParams) -> Result<Value, RpcError> {
+        let circuit = qasm::parse(&params.qasm).map_err(anyhow::Error::from)?;
+        let shots = params.shots.unwrap_or(simulator::DEFAULT_SHOTS);
+        let limits = self.limits();
+        limits.check_shots(shots).map_err(anyhow::Error::from)?;
+
+        let backend = params.backend.or_else(|| match self.config.quantum.provider.as_str() {
+            "simulator" => None,
+            _ => self.config.quantum.default_backend.clone(),
+        });
+        match backend.as_deref() {
+            None | Some("simulator") => {
+                let counts = tokio::task::spawn_blocking(move || {
+                    simulator::run(&circuit, shots, &mut rand::thread_rng())
+                })
+                .await
+                .map_err(|e| RpcError::new(SERVER_ERROR, format!("Simulator stopped: {}", e)))?
+                .map_err(anyhow::Error::from)?;
+                Ok(json!({ "backend": "simulator", "shots": shots, "counts": counts }))
+            }
+            Some(backend) => {
+                limits.check_hardware(backend).map_err(anyhow::Error::from)?;
+                // Mirrors `qhub run`, which has no hardware execution yet
+                Err(RpcError::new(SERVER_ERROR, "Hardware execution is not available yet; use backend \"simulator\"")
+                    .kind("not_implemented", json!({ "backend": backend })))
+            }
+        }
+    }
+
+    async fn list_backends(&self) -> Result<Value, RpcError> {
+        let Some(key) = self.config.get_quantum_api_key() else {
+            return Err(RpcError::new(AUTH_REQUIRED, "No IBM Quantum token set; run `qhub quantum login`")
+                .kind("no_quantum_key", json!({})));
+        };
+        let backends = IbmQuantumClient::new(key, &self.config.network).backends().await?;
+        Ok(Value::Array(
+            backends
+                .into_iter()
+                .map(|b| {
+                    json!({
+                        "name": b.name,
+                        "qubits": b.qubits,
+                        "pending_jobs": b.pending_jobs,
+                        "operational": b.operational,
+                    })
+                })
+                .collect(),
+        ))
+    }
+
+    async fn job_status(&self, params: JobStatusParams) -> Result<Value, RpcError> {
+        let Some(db) = self.db.clone() else {
+            return Err(RpcError::new(DB_UNAVAILABLE, "job_status reads jobs from the database: set DATABASE_URL")
+                .kind("no_database", json!({})));
+        };
+        let Some(email) = self.config.user().map(|u| u.email.clone()) else {
+            return Err(RpcError::new(AUTH_REQUIRED, "Log in to see your jobs").kind("logged_out", json!({})));
+        };
+        let id = params.id;
+        let lookup = id.clone();
+        let Some(job) = db.run(|pool| async move { pool.job_record(&lookup, &email).await }).await? else {
+            return Err(RpcError::new(NOT_FOUND, format!("Job {} not found", id)).kind("job", json!({ "id": id })));
+        };
+        Ok(json!({
+            "id": job.id,
+            "name": job.name,
+            "backend": job.backend,
+            "status": job.status,
+            "error": job.error,
+            "created_at": job.created_at,
+            "completed_at": job.completed_at,
+            "result": job.result,
+        }))
+    }
+
+    fn save(&self, params: SaveParams) -> Result<Value, RpcError> {
+        if !is_plain_file_name(&params.name) {
+            return Err(RpcError::new(
+                INVALID_PARAMS,
+                "name must be a plain file name without directories",
+            ));
+        }
+
+        let dir = Config::files_dir().map_err(|e| RpcError::new(SERVER_ERROR, e.to_string()))?;
+        std::fs::create_dir_all(&dir)
+            .and_then(|_| std::fs::write(dir.join(&params.name), &params.content))
+            .map_err(|e| RpcError::new(SERVER_ERROR, format!("Failed to save file: {}", e)))?;
+
+        Ok(json!({ "path": dir.join(&params.name).display().to_string() }))
+    }
+
+    /// `run_circuit` for a file on disk
+    async fn run(&self, params: RunParams) -> Result<Value, RpcError> {
+        if !Path::new(&params.file).is_file() {
+            return Err(RpcError::new(INVALID_PARAMS, format!("File not found: {}", params.file)));
+        }
+        if !params.file.ends_with(".qasm") {
+            return Err(RpcError::new(INVALID_PARAMS, "Only OpenQASM programs (.qasm) can be run"));
+        }
+        let qasm = std::fs::read_to_string(&params.file)
+            .map_err(|e| RpcError::new(SERVER_ERROR, format!("Failed to read file: {}", e)))?;
+        self.run_circuit(RunCircuitParams { qasm, backend: None, shots: None }).await
+    }
+
+    fn status(&self) -> Value {
+        let user = self.config.user();
+        json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "authenticated": user.is_some_and(|u| u.has_session()),
+            "email": user.map(|u| u.email.clone()),
+            "tier": user.map(|u| u.tier.clone()).unwrap_or_else(|| "free".to_string()),
+            "ai_provider": self.config.ai.provider,
+            "ai_key_configured": self.config.get_ai_api_key().is_some(),
+            "quantum_key_configured": self.config.get_quantum_api_key().is_some(),
+        })
+    }
+}
+
+fn parse_params<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params)
+        .map_err(|e| RpcError::new(INVALID_PARAMS, format!("Invalid params: {}", e)))
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    let mut body = json!({ "code": error.code, "message": error.message });
+    if let Some(data) = error.data {
+        body["data"] = data;
+    }
+    json!({ "jsonrpc": "2.0", "id": id, "error": body })
+}
+
+fn is_plain_file_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    )
+}
+
+/// Answer requests from `reader` on `writer` until the reader is closed and
+/// every request has been answered
+pub async fn serve<R, W>(server: Arc<Server>, reader: R, mut writer: W) -> Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let (tx, mut rx) = mpsc::unbounded_channel::<Value>();
+    // One writer, so concurrent responses never interleave within a line
+    let output = tokio::spawn(async move {
+        while let Some(response) = rx.recv().await {
+            writer.write_all(response.to_string().as_bytes()).await?;
+            writer.write_all(b"\n").await?;
+            writer.flush().await?;
+        }
+        anyhow::Ok(())
+    });
+
+    let mut reader = BufReader::new(reader);
+    let mut requests = JoinSet::new();
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        // read_until buffers partial lines until the newline (or EOF) arrives
+        if reader.read_until(b'\n', &mut buf).await? == 0 {
+            break;
+        }
+        let line = match String::from_utf8(buf.clone()) {
+            Ok(line) => line,
+            Err(_) => {
+                let error = RpcError::new(PARSE_ERROR, "Parse error: invalid UTF-8");
+                let _ = tx.send(error_response(Value::Null, error));
+                continue;
+            }
+        };
+        let (server, tx) = (server.clone(), tx.clone());
+        requests.spawn(async move {
+            if let Some(response) = server.handle_line(&line).await {
+                let _ = tx.send(response);
+            }
+        });
+        // Reap finished requests so a long session does not pile them up
+        while requests.try_join_next().is_some() {}
+    }
+
+    while requests.join_next().await.is_some() {}
+    drop(tx);
+    output.await?
+}
+
+/// Serve on stdin/stdout until stdin is closed, or on a Unix socket until
+/// interrupted
+pub async fn execute_serve(locator: &ConfigLocator, socket: Option<&str>) -> Result<()> {
+    let config = Config::load(locator).unwrap_or_else(|e| {
+        diagnostics::warn(format!("Failed to load config: {}. Using defaults.", e));
+        Config::default_at(locator)
+    });
+    let db = PoolManager::from_env(&config.db);
+    if let Some(db) = &db {
+        db.start();
+    }
+    let server = Arc::new(Server::new(config, db));
+
+    match socket {
+        None => serve(server, tokio::io::stdin(), tokio::io::stdout()).await,
+        Some(path) => listen(server, Path::new(path)).await,
+    }
+}
+
+/// Listen at `path`. Only a socket left behind by a previous run is
+/// replaced; any other file there is an error. Whoever connects chats with
+/// the user's AI key and runs on their quantum account, so the socket is
+/// made private to the user.
+#[cfg(unix)]
+fn bind(path: &Path) -> Result<tokio::net::UnixListener> {
+    use anyhow::Context;
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => {
+            std::fs::remove_file(path).with_context(|| format!("{} exists and cannot be replaced", path.display()))?;
+        }
+        Ok(_) => anyhow::bail!("{} exists and is not a socket; choose another --socket path", path.display()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e).with_context(|| format!("Cannot check {}", path.display())),
+    }
+    let listener =
+        tokio::net::UnixListener::bind(path).with_context(|| format!("Cannot listen on {}", path.display()))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+        .with_context(|| format!("Cannot restrict access to {}", path.display()))?;
+    Ok(listener)
+}
+
+#[cfg(unix)]
+async fn listen(server: Arc<Server>, path: &Path) -> Result<()> {
+    let listener = bind(path)?;
+    diagnostics::info(format!("Listening on {}", path.display()));
+
+    let result = loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let stream = match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(e) => break Err(e.into()),
+                };
+                let server = server.clone();
+                tokio::spawn(async move {
+                    let (reader, writer) = stream.into_split();
+                    if let Err(e) = serve(server, reader, writer).await {
+                        diagnostics::warn(format!("Editor connection closed: {}", e));
+                    }
+                });
+            }
+            _ = tokio::signal::ctrl_c() => break Ok(()),
+        }
+    };
+    let _ = std::fs::remove_file(path);
+    result
+}
+
+#[cfg(not(unix))]
+async fn listen(_server: Arc<Server>, _path: &Path) -> Result<()> {
+    anyhow::bail!("--socket needs Unix domain sockets; use --stdio on this platform")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::settings::DbConfig;
+    use std::time::Duration;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+    use uuid::Uuid;
+
+    fn server() -> Server {
+        Server::new(Config::default(), None)
+    }
+
+    /// An OpenAI-compatible endpoint that answers every request with `reply`
+    /// after `delay`, echoing how many messages it was sent
+    async fn fake_ai(reply: &'static str, delay: Duration) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut chunk = [0u8; 4096];
+                    // Headers, then the body Content-Length announces
+                    let body = loop {
+                        let n = stream.read(&mut chunk).await.unwrap();
+                        request.extend_from_slice(&chunk[..n]);
+                        let text = String::from_utf8_lossy(&request).to_string();
+                        if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                            let length = head
+                                .lines()
+                                .find_map(|l| l.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().to_string()))
+                                .and_then(|v| v.parse::<usize>().ok())
+                                .unwrap_or(0);
+                            if body.len() >= length {
+                                break body.to_string();
+                            }
+                        }
+                    };
+                    tokio::time::sleep(delay).await;
+                    let sent: Value = serde_json::from_str(&body).unwrap();
+                    let content = format!("{} ({} messages)", reply, sent["messages"].as_array().unwrap().len());
+                    let response = json!({
+                        "choices": [{ "message": { "content": content }, "finish_reason": "stop" }],
+                        "usage": { "total_tokens": 42 },
+                    })
+                    .to_string();
+                    let _ = stream
+                        .write_all(
+                            format!(
+                                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                                response.len(),
+                                response
+                            )
+                            .as_bytes(),
+                        )
+                        .await;
+                });
+            }
+        });
+        format!("http://{}", address)
+    }
+
+    /// Send `lines` through `serve` and collect the responses by id
+    async fn exchange(server: Server, lines: &[&str]) -> HashMap<String, Value> {
+        let (mut client, server_end) = tokio::io::duplex(64 * 1024);
+        let (reader, writer) = tokio::io::split(server_end);
+        let serving = tokio::spawn(serve(Arc::new(server), reader, writer));
+        for line in lines {
+            client.write_all(format!("{}\n", line).as_bytes()).await.unwrap();
+        }
+        client.shutdown().await.unwrap();
+        let mut output = String::new();
+        client.read_to_string(&mut output).await.unwrap();
+        serving.await.unwrap().unwrap();
+        output
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .map(|response| (response["id"].to_string(), response))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_malformed_input_gets_error_response() {
+        let server = server();
+        assert!(server.handle_line("   \n").await.is_none());
+
+        let response = server.handle_line("{\"method\": \"chat\"").await.unwrap();
+        assert_eq!(response["error"]["code"], PARSE_ERROR);
+
+        let response = server.handle_line("{\"id\": 7, \"params\": {}}").await.unwrap();
+        assert_eq!(response["id"], 7);
+        assert_eq!(response["error"]["code"], INVALID_REQUEST);
+
+        let response = server.handle_line("{\"id\": 8, \"method\": \"teleport\"}").await.unwrap();
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+
+        let response = server.handle_line("{\"id\": 9, \"method\": \"chat\", \"params\": {}}").await.unwrap();
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn test_status_and_save_validation() {
+        let server = server();
+        for method in ["status", "auth.status"] {
+            let response = server.handle_line(&format!("{{\"id\": \"a\", \"method\": \"{}\"}}", method)).await.unwrap();
+            assert_eq!(response["id"], "a");
+            assert_eq!(response["result"]["authenticated"], false);
+        }
+
+        let response = server
+            .handle_line("{\"id\": 1, \"method\": \"save\", \"params\": {\"name\": \"../evil\", \"content\": \"\"}}")
+            .await
+            .unwrap();
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn test_chat_sessions_through_the_protocol() {
+        let mut config = Config::default();
+        config.ai.base_url = fake_ai("Here is a Bell state", Duration::ZERO).await;
+        config.ai.api_key = Some("key".to_string());
+        let responses = exchange(
+            Server::new(config, None),
+            &[
+                r#"{"id": 1, "method": "chat", "params": {"prompt": "Make a Bell state", "session_id": "a"}}"#,
+                r#"{"id": 2, "method": "chat", "params": {"prompt": "Another one", "session_id": "b"}}"#,
+            ],
+        )
+        .await;
+        // System prompt and question each; the sessions do not see each other
+        assert_eq!(responses["1"]["result"]["content"], "Here is a Bell state (2 messages)");
+        assert_eq!(responses["1"]["result"]["session_id"], "a");
+        assert_eq!(responses["1"]["result"]["total_tokens"], 42);
+        assert_eq!(responses["2"]["result"]["content"], "Here is a Bell state (2 messages)");
+
+        let mut config = Config::default();
+        config.ai.base_url = fake_ai("Sure", Duration::ZERO).await;
+        config.ai.api_key = Some("key".to_string());
+        let server = Server::new(config, None);
+        server.handle_line(r#"{"id": 1, "method": "chat", "params": {"message": "Hi"}}"#).await;
+        let response = server.handle_line(r#"{"id": 2, "method": "chat", "params": {"prompt": "Again"}}"#).await.unwrap();
+        assert_eq!(response["result"]["content"], "Sure (4 messages)");
+        let response = server
+            .handle_line(r#"{"id": 3, "method": "chat", "params": {"prompt": "Fresh", "reset": true}}"#)
+            .await
+            .unwrap();
+        assert_eq!(response["result"]["content"], "Sure (2 messages)");
+    }
+
+    #[tokio::test]
+    async fn test_chat_history_is_trimmed_to_the_context() {
+        let mut config = Config::default();
+        config.ai.base_url = fake_ai("Sure", Duration::ZERO).await;
+        config.ai.api_key = Some("key".to_string());
+        config.ai.context_window = Some(2000);
+        config.ai.max_tokens = 1000;
+        let server = Server::new(config, None);
+        let prompt = "qubit ".repeat(400);
+        let line = json!({ "id": 1, "method": "chat", "params": { "prompt": prompt } }).to_string();
+        for round in 0..3 {
+            let response = server.handle_line(&line).await.unwrap();
+            // Room for the system prompt, one long question and the short
+            // answer before it; earlier questions are dropped
+            let sent = if round == 0 { 2 } else { 3 };
+            assert_eq!(response["result"]["content"], format!("Sure ({} messages)", sent));
+        }
+        let history = server.session(DEFAULT_SESSION);
+        assert_eq!(history.lock().await.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_chat_model_is_checked_against_the_tier() {
+        let mut config = Config::default();
+        config.ai.api_key = Some("key".to_string());
+        config.ai.model = "openai/gpt-4o".to_string();
+        // Refused before anything is sent to the hosted gateway
+        let response = Server::new(config, None)
+            .handle_line(r#"{"id": 1, "method": "chat", "params": {"prompt": "hi"}}"#)
+            .await
+            .unwrap();
+        assert_eq!(response["error"]["code"], TIER_RESTRICTED);
+
+        let mut config = Config::default();
+        config.ai.base_url = fake_ai("Sure", Duration::ZERO).await;
+        config.ai.api_key = Some("key".to_string());
+        config.ai.model = "openai/gpt-4o".to_string();
+        // Other endpoints are the user's own business
+        let response = Server::new(config, None)
+            .handle_line(r#"{"id": 1, "method": "chat", "params": {"prompt": "hi"}}"#)
+            .await
+            .unwrap();
+        assert_eq!(response["result"]["content"], "Sure (2 messages)");
+    }
+
+    #[tokio::test]
+    async fn test_simulator_run_through_the_protocol() {
+        let bell = "OPENQASM 2.0;\\ninclude \\\"qelib1.inc\\\";\\nqreg q[2];\\ncreg c[2];\\nh q[0];\\ncx q[0],q[1];\\nmeasure q -> c;\\n";
+        let run = format!(r#"{{"id": 1, "method": "run_circuit", "params": {{"qasm": "{}", "shots": 200}}}}"#, bell);
+        let explicit = format!(
+            r#"{{"id": 2, "method": "run_circuit", "params": {{"qasm": "{}", "backend": "simulator"}}}}"#,
+            bell
+        );
+        let responses = exchange(
+            server(),
+            &[
+                &run,
+                &explicit,
+                r#"{"id": 3, "method": "run_circuit", "params": {"qasm": "qreg q[1]; frobnicate q[0];"}}"#,
+                &format!(r#"{{"id": 4, "method": "run_circuit", "params": {{"qasm": "{}", "backend": "ibm_brisbane"}}}}"#, bell),
+                &format!(r#"{{"id": 5, "method": "run_circuit", "params": {{"qasm": "{}", "shots": 1000000}}}}"#, bell),
+            ],
+        )
+        .await;
+
+        let result = &responses["1"]["result"];
+        assert_eq!((result["backend"].as_str(), result["shots"].as_u64()), (Some("simulator"), Some(200)));
+        let counts = result["counts"].as_object().unwrap();
+        assert_eq!(counts.values().map(|n| n.as_u64().unwrap()).sum::<u64>(), 200);
+        assert!(counts.keys().all(|k| k == "00" || k == "11"));
+        assert_eq!(responses["2"]["result"]["shots"], 1024);
+
+        assert_eq!(responses["3"]["error"]["data"]["kind"], "qasm");
+        // Free tier: no hardware, and a cap on shots
+        assert_eq!(responses["4"]["error"]["code"], TIER_RESTRICTED);
+        assert_eq!(responses["5"]["error"]["code"], TIER_RESTRICTED);
+    }
+
+    #[tokio::test]
+    async fn test_requests_run_concurrently() {
+        // The slow chat answers after the status sent behind it
+        let mut config = Config::default();
+        config.ai.base_url = fake_ai("Slow", Duration::from_millis(300)).await;
+        config.ai.api_key = Some("key".to_string());
+        let (mut client, server_end) = tokio::io::duplex(4096);
+        let (reader, writer) = tokio::io::split(server_end);
+        tokio::spawn(serve(Arc::new(Server::new(config, None)), reader, writer));
+        client
+            .write_all(b"{\"id\": 1, \"method\": \"chat\", \"params\": {\"prompt\": \"hi\"}}\n{\"id\": 2, \"method\": \"status\"}\n")
+            .await
+            .unwrap();
+        let mut lines = BufReader::new(client).lines();
+        let first: Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(first["id"], 2);
+        let second: Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(second["id"], 1);
+        assert_eq!(second["result"]["content"], "Slow (2 messages)");
+    }
+
+    #[tokio::test]
+    async fn test_job_status_and_missing_setup() {
+        let response = server().handle_line(r#"{"id": 1, "method": "job_status", "params": {"id": "x"}}"#).await.unwrap();
+        assert_eq!(response["error"]["code"], DB_UNAVAILABLE);
+        let response = server().handle_line(r#"{"id": 2, "method": "list_backends"}"#).await.unwrap();
+        assert_eq!(response["error"]["data"]["kind"], "no_quantum_key");
+
+        let path = std::env::temp_dir().join(format!("qhub-serve-{}.db", Uuid::new_v4()));
+        let db = PoolManager::new(format!("sqlite://{}?mode=rwc", path.display()), DbConfig::default());
+        db.start();
+        assert!(db.wait_connected(Duration::from_secs(10)).await);
+        db.run(|pool| async move {
+            let pool = pool.sqlite().unwrap();
+            sqlx::query("INSERT INTO users (id, email, password_hash) VALUES ('u1', 'ada@example.com', 'x')")
+                .execute(pool)
+                .await?;
+            sqlx::query("INSERT INTO quantum_jobs (id, user_id, circuit_code, backend, status) VALUES ('j1', 'u1', 'h q[0];', 'ibm_kyiv', 'queued')")
+                .execute(pool)
+                .await?;
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        let mut config = Config::default();
+        config.add_account("ada@example.com".to_string(), "t".to_string(), "free".to_string()).unwrap();
+        let server = Server::new(config, Some(db));
+        let response = server.handle_line(r#"{"id": 1, "method": "job_status", "params": {"id": "j1"}}"#).await.unwrap();
+        assert_eq!(response["result"]["status"], "queued");
+        assert_eq!(response["result"]["backend"], "ibm_kyiv");
+        let response = server.handle_line(r#"{"id": 2, "method": "job_status", "params": {"id": "j2"}}"#).await.unwrap();
+        assert_eq!(response["error"]["code"], NOT_FOUND);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_errors_keep_their_kind() {
+        let error = anyhow::Error::from(QuantumError::BackendOffline {
+            backend: Some("ibm_kyiv".to_string()),
+            until: None,
+        })
+        .context("Could not submit");
+        let rpc = RpcError::from_error(&error);
+        assert_eq!(rpc.code, BACKEND_UNAVAILABLE);
+        assert_eq!(rpc.data.unwrap()["backend"], "ibm_kyiv");
+        assert!(rpc.message.starts_with("Could not submit: "));
+
+        let limited = RateLimited { retry_after: Some(Duration::from_secs(30)), remaining: None };
+        let rpc = RpcError::from_error(&anyhow::Error::from(limited));
+        assert_eq!((rpc.code, rpc.data.unwrap()["retry_after_secs"].as_u64()), (RATE_LIMITED, Some(30)));
+        assert_eq!(RpcError::from_error(&anyhow::anyhow!("boom")).code, SERVER_ERROR);
+    }
+
+    #[test]
+    fn test_plain_file_name() {
+        assert!(is_plain_file_name("bell.qqb"));
+        assert!(!is_plain_file_name("../bell.qqb"));
+        assert!(!is_plain_file_name("dir/bell.qqb"));
+        assert!(!is_plain_file_name("/etc/passwd"));
+        assert!(!is_plain_file_name(""));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_socket_replaces_only_sockets() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("qhub-serve-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("notes.txt");
+        std::fs::write(&file, "keep me").unwrap();
+        let error = bind(&file).unwrap_err().to_string();
+        assert!(error.contains("is not a socket"), "{}", error);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "keep me");
+
+        let socket = dir.join("qhub.sock");
+        drop(bind(&socket).unwrap());
+        let mode = std::fs::metadata(&socket).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        // The socket a previous run left behind is replaced
+        assert!(bind(&socket).is_ok());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,345 @@
+// `qhub update`: replace this executable with the latest release
+//
+// The latest GitHub release is read for its version, notes and assets. The
+// binary built for this platform is downloaded, checked against the
+// release's SHA256SUMS and moved over the running executable, which is safe
+// on Unix and done by renaming the old one aside on Windows. SHA256SUMS must
+// carry an Ed25519 signature from the release key built into this binary, so
+// a mirror or a tampered release cannot swap the download; builds without a
+// key only check for updates. Requests use the configured proxy and CA
+// bundle like every other client. QHUB_UPDATE_URL points at a mirror serving
+// the same release JSON, and is only read from the real environment.
+
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose, Engine as _};
+use ring::signature;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fmt::Write as _;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::api::http;
+use crate::config::{Config, ConfigLocator};
+use crate::diagnostics;
+
+const RELEASE_URL: &str = "https://api.github.com/repos/CeoatNorthstar/qhub-cli/releases/latest";
+
+/// Release asset listing the SHA-256 of every binary
+const CHECKSUMS: &str = "SHA256SUMS";
+
+/// Release asset holding the base64 Ed25519 signature of SHA256SUMS
+const SIGNATURE: &str = "SHA256SUMS.sig";
+
+/// Base64 of the raw Ed25519 public key releases are signed with, set by the
+/// release build
+const RELEASE_KEY: Option<&str> = option_env!("QHUB_RELEASE_KEY");
+
+/// Lines of release notes shown before pointing at the full notes
+const CHANGELOG_LINES: usize = 12;
+
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    #[serde(default)]
+    body: Option<String>,
+    #[serde(default)]
+    html_url: Option<String>,
+    #[serde(default)]
+    assets: Vec<Asset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+impl Release {
+    fn asset(&self, name: &str) -> Option<&Asset> {
+        self.assets.iter().find(|a| a.name == name)
+    }
+}
+
+/// major.minor.patch; a pre-release sorts before its release
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Version {
+    major: u64,
+    minor: u64,
+    patch: u64,
+    release: bool,
+}
+
+impl Version {
+    /// "1.2.3", "v1.2.3" or "1.2.3-rc.1"; build metadata is ignored
+    fn parse(text: &str) -> Option<Self> {
+        let text = text.trim().trim_start_matches('v');
+        let text = text.split('+').next()?;
+        let (core, pre) = match text.split_once('-') {
+            Some((core, pre)) => (core, Some(pre)),
+            None => (text, None),
+        };
+        let mut parts = core.split('.').map(|p| p.parse::<u64>());
+        let version = Version {
+            major: parts.next()?.ok()?,
+            minor: parts.next().unwrap_or(Ok(0)).ok()?,
+            patch: parts.next().unwrap_or(Ok(0)).ok()?,
+            release: pre.is_none(),
+        };
+        parts.next().is_none().then_some(version)
+    }
+}
+
+/// The asset built for this machine, e.g. `qhub-x86_64-linux`
+fn asset_name() -> String {
+    format!("qhub-{}-{}{}", std::env::consts::ARCH, std::env::consts::OS, std::env::consts::EXE_SUFFIX)
+}
+
+/// The checksum SHA256SUMS lists for `name`, in `sha256sum` output format
+fn expected_checksum(sums: &str, name: &str) -> Option<String> {
+    sums.lines().find_map(|line| {
+        let (hash, file) = line.trim().split_once(char::is_whitespace)?;
+        // `*` marks binary mode in sha256sum output
+        (file.trim().trim_start_matches('*') == name).then(|| hash.to_ascii_lowercase())
+    })
+}
+
+/// Check `signature` (base64) over SHA256SUMS against the release `key`
+fn verify_signature(key: &str, sums: &[u8], signature: &str) -> Result<()> {
+    let key = general_purpose::STANDARD.decode(key.trim()).context("The built-in release key is not base64")?;
+    let signature = general_purpose::STANDARD
+        .decode(signature.trim())
+        .with_context(|| format!("{} is not a base64 signature", SIGNATURE))?;
+    signature::UnparsedPublicKey::new(&signature::ED25519, key)
+        .verify(sums, &signature)
+        .map_err(|_| anyhow::anyhow!("{} is not signed by the qhub release key. Not installing it.", CHECKSUMS))
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{:02x}", byte);
+        hex
+    })
+}
+
+/// The first lines of the release notes, blank lines dropped
+fn changelog_summary(body: &str, url: Option<&str>) -> String {
+    let lines: Vec<&str> = body.lines().map(str::trim_end).filter(|l| !l.trim().is_empty()).collect();
+    let mut summary = lines.iter().take(CHANGELOG_LINES).map(|l| format!("  {}", l)).collect::<Vec<_>>().join("\n");
+    if lines.len() > CHANGELOG_LINES {
+        summary.push_str(&format!("\n  … {} more line(s)", lines.len() - CHANGELOG_LINES));
+        if let Some(url) = url {
+            summary.push_str(&format!(" at {}", url));
+        }
+    }
+    summary
+}
+
+/// Why a file could not be written, with what to do about it
+fn write_error(error: io::Error, path: &Path) -> anyhow::Error {
+    if error.kind() == io::ErrorKind::PermissionDenied {
+        anyhow::anyhow!(
+            "No permission to replace {}. Run `sudo qhub update`, or reinstall qhub somewhere you own (e.g. ~/.local/bin).",
+            path.display()
+        )
+    } else {
+        anyhow::Error::new(error).context(format!("Failed to replace {}", path.display()))
+    }
+}
+
+/// Put `bytes` in place of the executable at `target`. The new binary is
+/// written next to it first, so a failed download never leaves half a file.
+fn replace_executable(target: &Path, bytes: &[u8]) -> Result<()> {
+    let dir = target.parent().context("The executable has no parent directory")?;
+    let file_name = target.file_name().context("The executable has no file name")?.to_string_lossy();
+    let staged = dir.join(format!(".{}.update-{}", file_name, Uuid::new_v4()));
+    std::fs::write(&staged, bytes).map_err(|e| write_error(e, target))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if let Err(e) = std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755)) {
+            let _ = std::fs::remove_file(&staged);
+            return Err(write_error(e, target));
+        }
+    }
+    // A running executable cannot be overwritten on Windows, only renamed
+    #[cfg(windows)]
+    {
+        let old = target.with_extension("old.exe");
+        let _ = std::fs::remove_file(&old);
+        if let Err(e) = std::fs::rename(target, &old) {
+            let _ = std::fs::remove_file(&staged);
+            return Err(write_error(e, target));
+        }
+    }
+
+    std::fs::rename(&staged, target).map_err(|e| {
+        let _ = std::fs::remove_file(&staged);
+        // Put the old executable back rather than leave none at all
+        #[cfg(windows)]
+        let _ = std::fs::rename(target.with_extension("old.exe"), target);
+        write_error(e, target)
+    })
+}
+
+async fn download(client: &reqwest::Client, url: &str) -> Result<Vec<u8>> {
+    let response = client.get(url).send().await.with_context(|| format!("Failed to download {}", url))?;
+    if !response.status().is_success() {
+        anyhow::bail!("Download of {} failed: HTTP {}", url, response.status());
+    }
+    Ok(response.bytes().await?.to_vec())
+}
+
+/// Check for a newer release and, unless `check_only`, install it
+pub async fn execute_update(locator: &ConfigLocator, check_only: bool) -> Result<()> {
+    let config = Config::load(locator).unwrap_or_else(|e| {
+        diagnostics::warn(format!("Failed to load config: {}. Using default network settings.", e));
+        Config::default()
+    });
+    let client = http::client_builder(&config.network)
+        .timeout(Duration::from_secs(300))
+        .connect_timeout(Duration::from_secs(10))
+        // GitHub's API refuses requests without one
+        .user_agent(concat!("qhub-cli/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .context("Failed to set up the HTTP client")?;
+
+    let url = std::env::var("QHUB_UPDATE_URL").unwrap_or_else(|_| RELEASE_URL.to_string());
+    let response = client.get(&url).send().await.context("Could not reach the release server")?;
+    if !response.status().is_success() {
+        anyhow::bail!("Could not read the latest release: HTTP {}", response.status());
+    }
+    let release: Release = response.json().await.context("The release server sent an unreadable answer")?;
+
+    let current = env!("CARGO_PKG_VERSION");
+    let latest = Version::parse(&release.tag_name)
+        .with_context(|| format!("The latest release has no version number ({})", release.tag_name))?;
+    if Version::parse(current).is_some_and(|current| current >= latest) {
+        println!("qhub {} is up to date.", current);
+        return Ok(());
+    }
+
+    println!("qhub {} is available (you have {}).", release.tag_name.trim_start_matches('v'), current);
+    if let Some(body) = release.body.as_deref().filter(|b| !b.trim().is_empty()) {
+        println!("\nWhat's new:\n{}\n", changelog_summary(body, release.html_url.as_deref()));
+    }
+    if check_only {
+        println!("Run `qhub update` to install it.");
+        return Ok(());
+    }
+
+    let Some(key) = RELEASE_KEY else {
+        anyhow::bail!(
+            "This build of qhub has no release signing key, so it cannot verify updates. Download the new version from {}.",
+            release.html_url.as_deref().unwrap_or("the releases page")
+        );
+    };
+    let name = asset_name();
+    let Some(binary) = release.asset(&name) else {
+        let available: Vec<&str> = release.assets.iter().map(|a| a.name.as_str()).filter(|n| *n != CHECKSUMS && *n != SIGNATURE).collect();
+        anyhow::bail!(
+            "The release has no build for this platform ({}). Available: {}",
+            name,
+            if available.is_empty() { "none".to_string() } else { available.join(", ") }
+        );
+    };
+    let (Some(sums), Some(sig)) = (release.asset(CHECKSUMS), release.asset(SIGNATURE)) else {
+        anyhow::bail!("The release has no signed {}, so the download cannot be verified. Not installing it.", CHECKSUMS);
+    };
+
+    let sums = download(&client, &sums.browser_download_url).await?;
+    let sig = String::from_utf8(download(&client, &sig.browser_download_url).await?)
+        .with_context(|| format!("{} is not text", SIGNATURE))?;
+    verify_signature(key, &sums, &sig)?;
+    let sums = String::from_utf8(sums).with_context(|| format!("{} is not text", CHECKSUMS))?;
+    let expected = expected_checksum(&sums, &name).with_context(|| format!("{} does not list {}", CHECKSUMS, name))?;
+    println!("Downloading {}…", name);
+    let bytes = download(&client, &binary.browser_download_url).await?;
+    let actual = sha256_hex(&bytes);
+    if actual != expected {
+        anyhow::bail!("Checksum mismatch for {} (expected {}, got {}). Not installing it.", name, expected, actual);
+    }
+
+    let target = std::env::current_exe().context("Cannot tell where qhub is installed")?;
+    // Replace the real file, not a symlink to it
+    let target = std::fs::canonicalize(&target).unwrap_or(target);
+    replace_executable(&target, &bytes)?;
+    println!("Updated {} to {}.", target.display(), release.tag_name.trim_start_matches('v'));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versions() {
+        let v = |s| Version::parse(s).unwrap();
+        assert!(v("v0.2.0") > v("0.1.9"));
+        assert!(v("1.0.0") > v("1.0.0-rc.2"));
+        assert!(v("1.10.0") > v("1.9.3"));
+        assert_eq!(v("1.2"), v("1.2.0+build.7"));
+        assert_eq!(Version::parse("latest"), None);
+        assert_eq!(Version::parse("1.2.3.4"), None);
+    }
+
+    #[test]
+    fn test_expected_checksum() {
+        let sums = "ABC123  qhub-x86_64-linux\nfff000 *qhub-aarch64-macos\n\n";
+        assert_eq!(expected_checksum(sums, "qhub-x86_64-linux").as_deref(), Some("abc123"));
+        assert_eq!(expected_checksum(sums, "qhub-aarch64-macos").as_deref(), Some("fff000"));
+        assert_eq!(expected_checksum(sums, "qhub-x86_64-windows.exe"), None);
+        assert_eq!(sha256_hex(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        use ring::signature::{Ed25519KeyPair, KeyPair};
+        let rng = ring::rand::SystemRandom::new();
+        let keypair = |rng| Ed25519KeyPair::from_pkcs8(Ed25519KeyPair::generate_pkcs8(rng).unwrap().as_ref()).unwrap();
+        let (release, other) = (keypair(&rng), keypair(&rng));
+        let key = general_purpose::STANDARD.encode(release.public_key());
+        let sums = b"abc123  qhub-x86_64-linux\n";
+        let sig = general_purpose::STANDARD.encode(release.sign(sums));
+
+        assert!(verify_signature(&key, sums, &format!("{}\n", sig)).is_ok());
+        assert!(verify_signature(&key, b"fff000  qhub-x86_64-linux\n", &sig).is_err());
+        assert!(verify_signature(&key, sums, &general_purpose::STANDARD.encode(other.sign(sums))).is_err());
+        assert!(verify_signature(&key, sums, "not a signature").is_err());
+    }
+
+    #[test]
+    fn test_changelog_summary() {
+        let body: String = (1..=15).map(|i| format!("- change {}\n\n", i)).collect();
+        let summary = changelog_summary(&body, Some("https://example.com/notes"));
+        assert!(summary.starts_with("  - change 1\n  - change 2\n"));
+        assert!(summary.ends_with("  - change 12\n  … 3 more line(s) at https://example.com/notes"));
+        assert_eq!(changelog_summary("Fixes\n", None), "  Fixes");
+    }
+
+    #[test]
+    fn test_replace_executable() {
+        let dir = std::env::temp_dir().join(format!("qhub-update-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let target = dir.join("qhub");
+        std::fs::write(&target, b"old").unwrap();
+
+        replace_executable(&target, b"new").unwrap();
+        assert_eq!(std::fs::read(&target).unwrap(), b"new");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&target).unwrap().permissions().mode() & 0o777, 0o755);
+        }
+        // Nothing staged is left behind
+        let leftovers = std::fs::read_dir(&dir).unwrap().filter_map(|e| e.ok()).filter(|e| e.file_name() != "qhub").count();
+        assert_eq!(leftovers, 0);
+
+        let err = write_error(io::Error::from(io::ErrorKind::PermissionDenied), &target).to_string();
+        assert!(err.contains("sudo qhub update"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
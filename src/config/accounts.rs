@@ -0,0 +1,178 @@
+// QHub accounts saved on this machine
+//
+// Several accounts can be logged in at once, say a personal and a work one.
+// Each keeps its email, tier and where its session token is stored; one is
+// active, and that is the account requests, /status and job history use.
+// With `security.use_keychain` every account's token gets its own keychain
+// entry, otherwise it is kept in config.toml. Config files from before
+// accounts had a single `[user]`, which loading turns into the first one.
+
+use anyhow::{Context, Result};
+
+use super::secrets::KEYCHAIN_SERVICE;
+use super::settings::{UserConfig, CONFIG_VERSION};
+use super::Config;
+
+fn session_entry(email: &str) -> Result<keyring::Entry> {
+    keyring::Entry::new(KEYCHAIN_SERVICE, &format!("session:{}", email)).context("Failed to open keychain entry")
+}
+
+impl UserConfig {
+    /// Whether a session token is stored for this account
+    pub fn has_session(&self) -> bool {
+        self.token.is_some() || self.keychain
+    }
+}
+
+impl Config {
+    /// The active account
+    pub fn user(&self) -> Option<&UserConfig> {
+        self.accounts.iter().find(|a| a.active)
+    }
+
+    pub fn account(&self, email: &str) -> Option<&UserConfig> {
+        self.accounts.iter().find(|a| a.email.eq_ignore_ascii_case(email))
+    }
+
+    /// The session token stored for `account`, from config.toml or the keychain
+    pub fn session_token(&self, account: &UserConfig) -> Option<String> {
+        if account.token.is_some() {
+            return account.token.clone();
+        }
+        if !account.keychain {
+            return None;
+        }
+        session_entry(&account.email).ok()?.get_password().ok()
+    }
+
+    /// Store a session for `email` and make it the active account. Logging
+    /// in to (or switching to) an account that is already saved replaces its
+    /// session and tier.
+    pub fn add_account(&mut self, email: String, token: String, tier: String) -> Result<()> {
+        let keychain = self.security.use_keychain;
+        if keychain {
+            session_entry(&email)?
+                .set_password(&token)
+                .context("Failed to store the session in the keychain")?;
+        }
+        for account in &mut self.accounts {
+            account.active = false;
+        }
+        let account = UserConfig { email, token: (!keychain).then_some(token), tier, active: true, keychain };
+        match self.accounts.iter_mut().find(|a| a.email.eq_ignore_ascii_case(&account.email)) {
+            Some(existing) => *existing = account,
+            None => self.accounts.push(account),
+        }
+        Ok(())
+    }
+
+    /// Forget the active account and its session; the others stay saved
+    pub fn remove_active_account(&mut self) -> Option<UserConfig> {
+        let index = self.accounts.iter().position(|a| a.active)?;
+        let account = self.accounts.remove(index);
+        if account.keychain {
+            if let Ok(entry) = session_entry(&account.email) {
+                // A missing entry is fine - there is nothing to clear
+                let _ = entry.delete_credential();
+            }
+        }
+        Some(account)
+    }
+
+    /// Move `email`'s session token from config.toml into the keychain
+    pub fn move_session_to_keychain(&mut self, email: &str) -> Result<()> {
+        let account = self
+            .accounts
+            .iter_mut()
+            .find(|a| a.email.eq_ignore_ascii_case(email))
+            .with_context(|| format!("No saved account for {}", email))?;
+        let Some(token) = account.token.take() else {
+            return Ok(());
+        };
+        if let Err(e) = session_entry(&account.email).and_then(|entry| {
+            entry.set_password(&token).context("Failed to store the session in the keychain")
+        }) {
+            account.token = Some(token);
+            return Err(e);
+        }
+        account.keychain = true;
+        Ok(())
+    }
+
+    /// Drop `email`'s session, wherever it is stored; the account stays
+    /// saved and logging in to it starts a new one
+    pub fn forget_session(&mut self, email: &str) {
+        let Some(account) = self.accounts.iter_mut().find(|a| a.email.eq_ignore_ascii_case(email)) else {
+            return;
+        };
+        if account.keychain {
+            if let Ok(entry) = session_entry(&account.email) {
+                // A missing entry is fine - there is nothing to clear
+                let _ = entry.delete_credential();
+            }
+        }
+        account.token = None;
+        account.keychain = false;
+    }
+
+    /// Move a version 1 `[user]` into the account list
+    pub(super) fn migrate_accounts(&mut self) {
+        if let Some(mut user) = self.legacy_user.take() {
+            if self.account(&user.email).is_none() {
+                user.active = self.user().is_none();
+                self.accounts.push(user);
+            }
+        }
+        self.version = self.version.max(CONFIG_VERSION);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(toml: &str) -> Config {
+        let mut config: Config = toml::from_str(toml).unwrap();
+        config.migrate_accounts();
+        config
+    }
+
+    #[test]
+    fn test_single_user_becomes_the_active_account() {
+        let mut config = config("version = 1\n[user]\nemail = \"ada@home.org\"\ntoken = \"t1\"\ntier = \"pro\"");
+        assert_eq!(config.version, CONFIG_VERSION);
+        let user = config.user().unwrap();
+        assert_eq!((user.email.as_str(), user.tier.as_str()), ("ada@home.org", "pro"));
+        assert_eq!(config.session_token(user).as_deref(), Some("t1"));
+
+        let saved = toml::to_string(&config).unwrap();
+        assert!(!saved.contains("[user]"), "{}", saved);
+        assert!(saved.contains("[[accounts]]"), "{}", saved);
+        config = toml::from_str(&saved).unwrap();
+        assert_eq!(config.user().unwrap().email, "ada@home.org");
+    }
+
+    #[test]
+    fn test_second_login_adds_an_account() {
+        let mut config = config("");
+        assert!(config.user().is_none());
+        config.add_account("ada@home.org".to_string(), "t1".to_string(), "free".to_string()).unwrap();
+        config.add_account("ada@work.com".to_string(), "t2".to_string(), "pro".to_string()).unwrap();
+        assert_eq!(config.accounts.len(), 2);
+        assert_eq!(config.user().unwrap().email, "ada@work.com");
+
+        // Logging in again refreshes the session instead of adding a duplicate
+        config.add_account("Ada@Home.org".to_string(), "t3".to_string(), "free".to_string()).unwrap();
+        assert_eq!(config.accounts.len(), 2);
+        assert_eq!(config.session_token(config.user().unwrap()).as_deref(), Some("t3"));
+
+        config.add_account("ada@work.com".to_string(), "t2".to_string(), "pro".to_string()).unwrap();
+        assert_eq!(config.user().unwrap().tier, "pro");
+        assert!(config.account("nobody@example.com").is_none());
+
+        // Logging out only forgets the active account
+        assert_eq!(config.remove_active_account().unwrap().email, "ada@work.com");
+        assert!(config.user().is_none());
+        assert!(config.account("ada@home.org").is_some_and(|a| a.has_session()));
+    }
+}
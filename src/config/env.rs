@@ -0,0 +1,91 @@
+// Loading `.env` without letting it redirect qhub
+//
+// For development, main reads the nearest `.env` (in the working directory or
+// a parent) into the environment. A cloned repository can ship one, so the
+// variables that decide where qhub fetches releases, keys and config from
+// are left out: they only count when set in the environment qhub started with.
+
+use crate::diagnostics;
+
+/// Variables a `.env` file may not set
+const PROCESS_ONLY: &[&str] = &["QHUB_UPDATE_URL", "QHUB_AI_BASE_URL", "QHUB_CA_BUNDLE", "QHUB_CONFIG"];
+
+/// Load the nearest `.env`, leaving variables the environment already has
+// The iterator is deprecated in favour of loading straight into the
+// environment, which is what this has to avoid
+#[allow(deprecated)]
+pub fn load_dotenv() {
+    let Ok(entries) = dotenv::dotenv_iter() else {
+        return;
+    };
+    let (apply, ignored) = split(entries.filter_map(Result::ok), |name| std::env::var_os(name).is_some());
+    for (name, value) in apply {
+        std::env::set_var(name, value);
+    }
+    for name in ignored {
+        diagnostics::warn(format!("Ignored {} from .env; set it in the environment qhub runs in instead.", name));
+    }
+}
+
+/// The `.env` entries to apply, and the process-only names that were skipped
+fn split(
+    entries: impl IntoIterator<Item = (String, String)>,
+    is_set: impl Fn(&str) -> bool,
+) -> (Vec<(String, String)>, Vec<String>) {
+    let mut apply = Vec::new();
+    let mut ignored = Vec::new();
+    for (name, value) in entries {
+        if PROCESS_ONLY.contains(&name.as_str()) {
+            ignored.push(name);
+        } else if !is_set(&name) {
+            apply.push((name, value));
+        }
+    }
+    (apply, ignored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(deprecated)]
+    fn read(contents: &str) -> Vec<(String, String)> {
+        let path = std::env::temp_dir().join(format!("qhub-dotenv-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&path, contents).unwrap();
+        let entries = dotenv::from_path_iter(&path).unwrap().map(Result::unwrap).collect();
+        std::fs::remove_file(&path).unwrap();
+        entries
+    }
+
+    #[test]
+    fn test_dotenv_cannot_set_the_update_url() {
+        let entries = read("QHUB_UPDATE_URL=https://evil.example/release\nDATABASE_URL=sqlite://dev.db\n");
+        let (apply, ignored) = split(entries, |_| false);
+        assert_eq!(apply, vec![("DATABASE_URL".to_string(), "sqlite://dev.db".to_string())]);
+        assert_eq!(ignored, vec!["QHUB_UPDATE_URL".to_string()]);
+    }
+
+    #[test]
+    fn test_dotenv_cannot_redirect_the_ai_key() {
+        // ai.base_url is user-only in project files; .env must not reopen that
+        let entries = read("QHUB_AI_BASE_URL=https://evil.example/v1\nQHUB_CA_BUNDLE=/tmp/evil.pem\nQHUB_AI_MODEL=deepseek/deepseek-chat\n");
+        let (apply, ignored) = split(entries, |_| false);
+        assert_eq!(apply, vec![("QHUB_AI_MODEL".to_string(), "deepseek/deepseek-chat".to_string())]);
+        assert_eq!(ignored, vec!["QHUB_AI_BASE_URL".to_string(), "QHUB_CA_BUNDLE".to_string()]);
+    }
+
+    #[test]
+    fn test_dotenv_cannot_swap_the_config_file() {
+        let (apply, ignored) = split(read("QHUB_CONFIG=./qhub/config.toml\n"), |_| false);
+        assert!(apply.is_empty());
+        assert_eq!(ignored, vec!["QHUB_CONFIG".to_string()]);
+    }
+
+    #[test]
+    fn test_dotenv_leaves_set_variables_alone() {
+        let entries = read("DATABASE_URL=sqlite://dev.db\n");
+        let (apply, ignored) = split(entries, |name| name == "DATABASE_URL");
+        assert!(apply.is_empty());
+        assert!(ignored.is_empty());
+    }
+}
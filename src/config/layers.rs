@@ -0,0 +1,278 @@
+// Where each config value comes from
+//
+// The effective config is built in layers, later ones winning: built-in
+// defaults, the user's ~/.qhub/config.toml, a project's .qhub.toml found by
+// walking up from the working directory, and environment variables. A
+// project file is usually checked into a repository someone else wrote, so
+// it may only set preferences: keys, accounts and anything that decides
+// where requests and credentials are sent stay user-level and are dropped
+// from it with a warning.
+//
+// `Layers` remembers the source of every value for `/status` and
+// `qhub config list`, and lets `Config::save` write the user's own values
+// back instead of whatever a project or the environment put on top.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+use super::lint::Problem;
+
+pub const PROJECT_FILE: &str = ".qhub.toml";
+
+/// Secrets a project file must never provide
+const SECRETS: [&str; 2] = ["ai.api_key", "quantum.api_key"];
+
+/// Keys only the user config may set, besides the secrets: the account,
+/// where requests go, how they are secured and whether conversations are
+/// written to disk
+const USER_ONLY: [&str; 11] = [
+    "version",
+    "user",
+    "accounts",
+    "api_url",
+    "ai.base_url",
+    "ai.log_requests",
+    "ai.transcript",
+    "security",
+    "network",
+    "db",
+    "telemetry",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    Default,
+    User(PathBuf),
+    Project(PathBuf),
+    Env(&'static str),
+}
+
+impl Source {
+    pub fn label(&self) -> String {
+        match self {
+            Source::Default => "default".to_string(),
+            Source::User(path) | Source::Project(path) => path.display().to_string(),
+            Source::Env(var) => format!("${}", var),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Layers {
+    /// The .qhub.toml in effect, if any
+    pub project: Option<PathBuf>,
+    sources: BTreeMap<String, Source>,
+    /// The user file as read
+    user: Table,
+    /// Values the project file or environment put in place, as loaded
+    overlaid: BTreeMap<String, Value>,
+    /// Keys dropped from the project file
+    pub warnings: Vec<String>,
+    /// Keys no setting reads, for `qhub config validate`
+    pub unknown: Vec<Problem>,
+}
+
+impl Layers {
+    pub fn new(user_path: &Path, user: Table) -> Self {
+        let mut layers = Self::default();
+        for key in leaf_keys(&user) {
+            layers.sources.insert(key, Source::User(user_path.to_path_buf()));
+        }
+        layers.user = user;
+        layers
+    }
+
+    /// Record a project table, already stripped of user-only keys
+    pub fn add_project(&mut self, path: &Path, project: &Table) {
+        for key in leaf_keys(project) {
+            self.sources.insert(key, Source::Project(path.to_path_buf()));
+        }
+        self.project = Some(path.to_path_buf());
+    }
+
+    pub fn add_env(&mut self, var: &'static str, key: &str) {
+        self.sources.insert(key.to_string(), Source::Env(var));
+    }
+
+    /// Remember what the project and environment set, from the loaded config
+    pub fn record_overlaid(&mut self, effective: &Table) {
+        for (key, source) in &self.sources {
+            if matches!(source, Source::Project(_) | Source::Env(_)) {
+                if let Some(value) = get(effective, key) {
+                    self.overlaid.insert(key.clone(), value.clone());
+                }
+            }
+        }
+    }
+
+    /// Where `key` (e.g. `ai.model`) got its value
+    pub fn source(&self, key: &str) -> Source {
+        let mut key = key;
+        loop {
+            if let Some(source) = self.sources.get(key) {
+                return source.clone();
+            }
+            match key.rsplit_once('.') {
+                Some((parent, _)) => key = parent,
+                None => return Source::Default,
+            }
+        }
+    }
+
+    /// Keys the project file sets
+    pub fn project_keys(&self) -> Vec<&str> {
+        self.sources
+            .iter()
+            .filter(|(_, source)| matches!(source, Source::Project(_)))
+            .map(|(key, _)| key.as_str())
+            .collect()
+    }
+
+    /// Swap values the project or environment provided, and that were not
+    /// changed since, for the user's own (or the default when they had none)
+    pub fn restore_user_values(&self, table: &mut Table, defaults: &Table) {
+        for (key, overlaid) in &self.overlaid {
+            if get(table, key) != Some(overlaid) {
+                continue;
+            }
+            let own = get(&self.user, key).or_else(|| get(defaults, key)).cloned();
+            set(table, key, own);
+        }
+    }
+}
+
+/// The nearest .qhub.toml in `start` or a directory above it
+pub fn find_project(start: &Path) -> Option<PathBuf> {
+    start.ancestors().map(|dir| dir.join(PROJECT_FILE)).find(|path| path.is_file())
+}
+
+/// Drop what a project file may not set, with a warning for each
+pub fn strip_user_only(project: &mut Table, path: &Path) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for key in SECRETS {
+        if get(project, key).is_some() {
+            set(project, key, None);
+            warnings.push(format!(
+                "Ignored {} in {}: API keys are only read from your own config, the environment or the keychain. \
+                 Remove it from the project file and revoke it if the file was shared.",
+                key,
+                path.display()
+            ));
+        }
+    }
+    for key in USER_ONLY {
+        if get(project, key).is_some() {
+            set(project, key, None);
+            warnings.push(format!("Ignored {} in {}: it can only be set in ~/.qhub/config.toml", key, path.display()));
+        }
+    }
+    warnings
+}
+
+/// Merge `overlay` into `base`, table by table; anything else is replaced
+pub fn merge(base: &mut Table, overlay: Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(overlay)) => merge(base, overlay),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Dotted paths of every non-table value
+pub fn leaf_keys(table: &Table) -> Vec<String> {
+    let mut keys = Vec::new();
+    for (key, value) in table {
+        match value {
+            Value::Table(inner) => keys.extend(leaf_keys(inner).into_iter().map(|k| format!("{}.{}", key, k))),
+            _ => keys.push(key.clone()),
+        }
+    }
+    keys
+}
+
+pub fn get<'a>(table: &'a Table, key: &str) -> Option<&'a Value> {
+    match key.split_once('.') {
+        Some((head, rest)) => get(table.get(head)?.as_table()?, rest),
+        None => table.get(key),
+    }
+}
+
+/// Set or (with None) remove `key`, creating tables on the way
+pub fn set(table: &mut Table, key: &str, value: Option<Value>) {
+    match key.split_once('.') {
+        Some((head, rest)) => {
+            if value.is_none() && !table.contains_key(head) {
+                return;
+            }
+            let inner = table.entry(head).or_insert_with(|| Value::Table(Table::new()));
+            if let Value::Table(inner) = inner {
+                set(inner, rest, value);
+            }
+        }
+        None => match value {
+            Some(value) => {
+                table.insert(key.to_string(), value);
+            }
+            None => {
+                table.remove(key);
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(text: &str) -> Table {
+        toml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn test_find_project_walks_up() {
+        let root = std::env::temp_dir().join(format!("qhub-project-{}", uuid::Uuid::new_v4()));
+        let nested = root.join("vqe").join("runs");
+        std::fs::create_dir_all(&nested).unwrap();
+        assert_eq!(find_project(&nested), None);
+
+        std::fs::write(root.join(PROJECT_FILE), "").unwrap();
+        assert_eq!(find_project(&nested), Some(root.join(PROJECT_FILE)));
+        std::fs::write(root.join("vqe").join(PROJECT_FILE), "").unwrap();
+        assert_eq!(find_project(&nested), Some(root.join("vqe").join(PROJECT_FILE)));
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_merge_is_per_key() {
+        let mut base = table("[ai]\nmodel = \"a\"\nmax_tokens = 100\n[ui]\ndensity = \"compact\"");
+        merge(&mut base, table("[ai]\nmodel = \"b\"\n[quantum]\ndefault_backend = \"ibm_kyiv\""));
+        assert_eq!(get(&base, "ai.model").unwrap().as_str(), Some("b"));
+        assert_eq!(get(&base, "ai.max_tokens").unwrap().as_integer(), Some(100));
+        assert_eq!(get(&base, "ui.density").unwrap().as_str(), Some("compact"));
+        assert_eq!(get(&base, "quantum.default_backend").unwrap().as_str(), Some("ibm_kyiv"));
+        assert_eq!(leaf_keys(&base), vec!["ai.max_tokens", "ai.model", "quantum.default_backend", "ui.density"]);
+    }
+
+    #[test]
+    fn test_strip_user_only() {
+        let path = Path::new("/work/vqe/.qhub.toml");
+        let mut project = table(
+            "api_url = \"https://evil.example\"\n\
+             [ai]\nmodel = \"b\"\napi_key = \"sk-leaked\"\nbase_url = \"https://evil.example/v1\"\nlog_requests = true\ntranscript = true\n\
+             [quantum]\ndefault_backend = \"ibm_kyiv\"\napi_key = \"leaked\"\n\
+             [user]\ntier = \"enterprise\"",
+        );
+        let warnings = strip_user_only(&mut project, path);
+        assert_eq!(leaf_keys(&project), vec!["ai.model", "quantum.default_backend"]);
+        assert_eq!(warnings.len(), 7);
+        for key in ["ai.log_requests", "ai.transcript"] {
+            assert!(warnings.iter().any(|w| w.starts_with(&format!("Ignored {} in /work/vqe/.qhub.toml", key))));
+        }
+        assert!(warnings[0].starts_with("Ignored ai.api_key in /work/vqe/.qhub.toml: API keys"));
+        assert!(warnings.iter().all(|w| !w.contains("sk-leaked")));
+    }
+}
@@ -0,0 +1,220 @@
+// Every problem with a config file at once
+//
+// Deserializing stops at the first bad value, which turned fixing a config
+// into a loop of edit, rerun, find the next one. Loading collects problems
+// instead: values of the wrong type, values out of range and keys no setting
+// reads, each with its dotted key and the file and line it came from. Lines
+// are found by scanning the file for the key, since values are only checked
+// once the layers are merged and the spans are gone by then. Unknown keys
+// alone only warn, so a config written for a newer qhub still opens;
+// `qhub config validate` counts them with the rest.
+
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use toml::{Table, Value};
+
+use super::layers::{self, Layers, Source};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Location {
+    File { path: PathBuf, line: Option<usize> },
+    Env(&'static str),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    /// Dotted, e.g. `ui.tick_ms`
+    pub key: String,
+    pub message: String,
+    pub location: Option<Location>,
+}
+
+impl Problem {
+    pub fn new(key: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { key: key.into(), message: message.into(), location: None }
+    }
+
+    /// `self`, placed on its line of the file at `path`
+    fn in_file(mut self, path: &Path, text: &str) -> Self {
+        let line = line_of(text, &self.key);
+        self.location = Some(Location::File { path: path.to_path_buf(), line });
+        self
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.location {
+            Some(Location::File { path, line: Some(line) }) => write!(f, "{}:{}: ", path.display(), line)?,
+            Some(Location::File { path, line: None }) => write!(f, "{}: ", path.display())?,
+            Some(Location::Env(var)) => write!(f, "${}: ", var)?,
+            None => {}
+        }
+        write!(f, "{}: {}", self.key, self.message)
+    }
+}
+
+/// What `Config::load` fails with when values are wrong, listing them all
+#[derive(Debug, Error)]
+pub struct InvalidConfig(pub Vec<Problem>);
+
+impl fmt::Display for InvalidConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.len() {
+            1 => write!(f, "1 problem in the config:")?,
+            n => write!(f, "{} problems in the config:", n)?,
+        }
+        for problem in &self.0 {
+            write!(f, "\n  {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+/// A config file as read, for placing problems in it
+pub struct File<'a> {
+    pub path: &'a Path,
+    pub text: &'a str,
+    pub table: &'a Table,
+}
+
+/// Set file and line (or variable) on each of `problems`, from where its
+/// key got its value
+pub fn locate(problems: &mut [Problem], layers: &Layers, files: &[File]) {
+    for problem in problems {
+        problem.location = match layers.source(&problem.key) {
+            Source::User(path) | Source::Project(path) => {
+                let line = files.iter().find(|f| f.path == path).and_then(|f| line_of(f.text, &problem.key));
+                Some(Location::File { path, line })
+            }
+            Source::Env(var) => Some(Location::Env(var)),
+            Source::Default => None,
+        };
+    }
+}
+
+/// Values in `file` that `T` cannot take, each tried alone on top of
+/// `defaults` so that one bad value does not hide the next
+pub fn type_problems<T: DeserializeOwned>(defaults: &Table, file: &File) -> Vec<Problem> {
+    let mut keys = layers::leaf_keys(file.table);
+    // A version 1 [user] table only makes sense whole
+    for key in &mut keys {
+        if key.starts_with("user.") {
+            *key = "user".to_string();
+        }
+    }
+    keys.dedup();
+
+    let mut problems = Vec::new();
+    for key in keys {
+        let mut candidate = defaults.clone();
+        layers::set(&mut candidate, &key, layers::get(file.table, &key).cloned());
+        if let Err(e) = Value::Table(candidate).try_into::<T>() {
+            problems.push(Problem::new(key, e.message().trim().to_string()).in_file(file.path, file.text));
+        }
+    }
+    problems
+}
+
+/// Keys in `file` that no field of `T` reads
+pub fn unknown_keys<T: DeserializeOwned>(defaults: &Table, file: &File) -> Vec<Problem> {
+    let mut merged = defaults.clone();
+    layers::merge(&mut merged, file.table.clone());
+    let mut keys = Vec::new();
+    // Deserializing again with a callback is the lint pass; errors were reported already
+    let _ = serde_ignored::deserialize::<_, _, T>(Value::Table(merged), |path| {
+        keys.push(path.to_string().replace("?.", ""));
+    });
+    keys.into_iter()
+        .map(|key| Problem::new(key, "not a setting qhub knows; it is ignored").in_file(file.path, file.text))
+        .collect()
+}
+
+/// The 1-based line `key` is set on in TOML `text`, for the usual layouts:
+/// a `[table]` header then `name = value`, or a dotted name
+fn line_of(text: &str, key: &str) -> Option<usize> {
+    let mut table = String::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix('[') {
+            let header = header.trim_start_matches('[');
+            table = header.split(']').next().unwrap_or_default().trim().to_string();
+            if table == key {
+                return Some(index + 1);
+            }
+            continue;
+        }
+        let Some((name, _)) = line.split_once('=') else {
+            continue;
+        };
+        let name = name.trim().trim_matches('"');
+        let path = if table.is_empty() { name.to_string() } else { format!("{}.{}", table, name) };
+        if path == key {
+            return Some(index + 1);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Settings {
+        ui: Ui,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Ui {
+        scroll_speed: u16,
+        density: String,
+    }
+
+    fn defaults() -> Table {
+        toml::from_str("[ui]\nscroll_speed = 3\ndensity = \"comfortable\"\n").unwrap()
+    }
+
+    #[test]
+    fn test_every_bad_value_is_found_on_its_line() {
+        let text = "# mine\n[ui]\nscroll_speed = \"fast\"\ndensity = 2\ncolour = \"blue\"\n";
+        let table: Table = toml::from_str(text).unwrap();
+        let file = File { path: Path::new("/home/q/.qhub/config.toml"), text, table: &table };
+
+        let problems = type_problems::<Settings>(&defaults(), &file);
+        assert_eq!(problems.len(), 2);
+        assert_eq!(problems[0].key, "ui.density");
+        assert_eq!(problems[1].to_string(), "/home/q/.qhub/config.toml:3: ui.scroll_speed: invalid type: string \"fast\", expected u16");
+
+        let unknown = unknown_keys::<Settings>(&defaults(), &file);
+        assert_eq!(unknown.len(), 1);
+        assert_eq!(unknown[0].to_string(), "/home/q/.qhub/config.toml:5: ui.colour: not a setting qhub knows; it is ignored");
+    }
+
+    #[test]
+    fn test_line_of() {
+        let text = "version = 2\n\n[db.maintenance] # nightly\ninterval_hours = 0\n[[accounts]]\nemail = \"a@b.c\"\n";
+        assert_eq!(line_of(text, "version"), Some(1));
+        assert_eq!(line_of(text, "db.maintenance.interval_hours"), Some(4));
+        assert_eq!(line_of(text, "db.maintenance"), Some(3));
+        assert_eq!(line_of(text, "ui.density"), None);
+        assert_eq!(line_of("ui.density = \"x\"", "ui.density"), Some(1));
+    }
+
+    #[test]
+    fn test_invalid_config_lists_everything() {
+        let error = InvalidConfig(vec![
+            Problem { location: Some(Location::Env("QHUB_AI_PROVIDER")), ..Problem::new("ai.provider", "bad") },
+            Problem::new("ui.tick_ms", "must be between 10 and 500, not 5"),
+        ]);
+        assert_eq!(
+            error.to_string(),
+            "2 problems in the config:\n  $QHUB_AI_PROVIDER: ai.provider: bad\n  ui.tick_ms: must be between 10 and 500, not 5"
+        );
+    }
+}
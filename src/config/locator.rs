@@ -0,0 +1,100 @@
+// Which config file an instance uses
+//
+// Normally ~/.qhub/config.toml. `--config <path>`, or QHUB_CONFIG when the
+// flag is not given, points an instance somewhere else, so several can run
+// side by side (integration tests, demos) without sharing settings. The
+// choice is made once at startup and handed to whatever loads the config;
+// the loaded `Config` keeps it, so saving writes back to the same file.
+// Only the config file moves: sessions, caches and logs stay in ~/.qhub.
+
+use anyhow::{bail, Context, Result};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigLocator {
+    /// None for the default location in the home directory
+    explicit: Option<PathBuf>,
+}
+
+impl ConfigLocator {
+    /// `--config` wins over QHUB_CONFIG; neither means the default. Relative
+    /// paths are taken from the working directory.
+    pub fn resolve(flag: Option<PathBuf>, env: Option<OsString>) -> Result<Self> {
+        let Some(path) = flag.or_else(|| env.filter(|v| !v.is_empty()).map(PathBuf::from)) else {
+            return Ok(Self::default());
+        };
+        Self::at(path)
+    }
+
+    /// A config file at `path`, relative to the working directory unless absolute
+    pub fn at(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let path = if path.is_absolute() {
+            path
+        } else {
+            std::env::current_dir().context("Could not read the working directory")?.join(path)
+        };
+        Ok(Self { explicit: Some(path) })
+    }
+
+    /// The config file this instance reads and writes
+    pub fn path(&self) -> Result<PathBuf> {
+        match &self.explicit {
+            Some(path) => Ok(path.clone()),
+            None => super::Config::config_path(),
+        }
+    }
+
+    pub fn exists(&self) -> bool {
+        self.path().is_ok_and(|p| p.exists())
+    }
+
+    /// The directory `path` goes in, ready for writing. The default one is
+    /// created; a directory given with --config or QHUB_CONFIG has to exist.
+    pub(super) fn prepare_dir(&self, path: &Path) -> Result<()> {
+        let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) else {
+            return Ok(());
+        };
+        if dir.exists() {
+            return Ok(());
+        }
+        if self.explicit.is_some() {
+            bail!(
+                "Cannot save the config to {}: the directory {} does not exist",
+                path.display(),
+                dir.display()
+            );
+        }
+        std::fs::create_dir_all(dir).context("Failed to create config directory")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flag_wins_over_env() {
+        let flag = ConfigLocator::resolve(Some("/tmp/a.toml".into()), Some("/tmp/b.toml".into())).unwrap();
+        assert_eq!(flag.path().unwrap(), PathBuf::from("/tmp/a.toml"));
+        let env = ConfigLocator::resolve(None, Some("/tmp/b.toml".into())).unwrap();
+        assert_eq!(env.path().unwrap(), PathBuf::from("/tmp/b.toml"));
+        assert_eq!(ConfigLocator::resolve(None, Some("".into())).unwrap(), ConfigLocator::default());
+    }
+
+    #[test]
+    fn test_relative_paths_use_working_directory() {
+        let locator = ConfigLocator::resolve(Some("demo/config.toml".into()), None).unwrap();
+        assert_eq!(locator.path().unwrap(), std::env::current_dir().unwrap().join("demo/config.toml"));
+    }
+
+    #[test]
+    fn test_missing_directory_is_an_error() {
+        let dir = std::env::temp_dir().join(format!("qhub-locator-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("config.toml");
+        let error = ConfigLocator::at(&path).unwrap().prepare_dir(&path).unwrap_err().to_string();
+        assert!(error.contains("does not exist"), "{}", error);
+        assert!(!dir.exists());
+    }
+}
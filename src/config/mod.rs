@@ -1,3 +1,13 @@
+pub mod accounts;
+pub mod audit;
+pub mod env;
+pub mod layers;
+pub mod lint;
+pub mod locator;
+pub mod qiskit;
+pub mod secrets;
 pub mod settings;
+pub mod tiers;
 
+pub use locator::ConfigLocator;
 pub use settings::Config;
@@ -0,0 +1,163 @@
+// IBM Quantum accounts saved by Qiskit
+//
+// `QiskitRuntimeService.save_account()` writes ~/.qiskit/qiskit-ibm.json: an
+// object of named accounts, each with a channel, token and optionally an
+// instance. `qhub quantum import-qiskit` and the setup wizard read it so
+// users don't have to paste a token qhub could find itself. Tokens are never
+// shown; `Debug` redacts them.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, PartialEq)]
+pub struct QiskitAccount {
+    pub name: String,
+    /// `ibm_quantum`, `ibm_cloud` or `ibm_quantum_platform`
+    pub channel: String,
+    pub token: String,
+    /// hub/group/project, or a CRN on IBM Cloud
+    pub instance: Option<String>,
+    pub is_default: bool,
+}
+
+impl fmt::Debug for QiskitAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QiskitAccount")
+            .field("name", &self.name)
+            .field("channel", &self.channel)
+            .field("token", &"<hidden>")
+            .field("instance", &self.instance)
+            .field("is_default", &self.is_default)
+            .finish()
+    }
+}
+
+impl QiskitAccount {
+    /// "default-ibm-quantum (ibm_quantum, instance ibm-q/open/main)"
+    pub fn describe(&self) -> String {
+        match &self.instance {
+            Some(instance) => format!("{} ({}, instance {})", self.name, self.channel, instance),
+            None => format!("{} ({})", self.name, self.channel),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RawAccount {
+    #[serde(default)]
+    channel: Option<String>,
+    token: Option<String>,
+    #[serde(default)]
+    instance: Option<String>,
+    #[serde(default)]
+    is_default_account: bool,
+}
+
+/// ~/.qiskit/qiskit-ibm.json
+pub fn default_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".qiskit").join("qiskit-ibm.json"))
+}
+
+/// Accounts with a token in Qiskit's account file, sorted by name
+pub fn parse(json: &str) -> Result<Vec<QiskitAccount>> {
+    let raw: BTreeMap<String, RawAccount> =
+        serde_json::from_str(json).context("not a Qiskit account file")?;
+    Ok(raw
+        .into_iter()
+        .filter_map(|(name, account)| {
+            let token = account.token.filter(|t| !t.trim().is_empty())?;
+            // Older files only say which channel the default account is for in its name
+            let is_default = account.is_default_account || name.starts_with("default-");
+            Some(QiskitAccount {
+                channel: account.channel.unwrap_or_else(|| "ibm_quantum".to_string()),
+                token,
+                instance: account.instance.filter(|i| !i.is_empty()),
+                is_default,
+                name,
+            })
+        })
+        .collect())
+}
+
+/// Accounts in `path`, or none when the file does not exist
+pub fn load(path: &Path) -> Result<Vec<QiskitAccount>> {
+    match std::fs::read_to_string(path) {
+        Ok(json) => parse(&json).with_context(|| format!("Failed to read {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// The account to import: `name` when given, else the only or the default
+/// one. Err lists the choices when that is ambiguous.
+pub fn select<'a>(accounts: &'a [QiskitAccount], name: Option<&str>) -> Result<&'a QiskitAccount, String> {
+    let names = || accounts.iter().map(|a| a.name.as_str()).collect::<Vec<_>>().join(", ");
+    if let Some(name) = name {
+        return accounts
+            .iter()
+            .find(|a| a.name == name)
+            .ok_or_else(|| format!("No Qiskit account named '{}'. Accounts: {}", name, names()));
+    }
+    match accounts {
+        [] => Err("No Qiskit accounts with a token found.".to_string()),
+        [only] => Ok(only),
+        _ => {
+            let defaults: Vec<&QiskitAccount> = accounts.iter().filter(|a| a.is_default).collect();
+            match defaults[..] {
+                [default] => Ok(default),
+                _ => Err(format!("Several Qiskit accounts saved; choose one of: {}", names())),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE: &str = r#"{
+        "default-ibm-quantum": {
+            "channel": "ibm_quantum",
+            "token": "abc123def456ghi789jkl012",
+            "url": "https://auth.quantum-computing.ibm.com/api",
+            "instance": "ibm-q/open/main",
+            "private_endpoint": false
+        },
+        "work": {
+            "channel": "ibm_cloud",
+            "token": "zyx987wvu654tsr321qpo000",
+            "instance": "crn:v1:bluemix:public:quantum-computing:us-east:a/1::"
+        },
+        "broken": { "channel": "ibm_quantum" }
+    }"#;
+
+    #[test]
+    fn test_parse() {
+        let accounts = parse(FILE).unwrap();
+        let names: Vec<&str> = accounts.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, vec!["default-ibm-quantum", "work"]);
+        assert_eq!(accounts[0].instance.as_deref(), Some("ibm-q/open/main"));
+        assert!(accounts[0].is_default);
+        assert!(!accounts[1].is_default);
+        assert_eq!(accounts[1].describe(), "work (ibm_cloud, instance crn:v1:bluemix:public:quantum-computing:us-east:a/1::)");
+        assert!(!format!("{:?}", accounts[0]).contains("abc123"));
+        assert!(parse("[]").is_err());
+    }
+
+    #[test]
+    fn test_select() {
+        let accounts = parse(FILE).unwrap();
+        assert_eq!(select(&accounts, None).unwrap().name, "default-ibm-quantum");
+        assert_eq!(select(&accounts, Some("work")).unwrap().name, "work");
+        assert!(select(&accounts, Some("home")).unwrap_err().contains("default-ibm-quantum, work"));
+
+        let mut no_default = accounts.clone();
+        no_default[0].is_default = false;
+        assert!(select(&no_default, None).unwrap_err().contains("choose one of"));
+        assert_eq!(select(&no_default[1..], None).unwrap().name, "work");
+        assert!(select(&[], None).is_err());
+    }
+}
@@ -0,0 +1,258 @@
+use anyhow::{Context, Result};
+
+use super::layers::Source;
+use super::Config;
+
+/// Service name used for entries in the OS keychain
+pub(super) const KEYCHAIN_SERVICE: &str = "qhub";
+
+/// API keys that can be managed from inside the app
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ApiKeyKind {
+    Ai,
+    Quantum,
+}
+
+impl ApiKeyKind {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "ai" => Some(ApiKeyKind::Ai),
+            "quantum" | "ibm" => Some(ApiKeyKind::Quantum),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ApiKeyKind::Ai => "AI API key",
+            ApiKeyKind::Quantum => "IBM Quantum token",
+        }
+    }
+
+    /// Environment variable that overrides this key
+    pub fn env_var(&self) -> &'static str {
+        match self {
+            ApiKeyKind::Ai => "CLOUDFLARE_AI_TOKEN",
+            ApiKeyKind::Quantum => "IBM_QUANTUM_TOKEN",
+        }
+    }
+
+    fn keychain_account(&self) -> &'static str {
+        match self {
+            ApiKeyKind::Ai => "ai_api_key",
+            ApiKeyKind::Quantum => "quantum_api_key",
+        }
+    }
+}
+
+/// Where a configured key was found
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeySource {
+    Environment,
+    Keychain,
+    ConfigFile,
+}
+
+impl KeySource {
+    pub fn label(&self) -> &'static str {
+        match self {
+            KeySource::Environment => "environment",
+            KeySource::Keychain => "keychain",
+            KeySource::ConfigFile => "config file",
+        }
+    }
+}
+
+fn keychain_entry(kind: ApiKeyKind) -> Result<keyring::Entry> {
+    keyring::Entry::new(KEYCHAIN_SERVICE, kind.keychain_account())
+        .context("Failed to open keychain entry")
+}
+
+/// Read a key from the OS keychain, if present
+pub fn keychain_get(kind: ApiKeyKind) -> Option<String> {
+    keychain_entry(kind).ok()?.get_password().ok()
+}
+
+impl Config {
+    fn key_slot(&mut self, kind: ApiKeyKind) -> &mut Option<String> {
+        match kind {
+            ApiKeyKind::Ai => &mut self.ai.api_key,
+            ApiKeyKind::Quantum => &mut self.quantum.api_key,
+        }
+    }
+
+    /// Store an API key in the keychain (when enabled) or the config file, and save
+    pub fn set_api_key(&mut self, kind: ApiKeyKind, key: String) -> Result<KeySource> {
+        let source = if self.security.use_keychain {
+            keychain_entry(kind)?
+                .set_password(&key)
+                .context("Failed to store key in keychain")?;
+            // The keychain is the only copy - never write it to config.toml
+            *self.key_slot(kind) = None;
+            KeySource::Keychain
+        } else {
+            *self.key_slot(kind) = Some(key);
+            KeySource::ConfigFile
+        };
+
+        self.save()?;
+        Ok(source)
+    }
+
+    /// Remove an API key from both the keychain and the config file
+    pub fn clear_api_key(&mut self, kind: ApiKeyKind) -> Result<()> {
+        if self.security.use_keychain {
+            if let Ok(entry) = keychain_entry(kind) {
+                // A missing entry is fine - there is nothing to clear
+                let _ = entry.delete_credential();
+            }
+        }
+        *self.key_slot(kind) = None;
+        self.save()
+    }
+
+    /// With `security.use_keychain` on, move the keys and session tokens
+    /// still in config.toml into the keychain, as happens at startup; names
+    /// what moved. Values from a project file or the environment stay put.
+    pub fn move_secrets_to_keychain(&mut self) -> Result<Vec<String>> {
+        let mut moved = Vec::new();
+        if !self.security.use_keychain {
+            return Ok(moved);
+        }
+        for (kind, key) in [(ApiKeyKind::Ai, "ai.api_key"), (ApiKeyKind::Quantum, "quantum.api_key")] {
+            if !matches!(self.layers.source(key), Source::User(_)) {
+                continue;
+            }
+            if let Some(value) = self.key_slot(kind).clone() {
+                keychain_entry(kind)?.set_password(&value).context("Failed to store key in keychain")?;
+                *self.key_slot(kind) = None;
+                moved.push(format!("the {}", kind.label()));
+            }
+        }
+        if matches!(self.layers.source("accounts"), Source::User(_)) {
+            let plain: Vec<String> =
+                self.accounts.iter().filter(|a| a.token.is_some()).map(|a| a.email.clone()).collect();
+            for email in plain {
+                self.move_session_to_keychain(&email)?;
+                moved.push(format!("the session for {}", email));
+            }
+        }
+        if !moved.is_empty() {
+            self.save()?;
+        }
+        Ok(moved)
+    }
+
+    /// Report where a key is currently configured, without revealing it
+    pub fn api_key_source(&self, kind: ApiKeyKind) -> Option<KeySource> {
+        self.resolve_api_key(kind, |var| std::env::var(var).ok()).map(|(_, source)| source)
+    }
+
+    /// The key requests use and where it came from: the config (which the
+    /// environment variable already overrides at load), then the environment,
+    /// then the keychain
+    pub(super) fn resolve_api_key(
+        &self,
+        kind: ApiKeyKind,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Option<(String, KeySource)> {
+        let from_env = env(kind.env_var());
+        let slot = match kind {
+            ApiKeyKind::Ai => &self.ai.api_key,
+            ApiKeyKind::Quantum => &self.quantum.api_key,
+        };
+        if let Some(key) = slot {
+            let source = if from_env.as_ref() == Some(key) { KeySource::Environment } else { KeySource::ConfigFile };
+            return Some((key.clone(), source));
+        }
+        if let Some(key) = from_env {
+            return Some((key, KeySource::Environment));
+        }
+        if self.security.use_keychain {
+            return keychain_get(kind).map(|key| (key, KeySource::Keychain));
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_source_matches_the_key_used() {
+        let env = |var: &str| (var == "CLOUDFLARE_AI_TOKEN").then(|| "env-key".to_string());
+        let mut config = Config::default();
+        config.security.use_keychain = false;
+
+        // Saved with /keys after loading: the file's key is the one sent
+        config.ai.api_key = Some("file-key".to_string());
+        assert_eq!(config.resolve_api_key(ApiKeyKind::Ai, env), Some(("file-key".to_string(), KeySource::ConfigFile)));
+
+        // The variable replaced the file's key when the config was loaded
+        config.ai.api_key = Some("env-key".to_string());
+        assert_eq!(config.resolve_api_key(ApiKeyKind::Ai, env), Some(("env-key".to_string(), KeySource::Environment)));
+
+        config.ai.api_key = None;
+        assert_eq!(config.resolve_api_key(ApiKeyKind::Ai, env), Some(("env-key".to_string(), KeySource::Environment)));
+        assert_eq!(config.resolve_api_key(ApiKeyKind::Quantum, env), None);
+    }
+}
+
+/// A keychain kept in memory, so tests never read or overwrite the real one
+#[cfg(test)]
+pub mod test_keychain {
+    use keyring::credential::{Credential, CredentialApi, CredentialBuilderApi};
+    use std::any::Any;
+    use std::collections::BTreeMap;
+    use std::sync::{Mutex, Once};
+
+    static STORE: Mutex<BTreeMap<(String, String), Vec<u8>>> = Mutex::new(BTreeMap::new());
+
+    #[derive(Debug)]
+    struct Slot(String, String);
+
+    impl Slot {
+        fn key(&self) -> (String, String) {
+            (self.0.clone(), self.1.clone())
+        }
+    }
+
+    impl CredentialApi for Slot {
+        fn set_secret(&self, secret: &[u8]) -> keyring::Result<()> {
+            STORE.lock().unwrap().insert(self.key(), secret.to_vec());
+            Ok(())
+        }
+
+        fn get_secret(&self) -> keyring::Result<Vec<u8>> {
+            STORE.lock().unwrap().get(&self.key()).cloned().ok_or(keyring::Error::NoEntry)
+        }
+
+        fn delete_credential(&self) -> keyring::Result<()> {
+            STORE.lock().unwrap().remove(&self.key()).map(|_| ()).ok_or(keyring::Error::NoEntry)
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    #[derive(Debug)]
+    struct Builder;
+
+    impl CredentialBuilderApi for Builder {
+        fn build(&self, _: Option<&str>, service: &str, user: &str) -> keyring::Result<Box<Credential>> {
+            Ok(Box::new(Slot(service.to_string(), user.to_string())))
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    /// Send keychain reads and writes to memory for the rest of the run
+    pub fn install() {
+        static INSTALLED: Once = Once::new();
+        INSTALLED.call_once(|| keyring::set_default_credential_builder(Box::new(Builder)));
+    }
+}
@@ -1,9 +1,18 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::Once;
 
-const CONFIG_VERSION: u32 = 1;
+use super::layers::{self, Layers};
+use super::lint::{self, InvalidConfig, Problem};
+use super::locator::ConfigLocator;
+use super::secrets::ApiKeyKind;
+
+/// 2: `[user]` became the `[[accounts]]` list
+pub(super) const CONFIG_VERSION: u32 = 2;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
@@ -11,13 +20,32 @@ pub struct Config {
     pub version: u32,
     #[serde(default = "default_api_url")]
     pub api_url: String,
-    pub user: Option<UserConfig>,
+    /// Saved accounts; see `Config::user` for the active one
+    #[serde(default)]
+    pub accounts: Vec<UserConfig>,
+    /// The single account of version 1 files, moved into `accounts` on load
+    #[serde(default, rename = "user", skip_serializing)]
+    pub(super) legacy_user: Option<UserConfig>,
     #[serde(default)]
     pub ai: AiConfig,
     #[serde(default)]
     pub quantum: QuantumConfig,
     #[serde(default)]
     pub ui: UiConfig,
+    #[serde(default)]
+    pub security: SecurityConfig,
+    #[serde(default)]
+    pub db: DbConfig,
+    #[serde(default)]
+    pub network: NetworkConfig,
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+    /// Where each value came from; not part of the file
+    #[serde(skip)]
+    pub layers: Layers,
+    /// The file this was loaded from, and `save` writes
+    #[serde(skip)]
+    locator: ConfigLocator,
 }
 
 fn default_version() -> u32 {
@@ -45,7 +73,7 @@ fn default_api_url() -> String {
     // Priority 4: Debug builds default to local development
     #[cfg(debug_assertions)]
     {
-        return "http://localhost:8787".to_string();
+        "http://localhost:8787".to_string()
     }
 }
 
@@ -54,10 +82,17 @@ impl Default for Config {
         Self {
             version: CONFIG_VERSION,
             api_url: default_api_url(),
-            user: None,
+            accounts: Vec::new(),
+            legacy_user: None,
             ai: AiConfig::default(),
             quantum: QuantumConfig::default(),
             ui: UiConfig::default(),
+            security: SecurityConfig::default(),
+            db: DbConfig::default(),
+            network: NetworkConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            layers: Layers::default(),
+            locator: ConfigLocator::default(),
         }
     }
 }
@@ -65,8 +100,14 @@ impl Default for Config {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct UserConfig {
     pub email: String,
+    /// Session token, unless it is kept in the keychain
     pub token: Option<String>,
     pub tier: String,
+    #[serde(default)]
+    pub active: bool,
+    /// The session token is in the keychain entry for this account
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub keychain: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -77,6 +118,37 @@ pub struct AiConfig {
     pub model: String,
     #[serde(default = "default_max_tokens")]
     pub max_tokens: u32,
+    /// OpenAI-compatible API base; `/chat/completions` is appended
+    #[serde(default = "default_ai_base_url")]
+    pub base_url: String,
+    /// Model context window in tokens; known models are detected by name
+    #[serde(default)]
+    pub context_window: Option<usize>,
+    /// Append every prompt and response to ~/.qhub/files/transcript.jsonl
+    #[serde(default)]
+    pub transcript: bool,
+    /// Write every request and raw response to ~/.qhub/cache/ai_requests.jsonl
+    #[serde(default)]
+    pub log_requests: bool,
+    /// Keep replies and answer an identical request from them
+    #[serde(default)]
+    pub cache: CacheMode,
+    /// Prompts sent while a request waits out a rate limit
+    #[serde(default)]
+    pub rate_limit_queue: QueuePolicy,
+    /// Tell the model which quantum backends are available after /backends
+    #[serde(default = "default_true")]
+    pub inject_backend_context: bool,
+    /// Extra instructions appended to the built-in system prompt, e.g. a
+    /// project's conventions
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    /// Sampling temperature; unset leaves it to the provider
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    /// Nucleus sampling cutoff; unset leaves it to the provider
+    #[serde(default)]
+    pub top_p: Option<f32>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -84,6 +156,66 @@ pub struct QuantumConfig {
     pub provider: String,
     pub api_key: Option<String>,
     pub default_backend: Option<String>,
+    /// IBM Quantum channel, e.g. `ibm_quantum` or `ibm_cloud`
+    #[serde(default)]
+    pub channel: Option<String>,
+    /// hub/group/project (or a CRN on IBM Cloud) jobs are billed to
+    #[serde(default)]
+    pub instance: Option<String>,
+    /// Show an estimate and ask before running on hardware
+    #[serde(default = "default_true")]
+    pub confirm_hardware: bool,
+    /// Prices per backend name, for estimates
+    #[serde(default)]
+    pub pricing: HashMap<String, BackendPricing>,
+    /// Row order of exported counts
+    #[serde(default)]
+    pub export_order: CountOrder,
+    /// Joins the bits of separate classical registers in exported counts
+    #[serde(default = "default_register_separator")]
+    pub register_separator: String,
+}
+
+fn default_register_separator() -> String {
+    " ".to_string()
+}
+
+/// Row order of `/export counts` and `qhub jobs export`
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CountOrder {
+    /// By bit string
+    #[default]
+    Lexicographic,
+    /// Most frequent first
+    Count,
+}
+
+impl std::str::FromStr for CountOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "lexicographic" => Ok(CountOrder::Lexicographic),
+            "count" => Ok(CountOrder::Count),
+            _ => Err(format!("unknown order '{}'; use lexicographic or count", s)),
+        }
+    }
+}
+
+/// What a backend charges, in `unit`s
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BackendPricing {
+    #[serde(default)]
+    pub per_second: f64,
+    #[serde(default)]
+    pub per_shot: f64,
+    #[serde(default = "default_price_unit")]
+    pub unit: String,
+}
+
+fn default_price_unit() -> String {
+    "credits".to_string()
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -94,12 +226,181 @@ pub struct UiConfig {
     pub show_timestamps: bool,
     #[serde(default = "default_true")]
     pub syntax_highlighting: bool,
+    #[serde(default)]
+    pub density: Density,
+    #[serde(default)]
+    pub time_format: TimeFormat,
+    /// Event loop tick while requests are in flight; idle loops wait longer
+    #[serde(default = "default_tick_ms")]
+    pub tick_ms: u64,
+    /// Keep each conversation in ~/.qhub/cache/sessions so /resume can reload it
+    #[serde(default)]
+    pub autosave_sessions: bool,
+    /// Open the most recent autosaved session on launch; `qhub --new` skips it
+    #[serde(default)]
+    pub autoresume: bool,
+    /// Capture the mouse for clicks and wheel scrolling. Off leaves selection
+    /// and scrollback to the terminal.
+    #[serde(default = "default_true")]
+    pub mouse_capture: bool,
+    /// Widest the chat text wraps at; unset uses the whole pane
+    #[serde(default)]
+    pub max_content_width: Option<u16>,
+    /// Where a capped chat column sits in a wider pane
+    #[serde(default)]
+    pub content_align: ContentAlign,
+    /// Ping when a reply or watched job is done while you are elsewhere
+    #[serde(default)]
+    pub notify_on_complete: NotifyOn,
+    /// Plain linear text for screen readers: no borders, glyphs or spinner
+    #[serde(default)]
+    pub accessible: bool,
+    /// The banner and welcome on launch; off shows a one-line ready notice
+    #[serde(default = "default_true")]
+    pub show_welcome: bool,
+    /// Show JSON lines and ```json blocks indented and coloured
+    #[serde(default)]
+    pub pretty_json: bool,
+}
+
+/// How `ui.notify_on_complete` pings
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifyOn {
+    #[default]
+    Off,
+    /// The terminal bell
+    Bell,
+    /// A desktop notification
+    Desktop,
+    Both,
+}
+
+/// Placement of the chat column when `max_content_width` leaves room
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentAlign {
+    #[default]
+    Center,
+    Left,
+}
+
+/// Vertical spacing of the chat transcript
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Density {
+    /// Blank line between messages
+    #[default]
+    Comfortable,
+    /// No spacing between messages, role prefix always on the first content line
+    Compact,
+}
+
+/// Where `ai.cache` keeps replies
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheMode {
+    #[default]
+    Off,
+    /// For this run only
+    Memory,
+    /// In ~/.qhub/cache/ai, across runs
+    Disk,
+}
+
+/// What happens to a prompt sent while another waits out a rate limit
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum QueuePolicy {
+    /// Refuse it; the text stays in the input box
+    #[default]
+    Reject,
+    /// Send it after the waiting request is answered
+    Queue,
+}
+
+/// How message timestamps are written
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeFormat {
+    /// "2m ago", "yesterday 14:03"
+    #[default]
+    Relative,
+    /// "14:03", or "2026-03-11 14:03" before today
+    Absolute,
+    /// "2026-03-11T14:03:00+01:00"
+    Iso,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct SecurityConfig {
+    /// Store API keys set with /keys in the OS keychain instead of config.toml
+    #[serde(default)]
+    pub use_keychain: bool,
+    /// Log out after this many minutes without a key press (off when unset)
+    #[serde(default)]
+    pub idle_timeout_mins: Option<u64>,
+}
+
+/// Anonymous usage metrics (`[telemetry]`), only changed with /telemetry on|off
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct TelemetryConfig {
+    #[serde(default)]
+    pub enabled: bool,
+}
+
+/// Proxy and TLS settings for outgoing HTTP requests (`[network]`).
+/// HTTP_PROXY, HTTPS_PROXY and NO_PROXY are honored without any configuration.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct NetworkConfig {
+    /// Proxy URL for all requests, overriding HTTP_PROXY/HTTPS_PROXY (NO_PROXY still applies)
+    #[serde(default)]
+    pub proxy: Option<String>,
+    /// PEM file with extra root certificates, e.g. a corporate CA
+    #[serde(default)]
+    pub ca_bundle: Option<PathBuf>,
+    /// Disable certificate verification entirely. Insecure - last resort only
+    #[serde(default)]
+    pub danger_accept_invalid_certs: bool,
+}
+
+/// Database connection settings (`[db]`), used when DATABASE_URL is set
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DbConfig {
+    #[serde(default = "default_max_connections")]
+    pub max_connections: u32,
+    /// Seconds to wait for a free connection before failing
+    #[serde(default = "default_acquire_timeout_secs")]
+    pub acquire_timeout_secs: u64,
+    /// Seconds a single statement may run (busy timeout on SQLite)
+    #[serde(default = "default_statement_timeout_secs")]
+    pub statement_timeout_secs: u64,
+    #[serde(default)]
+    pub maintenance: MaintenanceConfig,
+}
+
+/// Periodic database cleanup (`[db.maintenance]`)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MaintenanceConfig {
+    /// Run the scheduler in the background while the TUI is open
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default = "default_maintenance_interval_hours")]
+    pub interval_hours: u64,
+    #[serde(default = "default_login_attempts_retention_days")]
+    pub login_attempts_retention_days: u64,
+    #[serde(default = "default_audit_retention_days")]
+    pub audit_retention_days: u64,
 }
 
 fn default_model() -> String {
     "deepseek/deepseek-chat".to_string()
 }
 
+fn default_ai_base_url() -> String {
+    crate::api::deepseek::DEFAULT_BASE_URL.to_string()
+}
+
 fn default_max_tokens() -> u32 {
     4096
 }
@@ -108,6 +409,40 @@ fn default_scroll_speed() -> u16 {
     3
 }
 
+/// Rows per wheel step beyond which scrolling skips whole replies
+const MAX_SCROLL_SPEED: u16 = 50;
+
+/// Longest time between maintenance runs: a year
+const MAX_MAINTENANCE_INTERVAL_HOURS: u64 = 8760;
+
+fn default_tick_ms() -> u64 {
+    50
+}
+
+fn default_max_connections() -> u32 {
+    5
+}
+
+fn default_acquire_timeout_secs() -> u64 {
+    10
+}
+
+fn default_statement_timeout_secs() -> u64 {
+    30
+}
+
+fn default_maintenance_interval_hours() -> u64 {
+    6
+}
+
+fn default_login_attempts_retention_days() -> u64 {
+    30
+}
+
+fn default_audit_retention_days() -> u64 {
+    90
+}
+
 fn default_true() -> bool {
     true
 }
@@ -119,6 +454,16 @@ impl Default for AiConfig {
             api_key: None,
             model: default_model(),
             max_tokens: default_max_tokens(),
+            base_url: default_ai_base_url(),
+            context_window: None,
+            transcript: false,
+            log_requests: false,
+            cache: CacheMode::default(),
+            rate_limit_queue: QueuePolicy::default(),
+            inject_backend_context: default_true(),
+            system_prompt: None,
+            temperature: None,
+            top_p: None,
         }
     }
 }
@@ -129,6 +474,34 @@ impl Default for QuantumConfig {
             provider: "ibm".to_string(),
             api_key: None,
             default_backend: None,
+            channel: None,
+            instance: None,
+            confirm_hardware: true,
+            pricing: HashMap::new(),
+            export_order: CountOrder::default(),
+            register_separator: default_register_separator(),
+        }
+    }
+}
+
+impl Default for DbConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: default_max_connections(),
+            acquire_timeout_secs: default_acquire_timeout_secs(),
+            statement_timeout_secs: default_statement_timeout_secs(),
+            maintenance: MaintenanceConfig::default(),
+        }
+    }
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_true(),
+            interval_hours: default_maintenance_interval_hours(),
+            login_attempts_retention_days: default_login_attempts_retention_days(),
+            audit_retention_days: default_audit_retention_days(),
         }
     }
 }
@@ -139,6 +512,18 @@ impl Default for UiConfig {
             scroll_speed: default_scroll_speed(),
             show_timestamps: default_true(),
             syntax_highlighting: default_true(),
+            density: Density::default(),
+            time_format: TimeFormat::default(),
+            tick_ms: default_tick_ms(),
+            autosave_sessions: false,
+            autoresume: false,
+            mouse_capture: default_true(),
+            max_content_width: None,
+            content_align: ContentAlign::default(),
+            notify_on_complete: NotifyOn::default(),
+            accessible: false,
+            show_welcome: default_true(),
+            pretty_json: false,
         }
     }
 }
@@ -151,7 +536,7 @@ impl Config {
             .context("Could not find home directory")
     }
 
-    /// Get the configuration file path
+    /// Get the default configuration file path
     pub fn config_path() -> Result<PathBuf> {
         Ok(Self::config_dir()?.join("config.toml"))
     }
@@ -166,108 +551,336 @@ impl Config {
         Ok(Self::config_dir()?.join("cache"))
     }
 
-    /// Load configuration from file, with environment variable overrides
-    pub fn load() -> Result<Self> {
-        let path = Self::config_path()?;
-        
-        let mut config = if path.exists() {
-            let content = fs::read_to_string(&path)
-                .context("Failed to read config file")?;
-            toml::from_str::<Config>(&content)
-                .context("Failed to parse config file")?
+    /// Get the log directory for background task output
+    pub fn logs_dir() -> Result<PathBuf> {
+        Ok(Self::config_dir()?.join("logs"))
+    }
+
+    /// Load configuration from `locator`'s file, merged with the project's
+    /// .qhub.toml (if the working directory is inside one) and environment
+    /// overrides
+    pub fn load(locator: &ConfigLocator) -> Result<Self> {
+        let project = std::env::current_dir().ok().and_then(|dir| layers::find_project(&dir));
+        let mut config = Self::load_from(&locator.path()?, project.as_deref(), |var| std::env::var(var).ok())?;
+        config.locator = locator.clone();
+
+        // Config is loaded again by many commands; warn about the files once
+        static PROJECT_WARNINGS: Once = Once::new();
+        PROJECT_WARNINGS.call_once(|| {
+            for warning in &config.layers.warnings {
+                crate::diagnostics::warn(warning.clone());
+            }
+            for unknown in &config.layers.unknown {
+                crate::diagnostics::warn(unknown.to_string());
+            }
+        });
+        Ok(config)
+    }
+
+    /// The config as it is on disk now, loaded the way `self` was
+    pub fn reload(&self) -> Result<Self> {
+        Self::load(&self.locator)
+    }
+
+    /// Dotted keys whose effective value differs between `self` and `other`
+    pub fn changed_keys(&self, other: &Config) -> Result<Vec<String>> {
+        let (ours, theirs) = (self.to_table()?, other.to_table()?);
+        let mut keys = layers::leaf_keys(&ours);
+        keys.extend(layers::leaf_keys(&theirs));
+        keys.sort();
+        keys.dedup();
+        keys.retain(|key| layers::get(&ours, key) != layers::get(&theirs, key));
+        Ok(keys)
+    }
+
+    /// Everything wrong with the config `load` reads, unknown keys
+    /// included; an error only when a file cannot be read or is not TOML
+    pub fn check(locator: &ConfigLocator) -> Result<Vec<Problem>> {
+        let project = std::env::current_dir().ok().and_then(|dir| layers::find_project(&dir));
+        match Self::load_from(&locator.path()?, project.as_deref(), |var| std::env::var(var).ok()) {
+            Ok(config) => Ok(config.layers.unknown),
+            Err(e) => match e.downcast::<InvalidConfig>() {
+                Ok(invalid) => Ok(invalid.0),
+                Err(e) => Err(e),
+            },
+        }
+    }
+
+    /// Defaults < user file < project file < environment
+    fn load_from(user_path: &Path, project_path: Option<&Path>, env: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let user_text = if user_path.exists() {
+            fs::read_to_string(user_path)
+                .context("Failed to read config file")?
         } else {
-            Config::default()
+            String::new()
+        };
+        let user = toml::from_str::<toml::Table>(&user_text)
+            .context("Failed to parse config file")?;
+        let defaults = Config::default().to_table()?;
+        let mut merged = defaults.clone();
+        layers::merge(&mut merged, user.clone());
+        let mut layers = Layers::new(user_path, user.clone());
+
+        let project = match project_path {
+            Some(path) => {
+                let text = fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                let mut project = toml::from_str::<toml::Table>(&text)
+                    .with_context(|| format!("Failed to parse {}", path.display()))?;
+                layers.warnings = layers::strip_user_only(&mut project, path);
+                layers.add_project(path, &project);
+                layers::merge(&mut merged, project.clone());
+                Some((path, text, project))
+            }
+            None => None,
+        };
+        let mut files = vec![lint::File { path: user_path, text: &user_text, table: &user }];
+        if let Some((path, text, table)) = &project {
+            files.push(lint::File { path, text, table });
+        }
+        let unknown: Vec<Problem> =
+            files.iter().flat_map(|file| lint::unknown_keys::<Config>(&defaults, file)).collect();
+
+        let mut config: Config = match toml::Value::Table(merged).try_into() {
+            Ok(config) => config,
+            Err(e) => {
+                let mut problems: Vec<Problem> =
+                    files.iter().flat_map(|file| lint::type_problems::<Config>(&defaults, file)).collect();
+                if problems.is_empty() {
+                    return Err(e).context("Failed to parse config file");
+                }
+                problems.extend(unknown);
+                return Err(InvalidConfig(problems).into());
+            }
         };
 
         // Apply environment variable overrides (higher precedence)
-        config.apply_env_overrides();
+        for (var, key) in config.apply_env_overrides(env) {
+            layers.add_env(var, key);
+        }
         
         // Validate configuration
-        config.validate()?;
-        
+        let mut problems = config.problems();
+        if !problems.is_empty() {
+            lint::locate(&mut problems, &layers, &files);
+            problems.extend(unknown);
+            return Err(InvalidConfig(problems).into());
+        }
+        layers.unknown = unknown;
+        config.migrate_accounts();
+
+        layers.record_overlaid(&config.to_table()?);
+        config.layers = layers;
         Ok(config)
     }
     
-    /// Apply environment variable overrides to configuration
-    fn apply_env_overrides(&mut self) {
+    /// Apply environment variable overrides to configuration, returning the
+    /// variables used and the keys they set
+    fn apply_env_overrides(&mut self, env: impl Fn(&str) -> Option<String>) -> Vec<(&'static str, &'static str)> {
+        let mut applied = Vec::new();
+        let mut var = |name: &'static str, key: &'static str| {
+            let value = env(name);
+            if value.is_some() {
+                applied.push((name, key));
+            }
+            value
+        };
+
         // AI Configuration
-        if let Ok(key) = std::env::var("CLOUDFLARE_AI_TOKEN") {
+        if let Some(key) = var("CLOUDFLARE_AI_TOKEN", "ai.api_key") {
             self.ai.api_key = Some(key);
         }
-        if let Ok(provider) = std::env::var("QHUB_AI_PROVIDER") {
+        if let Some(provider) = var("QHUB_AI_PROVIDER", "ai.provider") {
             self.ai.provider = provider;
         }
-        if let Ok(model) = std::env::var("QHUB_AI_MODEL") {
+        if let Some(model) = var("QHUB_AI_MODEL", "ai.model") {
             self.ai.model = model;
         }
+        if let Some(url) = var("QHUB_AI_BASE_URL", "ai.base_url") {
+            self.ai.base_url = url;
+        }
         
         // Quantum Configuration
-        if let Ok(key) = std::env::var("IBM_QUANTUM_TOKEN") {
+        if let Some(key) = var("IBM_QUANTUM_TOKEN", "quantum.api_key") {
             self.quantum.api_key = Some(key);
         }
-        if let Ok(provider) = std::env::var("QHUB_QUANTUM_PROVIDER") {
+        if let Some(provider) = var("QHUB_QUANTUM_PROVIDER", "quantum.provider") {
             self.quantum.provider = provider;
         }
-        if let Ok(backend) = std::env::var("QHUB_QUANTUM_BACKEND") {
+        if let Some(backend) = var("QHUB_QUANTUM_BACKEND", "quantum.default_backend") {
             self.quantum.default_backend = Some(backend);
         }
+
+        // Network Configuration
+        if let Some(path) = var("QHUB_CA_BUNDLE", "network.ca_bundle") {
+            self.network.ca_bundle = Some(PathBuf::from(path));
+        }
+        applied
+    }
+
+    fn to_table(&self) -> Result<toml::Table> {
+        match toml::Value::try_from(self).context("Failed to serialize config")? {
+            toml::Value::Table(table) => Ok(table),
+            _ => unreachable!("Config serializes to a table"),
+        }
+    }
+
+    /// What `save` writes: the effective config, except that values from the
+    /// project file or environment are swapped back for the user's own
+    /// unless they were changed since loading
+    fn user_file_contents(&self) -> Result<String> {
+        let mut table = self.to_table()?;
+        self.layers.restore_user_values(&mut table, &Config::default().to_table()?);
+        toml::to_string_pretty(&table).context("Failed to serialize config")
+    }
+
+    /// Every effective value with where it came from, API keys hidden
+    pub fn effective_values(&self) -> Result<Vec<(String, String, String)>> {
+        let table = self.to_table()?;
+        Ok(layers::leaf_keys(&table)
+            .into_iter()
+            .map(|key| {
+                let value = layers::get(&table, &key).map(|v| v.to_string()).unwrap_or_default();
+                let value = if key == "accounts" {
+                    // Emails only; the list holds session tokens
+                    self.accounts.iter().map(|a| a.email.as_str()).collect::<Vec<_>>().join(", ")
+                } else if key.ends_with("api_key") || key.ends_with("token") {
+                    "(hidden)".to_string()
+                } else {
+                    value
+                };
+                let source = self.layers.source(&key).label();
+                (key, value, source)
+            })
+            .collect())
     }
     
-    /// Validate configuration values
-    fn validate(&self) -> Result<()> {
+    /// Everything wrong with the values, without where they came from
+    fn problems(&self) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        let mut problem = |key: &str, message: String| problems.push(Problem::new(key, message));
+
         // Version check for future migrations
         if self.version > CONFIG_VERSION {
-            anyhow::bail!(
-                "Config file version {} is newer than supported version {}. Please update qhub.",
-                self.version,
-                CONFIG_VERSION
+            problem(
+                "version",
+                format!("{} is newer than supported version {}. Please update qhub.", self.version, CONFIG_VERSION),
             );
         }
-        
-        // Validate AI provider
+
         let valid_ai_providers = ["deepseek", "openai", "anthropic"];
         if !valid_ai_providers.contains(&self.ai.provider.as_str()) {
-            anyhow::bail!(
-                "Invalid AI provider '{}'. Valid options: {}",
-                self.ai.provider,
-                valid_ai_providers.join(", ")
+            problem(
+                "ai.provider",
+                format!("invalid provider '{}'. Valid options: {}", self.ai.provider, valid_ai_providers.join(", ")),
             );
         }
-        
-        // Validate quantum provider
+        if !self.ai.base_url.starts_with("http://") && !self.ai.base_url.starts_with("https://") {
+            problem("ai.base_url", format!("'{}' must start with http:// or https://", self.ai.base_url));
+        }
+        let sampling = [
+            ("temperature", self.ai.temperature.map(f64::from)),
+            ("top_p", self.ai.top_p.map(f64::from)),
+            ("max_tokens", Some(f64::from(self.ai.max_tokens))),
+        ];
+        for (name, value) in sampling {
+            if let Some(e) = value.and_then(|v| crate::api::deepseek::check_parameter(name, v).err()) {
+                problem(&format!("ai.{}", name), e);
+            }
+        }
+
+        if !(1..=MAX_SCROLL_SPEED).contains(&self.ui.scroll_speed) {
+            problem(
+                "ui.scroll_speed",
+                format!("must be between 1 and {}, not {}", MAX_SCROLL_SPEED, self.ui.scroll_speed),
+            );
+        }
+        if !(10..=500).contains(&self.ui.tick_ms) {
+            problem("ui.tick_ms", format!("must be between 10 and 500, not {}", self.ui.tick_ms));
+        }
+        if let Some(width) = self.ui.max_content_width.filter(|w| *w < 40) {
+            problem("ui.max_content_width", format!("must be at least 40, not {}", width));
+        }
+
         let valid_quantum_providers = ["ibm", "simulator"];
         if !valid_quantum_providers.contains(&self.quantum.provider.as_str()) {
-            anyhow::bail!(
-                "Invalid quantum provider '{}'. Valid options: {}",
-                self.quantum.provider,
-                valid_quantum_providers.join(", ")
+            problem(
+                "quantum.provider",
+                format!(
+                    "invalid provider '{}'. Valid options: {}",
+                    self.quantum.provider,
+                    valid_quantum_providers.join(", ")
+                ),
             );
         }
-        
-        Ok(())
+
+        if self.security.idle_timeout_mins == Some(0) {
+            problem("security.idle_timeout_mins", "must be at least 1; leave it unset to disable".to_string());
+        }
+        if self.db.max_connections == 0 {
+            problem("db.max_connections", "must be at least 1".to_string());
+        }
+        if !(1..=MAX_MAINTENANCE_INTERVAL_HOURS).contains(&self.db.maintenance.interval_hours) {
+            problem(
+                "db.maintenance.interval_hours",
+                format!(
+                    "must be between 1 and {}, not {}",
+                    MAX_MAINTENANCE_INTERVAL_HOURS, self.db.maintenance.interval_hours
+                ),
+            );
+        }
+
+        // Validate network settings up front so clients never silently drop them
+        if let Some(proxy) = &self.network.proxy {
+            if let Err(e) = reqwest::Proxy::all(proxy) {
+                problem("network.proxy", format!("invalid proxy '{}': {}", proxy, e));
+            }
+        }
+        if let Some(path) = &self.network.ca_bundle {
+            if let Err(e) = crate::api::http::load_ca_bundle(path) {
+                problem("network.ca_bundle", format!("{:#}", e));
+            }
+        }
+        problems
+    }
+
+    /// Defaults, saved to `locator`'s file; for when loading it failed
+    pub fn default_at(locator: &ConfigLocator) -> Self {
+        Self { locator: locator.clone(), ..Self::default() }
+    }
+
+    /// The file this config was loaded from and saves to
+    pub fn path(&self) -> Result<PathBuf> {
+        self.locator.path()
     }
 
     /// Save configuration to file
     pub fn save(&self) -> Result<()> {
-        let path = Self::config_path()?;
-        let dir = Self::config_dir()?;
+        let path = self.locator.path()?;
+        self.locator.prepare_dir(&path)?;
         
-        // Ensure directory exists
-        if !dir.exists() {
-            fs::create_dir_all(&dir)
-                .context("Failed to create config directory")?;
+        let content = self.user_file_contents()?;
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        // The file can hold API keys and session tokens: owner only
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(&path).context("Failed to write config file")?;
+        // The mode only applies to a new file; tighten one written before
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(fs::Permissions::from_mode(0o600))
+                .context("Failed to make the config file private")?;
         }
-        
-        // Serialize and write
-        let content = toml::to_string_pretty(self)
-            .context("Failed to serialize config")?;
-        fs::write(&path, content)
+        file.write_all(content.as_bytes())
             .context("Failed to write config file")?;
         
         Ok(())
     }
     
     /// Create initial configuration with prompts
+    #[allow(dead_code)]
     pub fn create_initial() -> Result<Self> {
         let config = Config::default();
         config.save()?;
@@ -292,22 +905,183 @@ impl Config {
         Ok(())
     }
     
-    /// Check if configuration file exists
-    pub fn exists() -> bool {
-        Self::config_path()
-            .map(|p| p.exists())
-            .unwrap_or(false)
-    }
-    
     /// Get AI API key with fallback to default
     pub fn get_ai_api_key(&self) -> Option<String> {
-        self.ai.api_key.clone()
-            .or_else(|| std::env::var("CLOUDFLARE_AI_TOKEN").ok())
+        self.resolve_api_key(ApiKeyKind::Ai, |var| std::env::var(var).ok()).map(|(key, _)| key)
     }
     
+    /// Whether a remote QHub API is configured: QHUB_API_URL is set, the URL
+    /// points somewhere other than this machine, or a session was saved with it
+    pub fn has_remote_api(&self) -> bool {
+        let local = ["://localhost", "://127.0.0.1", "://[::1]"]
+            .iter()
+            .any(|host| self.api_url.contains(host));
+        std::env::var("QHUB_API_URL").is_ok()
+            || !local
+            || self.accounts.iter().any(UserConfig::has_session)
+    }
+
     /// Get quantum API key
     pub fn get_quantum_api_key(&self) -> Option<String> {
-        self.quantum.api_key.clone()
-            .or_else(|| std::env::var("IBM_QUANTUM_TOKEN").ok())
+        self.resolve_api_key(ApiKeyKind::Quantum, |var| std::env::var(var).ok()).map(|(key, _)| key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::layers::Source;
+    use std::collections::HashMap;
+
+    /// A user config and a project file in a fresh temp directory
+    fn files(user: &str, project: &str) -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("qhub-layers-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (user_path, project_path) = (dir.join("config.toml"), dir.join(".qhub.toml"));
+        std::fs::write(&user_path, user).unwrap();
+        std::fs::write(&project_path, project).unwrap();
+        (user_path, project_path)
+    }
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    const USER: &str = "[ai]\nprovider = \"deepseek\"\nmodel = \"deepseek/deepseek-chat\"\napi_key = \"user-key\"\n\
+                        [quantum]\nprovider = \"ibm\"\ndefault_backend = \"ibm_brisbane\"\n[ui]\ndensity = \"compact\"";
+    const PROJECT: &str = "[ai]\nmodel = \"deepseek/deepseek-reasoner\"\nsystem_prompt = \"Use the VQE ansatz in ansatz.qasm\"\n\
+                           [quantum]\ndefault_backend = \"ibm_kyiv\"";
+
+    #[test]
+    fn test_max_content_width() {
+        let (user, _) = files("[ui]\nmax_content_width = 100\ncontent_align = \"left\"", "");
+        let config = Config::load_from(&user, None, env(&[])).unwrap();
+        assert_eq!((config.ui.max_content_width, config.ui.content_align), (Some(100), ContentAlign::Left));
+
+        let (user, _) = files("[ui]\nmax_content_width = 20", "");
+        let err = Config::load_from(&user, None, env(&[])).unwrap_err().to_string();
+        assert!(err.contains("ui.max_content_width"), "{}", err);
+    }
+
+    #[test]
+    fn test_all_problems_are_reported_with_lines() {
+        let (user, project) = files(
+            "[ui]\nscroll_speed = 0\ntick_ms = 5\ncolour = \"blue\"\n[quantum]\nprovider = \"aws\"\n",
+            "[ui]\ndensity = \"compact\"\n",
+        );
+        let err = Config::load_from(&user, Some(&project), env(&[("QHUB_AI_PROVIDER", "gemini")])).unwrap_err();
+        let problems: Vec<String> = err.downcast::<InvalidConfig>().unwrap().0.iter().map(|p| p.to_string()).collect();
+        let at = |line: usize| format!("{}:{}: ", user.display(), line);
+        assert_eq!(problems.len(), 5, "{:#?}", problems);
+        assert!(problems[0].starts_with("$QHUB_AI_PROVIDER: ai.provider: invalid provider 'gemini'"));
+        assert_eq!(problems[1], format!("{}ui.scroll_speed: must be between 1 and 50, not 0", at(2)));
+        assert_eq!(problems[2], format!("{}ui.tick_ms: must be between 10 and 500, not 5", at(3)));
+        assert!(problems[3].starts_with(&format!("{}quantum.provider: invalid provider 'aws'", at(6))));
+        assert_eq!(problems[4], format!("{}ui.colour: not a setting qhub knows; it is ignored", at(4)));
+
+        // Wrong types are all found too, and unknown keys alone only warn
+        let (user, _) = files("[ui]\nscroll_speed = \"fast\"\n[db]\nmax_connections = -1\n", "");
+        let err = Config::load_from(&user, None, env(&[])).unwrap_err();
+        assert_eq!(err.downcast::<InvalidConfig>().unwrap().0.len(), 2);
+        let (user, _) = files("[db.maintenance]\ninterval_hours = 9999999999999999\n", "");
+        let err = Config::load_from(&user, None, env(&[])).unwrap_err().to_string();
+        assert!(err.contains("db.maintenance.interval_hours: must be between 1 and 8760"), "{}", err);
+        let (user, _) = files("[ui]\ncolour = \"blue\"\n", "");
+        let config = Config::load_from(&user, None, env(&[])).unwrap();
+        assert_eq!(config.layers.unknown[0].key, "ui.colour");
+    }
+
+    #[test]
+    fn test_generation_parameter_ranges() {
+        let (user, _) = files("[ai]\ntemperature = 0.3\ntop_p = 0.95\nmax_tokens = 1024", "");
+        let config = Config::load_from(&user, None, env(&[])).unwrap();
+        assert_eq!((config.ai.temperature, config.ai.top_p, config.ai.max_tokens), (Some(0.3), Some(0.95), 1024));
+
+        for (toml, expected) in [("temperature = 2.5", "temperature"), ("top_p = 0.0", "top_p"), ("max_tokens = 0", "max_tokens")] {
+            let (user, _) = files(&format!("[ai]\n{}", toml), "");
+            let err = Config::load_from(&user, None, env(&[])).unwrap_err().to_string();
+            assert!(err.contains(expected), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_precedence() {
+        let (user, project) = files(USER, PROJECT);
+
+        // defaults < user
+        let config = Config::load_from(&user, None, env(&[])).unwrap();
+        assert_eq!(config.ai.model, "deepseek/deepseek-chat");
+        assert_eq!(config.ai.max_tokens, 4096);
+        assert_eq!(config.layers.source("ai.max_tokens"), Source::Default);
+        assert_eq!(config.layers.source("ai.model"), Source::User(user.clone()));
+
+        // user < project, per key
+        let config = Config::load_from(&user, Some(&project), env(&[])).unwrap();
+        assert_eq!(config.ai.model, "deepseek/deepseek-reasoner");
+        assert_eq!(config.quantum.default_backend.as_deref(), Some("ibm_kyiv"));
+        assert_eq!(config.ui.density, Density::Compact);
+        assert_eq!(config.ai.api_key.as_deref(), Some("user-key"));
+        assert_eq!(config.layers.source("quantum.default_backend"), Source::Project(project.clone()));
+        assert_eq!(config.layers.source("ui.density"), Source::User(user.clone()));
+        assert_eq!(config.layers.project_keys(), vec!["ai.model", "ai.system_prompt", "quantum.default_backend"]);
+
+        // project < environment
+        let config = Config::load_from(&user, Some(&project), env(&[("QHUB_QUANTUM_BACKEND", "ibm_torino")])).unwrap();
+        assert_eq!(config.quantum.default_backend.as_deref(), Some("ibm_torino"));
+        assert_eq!(config.layers.source("quantum.default_backend"), Source::Env("QHUB_QUANTUM_BACKEND"));
+        assert_eq!(config.ai.model, "deepseek/deepseek-reasoner");
+        let _ = std::fs::remove_dir_all(user.parent().unwrap());
+    }
+
+    #[test]
+    fn test_project_cannot_set_secrets() {
+        let (user, project) = files(
+            "[ai]\nprovider = \"deepseek\"",
+            "api_url = \"https://collector.example\"\n[ai]\napi_key = \"sk-from-repo\"\nmodel = \"gpt-4o-mini\"\n\
+             [quantum]\napi_key = \"ibm-from-repo\"",
+        );
+        let config = Config::load_from(&user, Some(&project), env(&[])).unwrap();
+        assert_eq!(config.ai.api_key, None);
+        assert_eq!(config.quantum.api_key, None);
+        assert_ne!(config.api_url, "https://collector.example");
+        assert_eq!(config.ai.model, "gpt-4o-mini");
+        assert_eq!(config.layers.warnings.len(), 3);
+        assert!(config.layers.warnings[0].contains("ai.api_key"));
+        assert!(config.layers.warnings[1].contains("quantum.api_key"));
+        let _ = std::fs::remove_dir_all(user.parent().unwrap());
+    }
+
+    #[test]
+    fn test_save_keeps_project_and_env_values_out() {
+        let (user, project) = files(USER, PROJECT);
+        let mut config =
+            Config::load_from(&user, Some(&project), env(&[("IBM_QUANTUM_TOKEN", "env-token")])).unwrap();
+        config.ui.scroll_speed = 7;
+        config.quantum.default_backend = Some("ibm_sherbrooke".to_string());
+
+        let saved: toml::Table = toml::from_str(&config.user_file_contents().unwrap()).unwrap();
+        // Unchanged project and environment values are not written
+        assert_eq!(layers::get(&saved, "ai.model").unwrap().as_str(), Some("deepseek/deepseek-chat"));
+        assert!(layers::get(&saved, "ai.system_prompt").is_none());
+        assert!(layers::get(&saved, "quantum.api_key").is_none());
+        // Edits made after loading are
+        assert_eq!(layers::get(&saved, "ui.scroll_speed").unwrap().as_integer(), Some(7));
+        assert_eq!(layers::get(&saved, "quantum.default_backend").unwrap().as_str(), Some("ibm_sherbrooke"));
+        assert_eq!(layers::get(&saved, "ai.api_key").unwrap().as_str(), Some("user-key"));
+        let _ = std::fs::remove_dir_all(user.parent().unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_save_keeps_the_file_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let (user, _) = files(USER, "");
+        std::fs::set_permissions(&user, std::fs::Permissions::from_mode(0o644)).unwrap();
+        let mut config = Config::default_at(&ConfigLocator::at(&user).unwrap());
+        config.ai.api_key = Some("user-key".to_string());
+        config.save().unwrap();
+        assert_eq!(std::fs::metadata(&user).unwrap().permissions().mode() & 0o777, 0o600);
+        let _ = std::fs::remove_dir_all(user.parent().unwrap());
     }
 }
@@ -0,0 +1,167 @@
+// What each subscription tier unlocks
+//
+// Free accounts get the local simulator and a small model; Pro and
+// Enterprise unlock hardware backends, the larger models and bigger jobs.
+// The server enforces the same limits (workers/src/routes); checking here
+// first gives an immediate, specific error instead of a failed request.
+// Tiers the table does not know get the Free limits.
+
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TierLimits {
+    pub name: &'static str,
+    /// Jobs may run on real hardware rather than only the simulator
+    pub hardware: bool,
+    /// Models that may be used, matched on the name after the provider
+    /// prefix; None allows any
+    pub models: Option<&'static [&'static str]>,
+    pub max_shots: usize,
+    /// AI tokens per calendar month; None is unlimited
+    pub monthly_tokens: Option<u64>,
+    /// Quantum jobs per calendar month; None is unlimited
+    pub monthly_jobs: Option<u64>,
+}
+
+/// Models available without Pro
+pub const SMALL_MODELS: &[&str] = &["deepseek-chat", "gpt-4o-mini", "gpt-3.5-turbo", "claude-3-haiku"];
+
+pub const FREE: TierLimits = TierLimits {
+    name: "free",
+    hardware: false,
+    models: Some(SMALL_MODELS),
+    max_shots: 4096,
+    monthly_tokens: Some(200_000),
+    monthly_jobs: Some(100),
+};
+
+pub const PRO: TierLimits = TierLimits {
+    name: "pro",
+    hardware: true,
+    models: None,
+    max_shots: 100_000,
+    monthly_tokens: Some(5_000_000),
+    monthly_jobs: Some(2_000),
+};
+
+pub const ENTERPRISE: TierLimits = TierLimits {
+    name: "enterprise",
+    hardware: true,
+    models: None,
+    max_shots: 1_000_000,
+    monthly_tokens: None,
+    monthly_jobs: None,
+};
+
+/// A capability the tier does not include
+#[derive(Debug, Error, PartialEq)]
+#[error("{capability} requires Pro — /upgrade")]
+pub struct Restricted {
+    pub capability: String,
+}
+
+/// Limits for `tier`, Free when it is unknown
+pub fn limits(tier: &str) -> TierLimits {
+    match tier.to_ascii_lowercase().as_str() {
+        "pro" => PRO,
+        "enterprise" => ENTERPRISE,
+        _ => FREE,
+    }
+}
+
+impl TierLimits {
+    /// `backend` may be empty when none is configured yet
+    pub fn check_hardware(&self, backend: &str) -> Result<(), Restricted> {
+        if self.hardware {
+            return Ok(());
+        }
+        let capability = match backend {
+            "" => "Hardware backends".to_string(),
+            name => format!("Hardware backend {}", name),
+        };
+        Err(Restricted { capability })
+    }
+
+    pub fn check_model(&self, model: &str) -> Result<(), Restricted> {
+        let name = model.rsplit('/').next().unwrap_or(model);
+        match self.models {
+            Some(allowed) if !allowed.iter().any(|m| m.eq_ignore_ascii_case(name)) => {
+                Err(Restricted { capability: format!("Model {}", model) })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn check_shots(&self, shots: usize) -> Result<(), Restricted> {
+        if shots <= self.max_shots {
+            return Ok(());
+        }
+        Err(Restricted {
+            capability: format!("{} shots per job (the {} limit is {})", shots, self.name, self.max_shots),
+        })
+    }
+
+    /// One line per capability, for /status
+    pub fn describe(&self) -> Vec<String> {
+        vec![
+            format!("Backends: {}", if self.hardware { "simulator and hardware" } else { "simulator only" }),
+            match self.models {
+                Some(models) => format!("Models: {}", models.join(", ")),
+                None => "Models: any".to_string(),
+            },
+            format!("Max shots per job: {}", self.max_shots),
+            format!("AI tokens per month: {}", quota(self.monthly_tokens)),
+            format!("Jobs per month: {}", quota(self.monthly_jobs)),
+        ]
+    }
+}
+
+fn quota(limit: Option<u64>) -> String {
+    limit.map_or_else(|| "unlimited".to_string(), |n| n.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_free() {
+        let free = limits("free");
+        assert_eq!(free, FREE);
+        assert_eq!(
+            free.check_hardware("ibm_brisbane").unwrap_err().to_string(),
+            "Hardware backend ibm_brisbane requires Pro — /upgrade"
+        );
+        assert_eq!(free.check_hardware("").unwrap_err().to_string(), "Hardware backends requires Pro — /upgrade");
+        assert!(free.check_model("deepseek/deepseek-chat").is_ok());
+        assert!(free.check_model("GPT-4o-mini").is_ok());
+        assert_eq!(
+            free.check_model("deepseek/deepseek-reasoner").unwrap_err().to_string(),
+            "Model deepseek/deepseek-reasoner requires Pro — /upgrade"
+        );
+        assert!(free.check_shots(4096).is_ok());
+        assert_eq!(
+            free.check_shots(8192).unwrap_err().to_string(),
+            "8192 shots per job (the free limit is 4096) requires Pro — /upgrade"
+        );
+    }
+
+    #[test]
+    fn test_pro_and_enterprise() {
+        for (tier, max_shots) in [("pro", 100_000), ("Enterprise", 1_000_000)] {
+            let limits = limits(tier);
+            assert!(limits.check_hardware("ibm_brisbane").is_ok());
+            assert!(limits.check_model("deepseek/deepseek-reasoner").is_ok());
+            assert!(limits.check_shots(max_shots).is_ok());
+            assert!(limits.check_shots(max_shots + 1).is_err());
+        }
+    }
+
+    #[test]
+    fn test_unknown_tier_defaults_to_free() {
+        assert_eq!(limits("platinum"), FREE);
+        assert_eq!(limits(""), FREE);
+        assert_eq!(limits("platinum").describe()[0], "Backends: simulator only");
+        assert_eq!(limits("enterprise").describe()[4], "Jobs per month: unlimited");
+    }
+}
@@ -0,0 +1,223 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use super::pool::{with_pool, DatabasePool};
+
+pub const VALID_TIERS: [&str; 3] = ["free", "pro", "enterprise"];
+
+/// User row as shown by `qhub admin users list`
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct UserSummary {
+    pub email: String,
+    pub username: Option<String>,
+    pub tier: String,
+    pub role: String,
+    pub is_active: bool,
+    pub created_at: i64,
+    pub last_login_at: Option<i64>,
+}
+
+/// Aggregate numbers for `qhub admin stats`
+#[derive(Debug, Clone, Serialize)]
+pub struct AdminStats {
+    pub users_total: i64,
+    pub users_active: i64,
+    pub jobs_by_status: Vec<(String, i64)>,
+    pub usage_totals: Vec<(String, i64)>,
+}
+
+impl DatabasePool {
+    /// Role of an active user, if the user exists
+    pub async fn user_role(&self, email: &str) -> Result<Option<String>> {
+        let sql = format!(
+            "SELECT role FROM {} WHERE email = $1 AND is_active",
+            self.table("users")
+        );
+        let role: Option<(String,)> = with_pool!(self, pool => {
+            sqlx::query_as(&sql).bind(email).fetch_optional(pool).await?
+        });
+        Ok(role.map(|(r,)| r))
+    }
+
+    pub async fn list_users(&self) -> Result<Vec<UserSummary>> {
+        let sql = format!(
+            "SELECT email, username, tier, role, is_active, created_at, last_login_at
+             FROM {} ORDER BY created_at",
+            self.table("users")
+        );
+        let users = with_pool!(self, pool => {
+            sqlx::query_as::<_, UserSummary>(&sql).fetch_all(pool).await?
+        });
+        Ok(users)
+    }
+
+    /// Change a user's tier, returning false when no such user exists
+    pub async fn set_user_tier(&self, email: &str, tier: &str) -> Result<bool> {
+        if !VALID_TIERS.contains(&tier) {
+            anyhow::bail!("Invalid tier '{}'. Valid options: {}", tier, VALID_TIERS.join(", "));
+        }
+        let sql = format!(
+            "UPDATE {} SET tier = $1, updated_at = $2 WHERE email = $3",
+            self.table("users")
+        );
+        let now = Utc::now().timestamp();
+        let affected = with_pool!(self, pool => {
+            sqlx::query(&sql).bind(tier).bind(now).bind(email).execute(pool).await?.rows_affected()
+        });
+        Ok(affected > 0)
+    }
+
+    /// Deactivate a user and revoke all of their sessions
+    pub async fn deactivate_user(&self, email: &str) -> Result<bool> {
+        let update = format!(
+            "UPDATE {} SET is_active = $1, updated_at = $2 WHERE email = $3",
+            self.table("users")
+        );
+        let revoke = format!(
+            "DELETE FROM {} WHERE user_id IN (SELECT id FROM {} WHERE email = $1)",
+            self.table("user_sessions"),
+            self.table("users")
+        );
+        let now = Utc::now().timestamp();
+        let affected = with_pool!(self, pool => {
+            let affected = sqlx::query(&update)
+                .bind(false)
+                .bind(now)
+                .bind(email)
+                .execute(pool)
+                .await?
+                .rows_affected();
+            sqlx::query(&revoke).bind(email).execute(pool).await?;
+            affected
+        });
+        Ok(affected > 0)
+    }
+
+    /// Delete sessions whose expiry has passed
+    pub async fn purge_expired_sessions(&self) -> Result<u64> {
+        let sql = format!(
+            "DELETE FROM {} WHERE expires_at < $1",
+            self.table("user_sessions")
+        );
+        let now = Utc::now().timestamp();
+        let purged = with_pool!(self, pool => {
+            sqlx::query(&sql).bind(now).execute(pool).await?.rows_affected()
+        });
+        Ok(purged)
+    }
+
+    pub async fn admin_stats(&self) -> Result<AdminStats> {
+        let users_sql = format!(
+            "SELECT COUNT(*), COUNT(CASE WHEN is_active THEN 1 END) FROM {}",
+            self.table("users")
+        );
+        let jobs_sql = format!(
+            "SELECT status, COUNT(*) FROM {} GROUP BY status ORDER BY status",
+            self.table("quantum_jobs")
+        );
+        let usage_sql = format!(
+            "SELECT resource_type, SUM(resource_count) FROM {} GROUP BY resource_type ORDER BY resource_type",
+            self.table("usage_records")
+        );
+
+        let ((users_total, users_active), jobs_by_status, usage_totals) = with_pool!(self, pool => {
+            let users: (i64, i64) = sqlx::query_as(&users_sql).fetch_one(pool).await?;
+            let jobs: Vec<(String, i64)> = sqlx::query_as(&jobs_sql).fetch_all(pool).await?;
+            let usage: Vec<(String, i64)> = sqlx::query_as(&usage_sql).fetch_all(pool).await?;
+            (users, jobs, usage)
+        });
+
+        Ok(AdminStats {
+            users_total,
+            users_active,
+            jobs_by_status,
+            usage_totals,
+        })
+    }
+
+    /// Append an entry to the audit log
+    pub async fn record_audit(
+        &self,
+        actor: &str,
+        action: &str,
+        target: Option<&str>,
+        details: Option<&str>,
+    ) -> Result<()> {
+        let sql = format!(
+            "INSERT INTO {} (id, actor, action, target, details, created_at)
+             VALUES ($1, $2, $3, $4, $5, $6)",
+            self.table("audit_log")
+        );
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now().timestamp();
+        with_pool!(self, pool => {
+            sqlx::query(&sql)
+                .bind(&id)
+                .bind(actor)
+                .bind(action)
+                .bind(target)
+                .bind(details)
+                .bind(now)
+                .execute(pool)
+                .await
+                .context("Failed to write audit log")?;
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::settings::DbConfig;
+
+    async fn test_pool() -> DatabasePool {
+        let path = std::env::temp_dir().join(format!("qhub-admin-{}.db", Uuid::new_v4()));
+        DatabasePool::from_url(&format!("sqlite://{}?mode=rwc", path.display()), &DbConfig::default())
+            .await
+            .unwrap()
+    }
+
+    async fn insert_user(db: &DatabasePool, email: &str, role: &str) {
+        let pool = db.sqlite().unwrap();
+        sqlx::query("INSERT INTO users (id, email, password_hash, role) VALUES ($1, $2, 'x', $3)")
+            .bind(Uuid::new_v4().to_string())
+            .bind(email)
+            .bind(role)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_admin_operations_on_sqlite() {
+        let db = test_pool().await;
+        insert_user(&db, "root@example.com", "admin").await;
+        insert_user(&db, "ada@example.com", "user").await;
+
+        assert_eq!(db.user_role("root@example.com").await.unwrap().as_deref(), Some("admin"));
+        assert_eq!(db.user_role("nobody@example.com").await.unwrap(), None);
+
+        assert!(db.set_user_tier("ada@example.com", "pro").await.unwrap());
+        assert!(db.set_user_tier("ada@example.com", "platinum").await.is_err());
+        assert!(!db.set_user_tier("nobody@example.com", "pro").await.unwrap());
+
+        assert!(db.deactivate_user("ada@example.com").await.unwrap());
+        assert_eq!(db.user_role("ada@example.com").await.unwrap(), None);
+
+        let users = db.list_users().await.unwrap();
+        assert_eq!(users.len(), 2);
+        let ada = users.iter().find(|u| u.email == "ada@example.com").unwrap();
+        assert_eq!(ada.tier, "pro");
+        assert!(!ada.is_active);
+
+        let stats = db.admin_stats().await.unwrap();
+        assert_eq!(stats.users_total, 2);
+        assert_eq!(stats.users_active, 1);
+
+        db.record_audit("root@example.com", "users.list", None, None).await.unwrap();
+    }
+}
@@ -0,0 +1,340 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde_json::Value;
+use uuid::Uuid;
+
+use super::pool::{with_pool, DatabasePool};
+use crate::quantum::compare::{self, Counts};
+use crate::quantum::export::{self, Source};
+
+/// A stored job as `job_status` reports it
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobRecord {
+    pub id: String,
+    pub name: Option<String>,
+    pub backend: Option<String>,
+    pub status: String,
+    pub error: Option<String>,
+    pub created_at: i64,
+    pub completed_at: Option<i64>,
+    pub result: Option<Value>,
+}
+
+/// A circuit to store as a pending job: a sweep point, a batch file or a
+/// `qhub run`
+pub struct NewJob {
+    pub name: String,
+    pub circuit_code: String,
+}
+
+impl DatabasePool {
+    /// Placeholder for a JSON value bound as text: PostgreSQL's column is JSONB
+    fn json_param(&self, n: usize) -> String {
+        if self.is_postgres() {
+            format!("CAST(${} AS JSONB)", n)
+        } else {
+            format!("${}", n)
+        }
+    }
+
+    /// Create the pending child jobs of a sweep for `email`, in point order.
+    /// Returns their ids.
+    pub async fn create_sweep_jobs(
+        &self,
+        sweep_id: &str,
+        email: &str,
+        backend: &str,
+        points: &[NewJob],
+    ) -> Result<Vec<String>> {
+        self.create_jobs(Some(sweep_id), email, backend, points).await
+    }
+
+    /// Create pending jobs for `email` in one transaction. Returns their ids.
+    pub async fn create_jobs(
+        &self,
+        sweep_id: Option<&str>,
+        email: &str,
+        backend: &str,
+        jobs: &[NewJob],
+    ) -> Result<Vec<String>> {
+        let user_sql = format!("SELECT id FROM {} WHERE email = $1 AND is_active = true", self.table("users"));
+        let insert_sql = format!(
+            "INSERT INTO {} (id, user_id, name, circuit_code, backend, status, created_at, sweep_id)
+             VALUES ($1, $2, $3, $4, $5, 'pending', $6, $7)",
+            self.table("quantum_jobs")
+        );
+        let now = Utc::now().timestamp();
+        let ids: Vec<String> = jobs.iter().map(|_| Uuid::new_v4().to_string()).collect();
+        with_pool!(self, pool => {
+            let user: Option<(String,)> = sqlx::query_as(&user_sql).bind(email).fetch_optional(pool).await?;
+            let Some((user_id,)) = user else {
+                anyhow::bail!("No active account for {}", email);
+            };
+            let mut tx = pool.begin().await?;
+            for (id, job) in ids.iter().zip(jobs) {
+                sqlx::query(&insert_sql)
+                    .bind(id)
+                    .bind(&user_id)
+                    .bind(&job.name)
+                    .bind(&job.circuit_code)
+                    .bind(backend)
+                    .bind(now)
+                    .bind(sweep_id)
+                    .execute(&mut *tx)
+                    .await
+                    .context("Failed to create jobs")?;
+            }
+            tx.commit().await?;
+        });
+        Ok(ids)
+    }
+
+    /// Record a job's result, or its error when `result` is `Err`
+    pub async fn finish_job(&self, id: &str, result: std::result::Result<&Value, &str>) -> Result<()> {
+        let now = Utc::now().timestamp();
+        let (status, result, error) = match result {
+            Ok(value) => ("completed", Some(value.to_string()), None),
+            Err(error) => ("failed", None, Some(error)),
+        };
+        let sql = format!(
+            "UPDATE {} SET status = $1, result = {}, error_message = $3, started_at = COALESCE(started_at, $4),
+             completed_at = $4 WHERE id = $5",
+            self.table("quantum_jobs"),
+            self.json_param(2)
+        );
+        with_pool!(self, pool => {
+            sqlx::query(&sql)
+                .bind(status)
+                .bind(result)
+                .bind(error)
+                .bind(now)
+                .bind(id)
+                .execute(pool)
+                .await
+                .context("Failed to store job result")?;
+        });
+        Ok(())
+    }
+
+    /// Store a finished run of `job` for `email` with its measurement counts,
+    /// so that `/compare` takes its id. Returns the id.
+    pub async fn record_run<C: serde::Serialize>(&self, email: &str, backend: &str, job: NewJob, counts: &C) -> Result<String> {
+        let ids = self.create_jobs(None, email, backend, std::slice::from_ref(&job)).await?;
+        let id = ids.into_iter().next().context("No job was created")?;
+        self.finish_job(&id, Ok(&serde_json::json!({ "counts": counts }))).await?;
+        Ok(id)
+    }
+
+    /// Mark the sweep's unstarted points cancelled; finished ones are kept
+    pub async fn cancel_pending_sweep_jobs(&self, sweep_id: &str) -> Result<u64> {
+        let sql = format!(
+            "UPDATE {} SET status = 'cancelled', completed_at = $1 WHERE sweep_id = $2 AND status = 'pending'",
+            self.table("quantum_jobs")
+        );
+        let now = Utc::now().timestamp();
+        let done = with_pool!(self, pool => {
+            sqlx::query(&sql).bind(now).bind(sweep_id).execute(pool).await?.rows_affected()
+        });
+        Ok(done)
+    }
+
+    /// Status and result of each point of a sweep owned by `email`, in point order
+    pub async fn sweep_jobs(&self, sweep_id: &str, email: &str) -> Result<Vec<(String, Option<Value>)>> {
+        let sql = format!(
+            "SELECT j.status, CAST(j.result AS TEXT) FROM {} j JOIN {} u ON u.id = j.user_id
+             WHERE j.sweep_id = $1 AND u.email = $2 ORDER BY j.name",
+            self.table("quantum_jobs"),
+            self.table("users")
+        );
+        let rows: Vec<(String, Option<String>)> = with_pool!(self, pool => {
+            sqlx::query_as(&sql).bind(sweep_id).bind(email).fetch_all(pool).await?
+        });
+        if rows.is_empty() {
+            anyhow::bail!("Sweep {} not found", sweep_id);
+        }
+        Ok(rows
+            .into_iter()
+            .map(|(status, result)| (status, result.and_then(|r| serde_json::from_str(&r).ok())))
+            .collect())
+    }
+
+    /// One job owned by `email`, or None when there is no such job
+    pub async fn job_record(&self, id: &str, email: &str) -> Result<Option<JobRecord>> {
+        let sql = format!(
+            "SELECT j.name, j.backend, j.status, j.error_message, j.created_at, j.completed_at, CAST(j.result AS TEXT)
+             FROM {} j JOIN {} u ON u.id = j.user_id WHERE j.id = $1 AND u.email = $2",
+            self.table("quantum_jobs"),
+            self.table("users")
+        );
+        type Row = (Option<String>, Option<String>, String, Option<String>, i64, Option<i64>, Option<String>);
+        let row: Option<Row> = with_pool!(self, pool => {
+            sqlx::query_as(&sql).bind(id).bind(email).fetch_optional(pool).await?
+        });
+        Ok(row.map(|(name, backend, status, error, created_at, completed_at, result)| JobRecord {
+            id: id.to_string(),
+            name,
+            backend,
+            status,
+            error,
+            created_at,
+            completed_at,
+            result: result.and_then(|r| serde_json::from_str(&r).ok()),
+        }))
+    }
+
+    /// Measurement counts of a finished job. With `email`, only that user's
+    /// jobs are visible.
+    pub async fn job_counts(&self, id: &str, email: Option<&str>) -> Result<Counts> {
+        // JSONB on PostgreSQL, TEXT on SQLite; both read back as text
+        let mut sql = format!(
+            "SELECT j.status, CAST(j.result AS TEXT) FROM {} j JOIN {} u ON u.id = j.user_id WHERE j.id = $1",
+            self.table("quantum_jobs"),
+            self.table("users")
+        );
+        if email.is_some() {
+            sql.push_str(" AND u.email = $2");
+        }
+        let row: Option<(String, Option<String>)> = with_pool!(self, pool => {
+            let mut query = sqlx::query_as(&sql).bind(id);
+            if let Some(email) = email {
+                query = query.bind(email);
+            }
+            query.fetch_optional(pool).await?
+        });
+
+        let Some((status, result)) = row else {
+            anyhow::bail!("Job {} not found", id);
+        };
+        let Some(result) = result else {
+            anyhow::bail!("Job {} has no results yet (status: {})", id, status);
+        };
+        serde_json::from_str(&result)
+            .ok()
+            .and_then(|value| compare::counts_from_result(&value))
+            .ok_or_else(|| anyhow::anyhow!("Job {} has a result but no measurement counts", id))
+    }
+
+    /// Counts for a compare: those of a job, or of a .json file `export` wrote
+    pub async fn counts_for(&self, reference: &str, email: Option<&str>) -> Result<Counts> {
+        if export::is_counts_file(reference) {
+            return export::read_counts(reference);
+        }
+        self.job_counts(reference, email).await
+    }
+
+    /// Counts of a finished job owned by `email`, and where they came from
+    pub async fn job_export(&self, id: &str, email: &str) -> Result<(Counts, Source)> {
+        let Some(job) = self.job_record(id, email).await? else {
+            anyhow::bail!("Job {} not found", id);
+        };
+        let Some(counts) = job.result.as_ref().and_then(compare::counts_from_result) else {
+            anyhow::bail!("Job {} has no measurement counts (status: {})", id, job.status);
+        };
+        Ok((counts, Source { job_id: Some(job.id), backend: job.backend }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::settings::DbConfig;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_job_counts_on_sqlite() {
+        let path = std::env::temp_dir().join(format!("qhub-jobs-{}.db", Uuid::new_v4()));
+        let db = DatabasePool::from_url(&format!("sqlite://{}?mode=rwc", path.display()), &DbConfig::default())
+            .await
+            .unwrap();
+        let pool = db.sqlite().unwrap();
+        for sql in [
+            "INSERT INTO users (id, email, password_hash) VALUES ('u1', 'ada@example.com', 'x')",
+            "INSERT INTO quantum_jobs (id, user_id, circuit_code, status, result)
+             VALUES ('done', 'u1', 'h q[0];', 'completed', '{\"counts\": {\"0\": 480, \"1\": 544}}')",
+            "INSERT INTO quantum_jobs (id, user_id, circuit_code, status) VALUES ('queued', 'u1', 'h q[0];', 'queued')",
+        ] {
+            sqlx::query(sql).execute(pool).await.unwrap();
+        }
+
+        let counts = db.job_counts("done", Some("ada@example.com")).await.unwrap();
+        assert_eq!(counts.get("1"), Some(&544));
+        assert!(db.job_counts("done", None).await.is_ok());
+
+        let err = db.job_counts("queued", None).await.unwrap_err().to_string();
+        assert_eq!(err, "Job queued has no results yet (status: queued)");
+        assert!(db.job_counts("done", Some("eve@example.com")).await.unwrap_err().to_string().contains("not found"));
+        assert!(db.job_counts("missing", None).await.unwrap_err().to_string().contains("not found"));
+
+        let record = db.job_record("queued", "ada@example.com").await.unwrap().unwrap();
+        assert_eq!((record.status.as_str(), record.result), ("queued", None));
+        let record = db.job_record("done", "ada@example.com").await.unwrap().unwrap();
+        assert_eq!(record.result.unwrap()["counts"]["1"], 544);
+        assert_eq!(db.job_record("done", "eve@example.com").await.unwrap(), None);
+
+        let (counts, source) = db.job_export("done", "ada@example.com").await.unwrap();
+        assert_eq!((counts.get("0"), source.job_id.as_deref()), (Some(&480), Some("done")));
+        let err = db.job_export("queued", "ada@example.com").await.unwrap_err().to_string();
+        assert_eq!(err, "Job queued has no measurement counts (status: queued)");
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_runs_are_stored_for_compare() {
+        let path = std::env::temp_dir().join(format!("qhub-runs-{}.db", Uuid::new_v4()));
+        let db = DatabasePool::from_url(&format!("sqlite://{}?mode=rwc", path.display()), &DbConfig::default())
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO users (id, email, password_hash) VALUES ('u1', 'ada@example.com', 'x')")
+            .execute(db.sqlite().unwrap())
+            .await
+            .unwrap();
+        let run = |name: &str| NewJob { name: name.to_string(), circuit_code: "h q[0];".to_string() };
+        let before: std::collections::BTreeMap<String, usize> = [("00".to_string(), 500), ("11".to_string(), 524)].into();
+        let after: std::collections::BTreeMap<String, usize> =
+            [("00".to_string(), 480), ("01".to_string(), 20), ("11".to_string(), 524)].into();
+        let a = db.record_run("ada@example.com", "simulator", run("bell.qasm"), &before).await.unwrap();
+        let b = db.record_run("ada@example.com", "simulator", run("bell-opt.qasm"), &after).await.unwrap();
+
+        let record = db.job_record(&a, "ada@example.com").await.unwrap().unwrap();
+        assert_eq!((record.status.as_str(), record.name.as_deref()), ("completed", Some("bell.qasm")));
+        // Outcomes only one run saw are compared against 0
+        let comparison = compare::compare(
+            &db.job_counts(&a, Some("ada@example.com")).await.unwrap(),
+            &db.job_counts(&b, Some("ada@example.com")).await.unwrap(),
+        );
+        assert_eq!(comparison.outcomes.len(), 3);
+        assert!(comparison.outcomes.iter().any(|o| o.outcome == "01" && o.probability_a == 0.0));
+        assert!((comparison.total_variation_distance - 20.0 / 1024.0).abs() < 1e-9);
+
+        assert!(db.record_run("eve@example.com", "simulator", run("x"), &before).await.is_err());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_sweep_jobs_on_sqlite() {
+        let path = std::env::temp_dir().join(format!("qhub-sweep-{}.db", Uuid::new_v4()));
+        let db = DatabasePool::from_url(&format!("sqlite://{}?mode=rwc", path.display()), &DbConfig::default())
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO users (id, email, password_hash) VALUES ('u1', 'ada@example.com', 'x')")
+            .execute(db.sqlite().unwrap())
+            .await
+            .unwrap();
+
+        let points: Vec<NewJob> = (0..3)
+            .map(|i| NewJob { name: format!("point {:04}", i), circuit_code: "rz(0) q[0];".to_string() })
+            .collect();
+        assert!(db.create_sweep_jobs("s1", "eve@example.com", "simulator", &points).await.is_err());
+        let ids = db.create_sweep_jobs("s1", "ada@example.com", "simulator", &points).await.unwrap();
+        db.finish_job(&ids[0], Ok(&serde_json::json!({"counts": {"0": 8}}))).await.unwrap();
+        db.finish_job(&ids[1], Err("boom")).await.unwrap();
+        assert_eq!(db.cancel_pending_sweep_jobs("s1").await.unwrap(), 1);
+
+        let jobs = db.sweep_jobs("s1", "ada@example.com").await.unwrap();
+        let statuses: Vec<&str> = jobs.iter().map(|(s, _)| s.as_str()).collect();
+        assert_eq!(statuses, vec!["completed", "failed", "cancelled"]);
+        assert_eq!(db.job_counts(&ids[0], None).await.unwrap().get("0"), Some(&8));
+        assert!(db.sweep_jobs("s1", "eve@example.com").await.is_err());
+        let _ = std::fs::remove_file(path);
+    }
+}
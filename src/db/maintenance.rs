@@ -0,0 +1,254 @@
+// Periodic database maintenance
+//
+// Deletes expired sessions, prunes old login attempts and audit rows, compacts
+// SQLite databases and removes cached job results whose job no longer exists.
+// Runs once from `qhub db maintain` (for cron) or on a schedule while the TUI is open.
+
+use anyhow::{Context, Result};
+use chrono::{Local, Utc};
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use super::manager::PoolManager;
+use super::pool::{with_pool, DatabasePool};
+use crate::config::settings::MaintenanceConfig;
+use crate::config::Config;
+
+/// First retry delay after the database could not be reached
+const INITIAL_BACKOFF: Duration = Duration::from_secs(60);
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Rows and files removed by one maintenance run
+#[derive(Debug, Clone, Default)]
+pub struct MaintenanceReport {
+    pub sessions: u64,
+    pub login_attempts: u64,
+    pub audit_log: u64,
+    pub cache_files: u64,
+    pub duration: Duration,
+}
+
+impl MaintenanceReport {
+    pub fn summary(&self) -> String {
+        format!(
+            "removed {} expired sessions, {} login_attempts rows, {} audit_log rows, {} orphaned cache files in {}ms",
+            self.sessions,
+            self.login_attempts,
+            self.audit_log,
+            self.cache_files,
+            self.duration.as_millis()
+        )
+    }
+}
+
+/// Directory holding cached job results, one `<job_id>.json` per job
+pub fn results_dir() -> Result<PathBuf> {
+    Ok(Config::cache_dir()?.join("results"))
+}
+
+impl DatabasePool {
+    pub async fn run_maintenance(
+        &self,
+        settings: &MaintenanceConfig,
+        results_dir: &Path,
+    ) -> Result<MaintenanceReport> {
+        let started = Instant::now();
+        let now = Utc::now().timestamp();
+        let days = |n: u64| now - n as i64 * SECONDS_PER_DAY;
+
+        let mut report = MaintenanceReport {
+            sessions: self.purge_expired_sessions().await?,
+            login_attempts: self
+                .delete_older_than("login_attempts", "attempted_at", days(settings.login_attempts_retention_days))
+                .await?,
+            audit_log: self
+                .delete_older_than("audit_log", "created_at", days(settings.audit_retention_days))
+                .await?,
+            cache_files: self.remove_orphaned_results(results_dir).await?,
+            ..Default::default()
+        };
+
+        self.compact().await?;
+
+        report.duration = started.elapsed();
+        Ok(report)
+    }
+
+    async fn delete_older_than(&self, table: &str, column: &str, cutoff: i64) -> Result<u64> {
+        let sql = format!("DELETE FROM {} WHERE {} < $1", self.table(table), column);
+        let deleted = with_pool!(self, pool => {
+            sqlx::query(&sql).bind(cutoff).execute(pool).await?.rows_affected()
+        });
+        Ok(deleted)
+    }
+
+    /// Reclaim space and refresh planner statistics
+    async fn compact(&self) -> Result<()> {
+        match self {
+            DatabasePool::Sqlite(pool) => {
+                sqlx::query("VACUUM").execute(pool).await.context("VACUUM failed")?;
+                sqlx::query("ANALYZE").execute(pool).await.context("ANALYZE failed")?;
+            }
+            DatabasePool::Postgres(pool) => {
+                // Autovacuum handles space; just keep statistics fresh
+                sqlx::query("ANALYZE").execute(pool).await.context("ANALYZE failed")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Delete cached result files for jobs that no longer exist
+    async fn remove_orphaned_results(&self, dir: &Path) -> Result<u64> {
+        if !dir.exists() {
+            return Ok(0);
+        }
+
+        let sql = format!("SELECT id FROM {}", self.table("quantum_jobs"));
+        let job_ids: Vec<(String,)> = with_pool!(self, pool => {
+            sqlx::query_as(&sql).fetch_all(pool).await?
+        });
+        let job_ids: HashSet<String> = job_ids.into_iter().map(|(id,)| id).collect();
+
+        let mut removed = 0;
+        for entry in fs::read_dir(dir).context("Failed to read results cache")? {
+            let path = entry?.path();
+            if !path.is_file() {
+                continue;
+            }
+            let Some(job_id) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            if !job_ids.contains(job_id) {
+                fs::remove_file(&path)
+                    .with_context(|| format!("Failed to remove {}", path.display()))?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// Append a timestamped line to ~/.qhub/logs/maintenance.log.
+/// Failures are ignored - logging must never take down the caller.
+pub fn log(message: &str) {
+    let Ok(dir) = Config::logs_dir() else { return };
+    if fs::create_dir_all(&dir).is_err() {
+        return;
+    }
+    if let Ok(mut file) = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join("maintenance.log"))
+    {
+        let _ = writeln!(file, "{} {}", Local::now().format("%Y-%m-%d %H:%M:%S"), message);
+    }
+}
+
+/// Run one maintenance pass through the shared pool manager
+async fn maintain_once(db: &PoolManager, settings: &MaintenanceConfig) -> Result<MaintenanceReport> {
+    let settings = settings.clone();
+    let dir = results_dir()?;
+    db.run(|pool| async move { pool.run_maintenance(&settings, &dir).await })
+        .await
+}
+
+/// Double the retry delay, never waiting longer than the regular interval
+fn next_backoff(current: Duration, max: Duration) -> Duration {
+    (current * 2).min(max)
+}
+
+/// Run maintenance now and then every `interval_hours`, until the task is aborted.
+/// While the database is unreachable, retries with exponential backoff and logs
+/// only the first failure and the recovery.
+pub async fn run_scheduler(db: PoolManager, settings: MaintenanceConfig) {
+    // Validation caps the interval; saturate anyway rather than overflow
+    let interval = Duration::from_secs(settings.interval_hours.saturating_mul(60 * 60));
+    // Give the initial connection a moment so the first run isn't logged as a failure
+    db.wait_connected(Duration::from_secs(30)).await;
+    let mut backoff = INITIAL_BACKOFF.min(interval);
+    let mut failing = false;
+
+    loop {
+        let delay = match maintain_once(&db, &settings).await {
+            Ok(report) => {
+                if failing {
+                    log("database reachable again");
+                }
+                failing = false;
+                backoff = INITIAL_BACKOFF.min(interval);
+                log(&report.summary());
+                interval
+            }
+            Err(e) => {
+                if !failing {
+                    log(&format!("maintenance failed, retrying with backoff: {:#}", e));
+                }
+                failing = true;
+                let delay = backoff;
+                backoff = next_backoff(backoff, interval);
+                delay
+            }
+        };
+        tokio::time::sleep(delay).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_backoff_is_capped_at_interval() {
+        let max = Duration::from_secs(300);
+        let mut delay = INITIAL_BACKOFF;
+        delay = next_backoff(delay, max);
+        assert_eq!(delay, Duration::from_secs(120));
+        delay = next_backoff(delay, max);
+        delay = next_backoff(delay, max);
+        assert_eq!(delay, max);
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_on_sqlite() {
+        let tmp = std::env::temp_dir().join(format!("qhub-maint-{}", Uuid::new_v4()));
+        let results = tmp.join("results");
+        fs::create_dir_all(&results).unwrap();
+        let db = DatabasePool::from_url(&format!("sqlite://{}/qhub.db?mode=rwc", tmp.display()), &Default::default())
+            .await
+            .unwrap();
+        let pool = db.sqlite().unwrap();
+        let now = Utc::now().timestamp();
+
+        sqlx::query("INSERT INTO users (id, email, password_hash) VALUES ('u1', 'ada@example.com', 'x')")
+            .execute(pool).await.unwrap();
+        sqlx::query("INSERT INTO quantum_jobs (id, user_id, circuit_code) VALUES ('job-1', 'u1', 'h q[0];')")
+            .execute(pool).await.unwrap();
+        for (id, expires) in [("s1", now - 10), ("s2", now + 3600)] {
+            sqlx::query("INSERT INTO user_sessions (id, user_id, token_hash, expires_at) VALUES ($1, 'u1', $1, $2)")
+                .bind(id).bind(expires).execute(pool).await.unwrap();
+        }
+        for (id, age_days) in [("a1", 200), ("a2", 1)] {
+            sqlx::query("INSERT INTO audit_log (id, actor, action, created_at) VALUES ($1, 'root', 'stats', $2)")
+                .bind(id).bind(now - age_days * SECONDS_PER_DAY).execute(pool).await.unwrap();
+        }
+        sqlx::query("INSERT INTO login_attempts (id, email, attempted_at) VALUES ('l1', 'ada@example.com', $1)")
+            .bind(now - 45 * SECONDS_PER_DAY).execute(pool).await.unwrap();
+        fs::write(results.join("job-1.json"), "{}").unwrap();
+        fs::write(results.join("job-gone.json"), "{}").unwrap();
+
+        let report = db.run_maintenance(&MaintenanceConfig::default(), &results).await.unwrap();
+        assert_eq!(report.sessions, 1);
+        assert_eq!(report.login_attempts, 1);
+        assert_eq!(report.audit_log, 1);
+        assert_eq!(report.cache_files, 1);
+        assert!(results.join("job-1.json").exists());
+        assert!(!results.join("job-gone.json").exists());
+
+        let _ = fs::remove_dir_all(&tmp);
+    }
+}
@@ -0,0 +1,227 @@
+// Self-healing access to the database pool
+//
+// sqlx pools do not survive the database going away (a Postgres restart, a
+// SQLite file being replaced). `PoolManager` notices connection-level errors,
+// drops the broken pool and reconnects in the background with backoff. While
+// it is degraded, callers fail fast with `DbError::Reconnecting` instead of
+// piling up timeouts, and `health()` lets the UI show the state.
+
+use anyhow::Result;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::RwLock;
+
+use super::DatabasePool;
+use crate::config::settings::DbConfig;
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DbHealth {
+    /// First connection attempt still running
+    Connecting,
+    Connected,
+    /// Connection lost - reconnecting in the background
+    Reconnecting,
+}
+
+#[derive(Debug, Error)]
+pub enum DbError {
+    #[error("Database reconnecting, please try again shortly")]
+    Reconnecting,
+}
+
+#[derive(Clone)]
+pub struct PoolManager {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    url: String,
+    settings: DbConfig,
+    pool: RwLock<Option<DatabasePool>>,
+    health: Mutex<DbHealth>,
+    initial_backoff: Duration,
+}
+
+impl PoolManager {
+    pub fn new(url: String, settings: DbConfig) -> Self {
+        Self::with_backoff(url, settings, INITIAL_BACKOFF)
+    }
+
+    fn with_backoff(url: String, settings: DbConfig, initial_backoff: Duration) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                url,
+                settings,
+                pool: RwLock::new(None),
+                health: Mutex::new(DbHealth::Connecting),
+                initial_backoff,
+            }),
+        }
+    }
+
+    /// Manager for DATABASE_URL, if it is set
+    pub fn from_env(settings: &DbConfig) -> Option<Self> {
+        std::env::var("DATABASE_URL")
+            .ok()
+            .map(|url| Self::new(url, settings.clone()))
+    }
+
+    /// Connect in the background; `health()` reports progress
+    pub fn start(&self) {
+        self.begin_reconnect(DbHealth::Connecting);
+    }
+
+    pub fn health(&self) -> DbHealth {
+        *self.inner.health.lock().unwrap()
+    }
+
+    /// Wait (up to `timeout`) for the first connection attempt to succeed
+    pub async fn wait_connected(&self, timeout: Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+        while self.health() != DbHealth::Connected {
+            if tokio::time::Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        true
+    }
+
+    /// Run `f` with a live pool. Connection failures mark the manager as
+    /// degraded, start a reconnect and surface as `DbError::Reconnecting`.
+    pub async fn run<T, F, Fut>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(DatabasePool) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let pool = self.inner.pool.read().await.clone();
+        let Some(pool) = pool else {
+            return Err(DbError::Reconnecting.into());
+        };
+
+        match f(pool).await {
+            Err(e) if is_connection_error(&e) => {
+                self.mark_degraded().await;
+                Err(anyhow::Error::new(DbError::Reconnecting).context(format!("{:#}", e)))
+            }
+            result => result,
+        }
+    }
+
+    async fn mark_degraded(&self) {
+        // Only the caller that takes the pool starts a reconnect
+        if let Some(pool) = self.inner.pool.write().await.take() {
+            pool.close().await;
+            self.begin_reconnect(DbHealth::Reconnecting);
+        }
+    }
+
+    /// Spawn a loop that connects with backoff and installs the new pool
+    fn begin_reconnect(&self, state: DbHealth) {
+        *self.inner.health.lock().unwrap() = state;
+
+        let inner = self.inner.clone();
+        tokio::spawn(async move {
+            let mut backoff = inner.initial_backoff;
+            loop {
+                if let Ok(pool) = DatabasePool::from_url(&inner.url, &inner.settings).await {
+                    *inner.pool.write().await = Some(pool);
+                    *inner.health.lock().unwrap() = DbHealth::Connected;
+                    return;
+                }
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        });
+    }
+}
+
+/// Whether an error means the connection itself is gone (as opposed to a bad query)
+pub fn is_connection_error(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| match cause.downcast_ref::<sqlx::Error>() {
+        Some(sqlx::Error::Io(_))
+        | Some(sqlx::Error::Tls(_))
+        | Some(sqlx::Error::PoolTimedOut)
+        | Some(sqlx::Error::PoolClosed)
+        | Some(sqlx::Error::WorkerCrashed) => true,
+        // SQLSTATE class 08 (connection exception) and 57P0x (server shutting down)
+        Some(sqlx::Error::Database(db)) => db
+            .code()
+            .map(|code| code.starts_with("08") || code.starts_with("57P0"))
+            .unwrap_or(false),
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use uuid::Uuid;
+
+    async fn count_users(manager: &PoolManager) -> Result<i64> {
+        manager
+            .run(|db| async move {
+                let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM users")
+                    .fetch_one(db.sqlite()?)
+                    .await?;
+                Ok(count)
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_recovers_after_database_file_is_restored() {
+        let dir = std::env::temp_dir().join(format!("qhub-pool-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("qhub.db");
+        let backup: &Path = &dir.join("qhub.db.bak");
+
+        // Create and migrate the file, then only allow opening an existing one
+        DatabasePool::from_url(&format!("sqlite://{}?mode=rwc", db_path.display()), &DbConfig::default())
+            .await
+            .unwrap()
+            .close()
+            .await;
+        let settings = DbConfig { acquire_timeout_secs: 1, ..DbConfig::default() };
+        let manager = PoolManager::with_backoff(
+            format!("sqlite://{}?mode=rw", db_path.display()),
+            settings,
+            Duration::from_millis(20),
+        );
+        manager.start();
+        assert!(manager.wait_connected(Duration::from_secs(5)).await);
+        assert_eq!(count_users(&manager).await.unwrap(), 0);
+
+        // Database goes away: the live pool breaks and the file is missing
+        std::fs::rename(&db_path, backup).unwrap();
+        if let Some(pool) = manager.inner.pool.read().await.as_ref() {
+            pool.close().await;
+        }
+        let err = count_users(&manager).await.unwrap_err();
+        assert!(err.downcast_ref::<DbError>().is_some());
+        assert_eq!(manager.health(), DbHealth::Reconnecting);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(manager.health(), DbHealth::Reconnecting);
+
+        // Database comes back: recovered without a restart
+        std::fs::rename(backup, &db_path).unwrap();
+        assert!(manager.wait_connected(Duration::from_secs(5)).await);
+        assert_eq!(count_users(&manager).await.unwrap(), 0);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_query_errors_are_not_connection_errors() {
+        let err = anyhow::Error::new(sqlx::Error::RowNotFound);
+        assert!(!is_connection_error(&err));
+        let err = anyhow::Error::new(sqlx::Error::PoolClosed).context("while listing users");
+        assert!(is_connection_error(&err));
+    }
+}
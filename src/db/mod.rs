@@ -1,5 +1,52 @@
+pub mod admin;
+pub mod jobs;
+pub mod maintenance;
+pub mod manager;
+#[allow(dead_code)]
 pub mod models;
 pub mod pool;
+pub mod usage;
 
+#[allow(unused_imports)]
 pub use models::*;
 pub use pool::DatabasePool;
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    /// Compile-time checked macros need DATABASE_URL or `.sqlx` metadata to build.
+    /// Queries must use runtime `sqlx::query`/`query_as` so a clean checkout builds.
+    #[test]
+    fn test_no_compile_time_checked_queries() {
+        const MACROS: [&str; 6] = [
+            "query!(",
+            "query_as!(",
+            "query_scalar!(",
+            "query_unchecked!(",
+            "query_as_unchecked!(",
+            "query_file",
+        ];
+
+        fn scan(dir: &Path, offenders: &mut Vec<String>) {
+            for entry in std::fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    scan(&path, offenders);
+                } else if path.extension().is_some_and(|ext| ext == "rs") {
+                    let source = std::fs::read_to_string(&path).unwrap();
+                    for (n, line) in source.lines().enumerate() {
+                        let code = line.trim_start();
+                        if !code.starts_with("//") && MACROS.iter().any(|m| code.contains(&format!("sqlx::{}", m))) {
+                            offenders.push(format!("{}:{}", path.display(), n + 1));
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut offenders = Vec::new();
+        scan(&Path::new(env!("CARGO_MANIFEST_DIR")).join("src"), &mut offenders);
+        assert!(offenders.is_empty(), "compile-time checked queries found: {:?}", offenders);
+    }
+}
@@ -1,8 +1,16 @@
 use chrono::{DateTime, Utc, TimeZone};
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+use sqlx::postgres::PgRow;
+use sqlx::sqlite::SqliteRow;
+use sqlx::{FromRow, Row};
+use uuid::Uuid;
 
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+// `User` and `UserSession` are mapped by hand instead of derived: the unified
+// schema stores TEXT ids and BIGINT/INTEGER unix timestamps on both backends,
+// but Postgres databases created before migration 002 still use UUID and
+// TIMESTAMPTZ columns. Both shapes are converted into the same String/i64 model.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
     pub id: String,  // UUID as TEXT for cross-DB compatibility
     pub email: String,
@@ -11,15 +19,57 @@ pub struct User {
     #[serde(skip_serializing)]
     pub password_hash: Option<String>,
     pub tier: String,
-    #[sqlx(try_from = "i64")]
     pub created_at: i64,  // Unix timestamp for cross-DB compatibility
-    #[sqlx(try_from = "i64")]
     pub updated_at: i64,
     pub last_login_at: Option<i64>,
     pub is_active: bool,
     pub email_verified: bool,
 }
 
+impl User {
+    /// Name to show: display name, then username, then email
+    pub fn name(&self) -> &str {
+        self.display_name.as_deref().or(self.username.as_deref()).unwrap_or(&self.email)
+    }
+}
+
+impl<'r> FromRow<'r, PgRow> for User {
+    fn from_row(row: &'r PgRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            id: pg_id(row, "id")?,
+            email: row.try_get("email")?,
+            username: row.try_get("username")?,
+            display_name: row.try_get("display_name")?,
+            password_hash: row.try_get("password_hash")?,
+            tier: row.try_get("tier")?,
+            created_at: pg_timestamp(row, "created_at")?.unwrap_or_default(),
+            updated_at: pg_timestamp(row, "updated_at")?.unwrap_or_default(),
+            last_login_at: pg_timestamp(row, "last_login_at")?,
+            // Nullable in the Postgres schema, NOT NULL on SQLite
+            is_active: row.try_get::<Option<bool>, _>("is_active")?.unwrap_or(true),
+            email_verified: row.try_get::<Option<bool>, _>("email_verified")?.unwrap_or(false),
+        })
+    }
+}
+
+impl<'r> FromRow<'r, SqliteRow> for User {
+    fn from_row(row: &'r SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            email: row.try_get("email")?,
+            username: row.try_get("username")?,
+            display_name: row.try_get("display_name")?,
+            password_hash: row.try_get("password_hash")?,
+            tier: row.try_get("tier")?,
+            created_at: row.try_get("created_at")?,
+            updated_at: row.try_get("updated_at")?,
+            last_login_at: row.try_get("last_login_at")?,
+            is_active: row.try_get("is_active")?,
+            email_verified: row.try_get("email_verified")?,
+        })
+    }
+}
+
 impl User {
     /// Convert created_at timestamp to DateTime
     pub fn created_at_dt(&self) -> DateTime<Utc> {
@@ -37,21 +87,49 @@ impl User {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserSession {
     pub id: String,
     pub user_id: String,
     pub token_hash: String,
     pub device_info: Option<String>,
     pub ip_address: Option<String>,
-    #[sqlx(try_from = "i64")]
     pub expires_at: i64,
-    #[sqlx(try_from = "i64")]
     pub created_at: i64,
-    #[sqlx(try_from = "i64")]
     pub last_active_at: i64,
 }
 
+impl<'r> FromRow<'r, PgRow> for UserSession {
+    fn from_row(row: &'r PgRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            id: pg_id(row, "id")?,
+            user_id: pg_id(row, "user_id")?,
+            token_hash: row.try_get("token_hash")?,
+            device_info: row.try_get("device_info")?,
+            // INET in the original schema; callers only ever store text
+            ip_address: row.try_get("ip_address").ok().flatten(),
+            expires_at: pg_timestamp(row, "expires_at")?.unwrap_or_default(),
+            created_at: pg_timestamp(row, "created_at")?.unwrap_or_default(),
+            last_active_at: pg_timestamp(row, "last_active_at")?.unwrap_or_default(),
+        })
+    }
+}
+
+impl<'r> FromRow<'r, SqliteRow> for UserSession {
+    fn from_row(row: &'r SqliteRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            user_id: row.try_get("user_id")?,
+            token_hash: row.try_get("token_hash")?,
+            device_info: row.try_get("device_info")?,
+            ip_address: row.try_get("ip_address")?,
+            expires_at: row.try_get("expires_at")?,
+            created_at: row.try_get("created_at")?,
+            last_active_at: row.try_get("last_active_at")?,
+        })
+    }
+}
+
 impl UserSession {
     pub fn expires_at_dt(&self) -> DateTime<Utc> {
         Utc.timestamp_opt(self.expires_at, 0).unwrap()
@@ -133,6 +211,8 @@ pub struct QuantumJob {
     pub created_at: i64,
     pub started_at: Option<i64>,
     pub completed_at: Option<i64>,
+    /// Set on the points of a parameter sweep
+    pub sweep_id: Option<String>,
 }
 
 // DTOs for API requests
@@ -141,6 +221,12 @@ pub struct CreateUserRequest {
     pub email: String,
     pub password: String,
     pub username: Option<String>,
+    pub display_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateProfileRequest {
+    pub display_name: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -161,3 +247,74 @@ impl AuthResponse {
         Utc.timestamp_opt(self.expires_at, 0).unwrap()
     }
 }
+
+/// Read an id stored as TEXT (unified schema) or UUID (pre-002 schema)
+fn pg_id(row: &PgRow, column: &str) -> sqlx::Result<String> {
+    match row.try_get::<String, _>(column) {
+        Ok(id) => Ok(id),
+        Err(_) => Ok(row.try_get::<Uuid, _>(column)?.to_string()),
+    }
+}
+
+/// Read a timestamp stored as BIGINT unix seconds (unified schema) or TIMESTAMPTZ (pre-002 schema)
+fn pg_timestamp(row: &PgRow, column: &str) -> sqlx::Result<Option<i64>> {
+    match row.try_get::<Option<i64>, _>(column) {
+        Ok(ts) => Ok(ts),
+        Err(_) => Ok(row
+            .try_get::<Option<DateTime<Utc>>, _>(column)?
+            .map(|dt| dt.timestamp())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run with `cargo test -- --ignored` and QHUB_TEST_POSTGRES_URL
+    /// pointing at a disposable database
+    #[tokio::test]
+    #[ignore = "needs QHUB_TEST_POSTGRES_URL"]
+    async fn test_pre_unified_postgres_rows_map_to_user() {
+        let url = std::env::var("QHUB_TEST_POSTGRES_URL").expect("QHUB_TEST_POSTGRES_URL is not set");
+        let pool = sqlx::PgPool::connect(&url).await.unwrap();
+        let schema = format!("legacy_{}", Uuid::new_v4().simple());
+
+        // Column types as created by migration 001, before the schema was unified
+        sqlx::query(&format!("CREATE SCHEMA {}", schema)).execute(&pool).await.unwrap();
+        sqlx::query(&format!(
+            "CREATE TABLE {}.users (
+                id UUID PRIMARY KEY, email TEXT NOT NULL, username TEXT, display_name TEXT,
+                password_hash TEXT, tier TEXT NOT NULL, created_at TIMESTAMPTZ,
+                updated_at TIMESTAMPTZ, last_login_at TIMESTAMPTZ,
+                is_active BOOLEAN, email_verified BOOLEAN)",
+            schema
+        ))
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let id = Uuid::new_v4();
+        sqlx::query(&format!(
+            "INSERT INTO {}.users (id, email, tier, created_at, updated_at)
+             VALUES ($1, 'ada@example.com', 'pro', to_timestamp(1700000000), to_timestamp(1700000060))",
+            schema
+        ))
+        .bind(id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let user: User = sqlx::query_as(&format!("SELECT * FROM {}.users", schema))
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        sqlx::query(&format!("DROP SCHEMA {} CASCADE", schema)).execute(&pool).await.unwrap();
+
+        assert_eq!(user.id, id.to_string());
+        assert_eq!(user.created_at, 1_700_000_000);
+        assert_eq!(user.updated_at, 1_700_000_060);
+        assert_eq!(user.last_login_at, None);
+        assert!(user.is_active);
+        assert!(!user.email_verified);
+    }
+}
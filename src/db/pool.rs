@@ -1,6 +1,23 @@
 use anyhow::{Context, Result};
+use sqlx::postgres::PgConnectOptions;
+use sqlx::sqlite::SqliteConnectOptions;
 use sqlx::{Pool, Postgres, Sqlite};
 use std::env;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::config::settings::DbConfig;
+
+/// Run the same sqlx expression against whichever backend a `DatabasePool` wraps
+macro_rules! with_pool {
+    ($db:expr, $pool:ident => $body:expr) => {
+        match $db {
+            $crate::db::DatabasePool::Postgres($pool) => $body,
+            $crate::db::DatabasePool::Sqlite($pool) => $body,
+        }
+    };
+}
+pub(crate) use with_pool;
 
 /// Database pool that supports both PostgreSQL (local) and SQLite (Cloudflare D1)
 #[derive(Clone)]
@@ -11,24 +28,36 @@ pub enum DatabasePool {
 
 impl DatabasePool {
     /// Create a new database pool from environment configuration
-    pub async fn new() -> Result<Self> {
+    pub async fn new(settings: &DbConfig) -> Result<Self> {
         let database_url = env::var("DATABASE_URL")
             .context("DATABASE_URL must be set")?;
 
+        Self::from_url(&database_url, settings).await
+    }
+
+    /// Create a pool for an explicit database URL
+    pub async fn from_url(database_url: &str, settings: &DbConfig) -> Result<Self> {
         if database_url.starts_with("postgres") {
-            Self::new_postgres(&database_url).await
+            Self::new_postgres(database_url, settings).await
         } else if database_url.starts_with("sqlite") || database_url.starts_with("file:") {
-            Self::new_sqlite(&database_url).await
+            Self::new_sqlite(database_url, settings).await
         } else {
             anyhow::bail!("Unsupported database URL format. Use 'postgres://' or 'sqlite://'")
         }
     }
 
     /// Create a PostgreSQL pool (for local development)
-    async fn new_postgres(url: &str) -> Result<Self> {
+    async fn new_postgres(url: &str, settings: &DbConfig) -> Result<Self> {
+        let options = PgConnectOptions::from_str(url)
+            .context("Invalid PostgreSQL URL")?
+            .options([(
+                "statement_timeout",
+                format!("{}s", settings.statement_timeout_secs),
+            )]);
         let pool = sqlx::postgres::PgPoolOptions::new()
-            .max_connections(5)
-            .connect(url)
+            .max_connections(settings.max_connections)
+            .acquire_timeout(Duration::from_secs(settings.acquire_timeout_secs))
+            .connect_with(options)
             .await
             .context("Failed to connect to PostgreSQL")?;
 
@@ -42,16 +71,22 @@ impl DatabasePool {
     }
 
     /// Create a SQLite pool (for Cloudflare D1 compatibility)
-    async fn new_sqlite(url: &str) -> Result<Self> {
+    async fn new_sqlite(url: &str, settings: &DbConfig) -> Result<Self> {
+        // SQLite has no statement timeout; the closest equivalent is how long
+        // a statement waits on a locked database
+        let options = SqliteConnectOptions::from_str(url)
+            .context("Invalid SQLite URL")?
+            .busy_timeout(Duration::from_secs(settings.statement_timeout_secs));
         let pool = sqlx::sqlite::SqlitePoolOptions::new()
-            .max_connections(5)
-            .connect(url)
+            .max_connections(settings.max_connections)
+            .acquire_timeout(Duration::from_secs(settings.acquire_timeout_secs))
+            .connect_with(options)
             .await
             .context("Failed to connect to SQLite")?;
 
         // Run migrations (use D1-compatible schema)
         // Note: In production with D1, migrations are handled via wrangler
-        sqlx::migrate!("./migrations")
+        sqlx::migrate!("./migrations/sqlite")
             .run(&pool)
             .await
             .context("Failed to run SQLite migrations")?;
@@ -60,6 +95,7 @@ impl DatabasePool {
     }
 
     /// Get the underlying postgres pool
+    #[allow(dead_code)]
     pub fn postgres(&self) -> Result<&Pool<Postgres>> {
         match self {
             DatabasePool::Postgres(pool) => Ok(pool),
@@ -70,6 +106,7 @@ impl DatabasePool {
     }
 
     /// Get the underlying sqlite pool
+    #[allow(dead_code)]
     pub fn sqlite(&self) -> Result<&Pool<Sqlite>> {
         match self {
             DatabasePool::Sqlite(pool) => Ok(pool),
@@ -79,12 +116,28 @@ impl DatabasePool {
         }
     }
 
+    /// Close all connections; later queries fail with `PoolClosed`
+    pub async fn close(&self) {
+        with_pool!(self, pool => pool.close().await)
+    }
+
+    /// Qualify a table name for the active backend
+    /// (PostgreSQL keeps tables in the `qhub` schema, SQLite has no schemas)
+    pub fn table(&self, name: &str) -> String {
+        match self {
+            DatabasePool::Postgres(_) => format!("qhub.{}", name),
+            DatabasePool::Sqlite(_) => name.to_string(),
+        }
+    }
+
     /// Check if using PostgreSQL
+    #[allow(dead_code)]
     pub fn is_postgres(&self) -> bool {
         matches!(self, DatabasePool::Postgres(_))
     }
 
     /// Check if using SQLite
+    #[allow(dead_code)]
     pub fn is_sqlite(&self) -> bool {
         matches!(self, DatabasePool::Sqlite(_))
     }
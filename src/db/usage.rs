@@ -0,0 +1,117 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use uuid::Uuid;
+
+use super::pool::{with_pool, DatabasePool};
+
+/// `usage_records.resource_type` for AI tokens consumed
+pub const AI_TOKENS: &str = "ai_tokens";
+
+/// What counts against the monthly quotas, over some period
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PeriodUsage {
+    pub tokens: u64,
+    pub jobs: u64,
+}
+
+impl DatabasePool {
+    /// Add a usage record for an active user. Unknown users are ignored.
+    pub async fn record_usage(&self, email: &str, resource_type: &str, count: i64) -> Result<()> {
+        let sql = format!(
+            "INSERT INTO {} (id, user_id, resource_type, resource_count, metadata, created_at)
+             SELECT $1, id, $2, $3, '{{}}', $4 FROM {} WHERE email = $5",
+            self.table("usage_records"),
+            self.table("users")
+        );
+        let id = Uuid::new_v4().to_string();
+        let count = i32::try_from(count).unwrap_or(i32::MAX);
+        let now = Utc::now().timestamp();
+        with_pool!(self, pool => {
+            sqlx::query(&sql)
+                .bind(&id)
+                .bind(resource_type)
+                .bind(count)
+                .bind(now)
+                .bind(email)
+                .execute(pool)
+                .await
+                .context("Failed to record usage")?;
+        });
+        Ok(())
+    }
+
+    /// All-time usage per resource type for one user
+    pub async fn user_usage_totals(&self, email: &str) -> Result<Vec<(String, i64)>> {
+        let sql = format!(
+            "SELECT r.resource_type, SUM(r.resource_count)
+             FROM {} r JOIN {} u ON u.id = r.user_id
+             WHERE u.email = $1
+             GROUP BY r.resource_type ORDER BY r.resource_type",
+            self.table("usage_records"),
+            self.table("users")
+        );
+        let totals = with_pool!(self, pool => {
+            sqlx::query_as(&sql).bind(email).fetch_all(pool).await?
+        });
+        Ok(totals)
+    }
+
+    /// AI tokens used and jobs created by one user since `since` (unix seconds)
+    pub async fn usage_since(&self, email: &str, since: i64) -> Result<PeriodUsage> {
+        let sql = format!(
+            "SELECT
+                (SELECT COALESCE(SUM(r.resource_count), 0) FROM {records} r JOIN {users} u ON u.id = r.user_id
+                 WHERE u.email = $1 AND r.resource_type = $2 AND r.created_at >= $3),
+                (SELECT COUNT(*) FROM {jobs} j JOIN {users} u ON u.id = j.user_id
+                 WHERE u.email = $1 AND j.created_at >= $3)",
+            records = self.table("usage_records"),
+            jobs = self.table("quantum_jobs"),
+            users = self.table("users")
+        );
+        let (tokens, jobs): (i64, i64) = with_pool!(self, pool => {
+            sqlx::query_as(&sql).bind(email).bind(AI_TOKENS).bind(since).fetch_one(pool).await?
+        });
+        Ok(PeriodUsage { tokens: tokens.max(0) as u64, jobs: jobs.max(0) as u64 })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::settings::DbConfig;
+    use crate::db::jobs::NewJob;
+
+    #[tokio::test]
+    async fn test_usage_totals_on_sqlite() {
+        let path = std::env::temp_dir().join(format!("qhub-usage-{}.db", Uuid::new_v4()));
+        let db = DatabasePool::from_url(&format!("sqlite://{}?mode=rwc", path.display()), &DbConfig::default())
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO users (id, email, password_hash) VALUES ('u1', 'ada@example.com', 'x')")
+            .execute(db.sqlite().unwrap())
+            .await
+            .unwrap();
+
+        db.record_usage("ada@example.com", AI_TOKENS, 120).await.unwrap();
+        db.record_usage("ada@example.com", AI_TOKENS, 30).await.unwrap();
+        db.record_usage("ada@example.com", "circuits_run", 1).await.unwrap();
+        db.record_usage("nobody@example.com", AI_TOKENS, 999).await.unwrap();
+
+        let totals = db.user_usage_totals("ada@example.com").await.unwrap();
+        assert_eq!(
+            totals,
+            vec![("ai_tokens".to_string(), 150), ("circuits_run".to_string(), 1)]
+        );
+        assert!(db.user_usage_totals("nobody@example.com").await.unwrap().is_empty());
+
+        db.create_jobs(None, "ada@example.com", "simulator", &[NewJob { name: "bell".to_string(), circuit_code: String::new() }])
+            .await
+            .unwrap();
+        let month = db.usage_since("ada@example.com", 0).await.unwrap();
+        assert_eq!(month, PeriodUsage { tokens: 150, jobs: 1 });
+        let future = Utc::now().timestamp() + 60;
+        assert_eq!(db.usage_since("ada@example.com", future).await.unwrap(), PeriodUsage::default());
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,132 @@
+// Warnings and notices from anywhere in qhub
+//
+// Library code must not print: once the TUI owns the terminal, anything
+// written to stderr lands on the alternate screen. Components `post` a
+// `Diagnostic` instead. While the TUI runs they are queued and drained into
+// the chat each tick (info goes to the status bar); otherwise, as in CLI
+// commands, they go straight to stderr.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    /// Shown briefly in the status bar
+    Info,
+    /// Added to the chat
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub level: Level,
+    pub text: String,
+}
+
+/// Set while the TUI is collecting diagnostics
+static CAPTURING: AtomicBool = AtomicBool::new(false);
+
+static QUEUE: Mutex<Vec<Diagnostic>> = Mutex::new(Vec::new());
+
+pub fn post(level: Level, text: impl Into<String>) {
+    let diagnostic = Diagnostic { level, text: text.into() };
+    if CAPTURING.load(Ordering::SeqCst) {
+        if let Ok(mut queue) = QUEUE.lock() {
+            queue.push(diagnostic);
+            return;
+        }
+    }
+    print(&diagnostic);
+}
+
+pub fn info(text: impl Into<String>) {
+    post(Level::Info, text);
+}
+
+pub fn warn(text: impl Into<String>) {
+    post(Level::Warning, text);
+}
+
+/// Detail for tracking down a problem, posted as info only when QHUB_DEBUG
+/// is set
+pub fn debug(text: impl Into<String>) {
+    if std::env::var_os("QHUB_DEBUG").is_some() {
+        post(Level::Info, text);
+    }
+}
+
+/// Queue diagnostics for the TUI instead of printing them
+pub fn capture() {
+    CAPTURING.store(true, Ordering::SeqCst);
+}
+
+/// Back to stderr once the terminal is restored, printing anything the TUI
+/// did not collect
+pub fn release() {
+    CAPTURING.store(false, Ordering::SeqCst);
+    for diagnostic in drain() {
+        print(&diagnostic);
+    }
+}
+
+/// Everything posted since the last drain, oldest first
+pub fn drain() -> Vec<Diagnostic> {
+    QUEUE.lock().map(|mut queue| std::mem::take(&mut *queue)).unwrap_or_default()
+}
+
+fn print(diagnostic: &Diagnostic) {
+    match diagnostic.level {
+        Level::Info => eprintln!("{}", diagnostic.text),
+        Level::Warning => eprintln!("⚠️  {}", diagnostic.text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_capture_queues_until_drained() {
+        capture();
+        warn("config.toml is invalid");
+        info("Welcome back");
+        let drained = drain();
+        assert!(drained.contains(&Diagnostic { level: Level::Warning, text: "config.toml is invalid".into() }));
+        assert!(drained.contains(&Diagnostic { level: Level::Info, text: "Welcome back".into() }));
+        assert!(!drain().contains(&Diagnostic { level: Level::Info, text: "Welcome back".into() }));
+        release();
+    }
+
+    /// Only the CLI front end (main.rs and src/cli) may write to stderr
+    /// directly; everything else posts a diagnostic
+    #[test]
+    fn test_no_eprintln_outside_cli() {
+        fn scan(dir: &Path, offenders: &mut Vec<String>) {
+            for entry in std::fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    if !path.ends_with("cli") {
+                        scan(&path, offenders);
+                    }
+                    continue;
+                }
+                if path.extension().is_none_or(|ext| ext != "rs")
+                    || path.ends_with("main.rs")
+                    || path.ends_with("diagnostics.rs")
+                {
+                    continue;
+                }
+                let source = std::fs::read_to_string(&path).unwrap();
+                for (number, line) in source.lines().enumerate() {
+                    if line.contains("eprintln!") || line.contains("eprint!") {
+                        offenders.push(format!("{}:{}", path.display(), number + 1));
+                    }
+                }
+            }
+        }
+        let mut offenders = Vec::new();
+        scan(&Path::new(env!("CARGO_MANIFEST_DIR")).join("src"), &mut offenders);
+        assert!(offenders.is_empty(), "use diagnostics::post instead of eprintln: {:?}", offenders);
+    }
+}
@@ -3,100 +3,199 @@ mod tui;
 mod config;
 mod api;
 mod quantum;
+mod db;
+mod diagnostics;
+mod auth;
 
 use anyhow::Result;
 use clap::Parser;
-use crossterm::{
-    event::{DisableMouseCapture, EnableMouseCapture},
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-};
 use ratatui::prelude::*;
 use std::io;
-use std::time::Duration;
+use std::time::Instant;
 
 use cli::Args;
-use config::Config;
-use tui::{app::App, input, ui};
+use config::{Config, ConfigLocator};
+use tui::{editor, input, terminal, ui, App, AppOptions};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // Load environment variables from .env file (development)
     // In production, env vars are set via deployment system
-    dotenv::dotenv().ok();
+    config::env::load_dotenv();
     
     let args = Args::parse();
+    let locator = ConfigLocator::resolve(args.config.clone(), std::env::var_os("QHUB_CONFIG"))?;
 
     // Ensure config directories exist
     Config::ensure_dirs()?;
 
     match args.command {
-        Some(cli::Command::Run { file }) => {
-            cli::commands::execute_run(&file).await?;
+        Some(cli::Command::Run { file, offline }) => {
+            let outcome = cli::commands::execute_run(&locator, &file, offline).await?;
+            print!("{}", outcome.report());
+            if outcome.status == cli::run::RunStatus::Interrupted {
+                std::process::exit(cli::interrupt::EXIT_CODE);
+            }
+        }
+        Some(cli::Command::Sweep { file, params, shots, backend, select, output }) => {
+            cli::commands::execute_sweep(&locator, &file, &params, shots, &backend, select.as_deref(), output.as_deref()).await?;
+        }
+        Some(cli::Command::Batch { dir, backend, shots, concurrency, output }) => {
+            cli::commands::execute_batch(&locator, &dir, &backend, shots, concurrency, output.as_deref()).await?;
+        }
+        Some(cli::Command::Estimate { file, backend, shots }) => {
+            cli::commands::execute_estimate(&locator, &file, backend.as_deref(), shots).await?;
+        }
+        Some(cli::Command::Admin { admin_token, json, command }) => {
+            cli::admin::execute_admin(&locator, command, admin_token, json).await?;
+        }
+        Some(cli::Command::Serve { socket, .. }) => {
+            cli::serve::execute_serve(&locator, socket.as_deref()).await?;
+        }
+        Some(cli::Command::Db { command: cli::DbCommand::Maintain }) => {
+            cli::commands::execute_maintain(&locator).await?;
+        }
+        Some(cli::Command::Config { command: cli::ConfigCommand::List }) => {
+            cli::commands::execute_config_list(&locator)?;
+        }
+        Some(cli::Command::Config { command: cli::ConfigCommand::Validate }) => {
+            cli::commands::execute_config_validate(&locator)?;
+        }
+        Some(cli::Command::Quantum { command: cli::QuantumCommand::ImportQiskit { file, account, dry_run } }) => {
+            cli::commands::execute_import_qiskit(&locator, file.as_deref(), account.as_deref(), dry_run).await?;
+        }
+        Some(cli::Command::Jobs { command: cli::JobsCommand::Compare { job_a, job_b, json } }) => {
+            cli::commands::execute_compare(&locator, &job_a, &job_b, json).await?;
+        }
+        Some(cli::Command::Jobs { command: cli::JobsCommand::Export { id, format, order, out } }) => {
+            cli::commands::execute_export(&locator, &id, format, order, &out).await?;
+        }
+        Some(cli::Command::Security { command: cli::SecurityCommand::Audit { fix } }) => {
+            cli::commands::execute_security_audit(&locator, fix)?;
+        }
+        Some(cli::Command::Transcript { command: cli::TranscriptCommand::Grep { pattern, regex, context } }) => {
+            api::transcript::execute_grep(&pattern, regex, context)?;
+        }
+        Some(cli::Command::Update { check }) => {
+            cli::update::execute_update(&locator, check).await?;
         }
         None => {
-            run_tui().await?;
+            let options = AppOptions {
+                resume: args.resume,
+                session: args.session,
+                new_session: args.new,
+                config: locator,
+                accessible: args.accessible,
+                quiet: args.quiet,
+            };
+            run_tui(args.skip_setup, options).await?;
         }
     }
 
     Ok(())
 }
 
-async fn run_tui() -> Result<()> {
-    // Setup terminal with panic handler for proper cleanup
-    let original_hook = std::panic::take_hook();
-    std::panic::set_hook(Box::new(move |panic_info| {
-        // Restore terminal on panic
-        let _ = disable_raw_mode();
-        let _ = execute!(
-            io::stdout(),
-            DisableMouseCapture,
-            LeaveAlternateScreen
+async fn run_tui(skip_setup: bool, options: AppOptions) -> Result<()> {
+    // Pipes, CI runners and dumb terminals can't host the full-screen UI
+    if let Some(reason) = terminal::check_support() {
+        anyhow::bail!(
+            "the interactive UI needs a terminal ({}).\n\
+             For scripts and editors use `qhub serve` (JSON-RPC on stdin/stdout), \
+             `qhub run <file>` to check a program, or `qhub transcript grep` to search past answers.",
+            reason
         );
-        original_hook(panic_info);
-    }));
-    
-    // Setup terminal
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
+    }
+
+    // Warnings from here on are shown in the chat rather than printed over it
+    diagnostics::capture();
+    let mut app = App::new(options);
+
+    // Setup terminal with panic handler for proper cleanup
+    terminal::install_panic_hook();
+    let mut session = terminal::Session::start(app.config.ui.mouse_capture)?;
+    terminal::watch_sigtstp()?;
+    terminal::watch_termination()?;
+    let backend = CrosstermBackend::new(io::stdout());
     let mut terminal = Terminal::new(backend)?;
 
-    // Create app state
-    let mut app = App::new();
+    if app.is_first_run && !skip_setup {
+        app.start_wizard();
+    }
 
-    // Main loop
-    let tick_rate = Duration::from_millis(50);
+    // Direct database access (and its housekeeping) only when DATABASE_URL is set
+    if let Some(db) = db::manager::PoolManager::from_env(&app.config.db) {
+        db.start();
+        if app.config.db.maintenance.enabled {
+            let settings = app.config.db.maintenance.clone();
+            app.tasks.spawn(db::maintenance::run_scheduler(db.clone(), settings));
+        }
+        app.db = Some(db);
+        app.refresh_capabilities();
+    }
+
+    // Main loop - draws only when something changed, and blocks on input for
+    // longer while no requests are in flight
     loop {
-        // Check for AI responses
-        app.check_ai_response();
-        
-        // Check for auth responses
-        app.check_auth_response();
+        // Collect AI, auth, key test, logout and usage results
+        if app.poll_background() {
+            app.pacer.mark_dirty();
+        }
         
         // Draw UI
-        terminal.draw(|f| ui::render(f, &mut app))?;
+        let now = Instant::now();
+        if app.pacer.take_frame(now) {
+            terminal.draw(|f| ui::render(f, &mut app))?;
+            if let Some(at) = app.next_timed_redraw(now) {
+                app.pacer.schedule(at);
+            }
+        }
+
+        // /edit: hand the terminal to the user's editor, then take it back
+        if let Some(request) = app.pending_edit.take() {
+            session.suspend()?;
+            let outcome = editor::launch(&request).await;
+            session.resume()?;
+            terminal.clear()?;
+            app.finish_edit(request, outcome);
+            app.pacer.mark_dirty();
+            continue;
+        }
+
+        // Ctrl+Z / SIGTSTP: back to the shell until `fg`
+        if terminal::take_suspend_request() {
+            if session.background()? {
+                terminal.clear()?;
+            }
+            app.pacer.mark_dirty();
+            continue;
+        }
+
+        // SIGTERM / SIGHUP: leave through the normal shutdown below
+        if terminal::termination_signal().is_some() {
+            break;
+        }
 
         // Check for exit
-        if app.should_quit || input::handle_events(&mut app, tick_rate)? {
+        let timeout = app.pacer.poll_timeout(Instant::now(), app.is_busy());
+        if app.should_quit || input::handle_events(&mut app, timeout)? {
             break;
         }
     }
 
-    // Restore terminal - order matters!
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        DisableMouseCapture,
-        LeaveAlternateScreen
-    )?;
-    terminal.show_cursor()?;
-    
-    // Explicit ANSI reset to prevent escape code leakage
-    // This ensures any residual formatting is cleared
-    print!("\x1b[0m");
-    std::io::Write::flush(&mut std::io::stdout())?;
+    // Restore terminal
+    session.suspend()?;
+    diagnostics::release();
 
+    // Abort in-flight requests, but let a pending logout settle first
+    if app.tasks.pending_critical() > 0 {
+        eprintln!("Finishing logout...");
+    }
+    for warning in app.shutdown().await {
+        eprintln!("⚠️  {}", warning);
+    }
+
+    if let Some(signal) = terminal::termination_signal() {
+        std::process::exit(terminal::signal_exit_code(signal));
+    }
     Ok(())
 }
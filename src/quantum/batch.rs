@@ -0,0 +1,203 @@
+// Batch runs
+//
+// `qhub batch <dir>` runs every circuit in a directory and writes one summary
+// of what happened to each: the job id it was stored under, its status and
+// the most frequent outcomes. Circuits are OpenQASM (`.qasm`); `.qqb` files
+// are picked up too so they show in the summary, but are skipped until that
+// format can be executed.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+/// Outcomes listed per circuit in the summary
+const TOP_OUTCOMES: usize = 3;
+
+/// Extensions picked up from the directory
+const EXTENSIONS: [&str; 2] = ["qasm", "qqb"];
+
+/// Circuit files directly in `dir`, sorted by name
+pub fn discover(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file())
+        .filter(|path| path.extension().and_then(|e| e.to_str()).is_some_and(|e| EXTENSIONS.contains(&e)))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Completed,
+    Failed,
+    /// Not a format the batch can run
+    Skipped,
+    /// Not started before Ctrl+C
+    Cancelled,
+}
+
+impl Status {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Status::Completed => "completed",
+            Status::Failed => "failed",
+            Status::Skipped => "skipped",
+            Status::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// What happened to one file
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Entry {
+    pub file: String,
+    /// Set when the run was stored as a quantum job
+    pub job_id: Option<String>,
+    pub status: Status,
+    pub shots: usize,
+    /// Most frequent outcomes first
+    pub top_outcomes: Vec<(String, usize)>,
+    pub error: Option<String>,
+}
+
+impl Entry {
+    pub fn new(file: &str, status: Status) -> Self {
+        Self { file: file.to_string(), job_id: None, status, shots: 0, top_outcomes: Vec::new(), error: None }
+    }
+
+    pub fn completed(file: &str, shots: usize, counts: &BTreeMap<String, usize>) -> Self {
+        Self { shots, top_outcomes: top_outcomes(counts), ..Self::new(file, Status::Completed) }
+    }
+
+    pub fn failed(file: &str, error: impl ToString) -> Self {
+        Self { error: Some(error.to_string()), ..Self::new(file, Status::Failed) }
+    }
+
+    /// One progress line: "bell.qasm ✓ 00 50.2%, 11 49.8%"
+    pub fn progress(&self) -> String {
+        match self.status {
+            Status::Completed => {
+                let outcomes: Vec<String> = self
+                    .top_outcomes
+                    .iter()
+                    .map(|(bits, n)| format!("{} {:.1}%", bits, *n as f64 * 100.0 / self.shots.max(1) as f64))
+                    .collect();
+                format!("{} ✓ {}", self.file, outcomes.join(", "))
+            }
+            status => format!(
+                "{} {}: {}",
+                self.file,
+                status.as_str(),
+                self.error.as_deref().unwrap_or("no details")
+            ),
+        }
+    }
+}
+
+fn top_outcomes(counts: &BTreeMap<String, usize>) -> Vec<(String, usize)> {
+    let mut sorted: Vec<(String, usize)> = counts.iter().map(|(bits, &n)| (bits.clone(), n)).collect();
+    sorted.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    sorted.truncate(TOP_OUTCOMES);
+    sorted
+}
+
+/// Quote a CSV cell when it needs it
+pub(super) fn cell(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+pub fn csv(entries: &[Entry]) -> String {
+    let mut text = String::from("file,job_id,status,shots,top_outcomes,error\n");
+    for entry in entries {
+        let outcomes: Vec<String> = entry.top_outcomes.iter().map(|(bits, n)| format!("{}:{}", bits, n)).collect();
+        let _ = writeln!(
+            text,
+            "{},{},{},{},{},{}",
+            cell(&entry.file),
+            entry.job_id.as_deref().unwrap_or_default(),
+            entry.status.as_str(),
+            entry.shots,
+            outcomes.join(" "),
+            cell(entry.error.as_deref().unwrap_or_default())
+        );
+    }
+    text
+}
+
+pub fn json(entries: &[Entry]) -> Result<String> {
+    Ok(serde_json::to_string_pretty(entries)?)
+}
+
+/// "12 circuits: 10 completed, 1 failed, 1 skipped"
+pub fn totals(entries: &[Entry]) -> String {
+    let mut by_status: Vec<(Status, usize)> = Vec::new();
+    for entry in entries {
+        match by_status.iter_mut().find(|(s, _)| *s == entry.status) {
+            Some((_, n)) => *n += 1,
+            None => by_status.push((entry.status, 1)),
+        }
+    }
+    let parts: Vec<String> = by_status.iter().map(|(s, n)| format!("{} {}", n, s.as_str())).collect();
+    format!("{} circuits: {}", entries.len(), parts.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discover() {
+        let dir = std::env::temp_dir().join(format!("qhub-batch-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        for name in ["b.qasm", "a.qqb", "notes.txt", "nested/c.qasm"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        let files: Vec<String> = discover(&dir)
+            .unwrap()
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(files, vec!["a.qqb", "b.qasm"]);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_summary() {
+        let counts = BTreeMap::from([
+            ("00".to_string(), 500),
+            ("11".to_string(), 480),
+            ("01".to_string(), 30),
+            ("10".to_string(), 14),
+        ]);
+        let mut bell = Entry::completed("bell.qasm", 1024, &counts);
+        bell.job_id = Some("j1".to_string());
+        let entries = vec![
+            bell,
+            Entry::failed("ghz.qasm", "line 3: unknown gate `foo`, expected h, x, ..."),
+            Entry { error: Some("the .qqb format is not supported yet".to_string()), ..Entry::new("old.qqb", Status::Skipped) },
+        ];
+
+        assert_eq!(entries[0].top_outcomes, vec![("00".to_string(), 500), ("11".to_string(), 480), ("01".to_string(), 30)]);
+        assert_eq!(entries[0].progress(), "bell.qasm ✓ 00 48.8%, 11 46.9%, 01 2.9%");
+        assert_eq!(
+            csv(&entries),
+            "file,job_id,status,shots,top_outcomes,error\n\
+             bell.qasm,j1,completed,1024,00:500 11:480 01:30,\n\
+             ghz.qasm,,failed,0,,\"line 3: unknown gate `foo`, expected h, x, ...\"\n\
+             old.qqb,,skipped,0,,the .qqb format is not supported yet\n"
+        );
+        let parsed: serde_json::Value = serde_json::from_str(&json(&entries).unwrap()).unwrap();
+        assert_eq!(parsed[0]["status"], "completed");
+        assert_eq!(parsed[0]["top_outcomes"][0], serde_json::json!(["00", 500]));
+        assert_eq!(totals(&entries), "3 circuits: 1 completed, 1 failed, 1 skipped");
+    }
+}
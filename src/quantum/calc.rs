@@ -0,0 +1,180 @@
+// Quick state-vector experiments (/calc)
+//
+// `/calc H 0; CX 0 1` applies gates to |0…0⟩ on the local simulator and
+// lists the amplitudes and probabilities that result, with no AI or backend
+// involved, so the answer is instant and always the same. Statements are
+// separated by `;`: a standard gate in any case, its parameters in brackets
+// when it takes some, then the qubits it acts on - `RZ(pi/2) 1`,
+// `ccx 0 1 2`. The register is as wide as the highest qubit used, capped at
+// `MAX_QUBITS` since every basis state may end up listed.
+
+use std::fmt::Write as _;
+use thiserror::Error;
+
+use super::qasm::{self, Circuit, Operation, QasmVersion, Register};
+use super::simulator::{self, SimulatorError};
+
+/// Widest register /calc builds; 2^6 rows is still readable
+pub const MAX_QUBITS: usize = 6;
+
+/// Probabilities below this are left out of the listing
+const NEGLIGIBLE: f64 = 1e-12;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum CalcError {
+    #[error("nothing to evaluate; try /calc H 0; CX 0 1")]
+    Empty,
+    #[error("`{0}` is not a standard gate")]
+    UnknownGate(String),
+    #[error("`{statement}`: {gate} acts on {expected} qubit(s), got {got}")]
+    QubitCount { statement: String, gate: String, expected: usize, got: usize },
+    #[error("`{0}`: qubits are numbers like 0 or 1")]
+    BadQubit(String),
+    #[error("`{0}`: a qubit is used twice")]
+    RepeatedQubit(String),
+    #[error("`{0}`: unclosed bracket")]
+    Unclosed(String),
+    #[error("qubit {qubit} is out of range; /calc handles up to {max} qubits")]
+    TooManyQubits { qubit: usize, max: usize },
+    #[error(transparent)]
+    Simulator(#[from] SimulatorError),
+}
+
+/// The circuit `input` describes
+pub fn parse(input: &str) -> Result<Circuit, CalcError> {
+    let mut operations = Vec::new();
+    for statement in input.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+        operations.push(parse_statement(statement)?);
+    }
+    let width = operations
+        .iter()
+        .filter_map(|op| match op {
+            Operation::Gate { qubits, .. } => qubits.iter().max().map(|q| q + 1),
+            _ => None,
+        })
+        .max()
+        .ok_or(CalcError::Empty)?;
+    Ok(Circuit {
+        version: QasmVersion::V3,
+        qregs: vec![Register { name: "q".to_string(), size: width, offset: 0 }],
+        cregs: Vec::new(),
+        custom_gates: Vec::new(),
+        operations,
+    })
+}
+
+fn parse_statement(statement: &str) -> Result<Operation, CalcError> {
+    // The gate and its bracketed parameters, which may contain spaces
+    let (head, rest) = match statement.find('(') {
+        Some(open) if !statement[..open].contains(char::is_whitespace) => {
+            let close = statement.find(')').ok_or_else(|| CalcError::Unclosed(statement.to_string()))?;
+            statement.split_at(close + 1)
+        }
+        _ => statement.split_once(char::is_whitespace).unwrap_or((statement, "")),
+    };
+    let (name, params) = match head.split_once('(') {
+        Some((name, params)) => {
+            let params = params.trim_end_matches(')');
+            (name.trim(), params.split(',').map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).collect())
+        }
+        None => (head.trim(), Vec::new()),
+    };
+    let name = name.to_ascii_lowercase();
+    let expected = qasm::standard_gate_qubits(&name).ok_or_else(|| CalcError::UnknownGate(name.clone()))?;
+
+    let mut qubits = Vec::new();
+    for token in rest.split(|c: char| c.is_whitespace() || c == ',').filter(|t| !t.is_empty()) {
+        let qubit: usize = token.parse().map_err(|_| CalcError::BadQubit(statement.to_string()))?;
+        if qubit >= MAX_QUBITS {
+            return Err(CalcError::TooManyQubits { qubit, max: MAX_QUBITS });
+        }
+        if qubits.contains(&qubit) {
+            return Err(CalcError::RepeatedQubit(statement.to_string()));
+        }
+        qubits.push(qubit);
+    }
+    if qubits.len() != expected {
+        return Err(CalcError::QubitCount { statement: statement.to_string(), gate: name, expected, got: qubits.len() });
+    }
+    Ok(Operation::Gate { name, params, qubits })
+}
+
+/// Run `input` and list the basis states it ends in, highest qubit first
+pub fn evaluate(input: &str) -> Result<String, CalcError> {
+    let circuit = parse(input)?;
+    let state = simulator::statevector(&circuit)?;
+    let qubits = circuit.num_qubits();
+    let mut report = format!(
+        "{} gate(s) on {} qubit(s), qubit 0 rightmost:\n",
+        circuit.operations.len(),
+        qubits
+    );
+    for (index, (re, im)) in state.into_iter().enumerate() {
+        let probability = re * re + im * im;
+        if probability < NEGLIGIBLE {
+            continue;
+        }
+        let _ = writeln!(
+            report,
+            "|{:0width$b}⟩  {:<18} {:>6.2}%",
+            index,
+            amplitude(re, im),
+            probability * 100.0,
+            width = qubits
+        );
+    }
+    Ok(report)
+}
+
+/// `re`+`im`i to four places, leaving out a part that is zero
+fn amplitude(re: f64, im: f64) -> String {
+    let part = |x: f64| if x.abs() < 5e-5 { 0.0 } else { x };
+    let (re, im) = (part(re), part(im));
+    if im == 0.0 {
+        format!("{:+.4}", re)
+    } else if re == 0.0 {
+        format!("{:+.4}i", im)
+    } else {
+        format!("{:+.4}{:+.4}i", re, im)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bell_state() {
+        assert_eq!(
+            evaluate("H 0; CX 0 1").unwrap(),
+            "2 gate(s) on 2 qubit(s), qubit 0 rightmost:\n\
+             |00⟩  +0.7071             50.00%\n\
+             |11⟩  +0.7071             50.00%\n"
+        );
+    }
+
+    #[test]
+    fn test_parameters_and_phases() {
+        let report = evaluate("h 0; rz(pi / 2) 0").unwrap();
+        assert!(report.contains("|0⟩  +0.5000-0.5000i"), "{}", report);
+        assert!(report.contains("|1⟩  +0.5000+0.5000i"), "{}", report);
+        // Untouched lower qubits still count towards the width
+        assert!(evaluate("x 2").unwrap().contains("|100⟩  +1.0000"));
+        assert!(evaluate("S 0").unwrap().contains("|0⟩  +1.0000"));
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        assert_eq!(evaluate("  ; "), Err(CalcError::Empty));
+        assert_eq!(evaluate("foo 0"), Err(CalcError::UnknownGate("foo".to_string())));
+        assert!(matches!(evaluate("cx 0"), Err(CalcError::QubitCount { expected: 2, got: 1, .. })));
+        assert!(matches!(evaluate("h q0"), Err(CalcError::BadQubit(_))));
+        assert!(matches!(evaluate("cx 1 1"), Err(CalcError::RepeatedQubit(_))));
+        assert!(matches!(evaluate("rz(pi 0"), Err(CalcError::Unclosed(_))));
+        assert_eq!(evaluate("h 6"), Err(CalcError::TooManyQubits { qubit: 6, max: MAX_QUBITS }));
+        assert!(matches!(
+            evaluate("rz 0"),
+            Err(CalcError::Simulator(SimulatorError::ParameterCount { expected: 1, got: 0, .. }))
+        ));
+    }
+}
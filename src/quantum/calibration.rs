@@ -0,0 +1,328 @@
+// Calibration details of one backend, for /backend-info
+//
+// IBM publishes each device's configuration (basis gates, coupling map) and
+// its latest calibration (T1/T2 and error rates per qubit and gate). Per-qubit
+// figures are summed up as median and range, which is what tells devices
+// apart; the full tables are in IBM's own dashboard. The coupling map is drawn
+// as a chain when the device is a line or ring, and as a neighbour list
+// otherwise.
+
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use super::estimate::{parameter, parameter_seconds, TWO_QUBIT};
+
+/// Qubits listed with their neighbours before the rest are counted
+const MAX_NEIGHBOUR_LINES: usize = 16;
+
+/// Columns a drawn chain wraps at
+const CHAIN_WIDTH: usize = 60;
+
+/// Median and range of a per-qubit or per-gate figure
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spread {
+    pub median: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Spread {
+    pub fn of(mut values: Vec<f64>) -> Option<Self> {
+        values.retain(|v| v.is_finite());
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_by(f64::total_cmp);
+        let mid = values.len() / 2;
+        let median = if values.len().is_multiple_of(2) { (values[mid - 1] + values[mid]) / 2.0 } else { values[mid] };
+        Some(Spread { median, min: values[0], max: values[values.len() - 1] })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BackendInfo {
+    pub name: String,
+    pub qubits: Option<u32>,
+    pub operational: bool,
+    /// IBM's note on the state, e.g. "active" or "maintenance"
+    pub status_message: Option<String>,
+    pub pending_jobs: Option<u32>,
+    pub basis_gates: Vec<String>,
+    /// Each coupled pair once, lower qubit first
+    pub coupling: Vec<(u32, u32)>,
+    pub t1_us: Option<Spread>,
+    pub t2_us: Option<Spread>,
+    pub readout_error: Option<Spread>,
+    pub one_qubit_error: Option<Spread>,
+    pub two_qubit_error: Option<Spread>,
+    pub calibrated_at: Option<String>,
+}
+
+impl BackendInfo {
+    /// From IBM's `/properties`, `/configuration` and status. Anything
+    /// missing, e.g. on simulators, is left out of the report.
+    pub fn from_ibm(
+        name: &str,
+        properties: &Value,
+        configuration: &Value,
+        operational: bool,
+        status_message: Option<String>,
+        pending_jobs: Option<u32>,
+    ) -> Self {
+        let qubits: Vec<&Value> = properties["qubits"].as_array().into_iter().flatten().collect();
+        let microseconds = |wanted: &str| {
+            Spread::of(qubits.iter().filter_map(|q| parameter_seconds(q, wanted)).map(|s| s * 1e6).collect())
+        };
+
+        let (mut one_qubit, mut two_qubit) = (Vec::new(), Vec::new());
+        for gate in properties["gates"].as_array().into_iter().flatten() {
+            // Virtual gates (rz) report no error; a 1.0 marks a broken gate
+            let Some(error) = parameter(&gate["parameters"], "gate_error").filter(|e| *e > 0.0 && *e < 1.0) else {
+                continue;
+            };
+            let width = gate["qubits"].as_array().map_or(0, |q| q.len());
+            if TWO_QUBIT.contains(&gate["gate"].as_str().unwrap_or_default()) || width == 2 {
+                two_qubit.push(error);
+            } else if width == 1 {
+                one_qubit.push(error);
+            }
+        }
+
+        let mut coupling: Vec<(u32, u32)> = configuration["coupling_map"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|pair| {
+                let a = pair.get(0)?.as_u64()? as u32;
+                let b = pair.get(1)?.as_u64()? as u32;
+                Some((a.min(b), a.max(b)))
+            })
+            .collect();
+        coupling.sort_unstable();
+        coupling.dedup();
+
+        BackendInfo {
+            name: name.to_string(),
+            qubits: configuration["n_qubits"].as_u64().map(|n| n as u32),
+            operational,
+            status_message: status_message.filter(|m| !m.is_empty()),
+            pending_jobs,
+            basis_gates: configuration["basis_gates"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|g| g.as_str().map(str::to_string))
+                .collect(),
+            coupling,
+            t1_us: microseconds("T1"),
+            t2_us: microseconds("T2"),
+            readout_error: Spread::of(qubits.iter().filter_map(|q| parameter(q, "readout_error")).collect()),
+            one_qubit_error: Spread::of(one_qubit),
+            two_qubit_error: Spread::of(two_qubit),
+            calibrated_at: properties["last_update_date"].as_str().map(str::to_string),
+        }
+    }
+
+    /// The /backend-info report
+    pub fn report(&self) -> String {
+        let state = match (self.operational, &self.status_message) {
+            (true, _) => "operational".to_string(),
+            (false, Some(message)) => format!("offline ({})", message),
+            (false, None) => "offline".to_string(),
+        };
+        let queue = match self.pending_jobs {
+            Some(0) => ", no queue".to_string(),
+            Some(jobs) => format!(", {} job(s) queued", jobs),
+            None => String::new(),
+        };
+        let mut text = format!("{} — {}{}", self.name, state, queue);
+        if let Some(qubits) = self.qubits {
+            let _ = write!(text, "\nQubits: {}", qubits);
+        }
+        if !self.basis_gates.is_empty() {
+            let _ = write!(text, "\nBasis gates: {}", self.basis_gates.join(", "));
+        }
+        if let Some(at) = &self.calibrated_at {
+            let _ = write!(text, "\nCalibrated: {}", at);
+        }
+
+        let rows = [
+            ("T1", self.t1_us.map(|s| microseconds(&s))),
+            ("T2", self.t2_us.map(|s| microseconds(&s))),
+            ("Readout error", self.readout_error.map(|s| percent(&s))),
+            ("1q gate error", self.one_qubit_error.map(|s| percent(&s))),
+            ("2q gate error", self.two_qubit_error.map(|s| percent(&s))),
+        ];
+        for (label, value) in rows {
+            if let Some(value) = value {
+                let _ = write!(text, "\n{:<14} {}", format!("{}:", label), value);
+            }
+        }
+
+        if !self.coupling.is_empty() {
+            let _ = write!(text, "\nCoupling map ({} links):\n{}", self.coupling.len(), draw_coupling(&self.coupling));
+        }
+        text
+    }
+}
+
+fn microseconds(spread: &Spread) -> String {
+    format!("median {:.0} µs ({:.0}–{:.0})", spread.median, spread.min, spread.max)
+}
+
+fn percent(spread: &Spread) -> String {
+    format!("median {:.2}% ({:.2}–{:.2}%)", spread.median * 100.0, spread.min * 100.0, spread.max * 100.0)
+}
+
+/// A line or ring as `0─1─2─3`, anything else as one line of neighbours
+/// per qubit
+pub fn draw_coupling(coupling: &[(u32, u32)]) -> String {
+    let mut neighbours: BTreeMap<u32, Vec<u32>> = BTreeMap::new();
+    for &(a, b) in coupling {
+        neighbours.entry(a).or_default().push(b);
+        neighbours.entry(b).or_default().push(a);
+    }
+
+    if let Some(chain) = chain(&neighbours, coupling.len()) {
+        let mut lines = vec![String::from("  ")];
+        for (i, qubit) in chain.iter().enumerate() {
+            let piece = if i == 0 { qubit.to_string() } else { format!("─{}", qubit) };
+            if lines.last().is_some_and(|l| l.chars().count() + piece.chars().count() > CHAIN_WIDTH) {
+                lines.push(String::from("  "));
+            }
+            if let Some(line) = lines.last_mut() {
+                line.push_str(&piece);
+            }
+        }
+        return lines.join("\n");
+    }
+
+    let mut lines: Vec<String> = neighbours
+        .iter()
+        .take(MAX_NEIGHBOUR_LINES)
+        .map(|(qubit, linked)| {
+            let linked: Vec<String> = linked.iter().map(u32::to_string).collect();
+            format!("  {:>3} ─ {}", qubit, linked.join(", "))
+        })
+        .collect();
+    if neighbours.len() > MAX_NEIGHBOUR_LINES {
+        lines.push(format!("  … {} more qubits", neighbours.len() - MAX_NEIGHBOUR_LINES));
+    }
+    lines.join("\n")
+}
+
+/// The qubits in order when they form one line, or one ring (drawn with
+/// its first qubit repeated at the end)
+fn chain(neighbours: &BTreeMap<u32, Vec<u32>>, links: usize) -> Option<Vec<u32>> {
+    if neighbours.values().any(|n| n.len() > 2) {
+        return None;
+    }
+    let ring = links == neighbours.len();
+    if !ring && links + 1 != neighbours.len() {
+        return None;
+    }
+    let start = if ring {
+        *neighbours.keys().next()?
+    } else {
+        *neighbours.iter().find(|(_, n)| n.len() == 1)?.0
+    };
+
+    let mut order = vec![start];
+    let mut previous = None;
+    let mut current = start;
+    while let Some(&next) = neighbours[&current].iter().find(|&&n| Some(n) != previous && n != start) {
+        order.push(next);
+        previous = Some(current);
+        current = next;
+    }
+    // Disconnected pieces don't form one chain
+    if order.len() != neighbours.len() {
+        return None;
+    }
+    if ring {
+        order.push(start);
+    }
+    Some(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn qubit(t1: f64, t2: f64, readout: f64) -> Value {
+        json!([
+            {"name": "T1", "value": t1, "unit": "us"},
+            {"name": "T2", "value": t2, "unit": "us"},
+            {"name": "readout_error", "value": readout}
+        ])
+    }
+
+    #[test]
+    fn test_from_ibm() {
+        let properties = json!({
+            "last_update_date": "2026-10-16T08:12:00Z",
+            "qubits": [qubit(200.0, 100.0, 0.01), qubit(300.0, 150.0, 0.03), qubit(100.0, 50.0, 0.02)],
+            "gates": [
+                {"gate": "sx", "qubits": [0], "parameters": [{"name": "gate_error", "value": 0.0002}]},
+                {"gate": "sx", "qubits": [1], "parameters": [{"name": "gate_error", "value": 0.0004}]},
+                {"gate": "rz", "qubits": [0], "parameters": [{"name": "gate_error", "value": 0.0}]},
+                {"gate": "ecr", "qubits": [0, 1], "parameters": [{"name": "gate_error", "value": 0.008}]},
+                {"gate": "ecr", "qubits": [1, 2], "parameters": [{"name": "gate_error", "value": 1.0}]}
+            ]
+        });
+        let configuration = json!({
+            "n_qubits": 3,
+            "basis_gates": ["ecr", "rz", "sx", "x"],
+            "coupling_map": [[0, 1], [1, 0], [1, 2], [2, 1]]
+        });
+        let info = BackendInfo::from_ibm("ibm_test", &properties, &configuration, true, None, Some(4));
+        assert_eq!(info.coupling, vec![(0, 1), (1, 2)]);
+        let t1 = info.t1_us.unwrap();
+        assert!((t1.median - 200.0).abs() < 1e-9 && (t1.min - 100.0).abs() < 1e-9);
+        assert!((info.one_qubit_error.unwrap().median - 0.0003).abs() < 1e-12);
+        assert_eq!(info.two_qubit_error.map(|s| s.max), Some(0.008));
+
+        assert_eq!(
+            info.report(),
+            "ibm_test — operational, 4 job(s) queued\n\
+             Qubits: 3\n\
+             Basis gates: ecr, rz, sx, x\n\
+             Calibrated: 2026-10-16T08:12:00Z\n\
+             T1:            median 200 µs (100–300)\n\
+             T2:            median 100 µs (50–150)\n\
+             Readout error: median 2.00% (1.00–3.00%)\n\
+             1q gate error: median 0.03% (0.02–0.04%)\n\
+             2q gate error: median 0.80% (0.80–0.80%)\n\
+             Coupling map (2 links):\n  0─1─2"
+        );
+    }
+
+    #[test]
+    fn test_missing_data_is_left_out() {
+        let info = BackendInfo::from_ibm("sim", &json!({}), &json!({}), false, Some("maintenance".into()), None);
+        assert_eq!(info.report(), "sim — offline (maintenance)");
+    }
+
+    #[test]
+    fn test_draw_coupling() {
+        // A line, given out of order
+        assert_eq!(draw_coupling(&[(2, 3), (0, 1), (1, 2)]), "  0─1─2─3");
+        // A ring closes on its first qubit
+        assert_eq!(draw_coupling(&[(0, 1), (1, 2), (2, 3), (0, 3)]), "  0─1─2─3─0");
+        // A T shape lists neighbours
+        assert_eq!(
+            draw_coupling(&[(0, 1), (1, 2), (1, 3), (3, 4)]),
+            "    0 ─ 1\n    1 ─ 0, 2, 3\n    2 ─ 1\n    3 ─ 1, 4\n    4 ─ 3"
+        );
+        // Long lines wrap
+        let long: Vec<(u32, u32)> = (0..40).map(|i| (i, i + 1)).collect();
+        let drawn = draw_coupling(&long);
+        assert!(drawn.lines().count() > 1);
+        assert!(drawn.lines().all(|l| l.chars().count() <= CHAIN_WIDTH));
+        // Heavy-hex sized maps are cut short
+        let big: Vec<(u32, u32)> = (0..30).flat_map(|i| [(i, i + 1), (i, i + 40)]).collect();
+        assert!(draw_coupling(&big).ends_with(&format!("… {} more qubits", 61 - MAX_NEIGHBOUR_LINES)));
+    }
+}
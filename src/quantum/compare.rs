@@ -0,0 +1,264 @@
+// Comparing the measurement results of two jobs
+//
+// Counts are normalised to probabilities first, so runs with different shot
+// counts compare fairly, and an outcome seen by only one job counts as 0 for
+// the other. The headline number is the total variation distance: half the
+// summed absolute differences, 0 for identical distributions and 1 for
+// disjoint ones.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+pub type Counts = BTreeMap<String, u64>;
+
+/// Outcomes listed in the bar view; the rest only count towards the distance
+const MAX_ROWS: usize = 16;
+
+/// Discrepancies marked in the bar view
+const HIGHLIGHTED: usize = 3;
+
+const BAR_WIDTH: usize = 12;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OutcomeDelta {
+    pub outcome: String,
+    pub probability_a: f64,
+    pub probability_b: f64,
+    /// `probability_b - probability_a`
+    pub delta: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Comparison {
+    pub shots_a: u64,
+    pub shots_b: u64,
+    pub total_variation_distance: f64,
+    /// Largest discrepancy first
+    pub outcomes: Vec<OutcomeDelta>,
+}
+
+/// Compare two non-empty sets of counts
+pub fn compare(a: &Counts, b: &Counts) -> Comparison {
+    let (shots_a, shots_b) = (a.values().sum::<u64>(), b.values().sum::<u64>());
+    let probability = |counts: &Counts, shots: u64, outcome: &str| {
+        counts.get(outcome).map_or(0.0, |&n| n as f64 / shots.max(1) as f64)
+    };
+
+    let keys: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+    let mut outcomes: Vec<OutcomeDelta> = keys
+        .into_iter()
+        .map(|outcome| {
+            let (pa, pb) = (probability(a, shots_a, outcome), probability(b, shots_b, outcome));
+            OutcomeDelta {
+                outcome: outcome.clone(),
+                probability_a: pa,
+                probability_b: pb,
+                delta: pb - pa,
+            }
+        })
+        .collect();
+    let total_variation_distance = outcomes.iter().map(|o| o.delta.abs()).sum::<f64>() / 2.0;
+    // Rounded so float noise doesn't break ties between equal deltas
+    let size = |o: &OutcomeDelta| (o.delta.abs() * 1e9).round() as u64;
+    outcomes.sort_by(|x, y| size(y).cmp(&size(x)).then(x.outcome.cmp(&y.outcome)));
+
+    Comparison { shots_a, shots_b, total_variation_distance, outcomes }
+}
+
+/// Measurement counts from a stored job result. Accepts `{"counts": {...}}`,
+/// IBM's `{"results": [{"data": {"counts": {...}}}]}` or a bare map; hex keys
+/// (`"0x3"`) become bit strings.
+pub fn counts_from_result(result: &Value) -> Option<Counts> {
+    let map = result
+        .get("counts")
+        .or_else(|| result.pointer("/results/0/data/counts"))
+        .unwrap_or(result)
+        .as_object()?;
+    let counts: Counts = map
+        .iter()
+        .map(|(k, v)| Some((k.clone(), v.as_u64()?)))
+        .collect::<Option<_>>()?;
+    if counts.is_empty() {
+        return None;
+    }
+    if !counts.keys().all(|k| k.starts_with("0x")) {
+        return Some(counts);
+    }
+
+    let values: Vec<(u64, u64)> = counts
+        .iter()
+        .map(|(k, &n)| Some((u64::from_str_radix(&k[2..], 16).ok()?, n)))
+        .collect::<Option<_>>()?;
+    let width = values.iter().map(|(v, _)| 64 - v.leading_zeros()).max().unwrap_or(1).max(1) as usize;
+    let mut binary = Counts::new();
+    for (value, n) in values {
+        *binary.entry(format!("{:0width$b}", value, width = width)).or_default() += n;
+    }
+    Some(binary)
+}
+
+/// Side-by-side bars for the largest discrepancies
+pub fn render(comparison: &Comparison, label_a: &str, label_b: &str) -> String {
+    let label_a = short(label_a);
+    let label_b = short(label_b);
+    let outcome_width = comparison
+        .outcomes
+        .iter()
+        .take(MAX_ROWS)
+        .map(|o| o.outcome.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("outcome".len());
+    let column = BAR_WIDTH + 8;
+
+    let mut text = format!(
+        "{} ({} shots) vs {} ({} shots)\nTotal variation distance: {:.3}\n\n",
+        label_a, comparison.shots_a, label_b, comparison.shots_b, comparison.total_variation_distance
+    );
+    text.push_str(&format!(
+        "{:<ow$}  {:<column$}  {:<column$}  {:>7}\n",
+        "outcome",
+        label_a,
+        label_b,
+        "Δ",
+        ow = outcome_width,
+        column = column
+    ));
+    for (i, o) in comparison.outcomes.iter().take(MAX_ROWS).enumerate() {
+        let marker = if i < HIGHLIGHTED && o.delta.abs() >= 0.005 { " ◀" } else { "" };
+        text.push_str(&format!(
+            "{:<ow$}  {}  {}  {:>+6.1}%{}\n",
+            o.outcome,
+            bar(o.probability_a),
+            bar(o.probability_b),
+            o.delta * 100.0,
+            marker,
+            ow = outcome_width
+        ));
+    }
+    if comparison.outcomes.len() > MAX_ROWS {
+        text.push_str(&format!("… {} more outcomes\n", comparison.outcomes.len() - MAX_ROWS));
+    }
+    text.trim_end().to_string()
+}
+
+/// The same comparison as sentences, one outcome per line, for screen
+/// readers (ui.accessible)
+pub fn render_plain(comparison: &Comparison, label_a: &str, label_b: &str) -> String {
+    let label_a = short(label_a);
+    let label_b = short(label_b);
+    let mut text = format!(
+        "{} ({} shots) vs {} ({} shots)\nTotal variation distance: {:.3}\n",
+        label_a, comparison.shots_a, label_b, comparison.shots_b, comparison.total_variation_distance
+    );
+    let shots = |probability: f64, total: u64| (probability * total as f64).round() as u64;
+    for o in comparison.outcomes.iter().take(MAX_ROWS) {
+        text.push_str(&format!(
+            "outcome {}: {} of {} shots ({}) in {}, {} of {} shots ({}) in {}\n",
+            o.outcome,
+            shots(o.probability_a, comparison.shots_a),
+            comparison.shots_a,
+            percent(o.probability_a),
+            label_a,
+            shots(o.probability_b, comparison.shots_b),
+            comparison.shots_b,
+            percent(o.probability_b),
+            label_b
+        ));
+    }
+    if comparison.outcomes.len() > MAX_ROWS {
+        text.push_str(&format!("{} more outcomes not listed\n", comparison.outcomes.len() - MAX_ROWS));
+    }
+    text.trim_end().to_string()
+}
+
+/// "50%", or "42.5%" when not whole
+fn percent(probability: f64) -> String {
+    let text = format!("{:.1}", probability * 100.0);
+    format!("{}%", text.strip_suffix(".0").unwrap_or(&text))
+}
+
+/// `████░░░░  42.0%`, always `BAR_WIDTH + 8` columns
+fn bar(probability: f64) -> String {
+    let filled = (probability * BAR_WIDTH as f64).round() as usize;
+    format!(
+        "{}{} {:>5.1}%",
+        "█".repeat(filled),
+        "░".repeat(BAR_WIDTH - filled),
+        probability * 100.0
+    )
+}
+
+/// Job ids are UUIDs; the first segment is enough to tell them apart
+fn short(label: &str) -> String {
+    if label.chars().count() > 12 {
+        format!("{}…", label.chars().take(11).collect::<String>())
+    } else {
+        label.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn counts(pairs: &[(&str, u64)]) -> Counts {
+        pairs.iter().map(|(k, v)| (k.to_string(), *v)).collect()
+    }
+
+    #[test]
+    fn test_compare_with_different_shots_and_outcomes() {
+        let simulator = counts(&[("00", 512), ("11", 512)]);
+        let hardware = counts(&[("00", 1800), ("11", 1600), ("01", 400), ("10", 200)]);
+        let comparison = compare(&simulator, &hardware);
+
+        assert_eq!((comparison.shots_a, comparison.shots_b), (1024, 4000));
+        // |0.45-0.5| + |0.4-0.5| + 0.1 + 0.05, halved
+        assert!((comparison.total_variation_distance - 0.15).abs() < 1e-9);
+        let order: Vec<&str> = comparison.outcomes.iter().map(|o| o.outcome.as_str()).collect();
+        assert_eq!(order, vec!["01", "11", "00", "10"]);
+        assert_eq!(comparison.outcomes[0].probability_a, 0.0);
+
+        assert_eq!(compare(&simulator, &simulator).total_variation_distance, 0.0);
+        assert_eq!(compare(&counts(&[("0", 1)]), &counts(&[("1", 5)])).total_variation_distance, 1.0);
+    }
+
+    #[test]
+    fn test_counts_from_result() {
+        let expected = counts(&[("00", 3), ("11", 5)]);
+        assert_eq!(counts_from_result(&json!({"counts": {"00": 3, "11": 5}})), Some(expected.clone()));
+        assert_eq!(counts_from_result(&json!({"00": 3, "11": 5})), Some(expected.clone()));
+        assert_eq!(
+            counts_from_result(&json!({"results": [{"data": {"counts": {"0x0": 3, "0x3": 5}}}]})),
+            Some(expected)
+        );
+        assert_eq!(counts_from_result(&json!({"counts": {}})), None);
+        assert_eq!(counts_from_result(&json!({"status": "queued"})), None);
+    }
+
+    #[test]
+    fn test_render_highlights_largest_deltas() {
+        let comparison = compare(&counts(&[("00", 50), ("11", 50)]), &counts(&[("00", 70), ("11", 30)]));
+        let text = render(&comparison, "3f2a9c1e-aaaa-bbbb", "sim");
+        assert!(text.starts_with("3f2a9c1e-aa… (100 shots) vs sim (100 shots)"));
+        assert!(text.contains("Total variation distance: 0.200"));
+        let rows: Vec<&str> = text.lines().skip(4).collect();
+        assert!(rows[0].starts_with("00") && rows[0].ends_with("+20.0% ◀"));
+        assert!(rows[1].starts_with("11") && rows[1].ends_with("-20.0% ◀"));
+    }
+
+    #[test]
+    fn test_render_plain() {
+        let comparison = compare(&counts(&[("00", 512), ("11", 512)]), &counts(&[("00", 700), ("11", 299), ("01", 1)]));
+        assert_eq!(
+            render_plain(&comparison, "sim", "ibm_kyiv"),
+            "sim (1024 shots) vs ibm_kyiv (1000 shots)\n\
+             Total variation distance: 0.201\n\
+             outcome 11: 512 of 1024 shots (50%) in sim, 299 of 1000 shots (29.9%) in ibm_kyiv\n\
+             outcome 00: 512 of 1024 shots (50%) in sim, 700 of 1000 shots (70%) in ibm_kyiv\n\
+             outcome 01: 0 of 1024 shots (0%) in sim, 1 of 1000 shots (0.1%) in ibm_kyiv"
+        );
+    }
+}
@@ -0,0 +1,313 @@
+// Preflight estimates for hardware runs
+//
+// Before shots are spent on a real device, qhub predicts how long the circuit
+// occupies the QPU, what it may cost and how long it may wait in the queue.
+// QPU time comes from the backend's reported gate and readout durations with
+// gates scheduled as early as their qubits allow; cost only appears when
+// `[quantum.pricing]` has an entry for the backend. Every figure rests on
+// assumptions that are listed alongside it, because real execution adds
+// transpilation, compilation and control-system overhead we cannot see.
+
+use serde_json::Value;
+use std::collections::HashMap;
+
+use super::qasm::{Circuit, Operation};
+use crate::config::settings::BackendPricing;
+
+/// Used for gates the backend doesn't report, by qubit count
+const DEFAULT_1Q_SECONDS: f64 = 50e-9;
+const DEFAULT_2Q_SECONDS: f64 = 500e-9;
+const DEFAULT_READOUT_SECONDS: f64 = 1.5e-6;
+/// IBM's usual default between shots
+const DEFAULT_REP_DELAY_SECONDS: f64 = 250e-6;
+
+/// Rough queue throughput: one queued job ahead of us per minute
+pub const MINUTES_PER_QUEUED_JOB: f64 = 1.0;
+
+/// Two-qubit gates a three-qubit gate roughly decomposes into (Toffoli: 6 CX)
+const TWO_QUBIT_GATES_PER_3Q: f64 = 6.0;
+
+/// Native two-qubit gates on IBM devices
+pub const TWO_QUBIT: &[&str] = &["cx", "ecr", "cz", "rzz"];
+
+/// What a backend reports about its speed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BackendProfile {
+    pub name: String,
+    /// Average duration of each native gate, in seconds
+    pub gate_seconds: HashMap<String, f64>,
+    pub readout_seconds: Option<f64>,
+    pub rep_delay_seconds: Option<f64>,
+    pub pending_jobs: Option<u32>,
+    /// Mean reported error of the native two-qubit gates
+    pub two_qubit_error: Option<f64>,
+}
+
+impl BackendProfile {
+    /// From IBM's `/properties` and `/configuration` documents. Missing
+    /// fields fall back to defaults when estimating.
+    pub fn from_ibm(name: &str, properties: &Value, configuration: &Value, pending_jobs: Option<u32>) -> Self {
+        let mut sums: HashMap<String, (f64, usize)> = HashMap::new();
+        let mut errors = Vec::new();
+        for gate in properties["gates"].as_array().into_iter().flatten() {
+            if TWO_QUBIT.contains(&gate["gate"].as_str().unwrap_or_default()) {
+                errors.extend(parameter(&gate["parameters"], "gate_error").filter(|e| *e < 1.0));
+            }
+            let (Some(name), Some(seconds)) = (gate["gate"].as_str(), parameter_seconds(&gate["parameters"], "gate_length"))
+            else {
+                continue;
+            };
+            let entry = sums.entry(name.to_string()).or_default();
+            entry.0 += seconds;
+            entry.1 += 1;
+        }
+        let readouts: Vec<f64> = properties["qubits"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|qubit| parameter_seconds(qubit, "readout_length"))
+            .collect();
+
+        BackendProfile {
+            name: name.to_string(),
+            gate_seconds: sums.into_iter().map(|(gate, (sum, n))| (gate, sum / n as f64)).collect(),
+            readout_seconds: (!readouts.is_empty()).then(|| readouts.iter().sum::<f64>() / readouts.len() as f64),
+            // Reported in microseconds
+            rep_delay_seconds: configuration["default_rep_delay"].as_f64().map(|us| us * 1e-6),
+            pending_jobs,
+            two_qubit_error: (!errors.is_empty()).then(|| errors.iter().sum::<f64>() / errors.len() as f64),
+        }
+    }
+
+    /// Mean duration of the reported one- or two-qubit gates, leaving out
+    /// virtual (zero-length) ones
+    fn typical(&self, two_qubit: bool) -> Option<f64> {
+        let times: Vec<f64> = self
+            .gate_seconds
+            .iter()
+            .filter(|(name, &t)| t > 0.0 && TWO_QUBIT.contains(&name.as_str()) == two_qubit)
+            .map(|(_, &t)| t)
+            .collect();
+        (!times.is_empty()).then(|| times.iter().sum::<f64>() / times.len() as f64)
+    }
+}
+
+/// The value of a `[{"name", "value", "unit"}]` entry, as reported
+pub fn parameter(parameters: &Value, wanted: &str) -> Option<f64> {
+    parameters.as_array()?.iter().find(|p| p["name"] == wanted)?["value"].as_f64()
+}
+
+/// A `[{"name", "value", "unit"}]` entry converted to seconds
+pub fn parameter_seconds(parameters: &Value, wanted: &str) -> Option<f64> {
+    let entry = parameters.as_array()?.iter().find(|p| p["name"] == wanted)?;
+    let value = entry["value"].as_f64()?;
+    let scale = match entry["unit"].as_str().unwrap_or("s") {
+        "ns" => 1e-9,
+        "us" | "µs" => 1e-6,
+        "ms" => 1e-3,
+        _ => 1.0,
+    };
+    Some(value * scale)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Estimate {
+    pub backend: String,
+    pub shots: usize,
+    pub qpu_seconds: f64,
+    /// Amount and unit, when pricing is configured
+    pub cost: Option<(f64, String)>,
+    pub queue_minutes: Option<f64>,
+    pub assumptions: Vec<String>,
+}
+
+impl Estimate {
+    /// "≈ 3.2 s QPU time, ~2 credits, queue ≈ 25 min"
+    pub fn summary(&self) -> String {
+        let mut parts = vec![format!("≈ {} QPU time", seconds(self.qpu_seconds))];
+        if let Some((amount, unit)) = &self.cost {
+            parts.push(format!("~{} {}", amount_text(*amount), unit));
+        }
+        if let Some(minutes) = self.queue_minutes {
+            parts.push(if minutes < 1.0 { "no queue".to_string() } else { format!("queue ≈ {:.0} min", minutes) });
+        }
+        parts.join(", ")
+    }
+
+    /// Summary followed by the assumptions, one per line
+    pub fn report(&self) -> String {
+        let mut text = format!("{} on {}, {} shots\nAssumptions:", self.summary(), self.backend, self.shots);
+        for assumption in &self.assumptions {
+            text.push_str(&format!("\n  - {}", assumption));
+        }
+        text
+    }
+}
+
+fn seconds(value: f64) -> String {
+    if value < 0.1 {
+        format!("{:.0} ms", value * 1000.0)
+    } else if value < 120.0 {
+        format!("{:.1} s", value)
+    } else {
+        format!("{:.1} min", value / 60.0)
+    }
+}
+
+fn amount_text(amount: f64) -> String {
+    if amount >= 10.0 {
+        format!("{:.0}", amount)
+    } else if amount >= 0.1 {
+        format!("{:.1}", amount)
+    } else {
+        format!("{:.3}", amount)
+    }
+}
+
+/// Predict QPU time, cost and queue wait for `shots` runs of `circuit`
+pub fn estimate(circuit: &Circuit, backend: &BackendProfile, shots: usize, pricing: Option<&BackendPricing>) -> Estimate {
+    let mut assumptions = vec![
+        "gates run as early as their qubits allow; transpilation to the device may add gates".to_string(),
+        "excludes compilation and per-job setup, which IBM also bills".to_string(),
+    ];
+    let one_qubit = backend.typical(false).unwrap_or(DEFAULT_1Q_SECONDS);
+    let two_qubit = backend.typical(true).unwrap_or(DEFAULT_2Q_SECONDS);
+    let mut defaulted = Vec::new();
+    let mut duration = |name: &str, qubits: usize| -> f64 {
+        if let Some(&t) = backend.gate_seconds.get(name) {
+            return t;
+        }
+        if !defaulted.contains(&name.to_string()) {
+            defaulted.push(name.to_string());
+        }
+        match (name, qubits) {
+            ("swap", _) => 3.0 * two_qubit,
+            (_, 0 | 1) => one_qubit,
+            (_, 2) => two_qubit,
+            (_, n) => TWO_QUBIT_GATES_PER_3Q * (n - 2) as f64 * two_qubit,
+        }
+    };
+
+    // Per-qubit timelines; each operation starts when all its qubits are free
+    let mut timeline = vec![0.0_f64; circuit.num_qubits()];
+    let readout = backend.readout_seconds.unwrap_or(DEFAULT_READOUT_SECONDS);
+    for op in &circuit.operations {
+        let mut op = op;
+        while let Operation::Conditional { op: inner, .. } = op {
+            op = inner;
+        }
+        let (qubits, length) = match op {
+            Operation::Gate { name, qubits, .. } => (qubits.clone(), duration(name, qubits.len())),
+            Operation::Measure { qubit, .. } | Operation::Reset { qubit } => (vec![*qubit], readout),
+            Operation::Barrier { qubits } => (qubits.clone(), 0.0),
+            Operation::Conditional { .. } => unreachable!(),
+        };
+        let start = qubits.iter().filter_map(|&q| timeline.get(q)).fold(0.0_f64, |a, &b| a.max(b));
+        for q in qubits {
+            if let Some(t) = timeline.get_mut(q) {
+                *t = start + length;
+            }
+        }
+    }
+    let rep_delay = backend.rep_delay_seconds.unwrap_or(DEFAULT_REP_DELAY_SECONDS);
+    let per_shot = timeline.iter().fold(0.0_f64, |a, &b| a.max(b)) + rep_delay;
+    let qpu_seconds = per_shot * shots as f64;
+
+    if !defaulted.is_empty() {
+        defaulted.sort();
+        assumptions.push(format!(
+            "no reported duration for {}; used typical gate times instead",
+            defaulted.join(", ")
+        ));
+    }
+    if backend.gate_seconds.is_empty() {
+        assumptions.push("the backend reported no gate durations; all gate times are defaults".to_string());
+    }
+    if backend.readout_seconds.is_none() {
+        assumptions.push(format!("readout takes {} per measurement", seconds(DEFAULT_READOUT_SECONDS)));
+    }
+    assumptions.push(format!("{} between shots", seconds(rep_delay)));
+
+    let cost = pricing.map(|p| {
+        assumptions.push(format!(
+            "price from [quantum.pricing]: {} {} per QPU second, {} per shot",
+            p.per_second, p.unit, p.per_shot
+        ));
+        (p.per_second * qpu_seconds + p.per_shot * shots as f64, p.unit.clone())
+    });
+    let queue_minutes = backend.pending_jobs.map(|jobs| {
+        assumptions.push(format!(
+            "{} job(s) ahead, each taking about {:.0} min",
+            jobs, MINUTES_PER_QUEUED_JOB
+        ));
+        jobs as f64 * MINUTES_PER_QUEUED_JOB
+    });
+
+    Estimate {
+        backend: backend.name.clone(),
+        shots,
+        qpu_seconds,
+        cost,
+        queue_minutes,
+        assumptions,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quantum::qasm;
+    use serde_json::json;
+
+    const BELL: &str = "OPENQASM 2.0; qreg q[2]; creg c[2]; h q[0]; cx q[0],q[1]; measure q -> c;";
+
+    fn profile() -> BackendProfile {
+        let properties = json!({
+            "qubits": [
+                [{"name": "T1", "value": 200.0, "unit": "us"}, {"name": "readout_length", "value": 1000.0, "unit": "ns"}],
+                [{"name": "readout_length", "value": 3000.0, "unit": "ns"}]
+            ],
+            "gates": [
+                {"gate": "sx", "qubits": [0], "parameters": [{"name": "gate_length", "value": 40.0, "unit": "ns"}]},
+                {"gate": "sx", "qubits": [1], "parameters": [{"name": "gate_length", "value": 60.0, "unit": "ns"}]},
+                {"gate": "rz", "qubits": [0], "parameters": [{"name": "gate_length", "value": 0.0, "unit": "ns"}]},
+                {"gate": "cx", "qubits": [0, 1], "parameters": [{"name": "gate_error", "value": 0.01}, {"name": "gate_length", "value": 0.4, "unit": "us"}]}
+            ]
+        });
+        BackendProfile::from_ibm("ibm_test", &properties, &json!({"default_rep_delay": 250.0}), Some(25))
+    }
+
+    #[test]
+    fn test_profile_from_ibm() {
+        let profile = profile();
+        assert!((profile.gate_seconds["sx"] - 50e-9).abs() < 1e-15);
+        assert!((profile.gate_seconds["cx"] - 400e-9).abs() < 1e-15);
+        assert!((profile.readout_seconds.unwrap() - 2e-6).abs() < 1e-15);
+        assert!((profile.rep_delay_seconds.unwrap() - 250e-6).abs() < 1e-15);
+        assert_eq!(profile.two_qubit_error, Some(0.01));
+    }
+
+    #[test]
+    fn test_estimate_bell() {
+        let circuit = qasm::parse(BELL).unwrap();
+        let pricing = BackendPricing { per_second: 1.6, per_shot: 0.0, unit: "credits".to_string() };
+        let estimate = estimate(&circuit, &profile(), 4000, Some(&pricing));
+
+        // h (typical 1q: 50 ns) + cx 400 ns + readout 2 µs + 250 µs between shots
+        let per_shot = 50e-9 + 400e-9 + 2e-6 + 250e-6;
+        assert!((estimate.qpu_seconds - per_shot * 4000.0).abs() < 1e-9);
+        assert_eq!(estimate.summary(), "≈ 1.0 s QPU time, ~1.6 credits, queue ≈ 25 min");
+        assert!(estimate.assumptions.iter().any(|a| a.contains("no reported duration for h")));
+        assert!(estimate.report().contains("Assumptions:\n  - gates run as early"));
+    }
+
+    #[test]
+    fn test_estimate_without_backend_data() {
+        let circuit = qasm::parse(BELL).unwrap();
+        let estimate = estimate(&circuit, &BackendProfile::default(), 100, None);
+        assert_eq!(estimate.cost, None);
+        assert_eq!(estimate.queue_minutes, None);
+        assert_eq!(estimate.summary(), "≈ 25 ms QPU time");
+        assert!(estimate.assumptions.iter().any(|a| a.contains("all gate times are defaults")));
+    }
+}
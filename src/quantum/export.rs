@@ -0,0 +1,197 @@
+// Measurement counts as CSV or JSON, for analysis elsewhere
+//
+// CSV has bitstring, count and probability columns under `#` lines naming
+// the job, backend and shots; JSON has the same as fields, with the counts
+// under "counts" so that `/compare` and `qhub jobs compare` take an
+// exported file wherever they take a job id. A result with several
+// classical registers has one group of bits per register, space-separated
+// as IBM reports them; `quantum.register_separator` joins them with
+// something else. Rows go in bit-string order or, with
+// `quantum.export_order = "count"`, most frequent first.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::batch::cell;
+use super::compare::{self, Counts};
+use crate::config::settings::CountOrder;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    /// JSON for a .json file, CSV otherwise
+    pub fn for_path(path: &str) -> Self {
+        if is_counts_file(path) {
+            ExportFormat::Json
+        } else {
+            ExportFormat::Csv
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "json" => Ok(ExportFormat::Json),
+            _ => Err(format!("unknown format '{}'; use csv or json", s)),
+        }
+    }
+}
+
+/// What an export says about where the counts came from
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Source {
+    pub job_id: Option<String>,
+    pub backend: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub format: ExportFormat,
+    pub order: CountOrder,
+    /// Put between register groups in place of IBM's space
+    pub separator: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Outcome<'a> {
+    bitstring: &'a str,
+    count: u64,
+    probability: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct Document<'a> {
+    job_id: Option<&'a str>,
+    backend: Option<&'a str>,
+    shots: u64,
+    counts: &'a Counts,
+    /// The counts again, in the chosen order and with probabilities
+    outcomes: Vec<Outcome<'a>>,
+}
+
+/// A path `/compare` reads as an exported file rather than a job id
+pub fn is_counts_file(reference: &str) -> bool {
+    Path::new(reference).extension().is_some_and(|e| e.eq_ignore_ascii_case("json"))
+}
+
+/// `counts` written out as `options` ask
+pub fn render(counts: &Counts, source: &Source, options: &Options) -> Result<String> {
+    let counts: Counts = counts.iter().map(|(bits, &n)| (bits.replace(' ', &options.separator), n)).collect();
+    let shots: u64 = counts.values().sum();
+    let mut rows: Vec<(&String, u64)> = counts.iter().map(|(bits, &n)| (bits, n)).collect();
+    if options.order == CountOrder::Count {
+        rows.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    }
+    let probability = |n: u64| n as f64 / shots.max(1) as f64;
+
+    match options.format {
+        ExportFormat::Csv => {
+            let mut text = String::new();
+            if let Some(job_id) = &source.job_id {
+                let _ = writeln!(text, "# job: {}", job_id);
+            }
+            if let Some(backend) = &source.backend {
+                let _ = writeln!(text, "# backend: {}", backend);
+            }
+            let _ = writeln!(text, "# shots: {}", shots);
+            text.push_str("bitstring,count,probability\n");
+            for (bits, n) in rows {
+                let _ = writeln!(text, "{},{},{}", cell(bits), n, probability(n));
+            }
+            Ok(text)
+        }
+        ExportFormat::Json => {
+            let document = Document {
+                job_id: source.job_id.as_deref(),
+                backend: source.backend.as_deref(),
+                shots,
+                counts: &counts,
+                outcomes: rows
+                    .into_iter()
+                    .map(|(bitstring, count)| Outcome { bitstring, count, probability: probability(count) })
+                    .collect(),
+            };
+            Ok(serde_json::to_string_pretty(&document)?)
+        }
+    }
+}
+
+/// Counts from a JSON file: an export, or a job result in any form
+/// `compare::counts_from_result` accepts
+pub fn read_counts(path: &str) -> Result<Counts> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?;
+    let value: serde_json::Value = serde_json::from_str(&text).with_context(|| format!("{} is not JSON", path))?;
+    compare::counts_from_result(&value).with_context(|| format!("{} has no measurement counts", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts(pairs: &[(&str, u64)]) -> Counts {
+        pairs.iter().map(|(bits, n)| (bits.to_string(), *n)).collect()
+    }
+
+    fn options(format: ExportFormat, order: CountOrder, separator: &str) -> Options {
+        Options { format, order, separator: separator.to_string() }
+    }
+
+    #[test]
+    fn test_csv() {
+        let source = Source { job_id: Some("j1".to_string()), backend: Some("ibm_kyiv".to_string()) };
+        let counts = counts(&[("00", 100), ("01", 700), ("11", 200)]);
+        assert_eq!(
+            render(&counts, &source, &options(ExportFormat::Csv, CountOrder::Count, " ")).unwrap(),
+            "# job: j1\n# backend: ibm_kyiv\n# shots: 1000\nbitstring,count,probability\n\
+             01,700,0.7\n11,200,0.2\n00,100,0.1\n"
+        );
+        let sorted = render(&counts, &Source::default(), &options(ExportFormat::Csv, CountOrder::Lexicographic, " "));
+        assert!(sorted.unwrap().starts_with("# shots: 1000\nbitstring,count,probability\n00,100,0.1\n"));
+    }
+
+    #[test]
+    fn test_registers_are_joined() {
+        let counts = counts(&[("01 1", 3), ("10 0", 1)]);
+        let csv = render(&counts, &Source::default(), &options(ExportFormat::Csv, CountOrder::Lexicographic, ",")).unwrap();
+        assert!(csv.contains("\"01,1\",3,0.75\n"), "{}", csv);
+        let json = render(&counts, &Source::default(), &options(ExportFormat::Json, CountOrder::Count, "_")).unwrap();
+        assert!(json.contains("\"01_1\": 3"), "{}", json);
+    }
+
+    #[test]
+    fn test_json_reads_back_for_compare() {
+        let dir = std::env::temp_dir().join(format!("qhub-export-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("results.json");
+        let path = path.to_str().unwrap();
+        let original = counts(&[("00", 480), ("11", 520), ("01", 24)]);
+        let source = Source { job_id: Some("j1".to_string()), backend: None };
+        let json = render(&original, &source, &options(ExportFormat::for_path(path), CountOrder::Count, " ")).unwrap();
+        std::fs::write(path, json).unwrap();
+
+        assert!(is_counts_file(path));
+        let read = read_counts(path).unwrap();
+        assert_eq!(read, original);
+        assert_eq!(compare::compare(&read, &original).total_variation_distance, 0.0);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_parse_options() {
+        assert_eq!("JSON".parse(), Ok(ExportFormat::Json));
+        assert!("xml".parse::<ExportFormat>().is_err());
+        assert_eq!("count".parse(), Ok(CountOrder::Count));
+        assert_eq!(ExportFormat::for_path("out.csv"), ExportFormat::Csv);
+    }
+}
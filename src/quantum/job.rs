@@ -1 +1,177 @@
-// Job management - to be implemented in Phase 7
+// Progress of a job waiting on a hardware backend
+//
+// IBM Quantum reports where a queued job stands in the backend's queue.
+// Positions are sampled every poll; the drain rate (positions per minute) is
+// averaged over the last few intervals to give an ETA. The queue is not
+// first-in first-out - higher-priority work can push a job back - so an
+// interval where the position goes up counts against the rate rather than
+// being dropped. Without queue positions only the time waited is reported.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Intervals the drain rate is averaged over
+const WINDOW: usize = 5;
+
+/// Where a job stands, as the provider reports it
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobProgress {
+    pub status: JobStatus,
+    /// Place in the backend queue while queued, when the provider says
+    pub position: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    /// IBM Quantum's status names; unknown ones count as queued
+    pub fn parse(status: &str) -> Self {
+        match status.to_ascii_lowercase().as_str() {
+            "running" | "validating" => JobStatus::Running,
+            "completed" | "done" => JobStatus::Completed,
+            "failed" | "error" => JobStatus::Failed,
+            "cancelled" | "canceled" => JobStatus::Cancelled,
+            _ => JobStatus::Queued,
+        }
+    }
+
+    pub fn is_finished(self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled)
+    }
+}
+
+/// Queue positions seen so far, for the ETA
+#[derive(Debug, Default)]
+pub struct QueueTracker {
+    /// The first position seen
+    first: Option<u32>,
+    /// (seconds since watching started, position), the last `WINDOW + 1`
+    samples: VecDeque<(f64, u32)>,
+}
+
+impl QueueTracker {
+    pub fn observe(&mut self, elapsed: Duration, position: u32) {
+        self.first.get_or_insert(position);
+        self.samples.push_back((elapsed.as_secs_f64(), position));
+        if self.samples.len() > WINDOW + 1 {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn position(&self) -> Option<u32> {
+        self.samples.back().map(|(_, p)| *p)
+    }
+
+    /// Positions gained per second, the mean over the recent intervals; None
+    /// before there are two samples
+    pub fn drain_rate(&self) -> Option<f64> {
+        let rates: Vec<f64> = self
+            .samples
+            .iter()
+            .zip(self.samples.iter().skip(1))
+            .filter(|((t0, _), (t1, _))| t1 > t0)
+            .map(|((t0, p0), (t1, p1))| (*p0 as f64 - *p1 as f64) / (t1 - t0))
+            .collect();
+        (!rates.is_empty()).then(|| rates.iter().sum::<f64>() / rates.len() as f64)
+    }
+
+    /// Time until the job reaches the front at the current rate; None while
+    /// the queue is not moving forward
+    pub fn eta(&self) -> Option<Duration> {
+        let rate = self.drain_rate().filter(|r| *r > 0.0)?;
+        Some(Duration::from_secs_f64(self.position()? as f64 / rate))
+    }
+
+    /// "Queue position 14 → 9 (ETA ~18 min)"; None before any position
+    pub fn describe(&self) -> Option<String> {
+        let (first, current) = (self.first?, self.position()?);
+        let positions = if first == current { current.to_string() } else { format!("{} → {}", first, current) };
+        let eta = match (self.eta(), self.drain_rate()) {
+            (Some(eta), _) => format!(" (ETA {})", approximate(eta)),
+            (None, Some(_)) => " (not moving)".to_string(),
+            (None, None) => String::new(),
+        };
+        Some(format!("Queue position {}{}", positions, eta))
+    }
+}
+
+/// "<1 min", "~18 min" or "~2 h 10 min"
+pub fn approximate(duration: Duration) -> String {
+    let minutes = (duration.as_secs_f64() / 60.0).round() as u64;
+    match minutes {
+        0 => "<1 min".to_string(),
+        1..=59 => format!("~{} min", minutes),
+        _ if minutes.is_multiple_of(60) => format!("~{} h", minutes / 60),
+        _ => format!("~{} h {} min", minutes / 60, minutes % 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(samples: &[(u64, u32)]) -> QueueTracker {
+        let mut tracker = QueueTracker::default();
+        for (secs, position) in samples {
+            tracker.observe(Duration::from_secs(*secs), *position);
+        }
+        tracker
+    }
+
+    #[test]
+    fn test_steady_drain() {
+        // One position a minute, nine to go
+        let tracker = track(&[(0, 14), (60, 13), (120, 12), (180, 11), (240, 10), (300, 9)]);
+        assert_eq!(tracker.drain_rate(), Some(1.0 / 60.0));
+        assert_eq!(tracker.eta(), Some(Duration::from_secs(540)));
+        assert_eq!(tracker.describe().unwrap(), "Queue position 14 → 9 (ETA ~9 min)");
+    }
+
+    #[test]
+    fn test_positions_going_up_slow_the_estimate() {
+        // Down four, then pushed back two: net two positions in two minutes
+        let tracker = track(&[(0, 10), (60, 6), (120, 8)]);
+        assert_eq!(tracker.drain_rate(), Some((4.0 / 60.0 - 2.0 / 60.0) / 2.0));
+        assert_eq!(tracker.eta(), Some(Duration::from_secs(480)));
+
+        // Only ever pushed back: no ETA
+        let tracker = track(&[(0, 5), (60, 7), (120, 7)]);
+        assert_eq!(tracker.eta(), None);
+        assert_eq!(tracker.describe().unwrap(), "Queue position 5 → 7 (not moving)");
+    }
+
+    #[test]
+    fn test_window_forgets_old_intervals() {
+        // A burst long ago no longer counts once it falls out of the window
+        let mut samples = vec![(0, 100), (60, 40)];
+        samples.extend((2..=7).map(|i| (i * 60, 40 - (i as u32 - 1))));
+        let tracker = track(&samples);
+        assert_eq!(tracker.position(), Some(34));
+        assert_eq!(tracker.drain_rate(), Some(1.0 / 60.0));
+        assert_eq!(tracker.describe().unwrap(), "Queue position 100 → 34 (ETA ~34 min)");
+    }
+
+    #[test]
+    fn test_first_sample_and_formatting() {
+        assert_eq!(QueueTracker::default().describe(), None);
+        let tracker = track(&[(0, 3)]);
+        assert_eq!(tracker.eta(), None);
+        assert_eq!(tracker.describe().unwrap(), "Queue position 3");
+
+        assert_eq!(approximate(Duration::from_secs(20)), "<1 min");
+        assert_eq!(approximate(Duration::from_secs(18 * 60 + 10)), "~18 min");
+        assert_eq!(approximate(Duration::from_secs(7200)), "~2 h");
+        assert_eq!(approximate(Duration::from_secs(7800)), "~2 h 10 min");
+        assert_eq!(JobStatus::parse("Queued"), JobStatus::Queued);
+        assert_eq!(JobStatus::parse("DONE"), JobStatus::Completed);
+        assert!(JobStatus::parse("canceled").is_finished());
+        assert!(!JobStatus::Running.is_finished());
+    }
+}
@@ -1,3 +1,15 @@
 pub mod qqb;
 pub mod transpiler;
 pub mod job;
+pub mod qasm;
+pub mod program;
+pub mod python;
+pub mod compare;
+pub mod simulator;
+pub mod sweep;
+pub mod estimate;
+pub mod batch;
+pub mod select;
+pub mod calibration;
+pub mod calc;
+pub mod export;
@@ -0,0 +1,95 @@
+// Quantum program files
+//
+// `qhub run` takes OpenQASM (.qasm) and Qiskit Python (.py); the extension
+// says which, and a file without a known one is recognised by its content:
+// an `OPENQASM` header, or a Qiskit import or QuantumCircuit. Either kind
+// ends up as a parsed `Circuit`. `.qqb` files are recognised but cannot be
+// read yet.
+
+use std::path::Path;
+use thiserror::Error;
+
+use super::python::{self, PythonError};
+use super::qasm::{self, Circuit, QasmError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Qasm,
+    Python,
+    Qqb,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ProgramError {
+    #[error(transparent)]
+    Qasm(#[from] QasmError),
+    #[error(
+        "{0}\nqhub reads Qiskit files that build one QuantumCircuit step by step. Export anything else with \
+         `qiskit.qasm2.dump(qc, \"circuit.qasm\")` and run the .qasm file; full Python programs are what the \
+         .qqb format is for, which is not supported yet"
+    )]
+    Python(PythonError),
+    #[error("the .qqb format is not supported yet")]
+    Qqb,
+    #[error("{0} is not a quantum program qhub knows: use OpenQASM (.qasm) or Qiskit Python (.py)")]
+    Unknown(String),
+}
+
+/// What kind of program `path` holds
+pub fn detect(path: &str, source: &str) -> Option<Kind> {
+    match Path::new(path).extension().and_then(|e| e.to_str()) {
+        Some("qasm") => return Some(Kind::Qasm),
+        Some("py") => return Some(Kind::Python),
+        Some("qqb") => return Some(Kind::Qqb),
+        _ => {}
+    }
+    let first = source.lines().map(str::trim).find(|l| !l.is_empty() && !l.starts_with("//"));
+    if first.is_some_and(|l| l.starts_with("OPENQASM")) {
+        Some(Kind::Qasm)
+    } else if source.contains("from qiskit") || source.contains("import qiskit") || source.contains("QuantumCircuit(") {
+        Some(Kind::Python)
+    } else {
+        None
+    }
+}
+
+/// Parse the program in `path`, whichever kind it is
+pub fn parse(path: &str, source: &str) -> Result<(Kind, Circuit), ProgramError> {
+    match detect(path, source) {
+        Some(Kind::Qasm) => Ok((Kind::Qasm, qasm::parse(source)?)),
+        Some(Kind::Python) => {
+            let qasm = python::to_qasm(source).map_err(ProgramError::Python)?;
+            Ok((Kind::Python, qasm::parse(&qasm)?))
+        }
+        Some(Kind::Qqb) => Err(ProgramError::Qqb),
+        None => Err(ProgramError::Unknown(path.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect("bell.qasm", ""), Some(Kind::Qasm));
+        assert_eq!(detect("bell.py", ""), Some(Kind::Python));
+        assert_eq!(detect("bell.qqb", ""), Some(Kind::Qqb));
+        assert_eq!(detect("bell", "// Bell pair\nOPENQASM 3.0;\n"), Some(Kind::Qasm));
+        assert_eq!(detect("bell.txt", "from qiskit import QuantumCircuit\n"), Some(Kind::Python));
+        assert_eq!(detect("notes.txt", "a Bell pair"), None);
+    }
+
+    #[test]
+    fn test_parse_python() {
+        let (kind, circuit) = parse("bell.py", "qc = QuantumCircuit(2, 2)\nqc.h(0)\nqc.cx(0, 1)\nqc.measure([0, 1], [0, 1])\n").unwrap();
+        assert_eq!(kind, Kind::Python);
+        assert_eq!(circuit.num_qubits(), 2);
+        assert_eq!(circuit.operations.len(), 4);
+
+        let error = parse("loop.py", "qc = QuantumCircuit(2)\nfor i in range(2):\n    qc.h(i)\n").unwrap_err().to_string();
+        assert!(error.starts_with("line 3: the circuit is changed inside a `for` block\n"), "{}", error);
+        assert!(error.contains("qasm2.dump"));
+        assert_eq!(parse("notes.txt", "hello").unwrap_err(), ProgramError::Unknown("notes.txt".to_string()));
+    }
+}
@@ -0,0 +1,483 @@
+// Qiskit Python programs
+//
+// qhub does not run Python. It reads the common shape of a Qiskit script
+// instead: an OpenQASM string handed to `QuantumCircuit.from_qasm_str` (or
+// `qasm2.loads`/`qasm3.loads`) is used as is, and otherwise one
+// `QuantumCircuit(n, m)` built by straight-line gate calls is translated to
+// OpenQASM 2.0. Imports and the code that runs the circuit afterwards are
+// skipped, since qhub does the running. Anything that needs Python to
+// evaluate - loops building the circuit, variables as angles, registers,
+// composed circuits - is rejected with the line it is on.
+
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum PythonError {
+    #[error("no QuantumCircuit found")]
+    NoCircuit,
+    #[error("line {line}: {reason}")]
+    Unsupported { line: usize, reason: String },
+}
+
+/// Qiskit method, OpenQASM 2.0 gate, angle count, qubit count
+const GATES: &[(&str, &str, usize, usize)] = &[
+    ("id", "id", 0, 1), ("i", "id", 0, 1), ("x", "x", 0, 1), ("y", "y", 0, 1), ("z", "z", 0, 1),
+    ("h", "h", 0, 1), ("s", "s", 0, 1), ("sdg", "sdg", 0, 1), ("t", "t", 0, 1), ("tdg", "tdg", 0, 1),
+    ("sx", "sx", 0, 1), ("sxdg", "sxdg", 0, 1), ("rx", "rx", 1, 1), ("ry", "ry", 1, 1), ("rz", "rz", 1, 1),
+    ("p", "p", 1, 1), ("u", "u3", 3, 1),
+    ("cx", "cx", 0, 2), ("cnot", "cx", 0, 2), ("cy", "cy", 0, 2), ("cz", "cz", 0, 2), ("ch", "ch", 0, 2),
+    ("csx", "csx", 0, 2), ("swap", "swap", 0, 2), ("iswap", "iswap", 0, 2), ("ecr", "ecr", 0, 2),
+    ("cp", "cp", 1, 2), ("crx", "crx", 1, 2), ("cry", "cry", 1, 2), ("crz", "crz", 1, 2),
+    ("rxx", "rxx", 1, 2), ("ryy", "ryy", 1, 2), ("rzz", "rzz", 1, 2), ("rzx", "rzx", 1, 2),
+    ("ccx", "ccx", 0, 3), ("toffoli", "ccx", 0, 3), ("cswap", "cswap", 0, 3), ("fredkin", "cswap", 0, 3),
+];
+
+/// Circuit methods that only look at it, skipped wherever they appear
+const READ_ONLY: &[&str] = &["draw", "depth", "size", "width", "count_ops", "num_nonlocal_gates", "qasm"];
+
+/// Statements that open a block
+const BLOCK_KEYWORDS: &[&str] = &["for", "while", "if", "elif", "else", "def", "with", "try", "except", "finally", "class"];
+
+/// An OpenQASM 2.0 program equivalent to the Qiskit circuit in `source`
+pub fn to_qasm(source: &str) -> Result<String, PythonError> {
+    if let Some(qasm) = embedded_qasm(source) {
+        return Ok(qasm);
+    }
+
+    let mut builder: Option<Builder> = None;
+    // The keyword of the block the current indented lines belong to
+    let mut block = "";
+    for Statement { line, text, indented } in statements(source) {
+        let unsupported = |reason: String| PythonError::Unsupported { line, reason };
+        let word = text.split(|c: char| !c.is_alphanumeric() && c != '_').next().unwrap_or("");
+        if !indented {
+            if word == "import" || word == "from" {
+                continue;
+            }
+            block = BLOCK_KEYWORDS.iter().find(|k| **k == word).copied().unwrap_or("");
+        }
+        let in_block = indented || !block.is_empty();
+
+        if let Some(at) = text.find("QuantumCircuit(") {
+            if in_block {
+                return Err(unsupported(format!("the circuit is created inside a `{}` block", block_name(block))));
+            }
+            if builder.is_some() {
+                return Err(unsupported("a second QuantumCircuit; only one circuit per file can be run".to_string()));
+            }
+            let name = text[..at].trim_end().strip_suffix('=').map(str::trim);
+            let Some(name) = name.filter(|n| is_identifier(n)) else {
+                return Err(unsupported("the QuantumCircuit has to be assigned to a name".to_string()));
+            };
+            let args = call_args(&text[at + "QuantumCircuit".len()..])
+                .ok_or_else(|| unsupported("could not read the QuantumCircuit arguments".to_string()))?;
+            let sizes: Option<Vec<usize>> = args.iter().map(|a| a.parse().ok()).collect();
+            let (qubits, clbits) = match sizes.as_deref() {
+                Some([qubits]) => (*qubits, 0),
+                Some([qubits, clbits]) => (*qubits, *clbits),
+                _ => {
+                    return Err(unsupported(
+                        "QuantumCircuit takes registers or computed sizes here; give the qubit and bit counts as numbers"
+                            .to_string(),
+                    ))
+                }
+            };
+            if qubits == 0 {
+                return Err(unsupported("the circuit has no qubits".to_string()));
+            }
+            builder = Some(Builder::new(name, qubits, clbits));
+            continue;
+        }
+
+        let Some(circuit) = builder.as_mut() else {
+            continue;
+        };
+        let prefix = format!("{}.", circuit.name);
+        let methods = calls_on(&text, &prefix);
+        if methods.iter().all(|m| READ_ONLY.contains(m)) {
+            // Printing, drawing or running the circuit: nothing to translate
+            continue;
+        }
+        if in_block {
+            return Err(unsupported(format!("the circuit is changed inside a `{}` block", block_name(block))));
+        }
+        let call = text.strip_prefix(&prefix).filter(|_| methods.len() == 1);
+        let Some(call) = call else {
+            return Err(unsupported(format!("`{}{}` is used inside an expression", prefix, methods[0])));
+        };
+        circuit.call(call).map_err(unsupported)?;
+    }
+
+    builder.map(Builder::finish).ok_or(PythonError::NoCircuit)
+}
+
+fn block_name(keyword: &str) -> &str {
+    if keyword.is_empty() {
+        "nested"
+    } else {
+        keyword
+    }
+}
+
+/// The first string literal holding an OpenQASM program
+fn embedded_qasm(source: &str) -> Option<String> {
+    let mut rest = source;
+    while let Some(start) = rest.find(['"', '\'']) {
+        let quote = &rest[start..start + 1];
+        let triple = quote.repeat(3);
+        let (body, after) = if rest[start..].starts_with(&triple) {
+            let body = &rest[start + 3..];
+            let end = body.find(&triple)?;
+            (body[..end].to_string(), &body[end + 3..])
+        } else {
+            let body = &rest[start + 1..];
+            let end = body.find([quote.chars().next()?, '\n']).unwrap_or(body.len());
+            (body[..end].replace("\\n", "\n").replace("\\\"", "\""), &body[(end + 1).min(body.len())..])
+        };
+        if body.trim_start().starts_with("OPENQASM") {
+            return Some(body.trim().to_string());
+        }
+        rest = after;
+    }
+    None
+}
+
+struct Statement {
+    /// Line the statement starts on, from 1
+    line: usize,
+    text: String,
+    indented: bool,
+}
+
+/// Logical lines without comments: bracketed and backslash continuations are
+/// joined, blank lines dropped
+fn statements(source: &str) -> Vec<Statement> {
+    let mut statements = Vec::new();
+    let mut current: Option<Statement> = None;
+    let mut depth = 0i32;
+    for (i, raw) in source.lines().enumerate() {
+        let code = strip_comment(raw);
+        let continued = code.trim_end().ends_with('\\');
+        let code = code.trim_end().trim_end_matches('\\');
+        for c in code.chars() {
+            match c {
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => depth -= 1,
+                _ => {}
+            }
+        }
+        match current.as_mut() {
+            Some(statement) => {
+                statement.text.push(' ');
+                statement.text.push_str(code.trim());
+            }
+            None if code.trim().is_empty() => continue,
+            None => {
+                current = Some(Statement {
+                    line: i + 1,
+                    text: code.trim().to_string(),
+                    indented: code.starts_with(char::is_whitespace),
+                })
+            }
+        }
+        if depth <= 0 && !continued {
+            depth = 0;
+            statements.extend(current.take());
+        }
+    }
+    statements.extend(current);
+    statements
+}
+
+/// `line` up to a `#` outside a string
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '#') => return &line[..i],
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            _ => {}
+        }
+    }
+    line
+}
+
+fn is_identifier(text: &str) -> bool {
+    text.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_') && text.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Methods called on the circuit anywhere in `text`, e.g. ["h"] for `qc.h(0)`
+fn calls_on<'a>(text: &'a str, prefix: &str) -> Vec<&'a str> {
+    let mut methods = Vec::new();
+    for (at, _) in text.match_indices(prefix) {
+        // `myqc.h` is not `qc.h`
+        if text[..at].chars().next_back().is_some_and(|c| c.is_alphanumeric() || c == '_') {
+            continue;
+        }
+        let rest = &text[at + prefix.len()..];
+        let end = rest.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(rest.len());
+        methods.push(&rest[..end]);
+    }
+    methods
+}
+
+/// The arguments of the call `text` starts with, `(…)`, split at top-level commas
+fn call_args(text: &str) -> Option<Vec<String>> {
+    let inner = text.trim().strip_prefix('(')?.strip_suffix(')')?;
+    Some(split_top_level(inner))
+}
+
+fn split_top_level(text: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut current = String::new();
+    for c in text.chars() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() {
+        parts.push(current.trim().to_string());
+    }
+    parts
+}
+
+/// The OpenQASM program being built from gate calls
+struct Builder {
+    name: String,
+    qubits: usize,
+    clbits: usize,
+    /// Set once `measure_all` added its own register
+    meas: bool,
+    body: Vec<String>,
+}
+
+impl Builder {
+    fn new(name: &str, qubits: usize, clbits: usize) -> Self {
+        Self { name: name.to_string(), qubits, clbits, meas: false, body: Vec::new() }
+    }
+
+    /// Translate one `method(args)` call on the circuit
+    fn call(&mut self, call: &str) -> Result<(), String> {
+        let open = call.find('(').ok_or_else(|| format!("`{}` is not a call", call))?;
+        let method = &call[..open];
+        let args = call_args(&call[open..]).ok_or_else(|| format!("could not read the arguments of `{}`", method))?;
+        if args.iter().any(|a| is_keyword_arg(a)) {
+            return Err(format!("keyword arguments to `{}` are not supported", method));
+        }
+        match method {
+            "measure_all" => {
+                self.body.push("barrier q;".to_string());
+                self.body.extend((0..self.qubits).map(|i| format!("measure q[{i}] -> meas[{i}];")));
+                self.meas = true;
+            }
+            "measure" => {
+                let [qubits, clbits] = args.as_slice() else {
+                    return Err("`measure` takes the qubits and the bits they are measured into".to_string());
+                };
+                let qubits = indexes(qubits, self.qubits, "qubit")?;
+                let clbits = indexes(clbits, self.clbits, "bit")?;
+                for pair in broadcast(&[qubits, clbits], method)? {
+                    self.body.push(format!("measure q[{}] -> c[{}];", pair[0], pair[1]));
+                }
+            }
+            "barrier" if args.is_empty() => self.body.push("barrier q;".to_string()),
+            "barrier" => {
+                let mut qubits = Vec::new();
+                for arg in &args {
+                    qubits.extend(indexes(arg, self.qubits, "qubit")?);
+                }
+                let operands: Vec<String> = qubits.iter().map(|q| format!("q[{}]", q)).collect();
+                self.body.push(format!("barrier {};", operands.join(",")));
+            }
+            "reset" => {
+                let [qubits] = args.as_slice() else {
+                    return Err("`reset` takes the qubits to reset".to_string());
+                };
+                for qubit in indexes(qubits, self.qubits, "qubit")? {
+                    self.body.push(format!("reset q[{}];", qubit));
+                }
+            }
+            _ => {
+                let Some(&(_, gate, angles, arity)) = GATES.iter().find(|(name, ..)| *name == method) else {
+                    return Err(format!("`{}.{}` has no OpenQASM translation", self.name, method));
+                };
+                if args.len() != angles + arity {
+                    return Err(format!("`{}` takes {} argument(s), got {}", method, angles + arity, args.len()));
+                }
+                let params = args[..angles].iter().map(|a| angle(a)).collect::<Result<Vec<_>, _>>()?;
+                let params = if params.is_empty() { String::new() } else { format!("({})", params.join(",")) };
+                let operands = args[angles..].iter().map(|a| indexes(a, self.qubits, "qubit")).collect::<Result<Vec<_>, _>>()?;
+                for qubits in broadcast(&operands, method)? {
+                    let qubits: Vec<String> = qubits.iter().map(|q| format!("q[{}]", q)).collect();
+                    self.body.push(format!("{}{} {};", gate, params, qubits.join(",")));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> String {
+        let mut qasm = format!("OPENQASM 2.0;\ninclude \"qelib1.inc\";\nqreg q[{}];\n", self.qubits);
+        if self.clbits > 0 {
+            qasm.push_str(&format!("creg c[{}];\n", self.clbits));
+        }
+        if self.meas {
+            qasm.push_str(&format!("creg meas[{}];\n", self.qubits));
+        }
+        for line in self.body {
+            qasm.push_str(&line);
+            qasm.push('\n');
+        }
+        qasm
+    }
+}
+
+fn is_keyword_arg(arg: &str) -> bool {
+    arg.split_once('=').is_some_and(|(name, value)| is_identifier(name.trim()) && !value.starts_with('='))
+}
+
+/// Qubit or bit indexes from `3`, `[0, 1]` or `range(2)` / `range(1, 3)`
+fn indexes(arg: &str, size: usize, what: &str) -> Result<Vec<usize>, String> {
+    let arg = arg.trim();
+    let not_numbers = || format!("`{}` is not a {} number or a list of them", arg, what);
+    let list: Vec<usize> = if let Some(inner) = arg.strip_prefix('[').and_then(|a| a.strip_suffix(']')) {
+        split_top_level(inner).iter().map(|i| i.parse()).collect::<Result<_, _>>().map_err(|_| not_numbers())?
+    } else if let Some(bounds) = arg.strip_prefix("range").and_then(call_args) {
+        let bounds: Vec<usize> = bounds.iter().map(|b| b.parse()).collect::<Result<_, _>>().map_err(|_| not_numbers())?;
+        match bounds.as_slice() {
+            [end] => (0..*end).collect(),
+            [start, end] => (*start..*end).collect(),
+            _ => return Err(not_numbers()),
+        }
+    } else {
+        vec![arg.parse().map_err(|_| not_numbers())?]
+    };
+    match list.iter().find(|i| **i >= size) {
+        Some(i) => Err(format!("{} {} is out of range for a circuit with {} {}s", what, i, size, what)),
+        None => Ok(list),
+    }
+}
+
+/// Zip the operand lists of a call, repeating single operands the way Qiskit
+/// broadcasts them
+fn broadcast(operands: &[Vec<usize>], method: &str) -> Result<Vec<Vec<usize>>, String> {
+    let n = operands.iter().map(Vec::len).max().unwrap_or(0);
+    if operands.iter().any(|o| o.len() != 1 && o.len() != n) {
+        return Err(format!("the argument lists of `{}` differ in length", method));
+    }
+    Ok((0..n).map(|i| operands.iter().map(|o| if o.len() == 1 { o[0] } else { o[i] }).collect()).collect())
+}
+
+/// An angle as OpenQASM: numbers, arithmetic and pi (`np.pi`, `math.pi`)
+fn angle(arg: &str) -> Result<String, String> {
+    let expr = arg.replace("numpy.pi", "pi").replace("np.pi", "pi").replace("math.pi", "pi");
+    if expr.contains("**") {
+        return Err(format!("`{}`: powers are not supported in angles", arg));
+    }
+    let mut rest = expr.as_str();
+    while let Some(start) = rest.find(|c: char| c.is_alphabetic() || c == '_') {
+        let word = &rest[start..];
+        let end = word.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(word.len());
+        // Exponents like 1e-3 are part of a number
+        let in_number = rest[..start].chars().next_back().is_some_and(|c| c.is_ascii_digit() || c == '.');
+        if &word[..end] != "pi" && !in_number {
+            return Err(format!("angle `{}`: qhub can't evaluate `{}`; write the value as a number", arg, &word[..end]));
+        }
+        rest = &word[end..];
+    }
+    if !expr.chars().all(|c| c.is_alphanumeric() || "+-*/(). _".contains(c)) {
+        return Err(format!("the angle `{}` is not a number", arg));
+    }
+    Ok(expr.split_whitespace().collect::<Vec<_>>().join(""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BELL: &str = r#"
+from qiskit import QuantumCircuit, transpile
+from qiskit_aer import AerSimulator
+
+qc = QuantumCircuit(2, 2)  # two qubits, two bits
+qc.h(0)
+qc.cx(0, 1)
+qc.measure([0, 1],
+           [0, 1])
+print(qc.draw())
+
+sim = AerSimulator()
+counts = sim.run(transpile(qc, sim), shots=1024).result().get_counts()
+for outcome, count in counts.items():
+    print(outcome, count)
+"#;
+
+    #[test]
+    fn test_straight_line_circuit() {
+        assert_eq!(
+            to_qasm(BELL).unwrap(),
+            "OPENQASM 2.0;\ninclude \"qelib1.inc\";\nqreg q[2];\ncreg c[2];\nh q[0];\ncx q[0],q[1];\n\
+             measure q[0] -> c[0];\nmeasure q[1] -> c[1];\n"
+        );
+    }
+
+    #[test]
+    fn test_angles_broadcast_and_measure_all() {
+        let source = "import numpy as np\ncircuit = QuantumCircuit(3)\ncircuit.h(range(3))\n\
+                      circuit.rz(np.pi / 4, 2)\ncircuit.cp(-pi/2, 0, [1, 2])\ncircuit.barrier()\ncircuit.measure_all()\n";
+        assert_eq!(
+            to_qasm(source).unwrap(),
+            "OPENQASM 2.0;\ninclude \"qelib1.inc\";\nqreg q[3];\ncreg meas[3];\nh q[0];\nh q[1];\nh q[2];\n\
+             rz(pi/4) q[2];\ncp(-pi/2) q[0],q[1];\ncp(-pi/2) q[0],q[2];\nbarrier q;\nbarrier q;\n\
+             measure q[0] -> meas[0];\nmeasure q[1] -> meas[1];\nmeasure q[2] -> meas[2];\n"
+        );
+    }
+
+    #[test]
+    fn test_embedded_qasm_is_used_as_is() {
+        let source = "from qiskit import QuantumCircuit\nqc = QuantumCircuit.from_qasm_str(\"\"\"\nOPENQASM 2.0;\nqreg q[1];\nh q[0];\n\"\"\")\n";
+        assert_eq!(to_qasm(source).unwrap(), "OPENQASM 2.0;\nqreg q[1];\nh q[0];");
+        let source = "qc = QuantumCircuit.from_qasm_str('OPENQASM 2.0;\\nqreg q[1];')";
+        assert_eq!(to_qasm(source).unwrap(), "OPENQASM 2.0;\nqreg q[1];");
+    }
+
+    #[test]
+    fn test_rejects_what_needs_python() {
+        let error = |source: &str| to_qasm(source).unwrap_err().to_string();
+        assert_eq!(error("import qiskit\nprint('hi')\n"), "no QuantumCircuit found");
+        assert_eq!(
+            error("qc = QuantumCircuit(3)\nfor i in range(2):\n    qc.cx(i, i + 1)\n"),
+            "line 3: the circuit is changed inside a `for` block"
+        );
+        assert_eq!(
+            error("theta = 0.5\nqc = QuantumCircuit(1)\nqc.rx(theta / 2, 0)\n"),
+            "line 3: angle `theta / 2`: qhub can't evaluate `theta`; write the value as a number"
+        );
+        assert_eq!(
+            error("qr = QuantumRegister(2)\nqc = QuantumCircuit(qr)\n"),
+            "line 2: QuantumCircuit takes registers or computed sizes here; give the qubit and bit counts as numbers"
+        );
+        assert_eq!(error("qc = QuantumCircuit(1)\nqc.h(1)\n"), "line 2: qubit 1 is out of range for a circuit with 1 qubits");
+        assert_eq!(
+            error("qc = QuantumCircuit(2)\nqc.append(CXGate(), [0, 1])\n"),
+            "line 2: `qc.append` has no OpenQASM translation"
+        );
+        assert_eq!(
+            error("qc = QuantumCircuit(2)\nbig = qc.compose(other)\n"),
+            "line 2: `qc.compose` is used inside an expression"
+        );
+        assert_eq!(error("qc = QuantumCircuit(1)\nqc.x(qubit=0)\n"), "line 2: keyword arguments to `x` are not supported");
+        // Exponents in numbers are fine
+        assert!(to_qasm("qc = QuantumCircuit(1)\nqc.rx(1e-3, 0)\n").unwrap().contains("rx(1e-3) q[0];"));
+    }
+}
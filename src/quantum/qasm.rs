@@ -0,0 +1,903 @@
+// OpenQASM parsing
+//
+// Reads OpenQASM 2.0 and 3.0 programs into a flat `Circuit`. The version is
+// taken from the `OPENQASM x.y;` header and selects the statement parser; both
+// share register handling, operand resolution and gate validation. QASM 3
+// constructs that go beyond what a gate-level simulator can run (loops,
+// subroutines, classical types, gate modifiers, ...) are rejected with
+// `QasmError::Unsupported` so callers can tell them apart from typos.
+
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QasmVersion {
+    V2,
+    V3,
+}
+
+impl std::fmt::Display for QasmVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QasmVersion::V2 => write!(f, "2.0"),
+            QasmVersion::V3 => write!(f, "3.0"),
+        }
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum QasmError {
+    #[error("missing OPENQASM version header (expected e.g. `OPENQASM 3.0;`)")]
+    MissingHeader,
+    #[error("unsupported OpenQASM version '{0}' (supported: 2.0, 3.0)")]
+    UnsupportedVersion(String),
+    #[error("line {line}: unsupported QASM3 feature: {feature}")]
+    Unsupported { line: usize, feature: String },
+    #[error("line {line}: {message}")]
+    Syntax { line: usize, message: String },
+}
+
+/// A named slice of the circuit's flat qubit or bit index space
+#[derive(Debug, Clone, PartialEq)]
+pub struct Register {
+    pub name: String,
+    pub size: usize,
+    pub offset: usize,
+}
+
+/// Classical condition guarding an operation
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    /// Whole register compared to an integer (`if (c == 3)`)
+    Register { name: String, value: u64 },
+    /// Single bit compared to 0/1 (`if (c[0])`, `if (c[1] == 0)`)
+    Bit { clbit: usize, value: bool },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation {
+    Gate { name: String, params: Vec<String>, qubits: Vec<usize> },
+    Measure { qubit: usize, clbit: usize },
+    Reset { qubit: usize },
+    Barrier { qubits: Vec<usize> },
+    Conditional { condition: Condition, op: Box<Operation> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Circuit {
+    pub version: QasmVersion,
+    pub qregs: Vec<Register>,
+    pub cregs: Vec<Register>,
+    /// Names of gates defined in the program itself
+    pub custom_gates: Vec<String>,
+    pub operations: Vec<Operation>,
+}
+
+impl Circuit {
+    pub fn num_qubits(&self) -> usize {
+        self.qregs.iter().map(|r| r.size).sum()
+    }
+
+    pub fn num_clbits(&self) -> usize {
+        self.cregs.iter().map(|r| r.size).sum()
+    }
+
+    /// One-line description, as printed by `qhub run`
+    pub fn summary(&self) -> String {
+        format!(
+            "OpenQASM {} circuit: {} qubit(s), {} bit(s), {} operation(s)",
+            self.version,
+            self.num_qubits(),
+            self.num_clbits(),
+            self.operations.len()
+        )
+    }
+}
+
+/// Gates from qelib1.inc / stdgates.inc and the built-ins, with their qubit counts
+const STANDARD_GATES: &[(&str, usize)] = &[
+    ("U", 1), ("u", 1), ("u0", 1), ("u1", 1), ("u2", 1), ("u3", 1), ("p", 1), ("phase", 1),
+    ("id", 1), ("x", 1), ("y", 1), ("z", 1), ("h", 1), ("s", 1), ("sdg", 1), ("t", 1),
+    ("tdg", 1), ("sx", 1), ("sxdg", 1), ("rx", 1), ("ry", 1), ("rz", 1),
+    ("CX", 2), ("cx", 2), ("cy", 2), ("cz", 2), ("ch", 2), ("cp", 2), ("cphase", 2),
+    ("crx", 2), ("cry", 2), ("crz", 2), ("cu", 2), ("cu1", 2), ("cu3", 2), ("csx", 2),
+    ("swap", 2), ("rxx", 2), ("ryy", 2), ("rzz", 2), ("rzx", 2), ("ecr", 2), ("iswap", 2),
+    ("ccx", 3), ("cswap", 3), ("rccx", 3), ("c3x", 4), ("c4x", 5),
+];
+
+/// Qubits a standard gate acts on, or None for a name that isn't one
+pub fn standard_gate_qubits(name: &str) -> Option<usize> {
+    STANDARD_GATES.iter().find(|(gate, _)| *gate == name).map(|(_, qubits)| *qubits)
+}
+
+/// QASM 3 statements that open a construct we cannot run
+const UNSUPPORTED_V3_KEYWORDS: &[(&str, &str)] = &[
+    ("for", "for loops"),
+    ("while", "while loops"),
+    ("def", "subroutine definitions (def)"),
+    ("defcal", "calibration definitions (defcal)"),
+    ("cal", "calibration blocks (cal)"),
+    ("defcalgrammar", "calibration grammars"),
+    ("box", "box blocks"),
+    ("delay", "delay instructions"),
+    ("let", "register aliases (let)"),
+    ("switch", "switch statements"),
+    ("input", "input parameters"),
+    ("output", "output parameters"),
+    ("extern", "extern functions"),
+    ("const", "classical constants"),
+    ("int", "classical int variables"),
+    ("uint", "classical uint variables"),
+    ("float", "classical float variables"),
+    ("angle", "classical angle variables"),
+    ("bool", "classical bool variables"),
+    ("complex", "classical complex variables"),
+    ("duration", "duration variables"),
+    ("stretch", "stretch variables"),
+    ("array", "classical arrays"),
+    ("return", "return statements"),
+    ("break", "break statements"),
+    ("continue", "continue statements"),
+    ("end", "end statements"),
+    ("gphase", "global phase (gphase)"),
+];
+
+/// One statement of source text with the line it starts on
+#[derive(Debug)]
+struct Statement {
+    text: String,
+    line: usize,
+}
+
+/// Parse an OpenQASM 2.0 or 3.0 program
+pub fn parse(source: &str) -> Result<Circuit, QasmError> {
+    let statements = split_statements(&strip_comments(source))?;
+    let mut statements = statements.into_iter();
+
+    let header = statements.next().ok_or(QasmError::MissingHeader)?;
+    let version = match header.text.strip_prefix("OPENQASM") {
+        Some(v) => match v.trim() {
+            "2.0" | "2" => QasmVersion::V2,
+            "3.0" | "3" => QasmVersion::V3,
+            other => return Err(QasmError::UnsupportedVersion(other.to_string())),
+        },
+        None => return Err(QasmError::MissingHeader),
+    };
+
+    let mut parser = Parser::new(version);
+    for statement in statements {
+        match version {
+            QasmVersion::V2 => parser.statement_v2(&statement)?,
+            QasmVersion::V3 => parser.statement_v3(&statement)?,
+        }
+    }
+    Ok(parser.circuit)
+}
+
+/// Remove `//` and `/* */` comments, keeping newlines so line numbers stay right
+fn strip_comments(source: &str) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut chars = source.chars().peekable();
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            in_string = c != '"';
+            out.push(c);
+            continue;
+        }
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                out.push(c);
+            }
+            ('/', Some('/')) => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        out.push('\n');
+                        break;
+                    }
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        out.push('\n');
+                    }
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Split source into statements at `;`, keeping `{ ... }` blocks attached
+/// to the statement that opens them
+fn split_statements(source: &str) -> Result<Vec<Statement>, QasmError> {
+    let mut statements = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0usize;
+    let mut line = 1;
+    let mut start_line = 1;
+
+    for c in source.chars() {
+        if current.trim().is_empty() {
+            start_line = line;
+        }
+        if c == '\n' {
+            line += 1;
+        }
+        match c {
+            '{' => {
+                depth += 1;
+                current.push(c);
+            }
+            '}' => {
+                if depth == 0 {
+                    return Err(syntax(line, "unexpected '}'"));
+                }
+                depth -= 1;
+                current.push(c);
+                if depth == 0 {
+                    push_statement(&mut statements, &mut current, start_line);
+                }
+            }
+            ';' if depth == 0 => push_statement(&mut statements, &mut current, start_line),
+            _ => current.push(c),
+        }
+    }
+
+    if depth > 0 {
+        return Err(syntax(line, "unclosed '{'"));
+    }
+    if !current.trim().is_empty() {
+        return Err(syntax(start_line, "missing ';' at end of statement"));
+    }
+    Ok(statements)
+}
+
+fn push_statement(statements: &mut Vec<Statement>, current: &mut String, line: usize) {
+    let text = current.split_whitespace().collect::<Vec<_>>().join(" ");
+    if !text.is_empty() {
+        statements.push(Statement { text, line });
+    }
+    current.clear();
+}
+
+fn syntax(line: usize, message: impl Into<String>) -> QasmError {
+    QasmError::Syntax { line, message: message.into() }
+}
+
+fn unsupported(line: usize, feature: impl Into<String>) -> QasmError {
+    QasmError::Unsupported { line, feature: feature.into() }
+}
+
+/// Split `head rest` at the first space or '('
+fn first_word(text: &str) -> (&str, &str) {
+    let end = text
+        .find(|c: char| c.is_whitespace() || c == '(' || c == '[')
+        .unwrap_or(text.len());
+    (&text[..end], text[end..].trim_start())
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Split on commas that are not nested inside brackets or parentheses
+fn split_top_level(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(text[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    let last = text[start..].trim();
+    if !last.is_empty() {
+        parts.push(last);
+    }
+    parts
+}
+
+/// Find the `)` matching the `(` at the start of `text`
+fn matching_paren(text: &str, line: usize) -> Result<usize, QasmError> {
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    Err(syntax(line, "unbalanced parentheses"))
+}
+
+#[derive(Debug, Clone)]
+struct GateDef {
+    qubits: usize,
+}
+
+struct Parser {
+    circuit: Circuit,
+    gates: HashMap<String, GateDef>,
+}
+
+impl Parser {
+    fn new(version: QasmVersion) -> Self {
+        let gates = STANDARD_GATES
+            .iter()
+            .map(|(name, qubits)| (name.to_string(), GateDef { qubits: *qubits }))
+            .collect();
+        Self {
+            circuit: Circuit {
+                version,
+                qregs: Vec::new(),
+                cregs: Vec::new(),
+                custom_gates: Vec::new(),
+                operations: Vec::new(),
+            },
+            gates,
+        }
+    }
+
+    // ---------------------------------------------------------------------
+    // Version-specific statement dispatch
+    // ---------------------------------------------------------------------
+
+    fn statement_v2(&mut self, st: &Statement) -> Result<(), QasmError> {
+        let (keyword, rest) = first_word(&st.text);
+        match keyword {
+            "include" => Ok(()),
+            "qreg" => self.declare_v2(rest, true, st.line),
+            "creg" => self.declare_v2(rest, false, st.line),
+            "gate" | "opaque" => self.gate_definition(rest, st.line),
+            "if" => {
+                let (condition, body) = self.condition(rest, st.line)?;
+                let ops = self.operations_v2(body, st.line)?;
+                self.push_conditional(condition, ops);
+                Ok(())
+            }
+            _ => {
+                let ops = self.operations_v2(&st.text, st.line)?;
+                self.circuit.operations.extend(ops);
+                Ok(())
+            }
+        }
+    }
+
+    fn statement_v3(&mut self, st: &Statement) -> Result<(), QasmError> {
+        let text = st.text.as_str();
+        if text.starts_with("#pragma") || text.starts_with("pragma") {
+            return Err(unsupported(st.line, "pragmas"));
+        }
+        if text.starts_with('@') {
+            return Err(unsupported(st.line, "annotations"));
+        }
+
+        let (keyword, rest) = first_word(text);
+        if let Some((_, feature)) = UNSUPPORTED_V3_KEYWORDS.iter().find(|(k, _)| *k == keyword) {
+            return Err(unsupported(st.line, *feature));
+        }
+
+        match keyword {
+            "include" => Ok(()),
+            "qubit" => self.declare_v3(rest, true, st.line),
+            "bit" => self.declare_v3(rest, false, st.line),
+            // Legacy declarations are still valid QASM 3
+            "qreg" => self.declare_v2(rest, true, st.line),
+            "creg" => self.declare_v2(rest, false, st.line),
+            "gate" => self.gate_definition(rest, st.line),
+            "opaque" => Err(unsupported(st.line, "opaque gates")),
+            "if" => {
+                let (condition, body) = self.condition(rest, st.line)?;
+                let ops = self.block_v3(body, st.line)?;
+                self.push_conditional(condition, ops);
+                Ok(())
+            }
+            _ => {
+                let ops = self.operation_v3(text, st.line)?;
+                self.circuit.operations.extend(ops);
+                Ok(())
+            }
+        }
+    }
+
+    /// Body of a QASM 3 `if`: a single statement or a `{ ... }` block
+    fn block_v3(&mut self, body: &str, line: usize) -> Result<Vec<Operation>, QasmError> {
+        let body = body.trim();
+        let inner = match body.strip_prefix('{') {
+            Some(rest) => {
+                let (inner, tail) = rest
+                    .rsplit_once('}')
+                    .ok_or_else(|| syntax(line, "unclosed '{' in if body"))?;
+                if tail.trim_start().starts_with("else") {
+                    return Err(unsupported(line, "else branches"));
+                }
+                inner
+            }
+            None => body,
+        };
+
+        let mut ops = Vec::new();
+        for part in inner.split(';').map(str::trim).filter(|p| !p.is_empty()) {
+            let (keyword, _) = first_word(part);
+            if keyword == "if" {
+                return Err(unsupported(line, "nested if statements"));
+            }
+            if let Some((_, feature)) = UNSUPPORTED_V3_KEYWORDS.iter().find(|(k, _)| *k == keyword) {
+                return Err(unsupported(line, *feature));
+            }
+            ops.extend(self.operation_v3(part, line)?);
+        }
+        Ok(ops)
+    }
+
+    /// A QASM 2 quantum operation: gate, measure, reset or barrier
+    fn operations_v2(&mut self, text: &str, line: usize) -> Result<Vec<Operation>, QasmError> {
+        let (keyword, rest) = first_word(text);
+        match keyword {
+            "measure" => self.measure_arrow(rest, line),
+            "reset" => self.reset(rest, line),
+            "barrier" => self.barrier(rest, line),
+            _ => self.gate_call(text, line),
+        }
+    }
+
+    /// A QASM 3 quantum operation, including `c[0] = measure q[0]`
+    fn operation_v3(&mut self, text: &str, line: usize) -> Result<Vec<Operation>, QasmError> {
+        if text.contains('@') {
+            return Err(unsupported(line, "gate modifiers (ctrl @, inv @, pow @)"));
+        }
+        if let Some((target, source)) = text.split_once('=') {
+            let source = source.trim();
+            let target = target.trim();
+            return match source.strip_prefix("measure") {
+                Some(qubits) => self.measure_assign(target, qubits, line),
+                None => Err(unsupported(line, "classical assignments")),
+            };
+        }
+        self.operations_v2(text, line)
+    }
+
+    // ---------------------------------------------------------------------
+    // Declarations
+    // ---------------------------------------------------------------------
+
+    /// `q[2]` (after `qreg` / `creg`)
+    fn declare_v2(&mut self, rest: &str, quantum: bool, line: usize) -> Result<(), QasmError> {
+        let (name, size) = rest
+            .split_once('[')
+            .and_then(|(name, size)| Some((name.trim(), size.strip_suffix(']')?.trim())))
+            .ok_or_else(|| syntax(line, format!("expected `name[size]`, found `{}`", rest)))?;
+        let size = size
+            .parse()
+            .map_err(|_| syntax(line, format!("invalid register size `{}`", size)))?;
+        self.add_register(name, size, quantum, line)
+    }
+
+    /// `[2] q` or `q` (after `qubit` / `bit`)
+    fn declare_v3(&mut self, rest: &str, quantum: bool, line: usize) -> Result<(), QasmError> {
+        let (size, name) = match rest.strip_prefix('[') {
+            Some(rest) => {
+                let (size, name) = rest
+                    .split_once(']')
+                    .ok_or_else(|| syntax(line, "expected `]` in declaration"))?;
+                let size = size
+                    .trim()
+                    .parse()
+                    .map_err(|_| unsupported(line, "register sizes that are not integer literals"))?;
+                (size, name.trim())
+            }
+            None => (1, rest.trim()),
+        };
+        if name.contains('=') {
+            return Err(unsupported(line, "initialised bit declarations"));
+        }
+        self.add_register(name, size, quantum, line)
+    }
+
+    fn add_register(&mut self, name: &str, size: usize, quantum: bool, line: usize) -> Result<(), QasmError> {
+        if !is_identifier(name) {
+            return Err(syntax(line, format!("invalid register name `{}`", name)));
+        }
+        if size == 0 {
+            return Err(syntax(line, format!("register `{}` must have at least one bit", name)));
+        }
+        if self.find_register(name, true).is_some() || self.find_register(name, false).is_some() {
+            return Err(syntax(line, format!("register `{}` is already declared", name)));
+        }
+
+        let registers = if quantum {
+            &mut self.circuit.qregs
+        } else {
+            &mut self.circuit.cregs
+        };
+        let offset = registers.iter().map(|r| r.size).sum();
+        registers.push(Register { name: name.to_string(), size, offset });
+        Ok(())
+    }
+
+    /// `name(params) a, b { body }` - only the signature is validated
+    fn gate_definition(&mut self, rest: &str, line: usize) -> Result<(), QasmError> {
+        let signature = rest.split('{').next().unwrap_or(rest).trim();
+        let (name, after) = first_word(signature);
+        let args = if after.starts_with('(') {
+            let close = matching_paren(after, line)?;
+            after[close + 1..].trim()
+        } else {
+            after
+        };
+
+        if !is_identifier(name) {
+            return Err(syntax(line, format!("invalid gate name `{}`", name)));
+        }
+        let qubits = split_top_level(args).len();
+        if qubits == 0 {
+            return Err(syntax(line, format!("gate `{}` must act on at least one qubit", name)));
+        }
+
+        self.gates.insert(name.to_string(), GateDef { qubits });
+        self.circuit.custom_gates.push(name.to_string());
+        Ok(())
+    }
+
+    // ---------------------------------------------------------------------
+    // Operations
+    // ---------------------------------------------------------------------
+
+    fn gate_call(&mut self, text: &str, line: usize) -> Result<Vec<Operation>, QasmError> {
+        let (name, after) = first_word(text);
+        let (params, args) = if after.starts_with('(') {
+            let close = matching_paren(after, line)?;
+            let params = split_top_level(&after[1..close])
+                .into_iter()
+                .map(str::to_string)
+                .collect();
+            (params, after[close + 1..].trim())
+        } else {
+            (Vec::new(), after)
+        };
+
+        let def = self
+            .gates
+            .get(name)
+            .cloned()
+            .ok_or_else(|| syntax(line, format!("unknown gate `{}`", name)))?;
+
+        let operands = split_top_level(args)
+            .into_iter()
+            .map(|arg| self.qubits(arg, line))
+            .collect::<Result<Vec<_>, _>>()?;
+        if operands.len() != def.qubits {
+            return Err(syntax(
+                line,
+                format!("gate `{}` takes {} qubit(s), got {}", name, def.qubits, operands.len()),
+            ));
+        }
+
+        Ok(broadcast(&operands, line)?
+            .into_iter()
+            .map(|qubits| Operation::Gate { name: name.to_string(), params: params.clone(), qubits })
+            .collect())
+    }
+
+    /// `q[0] -> c[0]` or `q -> c`
+    fn measure_arrow(&self, rest: &str, line: usize) -> Result<Vec<Operation>, QasmError> {
+        let (qubits, clbits) = rest
+            .split_once("->")
+            .ok_or_else(|| syntax(line, "expected `measure qubit -> bit`"))?;
+        self.measure_pairs(qubits, clbits, line)
+    }
+
+    /// `c[0] = measure q[0]`
+    fn measure_assign(&self, target: &str, qubits: &str, line: usize) -> Result<Vec<Operation>, QasmError> {
+        self.measure_pairs(qubits, target, line)
+    }
+
+    fn measure_pairs(&self, qubits: &str, clbits: &str, line: usize) -> Result<Vec<Operation>, QasmError> {
+        let qubits = self.qubits(qubits.trim(), line)?;
+        let clbits = self.clbits(clbits.trim(), line)?;
+        if qubits.len() != clbits.len() {
+            return Err(syntax(
+                line,
+                format!("cannot measure {} qubit(s) into {} bit(s)", qubits.len(), clbits.len()),
+            ));
+        }
+        Ok(qubits
+            .into_iter()
+            .zip(clbits)
+            .map(|(qubit, clbit)| Operation::Measure { qubit, clbit })
+            .collect())
+    }
+
+    fn reset(&self, rest: &str, line: usize) -> Result<Vec<Operation>, QasmError> {
+        Ok(self
+            .qubits(rest, line)?
+            .into_iter()
+            .map(|qubit| Operation::Reset { qubit })
+            .collect())
+    }
+
+    fn barrier(&self, rest: &str, line: usize) -> Result<Vec<Operation>, QasmError> {
+        let mut qubits = Vec::new();
+        if rest.is_empty() {
+            // QASM 3 allows a bare `barrier;` meaning all qubits
+            qubits.extend(0..self.circuit.num_qubits());
+        }
+        for arg in split_top_level(rest) {
+            qubits.extend(self.qubits(arg, line)?);
+        }
+        Ok(vec![Operation::Barrier { qubits }])
+    }
+
+    /// `(c == 1) body` / `(c[0]) body` - returns the condition and the remaining body
+    fn condition<'a>(&self, rest: &'a str, line: usize) -> Result<(Condition, &'a str), QasmError> {
+        if !rest.starts_with('(') {
+            return Err(syntax(line, "expected `(` after if"));
+        }
+        let close = matching_paren(rest, line)?;
+        let expr = rest[1..close].trim();
+        let body = rest[close + 1..].trim();
+
+        let (lhs, value) = match expr.split_once("==") {
+            Some((lhs, rhs)) => {
+                let rhs = rhs.trim();
+                let value = match rhs {
+                    "true" => 1,
+                    "false" => 0,
+                    _ => rhs
+                        .parse()
+                        .map_err(|_| unsupported(line, format!("condition value `{}`", rhs)))?,
+                };
+                (lhs.trim(), Some(value))
+            }
+            None if expr.contains(|c: char| "<>!&|".contains(c)) => {
+                return Err(unsupported(line, format!("condition `{}`", expr)));
+            }
+            None => (expr, None),
+        };
+
+        let condition = if lhs.contains('[') {
+            let clbits = self.clbits(lhs, line)?;
+            let value = value.unwrap_or(1);
+            if value > 1 {
+                return Err(syntax(line, format!("bit compared to {}", value)));
+            }
+            Condition::Bit { clbit: clbits[0], value: value == 1 }
+        } else {
+            if self.find_register(lhs, false).is_none() {
+                return Err(syntax(line, format!("unknown classical register `{}`", lhs)));
+            }
+            match value {
+                Some(value) => Condition::Register { name: lhs.to_string(), value },
+                // `if (c)` on a single-bit register
+                None if self.find_register(lhs, false).map(|r| r.size) == Some(1) => {
+                    Condition::Bit { clbit: self.clbits(lhs, line)?[0], value: true }
+                }
+                None => return Err(syntax(line, format!("register `{}` needs a comparison", lhs))),
+            }
+        };
+
+        if body.is_empty() {
+            return Err(syntax(line, "if statement has no body"));
+        }
+        Ok((condition, body))
+    }
+
+    fn push_conditional(&mut self, condition: Condition, ops: Vec<Operation>) {
+        self.circuit.operations.extend(ops.into_iter().map(|op| Operation::Conditional {
+            condition: condition.clone(),
+            op: Box::new(op),
+        }));
+    }
+
+    // ---------------------------------------------------------------------
+    // Operand resolution
+    // ---------------------------------------------------------------------
+
+    fn find_register(&self, name: &str, quantum: bool) -> Option<&Register> {
+        let registers = if quantum { &self.circuit.qregs } else { &self.circuit.cregs };
+        registers.iter().find(|r| r.name == name)
+    }
+
+    fn qubits(&self, operand: &str, line: usize) -> Result<Vec<usize>, QasmError> {
+        self.resolve(operand, true, line)
+    }
+
+    fn clbits(&self, operand: &str, line: usize) -> Result<Vec<usize>, QasmError> {
+        self.resolve(operand, false, line)
+    }
+
+    /// `q` (all bits) or `q[i]` to flat indices
+    fn resolve(&self, operand: &str, quantum: bool, line: usize) -> Result<Vec<usize>, QasmError> {
+        let kind = if quantum { "quantum" } else { "classical" };
+        let (name, index) = match operand.split_once('[') {
+            Some((name, index)) => {
+                let index = index
+                    .strip_suffix(']')
+                    .ok_or_else(|| syntax(line, format!("expected `]` in `{}`", operand)))?
+                    .trim();
+                (name.trim(), Some(index))
+            }
+            None => (operand.trim(), None),
+        };
+
+        let register = self
+            .find_register(name, quantum)
+            .ok_or_else(|| syntax(line, format!("unknown {} register `{}`", kind, name)))?;
+
+        match index {
+            None => Ok((register.offset..register.offset + register.size).collect()),
+            Some(index) => {
+                let i: usize = index.parse().map_err(|_| {
+                    if index.contains(':') || index.contains('{') {
+                        unsupported(line, "register slicing")
+                    } else {
+                        syntax(line, format!("invalid index `{}`", index))
+                    }
+                })?;
+                if i >= register.size {
+                    return Err(syntax(
+                        line,
+                        format!("index {} out of range for `{}[{}]`", i, name, register.size),
+                    ));
+                }
+                Ok(vec![register.offset + i])
+            }
+        }
+    }
+}
+
+/// Apply a gate across whole-register operands: `cx q, r` becomes `cx q[i], r[i]`
+fn broadcast(operands: &[Vec<usize>], line: usize) -> Result<Vec<Vec<usize>>, QasmError> {
+    let width = operands.iter().map(Vec::len).max().unwrap_or(1);
+    if operands.iter().any(|o| o.len() != 1 && o.len() != width) {
+        return Err(syntax(line, "registers in one gate call must have the same size"));
+    }
+
+    let calls: Vec<Vec<usize>> = (0..width)
+        .map(|i| operands.iter().map(|o| if o.len() == 1 { o[0] } else { o[i] }).collect())
+        .collect();
+    for qubits in &calls {
+        let mut seen = qubits.clone();
+        seen.sort_unstable();
+        seen.dedup();
+        if seen.len() != qubits.len() {
+            return Err(syntax(line, "a gate cannot use the same qubit twice"));
+        }
+    }
+    Ok(calls)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_qasm2_bell_state() {
+        let circuit = parse(
+            r#"OPENQASM 2.0;
+include "qelib1.inc";
+qreg q[2];
+creg c[2];
+h q[0];
+cx q[0],q[1];
+measure q -> c;
+"#,
+        )
+        .unwrap();
+        assert_eq!(circuit.version, QasmVersion::V2);
+        assert_eq!(circuit.num_qubits(), 2);
+        assert_eq!(circuit.operations.len(), 4);
+        assert_eq!(circuit.operations[3], Operation::Measure { qubit: 1, clbit: 1 });
+    }
+
+    #[test]
+    fn test_qasm3_as_emitted_by_qiskit() {
+        let circuit = parse(
+            r#"OPENQASM 3.0;
+include "stdgates.inc";
+gate rzx(_gate_p_0) _gate_q_0, _gate_q_1 {
+  h _gate_q_1;
+  cx _gate_q_0, _gate_q_1;
+  rz(_gate_p_0) _gate_q_1;
+}
+bit[2] meas;
+qubit[2] q;
+h q[0];
+rzx(pi/4) q[0], q[1];
+barrier q[0], q[1];
+meas[0] = measure q[0];
+meas[1] = measure q[1];
+if (meas[0]) x q[1];
+if (meas == 3) {
+  reset q[0];
+  reset q[1];
+}
+"#,
+        )
+        .unwrap();
+        assert_eq!(circuit.version, QasmVersion::V3);
+        assert_eq!(circuit.custom_gates, vec!["rzx".to_string()]);
+        assert_eq!(circuit.num_clbits(), 2);
+        assert_eq!(
+            circuit.operations[1],
+            Operation::Gate { name: "rzx".into(), params: vec!["pi/4".into()], qubits: vec![0, 1] }
+        );
+        assert!(matches!(
+            &circuit.operations[5],
+            Operation::Conditional { condition: Condition::Bit { clbit: 0, value: true }, .. }
+        ));
+        assert_eq!(circuit.operations.len(), 8);
+    }
+
+    #[test]
+    fn test_qasm2_classical_control() {
+        let circuit = parse("OPENQASM 2.0; qreg q[1]; creg c[1]; measure q[0] -> c[0]; if(c==1) x q[0];")
+            .unwrap();
+        assert_eq!(
+            circuit.operations[1],
+            Operation::Conditional {
+                condition: Condition::Register { name: "c".into(), value: 1 },
+                op: Box::new(Operation::Gate { name: "x".into(), params: vec![], qubits: vec![0] }),
+            }
+        );
+    }
+
+    #[test]
+    fn test_unsupported_qasm3_features_are_reported() {
+        let err = parse("OPENQASM 3.0;\nqubit[2] q;\nfor int i in [0:1] { h q[i]; }").unwrap_err();
+        assert_eq!(err, QasmError::Unsupported { line: 3, feature: "for loops".into() });
+
+        let err = parse("OPENQASM 3.0; qubit[2] q; ctrl @ x q[0], q[1];").unwrap_err();
+        assert!(matches!(err, QasmError::Unsupported { .. }));
+        assert!(err.to_string().contains("unsupported QASM3 feature"));
+
+        // QASM 3 syntax is a plain syntax error in a QASM 2 program
+        let err = parse("OPENQASM 2.0; qubit[2] q;").unwrap_err();
+        assert!(matches!(err, QasmError::Syntax { .. }));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(parse("qreg q[1];").unwrap_err(), QasmError::MissingHeader);
+        assert_eq!(
+            parse("OPENQASM 4.0;").unwrap_err(),
+            QasmError::UnsupportedVersion("4.0".into())
+        );
+        assert!(parse("OPENQASM 2.0; qreg q[1]; foo q[0];").unwrap_err().to_string().contains("unknown gate"));
+        assert!(parse("OPENQASM 2.0; qreg q[1]; h q[1];").unwrap_err().to_string().contains("out of range"));
+        assert!(parse("OPENQASM 2.0; qreg q[2]; cx q[0], q[0];").is_err());
+    }
+
+    #[test]
+    fn test_comments_are_ignored() {
+        let circuit = parse("// generated\nOPENQASM 2.0;\nqreg q[1];").unwrap();
+        assert_eq!(circuit.version, QasmVersion::V2);
+
+        let circuit = parse("OPENQASM 3;\n/* two\nlines */\nqubit q; // trailing\nh q;").unwrap();
+        assert_eq!(circuit.operations.len(), 1);
+    }
+}
@@ -0,0 +1,336 @@
+// Choosing a backend for a circuit
+//
+// Users new to the IBM fleet rarely know which device to pick. Given what a
+// circuit needs and the live backend list, `select_backend` keeps the
+// operational backends large enough for it that the tier allows, takes the
+// ones with the shortest queues and ranks those by the share of shots
+// expected to finish without a two-qubit gate error, discounted by the wait.
+// Error rates come from each backend's properties when they could be
+// fetched. When no IBM backend fits, or the tier only allows simulators, the
+// local simulator is recommended if the circuit is small enough for it.
+
+use std::collections::HashMap;
+
+use super::estimate::MINUTES_PER_QUEUED_JOB;
+use super::qasm::{Circuit, Operation};
+use super::simulator;
+use crate::api::ibm_quantum::BackendStatus;
+use crate::config::tiers::TierLimits;
+
+/// Backends ranked per recommendation; their error rates are looked up
+pub const SHORTLIST: usize = 3;
+
+/// Two-qubit error assumed when a backend reports none
+const DEFAULT_2Q_ERROR: f64 = 0.01;
+
+/// A queue this long halves a backend's score
+const QUEUE_HALF_MINUTES: f64 = 30.0;
+
+/// Beyond this depth decoherence dominates on current devices
+const DEEP_CIRCUIT: usize = 500;
+
+/// What a circuit asks of a backend
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Requirements {
+    pub qubits: usize,
+    /// Layers of operations, with each one starting once its qubits are free
+    pub depth: usize,
+    /// Two-qubit gates after decomposing larger ones
+    pub two_qubit_gates: usize,
+}
+
+impl Requirements {
+    pub fn of(circuit: &Circuit) -> Self {
+        let mut layers = vec![0_usize; circuit.num_qubits()];
+        let mut two_qubit_gates = 0;
+        for op in &circuit.operations {
+            let mut op = op;
+            while let Operation::Conditional { op: inner, .. } = op {
+                op = inner;
+            }
+            let (qubits, adds) = match op {
+                Operation::Gate { name, qubits, .. } => {
+                    two_qubit_gates += match (name.as_str(), qubits.len()) {
+                        ("swap", _) => 3,
+                        (_, 0 | 1) => 0,
+                        (_, 2) => 1,
+                        // A Toffoli takes 6 CX; wider gates grow with it
+                        (_, n) => 6 * (n - 2),
+                    };
+                    (qubits.clone(), 1)
+                }
+                Operation::Measure { qubit, .. } | Operation::Reset { qubit } => (vec![*qubit], 1),
+                // Lines its qubits up without adding a layer
+                Operation::Barrier { qubits } => (qubits.clone(), 0),
+                Operation::Conditional { .. } => unreachable!(),
+            };
+            let start = qubits.iter().filter_map(|&q| layers.get(q)).max().copied().unwrap_or(0);
+            for q in qubits {
+                if let Some(layer) = layers.get_mut(q) {
+                    *layer = start + adds;
+                }
+            }
+        }
+        Requirements {
+            qubits: circuit.num_qubits(),
+            depth: layers.into_iter().max().unwrap_or(0),
+            two_qubit_gates,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recommendation {
+    /// None for the local simulator
+    pub backend: Option<String>,
+    pub reasons: Vec<String>,
+    /// Runners-up, best first, described in a few words
+    pub alternatives: Vec<String>,
+}
+
+impl Recommendation {
+    pub fn name(&self) -> &str {
+        self.backend.as_deref().unwrap_or("the local simulator")
+    }
+
+    /// The choice, why, and what else would do
+    pub fn report(&self) -> String {
+        let mut text = format!("Recommended backend: {}", self.name());
+        for reason in &self.reasons {
+            text.push_str(&format!("\n  - {}", reason));
+        }
+        if !self.alternatives.is_empty() {
+            text.push_str(&format!("\nAlso possible: {}", self.alternatives.join("; ")));
+        }
+        text
+    }
+}
+
+fn is_simulator(name: &str) -> bool {
+    name.contains("simulator")
+}
+
+/// Backends that can run the circuit under `limits`, shortest queue first
+pub fn candidates<'a>(
+    requirements: &Requirements,
+    backends: &'a [BackendStatus],
+    limits: &TierLimits,
+) -> Vec<&'a BackendStatus> {
+    let mut fitting: Vec<&BackendStatus> = backends
+        .iter()
+        .filter(|b| b.operational && b.qubits.is_some_and(|q| q as usize >= requirements.qubits))
+        .filter(|b| limits.hardware || is_simulator(&b.name))
+        .collect();
+    fitting.sort_by(|a, b| {
+        a.pending_jobs
+            .unwrap_or(u32::MAX)
+            .cmp(&b.pending_jobs.unwrap_or(u32::MAX))
+            .then(a.name.cmp(&b.name))
+    });
+    fitting
+}
+
+/// Share of shots expected to finish without a two-qubit gate error
+fn error_free(requirements: &Requirements, error: f64) -> f64 {
+    (1.0 - error).powi(requirements.two_qubit_gates.min(i32::MAX as usize) as i32)
+}
+
+/// Pick the backend for a circuit needing `requirements`. `errors` holds
+/// the mean two-qubit error of the backends it could be fetched for. Err
+/// explains why nothing can run it.
+pub fn select_backend(
+    requirements: &Requirements,
+    backends: &[BackendStatus],
+    errors: &HashMap<String, f64>,
+    limits: &TierLimits,
+) -> Result<Recommendation, String> {
+    let shortlist: Vec<&BackendStatus> = candidates(requirements, backends, limits).into_iter().take(SHORTLIST).collect();
+    if shortlist.is_empty() {
+        return local_simulator(requirements, backends, limits);
+    }
+
+    let scored: Vec<(&BackendStatus, f64, f64, f64)> = shortlist
+        .into_iter()
+        .map(|backend| {
+            let error = if is_simulator(&backend.name) {
+                0.0
+            } else {
+                errors.get(&backend.name).copied().unwrap_or(DEFAULT_2Q_ERROR)
+            };
+            let fidelity = error_free(requirements, error);
+            let minutes = backend.pending_jobs.unwrap_or(0) as f64 * MINUTES_PER_QUEUED_JOB;
+            (backend, fidelity, minutes, fidelity / (1.0 + minutes / QUEUE_HALF_MINUTES))
+        })
+        .collect();
+    let best = scored
+        .iter()
+        .enumerate()
+        .max_by(|(i, a), (j, b)| a.3.total_cmp(&b.3).then(j.cmp(i)))
+        .map(|(i, _)| i)
+        .unwrap_or(0);
+    let (backend, fidelity, minutes, _) = scored[best];
+
+    let mut reasons = vec![format!(
+        "{} qubits; the circuit needs {} at depth {}",
+        backend.qubits.unwrap_or_default(),
+        requirements.qubits,
+        requirements.depth
+    )];
+    reasons.push(match backend.pending_jobs {
+        Some(0) => "no queue".to_string(),
+        Some(jobs) => format!("{} job(s) queued, about {:.0} min", jobs, minutes),
+        None => "queue length not reported".to_string(),
+    });
+    if !is_simulator(&backend.name) && requirements.two_qubit_gates > 0 {
+        reasons.push(match errors.get(&backend.name) {
+            Some(error) => format!(
+                "two-qubit error {:.2}% over {} two-qubit gates: ≈ {:.0}% of shots error-free",
+                error * 100.0,
+                requirements.two_qubit_gates,
+                fidelity * 100.0
+            ),
+            None => format!("no error rates reported; assumed {:.0}% per two-qubit gate", DEFAULT_2Q_ERROR * 100.0),
+        });
+    }
+    if scored.len() > 1 {
+        reasons.push(format!(
+            "best of the {} shortest queues that fit, weighing wait against expected errors",
+            scored.len()
+        ));
+    }
+    if requirements.depth > DEEP_CIRCUIT {
+        reasons.push(format!(
+            "depth {} is deep for current devices; expect decoherence to wash out the results",
+            requirements.depth
+        ));
+    }
+
+    let mut others: Vec<&(&BackendStatus, f64, f64, f64)> =
+        scored.iter().enumerate().filter(|(i, _)| *i != best).map(|(_, s)| s).collect();
+    others.sort_by(|a, b| b.3.total_cmp(&a.3));
+    let alternatives = others
+        .into_iter()
+        .map(|(backend, fidelity, minutes, _)| {
+            format!("{} (~{:.0} min queue, ≈ {:.0}% error-free)", backend.name, minutes, fidelity * 100.0)
+        })
+        .collect();
+
+    Ok(Recommendation { backend: Some(backend.name.clone()), reasons, alternatives })
+}
+
+/// The local simulator when nothing on IBM Quantum will do
+fn local_simulator(
+    requirements: &Requirements,
+    backends: &[BackendStatus],
+    limits: &TierLimits,
+) -> Result<Recommendation, String> {
+    let why = if let Err(restricted) = limits.check_hardware("") {
+        restricted.to_string()
+    } else {
+        let largest = backends.iter().filter(|b| b.operational).filter_map(|b| b.qubits).max();
+        match largest {
+            None => "no IBM Quantum backend is operational".to_string(),
+            Some(qubits) => format!(
+                "the largest operational backend has {} qubits; the circuit needs {}",
+                qubits, requirements.qubits
+            ),
+        }
+    };
+    if requirements.qubits > simulator::MAX_QUBITS {
+        return Err(format!(
+            "Nothing can run this circuit: {}, and the local simulator stops at {} qubits.",
+            why,
+            simulator::MAX_QUBITS
+        ));
+    }
+    Ok(Recommendation {
+        backend: None,
+        reasons: vec![
+            why,
+            format!("{} qubits fit the local simulator (up to {}), with no queue", requirements.qubits, simulator::MAX_QUBITS),
+        ],
+        alternatives: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::tiers;
+    use crate::quantum::qasm;
+
+    fn backend(name: &str, qubits: u32, pending_jobs: u32, operational: bool) -> BackendStatus {
+        BackendStatus {
+            name: name.to_string(),
+            qubits: Some(qubits),
+            pending_jobs: Some(pending_jobs),
+            operational,
+        }
+    }
+
+    fn fleet() -> Vec<BackendStatus> {
+        vec![
+            backend("ibm_brisbane", 127, 40, true),
+            backend("ibm_kyiv", 127, 5, true),
+            backend("ibm_sherbrooke", 127, 6, true),
+            backend("ibm_osaka", 127, 0, false),
+            backend("ibm_small", 5, 0, true),
+        ]
+    }
+
+    fn requirements(qubits: usize, two_qubit_gates: usize) -> Requirements {
+        Requirements { qubits, depth: 10, two_qubit_gates }
+    }
+
+    #[test]
+    fn test_requirements() {
+        let circuit = qasm::parse(
+            "OPENQASM 2.0; include \"qelib1.inc\"; qreg q[3]; creg c[3];\n\
+             h q[0]; cx q[0],q[1]; cx q[1],q[2]; barrier q; ccx q[0],q[1],q[2]; measure q -> c;",
+        )
+        .unwrap();
+        let requirements = Requirements::of(&circuit);
+        assert_eq!(requirements.qubits, 3);
+        // h, cx, cx, ccx, measure on the critical path
+        assert_eq!(requirements.depth, 5);
+        assert_eq!(requirements.two_qubit_gates, 8);
+    }
+
+    #[test]
+    fn test_prefers_short_queue_and_low_error() {
+        let backends = fleet();
+        let limits = tiers::PRO;
+
+        // Without error rates the shortest queue wins; the broken and the too small are left out
+        let pick = select_backend(&requirements(20, 50), &backends, &HashMap::new(), &limits).unwrap();
+        assert_eq!(pick.backend.as_deref(), Some("ibm_kyiv"));
+        assert!(pick.reasons[0].starts_with("127 qubits; the circuit needs 20"));
+        assert!(pick.reasons.iter().any(|r| r.contains("assumed 1%")));
+        assert_eq!(pick.alternatives.len(), 2);
+        assert!(pick.alternatives[0].starts_with("ibm_sherbrooke"));
+
+        // A much better device is worth one more job in the queue
+        let errors = HashMap::from([("ibm_kyiv".to_string(), 0.02), ("ibm_sherbrooke".to_string(), 0.005)]);
+        let pick = select_backend(&requirements(20, 50), &backends, &errors, &limits).unwrap();
+        assert_eq!(pick.backend.as_deref(), Some("ibm_sherbrooke"));
+        assert!(pick.report().contains("two-qubit error 0.50% over 50 two-qubit gates"));
+        assert!(pick.report().contains("\nAlso possible: ibm_kyiv"));
+    }
+
+    #[test]
+    fn test_local_simulator_when_nothing_fits() {
+        let backends = fleet();
+
+        let pick = select_backend(&requirements(4, 2), &backends, &HashMap::new(), &tiers::FREE).unwrap();
+        assert_eq!(pick.backend, None);
+        assert_eq!(pick.reasons[0], "Hardware backends requires Pro — /upgrade");
+
+        let pick = select_backend(&requirements(10, 2), &[backend("ibm_small", 5, 0, true)], &HashMap::new(), &tiers::PRO)
+            .unwrap();
+        assert_eq!(pick.name(), "the local simulator");
+        assert!(pick.reasons[0].contains("largest operational backend has 5 qubits"));
+
+        let error = select_backend(&requirements(200, 2), &backends, &HashMap::new(), &tiers::PRO).unwrap_err();
+        assert!(error.contains("the local simulator stops at 20 qubits"));
+    }
+}
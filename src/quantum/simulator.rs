@@ -0,0 +1,714 @@
+// Local state-vector simulator
+//
+// Runs a parsed `qasm::Circuit` without any account or network access, for
+// `qhub run --offline` and the `simulator` quantum provider. Gates are applied
+// to a full state vector, so memory doubles with every qubit and circuits are
+// capped at `MAX_QUBITS`. Circuits whose measurements all come at the end are
+// simulated once and sampled; mid-circuit measurement, reset and classical
+// conditions are simulated shot by shot.
+//
+// Gates the program defines itself are only run when they share a name with
+// a standard gate (Qiskit re-declares a few of those in its QASM 3 output).
+
+use rand::Rng;
+use std::collections::BTreeMap;
+use std::f64::consts::{FRAC_1_SQRT_2, PI};
+use std::ops::{Add, Mul};
+use thiserror::Error;
+
+use super::qasm::{Circuit, Condition, Operation};
+
+/// 2^20 amplitudes take 16 MB
+pub const MAX_QUBITS: usize = 20;
+
+pub const DEFAULT_SHOTS: usize = 1024;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum SimulatorError {
+    #[error("circuit uses {qubits} qubits; the local simulator handles at most {max}")]
+    TooManyQubits { qubits: usize, max: usize },
+    #[error("gate `{0}` is defined by the program; the local simulator only runs standard gates")]
+    CustomGate(String),
+    #[error("gate `{0}` is not supported by the local simulator")]
+    UnsupportedGate(String),
+    #[error("cannot evaluate gate parameter `{0}`")]
+    Parameter(String),
+    #[error("gate `{name}` expects {expected} parameter(s), got {got}")]
+    ParameterCount { name: String, expected: usize, got: usize },
+    #[error("condition on `{name}` compares {size} bits; the local simulator compares at most 64")]
+    WideCondition { name: String, size: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct C64 {
+    re: f64,
+    im: f64,
+}
+
+impl C64 {
+    const ZERO: C64 = C64 { re: 0.0, im: 0.0 };
+    const ONE: C64 = C64 { re: 1.0, im: 0.0 };
+    const I: C64 = C64 { re: 0.0, im: 1.0 };
+
+    fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    fn real(re: f64) -> Self {
+        Self { re, im: 0.0 }
+    }
+
+    /// e^(iθ)
+    fn phase(theta: f64) -> Self {
+        Self::new(theta.cos(), theta.sin())
+    }
+
+    fn norm_sqr(self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+}
+
+impl Add for C64 {
+    type Output = C64;
+    fn add(self, other: C64) -> C64 {
+        C64::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Mul for C64 {
+    type Output = C64;
+    fn mul(self, other: C64) -> C64 {
+        C64::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+/// A unitary on `targets` (first target is the most significant bit of the
+/// matrix index), applied only where every control qubit is 1
+#[derive(Debug, Clone)]
+struct Unitary {
+    targets: Vec<usize>,
+    controls: Vec<usize>,
+    matrix: Vec<C64>,
+}
+
+#[derive(Debug, Clone)]
+enum Step {
+    Unitary(Unitary),
+    Measure { qubit: usize, clbit: usize },
+    Reset { qubit: usize },
+    /// Runs when bits `offset..offset + width` read as `value`
+    Conditional { offset: usize, width: usize, value: u64, step: Box<Step> },
+}
+
+/// Simulate `shots` runs and count the classical outcomes. Bit strings list
+/// the highest classical bit first; a circuit without measurements is read
+/// out on every qubit instead.
+pub fn run(circuit: &Circuit, shots: usize, rng: &mut impl Rng) -> Result<BTreeMap<String, usize>, SimulatorError> {
+    let qubits = circuit.num_qubits();
+    if qubits > MAX_QUBITS {
+        return Err(SimulatorError::TooManyQubits { qubits, max: MAX_QUBITS });
+    }
+
+    let mut steps = Vec::new();
+    for op in &circuit.operations {
+        steps.extend(compile(circuit, op)?);
+    }
+    if !steps.iter().any(|s| matches!(s, Step::Measure { .. })) {
+        steps.extend((0..qubits).map(|q| Step::Measure { qubit: q, clbit: q }));
+    }
+    let clbits = circuit.num_clbits().max(
+        steps
+            .iter()
+            .filter_map(|s| match s {
+                Step::Measure { clbit, .. } => Some(clbit + 1),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0),
+    );
+
+    let mut counts = BTreeMap::new();
+    if is_dynamic(&steps) {
+        for _ in 0..shots {
+            let bits = run_shot(&steps, qubits, clbits, rng);
+            *counts.entry(bitstring(&bits)).or_insert(0) += 1;
+        }
+    } else {
+        // Measurements only at the end: one pass, then sample the final state
+        let mut state = initial_state(qubits);
+        let mut readout = Vec::new();
+        for step in &steps {
+            match step {
+                Step::Unitary(u) => apply(&mut state, u),
+                Step::Measure { qubit, clbit } => readout.push((*qubit, *clbit)),
+                _ => unreachable!("dynamic circuits are simulated per shot"),
+            }
+        }
+        let cumulative: Vec<f64> = state
+            .iter()
+            .scan(0.0, |sum, amp| {
+                *sum += amp.norm_sqr();
+                Some(*sum)
+            })
+            .collect();
+        for _ in 0..shots {
+            let r = rng.gen::<f64>() * cumulative[cumulative.len() - 1];
+            let index = cumulative.partition_point(|&c| c <= r).min(state.len() - 1);
+            let mut bits = vec![false; clbits];
+            for &(qubit, clbit) in &readout {
+                bits[clbit] = index >> qubit & 1 == 1;
+            }
+            *counts.entry(bitstring(&bits)).or_insert(0) += 1;
+        }
+    }
+    Ok(counts)
+}
+
+/// The state a circuit of gates leaves |0…0⟩ in, as (re, im) per basis
+/// state with qubit 0 the lowest bit of the index. Only gates are applied;
+/// measurements, resets and conditions are left out.
+pub fn statevector(circuit: &Circuit) -> Result<Vec<(f64, f64)>, SimulatorError> {
+    let qubits = circuit.num_qubits();
+    if qubits > MAX_QUBITS {
+        return Err(SimulatorError::TooManyQubits { qubits, max: MAX_QUBITS });
+    }
+    let mut state = initial_state(qubits);
+    for op in &circuit.operations {
+        if let Operation::Gate { .. } = op {
+            for step in compile(circuit, op)? {
+                if let Step::Unitary(u) = step {
+                    apply(&mut state, &u);
+                }
+            }
+        }
+    }
+    Ok(state.into_iter().map(|amp| (amp.re, amp.im)).collect())
+}
+
+/// Needs per-shot simulation: a reset, a condition, or a gate after a measurement
+fn is_dynamic(steps: &[Step]) -> bool {
+    let mut measured = false;
+    for step in steps {
+        match step {
+            Step::Measure { .. } => measured = true,
+            Step::Unitary(_) if measured => return true,
+            Step::Reset { .. } | Step::Conditional { .. } => return true,
+            Step::Unitary(_) => {}
+        }
+    }
+    false
+}
+
+fn run_shot(steps: &[Step], qubits: usize, clbits: usize, rng: &mut impl Rng) -> Vec<bool> {
+    let mut state = initial_state(qubits);
+    let mut bits = vec![false; clbits];
+    for step in steps {
+        execute(step, &mut state, &mut bits, rng);
+    }
+    bits
+}
+
+fn execute(step: &Step, state: &mut [C64], bits: &mut [bool], rng: &mut impl Rng) {
+    match step {
+        Step::Unitary(u) => apply(state, u),
+        Step::Measure { qubit, clbit } => bits[*clbit] = measure(state, *qubit, rng),
+        Step::Reset { qubit } => {
+            if measure(state, *qubit, rng) {
+                apply(state, &single(*qubit, x()));
+            }
+        }
+        Step::Conditional { offset, width, value, step } => {
+            let actual = (0..*width).fold(0u64, |acc, i| acc | (bits[offset + i] as u64) << i);
+            if actual == *value {
+                execute(step, state, bits, rng);
+            }
+        }
+    }
+}
+
+fn initial_state(qubits: usize) -> Vec<C64> {
+    let mut state = vec![C64::ZERO; 1 << qubits];
+    state[0] = C64::ONE;
+    state
+}
+
+fn bitstring(bits: &[bool]) -> String {
+    bits.iter().rev().map(|&b| if b { '1' } else { '0' }).collect()
+}
+
+/// Collapse `qubit` and return the outcome
+fn measure(state: &mut [C64], qubit: usize, rng: &mut impl Rng) -> bool {
+    let mask = 1 << qubit;
+    let p1: f64 = state
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| i & mask != 0)
+        .map(|(_, a)| a.norm_sqr())
+        .sum();
+    let outcome = rng.gen::<f64>() < p1;
+    let keep = if outcome { p1 } else { 1.0 - p1 };
+    let scale = C64::real(1.0 / keep.sqrt().max(f64::MIN_POSITIVE));
+    for (i, amp) in state.iter_mut().enumerate() {
+        *amp = if (i & mask != 0) == outcome { *amp * scale } else { C64::ZERO };
+    }
+    outcome
+}
+
+fn apply(state: &mut [C64], u: &Unitary) {
+    let k = u.targets.len();
+    let dim = 1 << k;
+    let target_mask: usize = u.targets.iter().map(|q| 1 << q).sum();
+    let control_mask: usize = u.controls.iter().map(|q| 1 << q).sum();
+    // Offsets of each local basis state from the all-zero index
+    let offsets: Vec<usize> = (0..dim)
+        .map(|local| {
+            u.targets
+                .iter()
+                .enumerate()
+                .filter(|(pos, _)| local >> (k - 1 - pos) & 1 == 1)
+                .map(|(_, q)| 1 << q)
+                .sum()
+        })
+        .collect();
+
+    let mut amps = vec![C64::ZERO; dim];
+    for base in 0..state.len() {
+        if base & target_mask != 0 || base & control_mask != control_mask {
+            continue;
+        }
+        for (local, offset) in offsets.iter().enumerate() {
+            amps[local] = state[base + offset];
+        }
+        for (row, offset) in offsets.iter().enumerate() {
+            state[base + offset] = (0..dim).fold(C64::ZERO, |acc, col| acc + u.matrix[row * dim + col] * amps[col]);
+        }
+    }
+}
+
+// -------------------------------------------------------------------------
+// Gate matrices
+// -------------------------------------------------------------------------
+
+fn single(qubit: usize, matrix: Vec<C64>) -> Unitary {
+    Unitary { targets: vec![qubit], controls: Vec::new(), matrix }
+}
+
+fn u3(theta: f64, phi: f64, lambda: f64) -> Vec<C64> {
+    let (c, s) = ((theta / 2.0).cos(), (theta / 2.0).sin());
+    vec![
+        C64::real(c),
+        C64::phase(lambda) * C64::real(-s),
+        C64::phase(phi) * C64::real(s),
+        C64::phase(phi + lambda) * C64::real(c),
+    ]
+}
+
+fn diag(a: C64, b: C64) -> Vec<C64> {
+    vec![a, C64::ZERO, C64::ZERO, b]
+}
+
+fn x() -> Vec<C64> {
+    vec![C64::ZERO, C64::ONE, C64::ONE, C64::ZERO]
+}
+
+fn sx(sign: f64) -> Vec<C64> {
+    let (p, m) = (C64::new(0.5, 0.5 * sign), C64::new(0.5, -0.5 * sign));
+    vec![p, m, m, p]
+}
+
+/// exp(-iθ/2 P⊗P) for a two-qubit Pauli product given as its 4x4 matrix
+fn pauli_rotation(theta: f64, pauli: [[C64; 4]; 4]) -> Vec<C64> {
+    let (c, s) = ((theta / 2.0).cos(), (theta / 2.0).sin());
+    let mut matrix = Vec::with_capacity(16);
+    for (row, entries) in pauli.iter().enumerate() {
+        for (col, p) in entries.iter().enumerate() {
+            let identity = if row == col { C64::real(c) } else { C64::ZERO };
+            matrix.push(identity + C64::new(0.0, -s) * *p);
+        }
+    }
+    matrix
+}
+
+/// Matrix for a 1-qubit gate name, or `None` when it isn't one
+fn one_qubit(name: &str, p: &[f64]) -> Option<Vec<C64>> {
+    let m = match name {
+        "U" | "u" | "u3" => u3(p[0], p[1], p[2]),
+        "u2" => u3(PI / 2.0, p[0], p[1]),
+        "u1" | "p" | "phase" => diag(C64::ONE, C64::phase(p[0])),
+        "u0" | "id" => diag(C64::ONE, C64::ONE),
+        "x" => x(),
+        "y" => vec![C64::ZERO, C64::new(0.0, -1.0), C64::I, C64::ZERO],
+        "z" => diag(C64::ONE, C64::real(-1.0)),
+        "h" => vec![C64::real(FRAC_1_SQRT_2), C64::real(FRAC_1_SQRT_2), C64::real(FRAC_1_SQRT_2), C64::real(-FRAC_1_SQRT_2)],
+        "s" => diag(C64::ONE, C64::I),
+        "sdg" => diag(C64::ONE, C64::new(0.0, -1.0)),
+        "t" => diag(C64::ONE, C64::phase(PI / 4.0)),
+        "tdg" => diag(C64::ONE, C64::phase(-PI / 4.0)),
+        "sx" => sx(1.0),
+        "sxdg" => sx(-1.0),
+        "rx" => u3(p[0], -PI / 2.0, PI / 2.0),
+        "ry" => u3(p[0], 0.0, 0.0),
+        "rz" => diag(C64::phase(-p[0] / 2.0), C64::phase(p[0] / 2.0)),
+        _ => return None,
+    };
+    Some(m)
+}
+
+/// Parameters each standard gate takes
+fn parameter_count(name: &str) -> usize {
+    match name {
+        "U" | "u" | "u3" | "cu3" => 3,
+        "cu" => 4,
+        "u2" => 2,
+        "u1" | "p" | "phase" | "rx" | "ry" | "rz" | "cp" | "cphase" | "cu1" | "crx" | "cry" | "crz"
+        | "rxx" | "ryy" | "rzz" | "rzx" => 1,
+        _ => 0,
+    }
+}
+
+fn compile(circuit: &Circuit, op: &Operation) -> Result<Vec<Step>, SimulatorError> {
+    match op {
+        Operation::Gate { name, params, qubits } => gate(circuit, name, params, qubits),
+        Operation::Measure { qubit, clbit } => Ok(vec![Step::Measure { qubit: *qubit, clbit: *clbit }]),
+        Operation::Reset { qubit } => Ok(vec![Step::Reset { qubit: *qubit }]),
+        Operation::Barrier { .. } => Ok(Vec::new()),
+        Operation::Conditional { condition, op } => {
+            let (offset, width, value) = match condition {
+                Condition::Bit { clbit, value } => (*clbit, 1, *value as u64),
+                Condition::Register { name, value } => {
+                    let register = circuit
+                        .cregs
+                        .iter()
+                        .find(|r| &r.name == name)
+                        .expect("parser resolves condition registers");
+                    // The bits are read into a u64
+                    if register.size > 64 {
+                        return Err(SimulatorError::WideCondition { name: name.clone(), size: register.size });
+                    }
+                    (register.offset, register.size, *value)
+                }
+            };
+            Ok(compile(circuit, op)?
+                .into_iter()
+                .map(|step| Step::Conditional { offset, width, value, step: Box::new(step) })
+                .collect())
+        }
+    }
+}
+
+fn gate(circuit: &Circuit, name: &str, params: &[String], qubits: &[usize]) -> Result<Vec<Step>, SimulatorError> {
+    if one_qubit(name, &[0.0; 3]).is_none() && !is_multi_qubit(name) {
+        return Err(if circuit.custom_gates.iter().any(|g| g == name) {
+            SimulatorError::CustomGate(name.to_string())
+        } else {
+            SimulatorError::UnsupportedGate(name.to_string())
+        });
+    }
+    let expected = parameter_count(name);
+    if params.len() != expected {
+        return Err(SimulatorError::ParameterCount { name: name.to_string(), expected, got: params.len() });
+    }
+    let p = params.iter().map(|e| evaluate(e)).collect::<Result<Vec<f64>, _>>()?;
+
+    if let Some(matrix) = one_qubit(name, &p) {
+        return Ok(vec![Step::Unitary(single(qubits[0], matrix))]);
+    }
+
+    let controlled = |controls: usize, matrix: Vec<C64>| Step::Unitary(Unitary {
+        targets: qubits[controls..].to_vec(),
+        controls: qubits[..controls].to_vec(),
+        matrix,
+    });
+    let swap = vec![
+        C64::ONE, C64::ZERO, C64::ZERO, C64::ZERO,
+        C64::ZERO, C64::ZERO, C64::ONE, C64::ZERO,
+        C64::ZERO, C64::ONE, C64::ZERO, C64::ZERO,
+        C64::ZERO, C64::ZERO, C64::ZERO, C64::ONE,
+    ];
+    let one = |name: &str, p: &[f64]| one_qubit(name, p).expect("standard gate");
+
+    let step = match name {
+        "CX" | "cx" => controlled(1, x()),
+        "cy" => controlled(1, one("y", &[])),
+        "cz" => controlled(1, one("z", &[])),
+        "ch" => controlled(1, one("h", &[])),
+        "csx" => controlled(1, sx(1.0)),
+        "cp" | "cphase" | "cu1" => controlled(1, one("p", &p)),
+        "crx" => controlled(1, one("rx", &p)),
+        "cry" => controlled(1, one("ry", &p)),
+        "crz" => controlled(1, one("rz", &p)),
+        "cu3" => controlled(1, u3(p[0], p[1], p[2])),
+        "cu" => controlled(1, u3(p[0], p[1], p[2]).into_iter().map(|a| a * C64::phase(p[3])).collect()),
+        "ccx" => controlled(2, x()),
+        "c3x" => controlled(3, x()),
+        "c4x" => controlled(4, x()),
+        "swap" => controlled(0, swap),
+        "cswap" => controlled(1, swap),
+        "iswap" => {
+            let mut m = swap;
+            m[6] = C64::I;
+            m[9] = C64::I;
+            controlled(0, m)
+        }
+        "rxx" | "ryy" | "rzz" | "rzx" => controlled(0, pauli_rotation(p[0], two_qubit_pauli(name))),
+        "ecr" => {
+            // ecr a, b = rzx(pi/4) a, b; x a; rzx(-pi/4) a, b
+            let rzx = |theta: f64| controlled(0, pauli_rotation(theta, two_qubit_pauli("rzx")));
+            return Ok(vec![rzx(PI / 4.0), Step::Unitary(single(qubits[0], x())), rzx(-PI / 4.0)]);
+        }
+        _ => unreachable!("checked by is_multi_qubit"),
+    };
+    Ok(vec![step])
+}
+
+fn is_multi_qubit(name: &str) -> bool {
+    matches!(
+        name,
+        "CX" | "cx" | "cy" | "cz" | "ch" | "csx" | "cp" | "cphase" | "cu1" | "crx" | "cry" | "crz" | "cu3"
+            | "cu" | "ccx" | "c3x" | "c4x" | "swap" | "cswap" | "iswap" | "rxx" | "ryy" | "rzz" | "rzx" | "ecr"
+    )
+}
+
+/// P⊗P in the |ab⟩ basis, `a` being the first operand
+fn two_qubit_pauli(name: &str) -> [[C64; 4]; 4] {
+    let (o, l) = (C64::ZERO, C64::ONE);
+    let m = C64::real(-1.0);
+    match name {
+        "rxx" => [[o, o, o, l], [o, o, l, o], [o, l, o, o], [l, o, o, o]],
+        "ryy" => [[o, o, o, m], [o, o, l, o], [o, l, o, o], [m, o, o, o]],
+        "rzz" => [[l, o, o, o], [o, m, o, o], [o, o, m, o], [o, o, o, l]],
+        // Z on a, X on b
+        "rzx" => [[o, l, o, o], [l, o, o, o], [o, o, o, m], [o, o, m, o]],
+        _ => unreachable!("not a two-qubit Pauli rotation: {}", name),
+    }
+}
+
+// -------------------------------------------------------------------------
+// Parameter expressions: numbers, pi/π/tau/e, + - * / ^ and a few functions
+// -------------------------------------------------------------------------
+
+/// Constants and functions parameter expressions may use
+pub const BUILTIN_NAMES: &[&str] = &["pi", "π", "tau", "τ", "euler", "ℇ", "sin", "cos", "tan", "exp", "ln", "sqrt"];
+
+pub fn evaluate(expr: &str) -> Result<f64, SimulatorError> {
+    let tokens = tokenize(expr).ok_or_else(|| SimulatorError::Parameter(expr.to_string()))?;
+    let mut parser = ExprParser { tokens, pos: 0 };
+    match parser.sum() {
+        Some(value) if parser.pos == parser.tokens.len() && value.is_finite() => Ok(value),
+        _ => Err(SimulatorError::Parameter(expr.to_string())),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Op(char),
+}
+
+fn tokenize(expr: &str) -> Option<Vec<Token>> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_ascii_digit()
+                    || chars[i] == '.'
+                    || chars[i] == 'e'
+                    || chars[i] == 'E'
+                    || ((chars[i] == '-' || chars[i] == '+') && matches!(chars[i - 1], 'e' | 'E')))
+            {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            tokens.push(Token::Number(text.parse().ok()?));
+        } else if c.is_alphabetic() || c == '_' || c == 'π' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == 'π') {
+                i += 1;
+            }
+            tokens.push(Token::Name(chars[start..i].iter().collect()));
+        } else if c == '*' && chars.get(i + 1) == Some(&'*') {
+            tokens.push(Token::Op('^'));
+            i += 2;
+        } else if "+-*/^()".contains(c) {
+            tokens.push(Token::Op(c));
+            i += 1;
+        } else {
+            return None;
+        }
+    }
+    Some(tokens)
+}
+
+struct ExprParser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl ExprParser {
+    fn peek_op(&self) -> Option<char> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(c)) => Some(*c),
+            _ => None,
+        }
+    }
+
+    fn sum(&mut self) -> Option<f64> {
+        let mut value = self.product()?;
+        while let Some(op @ ('+' | '-')) = self.peek_op() {
+            self.pos += 1;
+            let rhs = self.product()?;
+            value = if op == '+' { value + rhs } else { value - rhs };
+        }
+        Some(value)
+    }
+
+    fn product(&mut self) -> Option<f64> {
+        let mut value = self.unary()?;
+        while let Some(op @ ('*' | '/')) = self.peek_op() {
+            self.pos += 1;
+            let rhs = self.unary()?;
+            value = if op == '*' { value * rhs } else { value / rhs };
+        }
+        Some(value)
+    }
+
+    fn unary(&mut self) -> Option<f64> {
+        match self.peek_op() {
+            Some('-') => {
+                self.pos += 1;
+                Some(-self.unary()?)
+            }
+            Some('+') => {
+                self.pos += 1;
+                self.unary()
+            }
+            _ => self.power(),
+        }
+    }
+
+    fn power(&mut self) -> Option<f64> {
+        let base = self.atom()?;
+        if self.peek_op() == Some('^') {
+            self.pos += 1;
+            return Some(base.powf(self.unary()?));
+        }
+        Some(base)
+    }
+
+    fn atom(&mut self) -> Option<f64> {
+        let token = self.tokens.get(self.pos)?.clone();
+        self.pos += 1;
+        match token {
+            Token::Number(n) => Some(n),
+            Token::Op('(') => {
+                let value = self.sum()?;
+                (self.peek_op() == Some(')')).then(|| self.pos += 1)?;
+                Some(value)
+            }
+            Token::Name(name) => match name.as_str() {
+                "pi" | "π" => Some(PI),
+                "tau" | "τ" => Some(2.0 * PI),
+                "euler" | "ℇ" => Some(std::f64::consts::E),
+                function => {
+                    let f: fn(f64) -> f64 = match function {
+                        "sin" => f64::sin,
+                        "cos" => f64::cos,
+                        "tan" => f64::tan,
+                        "exp" => f64::exp,
+                        "ln" => f64::ln,
+                        "sqrt" => f64::sqrt,
+                        _ => return None,
+                    };
+                    (self.peek_op() == Some('(')).then_some(())?;
+                    Some(f(self.atom()?))
+                }
+            },
+            Token::Op(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quantum::qasm;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn counts(source: &str) -> BTreeMap<String, usize> {
+        let circuit = qasm::parse(source).unwrap();
+        run(&circuit, 1000, &mut StdRng::seed_from_u64(7)).unwrap()
+    }
+
+    #[test]
+    fn test_bell_state() {
+        let counts = counts("OPENQASM 2.0; include \"qelib1.inc\"; qreg q[2]; creg c[2]; h q[0]; cx q[0],q[1]; measure q -> c;");
+        assert_eq!(counts.keys().collect::<Vec<_>>(), vec!["00", "11"]);
+        assert!(counts["00"] > 400 && counts["11"] > 400);
+    }
+
+    #[test]
+    fn test_deterministic_gates() {
+        // x then controlled ops: bit order is c[2] c[1] c[0]
+        assert_eq!(counts("OPENQASM 2.0; qreg q[3]; creg c[3]; x q[0]; cx q[0],q[2]; measure q -> c;")["101"], 1000);
+        assert_eq!(counts("OPENQASM 2.0; qreg q[3]; creg c[3]; x q[0]; x q[1]; ccx q[0],q[1],q[2]; measure q -> c;")["111"], 1000);
+        assert_eq!(counts("OPENQASM 2.0; qreg q[2]; creg c[2]; x q[0]; swap q[0],q[1]; measure q -> c;")["10"], 1000);
+        // Two half turns make a bit flip
+        assert_eq!(counts("OPENQASM 3.0; qubit[1] q; bit[1] c; rx(pi/2) q[0]; rx(π/2) q[0]; c[0] = measure q[0];")["1"], 1000);
+        assert_eq!(counts("OPENQASM 2.0; qreg q[2]; creg c[2]; ecr q[0],q[1]; ecr q[0],q[1]; measure q -> c;")["00"], 1000);
+        assert_eq!(counts("OPENQASM 2.0; qreg q[2]; creg c[2]; rzz(pi) q[0],q[1]; h q[0]; h q[0]; measure q -> c;")["00"], 1000);
+    }
+
+    #[test]
+    fn test_mid_circuit_measurement_and_conditions() {
+        let counts = counts("OPENQASM 2.0; qreg q[1]; creg c[1]; h q[0]; measure q[0] -> c[0]; if(c==1) x q[0]; reset q[0]; x q[0]; measure q[0] -> c[0];");
+        assert_eq!(counts["1"], 1000);
+        // Qiskit's QASM 3 output re-declares rzx; it runs as the standard gate
+        let counts = self::counts("OPENQASM 3.0;\ngate rzx(p) a, b { h b; }\nqubit[2] q;\nbit[2] c;\nrzx(0) q[0], q[1];\nc[0] = measure q[0];");
+        assert_eq!(counts["00"], 1000);
+    }
+
+    #[test]
+    fn test_unmeasured_circuit_reads_all_qubits() {
+        assert_eq!(counts("OPENQASM 2.0; qreg q[2]; x q[1];")["10"], 1000);
+    }
+
+    #[test]
+    fn test_limits_and_errors() {
+        let circuit = qasm::parse("OPENQASM 3.0; qubit[21] q; h q[0];").unwrap();
+        let err = run(&circuit, 1, &mut StdRng::seed_from_u64(0)).unwrap_err();
+        assert_eq!(err, SimulatorError::TooManyQubits { qubits: 21, max: MAX_QUBITS });
+        assert!(err.to_string().contains("at most 20"));
+
+        let circuit = qasm::parse("OPENQASM 2.0; gate bell a, b { h a; cx a, b; } qreg q[2]; bell q[0], q[1];").unwrap();
+        let err = run(&circuit, 1, &mut StdRng::seed_from_u64(0)).unwrap_err();
+        assert_eq!(err, SimulatorError::CustomGate("bell".into()));
+
+        let circuit = qasm::parse("OPENQASM 2.0; qreg q[1]; creg c[70]; measure q[0] -> c[0]; if(c==1) x q[0];").unwrap();
+        let err = run(&circuit, 1, &mut StdRng::seed_from_u64(0)).unwrap_err();
+        assert_eq!(err, SimulatorError::WideCondition { name: "c".into(), size: 70 });
+        let circuit = qasm::parse("OPENQASM 2.0; qreg q[1]; creg c[64]; if(c==0) x q[0]; measure q[0] -> c[0];").unwrap();
+        assert_eq!(run(&circuit, 10, &mut StdRng::seed_from_u64(0)).unwrap().values().sum::<usize>(), 10);
+    }
+
+    #[test]
+    fn test_evaluate() {
+        assert_eq!(evaluate("pi/2").unwrap(), PI / 2.0);
+        assert_eq!(evaluate("-3*pi/4").unwrap(), -3.0 * PI / 4.0);
+        assert_eq!(evaluate("2**3 - (1 + 1)").unwrap(), 6.0);
+        assert_eq!(evaluate("1.5e-1").unwrap(), 0.15);
+        assert_eq!(evaluate("cos(0)").unwrap(), 1.0);
+        assert!(evaluate("theta").is_err());
+        assert!(evaluate("1 +").is_err());
+    }
+}
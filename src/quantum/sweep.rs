@@ -0,0 +1,341 @@
+// Parameter sweeps
+//
+// A circuit can leave gate angles as bare names (`rz(theta) q[0];`); a sweep
+// runs it once per point of a grid of values, substituting each name before
+// simulation. Every point becomes a child job sharing the sweep's id, so
+// `/sweep status` can report progress while it runs and the results stay
+// inspectable afterwards. The metric recorded per point is the expectation of
+// Z on every measured bit, i.e. the parity of the outcome, averaged.
+
+use regex::{Captures, Regex};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::str::FromStr;
+
+use super::qasm::{Circuit, Operation};
+use super::simulator;
+
+/// Points in one sweep; each is a full simulation and a database row
+pub const MAX_POINTS: usize = 1024;
+
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// `theta=0:3.14:16`: 16 evenly spaced values from 0 to 3.14 inclusive
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamRange {
+    pub name: String,
+    pub start: f64,
+    pub stop: f64,
+    pub points: usize,
+}
+
+impl ParamRange {
+    pub fn values(&self) -> Vec<f64> {
+        if self.points == 1 {
+            return vec![self.start];
+        }
+        let step = (self.stop - self.start) / (self.points - 1) as f64;
+        (0..self.points).map(|i| self.start + step * i as f64).collect()
+    }
+}
+
+impl FromStr for ParamRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let usage = || format!("expected NAME=START:STOP:POINTS, e.g. theta=0:3.14:16, got '{}'", s);
+        let (name, range) = s.split_once('=').ok_or_else(usage)?;
+        let name = name.trim();
+        let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            return Err(format!("'{}' is not a valid parameter name", name));
+        }
+        let parts: Vec<&str> = range.split(':').map(str::trim).collect();
+        let [start, stop, points] = parts[..] else {
+            return Err(usage());
+        };
+        let start = simulator::evaluate(start).map_err(|e| e.to_string())?;
+        let stop = simulator::evaluate(stop).map_err(|e| e.to_string())?;
+        let points: usize = points.parse().map_err(|_| usage())?;
+        if points == 0 {
+            return Err(format!("{} needs at least one point", name));
+        }
+        Ok(ParamRange { name: name.to_string(), start, stop, points })
+    }
+}
+
+/// Values for each parameter at one sweep point
+pub type Point = BTreeMap<String, f64>;
+
+/// Every combination of the ranges, the last range varying fastest
+pub fn grid(ranges: &[ParamRange]) -> Vec<Point> {
+    let mut points = vec![Point::new()];
+    for range in ranges {
+        let values = range.values();
+        points = points
+            .into_iter()
+            .flat_map(|point| {
+                values.iter().map(move |&value| {
+                    let mut point = point.clone();
+                    point.insert(range.name.clone(), value);
+                    point
+                })
+            })
+            .collect();
+    }
+    points
+}
+
+fn identifier() -> Regex {
+    Regex::new(r"\b[A-Za-z_][A-Za-z0-9_]*").expect("valid regex")
+}
+
+fn gate_params(op: &Operation) -> Vec<&String> {
+    match op {
+        Operation::Gate { params, .. } => params.iter().collect(),
+        Operation::Conditional { op, .. } => gate_params(op),
+        _ => Vec::new(),
+    }
+}
+
+/// Names used in gate parameters that the simulator doesn't know
+pub fn free_parameters(circuit: &Circuit) -> BTreeSet<String> {
+    let identifier = identifier();
+    circuit
+        .operations
+        .iter()
+        .flat_map(gate_params)
+        .flat_map(|param| identifier.find_iter(param).map(|m| m.as_str().to_string()).collect::<Vec<_>>())
+        .filter(|name| !simulator::BUILTIN_NAMES.contains(&name.as_str()))
+        .collect()
+}
+
+/// The circuit with each parameter name replaced by its value at `point`
+pub fn bind(circuit: &Circuit, point: &Point) -> Circuit {
+    let identifier = identifier();
+    let substitute = |param: &String| {
+        identifier
+            .replace_all(param, |c: &Captures| match point.get(&c[0]) {
+                Some(value) => format!("({})", value),
+                None => c[0].to_string(),
+            })
+            .into_owned()
+    };
+    fn bind_op(op: &Operation, substitute: &dyn Fn(&String) -> String) -> Operation {
+        match op {
+            Operation::Gate { name, params, qubits } => Operation::Gate {
+                name: name.clone(),
+                params: params.iter().map(substitute).collect(),
+                qubits: qubits.clone(),
+            },
+            Operation::Conditional { condition, op } => Operation::Conditional {
+                condition: condition.clone(),
+                op: Box::new(bind_op(op, substitute)),
+            },
+            other => other.clone(),
+        }
+    }
+
+    let mut bound = circuit.clone();
+    bound.operations = circuit.operations.iter().map(|op| bind_op(op, &substitute)).collect();
+    bound
+}
+
+/// Check the ranges cover exactly the circuit's parameters and build the grid
+pub fn plan(circuit: &Circuit, ranges: &[ParamRange]) -> Result<Vec<Point>, String> {
+    let free = free_parameters(circuit);
+    let given: BTreeSet<String> = ranges.iter().map(|r| r.name.clone()).collect();
+    if given.len() != ranges.len() {
+        return Err("each parameter can only be given one --param range".to_string());
+    }
+    if let Some(missing) = free.difference(&given).next() {
+        return Err(format!("circuit parameter `{}` has no --param range", missing));
+    }
+    if let Some(unused) = given.difference(&free).next() {
+        return Err(format!("--param {} does not appear in the circuit", unused));
+    }
+    let total: usize = ranges.iter().map(|r| r.points).product();
+    if total > MAX_POINTS {
+        return Err(format!("the sweep has {} points; at most {} are allowed", total, MAX_POINTS));
+    }
+    Ok(grid(ranges))
+}
+
+/// `theta=0.4189, phi=1`
+pub fn label(point: &Point) -> String {
+    point
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, round(*value)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Enough digits for a label or CSV cell without float noise
+fn round(value: f64) -> f64 {
+    (value * 1e6).round() / 1e6
+}
+
+/// ⟨Z⊗…⊗Z⟩ over the measured bits: +1 for even parity, -1 for odd
+pub fn expectation(counts: &BTreeMap<String, usize>) -> f64 {
+    let shots: usize = counts.values().sum();
+    if shots == 0 {
+        return 0.0;
+    }
+    let signed: i64 = counts
+        .iter()
+        .map(|(bits, &n)| if bits.matches('1').count() % 2 == 0 { n as i64 } else { -(n as i64) })
+        .sum();
+    signed as f64 / shots as f64
+}
+
+/// What a finished point stores in `quantum_jobs.result`
+pub fn point_result(point: &Point, counts: &BTreeMap<String, usize>) -> Value {
+    json!({
+        "parameters": point,
+        "counts": counts,
+        "expectation": expectation(counts),
+    })
+}
+
+/// Finished points as CSV: one column per parameter, the expectation and,
+/// with `select`, how often that outcome was measured
+pub fn csv(results: &[(Point, BTreeMap<String, usize>)], select: Option<&str>) -> String {
+    let mut text = String::new();
+    if let Some((first, _)) = results.first() {
+        let mut header: Vec<String> = first.keys().cloned().collect();
+        header.push("expectation".to_string());
+        if let Some(bits) = select {
+            header.push(format!("count_{}", bits));
+        }
+        text.push_str(&header.join(","));
+        text.push('\n');
+    }
+    for (point, counts) in results {
+        let mut row: Vec<String> = point.values().map(|v| round(*v).to_string()).collect();
+        row.push(round(expectation(counts)).to_string());
+        if let Some(bits) = select {
+            row.push(counts.get(bits).copied().unwrap_or(0).to_string());
+        }
+        let _ = writeln!(text, "{}", row.join(","));
+    }
+    text
+}
+
+/// One character per value, scaled between the smallest and largest
+pub fn sparkline(values: &[f64]) -> String {
+    let (min, max) = values
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+    values
+        .iter()
+        .map(|&v| {
+            let level = if max > min { (v - min) / (max - min) } else { 0.5 };
+            SPARKS[((level * (SPARKS.len() - 1) as f64).round() as usize).min(SPARKS.len() - 1)]
+        })
+        .collect()
+}
+
+/// Progress of a sweep from its child jobs, in point order
+pub fn status_report(sweep_id: &str, jobs: &[(String, Option<Value>)]) -> String {
+    let mut by_status: BTreeMap<&str, usize> = BTreeMap::new();
+    for (status, _) in jobs {
+        *by_status.entry(status.as_str()).or_default() += 1;
+    }
+    let done = by_status.get("completed").copied().unwrap_or(0);
+    let mut text = format!("Sweep {}: {}/{} points completed", sweep_id, done, jobs.len());
+    for (status, n) in by_status.iter().filter(|(s, _)| **s != "completed") {
+        let _ = write!(text, ", {} {}", n, status);
+    }
+
+    let metric: Vec<f64> = jobs
+        .iter()
+        .filter_map(|(_, result)| result.as_ref()?.get("expectation")?.as_f64())
+        .collect();
+    if !metric.is_empty() {
+        let (min, max) = metric
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+        let _ = write!(
+            text,
+            "\n⟨Z⟩ {}  (min {:.3}, max {:.3})",
+            sparkline(&metric),
+            min,
+            max
+        );
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quantum::qasm;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const ROTATION: &str = "OPENQASM 2.0; qreg q[1]; creg c[1]; rx(theta) q[0]; rz(2*phi + pi) q[0]; measure q -> c;";
+
+    #[test]
+    fn test_param_range() {
+        let range: ParamRange = "theta=0:pi:3".parse().unwrap();
+        assert_eq!(range.name, "theta");
+        assert_eq!(range.values(), vec![0.0, std::f64::consts::FRAC_PI_2, std::f64::consts::PI]);
+        assert_eq!("x=1:2:1".parse::<ParamRange>().unwrap().values(), vec![1.0]);
+
+        assert!("theta=0:1".parse::<ParamRange>().is_err());
+        assert!("theta=0:1:0".parse::<ParamRange>().is_err());
+        assert!("2theta=0:1:4".parse::<ParamRange>().is_err());
+    }
+
+    #[test]
+    fn test_plan_and_bind() {
+        let circuit = qasm::parse(ROTATION).unwrap();
+        assert_eq!(free_parameters(&circuit), BTreeSet::from(["phi".to_string(), "theta".to_string()]));
+
+        let theta: ParamRange = "theta=0:1:4".parse().unwrap();
+        let phi: ParamRange = "phi=0:1:2".parse().unwrap();
+        assert!(plan(&circuit, std::slice::from_ref(&theta)).unwrap_err().contains("`phi`"));
+        let points = plan(&circuit, &[theta, phi]).unwrap();
+        assert_eq!(points.len(), 8);
+        assert_eq!(label(&points[1]), "phi=1, theta=0");
+
+        let bound = bind(&circuit, &points[1]);
+        assert!(free_parameters(&bound).is_empty());
+        assert!(matches!(
+            &bound.operations[1],
+            Operation::Gate { params, .. } if params[0] == "2*(1) + pi"
+        ));
+    }
+
+    #[test]
+    fn test_bound_circuit_runs() {
+        let circuit = qasm::parse(ROTATION).unwrap();
+        let point = Point::from([("theta".to_string(), std::f64::consts::PI), ("phi".to_string(), 0.0)]);
+        let counts = simulator::run(&bind(&circuit, &point), 100, &mut StdRng::seed_from_u64(1)).unwrap();
+        assert_eq!(counts.get("1"), Some(&100));
+        assert_eq!(expectation(&counts), -1.0);
+    }
+
+    #[test]
+    fn test_csv_and_report() {
+        let point = |theta: f64| Point::from([("theta".to_string(), theta)]);
+        let results = vec![
+            (point(0.0), BTreeMap::from([("00".to_string(), 10)])),
+            (point(0.5), BTreeMap::from([("00".to_string(), 5), ("01".to_string(), 5)])),
+        ];
+        assert_eq!(csv(&results, Some("00")), "theta,expectation,count_00\n0,1,10\n0.5,0,5\n");
+
+        let jobs = vec![
+            ("completed".to_string(), Some(point_result(&results[0].0, &results[0].1))),
+            ("completed".to_string(), Some(point_result(&results[1].0, &results[1].1))),
+            ("cancelled".to_string(), None),
+        ];
+        assert_eq!(
+            status_report("s1", &jobs),
+            "Sweep s1: 2/3 points completed, 1 cancelled\n⟨Z⟩ █▁  (min 0.000, max 1.000)"
+        );
+        assert_eq!(sparkline(&[1.0, 1.0]), "▅▅");
+    }
+}
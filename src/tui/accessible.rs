@@ -0,0 +1,208 @@
+// Plain transcript for screen readers (ui.accessible, --accessible)
+//
+// Screen readers read the terminal a row at a time, so box borders, block
+// characters and glyph prefixes come out as noise and a spinner that redraws
+// every frame is read again and again. In accessible mode each message is
+// laid out here instead of by `ui::layout_message`: a line saying who spoke,
+// then the text with markdown markers dropped and code blocks announced
+// where they start and end. The rows go through the same render cache and
+// scrolling as the styled transcript, so only the drawing differs.
+// Progress is a "Working..." line that changes every few seconds at most.
+
+use std::time::Duration;
+
+use ratatui::style::{Color, Style};
+use ratatui::text::{Line, Span};
+
+use super::ai_queue::Held;
+use super::app::{AiProgress, Message, MessageRole};
+use super::clipboard;
+use super::format;
+use super::markdown;
+use super::render_cache::{self, Row, RowKind};
+
+/// The progress line changes at most this often
+const PROGRESS_STEP: Duration = Duration::from_secs(5);
+
+/// Said before a message, in place of the role's prefix glyph
+pub fn role_label(role: &MessageRole) -> &'static str {
+    match role {
+        MessageRole::User => "You said:",
+        MessageRole::Assistant => "Assistant replied:",
+        MessageRole::System => "Note:",
+        MessageRole::Error => "Error:",
+    }
+}
+
+/// The lines of `message` as they are read out, each with what it is for
+/// mouse clicks
+pub fn lines(message: &Message) -> Vec<(String, RowKind)> {
+    let mut lines = vec![(role_label(&message.role).to_string(), RowKind::Text)];
+    let blocks = clipboard::fenced_blocks(&message.content);
+    let mut in_code_block = false;
+    let mut code_blocks = 0;
+
+    for line in message.content.lines() {
+        if line.starts_with("```") {
+            in_code_block = !in_code_block;
+            if in_code_block {
+                let announcement = match blocks.get(code_blocks).map(|b| b.language) {
+                    Some("text") | None => "Code:".to_string(),
+                    Some(language) => format!("Code, {}:", language),
+                };
+                lines.push((announcement, RowKind::Text));
+            } else {
+                code_blocks += 1;
+                lines.push(("End of code.".to_string(), RowKind::Text));
+            }
+            continue;
+        }
+        let line = markdown::clip(line);
+        if in_code_block {
+            lines.push((line.into_owned(), RowKind::Code(code_blocks)));
+            continue;
+        }
+        let text = if message.role == MessageRole::Assistant { unmarked(&line) } else { line.into_owned() };
+        let plain = plain(&text);
+        // Rules and borders read as nothing, so they are left out
+        if plain.trim().is_empty() && !text.trim().is_empty() {
+            continue;
+        }
+        lines.push((plain, RowKind::Text));
+    }
+
+    if message.truncated {
+        lines.push(("Reply cut off at the length limit; /continue asks for the rest.".to_string(), RowKind::Text));
+    }
+    if message.cached {
+        lines.push(("Answered from the reply cache.".to_string(), RowKind::Text));
+    }
+    lines
+}
+
+/// Rows of one message wrapped to `width`, for `App::render_cache`
+pub fn layout_message(message: &Message, width: u16) -> Vec<Row> {
+    let style = match message.role {
+        MessageRole::Error => Style::default().fg(Color::Red),
+        _ => Style::default(),
+    };
+    let mut rows = Vec::new();
+    for (text, kind) in lines(message) {
+        for row in render_cache::wrap(&text, width as usize) {
+            rows.push(Row { line: Line::from(Span::styled(row, style)), kind });
+        }
+    }
+    rows.push(Row { line: Line::from(""), kind: RowKind::Spacer });
+    rows
+}
+
+/// "Working..." with the time so far in whole steps of `PROGRESS_STEP`,
+/// or how long a held request still waits
+pub fn progress_label(progress: Option<AiProgress>, held: Option<Held>) -> String {
+    if let Some(held) = held {
+        let left = held.retry_at.saturating_duration_since(std::time::Instant::now());
+        return format!(
+            "Queued, retrying in about {} seconds. Press Escape to cancel.",
+            round_up(left).as_secs()
+        );
+    }
+    match progress {
+        Some(p) if p.elapsed >= PROGRESS_STEP => {
+            format!("Working... {}", format::duration(round_down(p.elapsed)))
+        }
+        _ => "Working...".to_string(),
+    }
+}
+
+fn round_down(elapsed: Duration) -> Duration {
+    let step = PROGRESS_STEP.as_secs();
+    Duration::from_secs(elapsed.as_secs() / step * step)
+}
+
+fn round_up(left: Duration) -> Duration {
+    let step = PROGRESS_STEP.as_secs();
+    Duration::from_secs((left.as_secs_f64().ceil() as u64).div_ceil(step) * step)
+}
+
+/// A reply line with its markdown markers dropped and link targets after
+/// the link text
+fn unmarked(line: &str) -> String {
+    markdown::parse(line)
+        .into_iter()
+        .filter_map(|span| match span.kind {
+            markdown::Kind::Marker => None,
+            markdown::Kind::Url => Some(format!(" ({})", span.text)),
+            _ => Some(span.text.to_string()),
+        })
+        .collect()
+}
+
+/// `text` without box-drawing and block characters
+fn plain(text: &str) -> String {
+    let kept: String = text.chars().filter(|c| !matches!(c, '\u{2500}'..='\u{259F}')).collect();
+    kept.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    /// What a screen reader gets for `message` at `width` columns
+    fn read_out(message: &Message, width: u16) -> String {
+        layout_message(message, width)
+            .iter()
+            .map(|row| row.line.spans.iter().map(|s| s.content.as_ref()).collect::<String>() + "\n")
+            .collect()
+    }
+
+    #[test]
+    fn test_reply_snapshot() {
+        let mut reply = Message::assistant(
+            "Here is a **Bell state** ([docs](https://qiskit.org)):\n\
+             ```qasm\nOPENQASM 2.0;\nh q[0];\n```\n\
+             outcome 00: 512 of 1024 shots (50%)"
+                .to_string(),
+        );
+        reply.cached = true;
+        assert_eq!(
+            read_out(&reply, 80),
+            "Assistant replied:\n\
+             Here is a Bell state (docs (https://qiskit.org)):\n\
+             Code, qasm:\n\
+             OPENQASM 2.0;\n\
+             h q[0];\n\
+             End of code.\n\
+             outcome 00: 512 of 1024 shots (50%)\n\
+             Answered from the reply cache.\n\
+             \n"
+        );
+        let rows = layout_message(&reply, 80);
+        assert_eq!(rows[3].kind, RowKind::Code(0));
+    }
+
+    #[test]
+    fn test_boxes_and_wrapping_snapshot() {
+        let help = Message::system(
+            "╭──────────╮\n│ /help    │\n│ /clear   │\n╰──────────╯\n██▌ 50%".to_string(),
+        );
+        assert_eq!(read_out(&help, 80), "Note:\n /help\n /clear\n 50%\n\n");
+
+        let prompt = Message::user("Make me a GHZ state on three qubits".to_string());
+        assert_eq!(read_out(&prompt, 20), "You said:\nMake me a GHZ state \non three qubits\n\n");
+        let error = Message::error("Not logged in".to_string());
+        assert_eq!(read_out(&error, 80), "Error:\nNot logged in\n\n");
+    }
+
+    #[test]
+    fn test_progress_changes_slowly() {
+        let progress = |secs| Some(AiProgress { elapsed: Duration::from_secs(secs), received_chars: 10 });
+        assert_eq!(progress_label(None, None), "Working...");
+        assert_eq!(progress_label(progress(4), None), "Working...");
+        assert_eq!(progress_label(progress(7), None), progress_label(progress(9), None));
+        assert_ne!(progress_label(progress(9), None), progress_label(progress(10), None));
+
+        let held = Held { retry_at: Instant::now() + Duration::from_millis(11_500), remaining: None };
+        assert_eq!(progress_label(None, Some(held)), "Queued, retrying in about 15 seconds. Press Escape to cancel.");
+    }
+}
@@ -0,0 +1,165 @@
+// Waiting out AI rate limits
+//
+// A 429 from the provider doesn't fail the prompt: the request is held until
+// the provider's Retry-After (or a growing backoff when it sends none) and then
+// dispatched again. Prompts typed meanwhile are refused or lined up behind it,
+// per `ai.rate_limit_queue`.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::api::deepseek::RateLimited;
+
+/// Rate-limit waits for one prompt before it is reported as failed
+pub const MAX_WAITS: u32 = 5;
+
+/// First wait when the provider gives no Retry-After; doubles each time
+const BASE_BACKOFF: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Longest Retry-After taken at its word; the header comes from whatever
+/// `ai.base_url` points at
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(10 * 60);
+
+/// The request being held back
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Held {
+    pub retry_at: Instant,
+    /// Requests left in the provider's window, when it said
+    pub remaining: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+pub struct AiQueue {
+    held: Option<Held>,
+    /// Waits so far for the current prompt
+    waits: u32,
+    /// Prompts to send once the current one is answered
+    followups: VecDeque<String>,
+}
+
+impl AiQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hold the current request after a 429. Returns false once it has
+    /// waited `MAX_WAITS` times, when the caller should give up.
+    pub fn hold(&mut self, limited: RateLimited, now: Instant) -> bool {
+        if self.waits >= MAX_WAITS {
+            return false;
+        }
+        let wait = match limited.retry_after {
+            Some(retry_after) => retry_after.min(MAX_RETRY_AFTER),
+            None => BASE_BACKOFF.saturating_mul(1 << self.waits).min(MAX_BACKOFF),
+        };
+        self.waits += 1;
+        self.held = Some(Held {
+            retry_at: now.checked_add(wait).unwrap_or(now),
+            remaining: limited.remaining,
+        });
+        true
+    }
+
+    pub fn held(&self) -> Option<Held> {
+        self.held
+    }
+
+    /// True once when the held request may be sent again
+    pub fn take_due(&mut self, now: Instant) -> bool {
+        match self.held {
+            Some(held) if now >= held.retry_at => {
+                self.held = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The current prompt was answered (or failed for another reason)
+    pub fn finished(&mut self) {
+        self.held = None;
+        self.waits = 0;
+    }
+
+    pub fn push_followup(&mut self, text: String) {
+        self.followups.push_back(text);
+    }
+
+    pub fn next_followup(&mut self) -> Option<String> {
+        self.followups.pop_front()
+    }
+
+    /// Drop the held request and everything queued behind it. Returns how
+    /// many queued prompts were dropped.
+    pub fn cancel(&mut self) -> usize {
+        self.finished();
+        let dropped = self.followups.len();
+        self.followups.clear();
+        dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limited(retry_after: Option<u64>) -> RateLimited {
+        RateLimited {
+            retry_after: retry_after.map(Duration::from_secs),
+            remaining: None,
+        }
+    }
+
+    #[test]
+    fn test_hold_uses_retry_after_then_backoff() {
+        let now = Instant::now();
+        let mut queue = AiQueue::new();
+        assert!(queue.hold(limited(Some(42)), now));
+        assert_eq!(queue.held().unwrap().retry_at, now + Duration::from_secs(42));
+
+        assert!(!queue.take_due(now + Duration::from_secs(41)));
+        assert!(queue.take_due(now + Duration::from_secs(42)));
+        assert!(!queue.take_due(now + Duration::from_secs(43)));
+        assert_eq!(queue.held(), None);
+
+        // Second wait without a header: 5s doubled
+        assert!(queue.hold(limited(None), now));
+        assert_eq!(queue.held().unwrap().retry_at, now + Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_retry_after_is_capped() {
+        let now = Instant::now();
+        let mut queue = AiQueue::new();
+        assert!(queue.hold(limited(Some(u64::MAX)), now));
+        assert_eq!(queue.held().unwrap().retry_at, now + MAX_RETRY_AFTER);
+        assert!(queue.take_due(now + MAX_RETRY_AFTER));
+    }
+
+    #[test]
+    fn test_gives_up_after_max_waits() {
+        let now = Instant::now();
+        let mut queue = AiQueue::new();
+        for _ in 0..MAX_WAITS {
+            assert!(queue.hold(limited(None), now));
+            assert!(queue.held().unwrap().retry_at <= now + MAX_BACKOFF);
+        }
+        assert!(!queue.hold(limited(None), now));
+
+        // A new prompt starts counting again
+        queue.finished();
+        assert!(queue.hold(limited(None), now));
+    }
+
+    #[test]
+    fn test_cancel_drops_followups() {
+        let mut queue = AiQueue::new();
+        queue.hold(limited(Some(1)), Instant::now());
+        queue.push_followup("second".to_string());
+        queue.push_followup("third".to_string());
+        assert_eq!(queue.cancel(), 2);
+        assert_eq!(queue.held(), None);
+        assert_eq!(queue.next_followup(), None);
+    }
+}
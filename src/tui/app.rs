@@ -1,18 +1,186 @@
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Local, Utc};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 use uuid::Uuid;
-use anyhow::Result;
+use anyhow::{Context, Result};
+use crossterm::event::KeyEvent;
 
-use crate::api::deepseek::{ChatMessage, DeepSeekClient};
-use crate::api::ApiClient;
-use crate::config::Config;
+use crate::api::deepseek::{self, ChatMessage, ChatReply, DeepSeekClient, NoReply, RateLimited, Sampling};
+use crate::api::models::{self, Catalog, ModelList, NoModelsEndpoint};
+use crate::api::response_cache::{self, Cached, ResponseCache};
+use crate::api::tokens;
+use crate::api::transcript::{self, Transcript};
+use crate::api::ibm_quantum::{BackendStatus, IbmQuantumClient};
+use crate::api::quantum_error::QuantumError;
+use crate::api::{ApiClient, ApiError, LoginRequest, RegisterRequest, UpdateProfileRequest, User};
+use crate::auth;
+use crate::config::audit::{self, Severity};
+use crate::config::layers::Source;
+use crate::config::secrets::ApiKeyKind;
+use crate::config::settings::{QueuePolicy, TimeFormat};
+use crate::config::{qiskit, tiers};
+use crate::config::{Config, ConfigLocator};
+use crate::db::manager::{DbHealth, PoolManager};
+use crate::db::usage::{PeriodUsage, AI_TOKENS};
+use crate::diagnostics::{self, Level};
+use crate::quantum::calibration::BackendInfo;
+use crate::quantum::job::{JobProgress, JobStatus, QueueTracker};
+use crate::quantum::select::{self, Requirements};
+use crate::quantum::{calc, compare, export, python, qasm, sweep};
+
+use super::ai_queue::{AiQueue, Held};
+use super::backend_context::{self, BackendContext};
+use super::block_picker::{BlockId, BlockPicker, PickAction, PickResult};
+use super::capabilities::Capabilities;
+use super::clipboard::{self, CodeBlock};
+use super::config_reload::{self, Apply};
+use super::continuation;
+use super::diff;
+use super::draft::{self, DraftKeeper};
+use super::format;
+use super::notify::{self, Ping};
+use super::prompts;
+use super::quota;
+use super::editor::{self, EditAction, EditOutcome, EditRequest};
+use super::hit_test::{ClickTarget, HitMap};
+use super::input_history::{self, InputHistory, Search, SearchResult};
+use super::command_usage::{self, CommandUsage};
+use super::json_view;
+use super::services::{Ai, Auth};
+use super::language;
+use super::line_edit::{Edit, LineEditor};
+use super::palette::{self, Item, Palette, PaletteResult};
+use super::history::{self, Session};
+use super::pacer::FramePacer;
+use super::render_cache::RenderCache;
+use super::scroll::ScrollState;
+use super::share::{self, ShareOptions};
+use super::stats::SessionStats;
+use super::tasks::{Alarm, Operation, TaskTracker};
+use super::telemetry::{self, Batch, Telemetry};
+use super::wizard::{self, AccountChoice, SetupWizard, WizardAction};
+
+/// Result of a login/register request: the token and the account
+type AuthResult = Result<(String, User), String>;
+
+/// Result of an AI request and how long it took
+type AiResult = Result<(ChatReply, Duration), AiFailure>;
+
+/// Link and the confirmation to show with it
+type ShareResult = Result<(String, String), String>;
+
+/// Whether the user asked for the list, and the list
+type BackendsResult = (bool, Result<Vec<BackendStatus>, String>);
+
+/// Why an AI request produced no reply
+#[derive(Debug)]
+pub enum AiFailure {
+    /// The provider asked us to slow down; the request can be held and retried
+    RateLimited(RateLimited),
+    /// The provider answered with nothing usable: empty or refused
+    NoReply(NoReply),
+    Failed(String),
+}
+
+/// How often an in-flight AI request reports progress
+const AI_HEARTBEAT: Duration = Duration::from_secs(1);
+
+/// /regen asks again this much hotter than the configured temperature,
+/// capped at the 2.0 OpenAI-compatible APIs accept
+const REGEN_TEMPERATURE_BOOST: f32 = 0.3;
+const MAX_TEMPERATURE: f32 = 2.0;
+
+/// Models /models lists before summing up the rest
+const MODELS_SHOWN: usize = 40;
+
+/// Progress of an in-flight AI request, shown next to "thinking..."
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AiProgress {
+    pub elapsed: Duration,
+    /// Characters of the reply received so far; 0 until replies are streamed
+    pub received_chars: usize,
+}
+
+/// How long quitting waits for critical tasks such as logout
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often tracked tasks are checked for panics, deadlines and lost results
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Beyond the longest task deadline, how long the loading state may last
+/// with no sign of progress before it is cleared
+const LOADING_MARGIN: Duration = Duration::from_secs(30);
+
+/// How long an info diagnostic stays in the status bar
+const NOTICE_DURATION: Duration = Duration::from_secs(5);
+
+/// Two clicks on the same cell within this window are a double-click
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+
+/// How long /backend-info answers from its cache. IBM recalibrates devices
+/// about daily; the queue length is what goes stale.
+const BACKEND_INFO_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// How often /watch asks IBM Quantum about a job
+const JOB_POLL: Duration = Duration::from_secs(30);
+
+/// A hardware job /watch follows; its message is rewritten on every poll
+struct JobWatch {
+    job_id: String,
+    message: Uuid,
+    started: Instant,
+    tracker: QueueTracker,
+    rx: mpsc::Receiver<Result<JobProgress, String>>,
+}
+
+impl JobWatch {
+    /// The message text for the latest poll
+    fn update(&mut self, progress: Result<JobProgress, String>) -> String {
+        let elapsed = self.started.elapsed();
+        let state = match progress {
+            Err(e) => format!("could not check it ({}); stopped watching", e),
+            Ok(JobProgress { status: JobStatus::Queued, position }) => {
+                if let Some(position) = position {
+                    self.tracker.observe(elapsed, position);
+                }
+                match self.tracker.describe().filter(|_| position.is_some()) {
+                    Some(queue) => queue,
+                    // No queue information from this backend
+                    None => format!("queued, watched for {}", format::duration(elapsed)),
+                }
+            }
+            Ok(JobProgress { status: JobStatus::Running, .. }) => {
+                format!("running, watched for {}", format::duration(elapsed))
+            }
+            Ok(JobProgress { status, .. }) => {
+                let outcome = match status {
+                    JobStatus::Completed => "completed",
+                    JobStatus::Failed => "failed",
+                    _ => "cancelled",
+                };
+                format!("{} (watched for {})", outcome, format::duration(elapsed))
+            }
+        };
+        format!("Job {}: {}", self.job_id, state)
+    }
+}
 
 #[derive(Debug, Clone)]
+#[allow(dead_code)]
 pub struct Message {
     pub id: Uuid,
     pub role: MessageRole,
     pub content: String,
     pub timestamp: DateTime<Local>,
+    /// A reply cut off at the token limit; /continue asks for the rest
+    pub truncated: bool,
+    /// A reply answered from ai.cache
+    pub cached: bool,
+    /// Earlier /regen attempts at this reply, oldest first
+    pub alternatives: Vec<String>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -30,6 +198,9 @@ impl Message {
             role: MessageRole::User,
             content,
             timestamp: Local::now(),
+            truncated: false,
+            cached: false,
+            alternatives: Vec::new(),
         }
     }
 
@@ -39,6 +210,9 @@ impl Message {
             role: MessageRole::Assistant,
             content,
             timestamp: Local::now(),
+            truncated: false,
+            cached: false,
+            alternatives: Vec::new(),
         }
     }
 
@@ -48,6 +222,9 @@ impl Message {
             role: MessageRole::System,
             content,
             timestamp: Local::now(),
+            truncated: false,
+            cached: false,
+            alternatives: Vec::new(),
         }
     }
 
@@ -57,29 +234,113 @@ impl Message {
             role: MessageRole::Error,
             content,
             timestamp: Local::now(),
+            truncated: false,
+            cached: false,
+            alternatives: Vec::new(),
         }
     }
+
+    /// Show the attempt before this one; repeated calls cycle through all of
+    /// them. False when /regen never replaced this reply.
+    pub fn show_previous(&mut self) -> bool {
+        let Some(previous) = self.alternatives.pop() else {
+            return false;
+        };
+        let current = std::mem::replace(&mut self.content, previous);
+        self.alternatives.insert(0, current);
+        // A new id, so the render cache lays out the swapped text
+        self.id = Uuid::new_v4();
+        self.truncated = false;
+        self.cached = false;
+        true
+    }
+}
+
+/// What /regen does
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegenAction {
+    /// Ask again, optionally with another model
+    New { model: Option<String> },
+    /// Cycle back through earlier attempts
+    Previous,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[allow(dead_code)]
 pub enum InputMode {
     Normal,
     Editing,
 }
 
+#[derive(Debug, Clone)]
+pub enum KeysAction {
+    Set(ApiKeyKind),
+    Clear(ApiKeyKind),
+    Status,
+}
+
 #[derive(Debug, Clone)]
 pub enum SlashCommand {
     Login { email: String, password: String },
-    Register { email: String, username: String, password: String },
+    Register { email: String, username: String, password: String, display_name: Option<String> },
     Logout,
+    /// /accounts lists saved accounts (None) or switches to one
+    Accounts(Option<String>),
     Upgrade,
     Help,
     Quit,
     Clear,
     Status,
+    Stats,
+    Backends,
+    BackendInfo(String),
+    /// /backend show, or /backend set <name>
+    DefaultBackend(Option<String>),
+    /// A .qasm file, or the last code block when None
+    Recommend(Option<String>),
+    /// Gates to apply, as typed after /calc
+    Calc(String),
+    Compare { job_a: String, job_b: String },
+    /// /export counts <job id> <file>
+    ExportCounts { job_id: String, path: String },
+    SweepStatus { sweep_id: String },
+    /// Follow an IBM Quantum job's queue position until it finishes
+    Watch(String),
+    Share(ShareOptions),
+    SetDisplayName(String),
+    Connect,
+    Resume,
+    Retry,
+    Cancel,
+    /// /cache clear
+    CacheClear,
+    Continue,
+    Regen(RegenAction),
+    /// /set alone (None) shows the generation parameters
+    Set(Option<(String, String)>),
+    /// /model alone (None) shows the model in use
+    Model(Option<String>),
+    Models { refresh: bool },
+    /// --pretty copies a JSON block indented
+    Copy { pretty: bool },
+    Save { path: String, force: bool },
+    Diff(String),
+    Edit(EditAction),
+    RunPrompt { path: String, args: String },
+    Keys(KeysAction),
+    Telemetry(TelemetryAction),
+    Reload,
     Unknown(String),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TelemetryAction {
+    On,
+    Off,
+    Status,
+    Purge,
+}
+
 impl SlashCommand {
     pub fn parse(input: &str) -> Option<Self> {
         let input = input.trim();
@@ -87,9 +348,10 @@ impl SlashCommand {
             return None;
         }
 
+        // A lone slash is still meant as a command, not a prompt for the AI
         let parts: Vec<&str> = input[1..].split_whitespace().collect();
         if parts.is_empty() {
-            return None;
+            return Some(SlashCommand::Unknown(String::new()));
         }
 
         let cmd = parts[0].to_lowercase();
@@ -106,70 +368,431 @@ impl SlashCommand {
             }
             "register" => {
                 if parts.len() >= 4 {
+                    let display_name = parts[4..].join(" ");
                     SlashCommand::Register {
                         email: parts[1].to_string(),
                         username: parts[2].to_string(),
                         password: parts[3].to_string(),
+                        display_name: (!display_name.is_empty()).then_some(display_name),
                     }
                 } else {
-                    SlashCommand::Unknown("register <email> <username> <password>".to_string())
+                    SlashCommand::Unknown("register <email> <username> <password> [display name]".to_string())
                 }
             }
             "logout" => SlashCommand::Logout,
+            "accounts" => match parts[1..] {
+                [] | ["list"] => SlashCommand::Accounts(None),
+                ["switch", email] => SlashCommand::Accounts(Some(email.to_string())),
+                _ => SlashCommand::Unknown("accounts [switch <email>]".to_string()),
+            },
             "upgrade" => SlashCommand::Upgrade,
             "help" | "h" | "?" => SlashCommand::Help,
             "quit" | "q" | "exit" => SlashCommand::Quit,
             "clear" | "cls" => SlashCommand::Clear,
             "status" => SlashCommand::Status,
+            "stats" => SlashCommand::Stats,
+            "backends" => SlashCommand::Backends,
+            "backend-info" => match parts[1..] {
+                [name] => SlashCommand::BackendInfo(name.to_string()),
+                _ => SlashCommand::Unknown("backend-info <name>".to_string()),
+            },
+            "backend" => match parts[1..] {
+                [] | ["show"] => SlashCommand::DefaultBackend(None),
+                ["set", name] => SlashCommand::DefaultBackend(Some(name.to_string())),
+                _ => SlashCommand::Unknown("backend show | backend set <name>".to_string()),
+            },
+            "recommend" => match parts[1..] {
+                [] => SlashCommand::Recommend(None),
+                [path] => SlashCommand::Recommend(Some(path.to_string())),
+                _ => SlashCommand::Unknown("recommend [file.qasm]".to_string()),
+            },
+            "calc" => {
+                let gates = input[1..].trim_start_matches(|c: char| !c.is_whitespace()).trim();
+                if gates.is_empty() {
+                    SlashCommand::Unknown("calc <gates>, e.g. /calc H 0; CX 0 1".to_string())
+                } else {
+                    SlashCommand::Calc(gates.to_string())
+                }
+            }
+            "compare" => match parts[1..] {
+                [job_a, job_b] => SlashCommand::Compare {
+                    job_a: job_a.to_string(),
+                    job_b: job_b.to_string(),
+                },
+                _ => SlashCommand::Unknown("compare <job_a> <job_b>".to_string()),
+            },
+            "export" => match parts[1..] {
+                ["counts", job_id, path] => SlashCommand::ExportCounts {
+                    job_id: job_id.to_string(),
+                    path: path.to_string(),
+                },
+                _ => SlashCommand::Unknown("export counts <job id> <file.csv|file.json>".to_string()),
+            },
+            "sweep" => match parts[1..] {
+                ["status", sweep_id] => SlashCommand::SweepStatus { sweep_id: sweep_id.to_string() },
+                _ => SlashCommand::Unknown("sweep status <id>".to_string()),
+            },
+            "watch" => match parts[1..] {
+                [job_id] => SlashCommand::Watch(job_id.to_string()),
+                _ => SlashCommand::Unknown("watch <job id>".to_string()),
+            },
+            "profile" => match parts[1..] {
+                ["set", "display-name", _, ..] => {
+                    // The name may contain spaces; keep it as typed
+                    let mut name = &input[1..];
+                    for _ in 0..3 {
+                        name = name.trim_start().trim_start_matches(|c: char| !c.is_whitespace());
+                    }
+                    SlashCommand::SetDisplayName(name.trim().to_string())
+                }
+                _ => SlashCommand::Unknown("profile set display-name <name>".to_string()),
+            },
+            "share" => match ShareOptions::parse(&parts[1..]) {
+                Some(options) => SlashCommand::Share(options),
+                None => SlashCommand::Unknown("share [code] [private|unlisted] [expires=7d|never]".to_string()),
+            },
+            "connect" => SlashCommand::Connect,
+            "copy" => match parts[1..] {
+                [] => SlashCommand::Copy { pretty: false },
+                ["--pretty"] => SlashCommand::Copy { pretty: true },
+                _ => SlashCommand::Unknown("copy [--pretty]".to_string()),
+            },
+            "save" => match parts[1..] {
+                [path] => SlashCommand::Save { path: path.to_string(), force: false },
+                [path, "-f"] | ["-f", path] => SlashCommand::Save { path: path.to_string(), force: true },
+                _ => SlashCommand::Unknown("save <file> [-f]".to_string()),
+            },
+            "diff" => match parts[1..] {
+                [path] => SlashCommand::Diff(path.to_string()),
+                _ => SlashCommand::Unknown("diff <file>".to_string()),
+            },
+            "resume" => SlashCommand::Resume,
+            "retry" => SlashCommand::Retry,
+            "cancel" => SlashCommand::Cancel,
+            "cache" => match parts[1..] {
+                ["clear"] => SlashCommand::CacheClear,
+                _ => SlashCommand::Unknown("cache clear".to_string()),
+            },
+            "continue" => SlashCommand::Continue,
+            "regen" => match parts[1..] {
+                [] => SlashCommand::Regen(RegenAction::New { model: None }),
+                ["prev"] => SlashCommand::Regen(RegenAction::Previous),
+                ["--model", model] => SlashCommand::Regen(RegenAction::New { model: Some(model.to_string()) }),
+                _ => SlashCommand::Unknown("regen [prev | --model <name>]".to_string()),
+            },
+            "model" => match parts[1..] {
+                [] => SlashCommand::Model(None),
+                [name] => SlashCommand::Model(Some(name.to_string())),
+                _ => SlashCommand::Unknown("model [<name> | default]".to_string()),
+            },
+            "models" => match parts[1..] {
+                [] => SlashCommand::Models { refresh: false },
+                ["refresh"] => SlashCommand::Models { refresh: true },
+                _ => SlashCommand::Unknown("models [refresh]".to_string()),
+            },
+            "set" => match parts[1..] {
+                [] => SlashCommand::Set(None),
+                [name, value] => SlashCommand::Set(Some((name.to_string(), value.to_string()))),
+                _ => SlashCommand::Unknown("set [<parameter> <value | default>]".to_string()),
+            },
+            "run-prompt" => {
+                // Everything after the command word: <path> [name=value ...]
+                let rest = input[1..]
+                    .trim_start_matches(|c: char| !c.is_whitespace())
+                    .trim_start();
+                let (path, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                if path.is_empty() {
+                    SlashCommand::Unknown("run-prompt <path> [name=value ...]".to_string())
+                } else {
+                    SlashCommand::RunPrompt {
+                        path: path.to_string(),
+                        args: args.trim().to_string(),
+                    }
+                }
+            }
+            "edit" => match parts.get(1).copied() {
+                Some("code") | None => SlashCommand::Edit(EditAction::Code),
+                Some("run") => SlashCommand::Edit(EditAction::Run),
+                _ => SlashCommand::Unknown("edit code|run".to_string()),
+            },
+            "keys" => {
+                let kind = parts.get(2).and_then(|k| ApiKeyKind::parse(k));
+                match (parts.get(1).copied(), kind) {
+                    (Some("set"), Some(kind)) => SlashCommand::Keys(KeysAction::Set(kind)),
+                    (Some("clear"), Some(kind)) => SlashCommand::Keys(KeysAction::Clear(kind)),
+                    (Some("status") | None, _) => SlashCommand::Keys(KeysAction::Status),
+                    _ => SlashCommand::Unknown("keys set|clear ai|quantum, or /keys status".to_string()),
+                }
+            }
+            "telemetry" => match parts.get(1).copied() {
+                Some("on") => SlashCommand::Telemetry(TelemetryAction::On),
+                Some("off") => SlashCommand::Telemetry(TelemetryAction::Off),
+                Some("status") | None => SlashCommand::Telemetry(TelemetryAction::Status),
+                Some("purge") => SlashCommand::Telemetry(TelemetryAction::Purge),
+                _ => SlashCommand::Unknown("telemetry on|off|status|purge".to_string()),
+            },
+            "reload" => SlashCommand::Reload,
             other => SlashCommand::Unknown(other.to_string()),
         })
     }
+
+    /// Fixed name for usage counts; never includes arguments
+    pub fn name(&self) -> &'static str {
+        match self {
+            SlashCommand::Login { .. } => "login",
+            SlashCommand::Register { .. } => "register",
+            SlashCommand::Logout => "logout",
+            SlashCommand::Accounts(_) => "accounts",
+            SlashCommand::Upgrade => "upgrade",
+            SlashCommand::Help => "help",
+            SlashCommand::Quit => "quit",
+            SlashCommand::Clear => "clear",
+            SlashCommand::Status => "status",
+            SlashCommand::Stats => "stats",
+            SlashCommand::Backends => "backends",
+            SlashCommand::BackendInfo(_) => "backend-info",
+            SlashCommand::DefaultBackend(_) => "backend",
+            SlashCommand::Recommend(_) => "recommend",
+            SlashCommand::Calc(_) => "calc",
+            SlashCommand::Compare { .. } => "compare",
+            SlashCommand::ExportCounts { .. } => "export",
+            SlashCommand::SweepStatus { .. } => "sweep",
+            SlashCommand::Watch(_) => "watch",
+            SlashCommand::Share(_) => "share",
+            SlashCommand::SetDisplayName(_) => "profile",
+            SlashCommand::Connect => "connect",
+            SlashCommand::Resume => "resume",
+            SlashCommand::Retry => "retry",
+            SlashCommand::Cancel => "cancel",
+            SlashCommand::CacheClear => "cache",
+            SlashCommand::Continue => "continue",
+            SlashCommand::Regen(_) => "regen",
+            SlashCommand::Set(_) => "set",
+            SlashCommand::Model(_) => "model",
+            SlashCommand::Models { .. } => "models",
+            SlashCommand::Copy { .. } => "copy",
+            SlashCommand::Save { .. } => "save",
+            SlashCommand::Diff(_) => "diff",
+            SlashCommand::Edit(_) => "edit",
+            SlashCommand::RunPrompt { .. } => "run-prompt",
+            SlashCommand::Keys(_) => "keys",
+            SlashCommand::Telemetry(_) => "telemetry",
+            SlashCommand::Reload => "reload",
+            SlashCommand::Unknown(_) => "unknown",
+        }
+    }
+
+    /// Usage of a known command typed with the wrong arguments, shown live
+    /// under the input. Only checked once the command name is followed by a
+    /// space, so a half-typed name stays quiet.
+    pub fn usage_hint(input: &str) -> Option<String> {
+        let input = input.trim_start();
+        let (name, _) = input.strip_prefix('/')?.split_once(char::is_whitespace)?;
+        match Self::parse(input)? {
+            // Unknown commands carry their own name instead of a usage string
+            SlashCommand::Unknown(usage) if usage != name.to_lowercase() => {
+                Some(format!("expected: /{}", usage))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// How `qhub` was started, for what `App::new` opens with
+#[derive(Debug, Clone, Default)]
+pub struct AppOptions {
+    /// --resume: continue the most recent saved session
+    pub resume: bool,
+    /// --session <id>: open this saved session
+    pub session: Option<String>,
+    /// --new: start fresh even with ui.autoresume on
+    pub new_session: bool,
+    /// --config or QHUB_CONFIG: the config file to use
+    pub config: ConfigLocator,
+    /// --accessible: screen-reader output whatever ui.accessible says
+    pub accessible: bool,
+    /// --quiet: a one-line ready notice in place of the welcome
+    pub quiet: bool,
 }
 
 pub struct App {
     pub messages: Vec<Message>,
+    /// The welcome, or a one-line ready notice with ui.show_welcome off or
+    /// --quiet. Drawn above the transcript but kept out of `messages`, so it
+    /// is never saved, searched, shared or sent to the AI.
+    pub welcome: Option<Message>,
     pub input: String,
+    /// Where edits apply in `input`, as a byte offset; None is the end
+    input_cursor: Option<usize>,
+    line_editor: LineEditor,
     pub input_mode: InputMode,
-    pub scroll_offset: usize,
+    pub scroll: ScrollState,
     pub user_email: Option<String>,
+    // Display name, else username; shown instead of the email when set
+    pub user_name: Option<String>,
     pub user_tier: String,
+    /// Unix seconds of the previous login, for /status
+    user_last_login: Option<i64>,
     pub is_connected: bool,
     pub should_quit: bool,
     pub is_loading: bool,
-    pub ai_client: DeepSeekClient,
-    pub ai_response_rx: Option<mpsc::Receiver<Result<String, String>>>,
-    pub auth_response_rx: Option<mpsc::Receiver<Result<(String, String, String), String>>>,
+    pub ai_client: Arc<dyn Ai>,
+    pub ai_response_rx: Option<mpsc::Receiver<AiResult>>,
+    pub ai_progress_rx: Option<mpsc::Receiver<AiProgress>>,
+    pub ai_progress: Option<AiProgress>,
+    // Requests held back by provider rate limits, and prompts queued behind them
+    ai_queue: AiQueue,
+    /// Prompt of the last request that got no reply, for /retry. It is taken
+    /// out of the conversation so sending it again doesn't add it twice.
+    retry_prompt: Option<String>,
+    /// Truncated reply the in-flight request continues
+    continuing: Option<Uuid>,
+    /// The reply /regen took off screen, put back if the new attempt fails,
+    /// and the overrides the attempt is sent with
+    regenerating: Option<(Message, Sampling)>,
+    /// Generation parameters /set changed for this session
+    session_sampling: Sampling,
+    pub auth_response_rx: Option<mpsc::Receiver<AuthResult>>,
     pub conversation_history: Vec<ChatMessage>,
     pub config: Config,
+    /// Holds the active account's token, for sharing, profile and telemetry
     pub api_client: ApiClient,
+    /// Logging in and out, and checking a saved session
+    pub auth: Arc<dyn Auth>,
     // Autocomplete
     pub suggestions: Vec<String>,
     pub selected_suggestion: usize,
     pub show_suggestions: bool,
+    // First-run setup
+    pub is_first_run: bool,
+    pub wizard: Option<SetupWizard>,
+    // API key management (/keys)
+    pub pending_key: Option<ApiKeyKind>,
+    pub key_test_rx: Option<mpsc::Receiver<(ApiKeyKind, Result<(), String>)>>,
+    // Spawned work, cancelled or awaited on quit
+    pub tasks: TaskTracker,
+    next_watchdog: Instant,
+    // Last sign of life while `is_loading`: when it was first seen set, or the latest progress
+    loading_seen: Option<Instant>,
+    // Notices edits to the config files, for reloading them
+    config_watch: config_reload::Watcher,
+    pub logout_rx: Option<mpsc::Receiver<Result<(), String>>>,
+    // /accounts switch, answered once the account's session is verified
+    account_switch_rx: Option<mpsc::Receiver<(String, AuthResult)>>,
+    // Direct database connection, when DATABASE_URL is set
+    pub db: Option<PoolManager>,
+    // Session activity for /stats
+    pub stats: SessionStats,
+    pub show_stats: bool,
+    pub usage_rx: Option<mpsc::Receiver<Vec<(String, i64)>>>,
+    /// Month-to-date usage being read for the quota banner
+    quota_rx: Option<mpsc::Receiver<PeriodUsage>>,
+    job_watches: Vec<JobWatch>,
+    quota_checked: Option<Instant>,
+    /// The account `quota_banner` is about
+    quota_email: Option<String>,
+    quota_banner: Option<quota::Banner>,
+    /// The banner level Esc hid; only a higher one shows again
+    quota_dismissed: Option<quota::Level>,
+    // Which features the environment supports (guest mode when no backend exists)
+    pub capabilities: Capabilities,
+    // Direct database mode with JWT_SECRET unset: shown in the status bar
+    pub insecure_jwt_secret: bool,
+    // Latest info diagnostic and when it was posted, shown in the status bar
+    notice: Option<(String, Instant)>,
+    // Last key press, for security.idle_timeout_mins
+    last_activity: Instant,
+    // Logged out for inactivity: the chat is hidden until /login
+    pub locked: bool,
+    /// Whether the terminal has focus; None until it reports it
+    pub focused: Option<bool>,
+    /// Screen-reader output (ui.accessible or --accessible)
+    pub accessible: bool,
+    /// Replies kept by ai.cache
+    ai_cache: ResponseCache,
+    /// What the reply to the request in flight is cached under
+    ai_cache_key: Option<String>,
+    // When the main loop redraws and how long it blocks on input
+    pub pacer: FramePacer,
+    // Mouse support: regions drawn last frame and the clicked message
+    pub hits: HitMap,
+    pub selected_message: Option<usize>,
+    // Wrapped message rows, reused until the width or the message changes
+    pub render_cache: RenderCache,
+    last_click: Option<(Instant, u16, u16)>,
+    // Code block to open in $EDITOR, picked up by the main loop
+    pub pending_edit: Option<EditRequest>,
+    // File and code block waiting for a y/n after /save -f showed the diff
+    pending_save: Option<(PathBuf, String)>,
+    // ui.autosave_sessions: this run's session file and the previous one
+    session_path: Option<PathBuf>,
+    resume_path: Option<PathBuf>,
+    // Unsent input saved to disk in case of a crash
+    draft: DraftKeeper,
+    input_history: InputHistory,
+    /// Ctrl+R search over `input_history`, while open
+    pub history_search: Option<Search>,
+    // How often each slash command runs, for ranking suggestions
+    command_usage: CommandUsage,
+    // ai.transcript: every exchange appended to ~/.qhub/files/transcript.jsonl
+    transcript: Option<Transcript>,
+    // Ctrl+B: numbering the code blocks on screen
+    pub block_picker: Option<BlockPicker>,
+    /// Ctrl+P command palette, while open
+    pub palette: Option<Palette>,
+    // Opt-in usage counters; a failed upload hands its batch back
+    telemetry: Telemetry,
+    telemetry_rx: Option<mpsc::Receiver<Result<(), Batch>>>,
+    telemetry_upload_failed: bool,
+    // /backends results, also summarised for the AI; the flag marks a user-run fetch
+    backend_context: BackendContext,
+    backends_rx: Option<mpsc::Receiver<BackendsResult>>,
+    // /backend set waiting for the backend list to check the name against
+    pending_default_backend: Option<String>,
+    // /backend-info, kept for a few minutes per backend
+    backend_info_rx: Option<mpsc::Receiver<(String, Result<BackendInfo, String>)>>,
+    backend_info_cache: HashMap<String, (Instant, BackendInfo)>,
+    // Models /model checks and completes against; /models refresh asks the provider
+    model_catalog: Catalog,
+    models_rx: Option<mpsc::Receiver<anyhow::Result<Vec<String>>>>,
+    // /compare and /sweep status, rendered off the UI thread
+    report_rx: Option<mpsc::Receiver<Result<String, String>>>,
+    share_rx: Option<mpsc::Receiver<ShareResult>>,
+    profile_rx: Option<mpsc::Receiver<Result<User, String>>>,
+    db_health: Option<DbHealth>,
 }
 
 impl Default for App {
     fn default() -> Self {
-        Self::new()
+        Self::new(AppOptions::default())
     }
 }
 
 impl App {
-    pub fn new() -> Self {
+    pub fn new(options: AppOptions) -> Self {
         // 1. Load or create configuration
-        let config = Config::load().unwrap_or_else(|e| {
-            eprintln!("Warning: Failed to load config: {}. Using defaults.", e);
-            Config::default()
-        });
+        let (mut config, config_error) = match Config::load(&options.config) {
+            Ok(config) => (config, None),
+            Err(e) => (Config::default_at(&options.config), Some(e)),
+        };
+        // Secrets saved before security.use_keychain was turned on
+        match config.move_secrets_to_keychain() {
+            Ok(moved) if !moved.is_empty() => {
+                diagnostics::info(format!("Moved {} from config.toml into the keychain", moved.join(" and ")));
+            }
+            Ok(_) => {}
+            Err(e) => diagnostics::warn(format!("Could not move secrets from config.toml into the keychain: {:#}", e)),
+        }
+        let accessible = options.accessible || config.ui.accessible;
         
         // 2. Initialize API client
-        let mut api_client = ApiClient::new(config.api_url.clone())
+        let mut api_client = ApiClient::new(config.api_url.clone(), &config.network)
             .expect("Failed to create API client");
         
         // 3. Validate stored token if exists
-        let (user_email, user_tier, _is_authenticated) = if let Some(ref user_config) = config.user {
-            if let Some(ref token) = user_config.token {
-                api_client.set_token(token.clone());
+        let (user_email, user_name, user_tier, user_last_login) = if let Some(user_config) = config.user() {
+            if let Some(token) = config.session_token(user_config) {
+                api_client.set_token(token);
                 
                 // Verify token is still valid
                 match tokio::task::block_in_place(|| {
@@ -178,57 +801,142 @@ impl App {
                     })
                 }) {
                     Ok(user) => {
-                        eprintln!("✅ Session valid - Welcome back, {}!", user.email);
-                        (Some(user.email), user.tier, true)
+                        diagnostics::info(format!("Welcome back, {}", user.name()));
+                        let name = user.display_name.or(user.username);
+                        (Some(user.email), name, user.tier, user.last_login_at)
                     }
                     Err(e) => {
-                        eprintln!("⚠️  Session expired or invalid: {}", e);
-                        eprintln!("💡 Please login again with /login");
+                        diagnostics::warn(format!("Session expired or invalid: {}. Please /login again.", e));
                         api_client.clear_token();
-                        (None, "free".to_string(), false)
+                        (None, None, "free".to_string(), None)
                     }
                 }
             } else {
-                (None, "free".to_string(), false)
+                (None, None, "free".to_string(), None)
             }
         } else {
-            (None, "free".to_string(), false)
+            (None, None, "free".to_string(), None)
         };
         
-        // 4. Initialize AI client with config
-        let ai_client = if let Some(api_key) = config.get_ai_api_key() {
-            DeepSeekClient::new(api_key)
-        } else {
-            DeepSeekClient::with_default_key()
-        };
+        // 4. Initialize AI client with config, and the models it offers as last fetched
+        let ai_client: Arc<dyn Ai> = Arc::new(DeepSeekClient::from_config(&config));
+        let model_catalog = models::cache_path()
+            .ok()
+            .and_then(|path| models::load_cached(&path, &config.ai.base_url))
+            .map_or(Catalog::Builtin, Catalog::Provider);
         
-        // 5. Build App struct
+        // 5. Work out what this environment supports
+        let capabilities = Capabilities::new(
+            std::env::var("DATABASE_URL").is_ok(),
+            config.has_remote_api(),
+            config.get_ai_api_key().is_some(),
+        );
+        let pacer = FramePacer::new(Duration::from_millis(config.ui.tick_ms));
+        let config_files = config.path().ok().into_iter().chain(config.layers.project.clone());
+        let config_watch = config_reload::Watcher::new(config_files, Instant::now());
+        let telemetry = Telemetry::load(config.telemetry.enabled, telemetry::telemetry_path().ok());
+
+        // 6. Build App struct
+        let is_first_run = !options.config.exists();
+        let ai_cache_mode = config.ai.cache;
         let mut app = Self {
             messages: Vec::new(),
+            welcome: None,
             input: String::new(),
+            input_cursor: None,
+            line_editor: LineEditor::default(),
             input_mode: InputMode::Normal,
-            scroll_offset: 0,
+            scroll: ScrollState::new(),
             user_email,
+            user_name,
             user_tier,
+            user_last_login,
             is_connected: true,
             should_quit: false,
             is_loading: false,
             ai_client,
             ai_response_rx: None,
+            ai_progress_rx: None,
+            ai_progress: None,
+            ai_queue: AiQueue::new(),
+            retry_prompt: None,
+            continuing: None,
+            regenerating: None,
+            session_sampling: Sampling::default(),
             auth_response_rx: None,
-            conversation_history: vec![DeepSeekClient::get_system_prompt()],
+            conversation_history: vec![DeepSeekClient::get_system_prompt(&config.ai)],
             config,
+            auth: Arc::new(api_client.clone()),
             api_client,
             suggestions: Vec::new(),
             selected_suggestion: 0,
             show_suggestions: false,
+            is_first_run,
+            wizard: None,
+            pending_key: None,
+            key_test_rx: None,
+            tasks: TaskTracker::new(),
+            next_watchdog: Instant::now(),
+            loading_seen: None,
+            config_watch,
+            logout_rx: None,
+            account_switch_rx: None,
+            db: None,
+            stats: SessionStats::new(),
+            show_stats: false,
+            usage_rx: None,
+            quota_rx: None,
+            job_watches: Vec::new(),
+            quota_checked: None,
+            quota_email: None,
+            quota_banner: None,
+            quota_dismissed: None,
+            pacer,
+            hits: HitMap::default(),
+            selected_message: None,
+            render_cache: RenderCache::default(),
+            last_click: None,
+            pending_edit: None,
+            pending_save: None,
+            session_path: None,
+            resume_path: None,
+            draft: DraftKeeper::new(draft::draft_path().ok()),
+            input_history: InputHistory::load(input_history::history_path().ok()),
+            history_search: None,
+            command_usage: CommandUsage::load(command_usage::usage_path().ok()),
+            transcript: None,
+            block_picker: None,
+            palette: None,
+            telemetry,
+            telemetry_rx: None,
+            telemetry_upload_failed: false,
+            backend_context: BackendContext::new(),
+            backends_rx: None,
+            pending_default_backend: None,
+            backend_info_rx: None,
+            backend_info_cache: HashMap::new(),
+            model_catalog,
+            models_rx: None,
+            report_rx: None,
+            share_rx: None,
+            profile_rx: None,
+            db_health: None,
+            capabilities,
+            insecure_jwt_secret: false,
+            notice: None,
+            last_activity: Instant::now(),
+            locked: false,
+            focused: None,
+            accessible,
+            ai_cache: ResponseCache::new(ai_cache_mode, response_cache::cache_dir().ok()),
+            ai_cache_key: None,
         };
         
-        // 6. Add welcome message based on authentication state
-        let is_first_run = !Config::exists();
-        
+        // 7. Add welcome message based on authentication state
         // Welcome message based on auth state
-        let welcome_msg = if is_first_run {
+        let welcome_msg = if options.quiet || !app.config.ui.show_welcome {
+            app.ready_line()
+        } else if is_first_run {
             format!(
                 r#"
 ╔═══════════════════════════════════════════════════════════════════╗
@@ -269,7 +977,27 @@ After logging in, you can:
 
 Type /help for more commands.
 "#,
-                Config::config_path().map(|p| p.display().to_string()).unwrap_or_else(|_| "~/.qhub/config.toml".to_string())
+                app.config.path().map(|p| p.display().to_string()).unwrap_or_else(|_| "~/.qhub/config.toml".to_string())
+            )
+        } else if app.capabilities.is_guest() {
+            // No account backend - chat locally instead of nagging about login
+            let chat_line = if app.capabilities.ai_key {
+                "Ask anything to start - your history is kept on this machine."
+            } else {
+                "Add an AI key with /keys set ai to start chatting."
+            };
+            format!(
+                r#"
+QHub · guest mode
+
+No QHub account service is configured, so you're working locally.
+{}
+
+  /connect     - Set up an AI key or account
+  /keys status - Show configured API keys
+  /help        - Show all commands
+"#,
+                chat_line
             )
         } else if app.user_email.is_none() {
             // Returning user but not logged in
@@ -333,176 +1061,991 @@ Start generating quantum circuits:
   "Generate a Grover search algorithm"
   "Build a quantum Fourier transform"
 "#,
-                app.user_email.as_ref().unwrap(),
+                app.user_email.as_deref().unwrap_or_default(),
                 app.user_tier.to_uppercase()
             )
         };
         
-        app.messages.push(Message::system(welcome_msg));
+        // A resumed session opens instead of the welcome banner
+        let startup = app.startup_session(&options);
+        if !matches!(startup, Ok(Some(_))) {
+            app.welcome = Some(Message::system(welcome_msg));
+            if app.capabilities.is_guest() {
+                app.restore_guest_history();
+            }
+        }
+        if let Some(e) = config_error {
+            app.messages.push(Message::error(format!(
+                "Could not load the config, so QHub is running on defaults: {:#}\n\
+                 Fix it and QHub picks it up; `qhub config validate` checks it without starting QHub.",
+                e
+            )));
+        }
+        app.start_autosave();
+        match startup {
+            Ok(Some((path, session))) => app.resume_session(&path, session),
+            Ok(None) => {}
+            Err(e) => app.messages.push(Message::error(format!("Could not resume session: {:#}", e))),
+        }
+        if app.config.ai.transcript {
+            app.transcript = transcript::transcript_dir().ok().map(Transcript::start);
+        }
+        // Counts left over from earlier sessions
+        app.upload_telemetry(true);
+        if let Some(text) = app.draft.recover() {
+            app.input = text;
+            app.messages.push(Message::system(
+                "Recovered an unsent draft from your last session.".to_string()
+            ));
+        }
 
         app
     }
 
+    /// Recompute capabilities after keys, the database or the config change
+    pub fn refresh_capabilities(&mut self) {
+        self.capabilities = Capabilities::new(
+            self.db.is_some(),
+            self.config.has_remote_api(),
+            self.config.get_ai_api_key().is_some(),
+        );
+
+        // Sessions in the database are signed with JWT_SECRET
+        let insecure = self.db.is_some() && auth::configured_jwt_secret().is_none();
+        if insecure && !self.insecure_jwt_secret {
+            self.messages.push(Message::error(
+                "JWT_SECRET is not set, so sessions are signed with the public default secret and anyone can forge them. \
+                 Generate one with `openssl rand -base64 32` and export JWT_SECRET before sharing this database."
+                    .to_string(),
+            ));
+        }
+        self.insecure_jwt_secret = insecure;
+    }
+
+    /// Reload the guest conversation saved by a previous run
+    fn restore_guest_history(&mut self) {
+        let saved = match history::guest_history_path().and_then(|path| history::load(&path)) {
+            Ok(saved) => saved,
+            Err(e) => {
+                self.messages.push(Message::error(format!("Could not restore chat history: {}", e)));
+                return;
+            }
+        };
+        if saved.is_empty() {
+            return;
+        }
+
+        self.show_restored(&saved);
+        self.messages.push(Message::system(format!(
+            "Restored {} messages from your last guest session. /clear starts over.",
+            saved.len()
+        )));
+        self.conversation_history.extend(saved);
+        self.ensure_system_prompt();
+    }
+
+    /// Keep the system prompt at `conversation_history[0]`, which trimming
+    /// and /clear depend on
+    fn ensure_system_prompt(&mut self) {
+        let prompt = DeepSeekClient::get_system_prompt(&self.config.ai);
+        deepseek::ensure_system_prompt(&mut self.conversation_history, &prompt);
+    }
+
+    fn show_restored(&mut self, saved: &[ChatMessage]) {
+        for message in saved {
+            self.messages.push(match message.role.as_str() {
+                "user" => Message::user(message.content.clone()),
+                _ => Message::assistant(message.content.clone()),
+            });
+        }
+    }
+
+    /// What stands in for the welcome with ui.show_welcome off or --quiet
+    fn ready_line(&self) -> String {
+        if self.capabilities.is_guest() {
+            "QHub ready · guest mode · /help for commands".to_string()
+        } else if let Some(email) = &self.user_email {
+            format!("QHub ready · {} ({}) · /help for commands", email, self.user_tier.to_uppercase())
+        } else {
+            "QHub ready · /login <email> <password> to sign in · /help for commands".to_string()
+        }
+    }
+
+    /// The saved session to open on startup, if --session, --resume or
+    /// ui.autoresume asks for one. Autoresume with nothing saved yet just
+    /// starts fresh.
+    fn startup_session(&self, options: &AppOptions) -> Result<Option<(PathBuf, Session)>> {
+        let path = if let Some(id) = &options.session {
+            history::find_session(&history::sessions_dir(self.user_email.as_deref())?, id)?
+        } else if options.resume || (self.config.ui.autoresume && !options.new_session) {
+            match history::latest_session(&history::sessions_dir(self.user_email.as_deref())?)? {
+                Some(path) => path,
+                None if options.resume && !self.config.ui.autosave_sessions => anyhow::bail!(
+                    "no saved sessions. Set autosave_sessions = true under [ui] in config.toml."
+                ),
+                None if options.resume => anyhow::bail!("no saved sessions yet"),
+                None => return Ok(None),
+            }
+        } else {
+            return Ok(None);
+        };
+        let session = history::load_session(&path)?;
+        Ok(Some((path, session)))
+    }
+
+    /// Replace the conversation with a saved session, keeping the system prompt
+    fn resume_session(&mut self, path: &Path, session: Session) {
+        if self.resume_path.as_deref() == Some(path) {
+            self.resume_path = None;
+        }
+        self.conversation_history.retain(|m| m.role == "system");
+        self.messages.clear();
+        self.show_restored(&session.messages);
+        self.messages.push(Message::system(format!(
+            "Resumed {} messages from {}.",
+            session.messages.len(),
+            path.file_stem().and_then(|s| s.to_str()).unwrap_or("the last session")
+        )));
+        if let Some(warning) = session.mismatch(&self.config.ai) {
+            self.messages.push(Message::system(format!("⚠️  {}", warning)));
+        }
+        self.conversation_history.extend(session.messages);
+        self.ensure_system_prompt();
+        self.autosave_session();
+    }
+
+    /// Pick this run's autosave file and offer the previous session
+    fn start_autosave(&mut self) {
+        if !self.config.ui.autosave_sessions {
+            return;
+        }
+        let Ok(dir) = history::sessions_dir(self.user_email.as_deref()) else {
+            return;
+        };
+        self.resume_path = history::latest_session(&dir).ok().flatten();
+        let _ = history::prune_sessions(&dir);
+        self.session_path = Some(history::new_session_path(&dir));
+
+        let has_conversation = self.conversation_history.iter().any(|m| m.role != "system");
+        if !has_conversation && self.resume_path.is_some() {
+            self.messages.push(Message::system("Resume last session? /resume".to_string()));
+        }
+    }
+
+    /// Rewrite this run's session file; called after every reply and on exit
+    fn autosave_session(&mut self) {
+        let Some(path) = &self.session_path else {
+            return;
+        };
+        if !self.conversation_history.iter().any(|m| m.role != "system") {
+            return;
+        }
+        if let Err(e) = history::save_session(path, &self.conversation_history, &self.config.ai) {
+            self.messages.push(Message::error(format!("Could not autosave session: {}", e)));
+        }
+    }
+
+    fn save_guest_history(&mut self) {
+        if !self.capabilities.is_guest() {
+            return;
+        }
+        if let Err(e) = history::guest_history_path().and_then(|path| history::save(&path, &self.conversation_history)) {
+            self.messages.push(Message::error(format!("Could not save chat history: {}", e)));
+        }
+    }
+
     pub fn submit_input(&mut self) {
         let input = self.input.trim().to_string();
+
+        // Only logging in (or leaving) gets past the lock screen
+        if self.locked && !input.is_empty() {
+            match SlashCommand::parse(&input) {
+                Some(cmd @ (SlashCommand::Login { .. } | SlashCommand::Quit)) => {
+                    self.input.clear();
+                    let _ = self.draft.clear();
+                    self.handle_slash_command(cmd);
+                }
+                _ => self.messages.push(Message::error(
+                    "Session locked. Log in with /login <email> <password> to continue.".to_string()
+                )),
+            }
+            return;
+        }
+
+        // Masked key entry started by /keys set
+        if let Some(kind) = self.pending_key.take() {
+            self.input.clear();
+            self.handle_key_entry(kind, &input);
+            self.scroll_to_bottom();
+            return;
+        }
+
+        // The answer to the overwrite question /save -f asked
+        if let Some((path, code)) = self.pending_save.take() {
+            self.input.clear();
+            if matches!(input.to_lowercase().as_str(), "y" | "yes") {
+                self.write_code(&path, &code);
+            } else {
+                self.messages.push(Message::system(format!("Not saved; {} is unchanged.", path.display())));
+            }
+            self.scroll_to_bottom();
+            return;
+        }
+
+        // The setup wizard owns the input box while it runs (empty answers skip optional steps)
+        if self.wizard.is_some() && !self.is_loading {
+            // Untrimmed: the wizard trims everything but secrets itself
+            let raw = std::mem::take(&mut self.input);
+            self.handle_wizard_input(&raw);
+            self.scroll_to_bottom();
+            return;
+        }
+
+        // A plain prompt while another waits out a rate limit
+        if self.ai_queue.held().is_some() && !input.is_empty() && SlashCommand::parse(&input).is_none() {
+            match self.config.ai.rate_limit_queue {
+                QueuePolicy::Reject => self.messages.push(Message::error(
+                    "A request is waiting for the rate limit to clear. Press Esc to cancel it, \
+                     or send this once it is answered.".to_string()
+                )),
+                QueuePolicy::Queue => {
+                    self.remember_input(&input);
+                    self.ai_queue.push_followup(input);
+                    self.messages.push(Message::system(
+                        "Queued - this is sent once the waiting request is answered.".to_string()
+                    ));
+                    self.input.clear();
+                    let _ = self.draft.clear();
+                }
+            }
+            self.scroll_to_bottom();
+            return;
+        }
+
+        // Enter on an empty line sends a failed prompt again
+        if input.is_empty() && !self.is_loading && self.retry_prompt.is_some() {
+            self.retry();
+            self.scroll_to_bottom();
+            return;
+        }
+
+        // The one command that works while a request runs; the prompt it
+        // gives back takes the input line
+        if self.is_loading && matches!(SlashCommand::parse(&input), Some(SlashCommand::Cancel)) {
+            self.input.clear();
+            self.input_cursor = None;
+            let _ = self.draft.clear();
+            self.cancel_request();
+            return;
+        }
+
         if input.is_empty() || self.is_loading {
             return;
         }
 
+        self.remember_input(&input);
         // Check for slash commands
         if let Some(cmd) = SlashCommand::parse(&input) {
             self.handle_slash_command(cmd);
         } else {
-            // Regular message to AI - require authentication
-            if self.user_email.is_none() {
-                self.messages.push(Message::error(
-                    "⚠️  Authentication required. Please /login or /register first.".to_string()
-                ));
-                return;
-            }
-            
-            self.messages.push(Message::user(input.clone()));
-            
-            // Add to conversation history
-            self.conversation_history.push(ChatMessage {
-                role: "user".to_string(),
-                content: input.clone(),
-            });
-            
-            // Keep conversation history manageable (last 20 messages + system prompt)
-            // This prevents token overflow and keeps context relevant
-            if self.conversation_history.len() > 21 {
-                // Keep system prompt (first message) and last 20 messages
-                let system_prompt = self.conversation_history[0].clone();
-                let recent_messages: Vec<_> = self.conversation_history
-                    .iter()
-                    .skip(self.conversation_history.len() - 20)
-                    .cloned()
-                    .collect();
-                
-                self.conversation_history = vec![system_prompt];
-                self.conversation_history.extend(recent_messages);
-            }
-            
-            // Start async AI request
-            self.is_loading = true;
-            let (tx, rx) = mpsc::channel(1);
-            self.ai_response_rx = Some(rx);
-            
-            let client = self.ai_client.clone();
-            let history = self.conversation_history.clone();
-            
-            tokio::spawn(async move {
-                let result = client.chat(history).await;
-                let _ = tx.send(result.map_err(|e| e.to_string())).await;
-            });
+            self.send_chat(input);
         }
 
         self.input.clear();
+        self.input_cursor = None;
+        let _ = self.draft.clear();
         self.scroll_to_bottom();
     }
     
-    pub fn check_ai_response(&mut self) {
-        if let Some(ref mut rx) = self.ai_response_rx {
-            match rx.try_recv() {
-                Ok(Ok(response)) => {
-                    self.conversation_history.push(ChatMessage {
-                        role: "assistant".to_string(),
-                        content: response.clone(),
-                    });
-                    self.messages.push(Message::assistant(response));
-                    self.is_loading = false;
-                    self.ai_response_rx = None;
-                    self.scroll_to_bottom();
-                }
-                Ok(Err(error)) => {
-                    // User-friendly error messages
-                    let friendly_error = if error.contains("timeout") {
-                        "Request timed out. The AI service might be busy. Please try again.".to_string()
-                    } else if error.contains("429") {
-                        "Rate limit reached. Please wait a moment before trying again.".to_string()
-                    } else if error.contains("401") || error.contains("403") {
-                        "Authentication failed. Please check your API key in CLOUDFLARE_AI_TOKEN environment variable.".to_string()
-                    } else if error.contains("network") || error.contains("connection") {
-                        "Network error. Please check your internet connection.".to_string()
-                    } else {
-                        format!("AI service error: {}", error)
-                    };
-                    
-                    self.messages.push(Message::error(friendly_error));
-                    self.is_loading = false;
-                    self.ai_response_rx = None;
-                    self.scroll_to_bottom();
-                }
-                Err(mpsc::error::TryRecvError::Empty) => {
-                    // Still waiting
-                }
-                Err(mpsc::error::TryRecvError::Disconnected) => {
-                    self.messages.push(Message::error(
-                        "AI request failed unexpectedly. Please try again.".to_string()
-                    ));
-                    self.is_loading = false;
-                    self.ai_response_rx = None;
-                }
-            }
+    /// Add a submitted input to the Ctrl+R history
+    fn remember_input(&mut self, input: &str) {
+        if let Err(e) = self.input_history.record(input) {
+            diagnostics::debug(format!("Input history not saved: {}", e));
         }
     }
 
-    pub fn check_auth_response(&mut self) {
-        if let Some(ref mut rx) = self.auth_response_rx {
-            match rx.try_recv() {
-                Ok(Ok((token, email, tier))) => {
-                    // Save token to API client
-                    self.api_client.set_token(token.clone());
-                    
-                    // Save to config
-                    self.config.user = Some(crate::config::settings::UserConfig {
-                        email: email.clone(),
-                        token: Some(token),
-                        tier: tier.clone(),
-                    });
-                    
-                    if let Err(e) = self.config.save() {
-                        self.messages.push(Message::error(
-                            format!("Failed to save config: {}", e)
-                        ));
-                    } else {
-                        self.user_email = Some(email.clone());
-                        self.user_tier = tier.clone();
-                        self.messages.push(Message::system(
-                            format!("✓ Logged in successfully as {} ({})", email, tier)
-                        ));
-                    }
-                    
-                    self.is_loading = false;
-                    self.auth_response_rx = None;
-                    self.scroll_to_bottom();
-                }
-                Ok(Err(error)) => {
-                    let friendly_error = if error.contains("already registered") {
-                        "Email is already registered. Try logging in instead.".to_string()
-                    } else if error.contains("Invalid email or password") {
-                        "Invalid email or password. Please try again.".to_string()
-                    } else if error.contains("Invalid email format") {
-                        "Invalid email format. Please use a valid email address.".to_string()
-                    } else if error.contains("deactivated") {
-                        "Account is deactivated. Contact support for assistance.".to_string()
-                    } else {
-                        format!("Authentication error: {}", error)
-                    };
-                    
-                    self.messages.push(Message::error(friendly_error));
-                    self.is_loading = false;
-                    self.auth_response_rx = None;
-                    self.scroll_to_bottom();
-                }
-                Err(mpsc::error::TryRecvError::Empty) => {
-                    // Still waiting
-                }
-                Err(mpsc::error::TryRecvError::Disconnected) => {
+    /// Send a message to the AI, as typed or built by a command
+    fn send_chat(&mut self, text: String) {
+        if let Some(reason) = self.chat_blocked() {
+            self.messages.push(Message::error(reason));
+            return;
+        }
+        
+        self.messages.push(Message::user(text.clone()));
+        self.stats.messages_sent += 1;
+        self.telemetry.command("chat");
+        self.send_prompt(text);
+    }
+
+    /// Why a chat request can't be sent now. Guests need a key, everyone
+    /// else a login; accounts are limited to their tier's models on the
+    /// hosted gateway.
+    fn chat_blocked(&self) -> Option<String> {
+        if !self.capabilities.can_chat(self.is_authenticated()) {
+            return Some(self.capabilities.chat_blocked_reason().to_string());
+        }
+        self.model_restriction(self.active_model())
+    }
+
+    /// Why the account's tier may not use `model`; only the hosted gateway
+    /// is limited
+    fn model_restriction(&self, model: &str) -> Option<String> {
+        if !self.is_authenticated() || self.config.ai.base_url != deepseek::DEFAULT_BASE_URL {
+            return None;
+        }
+        let restricted = tiers::limits(&self.user_tier).check_model(model).err()?;
+        Some(format!("⚠️  {}", restricted))
+    }
+
+    /// The model requests go to: /model's choice, else the configured one
+    fn active_model(&self) -> &str {
+        self.session_sampling.model.as_deref().unwrap_or(&self.config.ai.model)
+    }
+
+    /// Add `text` to the conversation and request a reply; the user message
+    /// is already on screen
+    fn send_prompt(&mut self, text: String) {
+        self.retry_prompt = None;
+        self.ensure_system_prompt();
+        self.conversation_history.push(ChatMessage {
+            role: "user".to_string(),
+            content: text,
+        });
+        
+        // Drop the oldest messages once the estimated tokens exceed the
+        // model's context, keeping the system prompt
+        let budget = self.context_budget();
+        tokens::trim_to_budget(&mut self.conversation_history, budget);
+
+        // Keep the backend summary current while it is being sent
+        let now = Instant::now();
+        let inject = self.config.ai.inject_backend_context;
+        if inject && self.backend_context.needs_refresh(now) {
+            self.fetch_backends(false);
+        }
+        self.backend_context.apply(&mut self.conversation_history, inject, now);
+        self.dispatch_ai_request();
+    }
+
+    /// /retry: send the prompt that got no reply again
+    fn retry(&mut self) {
+        if self.is_loading {
+            self.messages.push(Message::error("Wait for the current reply first.".to_string()));
+            return;
+        }
+        let Some(prompt) = self.retry_prompt.take() else {
+            self.messages.push(Message::error("Nothing to retry.".to_string()));
+            return;
+        };
+        if let Some(reason) = self.chat_blocked() {
+            self.messages.push(Message::error(reason));
+            self.retry_prompt = Some(prompt);
+            return;
+        }
+        self.telemetry.command("retry");
+        self.send_prompt(prompt);
+    }
+
+    /// /continue: ask for the rest of a truncated reply. The instruction is
+    /// only in the conversation while the request is in flight.
+    fn continue_reply(&mut self) {
+        if self.is_loading {
+            self.messages.push(Message::error("Wait for the current reply first.".to_string()));
+            return;
+        }
+        let last_reply = self.messages.iter().rev().find(|m| m.role == MessageRole::Assistant);
+        let Some(id) = last_reply.filter(|m| m.truncated).map(|m| m.id) else {
+            self.messages.push(Message::error("Nothing to continue: the last reply was complete.".to_string()));
+            return;
+        };
+        if self.conversation_history.last().is_none_or(|m| m.role != "assistant") {
+            self.messages.push(Message::error(
+                "The truncated reply is no longer the end of the conversation.".to_string()
+            ));
+            return;
+        }
+        if let Some(reason) = self.chat_blocked() {
+            self.messages.push(Message::error(reason));
+            return;
+        }
+        self.telemetry.command("continue");
+        self.continuing = Some(id);
+        self.conversation_history.push(ChatMessage {
+            role: "user".to_string(),
+            content: continuation::INSTRUCTION.to_string(),
+        });
+        self.dispatch_ai_request();
+    }
+
+    /// /regen: ask for the last reply again, hotter and optionally from
+    /// another model. The old reply leaves the conversation while the new
+    /// one is fetched and is kept as an alternative.
+    fn regen(&mut self, model: Option<String>) {
+        if self.is_loading {
+            self.messages.push(Message::error("Wait for the current reply first.".to_string()));
+            return;
+        }
+        let index = self.messages.iter().rposition(|m| m.role == MessageRole::Assistant);
+        let Some(index) = index.filter(|_| self.conversation_history.last().is_some_and(|m| m.role == "assistant")) else {
+            self.messages.push(Message::error("Nothing to regenerate: the conversation does not end with a reply.".to_string()));
+            return;
+        };
+        if let Some(reason) = self.chat_blocked() {
+            self.messages.push(Message::error(reason));
+            return;
+        }
+        if let Some(reason) = model.as_deref().and_then(|model| self.model_restriction(model)) {
+            self.messages.push(Message::error(reason));
+            return;
+        }
+        self.telemetry.command("regen");
+        let base = self.session_sampling.temperature.unwrap_or_else(|| self.ai_client.temperature());
+        let temperature = (base + REGEN_TEMPERATURE_BOOST).min(MAX_TEMPERATURE);
+        self.conversation_history.pop();
+        let previous = self.messages.remove(index);
+        let sampling = Sampling { model, temperature: Some(temperature), ..Sampling::default() };
+        self.regenerating = Some((previous, sampling));
+        self.dispatch_ai_request();
+    }
+
+    /// /regen prev: show the attempt before the current one, in the
+    /// conversation too so the next prompt builds on what is on screen
+    fn regen_previous(&mut self) {
+        if self.is_loading {
+            self.messages.push(Message::error("Wait for the current reply first.".to_string()));
+            return;
+        }
+        let last_reply = self.messages.iter_mut().rev().find(|m| m.role == MessageRole::Assistant);
+        let Some(message) = last_reply.filter(|m| !m.alternatives.is_empty()) else {
+            self.messages.push(Message::error("No earlier answer to go back to; /regen asks for another.".to_string()));
+            return;
+        };
+        let Some(entry) = self.conversation_history.last_mut().filter(|m| m.role == "assistant") else {
+            self.messages.push(Message::error("That reply is no longer the end of the conversation.".to_string()));
+            return;
+        };
+        message.show_previous();
+        entry.content = message.content.clone();
+        let count = message.alternatives.len() + 1;
+        self.post_notice(format!("Showing another of {} answers; /regen prev cycles", count));
+        self.save_guest_history();
+        self.autosave_session();
+    }
+
+    /// /set: change a generation parameter for the rest of the session, or
+    /// show them all
+    fn set_parameter(&mut self, setting: Option<(String, String)>) {
+        let Some((name, value)) = setting else {
+            self.messages.push(Message::system(format!(
+                "Generation: {}\nChange one with /set <{}> <value | default>",
+                self.generation_summary(),
+                deepseek::PARAMETERS.join(" | ")
+            )));
+            return;
+        };
+        match self.session_sampling.set(&name, &value) {
+            Ok(()) => {
+                self.telemetry.command("set");
+                self.post_notice(format!("Generation: {}", self.generation_summary()));
+            }
+            Err(e) => self.messages.push(Message::error(e)),
+        }
+    }
+
+    /// /model: show the model in use, or switch to another for this session
+    fn set_model(&mut self, name: Option<String>) {
+        let Some(name) = name else {
+            let origin = if self.session_sampling.model.is_some() { "this session" } else { "from config" };
+            self.messages.push(Message::system(format!(
+                "Model: {} ({})\n/model <name> switches for this session; /models lists the available ones.",
+                self.active_model(),
+                origin
+            )));
+            return;
+        };
+        if name == "default" {
+            self.session_sampling.model = None;
+            self.post_notice(format!("Model: {} (from config)", self.config.ai.model));
+            return;
+        }
+        let model = match (self.model_catalog.find(&name), &self.model_catalog) {
+            (Some(listed), _) => listed.to_string(),
+            (None, Catalog::Provider(_)) => {
+                self.messages.push(Message::error(format!(
+                    "{} is not among the provider's models. /models lists them; /models refresh updates the list.",
+                    name
+                )));
+                return;
+            }
+            // The built-in list only knows common models
+            (None, Catalog::Builtin) => {
+                self.messages.push(Message::system(format!(
+                    "{} is not in the built-in model list; using it anyway. /models refresh checks with the provider.",
+                    name
+                )));
+                name
+            }
+        };
+        if let Some(reason) = self.model_restriction(&model) {
+            self.messages.push(Message::error(reason));
+            return;
+        }
+        self.post_notice(format!("Model: {} for this session", model));
+        self.session_sampling.model = Some(model);
+    }
+
+    /// /models: the models /model accepts
+    fn show_models(&mut self) {
+        let models = self.model_catalog.models();
+        let mut text = format!("Models ({}):", self.model_catalog.describe());
+        for model in models.iter().take(MODELS_SHOWN) {
+            let marker = if models::same_model(model, self.active_model()) { "●" } else { " " };
+            text.push_str(&format!("\n  {} {}", marker, model));
+        }
+        if models.len() > MODELS_SHOWN {
+            text.push_str(&format!("\n  … {} more; /model <prefix> completes them", models.len() - MODELS_SHOWN));
+        }
+        self.messages.push(Message::system(text));
+    }
+
+    /// /models refresh: fetch the provider's model list in the background
+    fn refresh_models(&mut self) {
+        if self.models_rx.is_some() {
+            return;
+        }
+        self.messages.push(Message::system("Fetching the model list from the provider…".to_string()));
+        let models = self.ai_client.list_models();
+        let (tx, rx) = mpsc::channel(1);
+        self.models_rx = Some(rx);
+        self.tasks.spawn_tracked(Operation::Models, async move {
+            let _ = tx.send(models.await).await;
+        });
+    }
+
+    pub fn check_models_response(&mut self) {
+        let result = match self.models_rx.as_mut().map(|rx| rx.try_recv()) {
+            Some(Ok(result)) => result,
+            Some(Err(mpsc::error::TryRecvError::Empty)) | None => return,
+            Some(Err(mpsc::error::TryRecvError::Disconnected)) => {
+                self.models_rx = None;
+                return;
+            }
+        };
+        self.models_rx = None;
+        match result {
+            Ok(listed) if listed.is_empty() => self.messages.push(Message::system(format!(
+                "The provider listed no models; keeping the {}.",
+                self.model_catalog.describe()
+            ))),
+            Ok(listed) => {
+                let list = ModelList {
+                    base_url: self.config.ai.base_url.clone(),
+                    fetched_at: Utc::now().timestamp(),
+                    models: listed,
+                };
+                if let Err(e) = models::cache_path().and_then(|path| models::save(&path, &list)) {
+                    diagnostics::warn(format!("Could not cache the model list: {}", e));
+                }
+                let count = list.models.len();
+                self.model_catalog = Catalog::Provider(list);
+                self.messages.push(Message::system(format!(
+                    "✓ {} models from the provider. /models lists them; /model <name> switches.",
+                    count
+                )));
+            }
+            Err(e) if e.is::<NoModelsEndpoint>() => self.messages.push(Message::system(format!(
+                "{}; keeping the {}.",
+                e,
+                self.model_catalog.describe()
+            ))),
+            Err(e) => self.messages.push(Message::error(format!(
+                "Could not fetch the model list: {}. Keeping the {}.",
+                e,
+                self.model_catalog.describe()
+            ))),
+        }
+        self.scroll_to_bottom();
+    }
+
+    /// Active generation parameters, marking the ones /set overrides
+    fn generation_summary(&self) -> String {
+        fn show(name: &str, session: Option<String>, configured: Option<String>) -> String {
+            match (session, configured) {
+                (Some(value), _) => format!("{} {} (this session)", name, value),
+                (None, Some(value)) => format!("{} {}", name, value),
+                (None, None) => format!("{} provider default", name),
+            }
+        }
+        let (session, ai) = (&self.session_sampling, &self.config.ai);
+        [
+            show("temperature", session.temperature.map(|v| v.to_string()), ai.temperature.map(|v| v.to_string())),
+            show("top_p", session.top_p.map(|v| v.to_string()), ai.top_p.map(|v| v.to_string())),
+            show("max_tokens", session.max_tokens.map(|v| v.to_string()), Some(ai.max_tokens.to_string())),
+        ]
+        .join(", ")
+    }
+
+    /// Stitch a continuation onto the reply it extends, on screen and in the
+    /// conversation
+    fn extend_reply(&mut self, id: Uuid, continued: String, truncated: bool) {
+        self.conversation_history.pop_if(|m| m.role == "user");
+        if let Some(entry) = self.conversation_history.last_mut().filter(|m| m.role == "assistant") {
+            entry.content = continuation::stitch(&entry.content, &continued);
+        }
+        if let Some(message) = self.messages.iter_mut().find(|m| m.id == id) {
+            message.content = continuation::stitch(&message.content, &continued);
+            message.truncated = truncated;
+        }
+    }
+
+    /// Send the conversation to the AI in the background, or answer it from
+    /// ai.cache when the same request was made before
+    fn dispatch_ai_request(&mut self) {
+        let sampling = match &self.regenerating {
+            Some((_, regen)) => self.session_sampling.and(regen),
+            None => self.session_sampling.clone(),
+        };
+        // /regen asks for a different answer than the one already given
+        self.ai_cache_key = (self.ai_cache.is_enabled() && self.regenerating.is_none())
+            .then(|| self.ai_client.cache_key(&self.conversation_history, &sampling));
+        if let Some(cached) = self.ai_cache_key.as_deref().and_then(|key| self.ai_cache.get(key)) {
+            self.ai_cache_key = None;
+            self.accept_reply(ChatReply { content: cached.content, usage: None, truncated: cached.truncated }, None);
+            return;
+        }
+
+        self.is_loading = true;
+        let (tx, rx) = mpsc::channel(1);
+        self.ai_response_rx = Some(rx);
+        let (progress_tx, progress_rx) = mpsc::channel(4);
+        self.ai_progress_rx = Some(progress_rx);
+        self.ai_progress = None;
+        
+        let request = self.ai_client.chat(self.conversation_history.clone(), sampling);
+
+        self.tasks.spawn_tracked(Operation::AiRequest, async move {
+            let started = Instant::now();
+            tokio::pin!(request);
+            
+            // Heartbeats so a slow reply doesn't look stuck
+            let mut heartbeat = tokio::time::interval_at(
+                tokio::time::Instant::now() + AI_HEARTBEAT,
+                AI_HEARTBEAT,
+            );
+            let result = loop {
+                tokio::select! {
+                    result = &mut request => break result,
+                    _ = heartbeat.tick() => {
+                        let _ = progress_tx.try_send(AiProgress {
+                            elapsed: started.elapsed(),
+                            received_chars: 0,
+                        });
+                    }
+                }
+            };
+            let result = match result {
+                Ok(reply) => Ok((reply, started.elapsed())),
+                Err(e) => Err(match e.downcast::<RateLimited>() {
+                    Ok(limited) => AiFailure::RateLimited(limited),
+                    Err(e) => match e.downcast::<NoReply>() {
+                        Ok(no_reply) => AiFailure::NoReply(no_reply),
+                        Err(e) => AiFailure::Failed(e.to_string()),
+                    },
+                }),
+            };
+            let _ = tx.send(result).await;
+        });
+    }
+
+    pub fn check_ai_response(&mut self) {
+        if let Some(ref mut rx) = self.ai_progress_rx {
+            while let Ok(progress) = rx.try_recv() {
+                self.ai_progress = Some(progress);
+                self.loading_seen = Some(Instant::now());
+            }
+        }
+        
+        if let Some(ref mut rx) = self.ai_response_rx {
+            match rx.try_recv() {
+                Ok(Ok((reply, latency))) => self.accept_reply(reply, Some(latency)),
+                Ok(Err(AiFailure::RateLimited(limited))) => {
+                    self.ai_response_rx = None;
+                    // Held until the window opens; poll_background sends it again
+                    if !self.ai_queue.hold(limited, Instant::now()) {
+                        self.fail_ai_request(
+                            Some("rate_limit"),
+                            "Still rate limited after several retries. Please try again later.".to_string(),
+                        );
+                    }
+                }
+                Ok(Err(AiFailure::NoReply(no_reply))) => {
+                    let (category, explanation) = match no_reply {
+                        NoReply::Empty => ("empty", "The AI returned an empty response.".to_string()),
+                        NoReply::Refused(reason) => ("refused", format!("The AI declined to answer: {}", reason)),
+                    };
+                    self.fail_ai_request(Some(category), explanation);
+                }
+                Ok(Err(AiFailure::Failed(error))) => {
+                    // User-friendly error messages
+                    let (category, friendly_error) = if error.contains("timeout") {
+                        ("timeout", "Request timed out. The AI service might be busy. Please try again.".to_string())
+                    } else if error.contains("429") {
+                        ("rate_limit", "Rate limit reached. Please wait a moment before trying again.".to_string())
+                    } else if error.contains("401") || error.contains("403") {
+                        ("auth", "Authentication failed. Please check your API key in CLOUDFLARE_AI_TOKEN environment variable.".to_string())
+                    } else if error.contains("network") || error.contains("connection") {
+                        ("network", "Network error. Please check your internet connection.".to_string())
+                    } else {
+                        ("other", format!("AI service error: {}", error))
+                    };
+                    self.fail_ai_request(Some(category), friendly_error);
+                }
+                Err(mpsc::error::TryRecvError::Empty) => {
+                    // Still waiting
+                }
+                Err(mpsc::error::TryRecvError::Disconnected) => {
+                    self.fail_ai_request(None, "AI request failed unexpectedly. Please try again.".to_string());
+                }
+            }
+            if self.ai_response_rx.is_none() {
+                self.ai_progress_rx = None;
+                self.ai_progress = None;
+            }
+        }
+    }
+
+    /// End the current request with an error; prompts queued behind it are
+    /// dropped. The prompt leaves the conversation and is kept for /retry.
+    fn fail_ai_request(&mut self, category: Option<&'static str>, error: String) {
+        if let Some(category) = category {
+            self.telemetry.error(category);
+        }
+        let regenerated = self.regenerating.take().map(|(previous, _)| {
+            // Back to how things were before /regen
+            self.conversation_history.push(ChatMessage {
+                role: "assistant".to_string(),
+                content: previous.content.clone(),
+            });
+            self.messages.push(previous);
+        });
+        let prompt = self
+            .conversation_history
+            .pop_if(|m| m.role == "user")
+            .map(|m| m.content);
+        let error = if regenerated.is_some() {
+            format!("{}\nThe previous answer is back; /regen tries again.", error)
+        } else if self.continuing.take().is_some() {
+            // Only the instruction goes; the truncated reply can be continued again
+            format!("{}\nType /continue to try again.", error)
+        } else if prompt.is_some() {
+            self.retry_prompt = prompt;
+            format!("{}\nNothing was added to the conversation. Press Enter or type /retry to send it again.", error)
+        } else {
+            error
+        };
+        self.messages.push(Message::error(error));
+        self.is_loading = false;
+        self.ai_response_rx = None;
+        let dropped = self.ai_queue.cancel();
+        if dropped > 0 {
+            self.messages.push(Message::system(format!("{} queued prompt(s) were not sent.", dropped)));
+        }
+        self.scroll_to_bottom();
+    }
+
+    /// Show a reply and add it to the conversation. `latency` is None for
+    /// one answered from ai.cache, which cost nothing and is not counted.
+    fn accept_reply(&mut self, reply: ChatReply, latency: Option<Duration>) {
+        let truncated = reply.truncated;
+        if let Some(latency) = latency {
+            let tokens = reply.usage.map(|u| u.total_tokens);
+            self.stats.record_reply(&reply.content, latency, tokens);
+            self.telemetry.latency(latency);
+            self.record_token_usage(tokens);
+            if let Some(key) = self.ai_cache_key.take() {
+                self.ai_cache.put(&key, Cached { content: reply.content.clone(), truncated });
+            }
+            self.notify_complete(format!("Reply ready: {}", notify::summary(&reply.content)));
+        }
+        let response = reply.content;
+        if let Some(id) = self.continuing.take() {
+            self.extend_reply(id, response, truncated);
+        } else {
+            self.record_transcript(&response);
+            self.conversation_history.push(ChatMessage {
+                role: "assistant".to_string(),
+                content: response.clone(),
+            });
+            let mut message = Message::assistant(response);
+            message.truncated = truncated;
+            message.cached = latency.is_none();
+            let mut notice = None;
+            if let Some((previous, _)) = self.regenerating.take() {
+                message.alternatives = previous.alternatives;
+                message.alternatives.push(previous.content);
+                let count = message.alternatives.len() + 1;
+                notice = Some(format!("Answer {} of {}; /regen prev shows the last one", count, count));
+            }
+            self.messages.push(message);
+            if let Some(notice) = notice {
+                self.post_notice(notice);
+            }
+        }
+        if truncated {
+            self.messages.push(Message::system(
+                "The reply was cut off at the length limit. Type /continue for the rest.".to_string()
+            ));
+        }
+        self.save_guest_history();
+        self.autosave_session();
+        self.is_loading = false;
+        self.ai_response_rx = None;
+        self.ai_queue.finished();
+        self.scroll_to_bottom();
+        if let Some(next) = self.ai_queue.next_followup() {
+            self.send_chat(next);
+        }
+    }
+
+    /// The request waiting out a rate limit, if any
+    pub fn queued_request(&self) -> Option<Held> {
+        self.ai_queue.held()
+    }
+
+    /// An AI request is being answered or waits out a rate limit
+    pub fn request_in_flight(&self) -> bool {
+        self.ai_response_rx.is_some() || self.ai_queue.held().is_some()
+    }
+
+    /// Esc or /cancel: abort the AI request in flight (and anything queued
+    /// behind it) and give the prompt back for editing
+    pub fn cancel_request(&mut self) {
+        if !self.request_in_flight() {
+            self.messages.push(Message::system("Nothing to cancel - no request is in flight.".to_string()));
+            self.scroll_to_bottom();
+            return;
+        }
+        let waiting = self.ai_queue.held().is_some();
+        let dropped = self.ai_queue.cancel();
+        self.tasks.abort(Operation::AiRequest);
+        self.ai_response_rx = None;
+        self.ai_progress_rx = None;
+        self.ai_progress = None;
+        self.is_loading = false;
+        if let Some((previous, _)) = self.regenerating.take() {
+            self.conversation_history.push(ChatMessage {
+                role: "assistant".to_string(),
+                content: previous.content.clone(),
+            });
+            self.messages.push(previous);
+        } else if self.continuing.take().is_some() {
+            // Only the continue instruction to drop
+            self.conversation_history.pop();
+        } else if self.conversation_history.last().is_some_and(|m| m.role == "user") {
+            if let Some(prompt) = self.conversation_history.pop() {
+                if self.input.is_empty() {
+                    self.input = prompt.content;
+                    self.input_cursor = None;
+                }
+            }
+        }
+        let mut note = if waiting { "Cancelled the queued request." } else { "Request cancelled." }.to_string();
+        if dropped > 0 {
+            note.push_str(&format!(" {} prompt(s) queued behind it were dropped.", dropped));
+        }
+        self.messages.push(Message::system(note));
+        self.scroll_to_bottom();
+    }
+
+    fn record_transcript(&self, response: &str) {
+        let Some(transcript) = &self.transcript else {
+            return;
+        };
+        let prompt = self.conversation_history.iter().rev().find(|m| m.role == "user");
+        if let Some(prompt) = prompt {
+            transcript.record(transcript::Entry::new(self.active_model(), &prompt.content, response));
+        }
+    }
+
+    pub fn check_auth_response(&mut self) {
+        if let Some(ref mut rx) = self.auth_response_rx {
+            match rx.try_recv() {
+                Ok(Ok((token, user))) => {
+                    self.locked = false;
+                    let label = user.name().to_string();
+                    let (email, tier) = (user.email, user.tier);
+                    // Save token to API client
+                    self.api_client.set_token(token.clone());
+                    
+                    // Save to config, next to any other accounts
+                    let switched = self.user_email.as_ref().is_some_and(|current| !current.eq_ignore_ascii_case(&email));
+                    let saved = self.config.add_account(email.clone(), token, tier.clone()).and_then(|_| self.config.save());
+                    if switched {
+                        self.start_account_session(&email);
+                    }
+                    
+                    if let Err(e) = saved {
+                        self.messages.push(Message::error(
+                            format!("Failed to save config: {}", e)
+                        ));
+                    } else {
+                        self.user_email = Some(email.clone());
+                        self.user_name = user.display_name.or(user.username);
+                        self.user_tier = tier.clone();
+                        self.user_last_login = user.last_login_at;
+                        let mut note = format!("✓ Logged in successfully as {} ({})", label, tier);
+                        if self.config.accounts.len() > 1 {
+                            note.push_str(&format!(
+                                "\n{} accounts saved; /accounts lists them and /accounts switch <email> changes",
+                                self.config.accounts.len()
+                            ));
+                        }
+                        self.messages.push(Message::system(note));
+                    }
+                    
+                    if self.wizard.take().is_some() {
+                        self.messages.push(Message::system(
+                            "✓ Setup complete. Ask me to build a quantum circuit!".to_string()
+                        ));
+                    }
+                    
+                    self.is_loading = false;
+                    self.auth_response_rx = None;
+                    self.scroll_to_bottom();
+                }
+                Ok(Err(error)) => {
+                    let friendly_error = if error.contains("already registered") {
+                        "Email is already registered. Try logging in instead.".to_string()
+                    } else if error.contains("Invalid email or password") {
+                        "Invalid email or password. Please try again.".to_string()
+                    } else if error.contains("Invalid email format") {
+                        "Invalid email format. Please use a valid email address.".to_string()
+                    } else if error.contains("deactivated") {
+                        "Account is deactivated. Contact support for assistance.".to_string()
+                    } else {
+                        format!("Authentication error: {}", error)
+                    };
+                    
+                    self.messages.push(Message::error(friendly_error));
+                    if let Some(wizard) = self.wizard.as_mut() {
+                        wizard.auth_failed();
+                        self.messages.push(Message::system(wizard.prompt()));
+                    }
+                    self.is_loading = false;
+                    self.auth_response_rx = None;
+                    self.scroll_to_bottom();
+                }
+                Err(mpsc::error::TryRecvError::Empty) => {
+                    // Still waiting
+                }
+                Err(mpsc::error::TryRecvError::Disconnected) => {
                     self.messages.push(Message::error(
                         "Authentication request failed. Please try again.".to_string()
                     ));
+                    if let Some(wizard) = self.wizard.as_mut() {
+                        wizard.auth_failed();
+                        self.messages.push(Message::system(wizard.prompt()));
+                    }
                     self.is_loading = false;
                     self.auth_response_rx = None;
                 }
@@ -510,76 +2053,1247 @@ Start generating quantum circuits:
         }
     }
 
+    /// The keys and session token /share must not upload, wherever they
+    /// are kept: config file, environment or keychain
+    fn share_secrets(&self) -> Vec<String> {
+        [
+            self.config.get_ai_api_key(),
+            self.config.get_quantum_api_key(),
+            self.config.user().and_then(|u| self.config.session_token(u)),
+            // A key in the environment too, when the file's is the one in use
+            std::env::var(ApiKeyKind::Ai.env_var()).ok(),
+            std::env::var(ApiKeyKind::Quantum.env_var()).ok(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    pub fn check_logout_response(&mut self) {
+        let result = match self.logout_rx.as_mut().map(|rx| rx.try_recv()) {
+            Some(Ok(result)) => result,
+            Some(Err(mpsc::error::TryRecvError::Empty)) | None => return,
+            Some(Err(mpsc::error::TryRecvError::Disconnected)) => {
+                Err("logout task was cancelled".to_string())
+            }
+        };
+        self.logout_rx = None;
+        
+        if let Err(error) = &result {
+            self.messages.push(Message::error(format!(
+                "Server logout failed: {}. Your session may still be active on the server.",
+                error
+            )));
+        }
+        
+        if let Err(e) = self.forget_session() {
+            self.messages.push(Message::error(
+                format!("Failed to save config: {}", e)
+            ));
+        } else {
+            let mut note = if result.is_ok() { "✓ Logged out successfully" } else { "Logged out locally" }.to_string();
+            let others: Vec<&str> = self.config.accounts.iter().map(|a| a.email.as_str()).collect();
+            if !others.is_empty() {
+                note.push_str(&format!(". Still saved: {}; /accounts switch <email> to use one", others.join(", ")));
+            }
+            self.messages.push(Message::system(note));
+        }
+        self.scroll_to_bottom();
+    }
+
+    /// /accounts: the accounts saved on this machine
+    fn list_accounts(&mut self) {
+        if self.config.accounts.is_empty() {
+            self.messages.push(Message::system("No saved accounts. /login <email> <password> adds one.".to_string()));
+            return;
+        }
+        let mut text = "Accounts:".to_string();
+        for account in &self.config.accounts {
+            let marker = if account.active { "●" } else { "○" };
+            let session = if account.has_session() { "" } else { ", logged out" };
+            text.push_str(&format!("\n  {} {} ({}{})", marker, account.email, account.tier, session));
+        }
+        text.push_str("\n/accounts switch <email> changes the active one; /login adds another.");
+        self.messages.push(Message::system(text));
+    }
+
+    /// /accounts switch: make another saved account active once the server
+    /// accepts its session
+    fn switch_account(&mut self, email: String) {
+        if self.account_switch_rx.is_some() || self.auth_response_rx.is_some() {
+            self.messages.push(Message::error("Wait for the current sign-in to finish.".to_string()));
+            return;
+        }
+        let Some(account) = self.config.account(&email) else {
+            self.messages.push(Message::error(format!("No saved account {}. /accounts lists them; /login adds one.", email)));
+            return;
+        };
+        let email = account.email.clone();
+        if self.user_email.as_ref().is_some_and(|current| current.eq_ignore_ascii_case(&email)) {
+            self.post_notice(format!("Already using {}", email));
+            return;
+        }
+        let Some(token) = self.config.session_token(account) else {
+            self.messages.push(Message::error(format!("No session saved for {}. Log in with /login {} <password>.", email, email)));
+            return;
+        };
+        self.messages.push(Message::system(format!("🔄 Switching to {}…", email)));
+
+        let verify = self.auth.verify(token.clone());
+        let (tx, rx) = mpsc::channel(1);
+        self.account_switch_rx = Some(rx);
+        self.tasks.spawn_tracked(Operation::AccountSwitch, async move {
+            let result = verify.await.map(|user| (token, user)).map_err(|e| e.to_string());
+            let _ = tx.send((email, result)).await;
+        });
+    }
+
+    pub fn check_account_switch_response(&mut self) {
+        let (email, result) = match self.account_switch_rx.as_mut().map(|rx| rx.try_recv()) {
+            Some(Ok(response)) => response,
+            Some(Err(mpsc::error::TryRecvError::Empty)) | None => return,
+            Some(Err(mpsc::error::TryRecvError::Disconnected)) => {
+                self.account_switch_rx = None;
+                return;
+            }
+        };
+        self.account_switch_rx = None;
+        let (token, user) = match result {
+            Ok(verified) => verified,
+            Err(error) => {
+                self.messages.push(Message::error(format!(
+                    "Could not switch to {}: {}. Log in again with /login {} <password>.",
+                    email, error, email
+                )));
+                self.scroll_to_bottom();
+                return;
+            }
+        };
+        // Saved again with the tier as the server has it now
+        let saved = self.config.add_account(email.clone(), token.clone(), user.tier.clone()).and_then(|_| self.config.save());
+        if let Err(e) = saved {
+            self.messages.push(Message::error(format!("Failed to save config: {}", e)));
+            return;
+        }
+        self.api_client.set_token(token);
+        self.start_account_session(&email);
+        self.user_email = Some(email);
+        self.user_name = user.display_name.or(user.username);
+        self.user_tier = user.tier;
+        self.user_last_login = user.last_login_at;
+        self.messages.push(Message::system(format!(
+            "✓ Switched to {} ({}). The previous conversation stays saved with its account.",
+            self.user_email.as_deref().unwrap_or_default(),
+            self.user_tier
+        )));
+        self.scroll_to_bottom();
+    }
+
+    /// Another account became active: the conversation so far stays in its
+    /// autosaved session and a new one starts, so one account's chat never
+    /// goes out with the other's requests
+    fn start_account_session(&mut self, email: &str) {
+        self.autosave_session();
+        self.conversation_history.retain(|m| m.role == "system");
+        self.messages.clear();
+        self.welcome = None;
+        self.retry_prompt = None;
+        self.continuing = None;
+        self.regenerating = None;
+        self.selected_message = None;
+        self.scroll = ScrollState::new();
+        if self.session_path.is_some() {
+            let dir = history::sessions_dir(Some(email)).ok();
+            self.resume_path = dir.as_deref().and_then(|dir| history::latest_session(dir).ok().flatten());
+            self.session_path = dir.map(|dir| history::new_session_path(&dir));
+        }
+    }
+
+    /// Clear the active account from memory and config.toml; other saved
+    /// accounts stay
+    fn forget_session(&mut self) -> Result<()> {
+        self.api_client.clear_token();
+        self.config.remove_active_account();
+        self.user_email = None;
+        self.user_name = None;
+        self.user_tier = "free".to_string();
+        self.user_last_login = None;
+        self.config.save()
+    }
+
+    /// A key was pressed
+    pub fn record_activity(&mut self, now: Instant) {
+        self.last_activity = now;
+    }
+
+    /// Log out once `security.idle_timeout_mins` pass without a key press.
+    /// Returns true if the session was locked just now.
+    fn check_idle(&mut self, now: Instant) -> bool {
+        let Some(minutes) = self.config.security.idle_timeout_mins else {
+            return false;
+        };
+        let idle = now.saturating_duration_since(self.last_activity);
+        if self.locked || !self.is_authenticated() || idle < Duration::from_secs(minutes * 60) {
+            return false;
+        }
+        self.lock_for_inactivity();
+        true
+    }
+
+    /// Log out and hide everything the next person at the terminal could read
+    fn lock_for_inactivity(&mut self) {
+        // Invalidate the token server-side too; best effort, like /logout
+        let logout = self.auth.logout(self.api_client.token().map(str::to_string));
+        self.tasks.spawn_critical(async move {
+            let _ = logout.await;
+        });
+
+        // A reply arriving now would only land behind the lock
+        self.ai_response_rx = None;
+        self.ai_progress_rx = None;
+        self.ai_progress = None;
+        self.is_loading = false;
+        self.ai_queue.cancel();
+        self.continuing = None;
+        self.regenerating = None;
+        self.retry_prompt = None;
+
+        self.locked = true;
+        self.messages.clear();
+        self.welcome = None;
+        self.conversation_history.retain(|m| m.role == "system");
+        self.input.clear();
+        let _ = self.draft.clear();
+        // Nothing asked before the lock may be answered from behind it
+        self.pending_key = None;
+        self.pending_save = None;
+        self.pending_edit = None;
+        self.pending_default_backend = None;
+        self.selected_message = None;
+        self.scroll = ScrollState::new();
+        self.telemetry.command("idle_lock");
+        if let Err(e) = self.forget_session() {
+            self.messages.push(Message::error(format!("Failed to save config: {}", e)));
+        }
+    }
+
+    /// Add reported tokens to the user's usage records when a database is connected
+    fn record_token_usage(&mut self, tokens: Option<u64>) {
+        let (Some(db), Some(email), Some(tokens)) = (self.db.clone(), self.user_email.clone(), tokens) else {
+            return;
+        };
+        self.tasks.spawn(async move {
+            // Best effort - the session counters already include these tokens
+            let _ = db
+                .run(|pool| async move { pool.record_usage(&email, AI_TOKENS, tokens as i64).await })
+                .await;
+        });
+    }
+
+    pub fn check_usage_response(&mut self) {
+        if let Some(ref mut rx) = self.usage_rx {
+            match rx.try_recv() {
+                Ok(totals) => {
+                    self.stats.all_time = Some(totals);
+                    self.usage_rx = None;
+                }
+                Err(mpsc::error::TryRecvError::Empty) => {}
+                Err(mpsc::error::TryRecvError::Disconnected) => {
+                    self.usage_rx = None;
+                }
+            }
+        }
+    }
+
+    /// Read this month's usage again when it is due. Quotas are only known
+    /// with a direct database connection and a logged-in account.
+    fn check_quota(&mut self, now: Instant) {
+        if self.quota_email != self.user_email {
+            self.quota_email = self.user_email.clone();
+            self.quota_rx = None;
+            self.quota_checked = None;
+            self.quota_banner = None;
+            self.quota_dismissed = None;
+        }
+        let (Some(db), Some(email)) = (self.db.clone(), self.user_email.clone()) else {
+            return;
+        };
+        if self.quota_rx.is_some() || self.quota_checked.is_some_and(|at| now.duration_since(at) < quota::CHECK_EVERY) {
+            return;
+        }
+        self.quota_checked = Some(now);
+        let (tx, rx) = mpsc::channel(1);
+        self.quota_rx = Some(rx);
+        let since = quota::month_start(Utc::now());
+        self.tasks.spawn(async move {
+            match db.run(|pool| async move { pool.usage_since(&email, since).await }).await {
+                Ok(usage) => {
+                    let _ = tx.send(usage).await;
+                }
+                Err(e) => diagnostics::debug(format!("Quota check failed: {}", e)),
+            }
+        });
+    }
+
+    fn check_quota_response(&mut self) {
+        if let Some(ref mut rx) = self.quota_rx {
+            match rx.try_recv() {
+                Ok(usage) => {
+                    self.quota_banner = quota::banner(usage, &tiers::limits(&self.user_tier));
+                    // A new month (or an upgrade) starts over
+                    if self.quota_banner.is_none() {
+                        self.quota_dismissed = None;
+                    }
+                    self.quota_rx = None;
+                }
+                Err(mpsc::error::TryRecvError::Empty) => {}
+                Err(mpsc::error::TryRecvError::Disconnected) => {
+                    self.quota_rx = None;
+                }
+            }
+        }
+    }
+
+    /// The quota banner, unless Esc hid it at this level
+    pub fn quota_banner(&self) -> Option<&quota::Banner> {
+        self.quota_banner.as_ref().filter(|b| self.quota_dismissed.is_none_or(|hidden| b.level > hidden))
+    }
+
+    /// Esc on the quota banner; false when none is showing
+    pub fn dismiss_quota_banner(&mut self) -> bool {
+        let Some(level) = self.quota_banner().map(|b| b.level) else {
+            return false;
+        };
+        self.quota_dismissed = Some(level);
+        true
+    }
+
+    /// List the account's IBM Quantum backends in the background. `announce`
+    /// shows the result in the chat; quiet refreshes only update the AI context.
+    fn fetch_backends(&mut self, announce: bool) {
+        if self.backends_rx.is_some() {
+            return;
+        }
+        let Some(key) = self.config.get_quantum_api_key() else {
+            return;
+        };
+        self.backend_context.mark_attempt(Instant::now());
+        let (tx, rx) = mpsc::channel(1);
+        self.backends_rx = Some(rx);
+        let client = IbmQuantumClient::new(key, &self.config.network);
+        self.tasks.spawn_tracked(Operation::Backends, async move {
+            let result = client.backends().await.map_err(|e| quantum_error_message(&e));
+            let _ = tx.send((announce, result)).await;
+        });
+    }
+
+    pub fn check_backends_response(&mut self) {
+        if let Some(ref mut rx) = self.backends_rx {
+            match rx.try_recv() {
+                Ok((announce, Ok(backends))) => {
+                    self.backend_context.update(backends, Instant::now());
+                    self.backends_rx = None;
+                    if announce {
+                        self.show_backends();
+                    }
+                    if let Some(name) = self.pending_default_backend.take() {
+                        self.set_default_backend(name);
+                    }
+                }
+                Ok((announce, Err(error))) => {
+                    self.backends_rx = None;
+                    if let Some(name) = self.pending_default_backend.take() {
+                        self.messages.push(Message::error(format!("Could not check {}: {}", name, error)));
+                        self.scroll_to_bottom();
+                    }
+                    if announce {
+                        self.messages.push(Message::error(format!("Could not list backends: {}", error)));
+                        self.scroll_to_bottom();
+                    }
+                }
+                Err(mpsc::error::TryRecvError::Empty) => {}
+                Err(mpsc::error::TryRecvError::Disconnected) => {
+                    self.backends_rx = None;
+                }
+            }
+        }
+    }
+
+    pub fn check_backend_info_response(&mut self) {
+        if let Some(ref mut rx) = self.backend_info_rx {
+            match rx.try_recv() {
+                Ok((name, result)) => {
+                    self.backend_info_rx = None;
+                    match result {
+                        Ok(info) => {
+                            self.messages.push(Message::system(info.report()));
+                            self.backend_info_cache.insert(name, (Instant::now(), info));
+                        }
+                        Err(error) => self.messages.push(Message::error(error)),
+                    }
+                    self.scroll_to_bottom();
+                }
+                Err(mpsc::error::TryRecvError::Empty) => {}
+                Err(mpsc::error::TryRecvError::Disconnected) => {
+                    self.backend_info_rx = None;
+                }
+            }
+        }
+    }
+
+    pub fn check_report_response(&mut self) {
+        if let Some(ref mut rx) = self.report_rx {
+            match rx.try_recv() {
+                Ok(result) => {
+                    self.messages.push(match result {
+                        Ok(text) => Message::system(text),
+                        Err(error) => Message::error(error),
+                    });
+                    self.report_rx = None;
+                    self.scroll_to_bottom();
+                }
+                Err(mpsc::error::TryRecvError::Empty) => {}
+                Err(mpsc::error::TryRecvError::Disconnected) => {
+                    self.report_rx = None;
+                }
+            }
+        }
+    }
+
+    pub fn check_share_response(&mut self) {
+        if let Some(ref mut rx) = self.share_rx {
+            match rx.try_recv() {
+                Ok(result) => {
+                    self.share_rx = None;
+                    match result {
+                        Ok((url, note)) => {
+                            let copied = if clipboard::copy(&url).is_ok() { " (copied to clipboard)" } else { "" };
+                            self.messages.push(Message::system(format!("🔗 {}{}\n{}", url, copied, note)));
+                        }
+                        Err(error) => self.messages.push(Message::error(error)),
+                    }
+                    self.scroll_to_bottom();
+                }
+                Err(mpsc::error::TryRecvError::Empty) => {}
+                Err(mpsc::error::TryRecvError::Disconnected) => {
+                    self.share_rx = None;
+                }
+            }
+        }
+    }
+
+    pub fn check_profile_response(&mut self) {
+        if let Some(ref mut rx) = self.profile_rx {
+            match rx.try_recv() {
+                Ok(result) => {
+                    self.profile_rx = None;
+                    match result {
+                        Ok(user) => {
+                            self.messages.push(Message::system(format!("✓ Display name set to {}", user.name())));
+                            self.user_name = user.display_name.or(user.username);
+                        }
+                        Err(error) => self.messages.push(Message::error(error)),
+                    }
+                    self.scroll_to_bottom();
+                }
+                Err(mpsc::error::TryRecvError::Empty) => {}
+                Err(mpsc::error::TryRecvError::Disconnected) => {
+                    self.profile_rx = None;
+                }
+            }
+        }
+    }
+
+    /// /profile set display-name: validated here first so typos don't need a round trip
+    fn set_display_name(&mut self, name: String) {
+        if !self.is_authenticated() {
+            self.messages.push(Message::error("/profile needs an account: /login or /register first.".to_string()));
+            return;
+        }
+        let display_name = match auth::validate_display_name(&name) {
+            Ok(name) => name,
+            Err(e) => {
+                self.messages.push(Message::error(e.to_string()));
+                return;
+            }
+        };
+        let (tx, rx) = mpsc::channel(1);
+        self.profile_rx = Some(rx);
+        let api_client = self.api_client.clone();
+        self.tasks.spawn_tracked(Operation::Profile, async move {
+            let result = match api_client.update_profile(&UpdateProfileRequest { display_name }).await {
+                Ok(user) => Ok(user),
+                Err(ApiError::Unauthorized(_)) => Err("Your session has expired: /login again.".to_string()),
+                Err(e) => Err(format!("Could not update your profile: {}", e)),
+            };
+            let _ = tx.send(result).await;
+        });
+    }
+
+    /// /share: upload the conversation, or one code block, with secrets removed
+    fn share(&mut self, options: ShareOptions) {
+        if !self.is_authenticated() {
+            self.messages.push(Message::error(
+                "/share uploads through your QHub account: /login or /register first.".to_string(),
+            ));
+            return;
+        }
+        if self.share_rx.is_some() {
+            self.messages.push(Message::system("Already sharing - one moment.".to_string()));
+            return;
+        }
+        let code = if options.code_only {
+            // The selected reply's main block, else the newest block
+            let selected = self
+                .selected_message
+                .and_then(|i| self.messages.get(i))
+                .filter(|m| m.role == MessageRole::Assistant)
+                .and_then(|m| clipboard::primary_code_block(&m.content));
+            let Some(code) = selected.or_else(|| self.last_code()) else {
+                self.messages.push(Message::error("No code block to share yet.".to_string()));
+                return;
+            };
+            Some(code)
+        } else if !self.conversation_history.iter().any(|m| m.role != "system") {
+            self.messages.push(Message::error("Nothing to share yet.".to_string()));
+            return;
+        } else {
+            None
+        };
+
+        let (request, redacted) =
+            share::request(&options, &self.conversation_history, code.as_deref(), &self.share_secrets());
+        let mut note = format!("Shared {} ({}).", if code.is_some() { "code block" } else { "conversation" }, options.describe());
+        if redacted > 0 {
+            note.push_str(&format!(" {} secret(s) were replaced with {} before upload.", redacted, share::REDACTED));
+        }
+
+        let (tx, rx) = mpsc::channel(1);
+        self.share_rx = Some(rx);
+        let api_client = self.api_client.clone();
+        let api_url = self.config.api_url.clone();
+        self.tasks.spawn_tracked(Operation::Share, async move {
+            let result = match api_client.share(&request).await {
+                Ok(response) => Ok((response.url, note)),
+                Err(ApiError::Network(_)) => Err(format!(
+                    "Could not reach {} to share. Check your connection and try again.",
+                    api_url
+                )),
+                Err(ApiError::Unauthorized(_)) => Err("Your session has expired: /login again to share.".to_string()),
+                Err(e) => Err(format!("Share failed: {}", e)),
+            };
+            let _ = tx.send(result).await;
+        });
+    }
+
+    /// Calibration details for one backend, from the cache while fresh
+    fn backend_info(&mut self, name: String) {
+        if let Some((fetched, info)) = self.backend_info_cache.get(&name) {
+            if fetched.elapsed() < BACKEND_INFO_TTL {
+                let age = fetched.elapsed().as_secs() / 60;
+                let note = if age == 0 { String::new() } else { format!("\n(fetched {} min ago)", age) };
+                self.messages.push(Message::system(format!("{}{}", info.report(), note)));
+                return;
+            }
+        }
+        let Some(key) = self.config.get_quantum_api_key() else {
+            self.messages.push(Message::error(
+                "No IBM Quantum token set. Add one with /keys set quantum.".to_string(),
+            ));
+            return;
+        };
+        if self.backend_info_rx.is_some() {
+            self.messages.push(Message::error("Still fetching backend details, try again in a moment.".to_string()));
+            return;
+        }
+        self.messages.push(Message::system(format!("Fetching calibration data for {}…", name)));
+        let client = IbmQuantumClient::new(key, &self.config.network);
+        let (tx, rx) = mpsc::channel(1);
+        self.backend_info_rx = Some(rx);
+        self.tasks.spawn_tracked(Operation::BackendInfo, async move {
+            let result = client.backend_info(&name).await.map_err(|e| quantum_error_message(&e));
+            let _ = tx.send((name, result)).await;
+        });
+    }
+
+    /// /watch: poll a job until it finishes, keeping one message up to date
+    /// with its queue position and ETA
+    fn watch_job(&mut self, job_id: String) {
+        let Some(key) = self.config.get_quantum_api_key() else {
+            self.messages.push(Message::error(
+                "No IBM Quantum token set. Add one with /keys set quantum.".to_string(),
+            ));
+            return;
+        };
+        if self.job_watches.iter().any(|w| w.job_id == job_id) {
+            self.messages.push(Message::system(format!("Already watching job {}.", job_id)));
+            return;
+        }
+        let message = Message::system(format!("Job {}: checking…", job_id));
+        let (tx, rx) = mpsc::channel(1);
+        self.job_watches.push(JobWatch {
+            job_id: job_id.clone(),
+            message: message.id,
+            started: Instant::now(),
+            tracker: QueueTracker::default(),
+            rx,
+        });
+        self.messages.push(message);
+        let client = IbmQuantumClient::new(key, &self.config.network);
+        // Runs until the job finishes, a check fails or the watch is dropped
+        self.tasks.spawn(async move {
+            loop {
+                let progress = client.job_progress(&job_id).await.map_err(|e| quantum_error_message(&e));
+                let done = progress.as_ref().map_or(true, |p| p.status.is_finished());
+                if tx.send(progress).await.is_err() || done {
+                    break;
+                }
+                tokio::time::sleep(JOB_POLL).await;
+            }
+        });
+    }
+
+    /// Rewrite watched jobs' messages with their latest progress. A watch
+    /// whose message was cleared away stops.
+    fn check_job_watches(&mut self) -> bool {
+        let mut changed = false;
+        let mut finished = Vec::new();
+        let mut watches = std::mem::take(&mut self.job_watches);
+        watches.retain_mut(|watch| loop {
+            match watch.rx.try_recv() {
+                Ok(progress) => {
+                    let done = progress.as_ref().is_ok_and(|p| p.status.is_finished());
+                    let text = watch.update(progress);
+                    changed = true;
+                    if done {
+                        finished.push(text.clone());
+                    }
+                    if !self.update_message(watch.message, text) {
+                        return false;
+                    }
+                }
+                Err(mpsc::error::TryRecvError::Empty) => return true,
+                Err(mpsc::error::TryRecvError::Disconnected) => return false,
+            }
+        });
+        self.job_watches = watches;
+        for text in finished {
+            self.notify_complete(text);
+        }
+        changed
+    }
+
+    /// /cache clear
+    fn clear_reply_cache(&mut self) {
+        match self.ai_cache.clear() {
+            Ok(0) => self.messages.push(Message::system("No cached replies to clear.".to_string())),
+            Ok(count) => self.messages.push(Message::system(format!("Cleared {} cached reply(s).", count))),
+            Err(e) => self.messages.push(Message::error(format!("Could not clear the reply cache: {:#}", e))),
+        }
+    }
+
+    /// Ping about a finished reply or job, as ui.notify_on_complete says
+    fn notify_complete(&mut self, body: String) {
+        for ping in notify::pings(self.config.ui.notify_on_complete, self.focused) {
+            match ping {
+                Ping::Bell => notify::bell(),
+                Ping::Desktop => self.tasks.spawn(notify::desktop("QHub".to_string(), body.clone())),
+            }
+        }
+    }
+
+    /// Replace a message's text in place; false when it is gone
+    fn update_message(&mut self, id: Uuid, content: String) -> bool {
+        let Some(message) = self.messages.iter_mut().find(|m| m.id == id) else {
+            return false;
+        };
+        message.content = content;
+        self.render_cache.invalidate(&id);
+        true
+    }
+
+    /// Recommend a backend for `path`, or the last code block
+    fn recommend(&mut self, path: Option<String>) {
+        let source = match &path {
+            Some(path) => std::fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e)),
+            None => self
+                .last_code()
+                .ok_or_else(|| "No circuit to look at: use /recommend <file.qasm> or ask for one first.".to_string()),
+        };
+        let circuit = source.and_then(|source| qasm::parse(&source).map_err(|e| format!("Not a circuit qhub can read: {}", e)));
+        let circuit = match circuit {
+            Ok(circuit) => circuit,
+            Err(error) => {
+                self.messages.push(Message::error(error));
+                return;
+            }
+        };
+        let requirements = Requirements::of(&circuit);
+        let limits = tiers::limits(&self.user_tier);
+
+        // Nothing to ask IBM Quantum about without hardware access
+        if !limits.hardware {
+            self.messages.push(match select::select_backend(&requirements, &[], &HashMap::new(), &limits) {
+                Ok(recommendation) => Message::system(recommendation.report()),
+                Err(error) => Message::error(error),
+            });
+            return;
+        }
+        let Some(key) = self.config.get_quantum_api_key() else {
+            self.messages.push(Message::error(
+                "No IBM Quantum token set. Add one with /keys set quantum.".to_string(),
+            ));
+            return;
+        };
+        let backends = self.backend_context.backends();
+        let backends = (!backends.is_empty()).then(|| backends.to_vec());
+        self.messages.push(Message::system("Comparing backends for this circuit…".to_string()));
+        let client = IbmQuantumClient::new(key, &self.config.network);
+        let (tx, rx) = mpsc::channel(1);
+        self.report_rx = Some(rx);
+        self.tasks.spawn_tracked(Operation::Report, async move {
+            let result = client
+                .recommend(&requirements, &limits, backends)
+                .await
+                .map(|recommendation| recommendation.report())
+                .map_err(|e| quantum_error_message(&e));
+            let _ = tx.send(result).await;
+        });
+    }
+
+    /// /backend show, or /backend set checked against the backend list,
+    /// fetching it first when /backends has not
+    fn default_backend(&mut self, name: Option<String>) {
+        let Some(name) = name else {
+            let text = match &self.config.quantum.default_backend {
+                Some(backend) => {
+                    format!("Default backend: {}\n/backend set <name> changes it; /backends lists them.", backend)
+                }
+                None => "No default backend: each run picks one for its circuit.\n\
+                         /backend set <name> fixes one; /backends lists them."
+                    .to_string(),
+            };
+            self.messages.push(Message::system(text));
+            return;
+        };
+        if name == "simulator" || !self.backend_context.backends().is_empty() {
+            self.set_default_backend(name);
+            return;
+        }
+        if self.config.get_quantum_api_key().is_none() {
+            self.messages.push(Message::error(
+                "Backends are checked against IBM Quantum: set a token with /keys set quantum, \
+                 or use /backend set simulator"
+                    .to_string(),
+            ));
+            return;
+        }
+        self.messages.push(Message::system(format!("Checking {} against IBM Quantum…", name)));
+        self.pending_default_backend = Some(name);
+        self.fetch_backends(false);
+    }
+
+    /// Save `name` as quantum.default_backend if it is the simulator or a
+    /// listed backend
+    fn set_default_backend(&mut self, name: String) {
+        let listed = self.backend_context.backends().iter().find(|b| b.name == name);
+        if name != "simulator" && listed.is_none() {
+            let names: Vec<&str> = self.backend_context.backends().iter().map(|b| b.name.as_str()).collect();
+            self.messages.push(Message::error(format!(
+                "{} is not one of your backends ({}, or simulator)",
+                name,
+                names.join(", ")
+            )));
+            self.scroll_to_bottom();
+            return;
+        }
+        let offline = listed.is_some_and(|b| !b.operational);
+        self.config.quantum.default_backend = Some(name.clone());
+        if let Err(e) = self.config.save() {
+            self.messages.push(Message::error(format!("Failed to save config: {}", e)));
+            return;
+        }
+        // A project file or variable takes over again on the next launch
+        let source = self.config.layers.source("quantum.default_backend");
+        if matches!(source, Source::Project(_) | Source::Env(_)) {
+            self.messages.push(Message::system(format!(
+                "{} sets the default backend too, and wins over this on the next launch.",
+                source.label()
+            )));
+        }
+        if offline {
+            self.messages.push(Message::system(format!("{} is offline at the moment.", name)));
+        }
+        self.post_notice(format!("Default backend: {}", name));
+        self.scroll_to_bottom();
+    }
+
+    fn show_backends(&mut self) {
+        let backends = self.backend_context.backends();
+        let mut text = if backends.is_empty() {
+            "No quantum backends are available to this account.".to_string()
+        } else {
+            let mut text = format!("Quantum backends ({}):", backends.len());
+            for backend in backends {
+                text.push_str(&format!("\n  {}", backend_context::describe(backend)));
+            }
+            text
+        };
+        if self.config.ai.inject_backend_context && !backends.is_empty() {
+            text.push_str("\n\nThe AI is told about these for the next 30 minutes (ai.inject_backend_context).");
+        }
+        self.messages.push(Message::system(text));
+        self.scroll_to_bottom();
+    }
+
+    /// Upload a full batch of usage counts (or whatever is pending, with
+    /// `flush`). Only runs with telemetry on, and gives up for the session
+    /// after a failure rather than retrying on every event.
+    fn upload_telemetry(&mut self, flush: bool) {
+        if self.telemetry_rx.is_some() || self.telemetry_upload_failed {
+            return;
+        }
+        let Some(batch) = self.telemetry.take_batch(flush) else {
+            return;
+        };
+        let api_client = self.api_client.clone();
+        let (tx, rx) = mpsc::channel(1);
+        self.telemetry_rx = Some(rx);
+        self.tasks.spawn(async move {
+            let result = api_client.upload_telemetry(&batch).await.map_err(|_| batch);
+            let _ = tx.send(result).await;
+        });
+    }
+
+    pub fn check_telemetry_response(&mut self) {
+        let result = match self.telemetry_rx.as_mut().map(|rx| rx.try_recv()) {
+            Some(Ok(result)) => result,
+            Some(Err(mpsc::error::TryRecvError::Empty)) | None => return,
+            // Cancelled on quit; the counts were saved before the upload
+            Some(Err(mpsc::error::TryRecvError::Disconnected)) => Ok(()),
+        };
+        self.telemetry_rx = None;
+        if let Err(batch) = result {
+            self.telemetry.restore(batch);
+            self.telemetry_upload_failed = true;
+        }
+    }
+
+    /// Receivers still waiting on a background task
+    fn pending_results(&self) -> usize {
+        [
+            self.ai_response_rx.is_some(),
+            self.auth_response_rx.is_some(),
+            self.account_switch_rx.is_some(),
+            self.key_test_rx.is_some(),
+            self.logout_rx.is_some(),
+            self.usage_rx.is_some(),
+            self.quota_rx.is_some(),
+            self.backends_rx.is_some(),
+            self.backend_info_rx.is_some(),
+            self.models_rx.is_some(),
+            self.report_rx.is_some(),
+            self.share_rx.is_some(),
+            self.profile_rx.is_some(),
+        ]
+        .iter()
+        .filter(|pending| **pending)
+        .count()
+    }
+
+    /// Background work may deliver a result at any moment
+    pub fn is_busy(&self) -> bool {
+        self.is_loading || self.pending_results() > 0
+    }
+
+    /// Collect finished background work. Returns true if anything on screen
+    /// may have changed.
+    pub fn poll_background(&mut self) -> bool {
+        let pending = self.pending_results();
+        let progress = self.ai_progress;
+        if self.ai_queue.take_due(Instant::now()) {
+            self.dispatch_ai_request();
+        }
+        let locked = self.check_idle(Instant::now());
+        // Before the checks, so a panicked task is reported by name rather
+        // than as a generic disconnect
+        let alarmed = self.run_watchdog(Instant::now());
+        let diagnosed = self.collect_diagnostics(Instant::now());
+        self.check_ai_response();
+        self.check_auth_response();
+        self.check_account_switch_response();
+        self.check_key_test_response();
+        self.check_logout_response();
+        self.check_usage_response();
+        self.check_quota(Instant::now());
+        self.check_quota_response();
+        let watched = self.check_job_watches();
+        let reloaded = self.config_watch.changed(Instant::now()) && self.reload_config(false);
+        self.check_backends_response();
+        self.check_backend_info_response();
+        self.check_models_response();
+        self.check_report_response();
+        self.check_share_response();
+        self.check_profile_response();
+        self.check_telemetry_response();
+        self.upload_telemetry(false);
+
+        // The status bar shows database health, which changes on its own
+        let health = self.db.as_ref().map(|db| db.health());
+        let health_changed = health != self.db_health;
+        self.db_health = health;
+
+        // Best effort: a failed draft write must not interrupt typing
+        let secret = self.pending_key.is_some() || self.wizard.is_some() || draft::is_secret(&self.input);
+        let _ = self.draft.observe(&self.input, secret, Instant::now());
+
+        // Reported once; the writer stays quiet until it recovers
+        let transcript_error = self.transcript.as_mut().and_then(|t| t.take_error());
+        if let Some(error) = &transcript_error {
+            self.messages.push(Message::error(format!("Could not write transcript: {}", error)));
+        }
+
+        self.pending_results() != pending
+            || self.ai_progress != progress
+            || health_changed
+            || transcript_error.is_some()
+            || locked
+            || alarmed
+            || diagnosed
+            || watched
+            || reloaded
+    }
+
+    /// Load the config files again and apply what changed; `forced` (from
+    /// /reload) also reports when nothing did. Returns true if the chat
+    /// changed.
+    fn reload_config(&mut self, forced: bool) -> bool {
+        let fresh = match self.config.reload() {
+            Ok(fresh) => fresh,
+            Err(e) => {
+                self.messages.push(Message::error(format!(
+                    "The config was not reloaded, so the previous one stays in use: {:#}",
+                    e
+                )));
+                return true;
+            }
+        };
+        let changed = match self.config.changed_keys(&fresh) {
+            Ok(changed) => changed,
+            Err(e) => {
+                self.messages.push(Message::error(format!("The config was not reloaded: {:#}", e)));
+                return true;
+            }
+        };
+        if changed.is_empty() {
+            if forced {
+                self.post_notice("Config unchanged".to_string());
+            }
+            return forced;
+        }
+
+        let (restart, applied): (Vec<String>, Vec<String>) =
+            changed.into_iter().partition(|key| config_reload::classify(key) == Apply::Restart);
+        // Restart-only values are taken on too, so that a later save keeps
+        // them; what was built from the old ones stays until the restart
+        self.config = fresh;
+        if applied.iter().any(|key| config_reload::classify(key) == Apply::Client) {
+            self.ai_client = Arc::new(DeepSeekClient::from_config(&self.config));
+        }
+        if applied.iter().any(|key| key == "ui.tick_ms") {
+            self.pacer = FramePacer::new(Duration::from_millis(self.config.ui.tick_ms));
+        }
+        self.ensure_system_prompt();
+        self.refresh_capabilities();
+        self.render_cache = RenderCache::default();
+
+        let mut report = Vec::new();
+        if !applied.is_empty() {
+            report.push(format!("Config reloaded: {}.", applied.join(", ")));
+        }
+        if !restart.is_empty() {
+            report.push(format!("Restart QHub to apply: {}.", restart.join(", ")));
+        }
+        self.messages.push(Message::system(report.join("\n")));
+        true
+    }
+
+    /// Move posted diagnostics into the chat (warnings) or the status bar
+    /// (info). Returns true if there were any.
+    fn collect_diagnostics(&mut self, now: Instant) -> bool {
+        let diagnostics = diagnostics::drain();
+        for diagnostic in &diagnostics {
+            match diagnostic.level {
+                Level::Info => self.post_notice_at(diagnostic.text.clone(), now),
+                Level::Warning => self.messages.push(Message::system(format!("⚠️  {}", diagnostic.text))),
+            }
+        }
+        !diagnostics.is_empty()
+    }
+
+    /// Show `text` in the status bar for a while; in accessible mode it is
+    /// added to the chat as well, where a screen reader reads it
+    fn post_notice(&mut self, text: String) {
+        self.post_notice_at(text, Instant::now());
+    }
+
+    fn post_notice_at(&mut self, text: String, now: Instant) {
+        if self.accessible {
+            self.messages.push(Message::system(text.clone()));
+        }
+        self.notice = Some((text, now));
+    }
+
+    /// The status bar notice, while it is fresh
+    pub fn notice(&self, now: Instant) -> Option<&str> {
+        self.notice
+            .as_ref()
+            .filter(|(_, posted)| now < *posted + NOTICE_DURATION)
+            .map(|(text, _)| text.as_str())
+    }
+
+    /// Operations whose result has not arrived yet
+    fn awaited_operations(&self) -> Vec<Operation> {
+        [
+            (Operation::AiRequest, self.ai_response_rx.as_ref().map(|rx| rx.is_empty())),
+            (Operation::Auth, self.auth_response_rx.as_ref().map(|rx| rx.is_empty())),
+            (Operation::AccountSwitch, self.account_switch_rx.as_ref().map(|rx| rx.is_empty())),
+            (Operation::KeyTest, self.key_test_rx.as_ref().map(|rx| rx.is_empty())),
+            (Operation::Usage, self.usage_rx.as_ref().map(|rx| rx.is_empty())),
+            (Operation::Backends, self.backends_rx.as_ref().map(|rx| rx.is_empty())),
+            (Operation::BackendInfo, self.backend_info_rx.as_ref().map(|rx| rx.is_empty())),
+            (Operation::Models, self.models_rx.as_ref().map(|rx| rx.is_empty())),
+            (Operation::Report, self.report_rx.as_ref().map(|rx| rx.is_empty())),
+            (Operation::Share, self.share_rx.as_ref().map(|rx| rx.is_empty())),
+            (Operation::Profile, self.profile_rx.as_ref().map(|rx| rx.is_empty())),
+        ]
+        .into_iter()
+        .filter(|(_, empty)| *empty == Some(true))
+        .map(|(operation, _)| operation)
+        .collect()
+    }
+
+    /// Check tracked tasks at most once per `WATCHDOG_INTERVAL`. Returns true
+    /// if anything was abandoned.
+    fn run_watchdog(&mut self, now: Instant) -> bool {
+        if now < self.next_watchdog {
+            return false;
+        }
+        self.next_watchdog = now + WATCHDOG_INTERVAL;
+        let waiting = self.awaited_operations();
+        let alarms = self.tasks.watchdog(now, &waiting);
+        for alarm in &alarms {
+            self.abandon(alarm);
+        }
+        let stuck = self.check_stuck_loading(now);
+        !alarms.is_empty() || stuck
+    }
+
+    /// Clear `is_loading` once it has lasted longer than any task may run
+    /// with no progress in between. The task watchdog normally gets there
+    /// first; this catches a loading state nothing tracked is behind, e.g.
+    /// one left by a task that died before its channel was set up, so the
+    /// input always comes back. True if it was cleared.
+    fn check_stuck_loading(&mut self, now: Instant) -> bool {
+        if !self.is_loading {
+            self.loading_seen = None;
+            return false;
+        }
+        // A held request waits on a timer, not a task
+        if self.ai_queue.held().is_some() {
+            self.loading_seen = Some(now);
+            return false;
+        }
+        let limit = Operation::AiRequest.deadline() + LOADING_MARGIN;
+        if now.duration_since(*self.loading_seen.get_or_insert(now)) < limit {
+            return false;
+        }
+        self.loading_seen = None;
+        self.tasks.abort(Operation::AiRequest);
+        self.tasks.abort(Operation::Auth);
+        let error = format!(
+            "Stopped waiting after {} with no progress. The input is back; try again if something is missing.",
+            format::duration(limit)
+        );
+        if self.ai_response_rx.is_some() {
+            self.fail_ai_request(None, error);
+        } else {
+            self.auth_response_rx = None;
+            self.is_loading = false;
+            self.messages.push(Message::error(error));
+        }
+        self.scroll_to_bottom();
+        true
+    }
+
+    /// Stop waiting for an operation the watchdog gave up on
+    fn abandon(&mut self, alarm: &Alarm) {
+        let pending = match alarm.operation {
+            Operation::AiRequest => {
+                if self.ai_response_rx.is_some() {
+                    self.fail_ai_request(None, alarm.message());
+                }
+                return;
+            }
+            Operation::Auth => {
+                let pending = self.auth_response_rx.take().is_some();
+                self.is_loading &= !pending;
+                pending
+            }
+            Operation::AccountSwitch => self.account_switch_rx.take().is_some(),
+            Operation::KeyTest => self.key_test_rx.take().is_some(),
+            Operation::Usage => self.usage_rx.take().is_some(),
+            Operation::Backends => self.backends_rx.take().is_some(),
+            Operation::BackendInfo => self.backend_info_rx.take().is_some(),
+            Operation::Models => self.models_rx.take().is_some(),
+            Operation::Report => self.report_rx.take().is_some(),
+            Operation::Share => self.share_rx.take().is_some(),
+            Operation::Profile => self.profile_rx.take().is_some(),
+        };
+        if pending {
+            self.messages.push(Message::error(alarm.message()));
+            self.scroll_to_bottom();
+        }
+    }
+
+    /// When a timer-driven view next needs a frame, e.g. the /stats uptime clock
+    pub fn next_timed_redraw(&self, now: Instant) -> Option<Instant> {
+        // The status bar notice disappears on its own
+        let notice = self
+            .notice
+            .as_ref()
+            .map(|(_, posted)| *posted + NOTICE_DURATION)
+            .filter(|expires| *expires > now);
+        match (notice, self.next_view_redraw(now)) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    fn next_view_redraw(&self, now: Instant) -> Option<Instant> {
+        // The rate-limit countdown ticks on whole seconds
+        if let Some(held) = self.ai_queue.held() {
+            let left = held.retry_at.saturating_duration_since(now);
+            let step = Duration::from_nanos(left.subsec_nanos() as u64);
+            return Some(now + if step.is_zero() { left.min(Duration::from_secs(1)) } else { step });
+        }
+        if self.show_stats {
+            let into_second = self.stats.uptime().subsec_nanos();
+            return Some(now + Duration::from_nanos(1_000_000_000 - into_second as u64));
+        }
+        // Relative timestamps change text as messages age
+        if self.config.ui.show_timestamps && self.config.ui.time_format == TimeFormat::Relative {
+            let local_now = Local::now();
+            return self
+                .messages
+                .iter()
+                .filter(|m| matches!(m.role, MessageRole::User | MessageRole::Assistant))
+                .map(|m| format::next_relative_change((local_now - m.timestamp).to_std().unwrap_or_default()))
+                .min()
+                .map(|wait| now + wait);
+        }
+        None
+    }
+
+    /// Cancel background work and wait for critical tasks before exiting.
+    /// Returns warnings to print once the terminal is restored.
+    pub async fn shutdown(&mut self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        self.autosave_session();
+        let finished = self.tasks.shutdown(SHUTDOWN_TIMEOUT).await;
+        
+        if self.logout_rx.is_some() {
+            if finished {
+                let before = self.messages.len();
+                self.check_logout_response();
+                warnings.extend(
+                    self.messages[before..]
+                        .iter()
+                        .filter(|m| m.role == MessageRole::Error)
+                        .map(|m| m.content.clone()),
+                );
+            } else {
+                warnings.push(
+                    "Logout did not finish before exit; you are still logged in locally. Run /logout again.".to_string()
+                );
+            }
+        }
+        
+        warnings
+    }
+
     fn handle_slash_command(&mut self, cmd: SlashCommand) {
+        self.telemetry.command(cmd.name());
+        if !matches!(cmd, SlashCommand::Unknown(_)) {
+            if let Err(e) = self.command_usage.record(cmd.name(), Utc::now()) {
+                diagnostics::debug(format!("Command usage not saved: {}", e));
+            }
+        }
         match cmd {
             SlashCommand::Login { email, password } => {
                 self.messages.push(Message::system("🔄 Logging in...".to_string()));
                 self.is_loading = true;
                 
-                let api_client = self.api_client.clone();
+                let login = self.auth.login(LoginRequest { email, password });
                 let (tx, rx) = mpsc::channel(1);
                 self.auth_response_rx = Some(rx);
                 
-                tokio::spawn(async move {
-                    let result = api_client.login(crate::api::client::LoginRequest {
-                        email,
-                        password,
-                    }).await;
+                self.tasks.spawn_tracked(Operation::Auth, async move {
+                    let result = login.await;
                     
                     let response = match result {
-                        Ok(auth_resp) => {
-                            Ok((auth_resp.token, auth_resp.user.email, auth_resp.user.tier))
-                        }
+                        Ok(auth_resp) => Ok((auth_resp.token, auth_resp.user)),
                         Err(e) => Err(e.to_string()),
                     };
                     let _ = tx.send(response).await;
                 });
             }
-            SlashCommand::Register { email, username, password } => {
+            SlashCommand::Register { email, username, password, display_name } => {
+                if let Some(Err(e)) = display_name.as_deref().map(auth::validate_display_name) {
+                    self.messages.push(Message::error(e.to_string()));
+                    return;
+                }
                 self.messages.push(Message::system("🔄 Creating account...".to_string()));
                 self.is_loading = true;
                 
-                let api_client = self.api_client.clone();
+                let register = self.auth.register(RegisterRequest {
+                    email,
+                    username: Some(username),
+                    password,
+                    display_name,
+                });
                 let (tx, rx) = mpsc::channel(1);
                 self.auth_response_rx = Some(rx);
                 
-                tokio::spawn(async move {
-                    let result = api_client.register(crate::api::client::RegisterRequest {
-                        email,
-                        username: Some(username),
-                        password,
-                    }).await;
+                self.tasks.spawn_tracked(Operation::Auth, async move {
+                    let result = register.await;
                     
                     let response = match result {
-                        Ok(auth_resp) => {
-                            Ok((auth_resp.token, auth_resp.user.email, auth_resp.user.tier))
-                        }
+                        Ok(auth_resp) => Ok((auth_resp.token, auth_resp.user)),
                         Err(e) => Err(e.to_string()),
                     };
                     let _ = tx.send(response).await;
                 });
             }
             SlashCommand::Logout => {
-                // Call logout API to invalidate session
-                let api_client = self.api_client.clone();
-                tokio::spawn(async move {
-                    let _ = api_client.logout().await;
-                });
-                
-                // Clear local state
-                self.api_client.clear_token();
-                self.config.user = None;
-                self.user_email = None;
-                self.user_tier = "free".to_string();
-                
-                if let Err(e) = self.config.save() {
-                    self.messages.push(Message::error(
-                        format!("Failed to save config: {}", e)
-                    ));
-                } else {
-                    self.messages.push(Message::system("✓ Logged out successfully".to_string()));
+                if self.logout_rx.is_some() {
+                    self.messages.push(Message::system("Logout already in progress...".to_string()));
+                    return;
                 }
+                self.messages.push(Message::system("🔄 Logging out...".to_string()));
+                
+                // Invalidate the session server-side first; local state is only
+                // forgotten once this finishes (see check_logout_response)
+                let logout = self.auth.logout(self.api_client.token().map(str::to_string));
+                let (tx, rx) = mpsc::channel(1);
+                self.logout_rx = Some(rx);
+                self.tasks.spawn_critical(async move {
+                    let result = logout.await.map_err(|e| e.to_string());
+                    let _ = tx.send(result).await;
+                });
             }
+            SlashCommand::Accounts(None) => self.list_accounts(),
+            SlashCommand::Accounts(Some(email)) => self.switch_account(email),
             SlashCommand::Upgrade => {
                 self.messages.push(Message::system(
                     "Opening upgrade page in your browser...".to_string()
@@ -594,21 +3308,78 @@ Start generating quantum circuits:
 ├──────────────────────────────────────────────────────────────────┤
 │  /login <email> <password>                                       │
 │      Log in to your QHub account                                 │
-│  /register <email> <username> <password>                         │
+│  /register <email> <username> <password> [display name]          │
 │      Create a new account                                        │
 │  /logout                                                         │
 │      Log out from your account                                   │
+│  /accounts [switch <email>]                                      │
+│      List saved accounts or change the active one                │
 │  /upgrade    Upgrade to Pro for more quantum backends            │
 │  /status     Show your current account status                    │
+│  /stats      Show activity for this session                      │
+│  /backends   List your IBM Quantum backends and queues           │
+│  /backend-info <name>                                            │
+│      Qubits, gates, coupling map and error rates of a backend    │
+│  /backend show | set <name>                                      │
+│      The backend runs use by default; set checks and saves it    │
+│  /recommend [file.qasm]                                          │
+│      Best backend for a circuit (default: last code block)       │
+│  /calc <gates>   e.g. /calc H 0; CX 0 1                          │
+│      Amplitudes from the local simulator, no AI involved         │
+│  /compare <job_a> <job_b>                                        │
+│      Compare two jobs' distributions (or exported .json files)   │
+│  /export counts <job id> <file.csv|file.json>                    │
+│      A job's counts with probabilities, for analysis elsewhere   │
+│  /sweep status <id>                                              │
+│      Progress of a qhub sweep, with its ⟨Z⟩ curve                │
+│  /watch <job id>                                                 │
+│      Follow an IBM Quantum job's queue position and ETA          │
+│  /share [code] [private|unlisted] [expires=7d|never]             │
+│      Upload the chat or a code block; the link is copied         │
+│  /profile set display-name <name>                                │
+│      Change the name shown instead of your email                 │
+│  /connect    Run setup again (AI key, account)                   │
+│  /keys set|clear ai|quantum, /keys status                        │
+│      Manage API keys without editing config files                │
 │  /clear      Clear the chat history                              │
+│  /cache clear  Forget replies kept by ai.cache                   │
+│  /resume     Reload the last autosaved session                   │
+│  /retry      Send a prompt that got no reply again (or Enter)    │
+│  /cancel     Stop the request being answered (or Esc)            │
+│  /continue   Ask for the rest of a reply that was cut off        │
+│  /regen [prev | --model <name>]                                  │
+│      Ask for the last reply again; prev cycles earlier answers   │
+│  /model [<name> | default]                                       │
+│      Show or switch the model for this session (Tab completes)   │
+│  /models [refresh]                                               │
+│      List models; refresh asks the provider for its list         │
+│  /set [temperature|top_p|max_tokens <value|default>]             │
+│      Change a generation parameter for this session              │
+│  /copy       Copy the last code block (--pretty indents JSON)    │
+│  /save <file> [-f]                                               │
+│      Write the last code block; -f shows the diff, then asks     │
+│  /diff <file>                                                    │
+│      Compare the last code block with a file                     │
+│  /edit code|run                                                  │
+│      Edit the last code block in $VISUAL/$EDITOR                 │
+│  /run-prompt <path> [name=value ...]                             │
+│      Send a prompt file; {{code}} is the last code block         │
+│  /telemetry on|off|status|purge                                  │
+│      Opt in to anonymous usage counts (off by default)           │
+│  /reload     Apply config file edits now (they are also noticed) │
 │  /help       Show this help message                              │
 │  /quit       Exit QHub                                           │
 ├──────────────────────────────────────────────────────────────────┤
 │  Keyboard Shortcuts:                                             │
 │  Ctrl+C      Exit QHub                                           │
 │  Ctrl+Q      Exit QHub                                           │
+│  Ctrl+Z      Suspend to the shell (resume with fg)               │
+│  Ctrl+B      Number code blocks, then copy/save/edit/run one     │
+│  Ctrl+P      Search commands and recent prompts                  │
+│  Ctrl+R      Search earlier prompts and commands                 │
 │  PageUp/Down Scroll through messages                             │
 │  Enter       Send message                                        │
+│  Click       Select a message; double-click code to copy it      │
 ╰──────────────────────────────────────────────────────────────────╯
 "#.to_string()
                 ));
@@ -619,10 +3390,68 @@ Start generating quantum circuits:
             }
             SlashCommand::Clear => {
                 self.messages.clear();
-                self.messages.push(Message::system("Chat cleared.".to_string()));
+                self.welcome = None;
+                self.retry_prompt = None;
+                // Guests start over completely, including the saved history
+                if self.capabilities.is_guest() {
+                    self.ensure_system_prompt();
+                    self.conversation_history.truncate(1);
+                    self.save_guest_history();
+                }
+                // The empty pane shows a hint on how to begin
+                self.scroll = ScrollState::new();
+                self.selected_message = None;
+            }
+            SlashCommand::Copy { pretty } => {
+                match self.last_code_block() {
+                    Some((message, block)) => self.copy_code_block(message, block, pretty),
+                    None => self.messages.push(Message::error(
+                        "No code block to copy yet.".to_string()
+                    )),
+                }
+            }
+            SlashCommand::Save { path, force } => self.save_to(PathBuf::from(path), force),
+            SlashCommand::Diff(path) => self.show_diff(PathBuf::from(path)),
+            SlashCommand::Retry => self.retry(),
+            SlashCommand::Cancel => self.cancel_request(),
+            SlashCommand::CacheClear => self.clear_reply_cache(),
+            SlashCommand::Continue => self.continue_reply(),
+            SlashCommand::Regen(RegenAction::New { model }) => self.regen(model),
+            SlashCommand::Regen(RegenAction::Previous) => self.regen_previous(),
+            SlashCommand::Set(setting) => self.set_parameter(setting),
+            SlashCommand::Model(name) => self.set_model(name),
+            SlashCommand::Models { refresh: false } => self.show_models(),
+            SlashCommand::Models { refresh: true } => self.refresh_models(),
+            SlashCommand::Resume => {
+                let Some(path) = self.resume_path.take() else {
+                    let hint = if self.config.ui.autosave_sessions {
+                        "No earlier session to resume."
+                    } else {
+                        "No saved sessions. Set autosave_sessions = true under [ui] in config.toml."
+                    };
+                    self.messages.push(Message::error(hint.to_string()));
+                    return;
+                };
+                match history::load_session(&path) {
+                    Ok(session) => self.resume_session(&path, session),
+                    Err(e) => self.messages.push(Message::error(format!("Could not resume session: {}", e))),
+                }
+            }
+            SlashCommand::RunPrompt { path, args } => {
+                match prompts::build(&path, &args, self.last_code().as_deref()) {
+                    Ok(prompt) => self.send_chat(prompt),
+                    Err(e) => self.messages.push(Message::error(format!("/run-prompt: {}", e))),
+                }
+            }
+            SlashCommand::Edit(action) => {
+                let Some(block) = self.last_block() else {
+                    self.messages.push(Message::error("No code block to edit yet.".to_string()));
+                    return;
+                };
+                self.open_editor(action, &block);
             }
             SlashCommand::Status => {
-                let config_path = Config::config_path()
+                let config_path = self.config.path()
                     .map(|p| p.display().to_string())
                     .unwrap_or_else(|_| "unknown".to_string());
                 
@@ -638,7 +3467,42 @@ Start generating quantum circuits:
                     "✗ Not set"
                 };
                 
+                let context = format!(
+                    "~{} / {} tokens",
+                    format::grouped(tokens::estimate_messages(&self.conversation_history) as u64),
+                    format::grouped(self.context_budget() as u64)
+                );
+                let default_backend =
+                    self.config.quantum.default_backend.as_deref().unwrap_or("none (picked per circuit)");
+                let high = audit::Scope::current()
+                    .map(|scope| audit::audit(&self.config, &scope))
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|finding| finding.severity == Severity::High)
+                    .count();
+                let security_line = match high {
+                    0 => String::new(),
+                    n => format!("\n│ ⚠ Security: {} high-severity finding(s); run `qhub security audit`", n),
+                };
+                let project_line = match &self.config.layers.project {
+                    Some(path) => format!(
+                        "\n│ Project file: {}\n│   sets {}",
+                        path.display(),
+                        self.config.layers.project_keys().join(", ")
+                    ),
+                    None => String::new(),
+                };
+                
                 let status = if let Some(email) = &self.user_email {
+                    let last_login = self
+                        .user_last_login
+                        .and_then(format::local)
+                        .map_or_else(|| "first login".to_string(), |at| format::ago(&at, &Local::now()));
+                    let tier_lines: Vec<String> = tiers::limits(&self.user_tier)
+                        .describe()
+                        .iter()
+                        .map(|line| format!("│   {}", line))
+                        .collect();
                     format!(
                         r#"
 ╭─────────────────────────────────────────────╮
@@ -646,27 +3510,39 @@ Start generating quantum circuits:
 ├─────────────────────────────────────────────┤
 │ Email: {}
 │ Tier:  {}
+{}
 │ Status: {}
+│ Last login: {}
 ├─────────────────────────────────────────────┤
 │ Configuration                               │
 ├─────────────────────────────────────────────┤
-│ Config file: {}
+│ Config file: {}{}
 │ API URL: {}
 │ AI Provider: {} ({})
 │ Quantum Provider: {} ({})
+│ Default backend: {}
 │ AI Model: {}
+│ Generation: {}
+│ Context: {}{}
 ╰─────────────────────────────────────────────╯
 "#,
                         email,
                         self.user_tier,
+                        tier_lines.join("\n"),
                         if self.is_connected { "Connected" } else { "Disconnected" },
+                        last_login,
                         config_path,
+                        project_line,
                         self.config.api_url,
                         self.config.ai.provider,
                         ai_key_status,
                         self.config.quantum.provider,
                         quantum_key_status,
-                        self.config.ai.model,
+                        default_backend,
+                        self.active_model(),
+                        self.generation_summary(),
+                        context,
+                        security_line,
                     )
                 } else {
                     format!(
@@ -679,49 +3555,879 @@ Start generating quantum circuits:
 ├─────────────────────────────────────────────┤
 │ Configuration                               │
 ├─────────────────────────────────────────────┤
-│ Config file: {}
+│ Config file: {}{}
 │ API URL: {}
 │ AI Provider: {} ({})
 │ Quantum Provider: {} ({})
+│ Default backend: {}
 │ AI Model: {}
+│ Generation: {}
+│ Context: {}{}
 ╰─────────────────────────────────────────────╯
 "#,
                         config_path,
+                        project_line,
                         self.config.api_url,
                         self.config.ai.provider,
                         ai_key_status,
                         self.config.quantum.provider,
                         quantum_key_status,
-                        self.config.ai.model,
+                        default_backend,
+                        self.active_model(),
+                        self.generation_summary(),
+                        context,
+                        security_line,
                     )
                 };
-                self.messages.push(Message::system(status));
+                self.messages.push(Message::system(status));
+            }
+            SlashCommand::Backends => {
+                if self.config.get_quantum_api_key().is_none() {
+                    self.messages.push(Message::error(
+                        "No IBM Quantum token set. Add one with /keys set quantum.".to_string(),
+                    ));
+                } else if self.backends_rx.is_none() {
+                    self.messages.push(Message::system("Fetching backends from IBM Quantum…".to_string()));
+                    self.fetch_backends(true);
+                }
+            }
+            SlashCommand::BackendInfo(name) => self.backend_info(name),
+            SlashCommand::DefaultBackend(name) => self.default_backend(name),
+            SlashCommand::Recommend(path) => self.recommend(path),
+            SlashCommand::Calc(gates) => self.messages.push(match calc::evaluate(&gates) {
+                Ok(report) => {
+                    self.stats.circuits_run += 1;
+                    Message::system(report)
+                }
+                Err(error) => Message::error(format!("/calc: {}", error)),
+            }),
+            SlashCommand::Compare { job_a, job_b } => {
+                let render: fn(&compare::Comparison, &str, &str) -> String =
+                    if self.accessible { compare::render_plain } else { compare::render };
+                // Exported files need no database
+                if export::is_counts_file(&job_a) && export::is_counts_file(&job_b) {
+                    let report = export::read_counts(&job_a).and_then(|a| {
+                        Ok(render(&compare::compare(&a, &export::read_counts(&job_b)?), &job_a, &job_b))
+                    });
+                    self.messages.push(match report {
+                        Ok(report) => Message::system(report),
+                        Err(e) => Message::error(format!("{:#}", e)),
+                    });
+                    return;
+                }
+                // Job results live in the database; there is no API for them yet
+                let (Some(db), Some(email)) = (self.db.clone(), self.user_email.clone()) else {
+                    self.messages.push(Message::error(
+                        "/compare reads job results from the database: set DATABASE_URL and log in".to_string(),
+                    ));
+                    return;
+                };
+                let (tx, rx) = mpsc::channel(1);
+                self.report_rx = Some(rx);
+                self.tasks.spawn_tracked(Operation::Report, async move {
+                    let result = db
+                        .run(|pool| async move {
+                            let a = pool.counts_for(&job_a, Some(&email)).await?;
+                            let b = pool.counts_for(&job_b, Some(&email)).await?;
+                            Ok(render(&compare::compare(&a, &b), &job_a, &job_b))
+                        })
+                        .await
+                        .map_err(|e| e.to_string());
+                    let _ = tx.send(result).await;
+                });
+            }
+            SlashCommand::ExportCounts { job_id, path } => {
+                let (Some(db), Some(email)) = (self.db.clone(), self.user_email.clone()) else {
+                    self.messages.push(Message::error(
+                        "/export reads job results from the database: set DATABASE_URL and log in".to_string(),
+                    ));
+                    return;
+                };
+                let options = export::Options {
+                    format: export::ExportFormat::for_path(&path),
+                    order: self.config.quantum.export_order,
+                    separator: self.config.quantum.register_separator.clone(),
+                };
+                let (tx, rx) = mpsc::channel(1);
+                self.report_rx = Some(rx);
+                self.tasks.spawn_tracked(Operation::Report, async move {
+                    let result = db
+                        .run(|pool| async move {
+                            let (counts, source) = pool.job_export(&job_id, &email).await?;
+                            std::fs::write(&path, export::render(&counts, &source, &options)?)
+                                .with_context(|| format!("Failed to write {}", path))?;
+                            Ok(format!("✓ Exported {} outcomes of job {} to {}", counts.len(), job_id, path))
+                        })
+                        .await
+                        .map_err(|e| format!("{:#}", e));
+                    let _ = tx.send(result).await;
+                });
+            }
+            SlashCommand::SweepStatus { sweep_id } => {
+                let (Some(db), Some(email)) = (self.db.clone(), self.user_email.clone()) else {
+                    self.messages.push(Message::error(
+                        "/sweep reads sweep points from the database: set DATABASE_URL and log in".to_string(),
+                    ));
+                    return;
+                };
+                let (tx, rx) = mpsc::channel(1);
+                self.report_rx = Some(rx);
+                self.tasks.spawn_tracked(Operation::Report, async move {
+                    let result = db
+                        .run(|pool| async move {
+                            let jobs = pool.sweep_jobs(&sweep_id, &email).await?;
+                            Ok(sweep::status_report(&sweep_id, &jobs))
+                        })
+                        .await
+                        .map_err(|e| e.to_string());
+                    let _ = tx.send(result).await;
+                });
+            }
+            SlashCommand::Watch(job_id) => self.watch_job(job_id),
+            SlashCommand::Share(options) => self.share(options),
+            SlashCommand::SetDisplayName(name) => self.set_display_name(name),
+            SlashCommand::Stats => {
+                self.show_stats = true;
+                // All-time totals are only available with a direct database connection
+                if let (Some(db), Some(email)) = (self.db.clone(), self.user_email.clone()) {
+                    let (tx, rx) = mpsc::channel(1);
+                    self.usage_rx = Some(rx);
+                    self.tasks.spawn_tracked(Operation::Usage, async move {
+                        let totals = db
+                            .run(|pool| async move { pool.user_usage_totals(&email).await })
+                            .await;
+                        if let Ok(totals) = totals {
+                            let _ = tx.send(totals).await;
+                        }
+                    });
+                }
+            }
+            SlashCommand::Connect => {
+                if self.wizard.is_some() {
+                    self.messages.push(Message::system("Setup is already running.".to_string()));
+                } else {
+                    self.start_wizard();
+                }
+            }
+            SlashCommand::Keys(action) => self.handle_keys_command(action),
+            SlashCommand::Telemetry(action) => self.handle_telemetry_command(action),
+            SlashCommand::Reload => {
+                self.reload_config(true);
+            }
+            SlashCommand::Unknown(cmd) if cmd.is_empty() => {
+                self.messages.push(Message::error(
+                    "Type a command after the /, e.g. /help for the full list.".to_string()
+                ));
+            }
+            SlashCommand::Unknown(cmd) => {
+                self.messages.push(Message::error(
+                    format!("Unknown command or invalid syntax: /{}. Type /help for available commands.", cmd)
+                ));
+            }
+        }
+        self.input.clear();
+        self.scroll_to_bottom();
+    }
+
+    /// Start the interactive first-run setup wizard
+    pub fn start_wizard(&mut self) {
+        let mut wizard = SetupWizard::new();
+        wizard.qiskit_accounts = qiskit::default_path()
+            .and_then(|path| qiskit::load(&path).ok())
+            .unwrap_or_default();
+        self.messages.push(Message::system(format!(
+            "Let's get you set up. Type /skip at any time to finish later.\n\n{}",
+            wizard.prompt()
+        )));
+        self.wizard = Some(wizard);
+        self.scroll_to_bottom();
+    }
+
+    fn handle_wizard_input(&mut self, input: &str) {
+        // Slash commands still work during setup; /skip leaves the wizard
+        if input.trim_start().starts_with('/') {
+            let input = input.trim();
+            if input.eq_ignore_ascii_case("/skip") {
+                self.wizard = None;
+                match self.config.save() {
+                    Ok(()) => self.messages.push(Message::system(
+                        "Setup skipped. Use /login or /register when you're ready.".to_string()
+                    )),
+                    Err(e) => self.messages.push(Message::error(
+                        format!("Failed to save config: {}", e)
+                    )),
+                }
+            } else if let Some(cmd) = SlashCommand::parse(input) {
+                self.handle_slash_command(cmd);
+            }
+            return;
+        }
+
+        let Some(wizard) = self.wizard.as_mut() else {
+            return;
+        };
+
+        // Never echo secrets back into the transcript
+        let echo = if wizard.is_secret() {
+            "•".repeat(input.chars().count().min(12))
+        } else {
+            input.trim().to_string()
+        };
+        if !echo.is_empty() {
+            self.messages.push(Message::user(echo));
+        }
+
+        match wizard.submit(input) {
+            Ok(WizardAction::Continue) => {
+                let prompt = wizard.prompt();
+                self.messages.push(Message::system(prompt));
+            }
+            Ok(WizardAction::Finish) => self.finish_wizard(),
+            Err(e) => self.messages.push(Message::error(e)),
+        }
+    }
+
+    /// Apply the collected wizard answers to the config and authenticate if requested
+    fn finish_wizard(&mut self) {
+        let Some(wizard) = self.wizard.clone() else {
+            return;
+        };
+
+        self.config.ai.provider = wizard.provider.clone();
+        if let Some(key) = wizard.ai_key {
+            self.config.ai.api_key = Some(key);
+            self.ai_client = Arc::new(DeepSeekClient::from_config(&self.config));
+        }
+        if let Some(token) = wizard.quantum_key {
+            self.config.quantum.api_key = Some(token);
+        }
+        if wizard.quantum_channel.is_some() {
+            self.config.quantum.provider = "ibm".to_string();
+            self.config.quantum.channel = wizard.quantum_channel;
+            self.config.quantum.instance = wizard.quantum_instance;
+        }
+        self.refresh_capabilities();
+
+        if let Err(e) = self.config.save() {
+            self.messages.push(Message::error(format!("Failed to save config: {}", e)));
+        } else {
+            let path = self.config.path()
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(|_| "~/.qhub/config.toml".to_string());
+            self.messages.push(Message::system(format!("✓ Configuration saved to {}", path)));
+        }
+
+        match wizard.account {
+            AccountChoice::Login => self.handle_slash_command(SlashCommand::Login {
+                email: wizard.email,
+                password: wizard.password,
+            }),
+            AccountChoice::Register => self.handle_slash_command(SlashCommand::Register {
+                email: wizard.email,
+                username: wizard.username,
+                password: wizard.password,
+                display_name: None,
+            }),
+            AccountChoice::Skip => {
+                self.wizard = None;
+                self.messages.push(Message::system(
+                    "✓ Setup complete. Use /login or /register when you're ready.".to_string()
+                ));
+            }
+        }
+    }
+
+    /// Whether the input box currently holds a secret that must be masked
+    pub fn is_secret_input(&self) -> bool {
+        self.pending_key.is_some() || self.wizard.as_ref().is_some_and(|w| w.is_secret())
+    }
+
+    /// The cursor in `input`, kept inside it and on a char boundary
+    pub fn input_cursor(&self) -> usize {
+        let mut cursor = self.input_cursor.unwrap_or(self.input.len()).min(self.input.len());
+        while !self.input.is_char_boundary(cursor) {
+            cursor -= 1;
+        }
+        cursor
+    }
+
+    /// Apply a readline-style edit to the input. Secrets being typed never
+    /// reach the kill ring.
+    pub fn edit_input(&mut self, edit: Edit) {
+        let mut cursor = self.input_cursor();
+        if self.is_secret_input() {
+            LineEditor::default().apply(edit, &mut self.input, &mut cursor);
+        } else {
+            self.line_editor.apply(edit, &mut self.input, &mut cursor);
+        }
+        self.input_cursor = (cursor < self.input.len()).then_some(cursor);
+    }
+
+    /// Live usage hint for a slash command being typed
+    pub fn command_hint(&self) -> Option<String> {
+        if self.wizard.is_some() || self.pending_key.is_some() {
+            return None;
+        }
+        SlashCommand::usage_hint(&self.input)
+    }
+
+    fn handle_telemetry_command(&mut self, action: TelemetryAction) {
+        match action {
+            TelemetryAction::On | TelemetryAction::Off => {
+                let enabled = action == TelemetryAction::On;
+                self.config.telemetry.enabled = enabled;
+                self.telemetry.set_enabled(enabled);
+                if let Err(e) = self.config.save() {
+                    self.messages.push(Message::error(format!("Failed to save config: {}", e)));
+                }
+                let message = if enabled {
+                    "✓ Telemetry on. QHub now counts which commands you use, AI error types and \
+                     reply times, and uploads the counts in batches. No messages, code, email or \
+                     account details are collected. /telemetry status shows exactly what is pending."
+                } else {
+                    "✓ Telemetry off. Nothing more is recorded or uploaded. \
+                     /telemetry purge deletes counts collected so far."
+                };
+                self.messages.push(Message::system(message.to_string()));
+            }
+            TelemetryAction::Status => {
+                let metrics = self.telemetry.metrics();
+                let mut lines = vec![format!(
+                    "Telemetry is {}. {} event(s) waiting to upload.",
+                    if self.telemetry.is_enabled() { "on" } else { "off (default)" },
+                    metrics.total()
+                )];
+                for (label, counts) in [
+                    ("Commands", &metrics.commands),
+                    ("Errors", &metrics.errors),
+                    ("Latency", &metrics.latency),
+                ] {
+                    if !counts.is_empty() {
+                        let counts: Vec<String> = counts.iter().map(|(k, n)| format!("{} {}", k, n)).collect();
+                        lines.push(format!("{}: {}", label, counts.join(", ")));
+                    }
+                }
+                self.messages.push(Message::system(lines.join("\n")));
+            }
+            TelemetryAction::Purge => match self.telemetry.purge() {
+                Ok(()) => self.messages.push(Message::system(
+                    "✓ Deleted all locally collected telemetry.".to_string()
+                )),
+                Err(e) => self.messages.push(Message::error(format!("Could not purge telemetry: {}", e))),
+            },
+        }
+    }
+
+    fn handle_keys_command(&mut self, action: KeysAction) {
+        match action {
+            KeysAction::Set(kind) => {
+                self.pending_key = Some(kind);
+                self.messages.push(Message::system(format!(
+                    "Paste your {} and press Enter (input is hidden). Submit an empty line to cancel.",
+                    kind.label()
+                )));
+            }
+            KeysAction::Clear(kind) => match self.config.clear_api_key(kind) {
+                Ok(()) => {
+                    if kind == ApiKeyKind::Ai {
+                        self.ai_client = Arc::new(DeepSeekClient::from_config(&self.config));
+                    }
+                    self.refresh_capabilities();
+                    let mut note = format!("✓ Cleared {}", kind.label());
+                    if std::env::var(kind.env_var()).is_ok() {
+                        note.push_str(&format!(
+                            " (still set via {} in the environment)",
+                            kind.env_var()
+                        ));
+                    }
+                    self.messages.push(Message::system(note));
+                }
+                Err(e) => self.messages.push(Message::error(
+                    format!("Failed to clear {}: {}", kind.label(), e)
+                )),
+            },
+            KeysAction::Status => {
+                let mut lines = vec!["API keys:".to_string()];
+                for kind in [ApiKeyKind::Ai, ApiKeyKind::Quantum] {
+                    let status = match self.config.api_key_source(kind) {
+                        Some(source) => format!("✓ Configured ({})", source.label()),
+                        None => "✗ Not set".to_string(),
+                    };
+                    lines.push(format!("  {:<18} {}", kind.label(), status));
+                }
+                let storage = if self.config.security.use_keychain {
+                    "keychain"
+                } else {
+                    "config file"
+                };
+                lines.push(format!("New keys are stored in the {}.", storage));
+                self.messages.push(Message::system(lines.join("\n")));
             }
-            SlashCommand::Unknown(cmd) => {
+        }
+    }
+
+    fn handle_key_entry(&mut self, kind: ApiKeyKind, key: &str) {
+        if key.is_empty() {
+            self.messages.push(Message::system("Key entry cancelled.".to_string()));
+            return;
+        }
+
+        let shape_check = match kind {
+            ApiKeyKind::Ai => wizard::validate_api_key(&self.config.ai.provider, key),
+            ApiKeyKind::Quantum => wizard::validate_quantum_token(key),
+        };
+        if let Err(e) = shape_check {
+            self.messages.push(Message::error(format!("{} rejected: {}", kind.label(), e)));
+            return;
+        }
+
+        let source = match self.config.set_api_key(kind, key.to_string()) {
+            Ok(source) => source,
+            Err(e) => {
                 self.messages.push(Message::error(
-                    format!("Unknown command or invalid syntax: /{}. Type /help for available commands.", cmd)
+                    format!("Failed to store {}: {}", kind.label(), e)
                 ));
+                return;
+            }
+        };
+        self.refresh_capabilities();
+        self.messages.push(Message::system(format!(
+            "✓ Saved {} to the {}. Testing it...",
+            kind.label(),
+            source.label()
+        )));
+
+        // Re-test the key straight away with a lightweight call
+        let (tx, rx) = mpsc::channel(1);
+        self.key_test_rx = Some(rx);
+        let key = key.to_string();
+        match kind {
+            ApiKeyKind::Ai => {
+                let client = DeepSeekClient::new(key, &self.config.ai, &self.config.network);
+                self.ai_client = Arc::new(client.clone());
+                self.tasks.spawn_tracked(Operation::KeyTest, async move {
+                    let result = client.verify_key().await.map_err(|e| e.to_string());
+                    let _ = tx.send((kind, result)).await;
+                });
+            }
+            ApiKeyKind::Quantum => {
+                let client = IbmQuantumClient::new(key, &self.config.network);
+                self.tasks.spawn_tracked(Operation::KeyTest, async move {
+                    let result = client.verify_token().await.map_err(|e| quantum_error_message(&e));
+                    let _ = tx.send((kind, result)).await;
+                });
             }
         }
-        self.input.clear();
-        self.scroll_to_bottom();
     }
 
-    pub fn scroll_up(&mut self) {
-        if self.scroll_offset > 0 {
-            self.scroll_offset -= 1;
+    pub fn check_key_test_response(&mut self) {
+        if let Some(ref mut rx) = self.key_test_rx {
+            match rx.try_recv() {
+                Ok((kind, Ok(()))) => {
+                    self.messages.push(Message::system(format!("✓ {} works", kind.label())));
+                    self.key_test_rx = None;
+                    self.scroll_to_bottom();
+                }
+                Ok((kind, Err(error))) => {
+                    self.messages.push(Message::error(format!(
+                        "{} was saved but the test call failed: {}",
+                        kind.label(),
+                        error
+                    )));
+                    self.key_test_rx = None;
+                    self.scroll_to_bottom();
+                }
+                Err(mpsc::error::TryRecvError::Empty) => {}
+                Err(mpsc::error::TryRecvError::Disconnected) => {
+                    self.key_test_rx = None;
+                }
+            }
         }
     }
 
+    pub fn scroll_up(&mut self) {
+        self.scroll.scroll_up(1);
+    }
+
     pub fn scroll_down(&mut self) {
-        self.scroll_offset += 1;
+        self.scroll.scroll_down(1);
     }
 
     pub fn scroll_to_bottom(&mut self) {
-        // Will be calculated properly in UI rendering
-        self.scroll_offset = usize::MAX;
+        self.scroll.scroll_to_bottom();
+    }
+
+    /// Handle a left click at a terminal cell
+    pub fn handle_click(&mut self, column: u16, row: u16) {
+        let now = Instant::now();
+        let double = self.last_click.is_some_and(|(at, c, r)| {
+            (c, r) == (column, row) && now.duration_since(at) <= DOUBLE_CLICK
+        });
+        self.last_click = Some((now, column, row));
+
+        match self.hits.target_at(column, row) {
+            Some(ClickTarget::Input) => self.input_mode = InputMode::Editing,
+            Some(ClickTarget::Suggestion(index)) => {
+                self.selected_suggestion = index;
+                self.apply_suggestion();
+            }
+            Some(ClickTarget::NewMessages) => self.scroll_to_bottom(),
+            Some(ClickTarget::CopyMessage(index)) => self.copy_message(index),
+            Some(ClickTarget::Row(target)) => match target.code_block {
+                Some(block) if double => self.copy_code_block(target.message, block, false),
+                _ => {
+                    // Clicking the selected message again deselects it
+                    self.selected_message = (self.selected_message != Some(target.message))
+                        .then_some(target.message);
+                }
+            },
+            None => {}
+        }
+    }
+
+    /// (message, block) of the last code block in an AI reply
+    fn last_code_block(&self) -> Option<(usize, usize)> {
+        self.messages.iter().enumerate().rev().find_map(|(i, m)| {
+            let count = clipboard::code_blocks(&m.content).len();
+            (m.role == MessageRole::Assistant && count > 0).then(|| (i, count - 1))
+        })
+    }
+
+    /// The last code block in an AI reply
+    fn last_block(&self) -> Option<CodeBlock> {
+        let (message, block) = self.last_code_block()?;
+        clipboard::fenced_blocks(&self.messages[message].content).into_iter().nth(block)
+    }
+
+    /// Content of the last code block in an AI reply
+    fn last_code(&self) -> Option<String> {
+        self.last_block().map(|block| block.code)
+    }
+
+    /// Import the result of /edit once the TUI is back
+    pub fn finish_edit(&mut self, request: EditRequest, outcome: anyhow::Result<EditOutcome>) {
+        let code = match outcome {
+            Ok(EditOutcome::Edited(code)) | Ok(EditOutcome::Unchanged(code)) => code,
+            Ok(EditOutcome::Empty) => {
+                self.messages.push(Message::system(
+                    "The edited file was empty - nothing imported.".to_string()
+                ));
+                self.scroll_to_bottom();
+                return;
+            }
+            Err(e) => {
+                self.messages.push(Message::error(format!("Editing failed: {}", e)));
+                self.scroll_to_bottom();
+                return;
+            }
+        };
+
+        // Attach the code to the conversation so follow-up questions can refer to it
+        let content = format!("```{}\n{}\n```", request.language, code.trim_end());
+        self.conversation_history.push(ChatMessage {
+            role: "user".to_string(),
+            content: content.clone(),
+        });
+        self.messages.push(Message::user(content));
+
+        if request.action == EditAction::Run {
+            let note = match request.language {
+                "text" => Message::system(
+                    "Only OpenQASM and Qiskit programs can be checked before running; the code was added to the chat."
+                        .to_string(),
+                ),
+                language => check_circuit(&code, language),
+            };
+            self.messages.push(note);
+        }
+        self.scroll_to_bottom();
+    }
+
+    /// Hand a code block to $VISUAL/$EDITOR; the main loop suspends the TUI
+    fn open_editor(&mut self, action: EditAction, block: &CodeBlock) {
+        let Some(editor_cmd) = editor::editor_command() else {
+            self.messages.push(Message::error(
+                "Set $VISUAL or $EDITOR to choose an editor for /edit.".to_string()
+            ));
+            return;
+        };
+        match editor::prepare(action, editor_cmd, block) {
+            Ok(request) => self.pending_edit = Some(request),
+            Err(e) => self.messages.push(Message::error(format!("Failed to open editor: {}", e))),
+        }
+    }
+
+    /// Ctrl+B: number the code blocks on screen for the keyboard
+    pub fn start_block_pick(&mut self) {
+        let visible: Vec<BlockId> = self.hits.code_block_spans().into_iter().map(|(id, _)| id).collect();
+        self.block_picker = BlockPicker::new(visible);
+        if self.block_picker.is_none() {
+            let message = if self.last_code_block().is_some() {
+                "No code blocks on screen - scroll to one, then press Ctrl+B."
+            } else {
+                "No code blocks yet."
+            };
+            self.messages.push(Message::system(message.to_string()));
+            self.scroll_to_bottom();
+        }
+    }
+
+    /// Ctrl+P: recent commands, every command and the recent prompts, to
+    /// search. Not while the input line is answering a question.
+    pub fn open_palette(&mut self) {
+        if self.locked || self.wizard.is_some() || self.pending_save.is_some() || self.is_secret_input() {
+            return;
+        }
+        let mut recent: Vec<&String> = Vec::new();
+        for entry in self.input_history.entries().iter().rev().filter(|e| e.starts_with('/')) {
+            if recent.len() == palette::RECENT_COMMANDS {
+                break;
+            }
+            if !recent.contains(&entry) {
+                recent.push(entry);
+            }
+        }
+        let now = Utc::now();
+        let boost = |line: &str| self.command_usage.boost(line.split_whitespace().next().unwrap_or(line), now);
+        let mut items: Vec<Item> =
+            recent.into_iter().map(|line| Item::recent(line.clone()).boosted(boost(line))).collect();
+        items.extend(
+            self.get_available_commands()
+                .into_iter()
+                .map(|(name, description)| Item::command(name, description).boosted(boost(name))),
+        );
+        let mut prompts: Vec<&str> = Vec::new();
+        for message in self.messages.iter().rev().filter(|m| m.role == MessageRole::User) {
+            if prompts.len() == palette::RECENT_PROMPTS {
+                break;
+            }
+            if !prompts.contains(&message.content.as_str()) {
+                prompts.push(&message.content);
+            }
+        }
+        items.extend(prompts.into_iter().map(|p| Item::prompt(p.to_string())));
+        self.palette = Some(Palette::new(items));
+    }
+
+    /// Ctrl+R: search earlier inputs. Not while the input line is answering
+    /// a question.
+    pub fn start_history_search(&mut self) {
+        if self.locked || self.wizard.is_some() || self.pending_save.is_some() || self.is_secret_input() {
+            return;
+        }
+        self.history_search = Some(Search::new(self.input.clone()));
+    }
+
+    /// A key press while Ctrl+R search is open
+    pub fn handle_history_search_key(&mut self, key: &KeyEvent) {
+        let Some(search) = self.history_search.as_mut() else {
+            return;
+        };
+        let text = match search.key(key, self.input_history.entries()) {
+            SearchResult::Pending => return,
+            SearchResult::Cancel(text) | SearchResult::Accept(text) => text,
+        };
+        self.history_search = None;
+        self.input = text;
+        self.input_cursor = None;
+        self.update_suggestions();
+    }
+
+    /// The Ctrl+R query, and the entry it matches with where
+    pub fn history_search_view(&self) -> Option<(&str, Option<input_history::Match<'_>>)> {
+        let search = self.history_search.as_ref()?;
+        Some((search.query(), search.current(self.input_history.entries())))
+    }
+
+    /// A key press while the palette is open
+    pub fn handle_palette_key(&mut self, key: &KeyEvent) {
+        let Some(palette) = self.palette.as_mut() else {
+            return;
+        };
+        match palette.key(key) {
+            PaletteResult::Pending => {}
+            PaletteResult::Cancel => self.palette = None,
+            PaletteResult::Run(command) => {
+                self.palette = None;
+                self.input = command;
+                self.input_cursor = None;
+                self.submit_input();
+            }
+            PaletteResult::Insert(text) => {
+                self.palette = None;
+                self.input = text;
+                self.input_cursor = None;
+                self.update_suggestions();
+            }
+        }
+    }
+
+    /// A key press while the block picker is open
+    pub fn handle_block_pick_key(&mut self, key: char) {
+        let Some(picker) = self.block_picker.as_mut() else {
+            return;
+        };
+        match picker.key(key) {
+            PickResult::Pending => {}
+            PickResult::Cancel => self.block_picker = None,
+            PickResult::Act(block, action) => {
+                self.block_picker = None;
+                self.act_on_block(block, action);
+            }
+        }
+    }
+
+    fn act_on_block(&mut self, (message, block): BlockId, action: PickAction) {
+        let Some(code) = self
+            .messages
+            .get(message)
+            .and_then(|m| clipboard::fenced_blocks(&m.content).into_iter().nth(block))
+        else {
+            return;
+        };
+        match action {
+            PickAction::Copy => self.copy_code_block(message, block, false),
+            PickAction::Edit => self.open_editor(EditAction::Code, &code),
+            PickAction::Run => {
+                let note = match code.language {
+                    "text" => Message::error("Only OpenQASM and Qiskit programs can be checked before running.".to_string()),
+                    language => check_circuit(&code.code, language),
+                };
+                self.messages.push(note);
+                self.scroll_to_bottom();
+            }
+            PickAction::Save => {
+                let note = match save_code(&code) {
+                    Ok(path) => {
+                        self.stats.circuits_saved += 1;
+                        Message::system(format!("✓ Saved to {}", path.display()))
+                    }
+                    Err(e) => Message::error(format!("Failed to save code block: {}", e)),
+                };
+                self.messages.push(note);
+                self.scroll_to_bottom();
+            }
+        }
+    }
+
+    /// `/save <file> [-f]`: write the last code block to `path`. An existing
+    /// file is only replaced with -f, once its diff has been confirmed.
+    fn save_to(&mut self, mut path: PathBuf, force: bool) {
+        let Some(CodeBlock { language, code }) = self.last_block() else {
+            self.messages.push(Message::error("No code block to save yet.".to_string()));
+            return;
+        };
+        // `/save bell` picks the extension for the block's language
+        if path.extension().is_none() {
+            path.set_extension(language::extension(language));
+        }
+        let existing = match std::fs::read_to_string(&path) {
+            Ok(existing) => existing,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return self.write_code(&path, &code),
+            Err(e) => {
+                self.messages.push(Message::error(format!("Could not read {}: {}", path.display(), e)));
+                return;
+            }
+        };
+        let shown = path.display().to_string();
+        if !force {
+            self.messages.push(Message::error(format!(
+                "{} already exists. /save {} -f shows the changes and asks before overwriting; /diff {} only shows them.",
+                shown, shown, shown
+            )));
+            return;
+        }
+        match diff::render(&existing, &code, &shown) {
+            None => self.messages.push(Message::system(format!("{} already matches the code block.", shown))),
+            Some(diff) => {
+                self.messages.push(Message::system(format!("{}\n\nOverwrite {}? (y/n)", diff, shown)));
+                self.pending_save = Some((path, code));
+            }
+        }
+    }
+
+    /// `/diff <file>`: how the last code block differs from `path`
+    fn show_diff(&mut self, path: PathBuf) {
+        let Some(code) = self.last_code() else {
+            self.messages.push(Message::error("No code block to compare yet.".to_string()));
+            return;
+        };
+        let shown = path.display().to_string();
+        self.messages.push(match std::fs::read_to_string(&path) {
+            Ok(existing) => match diff::render(&existing, &code, &shown) {
+                Some(diff) => Message::system(diff),
+                None => Message::system(format!("{} matches the code block.", shown)),
+            },
+            Err(e) => Message::error(format!("Could not read {}: {}", shown, e)),
+        });
+    }
+
+    fn write_code(&mut self, path: &Path, code: &str) {
+        self.messages.push(match std::fs::write(path, code) {
+            Ok(()) => {
+                self.stats.circuits_saved += 1;
+                Message::system(format!("✓ Saved to {}", path.display()))
+            }
+            Err(e) => Message::error(format!("Failed to save {}: {}", path.display(), e)),
+        });
+    }
+
+    /// Put one code block of a message on the clipboard
+    fn copy_code_block(&mut self, message: usize, block: usize, pretty: bool) {
+        let Some(code) = self
+            .messages
+            .get(message)
+            .and_then(|m| clipboard::code_blocks(&m.content).into_iter().nth(block))
+        else {
+            return;
+        };
+        // The block as sent, which for JSON is usually the compact form
+        let (code, what) = match pretty.then(|| json_view::pretty(&code)).flatten() {
+            Some(indented) => (indented, "code block, indented"),
+            None => (code, "code block"),
+        };
+        match clipboard::copy(&code) {
+            Ok(()) => self.messages.push(Message::system(format!(
+                "✓ Copied {} ({} lines)",
+                what,
+                code.lines().count()
+            ))),
+            Err(e) => self.messages.push(Message::error(format!("Failed to copy: {}", e))),
+        }
+        self.scroll_to_bottom();
     }
     
+    /// The copy button: a reply's main code block, or the whole reply when it
+    /// has none. Terminals without clipboard support are left alone.
+    fn copy_message(&mut self, index: usize) {
+        let Some(message) = self.messages.get(index) else {
+            return;
+        };
+        let (text, what) = match clipboard::primary_code_block(&message.content) {
+            Some(code) => (code, "code block"),
+            None => (message.content.trim().to_string(), "reply"),
+        };
+        if clipboard::copy(&text).is_ok() {
+            self.messages.push(Message::system(format!(
+                "✓ Copied {} ({} lines)",
+                what,
+                text.lines().count()
+            )));
+            self.scroll_to_bottom();
+        }
+    }
+
+    /// Tokens the conversation may use: the model's context window minus
+    /// room for the reply
+    fn context_budget(&self) -> usize {
+        let window = self
+            .config
+            .ai
+            .context_window
+            .unwrap_or_else(|| tokens::context_window(self.active_model()));
+        window.saturating_sub(self.session_sampling.max_tokens.unwrap_or(self.config.ai.max_tokens) as usize)
+    }
+
     /// Check if user is authenticated
     pub fn is_authenticated(&self) -> bool {
         self.user_email.is_some()
@@ -732,19 +4438,52 @@ Start generating quantum circuits:
         let mut commands = vec![
             ("/help", "Show all available commands"),
             ("/status", "Show account and system status"),
+            ("/stats", "Show session activity dashboard"),
+            ("/backends", "List IBM Quantum backends with queue depth"),
+            ("/backend-info", "Show a backend's calibration details (usage: /backend-info <name>)"),
+            ("/backend", "Show or set the default backend (usage: /backend set <name>)"),
+            ("/recommend", "Recommend a backend for a circuit (usage: /recommend [file.qasm])"),
+            ("/calc", "Apply gates on the local simulator (usage: /calc H 0; CX 0 1)"),
+            ("/compare", "Compare two job results (usage: /compare <job_a> <job_b>)"),
+            ("/export", "Write a job's counts to CSV or JSON (usage: /export counts <job id> <file>)"),
+            ("/sweep", "Show a parameter sweep's progress (usage: /sweep status <id>)"),
+            ("/watch", "Follow an IBM Quantum job's queue position (usage: /watch <job id>)"),
+            ("/share", "Share the chat or a code block (usage: /share [code] [private|unlisted] [expires=7d|never])"),
+            ("/keys", "Manage API keys (usage: /keys set|clear ai|quantum, /keys status)"),
+            ("/copy", "Copy the last code block to the clipboard (--pretty indents JSON)"),
+            ("/save", "Write the last code block to a file (usage: /save <file> [-f])"),
+            ("/diff", "Compare the last code block with a file (usage: /diff <file>)"),
+            ("/resume", "Reload the last autosaved session"),
+            ("/retry", "Send the last prompt again after an empty or failed reply"),
+            ("/cancel", "Stop the AI request in flight and get the prompt back"),
+            ("/continue", "Ask for the rest of a truncated reply"),
+            ("/regen", "Ask for the last reply again (usage: /regen [prev | --model <name>])"),
+            ("/model", "Show or switch the model for this session (usage: /model [<name> | default])"),
+            ("/models", "List available models (usage: /models [refresh])"),
+            ("/set", "Change temperature, top_p or max_tokens for this session (usage: /set <parameter> <value | default>)"),
+            ("/edit", "Edit the last code block in $EDITOR (usage: /edit code|run)"),
+            ("/run-prompt", "Send a prompt file (usage: /run-prompt <path> [name=value ...])"),
+            ("/telemetry", "Anonymous usage metrics (usage: /telemetry on|off|status|purge)"),
+            ("/reload", "Apply edits to the config file without restarting"),
             ("/clear", "Clear the message history"),
+            ("/cache clear", "Forget the replies ai.cache kept for repeated prompts"),
             ("/quit", "Exit QHub"),
         ];
         
-        if self.is_authenticated() {
+        if self.capabilities.is_guest() {
+            // Accounts need a backend; offer setup instead of login/register
+            commands.push(("/connect", "Set up an AI key or account"));
+        } else if self.is_authenticated() {
             commands.extend_from_slice(&[
                 ("/logout", "Log out of your account"),
+                ("/accounts", "List saved accounts (usage: /accounts [switch <email>])"),
+                ("/profile", "Change your display name (usage: /profile set display-name <name>)"),
                 ("/upgrade", "Upgrade your subscription tier"),
             ]);
         } else {
             commands.extend_from_slice(&[
                 ("/login", "Log in to your account (usage: /login <email> <password>)"),
-                ("/register", "Create a new account (usage: /register <email> <username> <password>)"),
+                ("/register", "Create a new account (usage: /register <email> <username> <password> [display name])"),
             ]);
         }
         
@@ -762,16 +4501,37 @@ Start generating quantum circuits:
             return;
         }
         
+        // Model names complete after /model
+        if let Some(prefix) = self.input.trim_start().strip_prefix("/model ") {
+            self.suggestions = self
+                .model_catalog
+                .complete(prefix.trim())
+                .into_iter()
+                .map(|model| format!("/model {} - switch for this session", model))
+                .collect();
+            self.show_suggestions = !self.suggestions.is_empty();
+            if self.selected_suggestion >= self.suggestions.len() {
+                self.selected_suggestion = 0;
+            }
+            return;
+        }
+
         // Get the command part (before any space)
         let cmd_part = input[1..].split_whitespace().next().unwrap_or(&input[1..]);
         
-        // Find matching commands
-        let commands = self.get_available_commands();
-        self.suggestions = commands
+        // Fuzzy-match command names, boosted by how often and lately each ran
+        let now = Utc::now();
+        let mut scored: Vec<(i64, String)> = self
+            .get_available_commands()
             .iter()
-            .filter(|(cmd, _)| cmd[1..].starts_with(cmd_part))
-            .map(|(cmd, desc)| format!("{} - {}", cmd, desc))
+            .filter_map(|(cmd, desc)| {
+                let score = self.command_usage.score(cmd_part, cmd, now)?;
+                Some((score, format!("{} - {}", cmd, desc)))
+            })
             .collect();
+        // Stable, so equal scores keep the list's own order
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        self.suggestions = scored.into_iter().map(|(_, suggestion)| suggestion).collect();
         
         self.show_suggestions = !self.suggestions.is_empty();
         
@@ -798,6 +4558,21 @@ Start generating quantum circuits:
         }
     }
     
+    /// The suggestion list is open with something to pick
+    pub fn suggesting(&self) -> bool {
+        self.show_suggestions && !self.suggestions.is_empty()
+    }
+
+    /// Input Enter still submits while a reply is on its way: answers to a
+    /// key or overwrite prompt, prompts queued behind a rate-limited request,
+    /// and /cancel
+    pub fn takes_input_while_loading(&self) -> bool {
+        self.pending_key.is_some()
+            || self.pending_save.is_some()
+            || (self.ai_queue.held().is_some() && !self.input.trim().is_empty())
+            || matches!(SlashCommand::parse(self.input.trim()), Some(SlashCommand::Cancel))
+    }
+
     /// Apply the selected suggestion (Tab or Enter on suggestion)
     pub fn apply_suggestion(&mut self) {
         if self.show_suggestions && !self.suggestions.is_empty() {
@@ -805,8 +4580,9 @@ Start generating quantum circuits:
             // Extract just the command part (before " - ")
             if let Some(cmd) = suggestion.split(" - ").next() {
                 self.input = cmd.to_string();
+                self.input_cursor = None;
                 // Add space for commands that need arguments
-                if matches!(cmd, "/login" | "/register" | "/upgrade") {
+                if matches!(cmd, "/login" | "/register" | "/upgrade" | "/keys" | "/run-prompt") {
                     self.input.push(' ');
                 }
             }
@@ -816,3 +4592,461 @@ Start generating quantum circuits:
         }
     }
 }
+
+/// What to tell the user about a failed quantum provider call
+fn quantum_error_message(error: &anyhow::Error) -> String {
+    let Some(error) = error.downcast_ref::<QuantumError>() else {
+        return error.to_string();
+    };
+    match error {
+        QuantumError::AuthInvalid => {
+            "IBM Quantum rejected the API token — set a new one with /keys set quantum".to_string()
+        }
+        QuantumError::BackendOffline { backend, until } => format!(
+            "Backend {} is offline{} — try /backends to pick another",
+            backend.as_deref().unwrap_or("(unnamed)"),
+            until.as_ref().map(|u| format!(" until {}", u)).unwrap_or_default()
+        ),
+        QuantumError::QueueLimitReached => {
+            "You have reached IBM Quantum's limit of pending jobs — wait for some to finish, \
+             or use the local simulator with `qhub run --offline`"
+                .to_string()
+        }
+        QuantumError::CircuitTooLarge { qubits, max } => format!(
+            "The circuit needs {} qubits but the backend has {} — pick a larger one with /backends",
+            qubits, max
+        ),
+        QuantumError::TranspileFailed(detail) => format!(
+            "IBM Quantum could not transpile the circuit: {} — stick to gates the backend supports",
+            detail
+        ),
+        QuantumError::ProviderInternal(raw) => format!("IBM Quantum returned an unexpected error:\n{}", raw),
+    }
+}
+
+/// Parse an OpenQASM program the way `qhub run` would before executing it
+fn check_circuit(code: &str, language: &str) -> Message {
+    let (code, what) = match language {
+        "python" => match python::to_qasm(code) {
+            Ok(qasm) => (qasm, "Qiskit program as "),
+            Err(e) => return Message::error(format!("Qiskit program: {}", e)),
+        },
+        _ => (code.to_string(), ""),
+    };
+    match qasm::parse(&code) {
+        Ok(circuit) => Message::system(format!(
+            "✓ Parsed {}{}. Running circuits from QHub is not available yet.",
+            what,
+            circuit.summary()
+        )),
+        Err(e) => Message::error(format!("OpenQASM error: {}", e)),
+    }
+}
+
+/// Write a code block to ~/.qhub/files with an extension for its language
+fn save_code(block: &CodeBlock) -> Result<PathBuf> {
+    let dir = Config::files_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let extension = language::extension(block.language);
+    let stamp = Local::now().format("%Y%m%d-%H%M%S");
+    let mut path = dir.join(format!("block-{}.{}", stamp, extension));
+    let mut n = 2;
+    while path.exists() {
+        path = dir.join(format!("block-{}-{}.{}", stamp, n, extension));
+        n += 1;
+    }
+    std::fs::write(&path, &block.code)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tui::services::fake::{FakeAi, FakeAuth};
+
+    /// A config file in a fresh temp directory
+    fn temp_config(contents: &str) -> ConfigLocator {
+        let dir = std::env::temp_dir().join(format!("qhub-app-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("config.toml"), contents).unwrap();
+        ConfigLocator::at(dir.join("config.toml")).unwrap()
+    }
+
+    /// Run `check` until `done`, giving the spawned request time to answer
+    async fn wait_for(app: &mut App, check: fn(&mut App), done: fn(&App) -> bool) {
+        for _ in 0..200 {
+            check(app);
+            if done(app) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        panic!("the request never finished");
+    }
+
+    #[tokio::test]
+    async fn test_calc_counts_as_a_circuit_run() {
+        let config = temp_config("[ai]\napi_key = \"key\"\n");
+        let mut app = App::new(AppOptions { new_session: true, config: config.clone(), ..AppOptions::default() });
+        app.handle_slash_command(SlashCommand::parse("/calc H 0; CX 0 1").unwrap());
+        assert_eq!(app.messages.last().unwrap().role, MessageRole::System);
+        app.handle_slash_command(SlashCommand::parse("/calc FOO 0").unwrap());
+        assert_eq!(app.messages.last().unwrap().role, MessageRole::Error);
+        assert_eq!(app.stats.circuits_run, 1);
+        let _ = std::fs::remove_dir_all(config.path().unwrap().parent().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_lock_drops_a_pending_save() {
+        let config = temp_config("[ai]\napi_key = \"key\"\n");
+        let mut app = App::new(AppOptions { new_session: true, config: config.clone(), ..AppOptions::default() });
+        let path = config.path().unwrap().parent().unwrap().join("bell.qasm");
+        std::fs::write(&path, "old").unwrap();
+        app.pending_save = Some((path.clone(), "new".to_string()));
+
+        app.lock_for_inactivity();
+        app.input = "y".to_string();
+        app.submit_input();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "old");
+        assert!(app.pending_save.is_none());
+        assert_eq!(app.messages.last().unwrap().role, MessageRole::Error);
+        let _ = std::fs::remove_dir_all(config.path().unwrap().parent().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_share_redacts_keys_kept_in_the_keychain() {
+        crate::config::secrets::test_keychain::install();
+        let config = temp_config("[ai]\napi_key = \"key\"\n[security]\nuse_keychain = true\n");
+        let mut app = App::new(AppOptions { new_session: true, config: config.clone(), ..AppOptions::default() });
+        // Nothing about it looks like a key, so only knowing it catches it
+        let key = "correct horse battery staple quantum";
+        app.config.set_api_key(ApiKeyKind::Ai, key.to_string()).unwrap();
+        assert_eq!(app.config.ai.api_key, None);
+        assert!(app.share_secrets().iter().any(|s| s == key));
+
+        let conversation = [ChatMessage { role: "user".to_string(), content: format!("my key is {}", key) }];
+        let (request, redacted) = share::request(&ShareOptions::default(), &conversation, None, &app.share_secrets());
+        assert_eq!(redacted, 1);
+        assert!(!serde_json::to_string(&request).unwrap().contains(key));
+        app.config.clear_api_key(ApiKeyKind::Ai).unwrap();
+        let _ = std::fs::remove_dir_all(config.path().unwrap().parent().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_prompts_are_answered_and_failures_retried() {
+        let config = temp_config("[ai]\napi_key = \"key\"\ninject_backend_context = false\n");
+        let mut app = App::new(AppOptions { new_session: true, config: config.clone(), ..AppOptions::default() });
+        let ai = Arc::new(FakeAi::default());
+        ai.fail("connection reset by peer").reply("Here is a Bell state.");
+        app.ai_client = ai.clone();
+
+        app.input = "Make a Bell state".to_string();
+        app.submit_input();
+        assert!(app.is_loading);
+        assert!(app.input.is_empty());
+        wait_for(&mut app, App::check_ai_response, |app| !app.is_loading).await;
+        let error = app.messages.last().unwrap();
+        assert_eq!(error.role, MessageRole::Error);
+        assert!(error.content.starts_with("Network error."), "{}", error.content);
+        assert_ne!(app.conversation_history.last().unwrap().role, "user");
+
+        app.handle_slash_command(SlashCommand::Retry);
+        wait_for(&mut app, App::check_ai_response, |app| !app.is_loading).await;
+        let reply = app.messages.last().unwrap();
+        assert_eq!(reply.role, MessageRole::Assistant);
+        assert_eq!(reply.content, "Here is a Bell state.");
+        assert_eq!(app.conversation_history.last().unwrap().content, "Here is a Bell state.");
+        assert_eq!(ai.prompts(), ["Make a Bell state", "Make a Bell state"]);
+
+        // Nothing is left to retry once a reply came
+        app.handle_slash_command(SlashCommand::Retry);
+        assert_eq!(app.messages.last().unwrap().content, "Nothing to retry.");
+        assert_eq!(ai.prompts().len(), 2);
+        let _ = std::fs::remove_dir_all(config.path().unwrap().parent().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_login_and_logout_go_through_auth() {
+        let config = temp_config("api_url = \"https://qhub.example\"\n[ai]\napi_key = \"key\"\ninject_backend_context = false\n");
+        let mut app = App::new(AppOptions { new_session: true, config: config.clone(), ..AppOptions::default() });
+        let ai = Arc::new(FakeAi::default());
+        let auth = Arc::new(FakeAuth::new("ada@example.com", "hunter22"));
+        app.ai_client = ai.clone();
+        app.auth = auth.clone();
+
+        // With an account backend, chat waits for a login
+        app.input = "hello".to_string();
+        app.submit_input();
+        assert!(app.messages.last().unwrap().content.contains("Authentication required"));
+        assert!(ai.prompts().is_empty());
+
+        app.handle_slash_command(SlashCommand::parse("/login ada@example.com wrong").unwrap());
+        assert!(app.is_loading);
+        wait_for(&mut app, App::check_auth_response, |app| !app.is_loading).await;
+        assert_eq!(app.messages.last().unwrap().content, "Invalid email or password. Please try again.");
+        assert_eq!(app.user_email, None);
+
+        app.handle_slash_command(SlashCommand::parse("/login ada@example.com hunter22").unwrap());
+        wait_for(&mut app, App::check_auth_response, |app| !app.is_loading).await;
+        assert_eq!(app.user_email.as_deref(), Some("ada@example.com"));
+        assert_eq!(app.user_tier, "pro");
+        assert_eq!(app.api_client.token(), Some(auth.token().as_str()));
+        assert!(app.messages.last().unwrap().content.starts_with("✓ Logged in successfully"));
+        let saved = std::fs::read_to_string(config.path().unwrap()).unwrap();
+        assert!(saved.contains("ada@example.com"), "{}", saved);
+
+        app.handle_slash_command(SlashCommand::Logout);
+        wait_for(&mut app, App::check_logout_response, |app| app.logout_rx.is_none()).await;
+        assert_eq!(*auth.logged_out.lock().unwrap(), [Some(auth.token())]);
+        assert_eq!(app.user_email, None);
+        assert!(app.config.accounts.is_empty());
+        assert!(app.messages.last().unwrap().content.starts_with("✓ Logged out successfully"));
+        let _ = std::fs::remove_dir_all(config.path().unwrap().parent().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_instances_keep_to_their_own_config_files() {
+        let first = temp_config("[ai]\napi_key = \"first-key\"\nmodel = \"deepseek/deepseek-chat\"\n");
+        let second = temp_config("[ai]\napi_key = \"second-key\"\nmodel = \"deepseek/deepseek-reasoner\"\n");
+        let mut a = App::new(AppOptions { new_session: true, config: first.clone(), ..AppOptions::default() });
+        let b = App::new(AppOptions { new_session: true, config: second.clone(), ..AppOptions::default() });
+        assert!(!a.is_first_run && !b.is_first_run);
+        assert_eq!(a.config.ai.model, "deepseek/deepseek-chat");
+        assert_eq!(b.config.ai.model, "deepseek/deepseek-reasoner");
+        assert_eq!(b.config.path().unwrap(), second.path().unwrap());
+
+        a.config.ui.scroll_speed = 7;
+        a.config.save().unwrap();
+        let first_file = std::fs::read_to_string(first.path().unwrap()).unwrap();
+        let second_file = std::fs::read_to_string(second.path().unwrap()).unwrap();
+        assert!(first_file.contains("scroll_speed = 7"), "{}", first_file);
+        assert!(!second_file.contains("scroll_speed"), "{}", second_file);
+
+        for locator in [first, second] {
+            let _ = std::fs::remove_dir_all(locator.path().unwrap().parent().unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_default_backend_is_checked_and_saved() {
+        let config = temp_config("[ai]\napi_key = \"key\"\n");
+        let mut app = App::new(AppOptions { new_session: true, config: config.clone(), ..AppOptions::default() });
+        let backend = |name: &str, operational| BackendStatus {
+            name: name.to_string(),
+            qubits: Some(127),
+            pending_jobs: Some(3),
+            operational,
+        };
+        app.backend_context.update(vec![backend("ibm_kyiv", true), backend("ibm_torino", false)], Instant::now());
+
+        app.handle_slash_command(SlashCommand::parse("/backend set ibm_nowhere").unwrap());
+        assert_eq!(app.config.quantum.default_backend, None);
+        assert!(app.messages.last().unwrap().content.contains("ibm_kyiv, ibm_torino, or simulator"));
+
+        app.handle_slash_command(SlashCommand::parse("/backend set ibm_torino").unwrap());
+        assert_eq!(app.config.quantum.default_backend.as_deref(), Some("ibm_torino"));
+        assert_eq!(app.messages.last().unwrap().content, "ibm_torino is offline at the moment.");
+        let saved = std::fs::read_to_string(config.path().unwrap()).unwrap();
+        assert!(saved.contains("default_backend = \"ibm_torino\""), "{}", saved);
+
+        app.handle_slash_command(SlashCommand::parse("/backend").unwrap());
+        assert!(app.messages.last().unwrap().content.starts_with("Default backend: ibm_torino\n"));
+        assert!(matches!(SlashCommand::parse("/backend set"), Some(SlashCommand::Unknown(_))));
+        let _ = std::fs::remove_dir_all(config.path().unwrap().parent().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_welcome_is_kept_out_of_the_transcript() {
+        let config = temp_config("[ai]\napi_key = \"key\"\n");
+        let mut app = App::new(AppOptions { new_session: true, config: config.clone(), ..AppOptions::default() });
+        let welcome = app.welcome.as_ref().unwrap().content.clone();
+        assert!(welcome.lines().count() > 1, "{}", welcome);
+        assert!(!app.messages.iter().any(|m| m.content == welcome));
+        app.handle_slash_command(SlashCommand::Clear);
+        assert!(app.welcome.is_none());
+
+        let quiet = App::new(AppOptions { new_session: true, quiet: true, config: config.clone(), ..AppOptions::default() });
+        assert_eq!(quiet.welcome.unwrap().content, "QHub ready · guest mode · /help for commands");
+        let _ = std::fs::remove_dir_all(config.path().unwrap().parent().unwrap());
+
+        let config = temp_config("[ai]\napi_key = \"key\"\n[ui]\nshow_welcome = false\n");
+        let app = App::new(AppOptions { new_session: true, config: config.clone(), ..AppOptions::default() });
+        assert_eq!(app.welcome.unwrap().content.lines().count(), 1);
+        let _ = std::fs::remove_dir_all(config.path().unwrap().parent().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_config_edits_are_reloaded() {
+        let config = temp_config("[ai]\napi_key = \"key\"\nmodel = \"deepseek/deepseek-chat\"\n");
+        let mut app = App::new(AppOptions { new_session: true, config: config.clone(), ..AppOptions::default() });
+        let path = config.path().unwrap();
+
+        std::fs::write(
+            &path,
+            "api_url = \"https://qhub.example\"\n[ai]\napi_key = \"key\"\nmodel = \"deepseek/deepseek-reasoner\"\ntemperature = 0.2\n\
+             [ui]\nscroll_speed = 7\n",
+        )
+        .unwrap();
+        app.handle_slash_command(SlashCommand::parse("/reload").unwrap());
+        assert_eq!(app.config.ui.scroll_speed, 7);
+        assert_eq!(app.ai_client.temperature(), 0.2);
+        assert_eq!(
+            app.messages.last().unwrap().content,
+            "Config reloaded: ai.model, ai.temperature, ui.scroll_speed.\nRestart QHub to apply: api_url."
+        );
+
+        // A bad value keeps the config in use
+        std::fs::write(&path, "[ui]\nscroll_speed = 0\n").unwrap();
+        app.handle_slash_command(SlashCommand::parse("/reload").unwrap());
+        assert_eq!(app.config.ui.scroll_speed, 7);
+        let error = app.messages.last().unwrap();
+        assert_eq!(error.role, MessageRole::Error);
+        assert!(error.content.contains("ui.scroll_speed"), "{}", error.content);
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_stuck_loading_is_cleared() {
+        let config = temp_config("[ai]\napi_key = \"key\"\n");
+        let mut app = App::new(AppOptions { new_session: true, config: config.clone(), ..AppOptions::default() });
+        // Loading with no task or channel behind it
+        app.is_loading = true;
+        let start = Instant::now();
+        assert!(!app.run_watchdog(start));
+        let limit = Operation::AiRequest.deadline() + LOADING_MARGIN;
+        assert!(!app.run_watchdog(start + limit - Duration::from_secs(1)));
+        assert!(app.is_loading);
+
+        assert!(app.run_watchdog(start + limit));
+        assert!(!app.is_loading);
+        assert!(app.messages.last().unwrap().content.starts_with("Stopped waiting after 10m 30s"));
+        let _ = std::fs::remove_dir_all(config.path().unwrap().parent().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_input_taken_while_loading() {
+        let config = temp_config("[ai]\napi_key = \"key\"\n");
+        let mut app = App::new(AppOptions { new_session: true, config: config.clone(), ..AppOptions::default() });
+        app.input = "what about GHZ?".to_string();
+        app.is_loading = true;
+        assert!(!app.takes_input_while_loading());
+        app.input = " /cancel ".to_string();
+        assert!(app.takes_input_while_loading());
+
+        app.input = "/he".to_string();
+        app.update_suggestions();
+        assert!(app.suggesting());
+        app.apply_suggestion();
+        assert_eq!(app.input, "/help");
+        assert!(!app.suggesting());
+        let _ = std::fs::remove_dir_all(config.path().unwrap().parent().unwrap());
+    }
+
+    #[test]
+    fn test_quantum_error_message() {
+        let offline = anyhow::Error::from(QuantumError::BackendOffline {
+            backend: Some("ibm_kyoto".into()),
+            until: Some("14:00 UTC".into()),
+        });
+        assert_eq!(
+            quantum_error_message(&offline),
+            "Backend ibm_kyoto is offline until 14:00 UTC — try /backends to pick another"
+        );
+        // Unknown payloads are shown in full
+        let raw = anyhow::Error::from(QuantumError::ProviderInternal("{\"trace\":\"abc\"}".into()));
+        assert!(quantum_error_message(&raw).ends_with("{\"trace\":\"abc\"}"));
+        assert_eq!(quantum_error_message(&anyhow::anyhow!("timed out")), "timed out");
+    }
+
+    #[test]
+    fn test_parse_profile_and_register_display_names() {
+        match SlashCommand::parse("/profile set display-name  Ada  Lovelace ") {
+            Some(SlashCommand::SetDisplayName(name)) => assert_eq!(name, "Ada  Lovelace"),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(SlashCommand::parse("/profile set display-name"), Some(SlashCommand::Unknown(_))));
+        match SlashCommand::parse("/register ada@example.com ada hunter2hunter2 Ada Lovelace") {
+            Some(SlashCommand::Register { display_name, .. }) => assert_eq!(display_name.as_deref(), Some("Ada Lovelace")),
+            other => panic!("unexpected {:?}", other),
+        }
+        match SlashCommand::parse("/register ada@example.com ada hunter2hunter2") {
+            Some(SlashCommand::Register { display_name, .. }) => assert_eq!(display_name, None),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_lone_slash_and_unknown_commands_are_not_prompts() {
+        for input in ["/", "/ ", "  /  "] {
+            match SlashCommand::parse(input) {
+                Some(SlashCommand::Unknown(cmd)) => assert_eq!(cmd, ""),
+                other => panic!("{:?} parsed as {:?}", input, other),
+            }
+        }
+        match SlashCommand::parse("/unknownthing") {
+            Some(SlashCommand::Unknown(cmd)) => assert_eq!(cmd, "unknownthing"),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(SlashCommand::parse("what does / mean in qasm?").is_none());
+        assert_eq!(SlashCommand::usage_hint("/ "), None);
+    }
+
+    #[test]
+    fn test_set_parse() {
+        let parsed = |input| match SlashCommand::parse(input) {
+            Some(SlashCommand::Set(setting)) => Some(setting),
+            _ => None,
+        };
+        assert_eq!(parsed("/set"), Some(None));
+        assert_eq!(parsed("/set temperature 0.2"), Some(Some(("temperature".to_string(), "0.2".to_string()))));
+        assert_eq!(parsed("/set top_p default"), Some(Some(("top_p".to_string(), "default".to_string()))));
+        assert_eq!(parsed("/set temperature"), None);
+    }
+
+    #[test]
+    fn test_model_parse() {
+        assert!(matches!(SlashCommand::parse("/model"), Some(SlashCommand::Model(None))));
+        assert!(matches!(SlashCommand::parse("/model gpt-4o"), Some(SlashCommand::Model(Some(name))) if name == "gpt-4o"));
+        assert!(matches!(SlashCommand::parse("/models"), Some(SlashCommand::Models { refresh: false })));
+        assert!(matches!(SlashCommand::parse("/models refresh"), Some(SlashCommand::Models { refresh: true })));
+        assert_eq!(SlashCommand::usage_hint("/models update").as_deref(), Some("expected: /models [refresh]"));
+    }
+
+    #[test]
+    fn test_regen_parse_and_cycling() {
+        let parsed = |input| match SlashCommand::parse(input) {
+            Some(SlashCommand::Regen(action)) => Some(action),
+            _ => None,
+        };
+        assert_eq!(parsed("/regen"), Some(RegenAction::New { model: None }));
+        assert_eq!(parsed("/regen prev"), Some(RegenAction::Previous));
+        assert_eq!(parsed("/regen --model gpt-4o"), Some(RegenAction::New { model: Some("gpt-4o".to_string()) }));
+        assert_eq!(parsed("/regen --model"), None);
+
+        let mut message = Message::assistant("C".to_string());
+        assert!(!message.show_previous());
+        message.alternatives = vec!["A".to_string(), "B".to_string()];
+        let id = message.id;
+        let mut seen = Vec::new();
+        for _ in 0..3 {
+            assert!(message.show_previous());
+            seen.push(message.content.clone());
+            assert_eq!(message.alternatives.len(), 2);
+        }
+        assert_eq!(seen, ["B", "A", "C"]);
+        assert_ne!(message.id, id);
+    }
+
+    #[test]
+    fn test_usage_hint() {
+        assert_eq!(
+            SlashCommand::usage_hint("/login ada@example.com").as_deref(),
+            Some("expected: /login <email> <password>")
+        );
+        assert_eq!(SlashCommand::usage_hint("/login ada@example.com hunter2"), None);
+        // Still typing the command name, or not a known command at all
+        assert_eq!(SlashCommand::usage_hint("/login"), None);
+        assert_eq!(SlashCommand::usage_hint("/frobnicate now"), None);
+        assert_eq!(SlashCommand::usage_hint("what does /login do?"), None);
+    }
+}
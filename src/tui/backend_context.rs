@@ -0,0 +1,230 @@
+// Quantum backend context for the AI
+//
+// After /backends has listed the user's IBM Quantum backends, a short system
+// message summarising them rides along with every chat request so generated
+// code targets a device that exists and isn't buried in a queue. There is at
+// most one such message in the conversation: a newer fetch replaces it, and it
+// is dropped once the data goes stale or `ai.inject_backend_context` is off.
+// It lives only in `conversation_history`, never in the visible chat, and
+// session files skip it like the system prompt.
+
+use std::time::{Duration, Instant};
+
+use crate::api::deepseek::ChatMessage;
+use crate::api::ibm_quantum::BackendStatus;
+
+/// First line of the injected message, used to find it again
+pub const MARKER: &str = "[QHub system context: quantum backends]";
+
+/// How often the backend list is refetched while it is being injected
+pub const REFRESH: Duration = Duration::from_secs(10 * 60);
+
+/// Older data is worse than none; the model is told nothing instead
+const MAX_AGE: Duration = Duration::from_secs(30 * 60);
+
+/// Upper bound on the message, whatever the account has access to
+const MAX_CHARS: usize = 1000;
+
+pub fn is_context(message: &ChatMessage) -> bool {
+    message.role == "system" && message.content.starts_with(MARKER)
+}
+
+/// The message text: operational backends first, shortest queue first
+pub fn summary(backends: &[BackendStatus]) -> String {
+    let mut sorted: Vec<&BackendStatus> = backends.iter().collect();
+    sorted.sort_by_key(|b| (!b.operational, b.pending_jobs.unwrap_or(u32::MAX)));
+
+    let mut text = format!(
+        "{}\nBackends available to the user. Target an operational one that fits \
+         the circuit; don't suggest backends that aren't listed.",
+        MARKER
+    );
+    for (i, backend) in sorted.iter().enumerate() {
+        let line = format!("\n- {}", describe(backend));
+        // Leave room for the "more" line
+        if text.len() + line.len() > MAX_CHARS - 20 {
+            text.push_str(&format!("\n- … and {} more", sorted.len() - i));
+            break;
+        }
+        text.push_str(&line);
+    }
+    text
+}
+
+pub fn describe(backend: &BackendStatus) -> String {
+    let qubits = backend
+        .qubits
+        .map(|n| format!("{} qubits", n))
+        .unwrap_or_else(|| "qubits unknown".to_string());
+    let queue = match (backend.operational, backend.pending_jobs) {
+        (false, _) => "offline".to_string(),
+        (true, Some(jobs)) => format!("{} jobs queued", jobs),
+        (true, None) => "queue unknown".to_string(),
+    };
+    format!("{}: {}, {}", backend.name, qubits, queue)
+}
+
+#[derive(Debug, Default)]
+pub struct BackendContext {
+    backends: Vec<BackendStatus>,
+    fetched_at: Option<Instant>,
+    /// Last fetch started, successful or not, so failures aren't retried per message
+    attempted_at: Option<Instant>,
+    /// Fetch time of the snapshot currently in the conversation
+    injected: Option<Instant>,
+}
+
+impl BackendContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn backends(&self) -> &[BackendStatus] {
+        &self.backends
+    }
+
+    /// A fetch finished
+    pub fn update(&mut self, backends: Vec<BackendStatus>, now: Instant) {
+        self.backends = backends;
+        self.fetched_at = Some(now);
+    }
+
+    pub fn mark_attempt(&mut self, now: Instant) {
+        self.attempted_at = Some(now);
+    }
+
+    /// True when an earlier /backends fetch is due to be refreshed. Nothing
+    /// is fetched on the user's behalf until they have run /backends once.
+    pub fn needs_refresh(&self, now: Instant) -> bool {
+        self.fetched_at.is_some()
+            && self
+                .attempted_at
+                .is_none_or(|at| now.saturating_duration_since(at) >= REFRESH)
+    }
+
+    /// Bring the context message in `history` up to date: insert it after the
+    /// system prompt, replace it when newer data arrived, or remove it
+    pub fn apply(&mut self, history: &mut Vec<ChatMessage>, enabled: bool, now: Instant) {
+        let fresh = enabled
+            && !self.backends.is_empty()
+            && self
+                .fetched_at
+                .is_some_and(|at| now.saturating_duration_since(at) < MAX_AGE);
+        if fresh && self.injected == self.fetched_at && history.iter().any(is_context) {
+            return;
+        }
+
+        history.retain(|m| !is_context(m));
+        self.injected = None;
+        if !fresh {
+            return;
+        }
+        let at = history.iter().take_while(|m| m.role == "system").count();
+        history.insert(
+            at,
+            ChatMessage {
+                role: "system".to_string(),
+                content: summary(&self.backends),
+            },
+        );
+        self.injected = self.fetched_at;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backend(name: &str, qubits: u32, pending_jobs: u32, operational: bool) -> BackendStatus {
+        BackendStatus {
+            name: name.to_string(),
+            qubits: Some(qubits),
+            pending_jobs: Some(pending_jobs),
+            operational,
+        }
+    }
+
+    fn message(role: &str, content: &str) -> ChatMessage {
+        ChatMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    fn contexts(history: &[ChatMessage]) -> Vec<&str> {
+        history.iter().filter(|m| is_context(m)).map(|m| m.content.as_str()).collect()
+    }
+
+    #[test]
+    fn test_summary_orders_and_caps() {
+        let text = summary(&[
+            backend("ibm_kyoto", 127, 0, false),
+            backend("ibm_brisbane", 127, 40, true),
+            backend("ibm_sherbrooke", 127, 3, true),
+        ]);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], MARKER);
+        assert_eq!(lines[2], "- ibm_sherbrooke: 127 qubits, 3 jobs queued");
+        assert_eq!(lines[4], "- ibm_kyoto: 127 qubits, offline");
+
+        let many: Vec<BackendStatus> = (0..200).map(|i| backend(&format!("ibm_device_{}", i), 27, i, true)).collect();
+        let text = summary(&many);
+        assert!(text.len() <= MAX_CHARS);
+        assert!(text.ends_with("more"));
+    }
+
+    #[test]
+    fn test_context_is_replaced_not_accumulated() {
+        let now = Instant::now();
+        let mut context = BackendContext::new();
+        let mut history = vec![message("system", "You are QHub"), message("user", "Bell state?")];
+
+        // Nothing until a fetch has happened
+        context.apply(&mut history, true, now);
+        assert!(contexts(&history).is_empty());
+
+        context.update(vec![backend("ibm_brisbane", 127, 5, true)], now);
+        context.apply(&mut history, true, now);
+        context.apply(&mut history, true, now);
+        assert_eq!(contexts(&history).len(), 1);
+        assert!(is_context(&history[1]));
+
+        history.push(message("user", "And GHZ?"));
+        context.update(vec![backend("ibm_torino", 133, 1, true)], now + REFRESH);
+        context.apply(&mut history, true, now + REFRESH);
+        let current = contexts(&history);
+        assert_eq!(current.len(), 1);
+        assert!(current[0].contains("ibm_torino"));
+        assert!(!current[0].contains("ibm_brisbane"));
+        assert_eq!(history.len(), 4);
+    }
+
+    #[test]
+    fn test_context_removed_when_disabled_or_stale() {
+        let now = Instant::now();
+        let mut context = BackendContext::new();
+        let mut history = vec![message("system", "You are QHub")];
+        context.update(vec![backend("ibm_brisbane", 127, 5, true)], now);
+
+        context.apply(&mut history, true, now);
+        context.apply(&mut history, false, now);
+        assert!(contexts(&history).is_empty());
+
+        context.apply(&mut history, true, now);
+        context.apply(&mut history, true, now + MAX_AGE);
+        assert!(contexts(&history).is_empty());
+        assert_eq!(history.len(), 1);
+    }
+
+    #[test]
+    fn test_refresh_waits_for_first_fetch_and_interval() {
+        let now = Instant::now();
+        let mut context = BackendContext::new();
+        assert!(!context.needs_refresh(now));
+
+        context.mark_attempt(now);
+        context.update(Vec::new(), now);
+        assert!(!context.needs_refresh(now + REFRESH / 2));
+        assert!(context.needs_refresh(now + REFRESH));
+    }
+}
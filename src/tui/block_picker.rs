@@ -0,0 +1,122 @@
+// Keyboard selection of code blocks (Ctrl+B)
+//
+// Replies often hold several blocks - imports, the circuit, sample output - so
+// acting on "the last block" can pick the wrong one. The picker numbers the
+// blocks currently on screen; a digit picks one, then a letter says what to do
+// with it.
+
+/// At most this many blocks are numbered, one digit each
+pub const MAX_BLOCKS: usize = 9;
+
+/// A code block: (message index, block index within the message)
+pub type BlockId = (usize, usize);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PickAction {
+    Copy,
+    Save,
+    Edit,
+    Run,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PickResult {
+    /// Waiting for more keys
+    Pending,
+    Cancel,
+    Act(BlockId, PickAction),
+}
+
+#[derive(Debug)]
+pub struct BlockPicker {
+    /// Numbered 1.. in screen order
+    blocks: Vec<BlockId>,
+    chosen: Option<usize>,
+}
+
+impl BlockPicker {
+    /// `None` when there is nothing to pick from
+    pub fn new(mut blocks: Vec<BlockId>) -> Option<Self> {
+        blocks.truncate(MAX_BLOCKS);
+        (!blocks.is_empty()).then_some(Self { blocks, chosen: None })
+    }
+
+    /// The number shown on a block, if it has one
+    pub fn label(&self, block: BlockId) -> Option<usize> {
+        self.blocks.iter().position(|b| *b == block).map(|i| i + 1)
+    }
+
+    pub fn chosen(&self) -> Option<BlockId> {
+        self.chosen.map(|i| self.blocks[i])
+    }
+
+    /// A digit picks a block (a single block is picked right away), then
+    /// c/s/e/r choose the action. Esc cancels; other keys are ignored.
+    pub fn key(&mut self, key: char) -> PickResult {
+        if key == '\x1b' {
+            return PickResult::Cancel;
+        }
+        if let Some(digit) = key.to_digit(10) {
+            let index = digit as usize;
+            if (1..=self.blocks.len()).contains(&index) {
+                self.chosen = Some(index - 1);
+            }
+            return PickResult::Pending;
+        }
+        let action = match key.to_ascii_lowercase() {
+            'c' => PickAction::Copy,
+            's' => PickAction::Save,
+            'e' => PickAction::Edit,
+            'r' => PickAction::Run,
+            _ => return PickResult::Pending,
+        };
+        let index = match (self.chosen, self.blocks.len()) {
+            (Some(index), _) => index,
+            (None, 1) => 0,
+            (None, _) => return PickResult::Pending,
+        };
+        PickResult::Act(self.blocks[index], action)
+    }
+
+    /// What to press next
+    pub fn hint(&self) -> String {
+        match (self.chosen, self.blocks.len()) {
+            (Some(index), _) => format!(
+                "block {}: c copy · s save · e edit · r run · Esc cancel",
+                index + 1
+            ),
+            (None, 1) => "block 1: c copy · s save · e edit · r run · Esc cancel".to_string(),
+            (None, n) => format!("pick a code block 1-{} · Esc cancel", n),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pick_then_act() {
+        let mut picker = BlockPicker::new(vec![(3, 0), (3, 1), (5, 0)]).unwrap();
+        assert_eq!(picker.label((3, 1)), Some(2));
+        assert_eq!(picker.label((9, 0)), None);
+
+        // An action needs a block first; out-of-range digits are ignored
+        assert_eq!(picker.key('c'), PickResult::Pending);
+        assert_eq!(picker.key('7'), PickResult::Pending);
+        assert_eq!(picker.chosen(), None);
+
+        assert_eq!(picker.key('2'), PickResult::Pending);
+        assert!(picker.hint().starts_with("block 2"));
+        assert_eq!(picker.key('3'), PickResult::Pending);
+        assert_eq!(picker.key('S'), PickResult::Act((5, 0), PickAction::Save));
+        assert_eq!(picker.key('\x1b'), PickResult::Cancel);
+    }
+
+    #[test]
+    fn test_single_block_needs_no_digit() {
+        assert!(BlockPicker::new(Vec::new()).is_none());
+        let mut picker = BlockPicker::new(vec![(1, 0)]).unwrap();
+        assert_eq!(picker.key('r'), PickResult::Act((1, 0), PickAction::Run));
+    }
+}
@@ -0,0 +1,81 @@
+// What the current environment lets the user do
+//
+// Accounts need a backend: a direct database (DATABASE_URL) or the remote API.
+// Without either, QHub runs in guest mode - chat works with a local AI key and
+// history stays on this machine, and /connect replaces /login and /register.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capabilities {
+    /// A database or remote API is available, so login/register work
+    pub accounts: bool,
+    /// An AI key is configured locally (config, environment or keychain)
+    pub ai_key: bool,
+}
+
+impl Capabilities {
+    pub fn new(database: bool, remote_api: bool, ai_key: bool) -> Self {
+        Self {
+            accounts: database || remote_api,
+            ai_key,
+        }
+    }
+
+    /// No account backend at all
+    pub fn is_guest(&self) -> bool {
+        !self.accounts
+    }
+
+    /// Whether chat messages may be sent. Guests chat with their own key;
+    /// with an account backend the user has to be logged in.
+    pub fn can_chat(&self, logged_in: bool) -> bool {
+        logged_in || (self.is_guest() && self.ai_key)
+    }
+
+    /// Explain why chat is unavailable
+    pub fn chat_blocked_reason(&self) -> &'static str {
+        if self.is_guest() {
+            "⚠️  No AI key configured. Use /keys set ai, or /connect to set up QHub."
+        } else {
+            "⚠️  Authentication required. Please /login or /register first."
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capability_matrix() {
+        // Database only: accounts work, chat needs a login
+        let caps = Capabilities::new(true, false, false);
+        assert!(!caps.is_guest());
+        assert!(!caps.can_chat(false));
+        assert!(caps.can_chat(true));
+
+        // AI key only: guest mode, chat works without an account
+        let caps = Capabilities::new(false, false, true);
+        assert!(caps.is_guest());
+        assert!(caps.can_chat(false));
+
+        // Both: accounts take precedence, so chat needs a login
+        let caps = Capabilities::new(true, false, true);
+        assert!(!caps.is_guest());
+        assert!(!caps.can_chat(false));
+        assert!(caps.can_chat(true));
+
+        // Neither: guest mode with nothing to chat with
+        let caps = Capabilities::new(false, false, false);
+        assert!(caps.is_guest());
+        assert!(!caps.can_chat(false));
+        assert!(caps.chat_blocked_reason().contains("/connect"));
+    }
+
+    #[test]
+    fn test_remote_api_enables_accounts() {
+        let caps = Capabilities::new(false, true, true);
+        assert!(!caps.is_guest());
+        assert!(!caps.can_chat(false));
+        assert!(caps.chat_blocked_reason().contains("/login"));
+    }
+}
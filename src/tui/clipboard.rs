@@ -0,0 +1,83 @@
+// Copying to the system clipboard
+//
+// Uses the OSC 52 escape sequence, which the terminal turns into a clipboard
+// write. It works over SSH and needs no platform libraries; terminals that
+// don't support it simply ignore the sequence.
+
+use base64::{engine::general_purpose, Engine as _};
+use std::io::{self, Write};
+
+use super::language;
+
+pub fn copy(text: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", general_purpose::STANDARD.encode(text))?;
+    stdout.flush()
+}
+
+/// A fenced code block and the language it is in
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeBlock {
+    /// "python", "qasm" or "text"
+    pub language: &'static str,
+    pub code: String,
+}
+
+/// The fenced code blocks in a message, in order, each with its fence
+/// language or the one its content suggests
+pub fn fenced_blocks(content: &str) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    let mut current: Option<(&str, Vec<&str>)> = None;
+    for line in content.lines() {
+        if let Some(label) = line.strip_prefix("```") {
+            match current.take() {
+                Some((label, lines)) => {
+                    let code = lines.join("\n");
+                    blocks.push(CodeBlock { language: language::of_block(label, &code), code });
+                }
+                None => current = Some((label, Vec::new())),
+            }
+        } else if let Some((_, lines)) = current.as_mut() {
+            lines.push(line);
+        }
+    }
+    blocks
+}
+
+/// Contents of the fenced code blocks in a message, in order
+pub fn code_blocks(content: &str) -> Vec<String> {
+    fenced_blocks(content).into_iter().map(|block| block.code).collect()
+}
+
+/// The longest code block, usually the circuit itself rather than a usage
+/// snippet; the first one wins a tie
+pub fn primary_code_block(content: &str) -> Option<String> {
+    code_blocks(content)
+        .into_iter()
+        .rev()
+        .max_by_key(|block| block.lines().count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_blocks() {
+        let content = "Bell state:\n```python\nqc.h(0)\nqc.cx(0, 1)\n```\nThen measure:\n```\nqc.measure_all()\n```";
+        assert_eq!(
+            code_blocks(content),
+            vec!["qc.h(0)\nqc.cx(0, 1)".to_string(), "qc.measure_all()".to_string()]
+        );
+        // An unterminated block is still being written, so it isn't copyable yet
+        assert!(code_blocks("```python\nqc.h(0)").is_empty());
+        assert_eq!(primary_code_block(content), Some("qc.h(0)\nqc.cx(0, 1)".to_string()));
+        assert_eq!(primary_code_block("```\na\n```\n```\nb\n```").as_deref(), Some("a"));
+        assert_eq!(primary_code_block("No code here"), None);
+
+        let languages: Vec<&str> = fenced_blocks(content).iter().map(|b| b.language).collect();
+        assert_eq!(languages, ["python", "text"]);
+        let bare = "```\nOPENQASM 2.0;\nqreg q[1];\nh q[0];\n```";
+        assert_eq!(fenced_blocks(bare)[0].language, "qasm");
+    }
+}
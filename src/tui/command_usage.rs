@@ -0,0 +1,183 @@
+// How often and how recently each slash command was run
+//
+// Every command that runs is counted in ~/.qhub/cache/command_usage.json
+// with the time it last ran. Inline suggestions and the Ctrl+P palette rank
+// commands by `palette::score` plus `boost`, so among names that match
+// about as well, the ones used most and most recently come first. The boost
+// is kept below the scorer's bonus for a prefix match so that how the
+// letters match still counts for more than habit.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use super::palette;
+
+/// Commands beyond this many are forgotten, least recently used first
+const MAX_COMMANDS: usize = 200;
+
+/// Most a command gains from being used often
+const MAX_FREQUENCY: i64 = 6;
+
+pub fn usage_path() -> Result<PathBuf> {
+    Ok(crate::config::Config::cache_dir()?.join("command_usage.json"))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    pub count: u32,
+    /// Unix seconds
+    pub last_used: i64,
+}
+
+#[derive(Debug, Default)]
+pub struct CommandUsage {
+    path: Option<PathBuf>,
+    /// By command name, without the slash
+    commands: BTreeMap<String, Usage>,
+}
+
+impl CommandUsage {
+    /// Read the saved counts; `None` keeps them in memory only
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let commands = path
+            .as_ref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        Self { path, commands }
+    }
+
+    /// Count a run of `command` at `now` and save
+    pub fn record(&mut self, command: &str, now: DateTime<Utc>) -> Result<()> {
+        let usage = self
+            .commands
+            .entry(command.trim_start_matches('/').to_string())
+            .or_insert(Usage { count: 0, last_used: now.timestamp() });
+        usage.count = usage.count.saturating_add(1);
+        usage.last_used = now.timestamp();
+        if self.commands.len() > MAX_COMMANDS {
+            let oldest = self.commands.iter().min_by_key(|(_, u)| u.last_used).map(|(name, _)| name.clone());
+            if let Some(oldest) = oldest {
+                self.commands.remove(&oldest);
+            }
+        }
+
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).context("Failed to create cache directory")?;
+        }
+        fs::write(path, serde_json::to_string(&self.commands)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// What `command`'s use adds to its match score: a point each time the
+    /// number of runs doubles, up to `MAX_FREQUENCY`, and a few more if it
+    /// ran in the last week, most in the last hour
+    pub fn boost(&self, command: &str, now: DateTime<Utc>) -> i64 {
+        let Some(usage) = self.commands.get(command.trim_start_matches('/')) else {
+            return 0;
+        };
+        let frequency = (u32::BITS - usage.count.leading_zeros()) as i64;
+        let age = Duration::seconds(now.timestamp() - usage.last_used);
+        let recency = if age < Duration::hours(1) {
+            3
+        } else if age < Duration::days(1) {
+            2
+        } else if age < Duration::weeks(1) {
+            1
+        } else {
+            0
+        };
+        frequency.min(MAX_FREQUENCY) + recency
+    }
+
+    /// `palette::score` of `command` for `query`, plus its `boost`
+    pub fn score(&self, query: &str, command: &str, now: DateTime<Utc>) -> Option<i64> {
+        let query = query.trim().trim_start_matches('/');
+        palette::score(query, command.trim_start_matches('/')).map(|s| s + self.boost(command, now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cmp::Reverse;
+
+    const COMMANDS: &[&str] = &["/help", "/status", "/stats", "/sessions", "/save", "/login", "/logout", "/model"];
+
+    fn at(hours: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000, 0).unwrap() + Duration::hours(hours)
+    }
+
+    /// Usage built from (command, hours after the start) pairs
+    fn history(runs: &[(&str, i64)]) -> CommandUsage {
+        let mut usage = CommandUsage::default();
+        for &(command, hours) in runs {
+            usage.record(command, at(hours)).unwrap();
+        }
+        usage
+    }
+
+    fn ranked(usage: &CommandUsage, query: &str, now: DateTime<Utc>) -> Vec<&'static str> {
+        let mut scored: Vec<(i64, &str)> =
+            COMMANDS.iter().filter_map(|&c| usage.score(query, c, now).map(|s| (s, c))).collect();
+        scored.sort_by_key(|&(score, _)| Reverse(score));
+        scored.into_iter().map(|(_, c)| c).collect()
+    }
+
+    #[test]
+    fn test_fuzzy_matches_without_history() {
+        let usage = CommandUsage::default();
+        assert_eq!(ranked(&usage, "/lg", at(0)), ["/login", "/logout"]);
+        assert_eq!(ranked(&usage, "st", at(0)), ["/status", "/stats"]);
+        assert_eq!(ranked(&usage, "mdl", at(0)), ["/model"]);
+        assert!(ranked(&usage, "xyz", at(0)).is_empty());
+    }
+
+    #[test]
+    fn test_frequent_and_recent_commands_rank_first() {
+        let usage = history(&[("stats", 0), ("stats", 0), ("stats", 1), ("stats", 2), ("status", 3)]);
+        // Four runs outweigh one that is a little more recent
+        assert_eq!(ranked(&usage, "st", at(3)), ["/stats", "/status"]);
+        assert_eq!(ranked(&usage, "/s", at(3)), ["/stats", "/status", "/sessions", "/save"]);
+
+        // Equal counts: the more recent wins
+        let usage = history(&[("login", 0), ("logout", 30)]);
+        assert_eq!(ranked(&usage, "lg", at(30)), ["/logout", "/login"]);
+        // Months later neither counts as recent, so the tie is the list order
+        assert_eq!(ranked(&usage, "lg", at(24 * 90)), ["/login", "/logout"]);
+
+        // Habit never beats a prefix over letters scattered through a name
+        let usage = history(&[("help", 0); 1000]);
+        assert_eq!(ranked(&usage, "l", at(0))[0], "/login");
+    }
+
+    #[test]
+    fn test_boost_grows_slowly() {
+        let now = at(24 * 30);
+        assert_eq!(history(&[]).boost("/status", now), 0);
+        assert_eq!(history(&[("status", 0)]).boost("/status", now), 1);
+        assert_eq!(history(&[("status", 0); 4]).boost("status", now), 3);
+        assert_eq!(history(&[("status", 0); 1000]).boost("status", now), MAX_FREQUENCY);
+        assert_eq!(history(&[("status", 24 * 30)]).boost("status", now), 4);
+    }
+
+    #[test]
+    fn test_usage_is_saved() {
+        let dir = std::env::temp_dir().join(format!("qhub-usage-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("command_usage.json");
+        let mut usage = CommandUsage::load(Some(path.clone()));
+        usage.record("/status", at(0)).unwrap();
+        usage.record("status", at(1)).unwrap();
+
+        let reloaded = CommandUsage::load(Some(path));
+        assert_eq!(reloaded.commands.get("status"), Some(&Usage { count: 2, last_used: at(1).timestamp() }));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,157 @@
+// Picking up config edits without a restart
+//
+// The user file and the project's .qhub.toml are checked for a new
+// modification time every couple of seconds; `/reload` forces a reload at
+// once. A reload runs the same load and validation as startup. A config
+// with problems is reported and the one in use is kept. Otherwise each key
+// that changed is applied according to `classify`:
+//
+// - Live settings are read wherever they are used, so swapping the config
+//   is enough.
+// - Settings the AI client copies when it is built cause it to be built
+//   again.
+// - Settings read once at startup are saved with the rest, but what was
+//   built from them stays as it is, and the chat says they need a restart.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often the files' modification times are read
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Settings that QHub only reads at startup: clients, the database pool,
+/// the terminal setup, the transcript and the reply cache. Accounts are
+/// here too, since they are changed through /login and /accounts.
+const RESTART: &[&str] = &[
+    "version",
+    "api_url",
+    "accounts",
+    "db",
+    "network",
+    "telemetry",
+    "security.use_keychain",
+    "ui.mouse_capture",
+    "ui.accessible",
+    "ui.show_welcome",
+    "ai.cache",
+    "ai.transcript",
+];
+
+/// Settings the AI client copies when it is built
+const CLIENT: &[&str] = &[
+    "ai.provider",
+    "ai.api_key",
+    "ai.base_url",
+    "ai.model",
+    "ai.max_tokens",
+    "ai.temperature",
+    "ai.top_p",
+    "ai.log_requests",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Apply {
+    /// Takes effect as soon as the new config is in place
+    Live,
+    /// Takes effect once the AI client is rebuilt
+    Client,
+    /// Needs QHub to be restarted
+    Restart,
+}
+
+/// How a change to the dotted `key` takes effect
+pub fn classify(key: &str) -> Apply {
+    let under = |setting: &&str| key == *setting || key.starts_with(&format!("{}.", setting));
+    if RESTART.iter().any(under) {
+        Apply::Restart
+    } else if CLIENT.iter().any(under) {
+        Apply::Client
+    } else {
+        Apply::Live
+    }
+}
+
+/// Notices when the config files change on disk
+#[derive(Debug)]
+pub struct Watcher {
+    /// Each file with the modification time last seen, None while missing
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    next_check: Instant,
+}
+
+impl Watcher {
+    pub fn new(paths: impl IntoIterator<Item = PathBuf>, now: Instant) -> Self {
+        let files = paths.into_iter().map(|path| {
+            let modified = modified(&path);
+            (path, modified)
+        });
+        Self { files: files.collect(), next_check: now + POLL_INTERVAL }
+    }
+
+    /// True once after any file is written, created or removed; reads the
+    /// disk at most every `POLL_INTERVAL`
+    pub fn changed(&mut self, now: Instant) -> bool {
+        if now < self.next_check {
+            return false;
+        }
+        self.next_check = now + POLL_INTERVAL;
+        let mut changed = false;
+        for (path, seen) in &mut self.files {
+            let modified = modified(path);
+            if modified != *seen {
+                *seen = modified;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        for key in ["ui.scroll_speed", "ui.show_timestamps", "ui.time_format", "ui.tick_ms", "ui.density"] {
+            assert_eq!(classify(key), Apply::Live, "{}", key);
+        }
+        for key in ["ai.system_prompt", "ai.context_window", "ai.inject_backend_context", "quantum.default_backend"] {
+            assert_eq!(classify(key), Apply::Live, "{}", key);
+        }
+        for key in ["ai.model", "ai.max_tokens", "ai.provider", "ai.base_url", "ai.temperature"] {
+            assert_eq!(classify(key), Apply::Client, "{}", key);
+        }
+        for key in ["db.max_connections", "db.maintenance.enabled", "network.proxy", "api_url", "accounts", "ai.cache"] {
+            assert_eq!(classify(key), Apply::Restart, "{}", key);
+        }
+        // Only whole segments match
+        assert_eq!(classify("ui.accessible_extra"), Apply::Live);
+        assert_eq!(classify("ai.models"), Apply::Live);
+    }
+
+    #[test]
+    fn test_watcher_sees_writes() {
+        let dir = std::env::temp_dir().join(format!("qhub-reload-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        std::fs::write(&path, "[ui]\nscroll_speed = 3\n").unwrap();
+        let start = Instant::now();
+        let mut watcher = Watcher::new([path.clone(), dir.join(".qhub.toml")], start);
+
+        let later = SystemTime::now() + Duration::from_secs(10);
+        std::fs::File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+        // Not read again until the interval passes
+        assert!(!watcher.changed(start));
+        assert!(watcher.changed(start + POLL_INTERVAL));
+        assert!(!watcher.changed(start + POLL_INTERVAL * 2));
+
+        std::fs::write(dir.join(".qhub.toml"), "").unwrap();
+        assert!(watcher.changed(start + POLL_INTERVAL * 3));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,101 @@
+// /continue: extend a reply cut off at the token limit
+//
+// The continuation is requested with a one-off instruction that never stays
+// in the conversation; its text is stitched onto the truncated reply so the
+// chat, the history sent to the model and /copy all see a single message.
+// The awkward part is a cut inside a code block: models usually open a new
+// fence and start the interrupted line again, which would otherwise leave two
+// half blocks and a duplicated line.
+
+/// Sent after the truncated reply to ask for the rest
+pub const INSTRUCTION: &str = "Your previous reply was cut off. Continue exactly where you left off, \
+     without repeating anything or adding an introduction. If you were inside a code block, \
+     continue the code without opening a new block.";
+
+/// Whether `text` ends inside an open ``` block
+pub fn in_code_block(text: &str) -> bool {
+    text.lines().filter(|line| line.starts_with("```")).count() % 2 == 1
+}
+
+/// `partial` followed by `continuation`, with a re-opened code fence and a
+/// restarted line folded back into the block the cut happened in
+pub fn stitch(partial: &str, continuation: &str) -> String {
+    if !in_code_block(partial) {
+        return format!("{}{}", partial, continuation);
+    }
+
+    // A fence with a language opening the continuation re-opens the block
+    // we are in; a bare one closes it and is kept
+    let trimmed = continuation.trim_start_matches(['\n', '\r']);
+    let reopened = trimmed
+        .strip_prefix("```")
+        .map(|after| after.split_once('\n').unwrap_or((after, "")))
+        .filter(|(language, _)| !language.trim().is_empty());
+    let Some((_, rest)) = reopened else {
+        return format!("{}{}", partial, continuation);
+    };
+
+    // The block restarts on a fresh line. If the cut was mid-line and the
+    // model wrote that line again, keep its complete version only.
+    let (complete, last_line) = match partial.rfind('\n') {
+        Some(at) => partial.split_at(at + 1),
+        None => ("", partial),
+    };
+    if last_line.is_empty() {
+        return format!("{}{}", partial, rest);
+    }
+    if rest.starts_with(last_line) {
+        return format!("{}{}", complete, rest);
+    }
+    format!("{}\n{}", partial, rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stitch_outside_code() {
+        assert_eq!(stitch("The Bell state is an ent", "angled pair."), "The Bell state is an entangled pair.");
+        // A fence after prose is a new block, not a re-opened one
+        assert_eq!(stitch("Here:\n", "```python\nqc.h(0)\n```"), "Here:\n```python\nqc.h(0)\n```");
+    }
+
+    #[test]
+    fn test_stitch_inside_code_without_new_fence() {
+        let partial = "```python\nqc = QuantumCircuit(2)\nqc.h(";
+        assert_eq!(stitch(partial, "0)\nqc.cx(0, 1)\n```"), "```python\nqc = QuantumCircuit(2)\nqc.h(0)\nqc.cx(0, 1)\n```");
+    }
+
+    #[test]
+    fn test_stitch_merges_reopened_fence() {
+        // Cut at a line boundary
+        let partial = "Circuit:\n```python\nqc = QuantumCircuit(2)\n";
+        let stitched = stitch(partial, "```python\nqc.h(0)\nqc.cx(0, 1)\n```\nDone.");
+        assert_eq!(stitched, "Circuit:\n```python\nqc = QuantumCircuit(2)\nqc.h(0)\nqc.cx(0, 1)\n```\nDone.");
+        assert_eq!(stitched.lines().filter(|l| l.starts_with("```")).count(), 2);
+
+        // Cut mid-line, and the model starts that line again
+        let partial = "```python\nfrom qiskit import QuantumCircuit\nqc = Quantum";
+        assert_eq!(
+            stitch(partial, "\n```python\nqc = QuantumCircuit(3)\n```"),
+            "```python\nfrom qiskit import QuantumCircuit\nqc = QuantumCircuit(3)\n```"
+        );
+
+        // Cut mid-line, the model carries on from a fresh line
+        let partial = "```python\nqc.h(0)\nqc.cx(0,";
+        assert_eq!(stitch(partial, "```python\nqc.measure_all()\n```"), "```python\nqc.h(0)\nqc.cx(0,\nqc.measure_all()\n```");
+    }
+
+    #[test]
+    fn test_stitch_twice() {
+        let first = stitch("```python\nqc = QuantumCircuit(3)\n", "```python\nqc.h(0)\n");
+        assert!(in_code_block(&first));
+        let second = stitch(&first, "```python\nqc.cx(0, 1)\n```");
+        assert_eq!(second, "```python\nqc = QuantumCircuit(3)\nqc.h(0)\nqc.cx(0, 1)\n```");
+        assert!(!in_code_block(&second));
+
+        // Only the closing fence was left
+        assert_eq!(stitch("```python\nqc.h(0)\n", "```\nDone."), "```python\nqc.h(0)\n```\nDone.");
+    }
+}
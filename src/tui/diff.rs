@@ -0,0 +1,106 @@
+// Diffs shown before saved code is overwritten
+//
+// `/save <file> -f` and `/diff <file>` compare a file on disk with a code
+// block and show a unified diff in the chat, inside a ```diff fence the
+// renderer colours line by line. Long diffs stop after `MAX_LINES` lines,
+// with a count of the hunks left out, so one rewrite cannot bury the
+// question asked under it.
+
+use similar::{ChangeTag, TextDiff};
+
+/// Diff lines shown before the remaining hunks are summed up
+pub const MAX_LINES: usize = 120;
+
+/// Unchanged lines kept around each change
+const CONTEXT: usize = 3;
+
+/// A unified diff from `old` (the file at `path`) to `new`, or None when
+/// they are the same
+pub fn render(old: &str, new: &str, path: &str) -> Option<String> {
+    let diff = TextDiff::from_lines(old, new);
+    let (mut added, mut removed) = (0, 0);
+    for change in diff.iter_all_changes() {
+        match change.tag() {
+            ChangeTag::Insert => added += 1,
+            ChangeTag::Delete => removed += 1,
+            ChangeTag::Equal => {}
+        }
+    }
+    if added == 0 && removed == 0 {
+        return None;
+    }
+
+    let mut text = format!("{}: +{} -{} lines\n```diff\n--- {}\n+++ code block\n", path, added, removed, path);
+    let mut shown = 0;
+    let mut hidden = 0;
+    for hunk in diff.unified_diff().context_radius(CONTEXT).iter_hunks() {
+        let hunk = hunk.to_string();
+        let lines = hunk.lines().count();
+        if hidden > 0 || (shown > 0 && shown + lines > MAX_LINES) {
+            hidden += 1;
+            continue;
+        }
+        // A single huge hunk is cut rather than left out
+        for line in hunk.lines().take(MAX_LINES) {
+            text.push_str(line);
+            text.push('\n');
+        }
+        if lines > MAX_LINES {
+            text.push_str(&format!("… {} more lines in this hunk\n", lines - MAX_LINES));
+        }
+        shown += lines.min(MAX_LINES);
+    }
+    text.push_str("```");
+    if hidden > 0 {
+        text.push_str(&format!("\n… {} more hunk(s) not shown", hidden));
+    }
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BELL: &str = "OPENQASM 2.0;\nqreg q[2];\nh q[0];\ncx q[0],q[1];\n";
+
+    #[test]
+    fn test_unchanged() {
+        assert_eq!(render(BELL, BELL, "bell.qasm"), None);
+        assert_eq!(render("", "", "empty.qasm"), None);
+    }
+
+    #[test]
+    fn test_added_lines() {
+        let new = format!("{}creg c[2];\nmeasure q -> c;\n", BELL);
+        let text = render(BELL, &new, "bell.qasm").unwrap();
+        assert_eq!(
+            text,
+            "bell.qasm: +2 -0 lines\n```diff\n--- bell.qasm\n+++ code block\n\
+             @@ -2,3 +2,5 @@\n qreg q[2];\n h q[0];\n cx q[0],q[1];\n+creg c[2];\n+measure q -> c;\n```"
+        );
+    }
+
+    #[test]
+    fn test_removed_and_changed_lines() {
+        let new = "OPENQASM 2.0;\nqreg q[2];\nx q[0];\n";
+        let text = render(BELL, new, "bell.qasm").unwrap();
+        assert!(text.starts_with("bell.qasm: +1 -2 lines\n"));
+        assert!(text.contains("\n-h q[0];\n-cx q[0],q[1];\n+x q[0];\n"));
+        assert!(!text.contains("more hunk"));
+    }
+
+    #[test]
+    fn test_long_diffs_are_truncated() {
+        // Changes far enough apart to make separate hunks
+        let old: String = (0..400).map(|i| format!("line {}\n", i)).collect();
+        let new: String = (0..400)
+            .map(|i| if i % 20 == 0 { format!("changed {}\n", i) } else { format!("line {}\n", i) })
+            .collect();
+        let text = render(&old, &new, "long.py").unwrap();
+        assert!(text.starts_with("long.py: +20 -20 lines\n"));
+        let shown = text.matches("\n@@ ").count();
+        assert!(shown > 0 && shown < 20);
+        assert!(text.ends_with(&format!("```\n… {} more hunk(s) not shown", 20 - shown)));
+        assert!(text.lines().count() <= MAX_LINES + 6);
+    }
+}
@@ -0,0 +1,152 @@
+// Crash-safe persistence of the unsent input
+//
+// The input box is written to ~/.qhub/cache/draft.txt a second after the last
+// keystroke, so a crash or dropped SSH session doesn't lose a long prompt.
+// Input that may contain credentials (/login, /register, key entry) is never
+// written, and any older draft is removed as soon as such input appears.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+
+/// Quiet period after the last change before the draft is written
+pub const DEBOUNCE: Duration = Duration::from_secs(1);
+
+pub fn draft_path() -> Result<PathBuf> {
+    Ok(Config::cache_dir()?.join("draft.txt"))
+}
+
+/// Commands whose arguments are passwords
+pub fn is_secret(input: &str) -> bool {
+    let command = input.split_whitespace().next().unwrap_or("");
+    matches!(command.to_lowercase().as_str(), "/login" | "/register")
+}
+
+pub struct DraftKeeper {
+    path: Option<PathBuf>,
+    /// Input as of the last `observe`
+    seen: String,
+    /// When `seen` last changed, while a write is pending
+    changed_at: Option<Instant>,
+}
+
+impl DraftKeeper {
+    /// `None` disables persistence, e.g. when there is no home directory
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Self {
+            path,
+            seen: String::new(),
+            changed_at: None,
+        }
+    }
+
+    /// A non-empty draft left by a previous run
+    pub fn recover(&mut self) -> Option<String> {
+        let text = fs::read_to_string(self.path.as_ref()?).ok()?;
+        if text.trim().is_empty() {
+            return None;
+        }
+        self.seen = text.clone();
+        Some(text)
+    }
+
+    /// Track the input box; writes once it has been unchanged for `DEBOUNCE`.
+    /// `secret` input is never written and removes any saved draft.
+    pub fn observe(&mut self, input: &str, secret: bool, now: Instant) -> Result<()> {
+        if input != self.seen {
+            self.seen = input.to_string();
+            if secret || input.trim().is_empty() {
+                self.changed_at = None;
+                return self.remove();
+            }
+            self.changed_at = Some(now);
+        }
+        match self.changed_at {
+            Some(at) if now.duration_since(at) >= DEBOUNCE => {
+                self.changed_at = None;
+                self.write(input)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The input was submitted or cleared
+    pub fn clear(&mut self) -> Result<()> {
+        self.seen.clear();
+        self.changed_at = None;
+        self.remove()
+    }
+
+    fn write(&self, input: &str) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).context("Failed to create cache directory")?;
+        }
+        fs::write(path, input).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    fn remove(&self) -> Result<()> {
+        match &self.path {
+            Some(path) if path.exists() => {
+                fs::remove_file(path).with_context(|| format!("Failed to remove {}", path.display()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keeper() -> (DraftKeeper, PathBuf) {
+        let path = std::env::temp_dir().join(format!("qhub-draft-{}.txt", uuid::Uuid::new_v4()));
+        (DraftKeeper::new(Some(path.clone())), path)
+    }
+
+    #[test]
+    fn test_draft_is_debounced_and_recovered() {
+        let (mut draft, path) = keeper();
+        let start = Instant::now();
+
+        draft.observe("Design a", false, start).unwrap();
+        draft.observe("Design a QFT", false, start + Duration::from_millis(500)).unwrap();
+        draft.observe("Design a QFT", false, start + Duration::from_millis(1200)).unwrap();
+        assert!(!path.exists());
+
+        draft.observe("Design a QFT", false, start + Duration::from_millis(1500)).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "Design a QFT");
+
+        let mut next_run = DraftKeeper::new(Some(path.clone()));
+        assert_eq!(next_run.recover().as_deref(), Some("Design a QFT"));
+
+        draft.clear().unwrap();
+        assert!(!path.exists());
+        assert_eq!(next_run.recover(), None);
+    }
+
+    #[test]
+    fn test_secrets_are_never_written() {
+        let (mut draft, path) = keeper();
+        let start = Instant::now();
+        draft.observe("/log", false, start).unwrap();
+        draft.observe("/log", false, start + DEBOUNCE).unwrap();
+        assert!(path.exists());
+
+        // Typing on into a /login command removes the saved draft at once
+        let later = start + DEBOUNCE * 2;
+        draft.observe("/login ada@example.com hunter2", true, later).unwrap();
+        assert!(!path.exists());
+        draft.observe("/login ada@example.com hunter2", true, later + DEBOUNCE).unwrap();
+        assert!(!path.exists());
+
+        assert!(is_secret("  /REGISTER ada ada secret"));
+        assert!(!is_secret("/loginx"));
+        assert!(!is_secret("How do logins work?"));
+    }
+}
@@ -0,0 +1,161 @@
+// Editing code blocks in the user's own editor (/edit)
+//
+// The code goes to a temp file and the TUI is suspended while $VISUAL or
+// $EDITOR runs. GUI editors that fork and return immediately (`code`, `subl`
+// without --wait) are detected by an unchanged file, and we then wait for the
+// file to be saved instead of importing it untouched.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use super::clipboard::CodeBlock;
+use super::language;
+
+/// An editor exiting faster than this without saving has probably forked
+const FORK_THRESHOLD: Duration = Duration::from_secs(1);
+
+/// How long to wait for a forked editor to save the file
+const FORK_WAIT: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EditAction {
+    /// Import the edited code into the chat
+    Code,
+    /// Import it and check it like `qhub run`
+    Run,
+}
+
+/// A code block waiting to be opened by the main loop
+#[derive(Debug)]
+pub struct EditRequest {
+    pub action: EditAction,
+    pub editor: String,
+    pub path: PathBuf,
+    pub language: &'static str,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum EditOutcome {
+    Edited(String),
+    Unchanged(String),
+    /// The file was saved empty
+    Empty,
+}
+
+/// `$VISUAL`, then `$EDITOR`, ignoring empty values
+pub fn editor_command() -> Option<String> {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.trim().is_empty())
+}
+
+/// Write the code to a temp file for the editor, named for its language so
+/// the editor highlights it
+pub fn prepare(action: EditAction, editor: String, block: &CodeBlock) -> Result<EditRequest> {
+    let (code, language) = (&block.code, block.language);
+    let path = std::env::temp_dir().join(format!(
+        "qhub-edit-{}.{}",
+        uuid::Uuid::new_v4(),
+        language::extension(language)
+    ));
+    fs::write(&path, code).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(EditRequest { action, editor, path, language })
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Run the editor on the request's file and read the result back. The
+/// terminal must already be restored; the temp file is removed afterwards.
+pub async fn launch(request: &EditRequest) -> Result<EditOutcome> {
+    let result = run_editor(request).await;
+    let _ = fs::remove_file(&request.path);
+    result
+}
+
+async fn run_editor(request: &EditRequest) -> Result<EditOutcome> {
+    let original = fs::read_to_string(&request.path)?;
+    let before = modified(&request.path);
+
+    // The variable may carry arguments, e.g. `code --wait`
+    let mut words = request.editor.split_whitespace();
+    let program = words.next().context("Editor command is empty")?;
+    let started = Instant::now();
+    let status = tokio::process::Command::new(program)
+        .args(words)
+        .arg(&request.path)
+        .status()
+        .await
+        .with_context(|| format!("Failed to start editor '{}'", program))?;
+    if !status.success() {
+        anyhow::bail!("Editor exited with {}", status);
+    }
+
+    if started.elapsed() < FORK_THRESHOLD && modified(&request.path) == before {
+        println!(
+            "The editor returned immediately - waiting for {} to be saved...",
+            request.path.display()
+        );
+        let deadline = Instant::now() + FORK_WAIT;
+        while modified(&request.path) == before && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+    }
+
+    let content = fs::read_to_string(&request.path)
+        .with_context(|| format!("Failed to read {}", request.path.display()))?;
+    Ok(classify(&original, content))
+}
+
+fn classify(original: &str, content: String) -> EditOutcome {
+    if content.trim().is_empty() {
+        EditOutcome::Empty
+    } else if content.trim_end() == original.trim_end() {
+        EditOutcome::Unchanged(content)
+    } else {
+        EditOutcome::Edited(content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify("qc.h(0)", "  \n".to_string()), EditOutcome::Empty);
+        assert_eq!(
+            classify("qc.h(0)", "qc.h(0)\n".to_string()),
+            EditOutcome::Unchanged("qc.h(0)\n".to_string())
+        );
+        assert_eq!(
+            classify("qc.h(0)", "qc.x(0)\n".to_string()),
+            EditOutcome::Edited("qc.x(0)\n".to_string())
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_launch_reads_back_and_removes_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // A scripted "editor" that rewrites the file and exits
+        let script = std::env::temp_dir().join(format!("qhub-editor-{}.sh", uuid::Uuid::new_v4()));
+        fs::write(&script, "#!/bin/sh\necho 'qc.x(0)' > \"$1\"\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let block = CodeBlock { language: "python", code: "qc.h(0)".to_string() };
+        let request = prepare(EditAction::Code, script.display().to_string(), &block).unwrap();
+        assert_eq!(request.language, "python");
+        assert_eq!(request.path.extension().unwrap(), "py");
+        let outcome = launch(&request).await.unwrap();
+        assert_eq!(outcome, EditOutcome::Edited("qc.x(0)\n".to_string()));
+        assert!(!request.path.exists());
+
+        let _ = fs::remove_file(&script);
+    }
+}
@@ -0,0 +1,253 @@
+// Display formatting for times, durations and sizes
+//
+// One place for the strings qhub shows for numbers, so the status bar,
+// /stats, /status, message timestamps and the CLI tables agree. Timestamps
+// follow `ui.time_format`; relative ones are computed at render time from
+// the message's own timestamp, so they stay current without touching
+// message content. Times stored as Unix seconds are shown in local time.
+
+use chrono::{DateTime, Duration as Elapsed, Local, TimeZone};
+use std::fmt::Display;
+use std::time::Duration;
+
+use crate::config::settings::TimeFormat;
+
+/// Older timestamps show a date instead of a weekday
+const WEEKDAY_DAYS: i64 = 6;
+
+/// A message time relative to `now`, e.g. "2m ago", "yesterday 14:03"
+pub fn timestamp<Tz: TimeZone>(time: &DateTime<Tz>, now: &DateTime<Tz>, format: TimeFormat) -> String
+where
+    Tz::Offset: Display,
+{
+    match format {
+        TimeFormat::Iso => time.format("%Y-%m-%dT%H:%M:%S%:z").to_string(),
+        TimeFormat::Absolute => absolute(time, now),
+        TimeFormat::Relative => relative(time, now),
+    }
+}
+
+fn absolute<Tz: TimeZone>(time: &DateTime<Tz>, now: &DateTime<Tz>) -> String
+where
+    Tz::Offset: Display,
+{
+    if time.date_naive() == now.date_naive() {
+        time.format("%H:%M").to_string()
+    } else {
+        time.format("%Y-%m-%d %H:%M").to_string()
+    }
+}
+
+fn relative<Tz: TimeZone>(time: &DateTime<Tz>, now: &DateTime<Tz>) -> String
+where
+    Tz::Offset: Display,
+{
+    let age = now.clone().signed_duration_since(time.clone());
+    // A timestamp from the future (clock changes) gets no relative wording
+    if age < Elapsed::zero() {
+        return absolute(time, now);
+    }
+    if age < Elapsed::minutes(1) {
+        return "just now".to_string();
+    }
+    if age < Elapsed::hours(1) {
+        return format!("{}m ago", age.num_minutes());
+    }
+
+    // Calendar days, not 24-hour periods: 23:50 yesterday is "yesterday"
+    let days = (now.date_naive() - time.date_naive()).num_days();
+    match days {
+        0 => format!("{}h ago", age.num_hours()),
+        1 => time.format("yesterday %H:%M").to_string(),
+        d if d <= WEEKDAY_DAYS => time.format("%a %H:%M").to_string(),
+        _ => time.format("%Y-%m-%d %H:%M").to_string(),
+    }
+}
+
+/// A stored Unix time in the local timezone
+pub fn local(secs: i64) -> Option<DateTime<Local>> {
+    Local.timestamp_opt(secs, 0).single()
+}
+
+/// Spelled-out age for account and job times: "2 hours ago", "yesterday",
+/// "5 days ago"; a date once it is over a month old
+pub fn ago<Tz: TimeZone>(time: &DateTime<Tz>, now: &DateTime<Tz>) -> String
+where
+    Tz::Offset: Display,
+{
+    let age = now.clone().signed_duration_since(time.clone());
+    let plural = |n: i64, unit: &str| format!("{} {}{} ago", n, unit, if n == 1 { "" } else { "s" });
+    if age < Elapsed::zero() {
+        return absolute(time, now);
+    }
+    if age < Elapsed::minutes(1) {
+        return "just now".to_string();
+    }
+    if age < Elapsed::hours(1) {
+        return plural(age.num_minutes(), "minute");
+    }
+    match (now.date_naive() - time.date_naive()).num_days() {
+        0 => plural(age.num_hours(), "hour"),
+        1 => "yesterday".to_string(),
+        d if d <= 30 => plural(d, "day"),
+        _ => time.format("%Y-%m-%d").to_string(),
+    }
+}
+
+/// Thousands grouped with commas: `1,234,567`
+pub fn grouped(n: u64) -> String {
+    let digits = n.to_string();
+    let mut text = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            text.push(',');
+        }
+        text.push(digit);
+    }
+    text
+}
+
+/// When a relative timestamp of this age next changes its text
+pub fn next_relative_change(age: Duration) -> Duration {
+    let secs = age.as_secs();
+    let step = if secs < 3600 { 60 } else { 3600 };
+    Duration::from_secs(step - secs % step)
+}
+
+/// `1h 12m`, `3m 04s` or `5s`
+pub fn duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, (secs % 3600) / 60, secs % 60);
+    if hours > 0 {
+        format!("{}h {:02}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m {:02}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+/// Large counts shortened: `950`, `1.2k`, `3.4M`
+pub fn count(n: u64) -> String {
+    match n {
+        0..=999 => n.to_string(),
+        1_000..=999_949 => scaled(n as f64 / 1e3, "k"),
+        999_950..=999_949_999 => scaled(n as f64 / 1e6, "M"),
+        _ => scaled(n as f64 / 1e9, "B"),
+    }
+}
+
+/// File sizes in binary units: `512 B`, `3.4 KB`, `1.0 MB`
+pub fn bytes(n: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if n < 1024 {
+        return format!("{} B", n);
+    }
+    let mut value = n as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1023.95 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// One decimal below 100, none above: `1.2k`, `12.5k`, `125k`
+fn scaled(value: f64, suffix: &str) -> String {
+    if value < 99.95 {
+        format!("{:.1}{}", value, suffix)
+    } else {
+        format!("{:.0}{}", value, suffix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{FixedOffset, Utc};
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, day, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_relative_timestamps() {
+        let now = at(12, 15, 30);
+        let relative = |time| timestamp(&time, &now, TimeFormat::Relative);
+        assert_eq!(relative(at(12, 15, 30)), "just now");
+        assert_eq!(relative(at(12, 15, 28)), "2m ago");
+        assert_eq!(relative(at(12, 12, 10)), "3h ago");
+        assert_eq!(relative(at(11, 14, 3)), "yesterday 14:03");
+        assert_eq!(relative(at(9, 8, 0)), "Mon 08:00");
+        assert_eq!(relative(at(2, 8, 0)), "2026-03-02 08:00");
+        // Ahead of the clock
+        assert_eq!(relative(at(12, 16, 0)), "16:00");
+    }
+
+    #[test]
+    fn test_day_boundaries() {
+        // Ten minutes ago, but across midnight
+        let just_after_midnight = at(12, 0, 5);
+        assert_eq!(timestamp(&at(11, 23, 55), &just_after_midnight, TimeFormat::Relative), "10m ago");
+        // Over an hour ago and before midnight: yesterday, not "1h ago"
+        assert_eq!(timestamp(&at(11, 22, 50), &just_after_midnight, TimeFormat::Relative), "yesterday 22:50");
+        assert_eq!(timestamp(&at(11, 22, 50), &just_after_midnight, TimeFormat::Absolute), "2026-03-11 22:50");
+        assert_eq!(timestamp(&at(12, 0, 1), &just_after_midnight, TimeFormat::Absolute), "00:01");
+
+        // Days follow the timestamp's own offset, not UTC
+        let tokyo = FixedOffset::east_opt(9 * 3600).unwrap();
+        let now = at(12, 16, 0).with_timezone(&tokyo); // 01:00 on the 13th
+        let time = at(12, 14, 0).with_timezone(&tokyo); // 23:00 on the 12th
+        assert_eq!(timestamp(&time, &now, TimeFormat::Relative), "yesterday 23:00");
+        assert_eq!(timestamp(&time, &now, TimeFormat::Iso), "2026-03-12T23:00:00+09:00");
+    }
+
+    #[test]
+    fn test_ago() {
+        let now = at(12, 15, 30);
+        assert_eq!(ago(&at(12, 15, 30), &now), "just now");
+        assert_eq!(ago(&at(12, 15, 29), &now), "1 minute ago");
+        assert_eq!(ago(&at(12, 13, 0), &now), "2 hours ago");
+        assert_eq!(ago(&at(11, 23, 0), &now), "yesterday");
+        assert_eq!(ago(&at(2, 8, 0), &now), "10 days ago");
+        assert_eq!(ago(&Utc.with_ymd_and_hms(2025, 12, 24, 9, 0, 0).unwrap(), &now), "2025-12-24");
+        assert_eq!(ago(&at(12, 16, 0), &now), "16:00");
+        assert_eq!(local(0).map(|t| t.timestamp()), Some(0));
+    }
+
+    #[test]
+    fn test_grouped() {
+        assert_eq!(grouped(0), "0");
+        assert_eq!(grouped(999), "999");
+        assert_eq!(grouped(1_000), "1,000");
+        assert_eq!(grouped(64_000), "64,000");
+        assert_eq!(grouped(1_234_567), "1,234,567");
+    }
+
+    #[test]
+    fn test_next_relative_change() {
+        assert_eq!(next_relative_change(Duration::from_secs(0)), Duration::from_secs(60));
+        assert_eq!(next_relative_change(Duration::from_secs(125)), Duration::from_secs(55));
+        assert_eq!(next_relative_change(Duration::from_secs(3700)), Duration::from_secs(3500));
+    }
+
+    #[test]
+    fn test_duration() {
+        assert_eq!(duration(Duration::from_secs(5)), "5s");
+        assert_eq!(duration(Duration::from_secs(184)), "3m 04s");
+        assert_eq!(duration(Duration::from_secs(4320)), "1h 12m");
+    }
+
+    #[test]
+    fn test_count_and_bytes() {
+        assert_eq!(count(950), "950");
+        assert_eq!(count(1_234), "1.2k");
+        assert_eq!(count(99_960), "100k");
+        assert_eq!(count(999_960), "1.0M");
+        assert_eq!(count(3_400_000), "3.4M");
+        assert_eq!(bytes(512), "512 B");
+        assert_eq!(bytes(1_536), "1.5 KB");
+        assert_eq!(bytes(3_565_158), "3.4 MB");
+        assert_eq!(bytes(1_048_575), "1.0 MB");
+    }
+}
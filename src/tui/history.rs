@@ -0,0 +1,280 @@
+// Locally stored chat history
+//
+// Guests have no account to keep their conversation in, so it is written to
+// ~/.qhub/cache/guest_history.json after every reply and restored on launch.
+// With `ui.autosave_sessions` every run also gets its own timestamped file in
+// ~/.qhub/cache/sessions, rewritten after each reply so a crash loses at most
+// the request in flight, and /resume (or `qhub --resume`) reloads the most
+// recent one. Each account keeps its sessions in a directory of its own, so
+// switching accounts never resumes the other one's conversation. Session
+// files also record the provider and model they were held with, so resuming
+// under a different one can say so.
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::api::deepseek::ChatMessage;
+use crate::config::settings::AiConfig;
+use crate::config::Config;
+
+pub fn guest_history_path() -> Result<PathBuf> {
+    Ok(Config::cache_dir()?.join("guest_history.json"))
+}
+
+/// Autosaved sessions kept; older files are removed when a new one starts
+const KEEP_SESSIONS: usize = 20;
+
+/// Where `account`'s sessions are saved; sessions held logged out go in the
+/// top directory
+pub fn sessions_dir(account: Option<&str>) -> Result<PathBuf> {
+    let dir = Config::cache_dir()?.join("sessions");
+    Ok(match account {
+        Some(email) => dir.join(account_dir_name(email)),
+        None => dir,
+    })
+}
+
+/// An email as a directory name
+fn account_dir_name(email: &str) -> String {
+    email
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || "@.-_+".contains(c) { c } else { '_' })
+        .collect()
+}
+
+/// File for a session starting now. Names sort chronologically.
+pub fn new_session_path(dir: &Path) -> PathBuf {
+    dir.join(format!("session-{}.json", Local::now().format("%Y%m%d-%H%M%S")))
+}
+
+/// Saved sessions, oldest first
+fn session_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("session-") && name.ends_with(".json"))
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// The most recent saved session
+pub fn latest_session(dir: &Path) -> Result<Option<PathBuf>> {
+    Ok(session_files(dir)?.pop())
+}
+
+/// The session `id` names: its file stem (`session-20260314-093000`), the
+/// timestamp alone, or a path
+pub fn find_session(dir: &Path, id: &str) -> Result<PathBuf> {
+    let path = Path::new(id);
+    if path.is_file() {
+        return Ok(path.to_path_buf());
+    }
+    let stem = id.trim_end_matches(".json");
+    let stem = if stem.starts_with("session-") { stem.to_string() } else { format!("session-{}", stem) };
+    let files = session_files(dir)?;
+    match files.iter().find(|p| p.file_stem().is_some_and(|s| s == stem.as_str())) {
+        Some(path) => Ok(path.clone()),
+        None => {
+            let recent: Vec<String> = files
+                .iter()
+                .rev()
+                .take(3)
+                .filter_map(|p| p.file_stem().and_then(|s| s.to_str()).map(str::to_string))
+                .collect();
+            match recent.is_empty() {
+                true => anyhow::bail!("no session {} (none saved in {})", id, dir.display()),
+                false => anyhow::bail!("no session {}; the latest are {}", id, recent.join(", ")),
+            }
+        }
+    }
+}
+
+/// An autosaved conversation and what it was held with
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub messages: Vec<ChatMessage>,
+}
+
+impl Session {
+    /// Why continuing under `ai` differs from how the session was held
+    pub fn mismatch(&self, ai: &AiConfig) -> Option<String> {
+        let was = format!(
+            "{}/{}",
+            self.provider.as_deref().unwrap_or(&ai.provider),
+            self.model.as_deref().unwrap_or(&ai.model)
+        );
+        let now = format!("{}/{}", ai.provider, ai.model);
+        (was != now).then(|| format!("This session was held with {}; continuing with {}.", was, now))
+    }
+}
+
+/// Sessions saved before they carried a provider and model are a bare list
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SessionFile {
+    Session(Session),
+    Messages(Vec<ChatMessage>),
+}
+
+pub fn load_session(path: &Path) -> Result<Session> {
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    match serde_json::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))? {
+        SessionFile::Session(session) => Ok(session),
+        SessionFile::Messages(messages) => Ok(Session { messages, ..Session::default() }),
+    }
+}
+
+/// Save the conversation with the provider and model in use, leaving out
+/// system messages
+pub fn save_session(path: &Path, history: &[ChatMessage], ai: &AiConfig) -> Result<()> {
+    let session = Session {
+        provider: Some(ai.provider.clone()),
+        model: Some(ai.model.clone()),
+        messages: history.iter().filter(|m| m.role != "system").cloned().collect(),
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).context("Failed to create cache directory")?;
+    }
+    fs::write(path, serde_json::to_string(&session)?).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Remove all but the newest `KEEP_SESSIONS` sessions
+pub fn prune_sessions(dir: &Path) -> Result<()> {
+    let files = session_files(dir)?;
+    let excess = files.len().saturating_sub(KEEP_SESSIONS);
+    for path in &files[..excess] {
+        fs::remove_file(path).with_context(|| format!("Failed to remove {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Load saved messages; a missing file is an empty history
+pub fn load(path: &Path) -> Result<Vec<ChatMessage>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Save the conversation, leaving out the system prompt
+pub fn save(path: &Path, history: &[ChatMessage]) -> Result<()> {
+    let messages: Vec<&ChatMessage> = history.iter().filter(|m| m.role != "system").collect();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).context("Failed to create cache directory")?;
+    }
+    fs::write(path, serde_json::to_string(&messages)?)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_skips_system_prompt() {
+        let path = std::env::temp_dir().join(format!("qhub-history-{}.json", uuid::Uuid::new_v4()));
+        assert!(load(&path).unwrap().is_empty());
+
+        let message = |role: &str, content: &str| ChatMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+        };
+        let history = vec![
+            message("system", "You are QHub"),
+            message("user", "Bell state?"),
+            message("assistant", "qc.h(0); qc.cx(0, 1)"),
+        ];
+        save(&path, &history).unwrap();
+
+        let loaded = load(&path).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0].role, "user");
+        assert_eq!(loaded[1].content, "qc.h(0); qc.cx(0, 1)");
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_account_dir_name() {
+        assert_eq!(account_dir_name("Ada.Lovelace+work@Example.com"), "ada.lovelace+work@example.com");
+        assert_eq!(account_dir_name("../x@y"), ".._x@y");
+    }
+
+    #[test]
+    fn test_latest_and_prune_sessions() {
+        let dir = std::env::temp_dir().join(format!("qhub-sessions-{}", uuid::Uuid::new_v4()));
+        assert_eq!(latest_session(&dir).unwrap(), None);
+
+        fs::create_dir_all(&dir).unwrap();
+        for i in 0..KEEP_SESSIONS + 3 {
+            fs::write(dir.join(format!("session-20260101-0000{:02}.json", i)), "[]").unwrap();
+        }
+        fs::write(dir.join("notes.txt"), "").unwrap();
+
+        assert_eq!(
+            latest_session(&dir).unwrap(),
+            Some(dir.join(format!("session-20260101-0000{:02}.json", KEEP_SESSIONS + 2)))
+        );
+        prune_sessions(&dir).unwrap();
+        assert_eq!(session_files(&dir).unwrap().len(), KEEP_SESSIONS);
+        assert!(!dir.join("session-20260101-000000.json").exists());
+        assert!(dir.join("notes.txt").exists());
+
+        assert_eq!(
+            find_session(&dir, "20260101-000022").unwrap(),
+            dir.join("session-20260101-000022.json")
+        );
+        assert_eq!(
+            find_session(&dir, "session-20260101-000021").unwrap(),
+            dir.join("session-20260101-000021.json")
+        );
+        let missing = find_session(&dir, "20250101-000000").unwrap_err().to_string();
+        assert!(missing.contains("the latest are session-20260101-000022, session-20260101-000021"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_sessions_record_the_model() {
+        let path = std::env::temp_dir().join(format!("qhub-session-{}.json", uuid::Uuid::new_v4()));
+        let mut ai = AiConfig {
+            provider: "deepseek".to_string(),
+            model: "deepseek-chat".to_string(),
+            ..AiConfig::default()
+        };
+        let history = vec![ChatMessage { role: "user".to_string(), content: "Bell state?".to_string() }];
+        save_session(&path, &history, &ai).unwrap();
+
+        let session = load_session(&path).unwrap();
+        assert_eq!(session.messages, history);
+        assert_eq!(session.mismatch(&ai), None);
+        ai.model = "deepseek-reasoner".to_string();
+        assert_eq!(
+            session.mismatch(&ai).unwrap(),
+            "This session was held with deepseek/deepseek-chat; continuing with deepseek/deepseek-reasoner."
+        );
+
+        // Files from before the provider and model were recorded
+        fs::write(&path, serde_json::to_string(&history).unwrap()).unwrap();
+        let old = load_session(&path).unwrap();
+        assert_eq!(old.messages, history);
+        assert_eq!(old.mismatch(&ai), None);
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,123 @@
+// Clickable regions of the last rendered frame
+//
+// The renderer records where it drew each interactive element; the mouse
+// handler looks clicks up here instead of recomputing the layout.
+
+use ratatui::layout::{Position, Rect};
+
+/// What a transcript row belongs to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RowTarget {
+    /// Index into `App::messages`
+    pub message: usize,
+    /// Index of the fenced code block within the message, if the row is code
+    pub code_block: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClickTarget {
+    Input,
+    Suggestion(usize),
+    Row(RowTarget),
+    NewMessages,
+    /// The copy button on a reply's first row
+    CopyMessage(usize),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct HitMap {
+    pub input: Rect,
+    /// One entry per visible suggestion row
+    pub suggestions: Vec<(Rect, usize)>,
+    /// One entry per visible transcript row
+    pub rows: Vec<(Rect, RowTarget)>,
+    pub new_messages: Option<Rect>,
+    /// Copy buttons and the message each copies
+    pub copy_buttons: Vec<(Rect, usize)>,
+}
+
+impl HitMap {
+    /// The element under a cell. Overlays (suggestions, the new-messages
+    /// marker) win over what they are drawn on top of.
+    pub fn target_at(&self, column: u16, row: u16) -> Option<ClickTarget> {
+        let position = Position::new(column, row);
+        if let Some((_, index)) = self.suggestions.iter().find(|(rect, _)| rect.contains(position)) {
+            return Some(ClickTarget::Suggestion(*index));
+        }
+        if self.new_messages.is_some_and(|rect| rect.contains(position)) {
+            return Some(ClickTarget::NewMessages);
+        }
+        if let Some((_, message)) = self.copy_buttons.iter().find(|(rect, _)| rect.contains(position)) {
+            return Some(ClickTarget::CopyMessage(*message));
+        }
+        if self.input.contains(position) {
+            return Some(ClickTarget::Input);
+        }
+        self.rows
+            .iter()
+            .find(|(rect, _)| rect.contains(position))
+            .map(|(_, target)| ClickTarget::Row(*target))
+    }
+
+    /// Code blocks on screen as (message, block) with the first visible row
+    /// of each, top to bottom
+    pub fn code_block_spans(&self) -> Vec<((usize, usize), Rect)> {
+        let mut spans: Vec<((usize, usize), Rect)> = Vec::new();
+        for (rect, target) in &self.rows {
+            if let Some(block) = target.code_block {
+                let id = (target.message, block);
+                if spans.last().map(|(last, _)| *last) != Some(id) {
+                    spans.push((id, *rect));
+                }
+            }
+        }
+        spans
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_at() {
+        let code = RowTarget { message: 1, code_block: Some(0) };
+        let map = HitMap {
+            input: Rect::new(0, 20, 80, 3),
+            suggestions: vec![(Rect::new(0, 23, 80, 1), 0), (Rect::new(0, 24, 80, 1), 1)],
+            rows: vec![
+                (Rect::new(0, 1, 80, 1), RowTarget { message: 0, code_block: None }),
+                (Rect::new(0, 2, 80, 1), code),
+            ],
+            new_messages: Some(Rect::new(60, 2, 18, 1)),
+            copy_buttons: vec![(Rect::new(78, 1, 1, 1), 0)],
+        };
+
+        assert_eq!(map.target_at(5, 21), Some(ClickTarget::Input));
+        assert_eq!(map.target_at(5, 24), Some(ClickTarget::Suggestion(1)));
+        assert_eq!(map.target_at(5, 2), Some(ClickTarget::Row(code)));
+        assert_eq!(map.target_at(65, 2), Some(ClickTarget::NewMessages));
+        assert_eq!(map.target_at(78, 1), Some(ClickTarget::CopyMessage(0)));
+        assert_eq!(map.target_at(77, 1), Some(ClickTarget::Row(RowTarget { message: 0, code_block: None })));
+        assert_eq!(map.target_at(5, 10), None);
+    }
+
+    #[test]
+    fn test_code_block_spans() {
+        let row = |y, message, code_block| (Rect::new(0, y, 80, 1), RowTarget { message, code_block });
+        let map = HitMap {
+            rows: vec![
+                row(1, 0, None),
+                row(2, 0, Some(0)),
+                row(3, 0, Some(0)),
+                row(4, 0, None),
+                row(5, 0, Some(1)),
+                row(6, 2, Some(0)),
+            ],
+            ..HitMap::default()
+        };
+        let spans: Vec<((usize, usize), u16)> =
+            map.code_block_spans().into_iter().map(|(id, rect)| (id, rect.y)).collect();
+        assert_eq!(spans, vec![((0, 0), 2), ((0, 1), 5), ((2, 0), 6)]);
+    }
+}
@@ -1,17 +1,71 @@
 use anyhow::Result;
-use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers, MouseEventKind};
-use std::time::Duration;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers, MouseButton, MouseEventKind};
+use std::time::{Duration, Instant};
 
 use super::app::{App, InputMode};
+use super::line_edit::{self, Edit};
+#[cfg(unix)]
+use super::terminal;
 
 pub fn handle_events(app: &mut App, timeout: Duration) -> Result<bool> {
     if event::poll(timeout)? {
-        match event::read()? {
+        let event = event::read()?;
+        // Any event but bare mouse movement may change what is on screen
+        if !matches!(event, Event::Mouse(m) if m.kind == MouseEventKind::Moved) {
+            app.pacer.mark_dirty();
+        }
+        match event {
             Event::Key(key) => {
                 // Only handle key press events, ignore release/repeat to prevent double input
                 if key.kind != KeyEventKind::Press {
                     return Ok(false);
                 }
+                app.record_activity(Instant::now());
+
+                // The /stats panel swallows the next key press
+                if app.show_stats {
+                    app.show_stats = false;
+                    return Ok(false);
+                }
+
+                // Esc cancels the AI request in flight before anything else
+                if key.code == KeyCode::Esc && app.request_in_flight() {
+                    app.cancel_request();
+                    return Ok(false);
+                }
+
+                // Ctrl+B numbering is showing: keys pick a block and an action
+                if app.block_picker.is_some() {
+                    match key.code {
+                        KeyCode::Esc => app.handle_block_pick_key('\x1b'),
+                        KeyCode::Char(c) => app.handle_block_pick_key(c),
+                        _ => {}
+                    }
+                    return Ok(false);
+                }
+
+                // The command palette takes every key until it closes
+                if app.palette.is_some() {
+                    app.handle_palette_key(&key);
+                    return Ok(false);
+                }
+                if key.code == KeyCode::Char('p') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                    app.open_palette();
+                    return Ok(false);
+                }
+                // Ctrl+R search takes every key until it is accepted or cancelled
+                if app.history_search.is_some() {
+                    app.handle_history_search_key(&key);
+                    return Ok(false);
+                }
+                if key.code == KeyCode::Char('r') && key.modifiers.contains(KeyModifiers::CONTROL) && !app.is_loading {
+                    app.start_history_search();
+                    return Ok(false);
+                }
+                // Esc hides the quota banner before it means quit
+                if key.code == KeyCode::Esc && app.dismiss_quota_banner() {
+                    return Ok(false);
+                }
                 
                 match app.input_mode {
                     InputMode::Normal => match key.code {
@@ -21,22 +75,18 @@ pub fn handle_events(app: &mut App, timeout: Duration) -> Result<bool> {
                         KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
                             return Ok(true);
                         }
-                        KeyCode::Enter => {
-                            app.submit_input();
-                        }
-                        KeyCode::Tab => {
-                            // Apply suggestion with Tab
-                            if app.show_suggestions {
-                                app.apply_suggestion();
-                            }
+                        KeyCode::Char('z') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            // Job control is Unix-only; elsewhere the key does nothing
+                            #[cfg(unix)]
+                            terminal::request_suspend();
                         }
-                        KeyCode::Char(c) => {
-                            app.input.push(c);
-                            app.update_suggestions();
+                        KeyCode::Char('b') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            app.start_block_pick();
                         }
-                        KeyCode::Backspace => {
-                            app.input.pop();
-                            app.update_suggestions();
+                        KeyCode::Enter => press_enter(app),
+                        KeyCode::Tab if app.show_suggestions => {
+                            // Apply suggestion with Tab
+                            app.apply_suggestion();
                         }
                         KeyCode::Up => {
                             // Navigate suggestions if showing, otherwise scroll
@@ -54,49 +104,60 @@ pub fn handle_events(app: &mut App, timeout: Duration) -> Result<bool> {
                                 app.scroll_down();
                             }
                         }
-                        KeyCode::PageUp => {
-                            for _ in 0..10 {
-                                app.scroll_up();
+                        KeyCode::PageUp => app.scroll.page_up(),
+                        KeyCode::PageDown => app.scroll.page_down(),
+                        KeyCode::Home => app.scroll.scroll_to_top(),
+                        KeyCode::End => app.scroll.scroll_to_bottom(),
+                        _ => {
+                            if let Some(edit) = line_edit::binding(&key) {
+                                app.edit_input(edit);
+                                app.update_suggestions();
                             }
                         }
-                        KeyCode::PageDown => {
-                            for _ in 0..10 {
-                                app.scroll_down();
-                            }
-                        }
-                        _ => {}
                     },
                     InputMode::Editing => match key.code {
                         KeyCode::Esc => {
                             app.input_mode = InputMode::Normal;
                         }
                         KeyCode::Enter if key.modifiers.contains(KeyModifiers::SHIFT) => {
-                            app.input.push('\n');
-                        }
-                        KeyCode::Enter => {
-                            app.submit_input();
-                        }
-                        KeyCode::Char(c) => {
-                            app.input.push(c);
-                        }
-                        KeyCode::Backspace => {
-                            app.input.pop();
+                            app.edit_input(Edit::Insert('\n'));
+                        }
+                        KeyCode::Enter => press_enter(app),
+                        KeyCode::Char('z') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            // Job control is Unix-only; elsewhere the key does nothing
+                            #[cfg(unix)]
+                            terminal::request_suspend();
+                        }
+                        KeyCode::Char('b') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            app.start_block_pick();
+                        }
+                        KeyCode::PageUp => app.scroll.page_up(),
+                        KeyCode::PageDown => app.scroll.page_down(),
+                        KeyCode::Home => app.scroll.scroll_to_top(),
+                        KeyCode::End => app.scroll.scroll_to_bottom(),
+                        _ => {
+                            if let Some(edit) = line_edit::binding(&key) {
+                                app.edit_input(edit);
+                            }
                         }
-                        _ => {}
                     },
                 }
             }
+            Event::FocusGained => app.focused = Some(true),
+            Event::FocusLost => app.focused = Some(false),
             Event::Mouse(mouse) => {
                 match mouse.kind {
                     MouseEventKind::ScrollUp => {
-                        for _ in 0..3 {
-                            app.scroll_up();
-                        }
+                        app.scroll.scroll_up(app.config.ui.scroll_speed as usize);
                     }
                     MouseEventKind::ScrollDown => {
-                        for _ in 0..3 {
-                            app.scroll_down();
-                        }
+                        app.scroll.scroll_down(app.config.ui.scroll_speed as usize);
+                    }
+                    MouseEventKind::Down(MouseButton::Left) if app.show_stats => {
+                        app.show_stats = false;
+                    }
+                    MouseEventKind::Down(MouseButton::Left) => {
+                        app.handle_click(mouse.column, mouse.row);
                     }
                     _ => {}
                 }
@@ -106,3 +167,47 @@ pub fn handle_events(app: &mut App, timeout: Duration) -> Result<bool> {
     }
     Ok(false)
 }
+
+/// What Enter does, in order of precedence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Enter {
+    /// The suggestion list is open: take the selected one into the input
+    ApplySuggestion,
+    /// A reply is on its way: keep the typed line for later
+    Ignore,
+    Submit,
+}
+
+fn enter_action(suggesting: bool, loading: bool, takes_input: bool) -> Enter {
+    if suggesting {
+        Enter::ApplySuggestion
+    } else if loading && !takes_input {
+        Enter::Ignore
+    } else {
+        Enter::Submit
+    }
+}
+
+fn press_enter(app: &mut App) {
+    match enter_action(app.suggesting(), app.is_loading, app.takes_input_while_loading()) {
+        Enter::ApplySuggestion => app.apply_suggestion(),
+        Enter::Ignore => {}
+        Enter::Submit => app.submit_input(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enter_precedence() {
+        // An open suggestion list wins, even over a request in flight
+        assert_eq!(enter_action(true, false, false), Enter::ApplySuggestion);
+        assert_eq!(enter_action(true, true, true), Enter::ApplySuggestion);
+        // A second Enter while the first prompt is answered sends nothing
+        assert_eq!(enter_action(false, true, false), Enter::Ignore);
+        assert_eq!(enter_action(false, true, true), Enter::Submit);
+        assert_eq!(enter_action(false, false, false), Enter::Submit);
+    }
+}
@@ -0,0 +1,261 @@
+// Submitted inputs, kept across sessions, and Ctrl+R search over them
+//
+// Every prompt and command sent is appended to ~/.qhub/cache/input_history.jsonl,
+// one JSON string per line so multi-line prompts survive. Input carrying a
+// password (/login, /register) is never recorded, and key entry never reaches
+// here. Ctrl+R searches the entries newest first, readline-style: typing
+// narrows to entries containing the text (ignoring case unless it has
+// capitals), Ctrl+R again steps to an older match, Enter puts the match in
+// the input line and Esc puts back what was there.
+
+use anyhow::{Context, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::ops::Range;
+use std::path::PathBuf;
+
+use super::draft;
+use crate::config::Config;
+
+/// Entries kept; older ones are dropped when the file is rewritten
+pub const MAX_ENTRIES: usize = 1000;
+
+pub fn history_path() -> Result<PathBuf> {
+    Ok(Config::cache_dir()?.join("input_history.jsonl"))
+}
+
+pub struct InputHistory {
+    path: Option<PathBuf>,
+    /// Oldest first
+    entries: Vec<String>,
+}
+
+impl InputHistory {
+    /// Read the saved history; `None` keeps it in memory only
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let entries = path
+            .as_ref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .map(|text| text.lines().filter_map(|line| serde_json::from_str::<String>(line).ok()).collect::<Vec<_>>())
+            .unwrap_or_default();
+        let skip = entries.len().saturating_sub(MAX_ENTRIES);
+        Self { path, entries: entries.into_iter().skip(skip).collect() }
+    }
+
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    /// Remember a submitted input. Repeating the previous entry, blank input
+    /// and input with a password are skipped.
+    pub fn record(&mut self, input: &str) -> Result<()> {
+        if input.trim().is_empty() || draft::is_secret(input) || self.entries.last().is_some_and(|last| last == input) {
+            return Ok(());
+        }
+        self.entries.push(input.to_string());
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).context("Failed to create cache directory")?;
+        }
+        // Appending is cheap; the file is only rewritten once it has grown
+        // well past the limit
+        if self.entries.len() > MAX_ENTRIES + MAX_ENTRIES / 10 {
+            self.entries.drain(..self.entries.len() - MAX_ENTRIES);
+            let mut text = String::new();
+            for entry in &self.entries {
+                text.push_str(&serde_json::to_string(entry)?);
+                text.push('\n');
+            }
+            return fs::write(path, text).with_context(|| format!("Failed to write {}", path.display()));
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        writeln!(file, "{}", serde_json::to_string(input)?).with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+/// A history entry and where the query is in it
+pub type Match<'a> = (&'a str, Range<usize>);
+
+#[derive(Debug, PartialEq)]
+pub enum SearchResult {
+    Pending,
+    /// Put this back in the input line
+    Cancel(String),
+    /// Put the match in the input line for editing
+    Accept(String),
+}
+
+#[derive(Debug)]
+pub struct Search {
+    query: String,
+    /// Index in the history of the match shown
+    found: Option<usize>,
+    /// The input when the search started
+    original: String,
+}
+
+/// Where `query` occurs in `entry`; case only matters when the query has capitals
+pub fn find(entry: &str, query: &str) -> Option<Range<usize>> {
+    let start = if query.chars().any(char::is_uppercase) {
+        entry.find(query)?
+    } else {
+        // ASCII lowercasing keeps byte offsets the same
+        entry.to_ascii_lowercase().find(&query.to_ascii_lowercase())?
+    };
+    Some(start..start + query.len())
+}
+
+impl Search {
+    pub fn new(original: String) -> Self {
+        Self { query: String::new(), found: None, original }
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// The entry shown and where the query is in it
+    pub fn current<'a>(&self, history: &'a [String]) -> Option<Match<'a>> {
+        let entry = history.get(self.found?)?;
+        Some((entry, find(entry, &self.query)?))
+    }
+
+    /// Typing refines the search, Ctrl+R steps to an older match, Enter (or
+    /// any motion key) accepts and Esc or Ctrl+G cancels
+    pub fn key(&mut self, key: &KeyEvent, history: &[String]) -> SearchResult {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return SearchResult::Cancel(self.original.clone()),
+            KeyCode::Char('g') if ctrl => return SearchResult::Cancel(self.original.clone()),
+            KeyCode::Char('r') if ctrl => {
+                if let Some(found) = self.found {
+                    let current = &history[found];
+                    // Skip repeats of the entry already shown
+                    if let Some(older) = self.search(history, found, |entry| entry != current) {
+                        self.found = Some(older);
+                    }
+                }
+            }
+            KeyCode::Char(c) if !ctrl && !key.modifiers.contains(KeyModifiers::ALT) => {
+                self.query.push(c);
+                // A longer query still matches from where the search got to
+                let from = self.found.map_or(history.len(), |found| found + 1);
+                self.found = self.search(history, from, |_| true);
+            }
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.found = self.search(history, history.len(), |_| true);
+            }
+            KeyCode::Enter | KeyCode::Tab | KeyCode::Left | KeyCode::Right | KeyCode::Home | KeyCode::End => {
+                return SearchResult::Accept(match self.current(history) {
+                    Some((entry, _)) => entry.to_string(),
+                    None => self.original.clone(),
+                });
+            }
+            _ => {}
+        }
+        SearchResult::Pending
+    }
+
+    /// The newest entry before `before` that contains the query and passes `keep`
+    fn search(&self, history: &[String], before: usize, keep: impl Fn(&str) -> bool) -> Option<usize> {
+        if self.query.is_empty() {
+            return None;
+        }
+        history[..before.min(history.len())]
+            .iter()
+            .rposition(|entry| keep(entry) && find(entry, &self.query).is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> Vec<String> {
+        ["/backends", "build a GHZ circuit", "/save ghz.qasm", "explain the ghz state", "/backends"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect()
+    }
+
+    fn press(search: &mut Search, history: &[String], code: KeyCode, modifiers: KeyModifiers) -> SearchResult {
+        search.key(&KeyEvent::new(code, modifiers), history)
+    }
+
+    fn typed(search: &mut Search, history: &[String], text: &str) {
+        for c in text.chars() {
+            press(search, history, KeyCode::Char(c), KeyModifiers::NONE);
+        }
+    }
+
+    #[test]
+    fn test_search_steps_back_through_matches() {
+        let history = history();
+        let mut search = Search::new("draft".to_string());
+        assert!(search.current(&history).is_none());
+
+        typed(&mut search, &history, "ghz");
+        assert_eq!(search.current(&history), Some(("explain the ghz state", 12..15)));
+        press(&mut search, &history, KeyCode::Char('r'), KeyModifiers::CONTROL);
+        assert_eq!(search.current(&history), Some(("/save ghz.qasm", 6..9)));
+        press(&mut search, &history, KeyCode::Char('r'), KeyModifiers::CONTROL);
+        // Lowercase queries ignore case
+        assert_eq!(search.current(&history), Some(("build a GHZ circuit", 8..11)));
+        // No older match: stays put
+        press(&mut search, &history, KeyCode::Char('r'), KeyModifiers::CONTROL);
+        assert_eq!(search.current(&history).unwrap().0, "build a GHZ circuit");
+
+        // Refining continues from the match shown
+        typed(&mut search, &history, " c");
+        assert_eq!(search.current(&history).unwrap().0, "build a GHZ circuit");
+        assert_eq!(
+            press(&mut search, &history, KeyCode::Enter, KeyModifiers::NONE),
+            SearchResult::Accept("build a GHZ circuit".to_string())
+        );
+    }
+
+    #[test]
+    fn test_search_cancel_and_case() {
+        let history = history();
+        let mut search = Search::new("draft".to_string());
+        typed(&mut search, &history, "GHZ");
+        assert_eq!(search.current(&history).unwrap().0, "build a GHZ circuit");
+        typed(&mut search, &history, "x");
+        assert!(search.current(&history).is_none());
+        assert_eq!(search.query(), "GHZx");
+        // Deleting searches again from the newest entry
+        press(&mut search, &history, KeyCode::Backspace, KeyModifiers::NONE);
+        assert_eq!(search.current(&history).unwrap().0, "build a GHZ circuit");
+        assert_eq!(press(&mut search, &history, KeyCode::Esc, KeyModifiers::NONE), SearchResult::Cancel("draft".to_string()));
+
+        let mut search = Search::new(String::new());
+        typed(&mut search, &history, "back");
+        press(&mut search, &history, KeyCode::Char('r'), KeyModifiers::CONTROL);
+        // The older "/backends" repeats the match shown, so it is skipped
+        assert_eq!(search.current(&history).unwrap().0, "/backends");
+        assert_eq!(search.found, Some(4));
+    }
+
+    #[test]
+    fn test_history_persists() {
+        let path = std::env::temp_dir().join(format!("qhub-input-history-{}.jsonl", uuid::Uuid::new_v4()));
+        let mut history = InputHistory::load(Some(path.clone()));
+        history.record("first\nsecond line").unwrap();
+        history.record("first\nsecond line").unwrap();
+        history.record("/login ada@example.com hunter2").unwrap();
+        history.record("  ").unwrap();
+        history.record("/status").unwrap();
+
+        let reloaded = InputHistory::load(Some(path.clone()));
+        assert_eq!(reloaded.entries(), ["first\nsecond line", "/status"]);
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,282 @@
+// Pretty-printed JSON in the chat (ui.pretty_json)
+//
+// Job results and some replies carry JSON as one long line. With
+// ui.pretty_json on, a line that is a whole JSON object or array, and a
+// ```json block (or a bare one holding nothing else), is shown indented with
+// keys, strings and other values coloured apart. serde_json decides what is
+// JSON, so prose that merely contains braces is left as it is. The indenting
+// works on the original text, so keys keep their order and numbers their
+// spelling. A value too long for the screen has its deepest levels folded
+// into `{…3 keys}` and `[…120 items]` until it fits in `MAX_ROWS`, and is
+// cut there if folding alone is not enough. Only the display changes: /copy
+// copies the block as it was sent, `/copy --pretty` the indented form.
+
+/// Most rows a value is shown in
+pub const MAX_ROWS: usize = 40;
+
+const INDENT: &str = "  ";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Key,
+    String,
+    /// Numbers, true, false and null
+    Literal,
+    Punctuation,
+    /// A folded object or array, or the note on a value cut short
+    Folded,
+}
+
+/// A row of the indented value, as pieces with what each is
+pub type Row = Vec<(String, Kind)>;
+
+/// True for a fence label that says JSON
+pub fn is_json_fence(label: &str) -> bool {
+    matches!(label.trim().to_ascii_lowercase().as_str(), "json" | "jsonc" | "json5")
+}
+
+/// The rows `text` is shown in, if it is a single JSON object or array
+pub fn rows(text: &str) -> Option<Vec<Row>> {
+    let node = parse(text)?;
+    let mut rows = Vec::new();
+    write(&node, 0, None, Vec::new(), false, &mut rows);
+    for fold_at in (1..levels(&node)).rev() {
+        if rows.len() <= MAX_ROWS {
+            break;
+        }
+        rows.clear();
+        write(&node, 0, Some(fold_at), Vec::new(), false, &mut rows);
+    }
+    if rows.len() > MAX_ROWS {
+        let hidden = rows.len() - (MAX_ROWS - 1);
+        rows.truncate(MAX_ROWS - 1);
+        rows.push(vec![(format!("… {} more lines", hidden), Kind::Folded)]);
+    }
+    Some(rows)
+}
+
+/// `text` indented in full, if it is a single JSON object or array
+pub fn pretty(text: &str) -> Option<String> {
+    let node = parse(text)?;
+    let mut rows = Vec::new();
+    write(&node, 0, None, Vec::new(), false, &mut rows);
+    let lines: Vec<String> = rows.into_iter().map(|row| row.into_iter().map(|(text, _)| text).collect()).collect();
+    Some(lines.join("\n"))
+}
+
+/// An object or array as written, with its scalars left as text
+#[derive(Debug)]
+enum Node<'a> {
+    Scalar(&'a str, Kind),
+    Object(Vec<(&'a str, Node<'a>)>),
+    Array(Vec<Node<'a>>),
+}
+
+fn parse(text: &str) -> Option<Node<'_>> {
+    let text = text.trim();
+    if !(text.starts_with('{') || text.starts_with('[')) {
+        return None;
+    }
+    serde_json::from_str::<serde::de::IgnoredAny>(text).ok()?;
+    // Valid from here on, so the walk below can take it on trust
+    Some(Parser { text, at: 0 }.value())
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    at: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> u8 {
+        self.text.as_bytes().get(self.at).copied().unwrap_or(0)
+    }
+
+    fn skip_space(&mut self) {
+        while self.peek().is_ascii_whitespace() {
+            self.at += 1;
+        }
+    }
+
+    /// Step over `byte` if it comes next
+    fn eat(&mut self, byte: u8) -> bool {
+        self.skip_space();
+        let found = self.peek() == byte;
+        if found {
+            self.at += 1;
+        }
+        found
+    }
+
+    fn value(&mut self) -> Node<'a> {
+        self.skip_space();
+        match self.peek() {
+            b'{' => {
+                self.at += 1;
+                let mut members = Vec::new();
+                while !self.eat(b'}') {
+                    self.skip_space();
+                    let key = self.string();
+                    self.eat(b':');
+                    members.push((key, self.value()));
+                    self.eat(b',');
+                }
+                Node::Object(members)
+            }
+            b'[' => {
+                self.at += 1;
+                let mut items = Vec::new();
+                while !self.eat(b']') {
+                    items.push(self.value());
+                    self.eat(b',');
+                }
+                Node::Array(items)
+            }
+            b'"' => Node::Scalar(self.string(), Kind::String),
+            _ => {
+                let start = self.at;
+                while !matches!(self.peek(), b',' | b']' | b'}' | 0) && !self.peek().is_ascii_whitespace() {
+                    self.at += 1;
+                }
+                Node::Scalar(&self.text[start..self.at], Kind::Literal)
+            }
+        }
+    }
+
+    /// A string with its quotes, escapes as written
+    fn string(&mut self) -> &'a str {
+        let start = self.at;
+        self.at += 1;
+        while self.peek() != b'"' {
+            if self.peek() == b'\\' {
+                self.at += 1;
+            }
+            self.at += 1;
+        }
+        self.at += 1;
+        &self.text[start..self.at]
+    }
+}
+
+/// How deeply containers nest in `node`, counting itself
+fn levels(node: &Node) -> usize {
+    match node {
+        Node::Scalar(..) => 0,
+        Node::Object(members) => 1 + members.iter().map(|(_, v)| levels(v)).max().unwrap_or(0),
+        Node::Array(items) => 1 + items.iter().map(levels).max().unwrap_or(0),
+    }
+}
+
+/// Add the rows of `node`, nested `depth` deep, to `rows`; `row` is what
+/// its first row starts with. Containers `fold_at` deep or more are summed
+/// up on one row.
+fn write(node: &Node, depth: usize, fold_at: Option<usize>, mut row: Row, comma: bool, rows: &mut Vec<Row>) {
+    let punctuation = |text: &str| (text.to_string(), Kind::Punctuation);
+    let end = |mut row: Row| {
+        if comma {
+            row.push(punctuation(","));
+        }
+        row
+    };
+    let folded = fold_at.is_some_and(|at| depth >= at);
+    match node {
+        Node::Scalar(text, kind) => row.push((text.to_string(), *kind)),
+        Node::Object(members) if members.is_empty() => row.push(punctuation("{}")),
+        Node::Array(items) if items.is_empty() => row.push(punctuation("[]")),
+        Node::Object(members) if folded => row.push((format!("{{…{}}}", count(members.len(), "key")), Kind::Folded)),
+        Node::Array(items) if folded => row.push((format!("[…{}]", count(items.len(), "item")), Kind::Folded)),
+        Node::Object(members) => {
+            row.push(punctuation("{"));
+            rows.push(row);
+            for (i, (key, value)) in members.iter().enumerate() {
+                let start = vec![
+                    (INDENT.repeat(depth + 1), Kind::Punctuation),
+                    (key.to_string(), Kind::Key),
+                    (": ".to_string(), Kind::Punctuation),
+                ];
+                write(value, depth + 1, fold_at, start, i + 1 < members.len(), rows);
+            }
+            rows.push(end(vec![punctuation(&INDENT.repeat(depth)), punctuation("}")]));
+            return;
+        }
+        Node::Array(items) => {
+            row.push(punctuation("["));
+            rows.push(row);
+            for (i, item) in items.iter().enumerate() {
+                write(item, depth + 1, fold_at, vec![punctuation(&INDENT.repeat(depth + 1))], i + 1 < items.len(), rows);
+            }
+            rows.push(end(vec![punctuation(&INDENT.repeat(depth)), punctuation("]")]));
+            return;
+        }
+    }
+    rows.push(end(row));
+}
+
+fn count(n: usize, noun: &str) -> String {
+    format!("{} {}{}", n, noun, if n == 1 { "" } else { "s" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(rows: &[Row]) -> String {
+        let lines: Vec<String> = rows.iter().map(|row| row.iter().map(|(t, _)| t.as_str()).collect()).collect();
+        lines.join("\n")
+    }
+
+    #[test]
+    fn test_nested_objects_and_arrays() {
+        let json = r#"{"job_id":"j1","counts":{"00":512,"11":488},"shots":1000,"tags":["bell",{"x":null}],"empty":{}}"#;
+        assert_eq!(
+            pretty(json).unwrap(),
+            "{\n  \"job_id\": \"j1\",\n  \"counts\": {\n    \"00\": 512,\n    \"11\": 488\n  },\n  \"shots\": 1000,\n  \
+             \"tags\": [\n    \"bell\",\n    {\n      \"x\": null\n    }\n  ],\n  \"empty\": {}\n}"
+        );
+        // Keys keep their order, numbers and escapes their spelling
+        assert_eq!(pretty(r#"[ 1.50 ,"a\"b" , {"z":1,"a":2} ]"#).unwrap(), "[\n  1.50,\n  \"a\\\"b\",\n  {\n    \"z\": 1,\n    \"a\": 2\n  }\n]");
+
+        let shown = rows(r#"{"shots":1000,"backend":"ibm_kyiv"}"#).unwrap();
+        let kinds: Vec<Kind> = shown[1].iter().map(|(_, kind)| *kind).collect();
+        assert_eq!(kinds, [Kind::Punctuation, Kind::Key, Kind::Punctuation, Kind::Literal, Kind::Punctuation]);
+        assert_eq!(shown[2][3], ("\"ibm_kyiv\"".to_string(), Kind::String));
+    }
+
+    #[test]
+    fn test_prose_and_invalid_json_pass_through() {
+        for line in [
+            "Use {x} as the angle",
+            "{ this is not json }",
+            "[1, 2,]",
+            "{\"a\": 1",
+            "The result was {\"a\": 1}",
+            "42",
+            "\"just a string\"",
+            "",
+        ] {
+            assert!(rows(line).is_none(), "{}", line);
+            assert!(pretty(line).is_none(), "{}", line);
+        }
+        assert!(is_json_fence("JSON "));
+        assert!(!is_json_fence("python"));
+    }
+
+    #[test]
+    fn test_large_values_fold_to_fit() {
+        // 30 results of 3 keys each: 214 rows in full
+        let item = r#"{"bits":"01","count":3,"meta":{"shot":1}}"#;
+        let json = format!("{{\"backend\":\"sim\",\"results\":[{}]}}", vec![item; 30].join(","));
+        let shown = rows(&json).unwrap();
+        assert!(shown.len() <= MAX_ROWS, "{}", shown.len());
+        assert_eq!(text(&shown[..3]), "{\n  \"backend\": \"sim\",\n  \"results\": [");
+        assert_eq!(text(&shown[3..4]), "    {…3 keys},");
+        assert_eq!(shown[3][1].1, Kind::Folded);
+
+        // Nothing left to fold: cut, saying how much is hidden
+        let json = format!("[{}]", (0..100).map(|n| n.to_string()).collect::<Vec<_>>().join(","));
+        let shown = rows(&json).unwrap();
+        assert_eq!(shown.len(), MAX_ROWS);
+        assert_eq!(text(&shown[MAX_ROWS - 1..]), "… 63 more lines");
+        assert_eq!(pretty(&json).unwrap().lines().count(), 102);
+    }
+}
@@ -0,0 +1,188 @@
+// Language of a code block
+//
+// The fence label decides when there is one. About a third of replies use
+// bare ``` fences, so unlabelled blocks are judged by their lines: an
+// OPENQASM header or register declarations make QASM, imports and
+// definitions make Python. A strong sign is not enough on its own - most
+// lines also have to look like code of that language - so prose that happens
+// to sit in a fence, or mentions "import", stays "text". When unsure the
+// answer is "text": a wrong guess would colour prose as code and save it as
+// a .py file.
+
+/// Share of non-blank lines that must look like code of the guessed language
+const CODE_SHARE: f64 = 0.75;
+
+/// Python statements that start with a keyword
+const PYTHON_KEYWORDS: &[&str] = &[
+    "import ", "from ", "def ", "class ", "for ", "while ", "if ", "elif ", "else:", "try:", "except", "finally:",
+    "with ", "return", "yield ", "raise ", "assert ", "pass", "break", "continue", "lambda ", "print(", "async ",
+    "await ",
+];
+
+/// "python", "qasm" or "text" for a fence label; None for a bare fence
+pub fn from_fence(label: &str) -> Option<&'static str> {
+    let label = label.trim().to_ascii_lowercase();
+    match label.as_str() {
+        "" => None,
+        "python" | "py" | "python3" | "qiskit" => Some("python"),
+        "qasm" | "openqasm" | "qasm2" | "qasm3" | "openqasm2" | "openqasm3" => Some("qasm"),
+        _ => Some("text"),
+    }
+}
+
+/// The language of a block: its fence label, or else its content
+pub fn of_block(label: &str, code: &str) -> &'static str {
+    from_fence(label).unwrap_or_else(|| detect(code))
+}
+
+/// "python", "qasm" or "text", judged from the code alone
+pub fn detect(code: &str) -> &'static str {
+    let lines: Vec<&str> = code.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+    if lines.is_empty() {
+        return "text";
+    }
+    let share = |looks_like: fn(&str) -> bool| lines.iter().filter(|l| looks_like(l)).count() as f64 / lines.len() as f64;
+
+    let first_code = lines.iter().find(|l| !l.starts_with("//"));
+    let qasm_sign = first_code.is_some_and(|l| l.starts_with("OPENQASM"))
+        || lines.iter().any(|l| l.starts_with("qreg ") || l.starts_with("creg ") || l.starts_with("qubit[") || l.starts_with("include \""));
+    if qasm_sign && share(is_qasm_line) >= CODE_SHARE {
+        return "qasm";
+    }
+
+    let python_sign = lines.iter().any(|l| {
+        (l.starts_with("import ") || l.starts_with("from ") && l.contains(" import "))
+            || l.starts_with("def ") && l.ends_with(':')
+            || l.contains("QuantumCircuit(")
+    });
+    if python_sign && share(is_python_line) >= CODE_SHARE {
+        return "python";
+    }
+    "text"
+}
+
+fn is_qasm_line(line: &str) -> bool {
+    line.starts_with("//")
+        || line.starts_with("OPENQASM")
+        || line.ends_with(';')
+        || line.ends_with('{')
+        || line.starts_with('}')
+        // Multi-line gate bodies
+        || line.starts_with("gate ")
+}
+
+fn is_python_line(line: &str) -> bool {
+    if line.starts_with('#') || line.starts_with('@') || PYTHON_KEYWORDS.iter().any(|k| line.starts_with(k)) {
+        return true;
+    }
+    // Continuations of a bracketed expression
+    if line.starts_with([')', ']', '}']) || line.ends_with([',', '(', '[', '{', '\\']) {
+        return true;
+    }
+    // `name = …`, `name.attr[0] += …` or `name.method(…)`
+    let target_end = line.find(|c: char| !(c.is_alphanumeric() || "_.[]'\"".contains(c))).unwrap_or(line.len());
+    let target = &line[..target_end];
+    let rest = line[target_end..].trim_start();
+    let starts_identifier = target.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_');
+    starts_identifier
+        && (rest.starts_with('(')
+            || (rest.starts_with('=') && !rest.starts_with("=="))
+            || ["+=", "-=", "*=", "/="].iter().any(|op| rest.starts_with(op)))
+}
+
+/// File extension for a language
+pub fn extension(language: &str) -> &'static str {
+    match language {
+        "qasm" => "qasm",
+        "python" => "py",
+        _ => "txt",
+    }
+}
+
+/// Where a comment starts in a line of `language` code
+pub fn comment_start(language: &str, line: &str) -> Option<usize> {
+    let marker = match language {
+        "python" => "#",
+        "qasm" => "//",
+        _ => return None,
+    };
+    // Ignore markers inside strings
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if line[i..].starts_with(marker) => return Some(i),
+            None => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Blocks as they appear in replies, with the language they should get
+    const CORPUS: &[(&str, &str)] = &[
+        (
+            "python",
+            "from qiskit import QuantumCircuit, transpile\nfrom qiskit_aer import AerSimulator\n\n\
+             qc = QuantumCircuit(2, 2)\nqc.h(0)\nqc.cx(0, 1)\nqc.measure([0, 1], [0, 1])\n\n\
+             sim = AerSimulator()\nresult = sim.run(transpile(qc, sim), shots=1024).result()\nprint(result.get_counts())",
+        ),
+        (
+            "python",
+            "import numpy as np\n\ndef grover_oracle(n, marked):\n    qc = QuantumCircuit(n)\n    for i, bit in enumerate(marked):\n        \
+             if bit == '0':\n            qc.x(i)\n    qc.h(n - 1)\n    return qc\n",
+        ),
+        ("python", "qc = QuantumCircuit(3)\nqc.h(0)\nqc.cx(0, 1)\nqc.cx(1, 2)  # GHZ"),
+        (
+            "qasm",
+            "OPENQASM 2.0;\ninclude \"qelib1.inc\";\nqreg q[2];\ncreg c[2];\nh q[0];\ncx q[0],q[1];\nmeasure q -> c;",
+        ),
+        ("qasm", "// Bell pair\nOPENQASM 3.0;\ninclude \"stdgates.inc\";\nqubit[2] q;\nbit[2] c;\nh q[0];\ncx q[0], q[1];\nc = measure q;"),
+        (
+            "qasm",
+            "qreg q[3];\ncreg c[3];\ngate majority a,b,c {\n  cx c,b;\n  cx c,a;\n  ccx a,b,c;\n}\nmajority q[0],q[1],q[2];",
+        ),
+        // Prose and quotes in bare fences
+        ("text", "To run this, import the circuit into your notebook and call the simulator."),
+        (
+            "text",
+            "> \"from qiskit import QuantumCircuit\" is the first line you need.\n> Then define the register sizes.",
+        ),
+        ("text", "Step 1: import the data\nStep 2: define the ansatz\nStep 3: run the optimizer"),
+        ("text", "Result:\n{'00': 512, '11': 512}"),
+        ("text", "$ pip install qiskit qiskit-aer\n$ python bell.py"),
+        ("text", "H 0\nCNOT 0 1\nMEASURE 0 [0]"),
+        ("text", "qreg is how OpenQASM 2 declares quantum registers, and creg declares classical ones."),
+        ("text", ""),
+    ];
+
+    #[test]
+    fn test_detect_corpus() {
+        for (expected, code) in CORPUS {
+            assert_eq!(detect(code), *expected, "{}", code);
+        }
+    }
+
+    #[test]
+    fn test_fence_label_wins() {
+        assert_eq!(of_block("py", "just words"), "python");
+        assert_eq!(of_block("OpenQASM", ""), "qasm");
+        assert_eq!(of_block("bash", "import foo"), "text");
+        assert_eq!(of_block("", "OPENQASM 2.0;\nqreg q[1];"), "qasm");
+        assert_eq!(extension("python"), "py");
+        assert_eq!(extension("text"), "txt");
+    }
+
+    #[test]
+    fn test_comment_start() {
+        assert_eq!(comment_start("python", "qc.h(0)  # first"), Some(9));
+        assert_eq!(comment_start("python", "print('#1')"), None);
+        assert_eq!(comment_start("qasm", "h q[0]; // first"), Some(8));
+        assert_eq!(comment_start("text", "# heading"), None);
+    }
+}
@@ -0,0 +1,329 @@
+// Readline-style editing of the input line
+//
+// The input keeps a cursor, a byte offset that always sits on a char
+// boundary, and a kill ring. Ctrl+K, Ctrl+U, Ctrl+W and Alt+D cut text into
+// the ring; Ctrl+Y puts the newest entry back and Alt+Y right after swaps it
+// for older ones. Kills made one after another add to the same entry, so
+// Ctrl+K followed by Alt+D yanks back as one piece, as in readline. Word
+// motion treats letters, digits and `_` as word characters: a snake_case
+// name is one word, while `/`, `.` and `-` split a path into its parts.
+//
+// `binding` maps keys to edits. Keys the app already uses (Ctrl+B, Ctrl+C,
+// Ctrl+Z, Enter, Tab and the scroll keys) are handled before it is asked.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::VecDeque;
+
+/// Kill ring entries kept
+const RING_SIZE: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    Insert(char),
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    LineStart,
+    LineEnd,
+    WordLeft,
+    WordRight,
+    KillToEnd,
+    KillToStart,
+    KillWordForward,
+    KillWordBackward,
+    Yank,
+    YankPop,
+    Transpose,
+}
+
+/// The edit `key` asks for, if any
+pub fn binding(key: &KeyEvent) -> Option<Edit> {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    let alt = key.modifiers.contains(KeyModifiers::ALT);
+    Some(match key.code {
+        KeyCode::Char('k') if ctrl && !alt => Edit::KillToEnd,
+        KeyCode::Char('u') if ctrl && !alt => Edit::KillToStart,
+        KeyCode::Char('w') if ctrl && !alt => Edit::KillWordBackward,
+        KeyCode::Char('y') if ctrl && !alt => Edit::Yank,
+        KeyCode::Char('t') if ctrl && !alt => Edit::Transpose,
+        KeyCode::Char('a') if ctrl && !alt => Edit::LineStart,
+        KeyCode::Char('e') if ctrl && !alt => Edit::LineEnd,
+        KeyCode::Char('d') if alt && !ctrl => Edit::KillWordForward,
+        KeyCode::Char('b') if alt && !ctrl => Edit::WordLeft,
+        KeyCode::Char('f') if alt && !ctrl => Edit::WordRight,
+        KeyCode::Char('y') if alt && !ctrl => Edit::YankPop,
+        // Ctrl+Alt is how AltGr arrives on some systems, so it still types
+        KeyCode::Char(c) if ctrl == alt => Edit::Insert(c),
+        KeyCode::Backspace if alt || ctrl => Edit::KillWordBackward,
+        KeyCode::Backspace => Edit::Backspace,
+        KeyCode::Delete => Edit::Delete,
+        KeyCode::Left if alt || ctrl => Edit::WordLeft,
+        KeyCode::Right if alt || ctrl => Edit::WordRight,
+        KeyCode::Left => Edit::Left,
+        KeyCode::Right => Edit::Right,
+        _ => return None,
+    })
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn prev_char(text: &str, cursor: usize) -> usize {
+    text[..cursor].char_indices().next_back().map_or(0, |(i, _)| i)
+}
+
+fn next_char(text: &str, cursor: usize) -> usize {
+    text[cursor..].chars().next().map_or(cursor, |c| cursor + c.len_utf8())
+}
+
+/// Start of the word before `cursor`, skipping separators first
+fn word_start(text: &str, cursor: usize) -> usize {
+    let before = &text[..cursor];
+    let end = before.trim_end_matches(|c| !is_word(c)).len();
+    before[..end].trim_end_matches(is_word).len()
+}
+
+/// End of the word after `cursor`, skipping separators first
+fn word_end(text: &str, cursor: usize) -> usize {
+    let after = &text[cursor..];
+    let start = after.len() - after.trim_start_matches(|c| !is_word(c)).len();
+    let rest = &after[start..];
+    cursor + start + (rest.len() - rest.trim_start_matches(is_word).len())
+}
+
+fn line_start(text: &str, cursor: usize) -> usize {
+    text[..cursor].rfind('\n').map_or(0, |i| i + 1)
+}
+
+fn line_end(text: &str, cursor: usize) -> usize {
+    text[cursor..].find('\n').map_or(text.len(), |i| cursor + i)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Last {
+    Other,
+    Kill,
+    /// The yanked text's range and which ring entry it was
+    Yank { start: usize, end: usize, index: usize },
+}
+
+/// Kill ring and the memory of what the last edit was
+#[derive(Debug, Clone)]
+pub struct LineEditor {
+    ring: VecDeque<String>,
+    last: Last,
+}
+
+impl Default for LineEditor {
+    fn default() -> Self {
+        Self { ring: VecDeque::new(), last: Last::Other }
+    }
+}
+
+impl LineEditor {
+    /// Apply `edit` to `text` at `cursor`, moving the cursor
+    pub fn apply(&mut self, edit: Edit, text: &mut String, cursor: &mut usize) {
+        let last = std::mem::replace(&mut self.last, Last::Other);
+        let at = *cursor;
+        match edit {
+            Edit::Insert(c) => {
+                text.insert(at, c);
+                *cursor = at + c.len_utf8();
+            }
+            Edit::Backspace => {
+                let start = prev_char(text, at);
+                text.replace_range(start..at, "");
+                *cursor = start;
+            }
+            Edit::Delete => {
+                let end = next_char(text, at);
+                text.replace_range(at..end, "");
+            }
+            Edit::Left => *cursor = prev_char(text, at),
+            Edit::Right => *cursor = next_char(text, at),
+            Edit::LineStart => *cursor = line_start(text, at),
+            Edit::LineEnd => *cursor = line_end(text, at),
+            Edit::WordLeft => *cursor = word_start(text, at),
+            Edit::WordRight => *cursor = word_end(text, at),
+            Edit::KillToEnd => {
+                // At the end of a line the line break goes, as in Emacs
+                let end = match line_end(text, at) {
+                    end if end == at => next_char(text, at),
+                    end => end,
+                };
+                self.kill(text, at..end, true, last);
+            }
+            Edit::KillToStart => {
+                let start = line_start(text, at);
+                self.kill(text, start..at, false, last);
+                *cursor = start;
+            }
+            Edit::KillWordForward => {
+                let end = word_end(text, at);
+                self.kill(text, at..end, true, last);
+            }
+            Edit::KillWordBackward => {
+                let start = word_start(text, at);
+                self.kill(text, start..at, false, last);
+                *cursor = start;
+            }
+            Edit::Yank => {
+                if let Some(entry) = self.ring.front() {
+                    text.insert_str(at, entry);
+                    *cursor = at + entry.len();
+                    self.last = Last::Yank { start: at, end: *cursor, index: 0 };
+                }
+            }
+            Edit::YankPop => {
+                // Only straight after a yank whose text is still in place
+                let Last::Yank { start, end, index } = last else {
+                    return;
+                };
+                if text.get(start..end) == Some(self.ring[index].as_str()) {
+                    let index = (index + 1) % self.ring.len();
+                    let entry = &self.ring[index];
+                    text.replace_range(start..end, entry);
+                    *cursor = start + entry.len();
+                    self.last = Last::Yank { start, end: *cursor, index };
+                }
+            }
+            Edit::Transpose => *cursor = transpose(text, at),
+        }
+    }
+
+    /// Cut `range` into the ring, joining the newest entry when the last
+    /// edit was a kill too
+    fn kill(&mut self, text: &mut String, range: std::ops::Range<usize>, forward: bool, last: Last) {
+        self.last = Last::Kill;
+        if range.is_empty() {
+            return;
+        }
+        let killed: String = text.drain(range).collect();
+        match self.ring.front_mut() {
+            Some(entry) if last == Last::Kill && forward => entry.push_str(&killed),
+            Some(entry) if last == Last::Kill => entry.insert_str(0, &killed),
+            _ => {
+                self.ring.push_front(killed);
+                self.ring.truncate(RING_SIZE);
+            }
+        }
+    }
+}
+
+/// Swap the characters around the cursor and step past them; at the end of
+/// a line the two before it are swapped instead. Returns the new cursor.
+fn transpose(text: &mut String, cursor: usize) -> usize {
+    let right = if cursor == line_end(text, cursor) { prev_char(text, cursor) } else { cursor };
+    // Needs a character on this line before the one that moves
+    if right <= line_start(text, cursor) {
+        return cursor;
+    }
+    let left = prev_char(text, right);
+    let end = next_char(text, right);
+    let swapped = format!("{}{}", &text[right..end], &text[left..right]);
+    text.replace_range(left..end, &swapped);
+    end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Apply `edits` to `text` with the cursor at `|`, returning the result
+    /// the same way
+    fn run(editor: &mut LineEditor, text: &str, edits: &[Edit]) -> String {
+        let mut cursor = text.find('|').unwrap();
+        let mut text = text.replacen('|', "", 1);
+        for edit in edits {
+            editor.apply(*edit, &mut text, &mut cursor);
+            assert!(text.is_char_boundary(cursor));
+        }
+        text.insert(cursor, '|');
+        text
+    }
+
+    fn edit(text: &str, edits: &[Edit]) -> String {
+        run(&mut LineEditor::default(), text, edits)
+    }
+
+    #[test]
+    fn test_motion_over_unicode() {
+        assert_eq!(edit("héllo wörld|", &[Edit::Left, Edit::Left]), "héllo wör|ld");
+        assert_eq!(edit("日本|語", &[Edit::Left, Edit::Backspace]), "|本語");
+        assert_eq!(edit("a🙂|b", &[Edit::Left, Edit::Delete]), "a|b");
+        assert_eq!(edit("|", &[Edit::Left, Edit::Backspace, Edit::Delete, Edit::Right]), "|");
+        assert_eq!(edit("ab\ncd|", &[Edit::LineStart]), "ab\n|cd");
+    }
+
+    #[test]
+    fn test_word_boundaries() {
+        assert_eq!(edit("save ~/qhub/bell_state.qasm|", &[Edit::WordLeft]), "save ~/qhub/bell_state.|qasm");
+        assert_eq!(edit("save ~/qhub/bell_state.qasm|", &[Edit::WordLeft, Edit::WordLeft]), "save ~/qhub/|bell_state.qasm");
+        assert_eq!(edit("|save ~/qhub", &[Edit::WordRight, Edit::WordRight]), "save ~/qhub|");
+        assert_eq!(edit("größe  über|", &[Edit::WordLeft, Edit::WordLeft]), "|größe  über");
+        assert_eq!(edit("x|", &[Edit::WordRight]), "x|");
+    }
+
+    #[test]
+    fn test_kill_and_yank() {
+        assert_eq!(edit("héllo| wörld", &[Edit::KillToEnd]), "héllo|");
+        assert_eq!(edit("héllo| wörld", &[Edit::KillToEnd, Edit::LineStart, Edit::Yank]), " wörld|héllo");
+        assert_eq!(edit("héllo wörld|", &[Edit::KillWordBackward, Edit::Yank, Edit::Yank]), "héllo wörldwörld|");
+        assert_eq!(edit("/save ~/qhub/|bell.qasm", &[Edit::KillToStart]), "|bell.qasm");
+        assert_eq!(edit("ab|", &[Edit::Yank]), "ab|");
+        // At the end of a line Ctrl+K takes the line break
+        assert_eq!(edit("ab|\ncd", &[Edit::KillToEnd]), "ab|cd");
+    }
+
+    #[test]
+    fn test_successive_kills_share_an_entry() {
+        let mut editor = LineEditor::default();
+        // Forward kills append, backward kills prepend
+        assert_eq!(run(&mut editor, "one |two three", &[Edit::KillWordForward, Edit::KillWordForward]), "one |");
+        assert_eq!(run(&mut editor, "|", &[Edit::Yank]), "two three|");
+        assert_eq!(run(&mut editor, "α β γ|", &[Edit::KillWordBackward, Edit::KillWordBackward]), "α |");
+        assert_eq!(run(&mut editor, "|", &[Edit::Yank]), "β γ|");
+
+        // Anything in between starts a new entry
+        assert_eq!(run(&mut editor, "a b|", &[Edit::KillWordBackward, Edit::Left, Edit::KillWordBackward]), "| ");
+        assert_eq!(run(&mut editor, "|", &[Edit::Yank]), "a|");
+    }
+
+    #[test]
+    fn test_yank_pop_cycles_the_ring() {
+        let mut editor = LineEditor::default();
+        run(&mut editor, "first|", &[Edit::KillWordBackward]);
+        // Any other edit in between starts a new entry
+        run(&mut editor, "second|", &[Edit::Right, Edit::KillWordBackward]);
+        assert_eq!(run(&mut editor, "> |", &[Edit::Yank]), "> second|");
+        assert_eq!(run(&mut editor, "> |", &[Edit::Yank, Edit::YankPop]), "> first|");
+        assert_eq!(run(&mut editor, "> |", &[Edit::Yank, Edit::YankPop, Edit::YankPop]), "> second|");
+        // Not straight after a yank: nothing happens
+        assert_eq!(run(&mut editor, "> |", &[Edit::YankPop]), "> |");
+        assert_eq!(run(&mut editor, "> |", &[Edit::Yank, Edit::Left, Edit::YankPop]), "> secon|d");
+    }
+
+    #[test]
+    fn test_transpose() {
+        assert_eq!(edit("ab|cd", &[Edit::Transpose]), "acb|d");
+        assert_eq!(edit("abcd|", &[Edit::Transpose]), "abdc|");
+        assert_eq!(edit("hé|ß", &[Edit::Transpose]), "hßé|");
+        assert_eq!(edit("日本|", &[Edit::Transpose]), "本日|");
+        assert_eq!(edit("a🙂|b", &[Edit::Transpose]), "ab🙂|");
+        assert_eq!(edit("|ab", &[Edit::Transpose]), "|ab");
+        assert_eq!(edit("a|", &[Edit::Transpose]), "a|");
+        assert_eq!(edit("ab\n|cd", &[Edit::Transpose]), "ab\n|cd");
+    }
+
+    #[test]
+    fn test_bindings() {
+        let key = |code, modifiers| binding(&KeyEvent::new(code, modifiers));
+        assert_eq!(key(KeyCode::Char('k'), KeyModifiers::CONTROL), Some(Edit::KillToEnd));
+        assert_eq!(key(KeyCode::Char('d'), KeyModifiers::ALT), Some(Edit::KillWordForward));
+        assert_eq!(key(KeyCode::Char('K'), KeyModifiers::SHIFT), Some(Edit::Insert('K')));
+        assert_eq!(key(KeyCode::Char('@'), KeyModifiers::CONTROL | KeyModifiers::ALT), Some(Edit::Insert('@')));
+        assert_eq!(key(KeyCode::Char('q'), KeyModifiers::CONTROL), None);
+    }
+}
@@ -0,0 +1,259 @@
+// Inline markdown in replies
+//
+// Text outside code blocks gets **bold**, *italic*, ~~strikethrough~~,
+// `code` and [links](url). Parsing is one left-to-right pass: the closing
+// delimiter for an opener is looked up in a table of next occurrences built
+// up front, so a line full of unmatched backticks or stars is still linear.
+// A span without its closer is left as literal text, and the delimiters are
+// kept as `Marker` spans, so the parsed pieces always add back up to the
+// line. Lines longer than `MAX_LINE_CHARS` are cut before any of this.
+
+use std::borrow::Cow;
+
+/// Characters of a single line that are laid out; the rest is replaced by a note
+pub const MAX_LINE_CHARS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Format {
+    pub bold: bool,
+    pub italic: bool,
+    pub strike: bool,
+    /// The text of a [link](url)
+    pub link: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Text(Format),
+    Code,
+    /// The target of a link
+    Url,
+    /// Delimiters, not shown
+    Marker,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span<'a> {
+    pub text: &'a str,
+    pub kind: Kind,
+}
+
+/// `line`, cut to `MAX_LINE_CHARS` with a note saying how much is not shown
+pub fn clip(line: &str) -> Cow<'_, str> {
+    match line.char_indices().nth(MAX_LINE_CHARS) {
+        Some((at, _)) => {
+            let hidden = line[at..].chars().count();
+            Cow::Owned(format!("{} … {} more characters not shown", &line[..at], hidden))
+        }
+        None => Cow::Borrowed(line),
+    }
+}
+
+/// Split `line` into styled pieces; their texts concatenated give back `line`
+pub fn parse(line: &str) -> Vec<Span<'_>> {
+    let parser = Parser::new(line);
+    let mut spans = Vec::new();
+    parser.parse(0, line.len(), Format::default(), &mut spans);
+    spans
+}
+
+/// Opening and closing delimiters
+#[derive(Clone, Copy)]
+enum Delimiter {
+    Backtick,
+    DoubleStar,
+    Star,
+    DoubleTilde,
+    CloseBracket,
+    CloseParen,
+}
+
+const DELIMITERS: usize = 6;
+
+struct Parser<'a> {
+    text: &'a str,
+    /// next[d][i]: the first position at or after i where delimiter d starts
+    next: [Vec<usize>; DELIMITERS],
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Self {
+        let bytes = text.as_bytes();
+        let n = bytes.len();
+        let mut next: [Vec<usize>; DELIMITERS] = std::array::from_fn(|_| vec![n; n + 1]);
+        for i in (0..n).rev() {
+            let pair = |c: u8| bytes[i] == c && bytes.get(i + 1) == Some(&c);
+            let starts = [
+                bytes[i] == b'`',
+                pair(b'*'),
+                bytes[i] == b'*',
+                pair(b'~'),
+                bytes[i] == b']',
+                bytes[i] == b')',
+            ];
+            for (d, starts) in starts.into_iter().enumerate() {
+                next[d][i] = if starts { i } else { next[d][i + 1] };
+            }
+        }
+        Self { text, next }
+    }
+
+    /// Where `delimiter` next starts in `from..end`
+    fn find(&self, delimiter: Delimiter, from: usize, end: usize) -> Option<usize> {
+        let at = *self.next[delimiter as usize].get(from)?;
+        (at < end).then_some(at)
+    }
+
+    fn byte(&self, i: usize) -> Option<u8> {
+        self.text.as_bytes().get(i).copied()
+    }
+
+    /// Emphasis needs text right inside the delimiters: `* a *` stays literal
+    fn hugs(&self, start: usize, end: usize) -> bool {
+        let inner = &self.text[start..end];
+        !inner.is_empty() && !inner.starts_with(char::is_whitespace) && !inner.ends_with(char::is_whitespace)
+    }
+
+    fn parse(&self, start: usize, end: usize, format: Format, spans: &mut Vec<Span<'a>>) {
+        let mut plain = start;
+        let mut i = start;
+        while i < end {
+            let Some(span_end) = self.span_at(i, end, format, spans, plain) else {
+                i += 1;
+                continue;
+            };
+            i = span_end;
+            plain = span_end;
+        }
+        self.push(plain, end, Kind::Text(format), spans);
+    }
+
+    /// Parse a span opening at `i`, flushing the plain text before it; the
+    /// position after it, or None when nothing opens here
+    fn span_at(&self, i: usize, end: usize, format: Format, spans: &mut Vec<Span<'a>>, plain: usize) -> Option<usize> {
+        // Delimiters are ASCII, so `i` is on a char boundary whenever one matches
+        let emphasis = |width: usize, close: usize, inner: Format, spans: &mut Vec<Span<'a>>| {
+            self.push(plain, i, Kind::Text(format), spans);
+            self.push(i, i + width, Kind::Marker, spans);
+            self.parse(i + width, close, inner, spans);
+            self.push(close, close + width, Kind::Marker, spans);
+            close + width
+        };
+        match self.byte(i)? {
+            b'`' => {
+                let close = self.find(Delimiter::Backtick, i + 1, end).filter(|c| *c > i + 1)?;
+                self.push(plain, i, Kind::Text(format), spans);
+                self.push(i, i + 1, Kind::Marker, spans);
+                self.push(i + 1, close, Kind::Code, spans);
+                self.push(close, close + 1, Kind::Marker, spans);
+                Some(close + 1)
+            }
+            b'*' if self.byte(i + 1) == Some(b'*') => {
+                let close = self.find(Delimiter::DoubleStar, i + 2, end).filter(|c| self.hugs(i + 2, *c))?;
+                Some(emphasis(2, close, Format { bold: true, ..format }, spans))
+            }
+            b'*' => {
+                let close = self.find(Delimiter::Star, i + 1, end).filter(|c| self.byte(c + 1) != Some(b'*') && self.hugs(i + 1, *c))?;
+                Some(emphasis(1, close, Format { italic: true, ..format }, spans))
+            }
+            b'~' if self.byte(i + 1) == Some(b'~') => {
+                let close = self.find(Delimiter::DoubleTilde, i + 2, end).filter(|c| self.hugs(i + 2, *c))?;
+                Some(emphasis(2, close, Format { strike: true, ..format }, spans))
+            }
+            b'[' => {
+                let bracket = self.find(Delimiter::CloseBracket, i + 1, end).filter(|b| *b > i + 1)?;
+                if self.byte(bracket + 1) != Some(b'(') {
+                    return None;
+                }
+                let paren = self.find(Delimiter::CloseParen, bracket + 2, end).filter(|p| *p > bracket + 2)?;
+                self.push(plain, i, Kind::Text(format), spans);
+                self.push(i, i + 1, Kind::Marker, spans);
+                self.parse(i + 1, bracket, Format { link: true, ..format }, spans);
+                self.push(bracket, bracket + 2, Kind::Marker, spans);
+                self.push(bracket + 2, paren, Kind::Url, spans);
+                self.push(paren, paren + 1, Kind::Marker, spans);
+                Some(paren + 1)
+            }
+            _ => None,
+        }
+    }
+
+    fn push(&self, start: usize, end: usize, kind: Kind, spans: &mut Vec<Span<'a>>) {
+        if start < end {
+            spans.push(Span { text: &self.text[start..end], kind });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn shown(line: &str) -> Vec<(&str, Kind)> {
+        parse(line).into_iter().filter(|s| s.kind != Kind::Marker).map(|s| (s.text, s.kind)).collect()
+    }
+
+    const PLAIN: Kind = Kind::Text(Format { bold: false, italic: false, strike: false, link: false });
+    const BOLD: Format = Format { bold: true, italic: false, strike: false, link: false };
+
+    #[test]
+    fn test_spans() {
+        assert_eq!(
+            shown("a **bold** and `code`"),
+            [("a ", PLAIN), ("bold", Kind::Text(BOLD)), (" and ", PLAIN), ("code", Kind::Code)]
+        );
+        assert_eq!(
+            shown("**bold *and* more**"),
+            [("bold ", Kind::Text(BOLD)), ("and", Kind::Text(Format { italic: true, ..BOLD })), (" more", Kind::Text(BOLD))]
+        );
+        assert_eq!(shown("~~gone~~"), [("gone", Kind::Text(Format { strike: true, ..Format::default() }))]);
+        assert_eq!(
+            shown("see [the docs](https://qiskit.org) now"),
+            [
+                ("see ", PLAIN),
+                ("the docs", Kind::Text(Format { link: true, ..Format::default() })),
+                ("https://qiskit.org", Kind::Url),
+                (" now", PLAIN)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unterminated_spans_stay_literal() {
+        assert_eq!(shown("an `unclosed backtick"), [("an `unclosed backtick", PLAIN)]);
+        assert_eq!(shown("**no closer here"), [("**no closer here", PLAIN)]);
+        assert_eq!(shown("2 * 3 * 4"), [("2 * 3 * 4", PLAIN)]);
+        assert_eq!(shown("[not a link] (x)"), [("[not a link] (x)", PLAIN)]);
+        assert_eq!(shown("`` and ~~~~"), [("`` and ~~~~", PLAIN)]);
+        // A later pair still works after an unmatched opener
+        assert_eq!(shown("a ` b"), [("a ` b", PLAIN)]);
+        assert_eq!(shown("* list item with **bold**"), [("* list item with ", PLAIN), ("bold", Kind::Text(BOLD))]);
+    }
+
+    #[test]
+    fn test_long_lines_are_clipped() {
+        let line = "x".repeat(MAX_LINE_CHARS + 5);
+        let clipped = clip(&line);
+        assert!(clipped.ends_with("x … 5 more characters not shown"));
+        assert_eq!(clip("short"), "short");
+
+        // Unmatched delimiters all the way along stay linear
+        let line = "`*[~".repeat(25_000);
+        let started = std::time::Instant::now();
+        assert_eq!(parse(&line).iter().map(|s| s.text).collect::<String>(), line);
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    }
+
+    proptest! {
+        #[test]
+        fn prop_spans_add_up_to_the_line(line in "[a-z é*`~\\[\\]()]{0,120}") {
+            prop_assert_eq!(parse(&line).iter().map(|s| s.text).collect::<String>(), line);
+        }
+
+        #[test]
+        fn prop_any_text_parses(line in "\\PC{0,200}") {
+            prop_assert_eq!(parse(&line).iter().map(|s| s.text).collect::<String>(), line);
+        }
+    }
+}
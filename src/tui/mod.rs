@@ -2,5 +2,40 @@ pub mod app;
 pub mod ui;
 pub mod input;
 pub mod components;
+pub mod wizard;
+pub mod tasks;
+pub mod stats;
+pub mod capabilities;
+pub mod history;
+pub mod pacer;
+pub mod scroll;
+pub mod hit_test;
+pub mod clipboard;
+pub mod terminal;
+pub mod editor;
+pub mod prompts;
+pub mod quota;
+pub mod draft;
+pub mod telemetry;
+pub mod format;
+pub mod block_picker;
+pub mod ai_queue;
+pub mod backend_context;
+pub mod share;
+pub mod render_cache;
+pub mod status_line;
+pub mod continuation;
 
-pub use app::App;
+pub use app::{App, AppOptions};
+pub mod diff;
+pub mod input_history;
+pub mod line_edit;
+pub mod markdown;
+pub mod language;
+pub mod palette;
+pub mod notify;
+pub mod accessible;
+pub mod config_reload;
+pub mod command_usage;
+pub mod json_view;
+pub mod services;
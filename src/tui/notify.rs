@@ -0,0 +1,86 @@
+// Pings when a reply or watched job is done (ui.notify_on_complete)
+//
+// Long generations and queued jobs are easy to walk away from, so QHub can
+// ring the terminal bell, show a desktop notification, or both. Desktop
+// notifications go through notify-rust: the freedesktop notification service
+// on Linux and the BSDs, Notification Center on macOS, toasts on Windows.
+// Terminals that report focus (most do once asked) get no ping while QHub is
+// in front; one that never reports it gets every ping, since there is no
+// telling.
+
+use std::io::{self, Write};
+
+use crate::config::settings::NotifyOn;
+use crate::diagnostics;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ping {
+    Bell,
+    Desktop,
+}
+
+/// What to send for a finished reply or job. `focused` is None until the
+/// terminal reports focus.
+pub fn pings(setting: NotifyOn, focused: Option<bool>) -> &'static [Ping] {
+    if focused == Some(true) {
+        return &[];
+    }
+    match setting {
+        NotifyOn::Off => &[],
+        NotifyOn::Bell => &[Ping::Bell],
+        NotifyOn::Desktop => &[Ping::Desktop],
+        NotifyOn::Both => &[Ping::Bell, Ping::Desktop],
+    }
+}
+
+/// Ring the terminal bell
+pub fn bell() {
+    let mut stdout = io::stdout();
+    let _ = stdout.write_all(b"\x07");
+    let _ = stdout.flush();
+}
+
+/// Show a desktop notification. A missing notification service only shows
+/// up in debug output; the reply is on screen either way.
+pub async fn desktop(title: String, body: String) {
+    // The platform calls block, so keep them off the event loop
+    let shown = tokio::task::spawn_blocking(move || {
+        notify_rust::Notification::new().appname("QHub").summary(&title).body(&body).show().map(|_| ())
+    })
+    .await;
+    match shown {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => diagnostics::debug(format!("Desktop notification failed: {}", e)),
+        Err(e) => diagnostics::debug(format!("Desktop notification failed: {}", e)),
+    }
+}
+
+/// The first line of a reply, short enough for a notification
+pub fn summary(text: &str) -> String {
+    const MAX_CHARS: usize = 100;
+    let line = text.lines().map(str::trim).find(|l| !l.is_empty()).unwrap_or_default();
+    match line.char_indices().nth(MAX_CHARS) {
+        Some((at, _)) => format!("{}…", &line[..at]),
+        None => line.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pings_follow_setting_and_focus() {
+        assert_eq!(pings(NotifyOn::Off, Some(false)), &[]);
+        assert_eq!(pings(NotifyOn::Both, None), &[Ping::Bell, Ping::Desktop]);
+        assert_eq!(pings(NotifyOn::Bell, Some(false)), &[Ping::Bell]);
+        // Nobody needs a ping while looking at the reply
+        assert_eq!(pings(NotifyOn::Desktop, Some(true)), &[]);
+    }
+
+    #[test]
+    fn test_summary() {
+        assert_eq!(summary("\n  Here is a Bell state:\n```qasm"), "Here is a Bell state:");
+        assert_eq!(summary(&"x".repeat(150)).chars().count(), 101);
+    }
+}
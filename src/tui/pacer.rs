@@ -0,0 +1,124 @@
+// Redraw and polling schedule for the main loop
+//
+// Drawing only happens when something changed: an input event, a background
+// result, or a deadline registered by a timer-driven view (such as the /stats
+// uptime clock). While nothing is in flight the loop blocks on input for
+// IDLE_POLL, so an idle qhub lets the CPU sleep; pending requests switch it
+// back to `ui.tick_ms`.
+
+use std::time::{Duration, Instant};
+
+/// Longest the loop waits for input when nothing is pending
+pub const IDLE_POLL: Duration = Duration::from_millis(500);
+
+pub struct FramePacer {
+    tick: Duration,
+    dirty: bool,
+    deadline: Option<Instant>,
+}
+
+impl FramePacer {
+    /// A pacer whose first frame is always drawn
+    pub fn new(tick: Duration) -> Self {
+        Self {
+            tick,
+            dirty: true,
+            deadline: None,
+        }
+    }
+
+    /// Something visible changed; draw on the next iteration
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Redraw at `at` even if nothing else changes. The earliest pending
+    /// deadline wins.
+    pub fn schedule(&mut self, at: Instant) {
+        self.deadline = Some(self.deadline.map_or(at, |d| d.min(at)));
+    }
+
+    /// Whether a frame is due. Clears the dirty flag and any expired
+    /// deadline, so the caller must draw when this returns true.
+    pub fn take_frame(&mut self, now: Instant) -> bool {
+        let expired = self.deadline.is_some_and(|d| d <= now);
+        if expired {
+            self.deadline = None;
+        }
+        let due = self.dirty || expired;
+        self.dirty = false;
+        due
+    }
+
+    /// How long to block waiting for input. `busy` means background work may
+    /// deliver a result at any moment, so the loop keeps ticking quickly.
+    pub fn poll_timeout(&self, now: Instant, busy: bool) -> Duration {
+        let base = if busy { self.tick } else { IDLE_POLL };
+        match self.deadline {
+            Some(deadline) => base.min(deadline.saturating_duration_since(now)),
+            None => base,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TICK: Duration = Duration::from_millis(50);
+
+    /// Run `iterations` loop turns without input, advancing a fake clock by
+    /// each poll timeout. Returns the number of frames drawn.
+    fn run_idle(pacer: &mut FramePacer, now: &mut Instant, iterations: usize, busy: bool) -> usize {
+        let mut draws = 0;
+        for _ in 0..iterations {
+            if pacer.take_frame(*now) {
+                draws += 1;
+            }
+            *now += pacer.poll_timeout(*now, busy);
+        }
+        draws
+    }
+
+    #[test]
+    fn test_idle_loop_does_not_draw() {
+        let mut pacer = FramePacer::new(TICK);
+        let mut now = Instant::now();
+
+        // Only the initial frame is drawn, and the loop blocks for IDLE_POLL
+        assert_eq!(run_idle(&mut pacer, &mut now, 100, false), 1);
+        assert_eq!(pacer.poll_timeout(now, false), IDLE_POLL);
+
+        pacer.mark_dirty();
+        assert_eq!(run_idle(&mut pacer, &mut now, 100, false), 1);
+    }
+
+    #[test]
+    fn test_busy_loop_ticks_fast_without_drawing() {
+        let mut pacer = FramePacer::new(TICK);
+        let mut now = Instant::now();
+        assert_eq!(pacer.poll_timeout(now, true), TICK);
+        assert_eq!(run_idle(&mut pacer, &mut now, 10, true), 1);
+    }
+
+    #[test]
+    fn test_deadline_fires_once_on_time() {
+        let mut pacer = FramePacer::new(TICK);
+        let start = Instant::now();
+        let mut now = start;
+        assert!(pacer.take_frame(now));
+
+        pacer.schedule(start + Duration::from_millis(1200));
+        pacer.schedule(start + Duration::from_secs(1));
+        assert_eq!(pacer.poll_timeout(now, false), IDLE_POLL);
+
+        now += IDLE_POLL;
+        assert!(!pacer.take_frame(now));
+        assert_eq!(pacer.poll_timeout(now, false), Duration::from_millis(500));
+
+        now += Duration::from_millis(500);
+        assert!(pacer.take_frame(now));
+        assert!(!pacer.take_frame(now));
+        assert_eq!(pacer.poll_timeout(now, false), IDLE_POLL);
+    }
+}
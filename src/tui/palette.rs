@@ -0,0 +1,380 @@
+// Command palette (Ctrl+P)
+//
+// Lists the commands run recently, every slash command with its usage, and
+// the prompts sent recently, narrowed by a fuzzy match as you type. Matches
+// at the start of a name or of a word inside it rank above ones scattered
+// through it, and commands get the same boost for frequent and recent use
+// as inline suggestions (see `command_usage`). Enter runs a recent command or one that takes no arguments.
+// A command that takes arguments asks for them in the palette, with its
+// usage above, and runs once they are typed; a prompt goes into the input
+// line to be sent.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Rows the overlay shows at once
+pub const VISIBLE: usize = 10;
+
+/// Recent prompts offered after the commands
+pub const RECENT_PROMPTS: usize = 10;
+
+/// Commands run recently, offered first
+pub const RECENT_COMMANDS: usize = 5;
+
+/// Prompts are only matched on their start, which keeps long ones cheap
+const MATCHED_CHARS: usize = 200;
+
+// Fuzzy scoring weights
+const PREFIX: i64 = 10;
+const WORD_START: i64 = 8;
+const CONSECUTIVE: i64 = 8;
+const MATCHED: i64 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ItemKind {
+    Command,
+    /// A command line run before, arguments and all
+    Recent,
+    Prompt,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    pub kind: ItemKind,
+    /// The command name or the prompt itself
+    pub text: String,
+    /// A command's description and usage
+    pub detail: String,
+    /// Added to the match score, for commands used often or lately
+    pub boost: i64,
+}
+
+impl Item {
+    pub fn command(name: &str, description: &str) -> Self {
+        Self { kind: ItemKind::Command, text: name.to_string(), detail: description.to_string(), boost: 0 }
+    }
+
+    pub fn recent(line: String) -> Self {
+        Self { kind: ItemKind::Recent, text: line, detail: String::new(), boost: 0 }
+    }
+
+    pub fn prompt(text: String) -> Self {
+        Self { kind: ItemKind::Prompt, text, detail: String::new(), boost: 0 }
+    }
+
+    pub fn boosted(mut self, boost: i64) -> Self {
+        self.boost = boost;
+        self
+    }
+
+    /// What a command's description shows after "usage:"; commands with
+    /// one take arguments
+    fn usage(&self) -> Option<&str> {
+        let (_, usage) = self.detail.split_once("usage: ")?;
+        Some(usage.strip_suffix(')').unwrap_or(usage))
+    }
+
+    /// What the query is matched against
+    fn key(&self) -> &str {
+        match self.kind {
+            ItemKind::Command | ItemKind::Recent => self.text.trim_start_matches('/'),
+            ItemKind::Prompt => &self.text,
+        }
+    }
+}
+
+/// A command picked from the list, waiting for its arguments
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arguments {
+    pub command: String,
+    pub usage: String,
+    /// The list's query, restored if Esc goes back to it
+    list_query: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PaletteResult {
+    /// Still open
+    Pending,
+    Cancel,
+    /// Run this command
+    Run(String),
+    /// Put this in the input line
+    Insert(String),
+}
+
+#[derive(Debug)]
+pub struct Palette {
+    items: Vec<Item>,
+    query: String,
+    /// Indexes into `items`, best match first
+    matches: Vec<usize>,
+    selected: usize,
+    /// Set while asking for a command's arguments; `query` holds them
+    arguments: Option<Arguments>,
+}
+
+impl Palette {
+    pub fn new(items: Vec<Item>) -> Self {
+        let mut palette = Self { items, query: String::new(), matches: Vec::new(), selected: 0, arguments: None };
+        palette.refilter();
+        palette
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn matches(&self) -> Vec<&Item> {
+        self.matches.iter().map(|&i| &self.items[i]).collect()
+    }
+
+    /// Position of the highlighted row in `matches()`
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// The command whose arguments are being typed, if any
+    pub fn arguments(&self) -> Option<&Arguments> {
+        self.arguments.as_ref()
+    }
+
+    /// Typing narrows the list, ↑/↓ (or Ctrl+P/Ctrl+N) move, Enter picks and
+    /// Esc closes. While asking for arguments, typing fills them in, Enter
+    /// runs the command and Esc goes back to the list.
+    pub fn key(&mut self, key: &KeyEvent) -> PaletteResult {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        if let Some(arguments) = &self.arguments {
+            match key.code {
+                KeyCode::Esc => {
+                    self.query = arguments.list_query.clone();
+                    self.arguments = None;
+                    self.refilter();
+                }
+                KeyCode::Enter => {
+                    let line = format!("{} {}", arguments.command, self.query.trim());
+                    return PaletteResult::Run(line.trim_end().to_string());
+                }
+                KeyCode::Char('u') if ctrl => self.query.clear(),
+                KeyCode::Char(c) if !ctrl && !key.modifiers.contains(KeyModifiers::ALT) => self.query.push(c),
+                KeyCode::Backspace => {
+                    self.query.pop();
+                }
+                _ => {}
+            }
+            return PaletteResult::Pending;
+        }
+        match key.code {
+            KeyCode::Esc => return PaletteResult::Cancel,
+            KeyCode::Enter => return self.choose(),
+            KeyCode::Up => self.move_selection(-1),
+            KeyCode::Down | KeyCode::Tab => self.move_selection(1),
+            KeyCode::Char('p') if ctrl => self.move_selection(-1),
+            KeyCode::Char('n') if ctrl => self.move_selection(1),
+            KeyCode::Char('u') if ctrl => {
+                self.query.clear();
+                self.refilter();
+            }
+            KeyCode::Char(c) if !ctrl && !key.modifiers.contains(KeyModifiers::ALT) => {
+                self.query.push(c);
+                self.refilter();
+            }
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.refilter();
+            }
+            _ => {}
+        }
+        PaletteResult::Pending
+    }
+
+    fn choose(&mut self) -> PaletteResult {
+        let Some(item) = self.matches.get(self.selected).map(|&i| &self.items[i]) else {
+            return PaletteResult::Pending;
+        };
+        match (item.kind, item.usage()) {
+            (ItemKind::Command, Some(usage)) => {
+                self.arguments = Some(Arguments {
+                    command: item.text.clone(),
+                    usage: usage.to_string(),
+                    list_query: std::mem::take(&mut self.query),
+                });
+                PaletteResult::Pending
+            }
+            (ItemKind::Command | ItemKind::Recent, _) => PaletteResult::Run(item.text.clone()),
+            (ItemKind::Prompt, _) => PaletteResult::Insert(item.text.clone()),
+        }
+    }
+
+    fn move_selection(&mut self, by: isize) {
+        if !self.matches.is_empty() {
+            self.selected = (self.selected as isize + by).rem_euclid(self.matches.len() as isize) as usize;
+        }
+    }
+
+    fn refilter(&mut self) {
+        let query = self.query.trim().trim_start_matches('/');
+        let mut scored: Vec<(i64, usize)> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(i, item)| score(query, item.key()).map(|s| (s + item.boost, i)))
+            .collect();
+        // Stable, so equal scores keep the list's own order
+        scored.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+        self.matches = scored.into_iter().map(|(_, i)| i).collect();
+        self.selected = 0;
+    }
+}
+
+/// How well `query` matches `candidate`, ignoring case; None unless every
+/// query character appears in order. Each character scores more at the
+/// start of the candidate or of a word, and right after the previous match;
+/// skipped characters in between cost a point each.
+pub fn score(query: &str, candidate: &str) -> Option<i64> {
+    let query: Vec<char> = query.chars().flat_map(char::to_lowercase).filter(|c| !c.is_whitespace()).collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+    let text: Vec<char> = candidate.chars().take(MATCHED_CHARS).flat_map(char::to_lowercase).collect();
+    let bonus = |i: usize| {
+        MATCHED
+            + if i == 0 {
+                PREFIX
+            } else if !text[i - 1].is_alphanumeric() {
+                WORD_START
+            } else {
+                0
+            }
+    };
+
+    // best[i]: the best score with the query so far matched and its last
+    // character at text[i]
+    let mut best: Vec<Option<i64>> = text.iter().enumerate().map(|(i, &c)| (c == query[0]).then(|| bonus(i))).collect();
+    for &q in &query[1..] {
+        let mut next = vec![None; text.len()];
+        // max of best[k] + k over k < i - 1, for the gap penalty
+        let mut reach: Option<i64> = None;
+        for i in 1..text.len() {
+            if i >= 2 {
+                if let Some(s) = best[i - 2] {
+                    reach = Some(reach.map_or(s + (i as i64 - 2), |r| r.max(s + (i as i64 - 2))));
+                }
+            }
+            if text[i] != q {
+                continue;
+            }
+            let after_gap = reach.map(|r| r - (i as i64 - 1));
+            let consecutive = best[i - 1].map(|s| s + CONSECUTIVE);
+            next[i] = after_gap.max(consecutive).map(|s| s + bonus(i));
+        }
+        best = next;
+    }
+    best.into_iter().flatten().max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(palette: &mut Palette, code: KeyCode) -> PaletteResult {
+        palette.key(&KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn ranked(query: &str, names: &[&str]) -> Vec<String> {
+        let mut palette = Palette::new(names.iter().map(|n| Item::command(n, "")).collect());
+        for c in query.chars() {
+            press(&mut palette, KeyCode::Char(c));
+        }
+        palette.matches().into_iter().map(|item| item.text.clone()).collect()
+    }
+
+    #[test]
+    fn test_score_requires_order() {
+        assert!(score("mdl", "model").is_some());
+        assert!(score("MOD", "model").is_some());
+        assert_eq!(score("ldm", "model"), None);
+        assert_eq!(score("models", "model"), None);
+        assert_eq!(score("", "anything"), Some(0));
+    }
+
+    #[test]
+    fn test_prefix_and_word_starts_rank_first() {
+        // A prefix beats the same letters in the middle of a word
+        assert_eq!(ranked("re", &["/share", "/retry", "/compare"]), ["/retry", "/share", "/compare"]);
+        // The start of a word beats letters scattered inside one
+        assert!(score("bi", "backend-info") > score("bi", "cabinet"));
+        assert_eq!(ranked("bi", &["/cabinet", "/backend-info"]), ["/backend-info", "/cabinet"]);
+        // Consecutive letters beat spread-out ones
+        assert_eq!(ranked("mod", &["/my-old-data", "/models"]), ["/models", "/my-old-data"]);
+        // Ties keep the list order; a leading slash in the query is ignored
+        assert_eq!(ranked("/mo", &["/model", "/models"]), ["/model", "/models"]);
+        assert!(ranked("zz", &["/model"]).is_empty());
+    }
+
+    #[test]
+    fn test_boost_breaks_ties() {
+        let items = vec![Item::command("/status", ""), Item::command("/stats", "").boosted(3), Item::command("/sweep", "")];
+        let mut palette = Palette::new(items);
+        press(&mut palette, KeyCode::Char('s'));
+        let names: Vec<&str> = palette.matches().iter().map(|item| item.text.as_str()).collect();
+        assert_eq!(names, ["/stats", "/status", "/sweep"]);
+    }
+
+    #[test]
+    fn test_enter_runs_or_inserts() {
+        let items = vec![
+            Item::command("/help", "Show all available commands"),
+            Item::command("/save", "Write the last code block to a file (usage: /save <file> [-f])"),
+            Item::prompt("Build a Bell state".to_string()),
+        ];
+        let mut palette = Palette::new(items);
+        assert_eq!(press(&mut palette, KeyCode::Enter), PaletteResult::Run("/help".to_string()));
+        press(&mut palette, KeyCode::Down);
+        assert_eq!(press(&mut palette, KeyCode::Enter), PaletteResult::Pending);
+        assert_eq!(palette.arguments().unwrap().usage, "/save <file> [-f]");
+        // Esc goes back to the list rather than closing
+        assert_eq!(press(&mut palette, KeyCode::Esc), PaletteResult::Pending);
+        assert_eq!(palette.arguments(), None);
+        for c in "bell".chars() {
+            press(&mut palette, KeyCode::Char(c));
+        }
+        assert_eq!(palette.query(), "bell");
+        assert_eq!(palette.selected(), 0);
+        assert_eq!(press(&mut palette, KeyCode::Enter), PaletteResult::Insert("Build a Bell state".to_string()));
+        // Selection wraps around
+        press(&mut palette, KeyCode::Up);
+        assert_eq!(palette.selected(), 0);
+
+        for _ in 0..4 {
+            press(&mut palette, KeyCode::Backspace);
+        }
+        press(&mut palette, KeyCode::Char('x'));
+        assert_eq!(press(&mut palette, KeyCode::Enter), PaletteResult::Pending);
+        assert_eq!(press(&mut palette, KeyCode::Esc), PaletteResult::Cancel);
+    }
+
+    #[test]
+    fn test_arguments_are_asked_for() {
+        let items = vec![
+            Item::recent("/backend set ibm_kyiv".to_string()),
+            Item::command("/backend", "Show or set the default backend (usage: /backend set <name>)"),
+        ];
+        let mut palette = Palette::new(items);
+        for c in "backend".chars() {
+            press(&mut palette, KeyCode::Char(c));
+        }
+        assert_eq!(palette.matches().len(), 2);
+        press(&mut palette, KeyCode::Down);
+        press(&mut palette, KeyCode::Enter);
+        assert_eq!(palette.query(), "");
+        for c in "set ibm_torino ".chars() {
+            press(&mut palette, KeyCode::Char(c));
+        }
+        assert_eq!(press(&mut palette, KeyCode::Enter), PaletteResult::Run("/backend set ibm_torino".to_string()));
+
+        // Back in the list the query is as it was, and a recent command runs whole
+        press(&mut palette, KeyCode::Esc);
+        assert_eq!(palette.query(), "backend");
+        assert_eq!(press(&mut palette, KeyCode::Enter), PaletteResult::Run("/backend set ibm_kyiv".to_string()));
+    }
+}
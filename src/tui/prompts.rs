@@ -0,0 +1,179 @@
+// Reusable prompt files for /run-prompt
+//
+// A prompt is a text file with optional `{{name}}` placeholders, filled from
+// `name=value` arguments. `{{code}}` defaults to the last code block in the
+// chat, so recipes like "optimize this circuit for depth" can refer to it.
+// Files must live in ~/.qhub/prompts or under the current directory.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::format;
+use crate::config::Config;
+
+/// Largest prompt file accepted
+pub const MAX_PROMPT_BYTES: u64 = 64 * 1024;
+
+pub fn prompts_dir() -> Result<PathBuf> {
+    Ok(Config::config_dir()?.join("prompts"))
+}
+
+/// Read a prompt file and fill in its placeholders
+pub fn build(path: &str, args: &str, code: Option<&str>) -> Result<String> {
+    let roots = allowed_roots();
+    let path = resolve(path, &roots)?;
+    let template = load(&path)?;
+
+    let mut vars: HashMap<String, String> = parse_vars(args)?.into_iter().collect();
+    if let Some(code) = code {
+        vars.entry("code".to_string()).or_insert_with(|| code.to_string());
+    }
+    render(&template, &vars)
+}
+
+fn allowed_roots() -> Vec<PathBuf> {
+    [prompts_dir().ok(), std::env::current_dir().ok()]
+        .into_iter()
+        .flatten()
+        .filter_map(|dir| dir.canonicalize().ok())
+        .collect()
+}
+
+/// Find the file (relative names are tried in the prompts directory first,
+/// then the current directory) and check it is inside an allowed root
+fn resolve(path: &str, roots: &[PathBuf]) -> Result<PathBuf> {
+    let path = Path::new(path);
+    let candidates: Vec<PathBuf> = if path.is_absolute() {
+        vec![path.to_path_buf()]
+    } else {
+        roots.iter().map(|root| root.join(path)).collect()
+    };
+    let found = candidates
+        .iter()
+        .find_map(|candidate| candidate.canonicalize().ok())
+        .with_context(|| format!("Prompt file not found: {}", path.display()))?;
+
+    if !roots.iter().any(|root| found.starts_with(root)) {
+        anyhow::bail!(
+            "{} is outside the allowed directories (~/.qhub/prompts and the current directory)",
+            found.display()
+        );
+    }
+    Ok(found)
+}
+
+fn load(path: &Path) -> Result<String> {
+    let size = fs::metadata(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?
+        .len();
+    if size > MAX_PROMPT_BYTES {
+        anyhow::bail!(
+            "{} is {}; prompt files are limited to {}",
+            path.display(),
+            format::bytes(size),
+            format::bytes(MAX_PROMPT_BYTES)
+        );
+    }
+    fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))
+}
+
+/// `name=value` pairs; values may be double-quoted to include spaces
+pub fn parse_vars(args: &str) -> Result<Vec<(String, String)>> {
+    let mut vars = Vec::new();
+    let mut rest = args.trim_start();
+    while !rest.is_empty() {
+        let (name, after) = rest
+            .split_once('=')
+            .with_context(|| format!("Expected name=value, got '{}'", rest.split_whitespace().next().unwrap_or(rest)))?;
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            anyhow::bail!("Expected name=value, got '{}='", name);
+        }
+        let (value, remaining) = match after.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"').with_context(|| format!("Unterminated quote in {}=", name))?;
+                (&quoted[..end], &quoted[end + 1..])
+            }
+            None => after.split_once(char::is_whitespace).unwrap_or((after, "")),
+        };
+        vars.push((name.to_string(), value.to_string()));
+        rest = remaining.trim_start();
+    }
+    Ok(vars)
+}
+
+/// Replace `{{name}}` placeholders; any left without a value is an error
+pub fn render(template: &str, vars: &HashMap<String, String>) -> Result<String> {
+    let mut output = String::with_capacity(template.len());
+    let mut missing = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        output.push_str(&rest[..start]);
+        let name = rest[start + 2..start + 2 + len].trim();
+        match vars.get(name) {
+            Some(value) => output.push_str(value),
+            None => missing.push(name.to_string()),
+        }
+        rest = &rest[start + 2 + len + 2..];
+    }
+    output.push_str(rest);
+
+    if !missing.is_empty() {
+        missing.sort();
+        missing.dedup();
+        anyhow::bail!("Missing values for: {} (pass them as name=value)", missing.join(", "));
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_vars() {
+        let vars = parse_vars(r#"target=depth backend="ibm brisbane""#).unwrap();
+        assert_eq!(
+            vars,
+            vec![
+                ("target".to_string(), "depth".to_string()),
+                ("backend".to_string(), "ibm brisbane".to_string()),
+            ]
+        );
+        assert!(parse_vars("").unwrap().is_empty());
+        assert!(parse_vars("oops").is_err());
+        assert!(parse_vars(r#"x="open"#).is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        let vars: HashMap<String, String> =
+            [("target".to_string(), "depth".to_string())].into_iter().collect();
+        assert_eq!(
+            render("Optimize for {{ target }}, then {{target}}.", &vars).unwrap(),
+            "Optimize for depth, then depth."
+        );
+        let err = render("{{code}} and {{target}}", &vars).unwrap_err();
+        assert!(err.to_string().contains("code"));
+        assert_eq!(render("no {{ placeholder", &vars).unwrap(), "no {{ placeholder");
+    }
+
+    #[test]
+    fn test_resolve_stays_inside_roots() {
+        let root = std::env::temp_dir().join(format!("qhub-prompts-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("depth.md"), "Optimize {{code}}").unwrap();
+        let roots = vec![root.canonicalize().unwrap()];
+
+        assert!(resolve("depth.md", &roots).is_ok());
+        assert!(resolve("missing.md", &roots).is_err());
+        let outside = resolve("../", &roots).unwrap_err();
+        assert!(outside.to_string().contains("outside"));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,106 @@
+// Warning banner as the monthly quotas run out
+//
+// The tier allows so many AI tokens and quantum jobs per calendar month
+// (UTC). With a database connected, month-to-date usage is read from the
+// usage records every few minutes; from 80% of either quota a banner above
+// the chat says so and points at /upgrade, turning red from 95%. Esc hides
+// it until the next threshold is crossed.
+
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use std::time::Duration;
+
+use crate::config::tiers::TierLimits;
+use crate::db::usage::PeriodUsage;
+
+/// How often usage is read again
+pub const CHECK_EVERY: Duration = Duration::from_secs(300);
+
+/// Share of a quota that raises the banner
+const WARN_AT: f64 = 0.8;
+/// Share at which it turns red
+const CRITICAL_AT: f64 = 0.95;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Warning,
+    Critical,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Banner {
+    pub level: Level,
+    pub text: String,
+}
+
+/// Start of the calendar month `now` is in, as unix seconds
+pub fn month_start(now: DateTime<Utc>) -> i64 {
+    Utc.with_ymd_and_hms(now.year(), now.month(), 1, 0, 0, 0)
+        .single()
+        .map_or(now.timestamp(), |start| start.timestamp())
+}
+
+/// The banner for this month's usage, about whichever quota is closest to
+/// running out; None below 80% of both or when the tier has no quotas
+pub fn banner(usage: PeriodUsage, limits: &TierLimits) -> Option<Banner> {
+    let share = |used: u64, limit: Option<u64>| limit.filter(|l| *l > 0).map(|l| (used as f64 / l as f64, used, l));
+    let (share, used, limit, what) = [
+        share(usage.tokens, limits.monthly_tokens).map(|(s, u, l)| (s, u, l, "AI tokens")),
+        share(usage.jobs, limits.monthly_jobs).map(|(s, u, l)| (s, u, l, "jobs")),
+    ]
+    .into_iter()
+    .flatten()
+    .max_by(|a, b| a.0.total_cmp(&b.0))?;
+    if share < WARN_AT {
+        return None;
+    }
+    let level = if share >= CRITICAL_AT { Level::Critical } else { Level::Warning };
+    let text = if used >= limit {
+        format!("This month's {} quota is used up ({} of {}) - /upgrade for more", what, used, limit)
+    } else {
+        format!(
+            "{:.0}% of this month's {} used ({} of {}) - /upgrade for more · Esc hides this",
+            (share * 100.0).floor(),
+            what,
+            used,
+            limit
+        )
+    };
+    Some(Banner { level, text })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::tiers::{ENTERPRISE, FREE};
+
+    #[test]
+    fn test_banner_thresholds() {
+        let usage = |tokens, jobs| PeriodUsage { tokens, jobs };
+        assert_eq!(banner(usage(150_000, 10), &FREE), None);
+
+        let warning = banner(usage(170_000, 10), &FREE).unwrap();
+        assert_eq!(warning.level, Level::Warning);
+        assert_eq!(
+            warning.text,
+            "85% of this month's AI tokens used (170000 of 200000) - /upgrade for more · Esc hides this"
+        );
+
+        // The quota closest to running out is the one shown
+        let jobs = banner(usage(170_000, 97), &FREE).unwrap();
+        assert_eq!(jobs.level, Level::Critical);
+        assert!(jobs.text.starts_with("97% of this month's jobs"), "{}", jobs.text);
+        assert_eq!(
+            banner(usage(0, 120), &FREE).unwrap().text,
+            "This month's jobs quota is used up (120 of 100) - /upgrade for more"
+        );
+        assert!(Level::Critical > Level::Warning);
+
+        assert_eq!(banner(usage(u64::MAX, u64::MAX), &ENTERPRISE), None);
+    }
+
+    #[test]
+    fn test_month_start() {
+        let now = Utc.with_ymd_and_hms(2026, 3, 17, 12, 30, 0).unwrap();
+        assert_eq!(month_start(now), Utc.with_ymd_and_hms(2026, 3, 1, 0, 0, 0).unwrap().timestamp());
+    }
+}
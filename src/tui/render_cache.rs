@@ -0,0 +1,278 @@
+// Wrapped transcript rows, cached per message
+//
+// Laying out a message means splitting it into lines, tracking code fences
+// and wrapping to the terminal width. None of that changes between frames
+// unless the width does or the message itself is edited, so the rows are
+// kept here keyed by message id and only the new or changed messages are
+// laid out again. Wrapping is done here rather than by the Paragraph so the
+// renderer knows the real number of rows, which the scroll bounds and the
+// click map are measured in.
+
+use std::collections::{HashMap, HashSet};
+
+use ratatui::style::Style;
+use ratatui::text::{Line, Span};
+use unicode_width::UnicodeWidthChar;
+use uuid::Uuid;
+
+use super::app::Message;
+
+/// What a row of a message is, for mouse clicks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RowKind {
+    Text,
+    /// Inside the message's nth code block
+    Code(usize),
+    /// Blank row after a message
+    Spacer,
+}
+
+#[derive(Debug, Clone)]
+pub struct Row {
+    pub line: Line<'static>,
+    pub kind: RowKind,
+}
+
+struct Entry {
+    /// Catches most in-place edits; others call `invalidate`
+    content_len: usize,
+    truncated: bool,
+    compact: bool,
+    rows: Vec<Row>,
+}
+
+#[derive(Default)]
+pub struct RenderCache {
+    width: u16,
+    entries: HashMap<Uuid, Entry>,
+    /// Messages laid out so far, for the tests and the benchmark
+    #[cfg_attr(not(test), allow(dead_code))]
+    laid_out: usize,
+}
+
+impl RenderCache {
+    /// Drop everything when the terminal width changes
+    pub fn set_width(&mut self, width: u16) {
+        if width != self.width {
+            self.width = width;
+            self.entries.clear();
+        }
+    }
+
+    /// Lay `id` out again next frame, after its content was edited in place
+    pub fn invalidate(&mut self, id: &Uuid) {
+        self.entries.remove(id);
+    }
+
+    /// Rows of `message`, laid out with `layout` unless cached
+    pub fn rows(&mut self, message: &Message, compact: bool, layout: impl FnOnce(&Message, u16) -> Vec<Row>) -> &[Row] {
+        let fresh = self
+            .entries
+            .get(&message.id)
+            .is_some_and(|e| {
+                e.content_len == message.content.len() && e.truncated == message.truncated && e.compact == compact
+            });
+        if !fresh {
+            self.laid_out += 1;
+            let rows = layout(message, self.width);
+            let entry = Entry { content_len: message.content.len(), truncated: message.truncated, compact, rows };
+            self.entries.insert(message.id, entry);
+        }
+        &self.entries[&message.id].rows
+    }
+
+    /// Rows already laid out for `id` this frame
+    pub fn get(&self, id: &Uuid) -> &[Row] {
+        self.entries.get(id).map_or(&[], |e| e.rows.as_slice())
+    }
+
+    /// Drop messages that are gone, e.g. after /clear; `notice` is drawn
+    /// with them and kept too
+    pub fn retain(&mut self, messages: &[Message], notice: Option<&Message>) {
+        if self.entries.len() > messages.len() + notice.is_some() as usize {
+            let ids: HashSet<Uuid> = messages.iter().chain(notice).map(|m| m.id).collect();
+            self.entries.retain(|id, _| ids.contains(id));
+        }
+    }
+}
+
+/// Split `text` into rows at most `width` columns wide, breaking after
+/// whitespace where possible. Whitespace is kept so code stays aligned.
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut rows = Vec::new();
+    let mut row = String::new();
+    let mut row_width = 0;
+    // Byte offset in `row` just after the last whitespace, and the width before it
+    let mut last_break: Option<(usize, usize)> = None;
+
+    for c in text.chars() {
+        let w = c.width().unwrap_or(0);
+        if row_width + w > width && !row.is_empty() {
+            match last_break {
+                Some((at, before)) if at < row.len() => {
+                    let rest = row.split_off(at);
+                    rows.push(std::mem::replace(&mut row, rest));
+                    row_width -= before;
+                }
+                _ => {
+                    rows.push(std::mem::take(&mut row));
+                    row_width = 0;
+                }
+            }
+            last_break = None;
+        }
+        row.push(c);
+        row_width += w;
+        if c.is_whitespace() {
+            last_break = Some((row.len(), row_width));
+        }
+    }
+    if !row.is_empty() || rows.is_empty() {
+        rows.push(row);
+    }
+    rows
+}
+
+/// `wrap` for styled text: the pieces split across rows where the wrap falls
+pub fn wrap_styled(pieces: &[(String, Style)], width: usize) -> Vec<Vec<Span<'static>>> {
+    let text: String = pieces.iter().map(|(piece, _)| piece.as_str()).collect();
+    let mut pieces = pieces.iter().filter(|(piece, _)| !piece.is_empty());
+    let mut current = pieces.next().map(|(piece, style)| (piece.as_str(), *style));
+    let mut rows = Vec::new();
+    // Wrapped rows add back up to the text, so each takes the next bytes of the pieces
+    for row in wrap(&text, width) {
+        let mut spans = Vec::new();
+        let mut left = row.len();
+        while left > 0 {
+            let Some((piece, style)) = current else {
+                break;
+            };
+            let take = left.min(piece.len());
+            spans.push(Span::styled(piece[..take].to_string(), style));
+            left -= take;
+            current = if take < piece.len() {
+                Some((&piece[take..], style))
+            } else {
+                pieces.next().map(|(piece, style)| (piece.as_str(), *style))
+            };
+        }
+        rows.push(spans);
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::app::MessageRole;
+    use chrono::Local;
+    use ratatui::style::Modifier;
+    use std::time::Instant;
+
+    fn message(content: &str) -> Message {
+        Message {
+            id: Uuid::new_v4(),
+            role: MessageRole::Assistant,
+            content: content.to_string(),
+            timestamp: Local::now(),
+            truncated: false,
+            cached: false,
+            alternatives: Vec::new(),
+        }
+    }
+
+    /// Stand-in for the renderer's layout: two-column prefix, wrapped text
+    fn layout(message: &Message, width: u16) -> Vec<Row> {
+        message
+            .content
+            .lines()
+            .flat_map(|line| wrap(line, width as usize - 2))
+            .map(|text| Row { line: Line::from(vec![Span::raw("  "), Span::raw(text)]), kind: RowKind::Text })
+            .collect()
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap("", 10), vec![""]);
+        assert_eq!(wrap("hello world", 20), vec!["hello world"]);
+        assert_eq!(wrap("hello world again", 11), vec!["hello ", "world again"]);
+        assert_eq!(wrap("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+        // Wide characters take two columns
+        assert_eq!(wrap("量子計算", 5), vec!["量子", "計算"]);
+        assert_eq!(wrap("    qc.h(0)", 6), vec!["    ", "qc.h(0", ")"]);
+    }
+
+    #[test]
+    fn test_wrap_styled_splits_pieces() {
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+        let pieces = vec![("hello ".to_string(), Style::default()), ("bold world".to_string(), bold)];
+        let rows = wrap_styled(&pieces, 11);
+        let texts: Vec<Vec<(&str, Style)>> =
+            rows.iter().map(|row| row.iter().map(|s| (s.content.as_ref(), s.style)).collect()).collect();
+        assert_eq!(texts, vec![vec![("hello ", Style::default()), ("bold ", bold)], vec![("world", bold)]]);
+        assert_eq!(wrap_styled(&[], 10), vec![Vec::<Span>::new()]);
+    }
+
+    #[test]
+    fn test_only_new_or_changed_messages_are_laid_out() {
+        let mut cache = RenderCache::default();
+        let mut messages = vec![message("one"), message("two")];
+        cache.set_width(40);
+        for m in &messages {
+            cache.rows(m, false, layout);
+        }
+        assert_eq!(cache.laid_out, 2);
+
+        messages.push(message("three"));
+        for m in &messages {
+            cache.rows(m, false, layout);
+        }
+        assert_eq!(cache.laid_out, 3);
+
+        messages[0].content.push_str(" more");
+        cache.rows(&messages[0], false, layout);
+        cache.rows(&messages[1], true, layout);
+        assert_eq!(cache.laid_out, 5);
+
+        cache.set_width(8);
+        assert!(cache.get(&messages[2].id).is_empty());
+        cache.rows(&messages[0], false, layout);
+        assert_eq!(cache.get(&messages[0].id).len(), 2);
+
+        let notice = messages.pop().unwrap();
+        cache.rows(&notice, false, layout);
+        messages.truncate(1);
+        cache.retain(&messages, Some(&notice));
+        assert_eq!(cache.entries.len(), 2);
+        cache.retain(&messages, None);
+        assert_eq!(cache.entries.len(), 1);
+    }
+
+    /// `cargo test --release render_cache -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_2000_message_transcript() {
+        let messages: Vec<Message> = (0..2000)
+            .map(|i| message(&format!("Message {} with enough words to wrap at least once on a narrow terminal\n```python\nqc.h({})\n```", i, i)))
+            .collect();
+        let frame = |cache: &mut RenderCache| -> usize {
+            cache.set_width(80);
+            messages.iter().map(|m| cache.rows(m, false, layout).len()).sum()
+        };
+
+        let start = Instant::now();
+        let rows = frame(&mut RenderCache::default());
+        let cold = start.elapsed();
+
+        let mut cache = RenderCache::default();
+        frame(&mut cache);
+        let start = Instant::now();
+        for _ in 0..100 {
+            assert_eq!(frame(&mut cache), rows);
+        }
+        let warm = start.elapsed() / 100;
+        assert_eq!(cache.laid_out, messages.len());
+        println!("{} rows: uncached frame {:?}, cached frame {:?}", rows, cold, warm);
+    }
+}
@@ -0,0 +1,177 @@
+// Scroll position of the chat transcript
+//
+// Everything is in rendered rows. The renderer reports the transcript height
+// and viewport each frame with `set_layout`; key and mouse handlers only move
+// the offset, which is clamped there so it can never leave the valid range.
+
+#[derive(Debug, Clone, Default)]
+pub struct ScrollState {
+    /// First visible row
+    offset: usize,
+    /// Pinned to the bottom, so new messages stay in view
+    follow: bool,
+    /// Rows were added below the viewport since the user scrolled up
+    unseen: bool,
+    total_rows: usize,
+    viewport: usize,
+}
+
+impl ScrollState {
+    pub fn new() -> Self {
+        Self {
+            follow: true,
+            ..Self::default()
+        }
+    }
+
+    /// Update the transcript and viewport height, clamping the offset
+    pub fn set_layout(&mut self, total_rows: usize, viewport: usize) {
+        if !self.follow && total_rows > self.total_rows {
+            self.unseen = true;
+        }
+        self.total_rows = total_rows;
+        self.viewport = viewport;
+        self.offset = if self.follow {
+            self.max_offset()
+        } else {
+            self.offset.min(self.max_offset())
+        };
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Whether to show the "new messages" marker
+    pub fn has_unseen(&self) -> bool {
+        self.unseen
+    }
+
+    /// Offset that shows the last row at the bottom of the viewport
+    pub fn max_offset(&self) -> usize {
+        self.total_rows.saturating_sub(self.viewport)
+    }
+
+    /// Rows moved by PageUp/PageDown: a screenful, keeping two rows of context
+    pub fn page_size(&self) -> usize {
+        self.viewport.saturating_sub(2).max(1)
+    }
+
+    pub fn scroll_up(&mut self, rows: usize) {
+        self.offset = self.offset.min(self.max_offset()).saturating_sub(rows);
+        self.follow = false;
+    }
+
+    pub fn scroll_down(&mut self, rows: usize) {
+        self.offset = self.offset.saturating_add(rows).min(self.max_offset());
+        self.follow = self.offset == self.max_offset();
+        self.unseen &= !self.follow;
+    }
+
+    pub fn page_up(&mut self) {
+        self.scroll_up(self.page_size());
+    }
+
+    pub fn page_down(&mut self) {
+        self.scroll_down(self.page_size());
+    }
+
+    pub fn scroll_to_top(&mut self) {
+        self.offset = 0;
+        self.follow = self.max_offset() == 0;
+    }
+
+    pub fn scroll_to_bottom(&mut self) {
+        self.follow = true;
+        self.unseen = false;
+        self.offset = self.max_offset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scrolled(total_rows: usize, viewport: usize) -> ScrollState {
+        let mut scroll = ScrollState::new();
+        scroll.set_layout(total_rows, viewport);
+        scroll
+    }
+
+    #[test]
+    fn test_follows_bottom_until_scrolled_up() {
+        let mut scroll = scrolled(100, 20);
+        assert_eq!(scroll.offset(), 80);
+
+        scroll.set_layout(110, 20);
+        assert_eq!(scroll.offset(), 90);
+
+        scroll.scroll_up(5);
+        scroll.set_layout(120, 20);
+        assert_eq!(scroll.offset(), 85);
+
+        scroll.scroll_down(1000);
+        scroll.set_layout(130, 20);
+        assert_eq!(scroll.offset(), 110);
+    }
+
+    #[test]
+    fn test_paging_uses_viewport_height() {
+        let mut scroll = scrolled(100, 20);
+        assert_eq!(scroll.page_size(), 18);
+
+        scroll.page_up();
+        assert_eq!(scroll.offset(), 62);
+        scroll.page_down();
+        assert_eq!(scroll.offset(), 80);
+
+        // Tiny panes still move
+        assert_eq!(scrolled(100, 1).page_size(), 1);
+    }
+
+    #[test]
+    fn test_home_and_end() {
+        let mut scroll = scrolled(100, 20);
+        scroll.scroll_to_top();
+        assert_eq!(scroll.offset(), 0);
+        scroll.set_layout(150, 20);
+        assert_eq!(scroll.offset(), 0);
+
+        scroll.scroll_to_bottom();
+        assert_eq!(scroll.offset(), 130);
+    }
+
+    #[test]
+    fn test_new_rows_below_viewport_are_unseen() {
+        let mut scroll = scrolled(100, 20);
+        scroll.set_layout(110, 20);
+        assert!(!scroll.has_unseen());
+
+        scroll.scroll_up(10);
+        scroll.set_layout(110, 20);
+        assert!(!scroll.has_unseen());
+        scroll.set_layout(115, 20);
+        assert!(scroll.has_unseen());
+
+        scroll.scroll_down(1000);
+        assert!(!scroll.has_unseen());
+    }
+
+    #[test]
+    fn test_clamps_at_both_ends() {
+        let mut scroll = scrolled(100, 20);
+        scroll.scroll_to_top();
+        scroll.scroll_up(50);
+        assert_eq!(scroll.offset(), 0);
+
+        scroll.scroll_down(usize::MAX);
+        assert_eq!(scroll.offset(), 80);
+
+        // Shorter than the viewport: nothing to scroll
+        let mut scroll = scrolled(5, 20);
+        scroll.scroll_down(3);
+        scroll.page_up();
+        assert_eq!(scroll.offset(), 0);
+        assert_eq!(scroll.max_offset(), 0);
+    }
+}
@@ -0,0 +1,229 @@
+// What App needs from the AI provider and the account service
+//
+// App reaches both through these traits rather than `DeepSeekClient` and
+// `ApiClient` directly, so tests can hand it the fakes in `fake`, which
+// answer from a script, and drive prompts, slash commands and the
+// check_*_response handlers without a network. Each call returns a boxed
+// future owning all it needs, since App runs them on spawned tasks. Account
+// calls take the session token as an argument: the `ApiClient` App keeps
+// for sharing and telemetry holds the token of the account in use, and a
+// switch checks another one before anything changes.
+
+use anyhow::Result;
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::api::client::{ApiClient, ApiError, AuthResponse, LoginRequest, RegisterRequest, User};
+use crate::api::deepseek::{ChatMessage, ChatReply, DeepSeekClient, Sampling};
+
+/// A request on its way, for spawning
+pub type Pending<T, E = anyhow::Error> = Pin<Box<dyn Future<Output = Result<T, E>> + Send>>;
+
+/// The chat model
+pub trait Ai: Send + Sync {
+    /// Temperature requests use unless the session or the request sets one
+    fn temperature(&self) -> f32;
+
+    /// What `ai.cache` files a reply to `history` under
+    fn cache_key(&self, history: &[ChatMessage], sampling: &Sampling) -> String;
+
+    /// The reply to `history`, with `sampling` over the configured model and
+    /// parameters. Fails with `RateLimited` or `NoReply` where those apply.
+    fn chat(&self, history: Vec<ChatMessage>, sampling: Sampling) -> Pending<ChatReply>;
+
+    /// The models the provider offers
+    fn list_models(&self) -> Pending<Vec<String>>;
+}
+
+/// The QHub account service
+pub trait Auth: Send + Sync {
+    fn login(&self, request: LoginRequest) -> Pending<AuthResponse, ApiError>;
+
+    fn register(&self, request: RegisterRequest) -> Pending<AuthResponse, ApiError>;
+
+    /// The account `token` belongs to, if the session is still valid
+    fn verify(&self, token: String) -> Pending<User, ApiError>;
+
+    /// End the session `token` is for; None fails as not logged in
+    fn logout(&self, token: Option<String>) -> Pending<(), ApiError>;
+}
+
+impl Ai for DeepSeekClient {
+    fn temperature(&self) -> f32 {
+        DeepSeekClient::temperature(self)
+    }
+
+    fn cache_key(&self, history: &[ChatMessage], sampling: &Sampling) -> String {
+        DeepSeekClient::cache_key(self, history, sampling)
+    }
+
+    fn chat(&self, history: Vec<ChatMessage>, sampling: Sampling) -> Pending<ChatReply> {
+        let client = self.clone();
+        Box::pin(async move { client.chat_with(history, &sampling).await })
+    }
+
+    fn list_models(&self) -> Pending<Vec<String>> {
+        let client = self.clone();
+        Box::pin(async move { DeepSeekClient::list_models(&client).await })
+    }
+}
+
+impl Auth for ApiClient {
+    fn login(&self, request: LoginRequest) -> Pending<AuthResponse, ApiError> {
+        let client = self.clone();
+        Box::pin(async move { ApiClient::login(&client, request).await })
+    }
+
+    fn register(&self, request: RegisterRequest) -> Pending<AuthResponse, ApiError> {
+        let client = self.clone();
+        Box::pin(async move { ApiClient::register(&client, request).await })
+    }
+
+    fn verify(&self, token: String) -> Pending<User, ApiError> {
+        let mut client = self.clone();
+        client.set_token(token);
+        Box::pin(async move { client.verify_token().await })
+    }
+
+    fn logout(&self, token: Option<String>) -> Pending<(), ApiError> {
+        let mut client = self.clone();
+        match token {
+            Some(token) => client.set_token(token),
+            None => client.clear_token(),
+        }
+        Box::pin(async move { ApiClient::logout(&client).await })
+    }
+}
+
+/// Scripted stand-ins for tests
+#[cfg(test)]
+pub mod fake {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    /// Replies with what `reply` and `fail` queued, in order, and keeps every
+    /// history it was sent
+    #[derive(Default)]
+    pub struct FakeAi {
+        replies: Mutex<VecDeque<Result<ChatReply, String>>>,
+        pub sent: Mutex<Vec<Vec<ChatMessage>>>,
+        pub models: Vec<String>,
+    }
+
+    impl FakeAi {
+        pub fn reply(&self, content: &str) -> &Self {
+            let reply = ChatReply { content: content.to_string(), usage: None, truncated: false };
+            self.replies.lock().unwrap().push_back(Ok(reply));
+            self
+        }
+
+        pub fn fail(&self, error: &str) -> &Self {
+            self.replies.lock().unwrap().push_back(Err(error.to_string()));
+            self
+        }
+
+        /// The last message of each history sent
+        pub fn prompts(&self) -> Vec<String> {
+            let sent = self.sent.lock().unwrap();
+            sent.iter().filter_map(|history| history.last()).map(|m| m.content.clone()).collect()
+        }
+    }
+
+    impl Ai for FakeAi {
+        fn temperature(&self) -> f32 {
+            1.0
+        }
+
+        fn cache_key(&self, history: &[ChatMessage], _: &Sampling) -> String {
+            history.iter().map(|m| m.content.as_str()).collect::<Vec<_>>().join("\n")
+        }
+
+        fn chat(&self, history: Vec<ChatMessage>, _: Sampling) -> Pending<ChatReply> {
+            self.sent.lock().unwrap().push(history);
+            let next = self.replies.lock().unwrap().pop_front().unwrap_or_else(|| Err("no reply scripted".to_string()));
+            Box::pin(async move { next.map_err(anyhow::Error::msg) })
+        }
+
+        fn list_models(&self) -> Pending<Vec<String>> {
+            let models = self.models.clone();
+            Box::pin(async move { Ok(models) })
+        }
+    }
+
+    /// One account, `email` with `password`, whose session token is
+    /// "token-" and the email; keeps the tokens it was asked to log out
+    pub struct FakeAuth {
+        pub email: String,
+        pub password: String,
+        pub tier: String,
+        pub logged_out: Mutex<Vec<Option<String>>>,
+    }
+
+    impl FakeAuth {
+        pub fn new(email: &str, password: &str) -> Self {
+            Self {
+                email: email.to_string(),
+                password: password.to_string(),
+                tier: "pro".to_string(),
+                logged_out: Mutex::new(Vec::new()),
+            }
+        }
+
+        pub fn token(&self) -> String {
+            format!("token-{}", self.email)
+        }
+
+        fn user(&self) -> User {
+            User {
+                id: "u1".to_string(),
+                email: self.email.clone(),
+                username: None,
+                display_name: None,
+                tier: self.tier.clone(),
+                last_login_at: None,
+            }
+        }
+
+        fn session(&self) -> AuthResponse {
+            AuthResponse { token: self.token(), user: self.user(), expires_at: 0 }
+        }
+    }
+
+    impl Auth for FakeAuth {
+        fn login(&self, request: LoginRequest) -> Pending<AuthResponse, ApiError> {
+            let result = if request.email == self.email && request.password == self.password {
+                Ok(self.session())
+            } else {
+                Err(ApiError::Unauthorized("Invalid email or password".to_string()))
+            };
+            Box::pin(async move { result })
+        }
+
+        fn register(&self, request: RegisterRequest) -> Pending<AuthResponse, ApiError> {
+            let result = if request.email == self.email {
+                Err(ApiError::Validation("Email already registered".to_string()))
+            } else {
+                let mut session = self.session();
+                session.user.email = request.email;
+                session.user.tier = "free".to_string();
+                Ok(session)
+            };
+            Box::pin(async move { result })
+        }
+
+        fn verify(&self, token: String) -> Pending<User, ApiError> {
+            let result = if token == self.token() {
+                Ok(self.user())
+            } else {
+                Err(ApiError::Unauthorized("Session expired".to_string()))
+            };
+            Box::pin(async move { result })
+        }
+
+        fn logout(&self, token: Option<String>) -> Pending<(), ApiError> {
+            self.logged_out.lock().unwrap().push(token);
+            Box::pin(async move { Ok(()) })
+        }
+    }
+}
@@ -0,0 +1,99 @@
+// Session activity counters behind the /stats dashboard
+//
+// Everything here is in-memory and resets when qhub exits; all-time numbers
+// come from the `usage_records` table when a database is connected.
+
+use std::time::{Duration, Instant};
+
+/// How many recent AI latencies the sparkline shows
+const LATENCY_HISTORY: usize = 40;
+
+pub struct SessionStats {
+    started: Instant,
+    pub messages_sent: usize,
+    pub tokens_used: u64,
+    pub circuits_generated: usize,
+    pub circuits_saved: usize,
+    pub circuits_run: usize,
+    latencies: Vec<Duration>,
+    /// Per-resource totals from `usage_records`, once loaded
+    pub all_time: Option<Vec<(String, i64)>>,
+}
+
+impl Default for SessionStats {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            messages_sent: 0,
+            tokens_used: 0,
+            circuits_generated: 0,
+            circuits_saved: 0,
+            circuits_run: 0,
+            latencies: Vec::new(),
+            all_time: None,
+        }
+    }
+}
+
+impl SessionStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a completed AI reply
+    pub fn record_reply(&mut self, content: &str, latency: Duration, tokens: Option<u64>) {
+        if self.latencies.len() == LATENCY_HISTORY {
+            self.latencies.remove(0);
+        }
+        self.latencies.push(latency);
+        self.tokens_used += tokens.unwrap_or(0);
+        if contains_code_block(content) {
+            self.circuits_generated += 1;
+        }
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub fn average_latency(&self) -> Option<Duration> {
+        if self.latencies.is_empty() {
+            return None;
+        }
+        Some(self.latencies.iter().sum::<Duration>() / self.latencies.len() as u32)
+    }
+
+    /// Recent latencies in milliseconds, oldest first
+    pub fn latency_history_ms(&self) -> Vec<u64> {
+        self.latencies.iter().map(|d| d.as_millis() as u64).collect()
+    }
+}
+
+/// Replies are asked to put circuits in fenced code blocks
+fn contains_code_block(content: &str) -> bool {
+    content.lines().any(|line| line.trim_start().starts_with("```"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_reply() {
+        let mut stats = SessionStats::new();
+        assert_eq!(stats.average_latency(), None);
+
+        stats.record_reply("Here you go:\n```python\nqc.h(0)\n```", Duration::from_millis(300), Some(50));
+        stats.record_reply("Sure, happy to explain.", Duration::from_millis(100), None);
+
+        assert_eq!(stats.tokens_used, 50);
+        assert_eq!(stats.circuits_generated, 1);
+        assert_eq!(stats.average_latency(), Some(Duration::from_millis(200)));
+        assert_eq!(stats.latency_history_ms(), vec![300, 100]);
+
+        for _ in 0..LATENCY_HISTORY {
+            stats.record_reply("", Duration::from_millis(1), None);
+        }
+        assert_eq!(stats.latency_history_ms().len(), LATENCY_HISTORY);
+    }
+}
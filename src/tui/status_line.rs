@@ -0,0 +1,228 @@
+// Status bar composition
+//
+// The status bar is one row, and everything it can show (who is logged in,
+// key hints, token count, database health, notices, warnings) does not fit
+// on an 80-column terminal. Each segment therefore carries a priority and
+// progressively shorter forms. While the line is too wide, the least
+// important segment that can still give way is shortened or dropped, so key
+// hints disappear first and the account and warnings stay longest. The line
+// is then padded to exactly the available width.
+
+use ratatui::style::Style;
+use ratatui::text::{Line, Span};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+const SEPARATOR: &str = " · ";
+
+/// Longest the account is shown in full, in columns
+const MAX_USER_WIDTH: usize = 32;
+
+#[derive(Debug, Clone)]
+pub struct Segment {
+    /// Longest first; the last is the minimum form
+    forms: Vec<String>,
+    style: Style,
+    /// Higher is kept longer
+    priority: u8,
+    /// Kept (ellipsized if need be) rather than dropped
+    required: bool,
+}
+
+impl Segment {
+    pub fn new(text: impl Into<String>, style: Style, priority: u8) -> Self {
+        Self { forms: vec![text.into()], style, priority, required: false }
+    }
+
+    /// A shorter form to use before the segment is dropped
+    pub fn or(mut self, shorter: impl Into<String>) -> Self {
+        self.forms.push(shorter.into());
+        self
+    }
+
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    /// The logged-in account: an email shortens to its local part, and
+    /// anything long is ellipsized in the middle
+    pub fn user(name: &str, style: Style, priority: u8) -> Self {
+        let mut segment = Self::new(ellipsize_middle(name, MAX_USER_WIDTH), style, priority).required();
+        if let Some((local, _)) = name.split_once('@') {
+            segment = segment.or(ellipsize_middle(local, MAX_USER_WIDTH));
+        }
+        segment
+    }
+}
+
+/// Shorten `text` to at most `max` columns by replacing its middle with "…"
+pub fn ellipsize_middle(text: &str, max: usize) -> String {
+    if text.width() <= max {
+        return text.to_string();
+    }
+    if max == 0 {
+        return String::new();
+    }
+    let budget = max - 1;
+    let head_budget = budget.div_ceil(2);
+    let tail_budget = budget - head_budget;
+
+    let mut head = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w > head_budget {
+            break;
+        }
+        head.push(c);
+        used += w;
+    }
+    let mut tail = Vec::new();
+    let mut used = 0;
+    for c in text.chars().rev() {
+        let w = c.width().unwrap_or(0);
+        if used + w > tail_budget {
+            break;
+        }
+        tail.push(c);
+        used += w;
+    }
+    head.push('…');
+    head.extend(tail.into_iter().rev());
+    head
+}
+
+/// Cut `text` to at most `max` columns
+pub fn truncate(text: &str, max: usize) -> String {
+    let mut out = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w > max {
+            break;
+        }
+        out.push(c);
+        used += w;
+    }
+    out
+}
+
+/// Lay `segments` out in order on one line exactly `width` columns wide
+pub fn compose(segments: &[Segment], separator_style: Style, width: usize) -> Line<'static> {
+    // Index into each segment's forms, or None once dropped
+    let mut chosen: Vec<Option<usize>> = vec![Some(0); segments.len()];
+    let mut texts: Vec<String> = segments.iter().map(|s| s.forms[0].clone()).collect();
+    let total = |chosen: &[Option<usize>], texts: &[String]| -> usize {
+        let shown: Vec<usize> = chosen
+            .iter()
+            .zip(texts)
+            .filter(|(c, _)| c.is_some())
+            .map(|(_, t)| t.width())
+            .collect();
+        shown.iter().sum::<usize>() + SEPARATOR.width() * shown.len().saturating_sub(1)
+    };
+
+    while total(&chosen, &texts) > width {
+        // Least important segment that can still give way; later ones first on ties
+        let candidate = (0..segments.len())
+            .filter(|&i| match chosen[i] {
+                Some(form) => form + 1 < segments[i].forms.len() || !segments[i].required,
+                None => false,
+            })
+            .min_by_key(|&i| (segments[i].priority, std::cmp::Reverse(i)));
+        let Some(i) = candidate else { break };
+        let next = chosen[i].map(|form| form + 1).filter(|&form| form < segments[i].forms.len());
+        chosen[i] = next;
+        if let Some(form) = next {
+            texts[i] = segments[i].forms[form].clone();
+        }
+    }
+
+    // Only required segments are left; ellipsize the widest to fit
+    let excess = total(&chosen, &texts).saturating_sub(width);
+    if excess > 0 {
+        if let Some(i) = (0..segments.len()).filter(|&i| chosen[i].is_some()).max_by_key(|&i| texts[i].width()) {
+            let keep = texts[i].width().saturating_sub(excess).max(1);
+            texts[i] = ellipsize_middle(&texts[i], keep);
+        }
+    }
+
+    let mut spans = Vec::new();
+    let mut used = 0;
+    for (i, segment) in segments.iter().enumerate() {
+        if chosen[i].is_none() {
+            continue;
+        }
+        if used > 0 {
+            let separator = truncate(SEPARATOR, width - used);
+            used += separator.width();
+            spans.push(Span::styled(separator, separator_style));
+        }
+        let text = truncate(&texts[i], width - used);
+        used += text.width();
+        spans.push(Span::styled(text, segment.style));
+    }
+    if used < width {
+        spans.push(Span::raw(" ".repeat(width - used)));
+    }
+    Line::from(spans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The segments of a logged-in session with a database and some usage
+    fn session() -> Vec<Segment> {
+        let style = Style::default();
+        vec![
+            Segment::user("ada.lovelace@analytical-engines.example.org", style, 80),
+            Segment::new("esc to exit", style, 10),
+            Segment::new("tab for commands", style, 20).or("tab"),
+            Segment::new("12.4k tokens", style, 40).or("12.4k"),
+            Segment::new("db ●", style, 70).or("●"),
+        ]
+    }
+
+    fn text(line: &Line) -> String {
+        line.spans.iter().map(|s| s.content.as_ref()).collect::<String>().trim_end().to_string()
+    }
+
+    #[test]
+    fn test_ellipsize_middle() {
+        assert_eq!(ellipsize_middle("short", 10), "short");
+        assert_eq!(ellipsize_middle("abcdefghij", 5), "ab…ij");
+        assert_eq!(ellipsize_middle("abcdefghij", 6), "abc…ij");
+        assert_eq!(ellipsize_middle("abcdefghij", 1), "…");
+        assert_eq!(ellipsize_middle("量子計算機", 5), "量…機");
+    }
+
+    #[test]
+    fn test_compose_at_common_widths() {
+        let style = Style::default();
+        let line = |width| text(&compose(&session(), style, width));
+        assert_eq!(
+            line(140),
+            "ada.lovelace@ana…nes.example.org · esc to exit · tab for commands · 12.4k tokens · db ●"
+        );
+        assert_eq!(line(100), line(140));
+        // The key hints give way first, then the token count
+        assert_eq!(line(80), "ada.lovelace@ana…nes.example.org · tab for commands · 12.4k tokens · db ●");
+        assert_eq!(line(60), "ada.lovelace@ana…nes.example.org · tab · 12.4k tokens · db ●");
+        assert_eq!(line(40), "ada.lovelace@ana…nes.example.org · db ●");
+        // The account shortens to its local part, then is ellipsized
+        assert_eq!(line(16), "ada.lovelace");
+        assert_eq!(line(8), "ada.…ace");
+    }
+
+    #[test]
+    fn test_compose_fills_the_width_exactly() {
+        let style = Style::default();
+        let mut segments = session();
+        segments.push(Segment::new("⚠ default JWT secret", style, 90).or("⚠ JWT"));
+        for width in 0..=160 {
+            let line = compose(&segments, style, width);
+            assert_eq!(line.width(), width, "at width {}", width);
+        }
+    }
+}
@@ -0,0 +1,413 @@
+// Tracking for tasks spawned by the TUI
+//
+// Background tasks (AI requests, logins, key tests) are aborted on quit - their
+// results would have nowhere to go. Critical tasks (logout) are awaited, up to a
+// timeout, so server-side state is settled before the process exits.
+//
+// Tasks the UI waits on are spawned with `spawn_tracked`, which gives them a
+// deadline. The watchdog reports a tracked task that panicked, overran its
+// deadline (it is aborted) or finished without delivering its result, so the
+// app can drop the matching loading state instead of waiting forever.
+
+use chrono::Local;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::future::Future;
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::task::{AbortHandle, Id, JoinSet};
+
+use crate::config::Config;
+
+/// Background work whose result the UI waits for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    AiRequest,
+    Auth,
+    AccountSwitch,
+    KeyTest,
+    Usage,
+    Backends,
+    BackendInfo,
+    Models,
+    Report,
+    Share,
+    Profile,
+}
+
+impl Operation {
+    /// Named in the error shown when the task goes wrong
+    pub fn label(self) -> &'static str {
+        match self {
+            Operation::AiRequest => "AI request",
+            Operation::Auth => "Sign-in",
+            Operation::AccountSwitch => "Account switch",
+            Operation::KeyTest => "Key check",
+            Operation::Usage => "Usage lookup",
+            Operation::Backends => "Backend list",
+            Operation::BackendInfo => "Backend details",
+            Operation::Models => "Model list",
+            Operation::Report => "Job report",
+            Operation::Share => "Share",
+            Operation::Profile => "Profile update",
+        }
+    }
+
+    /// Longest the task may run. Generous: the clients time out on their own,
+    /// and an AI request retries a timed out attempt a few times.
+    pub fn deadline(self) -> Duration {
+        match self {
+            Operation::AiRequest => Duration::from_secs(10 * 60),
+            _ => Duration::from_secs(2 * 60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cause {
+    /// The panic message
+    Panicked(String),
+    /// Ran past its deadline and was aborted
+    Overdue(Duration),
+    /// Finished, but its result never arrived
+    Dropped,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alarm {
+    pub operation: Operation,
+    pub cause: Cause,
+}
+
+impl Alarm {
+    pub fn message(&self) -> String {
+        let label = self.operation.label();
+        match &self.cause {
+            Cause::Panicked(message) => format!(
+                "{} failed unexpectedly ({}). Details are in ~/.qhub/logs/tasks.log. Please try again.",
+                label, message
+            ),
+            Cause::Overdue(deadline) => format!(
+                "{} did not finish within {}s and was cancelled. Please try again.",
+                label,
+                deadline.as_secs()
+            ),
+            Cause::Dropped => format!("{} ended without a result. Please try again.", label),
+        }
+    }
+}
+
+struct Watched {
+    operation: Operation,
+    deadline: Instant,
+    abort: AbortHandle,
+    finished: bool,
+}
+
+tokio::task_local! {
+    static TRACKED: Operation;
+}
+
+/// Backtraces of tracked tasks that panicked, kept by the panic hook until
+/// the watchdog collects them
+static PANICS: Mutex<Vec<(Id, String)>> = Mutex::new(Vec::new());
+
+/// Called from the panic hook. A panic inside a tracked task is recorded for
+/// the watchdog and true is returned: the TUI keeps running, so nothing may
+/// be printed over it.
+pub fn record_panic(info: &std::panic::PanicHookInfo) -> bool {
+    let (Ok(_), Some(id)) = (TRACKED.try_with(|_| ()), tokio::task::try_id()) else {
+        return false;
+    };
+    let report = format!("{}\n{}", info, std::backtrace::Backtrace::capture());
+    if let Ok(mut panics) = PANICS.lock() {
+        panics.push((id, report));
+    }
+    true
+}
+
+/// Append to ~/.qhub/logs/tasks.log. Failures are ignored - the alarm is
+/// still shown in the chat.
+fn log(operation: Operation, report: &str) {
+    let Ok(dir) = Config::logs_dir() else { return };
+    if fs::create_dir_all(&dir).is_err() {
+        return;
+    }
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(dir.join("tasks.log")) {
+        let _ = writeln!(file, "{} {} panicked: {}", Local::now().format("%Y-%m-%d %H:%M:%S"), operation.label(), report);
+    }
+}
+
+#[derive(Default)]
+pub struct TaskTracker {
+    background: JoinSet<()>,
+    critical: JoinSet<()>,
+    watched: HashMap<Id, Watched>,
+}
+
+impl TaskTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Spawn a task that may be cancelled on shutdown
+    pub fn spawn<F>(&mut self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.background.spawn(future);
+    }
+
+    /// Spawn a task the UI waits on, watched until `Operation::deadline`
+    pub fn spawn_tracked<F>(&mut self, operation: Operation, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let abort = self.background.spawn(TRACKED.scope(operation, future));
+        let deadline = Instant::now() + operation.deadline();
+        self.watched.insert(abort.id(), Watched { operation, deadline, abort, finished: false });
+    }
+
+    /// Abort the running tasks for `operation`; false when there were none.
+    /// They are no longer watched, so no alarm is raised for them.
+    pub fn abort(&mut self, operation: Operation) -> bool {
+        let before = self.watched.len();
+        self.watched.retain(|_, watched| {
+            let running = watched.operation == operation && !watched.finished;
+            if running {
+                watched.abort.abort();
+            }
+            !running
+        });
+        self.watched.len() < before
+    }
+
+    /// Spawn a task that shutdown waits for
+    pub fn spawn_critical<F>(&mut self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.critical.spawn(future);
+    }
+
+    /// Drop handles of finished tasks and report tracked ones that went
+    /// wrong. `waiting` lists the operations whose result the UI still waits
+    /// for; a finished task whose operation is among them never delivered.
+    pub fn watchdog(&mut self, now: Instant, waiting: &[Operation]) -> Vec<Alarm> {
+        let mut alarms = Vec::new();
+        while let Some(joined) = self.background.try_join_next_with_id() {
+            match joined {
+                Ok((id, ())) => {
+                    if let Some(watched) = self.watched.get_mut(&id) {
+                        watched.finished = true;
+                    }
+                }
+                Err(error) => {
+                    let Some(watched) = self.watched.remove(&error.id()) else {
+                        continue;
+                    };
+                    if error.is_panic() {
+                        let id = error.id();
+                        let report = PANICS
+                            .lock()
+                            .ok()
+                            .and_then(|mut panics| {
+                                let at = panics.iter().position(|(panicked, _)| *panicked == id)?;
+                                Some(panics.remove(at).1)
+                            })
+                            .unwrap_or_else(|| "no backtrace captured".to_string());
+                        log(watched.operation, &report);
+                        alarms.push(Alarm { operation: watched.operation, cause: Cause::Panicked(panic_message(error)) });
+                    }
+                }
+            }
+        }
+        while self.critical.try_join_next().is_some() {}
+
+        self.watched.retain(|_, watched| {
+            if watched.finished {
+                if waiting.contains(&watched.operation) {
+                    alarms.push(Alarm { operation: watched.operation, cause: Cause::Dropped });
+                }
+                return false;
+            }
+            if now >= watched.deadline {
+                watched.abort.abort();
+                alarms.push(Alarm { operation: watched.operation, cause: Cause::Overdue(watched.operation.deadline()) });
+                return false;
+            }
+            true
+        });
+        // A newer task for the same operation now owns the loading state
+        alarms.retain(|alarm| {
+            matches!(alarm.cause, Cause::Overdue(_))
+                || !self.watched.values().any(|watched| watched.operation == alarm.operation)
+        });
+        alarms
+    }
+
+    /// Number of critical tasks still running
+    pub fn pending_critical(&self) -> usize {
+        self.critical.len()
+    }
+
+    /// Wait for critical tasks (up to `timeout`) and abort everything else.
+    /// Returns false if a critical task had to be abandoned.
+    pub async fn shutdown(&mut self, timeout: Duration) -> bool {
+        self.background.abort_all();
+        self.watched.clear();
+
+        let critical = &mut self.critical;
+        let finished = tokio::time::timeout(timeout, async {
+            while critical.join_next().await.is_some() {}
+        })
+        .await
+        .is_ok();
+
+        if !finished {
+            self.critical.abort_all();
+        }
+        while self.background.join_next().await.is_some() {}
+        while self.critical.join_next().await.is_some() {}
+
+        finished
+    }
+}
+
+fn panic_message(error: tokio::task::JoinError) -> String {
+    let payload = error.into_panic();
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "panic".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_shutdown_waits_for_critical_and_aborts_background() {
+        let mut tasks = TaskTracker::new();
+        let critical_done = Arc::new(AtomicBool::new(false));
+        let background_done = Arc::new(AtomicBool::new(false));
+
+        let flag = critical_done.clone();
+        tasks.spawn_critical(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            flag.store(true, Ordering::SeqCst);
+        });
+        let flag = background_done.clone();
+        tasks.spawn(async move {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            flag.store(true, Ordering::SeqCst);
+        });
+
+        assert!(tasks.shutdown(Duration::from_secs(5)).await);
+        assert!(critical_done.load(Ordering::SeqCst));
+        assert!(!background_done.load(Ordering::SeqCst));
+    }
+
+    /// Let spawned tasks run to completion
+    async fn settle() {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    #[tokio::test]
+    async fn test_watchdog_reports_dropped_sender_within_one_pass() {
+        let mut tasks = TaskTracker::new();
+        let (tx, mut rx) = tokio::sync::mpsc::channel::<String>(1);
+        tasks.spawn_tracked(Operation::AiRequest, async move {
+            drop(tx);
+        });
+        settle().await;
+
+        let waiting = if rx.is_empty() { vec![Operation::AiRequest] } else { vec![] };
+        let alarms = tasks.watchdog(Instant::now(), &waiting);
+        assert_eq!(alarms, vec![Alarm { operation: Operation::AiRequest, cause: Cause::Dropped }]);
+        assert!(alarms[0].message().starts_with("AI request ended without a result"));
+        assert!(rx.try_recv().is_err());
+        assert!(tasks.watchdog(Instant::now(), &waiting).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_watchdog_ignores_delivered_results_and_newer_requests() {
+        let mut tasks = TaskTracker::new();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        tasks.spawn_tracked(Operation::Share, async move {
+            let _ = tx.send("https://qhub.dev/s/abc").await;
+        });
+        settle().await;
+        // The result is waiting in the channel, so the UI is not stuck
+        assert!(!rx.is_empty());
+        assert!(tasks.watchdog(Instant::now(), &[]).is_empty());
+
+        // An old request finished; a new one for the same operation is running
+        tasks.spawn_tracked(Operation::Report, async {});
+        settle().await;
+        tasks.spawn_tracked(Operation::Report, async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        });
+        assert!(tasks.watchdog(Instant::now(), &[Operation::Report]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_watchdog_reports_panics_and_aborts_overdue_tasks() {
+        let mut tasks = TaskTracker::new();
+        tasks.spawn_tracked(Operation::Auth, async {
+            panic!("token was not JSON");
+        });
+        let flag = Arc::new(AtomicBool::new(false));
+        let done = flag.clone();
+        tasks.spawn_tracked(Operation::Backends, async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            done.store(true, Ordering::SeqCst);
+        });
+        settle().await;
+
+        let alarms = tasks.watchdog(Instant::now() + Operation::Backends.deadline(), &[]);
+        assert_eq!(alarms.len(), 2);
+        assert!(alarms.contains(&Alarm { operation: Operation::Auth, cause: Cause::Panicked("token was not JSON".into()) }));
+        assert!(alarms.contains(&Alarm { operation: Operation::Backends, cause: Cause::Overdue(Duration::from_secs(120)) }));
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(!flag.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_abort_stops_the_operation_quietly() {
+        let mut tasks = TaskTracker::new();
+        let flag = Arc::new(AtomicBool::new(false));
+        let done = flag.clone();
+        tasks.spawn_tracked(Operation::AiRequest, async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            done.store(true, Ordering::SeqCst);
+        });
+        tasks.spawn_tracked(Operation::Usage, async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        });
+
+        assert!(tasks.abort(Operation::AiRequest));
+        assert!(!tasks.abort(Operation::AiRequest));
+        settle().await;
+        assert!(tasks.watchdog(Instant::now(), &[Operation::AiRequest]).is_empty());
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(!flag.load(Ordering::SeqCst));
+        assert!(tasks.abort(Operation::Usage));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_gives_up_on_slow_critical_task() {
+        let mut tasks = TaskTracker::new();
+        tasks.spawn_critical(async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        });
+        assert_eq!(tasks.pending_critical(), 1);
+        assert!(!tasks.shutdown(Duration::from_millis(20)).await);
+        assert_eq!(tasks.pending_critical(), 0);
+    }
+}
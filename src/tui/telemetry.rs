@@ -0,0 +1,231 @@
+// Opt-in anonymous usage metrics (/telemetry on)
+//
+// Off by default. While enabled, only counters are kept: which slash commands
+// ran, which category an AI error fell into and which latency bucket a reply
+// landed in. Every key is a fixed string chosen here, never user text, and no
+// account, email or machine identifier is attached. Counts accumulate in
+// ~/.qhub/cache/telemetry.json and are uploaded in batches; nothing touches the
+// network until the user turns telemetry on.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::config::Config;
+
+/// Events collected before a batch is uploaded
+pub const BATCH_EVENTS: u64 = 50;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Metrics {
+    #[serde(default)]
+    pub commands: BTreeMap<String, u64>,
+    #[serde(default)]
+    pub errors: BTreeMap<String, u64>,
+    #[serde(default)]
+    pub latency: BTreeMap<String, u64>,
+}
+
+impl Metrics {
+    pub fn total(&self) -> u64 {
+        [&self.commands, &self.errors, &self.latency]
+            .iter()
+            .flat_map(|counts| counts.values())
+            .sum()
+    }
+
+    fn merge(&mut self, other: Metrics) {
+        for (mine, theirs) in [
+            (&mut self.commands, other.commands),
+            (&mut self.errors, other.errors),
+            (&mut self.latency, other.latency),
+        ] {
+            for (key, count) in theirs {
+                *mine.entry(key).or_default() += count;
+            }
+        }
+    }
+}
+
+/// What is sent to `/telemetry`: the counters and the client version
+#[derive(Debug, Serialize)]
+pub struct Batch {
+    pub version: &'static str,
+    #[serde(flatten)]
+    pub metrics: Metrics,
+}
+
+pub fn telemetry_path() -> Result<PathBuf> {
+    Ok(Config::cache_dir()?.join("telemetry.json"))
+}
+
+pub fn latency_bucket(latency: Duration) -> &'static str {
+    match latency.as_secs() {
+        0 => "<1s",
+        1..=2 => "1-3s",
+        3..=9 => "3-10s",
+        10..=29 => "10-30s",
+        _ => "30s+",
+    }
+}
+
+pub struct Telemetry {
+    enabled: bool,
+    /// `None` keeps counts in memory only
+    path: Option<PathBuf>,
+    metrics: Metrics,
+}
+
+impl Telemetry {
+    /// Pick up counts left by earlier sessions
+    pub fn load(enabled: bool, path: Option<PathBuf>) -> Self {
+        let metrics = path
+            .as_ref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        Self { enabled, path, metrics }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Counts collected locally and not yet uploaded
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    pub fn command(&mut self, name: &'static str) {
+        self.bump(|m| &mut m.commands, name);
+    }
+
+    pub fn error(&mut self, category: &'static str) {
+        self.bump(|m| &mut m.errors, category);
+    }
+
+    pub fn latency(&mut self, latency: Duration) {
+        self.bump(|m| &mut m.latency, latency_bucket(latency));
+    }
+
+    fn bump(&mut self, counts: fn(&mut Metrics) -> &mut BTreeMap<String, u64>, key: &'static str) {
+        if !self.enabled {
+            return;
+        }
+        *counts(&mut self.metrics).entry(key.to_string()).or_default() += 1;
+        // Best effort: losing a counter is not worth interrupting the user
+        let _ = self.save();
+    }
+
+    /// Hand out the collected counts for upload once a full batch is ready,
+    /// or any pending counts when `flush` is set
+    pub fn take_batch(&mut self, flush: bool) -> Option<Batch> {
+        let total = self.metrics.total();
+        if !self.enabled || total == 0 || (!flush && total < BATCH_EVENTS) {
+            return None;
+        }
+        let metrics = std::mem::take(&mut self.metrics);
+        let _ = self.save();
+        Some(Batch {
+            version: env!("CARGO_PKG_VERSION"),
+            metrics,
+        })
+    }
+
+    /// Put back a batch whose upload failed
+    pub fn restore(&mut self, batch: Batch) {
+        self.metrics.merge(batch.metrics);
+        let _ = self.save();
+    }
+
+    /// Delete everything collected locally
+    pub fn purge(&mut self) -> Result<()> {
+        self.metrics = Metrics::default();
+        match &self.path {
+            Some(path) if path.exists() => {
+                fs::remove_file(path).with_context(|| format!("Failed to remove {}", path.display()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).context("Failed to create cache directory")?;
+        }
+        fs::write(path, serde_json::to_string(&self.metrics)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path() -> PathBuf {
+        std::env::temp_dir().join(format!("qhub-telemetry-{}.json", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn test_nothing_is_recorded_until_enabled() {
+        let path = temp_path();
+        let mut telemetry = Telemetry::load(false, Some(path.clone()));
+        telemetry.command("help");
+        telemetry.latency(Duration::from_millis(1500));
+        assert_eq!(telemetry.metrics().total(), 0);
+        assert!(!path.exists());
+        assert!(telemetry.take_batch(true).is_none());
+    }
+
+    #[test]
+    fn test_batches_persist_and_restore() {
+        let path = temp_path();
+        let mut telemetry = Telemetry::load(true, Some(path.clone()));
+        telemetry.command("help");
+        telemetry.error("timeout");
+        telemetry.latency(Duration::from_secs(12));
+
+        // Counts survive a restart
+        let mut telemetry = Telemetry::load(true, Some(path.clone()));
+        assert_eq!(telemetry.metrics().latency.get("10-30s"), Some(&1));
+        assert!(telemetry.take_batch(false).is_none());
+
+        let batch = telemetry.take_batch(true).unwrap();
+        assert_eq!(batch.metrics.total(), 3);
+        assert_eq!(telemetry.metrics().total(), 0);
+
+        telemetry.command("help");
+        telemetry.restore(batch);
+        assert_eq!(telemetry.metrics().commands.get("help"), Some(&2));
+
+        telemetry.purge().unwrap();
+        assert_eq!(telemetry.metrics().total(), 0);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_batch_payload_has_no_free_text() {
+        let mut metrics = Metrics::default();
+        metrics.commands.insert("copy".to_string(), 2);
+        let json = serde_json::to_value(Batch { version: "1.0.0", metrics }).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "version": "1.0.0",
+                "commands": {"copy": 2},
+                "errors": {},
+                "latency": {},
+            })
+        );
+    }
+}
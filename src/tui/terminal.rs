@@ -0,0 +1,281 @@
+// Terminal setup and teardown
+//
+// `restore` is the single teardown path: the panic hook, normal exit, SIGTERM
+// and SIGHUP, and every suspension (an external editor, Ctrl+Z) go through it,
+// so no exit route can leave the terminal in raw mode or on the alternate
+// screen.
+
+use crossterm::{
+    cursor::Show,
+    event::{DisableFocusChange, DisableMouseCapture, EnableFocusChange, EnableMouseCapture},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::time::Duration;
+
+/// Set by Ctrl+Z or an external SIGTSTP; the main loop suspends when it sees it
+static SUSPEND_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// The SIGTERM or SIGHUP received, 0 while none has been; the main loop
+/// shuts down normally when it sees one
+static TERMINATE_SIGNAL: AtomicI32 = AtomicI32::new(0);
+
+/// How long a normal shutdown may take after SIGTERM/SIGHUP before the
+/// terminal is restored and the process exits regardless. Longer than the
+/// app's own shutdown timeout, so a pending logout can still finish.
+const FORCE_EXIT_AFTER: Duration = Duration::from_secs(10);
+
+/// Restore the terminal before the default panic message is printed. A
+/// panicking tracked task is left to the watchdog and the TUI keeps running.
+pub fn install_panic_hook() {
+    let original_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |panic_info| {
+        if super::tasks::record_panic(panic_info) {
+            return;
+        }
+        let _ = restore();
+        original_hook(panic_info);
+    }));
+}
+
+/// Why the TUI can't run here: it needs a real terminal on stdin and stdout
+/// that understands cursor movement and the alternate screen
+pub fn unsupported_reason(stdin_tty: bool, stdout_tty: bool, term: Option<&str>) -> Option<&'static str> {
+    if !stdout_tty {
+        Some("stdout is not a terminal")
+    } else if !stdin_tty {
+        Some("stdin is not a terminal")
+    } else if matches!(term, Some("dumb")) {
+        Some("TERM=dumb does not support full-screen programs")
+    } else {
+        None
+    }
+}
+
+/// `unsupported_reason` for the current process
+pub fn check_support() -> Option<&'static str> {
+    let term = std::env::var("TERM").ok();
+    unsupported_reason(io::stdin().is_terminal(), io::stdout().is_terminal(), term.as_deref())
+}
+
+/// Raw mode, alternate screen, focus reports and (unless disabled) mouse
+/// capture for the TUI
+pub fn enter(mouse: bool) -> io::Result<()> {
+    enable_raw_mode()?;
+    // Focus reports let ui.notify_on_complete stay quiet while QHub is in front
+    execute!(io::stdout(), EnterAlternateScreen, EnableFocusChange)?;
+    if mouse {
+        execute!(io::stdout(), EnableMouseCapture)?;
+    }
+    Ok(())
+}
+
+/// Back to a normal shell terminal. Safe to call more than once.
+pub fn restore() -> io::Result<()> {
+    disable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, DisableMouseCapture, DisableFocusChange, LeaveAlternateScreen, Show)?;
+    // Explicit ANSI reset to prevent escape code leakage
+    write!(stdout, "\x1b[0m")?;
+    stdout.flush()
+}
+
+pub fn request_suspend() {
+    SUSPEND_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+pub fn take_suspend_request() -> bool {
+    SUSPEND_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+/// Turn SIGTSTP (`kill -TSTP`, or Ctrl+Z while the terminal is not raw) into
+/// a suspend request, so the terminal is restored before the process stops
+#[cfg(unix)]
+pub fn watch_sigtstp() -> io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut stream = signal(SignalKind::from_raw(libc::SIGTSTP))?;
+    tokio::spawn(async move {
+        while stream.recv().await.is_some() {
+            request_suspend();
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn watch_sigtstp() -> io::Result<()> {
+    Ok(())
+}
+
+/// The termination signal received, if any
+pub fn termination_signal() -> Option<i32> {
+    match TERMINATE_SIGNAL.load(Ordering::SeqCst) {
+        0 => None,
+        signal => Some(signal),
+    }
+}
+
+/// Shell convention for a process ended by `signal`
+pub fn signal_exit_code(signal: i32) -> i32 {
+    128 + signal
+}
+
+/// Turn SIGTERM (e.g. `kill`, or a multiplexer closing the pane) and SIGHUP
+/// (the terminal going away) into a shutdown request. Should the main loop
+/// be stuck, the terminal is restored and the process exits anyway.
+#[cfg(unix)]
+pub fn watch_termination() -> io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut term = signal(SignalKind::terminate())?;
+    let mut hangup = signal(SignalKind::hangup())?;
+    tokio::spawn(async move {
+        let received = tokio::select! {
+            _ = term.recv() => libc::SIGTERM,
+            _ = hangup.recv() => libc::SIGHUP,
+        };
+        TERMINATE_SIGNAL.store(received, Ordering::SeqCst);
+        tokio::time::sleep(FORCE_EXIT_AFTER).await;
+        let _ = restore();
+        std::process::exit(signal_exit_code(received));
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn watch_termination() -> io::Result<()> {
+    Ok(())
+}
+
+/// Terminal mode switches, so `Session` can be tested without a terminal
+pub trait TerminalModes {
+    fn enter(&mut self) -> io::Result<()>;
+    fn restore(&mut self) -> io::Result<()>;
+}
+
+/// The real terminal
+pub struct Crossterm {
+    /// `ui.mouse_capture`; off keeps the terminal's own selection and scrollback
+    pub mouse: bool,
+}
+
+impl TerminalModes for Crossterm {
+    fn enter(&mut self) -> io::Result<()> {
+        enter(self.mouse)
+    }
+
+    fn restore(&mut self) -> io::Result<()> {
+        restore()
+    }
+}
+
+/// Tracks whether the TUI owns the terminal. Suspending and resuming are
+/// idempotent, so nested or repeated requests never toggle modes twice.
+pub struct Session<M: TerminalModes = Crossterm> {
+    modes: M,
+    active: bool,
+}
+
+impl Session<Crossterm> {
+    /// Take over the terminal
+    pub fn start(mouse: bool) -> io::Result<Self> {
+        let mut session = Self { modes: Crossterm { mouse }, active: false };
+        session.resume()?;
+        Ok(session)
+    }
+}
+
+impl<M: TerminalModes> Session<M> {
+    /// Give the terminal back to the shell (or an editor)
+    pub fn suspend(&mut self) -> io::Result<()> {
+        if self.active {
+            self.modes.restore()?;
+            self.active = false;
+        }
+        Ok(())
+    }
+
+    /// Take the terminal back. Returns true if the screen must be redrawn
+    /// from scratch.
+    pub fn resume(&mut self) -> io::Result<bool> {
+        if self.active {
+            return Ok(false);
+        }
+        self.modes.enter()?;
+        self.active = true;
+        Ok(true)
+    }
+
+    /// Ctrl+Z: restore the terminal, stop until the shell continues us, then
+    /// take the terminal back. Returns true if the screen must be redrawn.
+    pub fn background(&mut self) -> io::Result<bool> {
+        self.suspend()?;
+        stop_process();
+        self.resume()
+    }
+}
+
+#[cfg(unix)]
+fn stop_process() {
+    // Execution continues here on SIGCONT (`fg`)
+    unsafe {
+        libc::raise(libc::SIGSTOP);
+    }
+}
+
+#[cfg(not(unix))]
+fn stop_process() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder(Vec<&'static str>);
+
+    impl TerminalModes for Recorder {
+        fn enter(&mut self) -> io::Result<()> {
+            self.0.push("enter");
+            Ok(())
+        }
+
+        fn restore(&mut self) -> io::Result<()> {
+            self.0.push("restore");
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_suspend_and_resume_are_idempotent() {
+        let mut session = Session { modes: Recorder::default(), active: false };
+        assert!(session.resume().unwrap());
+        assert!(!session.resume().unwrap());
+        assert!(session.active);
+
+        session.suspend().unwrap();
+        session.suspend().unwrap();
+        assert!(!session.active);
+
+        assert!(session.resume().unwrap());
+        assert_eq!(session.modes.0, vec!["enter", "restore", "enter"]);
+    }
+
+    #[test]
+    fn test_unsupported_reason() {
+        assert_eq!(unsupported_reason(true, true, Some("xterm-256color")), None);
+        assert_eq!(unsupported_reason(true, true, None), None);
+        assert!(unsupported_reason(true, false, Some("xterm")).unwrap().contains("stdout"));
+        assert!(unsupported_reason(false, true, Some("xterm")).unwrap().contains("stdin"));
+        assert!(unsupported_reason(true, true, Some("dumb")).is_some());
+    }
+
+    #[test]
+    fn test_suspend_request_is_taken_once() {
+        request_suspend();
+        assert!(take_suspend_request());
+        assert!(!take_suspend_request());
+    }
+}
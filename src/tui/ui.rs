@@ -1,12 +1,27 @@
 use ratatui::{
-    layout::{Constraint, Direction, Layout, Rect},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Paragraph, Wrap, Scrollbar, ScrollbarOrientation, ScrollbarState},
+    widgets::{Block, Borders, Clear, Paragraph, Wrap, Scrollbar, ScrollbarOrientation, ScrollbarState, Sparkline},
     Frame,
 };
 
-use super::app::{App, MessageRole};
+use super::accessible;
+use super::ai_queue::Held;
+use super::app::{AiProgress, App, Message, MessageRole};
+use super::hit_test::{HitMap, RowTarget};
+use super::format;
+use super::clipboard;
+use super::input_history::Match;
+use super::json_view;
+use super::language;
+use super::markdown;
+use super::palette::{self, ItemKind};
+use super::quota::Level as QuotaLevel;
+use super::render_cache::{self, Row as CachedRow, RowKind};
+use super::status_line::{self, Segment};
+use crate::config::settings::{ContentAlign, Density};
+use crate::db::manager::DbHealth;
 
 // Minimal color palette - muted and clean
 const MUTED_WHITE: Color = Color::Rgb(200, 200, 200);
@@ -15,6 +30,8 @@ const SOFT_BLUE: Color = Color::Rgb(130, 160, 200);
 const SOFT_GREEN: Color = Color::Rgb(120, 180, 120);
 const SOFT_RED: Color = Color::Rgb(200, 100, 100);
 const CYAN: Color = Color::Rgb(0, 205, 205);  // Smooth cyan
+const SELECTED_BG: Color = Color::Rgb(0, 120, 120);
+const AMBER: Color = Color::Rgb(220, 170, 80);  // Usage hints, distinct from suggestions
 
 pub fn render(frame: &mut Frame, app: &mut App) {
     // Calculate suggestion height dynamically
@@ -24,10 +41,13 @@ pub fn render(frame: &mut Frame, app: &mut App) {
         0
     };
     
+    let banner_height = if app.quota_banner().is_some() { 1 } else { 0 };
+
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
             Constraint::Length(1),              // Header - minimal
+            Constraint::Length(banner_height),  // Quota banner
             Constraint::Min(10),                // Messages
             Constraint::Length(3),              // Input
             Constraint::Length(suggestion_height), // Suggestions (dynamic)
@@ -35,16 +55,31 @@ pub fn render(frame: &mut Frame, app: &mut App) {
         ])
         .split(frame.area());
 
+    // Clickable regions are recorded as they are drawn
+    app.hits = HitMap::default();
+    app.hits.input = chunks[3];
+
     render_header(frame, chunks[0]);
-    render_messages(frame, app, chunks[1]);
-    render_input(frame, app, chunks[2]);
+    render_quota_banner(frame, app, chunks[1]);
+    render_messages(frame, app, chunks[2]);
+    if app.block_picker.is_some() {
+        render_block_labels(frame, app);
+    }
+    render_input(frame, app, chunks[3]);
     
     // Render suggestions if showing
     if app.show_suggestions {
-        render_suggestions(frame, app, chunks[3]);
+        render_suggestions(frame, app, chunks[4]);
     }
     
-    render_status_bar(frame, app, chunks[4]);
+    render_status_bar(frame, app, chunks[5]);
+
+    if app.show_stats {
+        render_stats(frame, app);
+    }
+    if app.palette.is_some() {
+        render_palette(frame, app);
+    }
 }
 
 fn render_header(frame: &mut Frame, area: Rect) {
@@ -55,93 +90,141 @@ fn render_header(frame: &mut Frame, area: Rect) {
     frame.render_widget(header, area);
 }
 
+fn render_quota_banner(frame: &mut Frame, app: &App, area: Rect) {
+    let Some(banner) = app.quota_banner() else {
+        return;
+    };
+    let color = match banner.level {
+        QuotaLevel::Warning => AMBER,
+        QuotaLevel::Critical => SOFT_RED,
+    };
+    let text = status_line::truncate(&format!(" ⚠ {}", banner.text), area.width as usize);
+    frame.render_widget(
+        Paragraph::new(Span::styled(text, Style::default().fg(Color::Black).bg(color).add_modifier(Modifier::BOLD)))
+            .style(Style::default().bg(color)),
+        area,
+    );
+}
+
 fn render_messages(frame: &mut Frame, app: &mut App, area: Rect) {
+    if app.locked {
+        render_lock_screen(frame, app, area);
+        return;
+    }
+    if app.messages.is_empty() && app.welcome.is_none() && !app.is_loading {
+        render_empty_state(frame, app, area);
+        return;
+    }
+
     let inner_height = area.height.saturating_sub(2) as usize;
-    
-    let mut all_lines: Vec<Line> = Vec::new();
-    
+    let compact = app.config.ui.density == Density::Compact;
+    let column = content_column(area, app.config.ui.max_content_width, app.config.ui.content_align);
+
+    // Rows are wrapped to the column, which leaves the scrollbar free, so the
+    // row counts below are what is actually drawn
+    app.render_cache.set_width(column.width);
+    app.render_cache.retain(&app.messages, app.welcome.as_ref());
+    let plain = app.accessible;
+    let pretty_json = app.config.ui.pretty_json;
+    let lay_out = move |m: &Message, width: u16| {
+        if plain {
+            accessible::layout_message(m, width)
+        } else {
+            layout_message(m, width, compact, pretty_json)
+        }
+    };
+    // The welcome comes first but is not one of the messages
+    let welcome_rows = app.welcome.as_ref().map_or(0, |w| app.render_cache.rows(w, compact, lay_out).len());
+    let mut total_rows = welcome_rows;
+    let mut first_rows = Vec::with_capacity(app.messages.len());
     for message in &app.messages {
-        let (prefix, prefix_style) = match message.role {
-            MessageRole::User => ("> ", Style::default().fg(SOFT_GREEN)),
-            MessageRole::Assistant => ("  ", Style::default().fg(SOFT_BLUE)),
-            MessageRole::System => ("  ", Style::default().fg(DIM_GRAY)),
-            MessageRole::Error => ("! ", Style::default().fg(SOFT_RED)),
-        };
+        first_rows.push(total_rows);
+        total_rows += app.render_cache.rows(message, compact, lay_out).len();
+    }
+    if app.is_loading {
+        total_rows += 1;
+    }
+    app.scroll.set_layout(total_rows, inner_height);
 
-        let content_style = match message.role {
-            MessageRole::User => Style::default().fg(MUTED_WHITE),
-            MessageRole::Assistant => Style::default().fg(MUTED_WHITE),
-            MessageRole::System => Style::default().fg(DIM_GRAY),
-            MessageRole::Error => Style::default().fg(SOFT_RED),
-        };
+    let offset = app.scroll.offset();
+    let end = offset + inner_height;
+    let mut visible_lines: Vec<Line> = Vec::with_capacity(inner_height);
+    // (row, message, row width) of each prompt's and reply's first row
+    let mut header_lines: Vec<(usize, usize, usize)> = Vec::new();
+    let first_row = area.y + 1;
+    // Messages that end above the viewport are skipped without touching their rows
+    let first_visible = first_rows.partition_point(|&row| row <= offset).saturating_sub(1);
 
-        let mut in_code_block = false;
-        
-        for (i, line) in message.content.lines().enumerate() {
-            if line.starts_with("```") {
-                in_code_block = !in_code_block;
-                if in_code_block {
-                    all_lines.push(Line::from(Span::styled("", Style::default())));
-                }
+    if let Some(welcome) = &app.welcome {
+        let rows = app.render_cache.get(&welcome.id);
+        visible_lines.extend(rows.iter().skip(offset).take(end.saturating_sub(offset)).map(|row| row.line.clone()));
+    }
+    for (index, message) in app.messages.iter().enumerate().skip(first_visible) {
+        let start = first_rows[index];
+        if start >= end {
+            break;
+        }
+        let rows = app.render_cache.get(&message.id);
+        let header = matches!(message.role, MessageRole::User | MessageRole::Assistant);
+        if header && start >= offset && !rows.is_empty() {
+            header_lines.push((start, index, rows[0].line.width()));
+        }
+        for (i, row) in rows.iter().enumerate() {
+            let at = start + i;
+            if at < offset {
                 continue;
             }
-            
-            if in_code_block {
-                all_lines.push(Line::from(vec![
-                    Span::styled("  ", Style::default()),
-                    Span::styled(line.to_string(), Style::default().fg(SOFT_BLUE)),
-                ]));
-            } else {
-                let line_prefix = if i == 0 { prefix } else { "  " };
-                all_lines.push(Line::from(vec![
-                    Span::styled(line_prefix, prefix_style),
-                    Span::styled(line.to_string(), content_style),
-                ]));
+            if at >= end {
+                break;
+            }
+            let mut line = row.line.clone();
+            // Highlight the prefix of the clicked message
+            if app.selected_message == Some(index) && line.spans.len() > 1 {
+                line.spans[0].style = line.spans[0].style.bg(SELECTED_BG);
             }
+            visible_lines.push(line);
+            let code_block = match row.kind {
+                RowKind::Text => None,
+                RowKind::Code(block) => Some(block),
+                RowKind::Spacer => continue,
+            };
+            let rect = Rect::new(column.x, first_row + (at - offset) as u16, column.width, 1);
+            app.hits.rows.push((rect, RowTarget { message: index, code_block }));
         }
-        
-        all_lines.push(Line::from(""));
     }
-    
+
     // Show loading indicator
-    if app.is_loading {
-        all_lines.push(Line::from(vec![
+    if app.is_loading && total_rows - 1 < end && app.accessible {
+        let label = accessible::progress_label(app.ai_progress, app.queued_request());
+        visible_lines.push(Line::from(label));
+    } else if app.is_loading && total_rows - 1 < end {
+        visible_lines.push(Line::from(vec![
             Span::styled("  ", Style::default()),
             Span::styled("● ", Style::default().fg(CYAN).add_modifier(Modifier::SLOW_BLINK)),
-            Span::styled("thinking...", Style::default().fg(DIM_GRAY)),
+            Span::styled(loading_label(app.ai_progress, app.queued_request()), Style::default().fg(DIM_GRAY)),
         ]));
     }
 
-    let total_lines = all_lines.len();
-    let max_scroll = total_lines.saturating_sub(inner_height);
-    
-    if app.scroll_offset > max_scroll {
-        app.scroll_offset = max_scroll;
+    frame.render_widget(
+        Block::default()
+            .borders(borders(app, Borders::TOP))
+            .border_style(Style::default().fg(DIM_GRAY)),
+        area,
+    );
+    let text_area = Rect::new(column.x, area.y + 1, column.width, area.height.saturating_sub(1));
+    frame.render_widget(Paragraph::new(visible_lines), text_area);
+    // Times and the copy button are drawn over the text, which reads badly
+    if !app.accessible {
+        render_headers(frame, app, column, &header_lines);
     }
 
-    let visible_lines: Vec<Line> = all_lines
-        .into_iter()
-        .skip(app.scroll_offset)
-        .take(inner_height)
-        .collect();
-
-    let messages_widget = Paragraph::new(visible_lines)
-        .block(
-            Block::default()
-                .borders(Borders::TOP)
-                .border_style(Style::default().fg(DIM_GRAY))
-        )
-        .wrap(Wrap { trim: false });
-
-    frame.render_widget(messages_widget, area);
-
-    if total_lines > inner_height {
+    if total_rows > inner_height && !app.accessible {
         let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
             .begin_symbol(None)
             .end_symbol(None);
         
-        let mut scrollbar_state = ScrollbarState::new(max_scroll)
-            .position(app.scroll_offset);
+        let mut scrollbar_state = ScrollbarState::new(app.scroll.max_offset())
+            .position(app.scroll.offset());
         
         frame.render_stateful_widget(
             scrollbar,
@@ -149,15 +232,415 @@ fn render_messages(frame: &mut Frame, app: &mut App, area: Rect) {
             &mut scrollbar_state,
         );
     }
+
+    if app.scroll.has_unseen() {
+        render_new_messages_marker(frame, app, area);
+    }
+}
+
+/// `wanted`, or none in accessible mode, where borders are read out as noise
+fn borders(app: &App, wanted: Borders) -> Borders {
+    if app.accessible {
+        Borders::NONE
+    } else {
+        wanted
+    }
+}
+
+/// The part of the chat pane text is wrapped in: all of it but the scrollbar
+/// column, or `max_width` columns of it placed by `align`
+fn content_column(area: Rect, max_width: Option<u16>, align: ContentAlign) -> Rect {
+    let available = area.width.saturating_sub(1);
+    let width = max_width.map_or(available, |max| max.min(available));
+    let x = match align {
+        ContentAlign::Center => area.x + (available - width) / 2,
+        ContentAlign::Left => area.x,
+    };
+    Rect::new(x, area.y, width, area.height)
+}
+
+/// Rows of one message wrapped to `width`, cached by `App::render_cache`.
+/// Continuation rows of a wrapped line are indented like the text above them.
+/// With `pretty_json`, JSON lines and blocks are laid out by `json_view`.
+fn layout_message(message: &Message, width: u16, compact: bool, pretty_json: bool) -> Vec<CachedRow> {
+    let prefix = match message.role {
+        MessageRole::User => ("> ", Style::default().fg(SOFT_GREEN)),
+        MessageRole::Assistant => ("  ", Style::default().fg(SOFT_BLUE)),
+        MessageRole::System => ("  ", Style::default().fg(DIM_GRAY)),
+        MessageRole::Error => ("! ", Style::default().fg(SOFT_RED)),
+    };
+
+    let content_style = match message.role {
+        MessageRole::User => Style::default().fg(MUTED_WHITE),
+        MessageRole::Assistant => Style::default().fg(MUTED_WHITE),
+        MessageRole::System => Style::default().fg(DIM_GRAY),
+        MessageRole::Error => Style::default().fg(SOFT_RED),
+    };
+
+    let text_width = (width as usize).saturating_sub(2);
+    let mut rows = Vec::new();
+    let mut in_code_block = false;
+    let mut in_diff = false;
+    let mut code_blocks = 0;
+    // In compact mode leading blank lines are dropped so the role prefix
+    // lands on the first line with content, even if that is code
+    let content = if compact {
+        message.content.trim_start_matches(['\n', '\r'])
+    } else {
+        message.content.as_str()
+    };
+    let mut prefix_pending = true;
+    let blocks = clipboard::fenced_blocks(content);
+    let mut language = "text";
+    // The open code block was laid out whole as JSON
+    let mut in_json_block = false;
+
+    for line in content.lines() {
+        if let Some(label) = line.strip_prefix("```") {
+            in_code_block = !in_code_block;
+            in_diff = in_code_block && label.trim_end() == "diff";
+            // A block still being streamed has no content to judge yet
+            language = match blocks.get(code_blocks) {
+                Some(block) => block.language,
+                None => language::from_fence(label).unwrap_or("text"),
+            };
+            if !in_code_block {
+                code_blocks += 1;
+            }
+            if in_code_block && !compact {
+                rows.push(CachedRow { line: Line::from(""), kind: RowKind::Text });
+            }
+            // Comfortable mode only prefixes the message's very first line
+            prefix_pending &= compact;
+            let json_fence = json_view::is_json_fence(label) || label.trim().is_empty();
+            let json = blocks
+                .get(code_blocks)
+                .filter(|_| in_code_block && pretty_json && json_fence)
+                .and_then(|block| json_view::rows(&block.code));
+            in_json_block = json.is_some();
+            for row in json.iter().flatten() {
+                let kind = RowKind::Code(code_blocks);
+                push_wrapped(&mut rows, json_pieces(row), kind, text_width, prefix, &mut prefix_pending);
+            }
+            continue;
+        }
+        if in_json_block {
+            continue;
+        }
+
+        let json = match message.role {
+            MessageRole::Assistant | MessageRole::System if pretty_json && !in_code_block => json_view::rows(line),
+            _ => None,
+        };
+        if let Some(json_rows) = json {
+            for row in &json_rows {
+                push_wrapped(&mut rows, json_pieces(row), RowKind::Text, text_width, prefix, &mut prefix_pending);
+            }
+            continue;
+        }
+
+        let line = markdown::clip(line);
+        let (pieces, kind) = if in_diff {
+            let color = diff_color(&line);
+            (vec![(line.into_owned(), Style::default().fg(color))], RowKind::Code(code_blocks))
+        } else if in_code_block {
+            (code_pieces(&line, language), RowKind::Code(code_blocks))
+        } else if message.role == MessageRole::Assistant {
+            (styled_markdown(&line, content_style), RowKind::Text)
+        } else {
+            (vec![(line.into_owned(), content_style)], RowKind::Text)
+        };
+        push_wrapped(&mut rows, pieces, kind, text_width, prefix, &mut prefix_pending);
+    }
+
+    if message.truncated {
+        rows.push(CachedRow {
+            line: Line::from(vec![
+                Span::raw("  "),
+                Span::styled("… truncated", Style::default().fg(DIM_GRAY).add_modifier(Modifier::ITALIC)),
+            ]),
+            kind: RowKind::Text,
+        });
+    }
+    if message.cached {
+        rows.push(CachedRow {
+            line: Line::from(vec![
+                Span::raw("  "),
+                Span::styled("(cached)", Style::default().fg(DIM_GRAY).add_modifier(Modifier::ITALIC)),
+            ]),
+            kind: RowKind::Text,
+        });
+    }
+
+    if !compact {
+        rows.push(CachedRow { line: Line::from(""), kind: RowKind::Spacer });
+    }
+    rows
+}
+
+/// `pieces` wrapped to `width` and added to `rows`, the first row after the
+/// role's prefix if it is still `pending` and the rest indented
+fn push_wrapped(
+    rows: &mut Vec<CachedRow>,
+    pieces: Vec<(String, Style)>,
+    kind: RowKind,
+    width: usize,
+    (prefix, prefix_style): (&'static str, Style),
+    pending: &mut bool,
+) {
+    for spans in render_cache::wrap_styled(&pieces, width) {
+        let line_prefix = if *pending { prefix } else { "  " };
+        *pending = false;
+        let mut line = vec![Span::styled(line_prefix, prefix_style)];
+        line.extend(spans);
+        rows.push(CachedRow { line: Line::from(line), kind });
+    }
+}
+
+/// A row of pretty-printed JSON: keys, strings and other values in their
+/// own colours, folded parts dimmed
+fn json_pieces(row: &json_view::Row) -> Vec<(String, Style)> {
+    row.iter()
+        .map(|(text, kind)| {
+            let style = match kind {
+                json_view::Kind::Key => Style::default().fg(SOFT_BLUE),
+                json_view::Kind::String => Style::default().fg(SOFT_GREEN),
+                json_view::Kind::Literal => Style::default().fg(AMBER),
+                json_view::Kind::Punctuation => Style::default().fg(DIM_GRAY),
+                json_view::Kind::Folded => Style::default().fg(DIM_GRAY).add_modifier(Modifier::ITALIC),
+            };
+            (text.clone(), style)
+        })
+        .collect()
+}
+
+/// A line of code, with its comment dimmed
+fn code_pieces(line: &str, language: &str) -> Vec<(String, Style)> {
+    let code = Style::default().fg(SOFT_BLUE);
+    match language::comment_start(language, line) {
+        Some(at) => vec![(line[..at].to_string(), code), (line[at..].to_string(), Style::default().fg(DIM_GRAY))],
+        None => vec![(line.to_string(), code)],
+    }
+}
+
+/// A reply line with its inline markdown applied: delimiters dropped, link
+/// text underlined with the URL dimmed after it
+fn styled_markdown(line: &str, base: Style) -> Vec<(String, Style)> {
+    markdown::parse(line)
+        .into_iter()
+        .filter_map(|span| {
+            let style = match span.kind {
+                markdown::Kind::Marker => return None,
+                markdown::Kind::Code => Style::default().fg(SOFT_BLUE),
+                markdown::Kind::Url => return Some((format!(" ({})", span.text), Style::default().fg(DIM_GRAY))),
+                markdown::Kind::Text(format) => {
+                    let mut style = base;
+                    for (on, modifier) in [
+                        (format.bold, Modifier::BOLD),
+                        (format.italic, Modifier::ITALIC),
+                        (format.strike, Modifier::CROSSED_OUT),
+                        (format.link, Modifier::UNDERLINED),
+                    ] {
+                        if on {
+                            style = style.add_modifier(modifier);
+                        }
+                    }
+                    style
+                }
+            };
+            Some((span.text.to_string(), style))
+        })
+        .collect()
+}
+
+/// Lines of a ```diff block: additions green, removals red, hunk headers cyan
+fn diff_color(line: &str) -> Color {
+    if line.starts_with("+++") || line.starts_with("---") {
+        MUTED_WHITE
+    } else if line.starts_with('+') {
+        SOFT_GREEN
+    } else if line.starts_with('-') {
+        SOFT_RED
+    } else if line.starts_with("@@") {
+        CYAN
+    } else {
+        DIM_GRAY
+    }
+}
+
+/// Copy button (replies, with mouse capture) and message time drawn over the
+/// right edge of the text column on each message's first row. Kept out of the message lines so
+/// relative times can change without rebuilding them; each is skipped where
+/// the text would be covered.
+fn render_headers(frame: &mut Frame, app: &mut App, column: Rect, header_lines: &[(usize, usize, usize)]) {
+    const COPY_BUTTON: &str = "⧉";
+    let now = chrono::Local::now();
+    let offset = app.scroll.offset();
+    let inner_height = column.height.saturating_sub(2) as usize;
+    let row_width = column.width as usize;
+
+    for &(line, index, line_width) in header_lines {
+        if line < offset || line >= offset + inner_height {
+            continue;
+        }
+        let y = column.y + 1 + (line - offset) as u16;
+        let mut width = row_width;
+
+        let button = app.config.ui.mouse_capture && app.messages[index].role == MessageRole::Assistant;
+        if button && line_width + 2 <= width {
+            let rect = Rect::new(column.x + width as u16 - 1, y, 1, 1);
+            frame.render_widget(Paragraph::new(Span::styled(COPY_BUTTON, Style::default().fg(CYAN))), rect);
+            app.hits.copy_buttons.push((rect, index));
+            width -= 2;
+        }
+
+        if !app.config.ui.show_timestamps {
+            continue;
+        }
+        let label = format::timestamp(&app.messages[index].timestamp, &now, app.config.ui.time_format);
+        let label_width = label.chars().count();
+        if line_width + label_width + 2 > width {
+            continue;
+        }
+        frame.render_widget(
+            Paragraph::new(Span::styled(label, Style::default().fg(DIM_GRAY))).alignment(Alignment::Right),
+            Rect::new(column.x, y, width as u16, 1),
+        );
+    }
+}
+
+/// Ctrl+B numbers over the prefix column of each code block on screen
+fn render_block_labels(frame: &mut Frame, app: &App) {
+    let Some(picker) = &app.block_picker else {
+        return;
+    };
+    for (block, rect) in app.hits.code_block_spans() {
+        let Some(number) = picker.label(block) else {
+            continue;
+        };
+        let style = if picker.chosen() == Some(block) {
+            Style::default().fg(Color::Black).bg(AMBER).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(Color::Black).bg(CYAN).add_modifier(Modifier::BOLD)
+        };
+        let cell = Rect::new(rect.x, rect.y, 2.min(rect.width), 1);
+        frame.render_widget(Paragraph::new(Span::styled(format!("{} ", number), style)), cell);
+    }
+}
+
+/// "↓ new messages" in the bottom-right corner while scrolled up
+fn render_new_messages_marker(frame: &mut Frame, app: &mut App, area: Rect) {
+    let label = if app.accessible { " New messages below " } else { " ↓ new messages " };
+    let width = label.chars().count() as u16;
+    if area.width < width + 2 || area.height < 2 {
+        return;
+    }
+    let rect = Rect::new(area.right() - width - 2, area.bottom() - 1, width, 1);
+    frame.render_widget(
+        Paragraph::new(Span::styled(label, Style::default().fg(Color::Black).bg(CYAN))),
+        rect,
+    );
+    app.hits.new_messages = Some(rect);
+}
+
+/// Centered hint for an empty chat, e.g. after /clear
+fn render_empty_state(frame: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default()
+        .borders(borders(app, Borders::TOP))
+        .border_style(Style::default().fg(DIM_GRAY));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let mut lines = vec![Line::from(""); (inner.height / 2).saturating_sub(1) as usize];
+    lines.push(Line::from(vec![
+        Span::styled("Type a message or ", Style::default().fg(DIM_GRAY)),
+        Span::styled("/help", Style::default().fg(CYAN)),
+        Span::styled(" to begin", Style::default().fg(DIM_GRAY)),
+    ]));
+    frame.render_widget(
+        Paragraph::new(lines).alignment(Alignment::Center),
+        inner,
+    );
+}
+
+/// Shown instead of the chat after an idle logout, with the outcome of the
+/// last login attempt underneath
+fn render_lock_screen(frame: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default()
+        .borders(borders(app, Borders::TOP))
+        .border_style(Style::default().fg(DIM_GRAY));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let mut lines = vec![Line::from(""); (inner.height / 2).saturating_sub(2) as usize];
+    lines.push(Line::from(Span::styled(
+        "🔒 Session locked due to inactivity",
+        Style::default().fg(MUTED_WHITE).add_modifier(Modifier::BOLD),
+    )));
+    lines.push(Line::from(""));
+    lines.push(Line::from(vec![
+        Span::styled("Log in with ", Style::default().fg(DIM_GRAY)),
+        Span::styled("/login <email> <password>", Style::default().fg(CYAN)),
+        Span::styled(" to continue", Style::default().fg(DIM_GRAY)),
+    ]));
+    if let Some(last) = app.messages.last() {
+        let color = if last.role == MessageRole::Error { SOFT_RED } else { DIM_GRAY };
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(last.content.clone(), Style::default().fg(color))));
+    }
+    frame.render_widget(
+        Paragraph::new(lines).alignment(Alignment::Center).wrap(Wrap { trim: true }),
+        inner,
+    );
+}
+
+/// "thinking...", then elapsed time and (once streamed) characters received
+fn loading_label(progress: Option<AiProgress>, held: Option<Held>) -> String {
+    if let Some(held) = held {
+        let left = held.retry_at.saturating_duration_since(std::time::Instant::now());
+        let quota = match held.remaining {
+            Some(n) => format!(" ({} requests left)", n),
+            None => String::new(),
+        };
+        return format!(
+            "Queued — retrying in {}s{} · Esc to cancel",
+            left.as_secs_f64().ceil() as u64,
+            quota
+        );
+    }
+    match progress {
+        None => "thinking...".to_string(),
+        Some(p) if p.received_chars > 0 => format!(
+            "received {} chars… {}",
+            p.received_chars,
+            format::duration(p.elapsed)
+        ),
+        Some(p) => format!("thinking... {}", format::duration(p.elapsed)),
+    }
 }
 
 fn render_input(frame: &mut Frame, app: &App, area: Rect) {
+    if let Some((query, found)) = app.history_search_view() {
+        render_history_search(frame, query, found, area);
+        return;
+    }
+    let masked: String;
     let input_text = if app.is_loading {
         Span::styled("...", Style::default().fg(DIM_GRAY))
+    } else if let (true, Some(wizard)) = (app.input.is_empty(), &app.wizard) {
+        Span::styled(wizard.hint(), Style::default().fg(DIM_GRAY))
+    } else if app.is_secret_input() {
+        // Never render secrets entered during setup or /keys set
+        masked = "•".repeat(app.input.chars().count());
+        Span::styled(masked.as_str(), Style::default().fg(MUTED_WHITE))
+    } else if app.input.is_empty() && app.pending_key.is_some() {
+        Span::styled("Paste key (hidden) - empty Enter cancels", Style::default().fg(DIM_GRAY))
     } else if app.input.is_empty() {
         // Show helpful hint based on auth status
-        if app.user_email.is_some() {
+        if app.capabilities.can_chat(app.user_email.is_some()) {
             Span::styled("Type a message or / for commands...", Style::default().fg(DIM_GRAY))
+        } else if app.capabilities.is_guest() {
+            Span::styled("Type /connect or /keys set ai to get started...", Style::default().fg(DIM_GRAY))
         } else {
             Span::styled("Type /login or /register to get started...", Style::default().fg(DIM_GRAY))
         }
@@ -165,20 +648,27 @@ fn render_input(frame: &mut Frame, app: &App, area: Rect) {
         Span::styled(&app.input, Style::default().fg(MUTED_WHITE))
     };
 
-    let input_widget = Paragraph::new(Line::from(vec![
+    let mut lines = vec![Line::from(vec![
         Span::styled("> ", Style::default().fg(DIM_GRAY)),
         input_text,
-    ]))
+    ])];
+    // Block picker keys, or wrong arguments for a slash command
+    let hint = app.block_picker.as_ref().map(|p| p.hint()).or_else(|| app.command_hint());
+    if let Some(hint) = hint {
+        lines.push(Line::from(Span::styled(format!("  {}", hint), Style::default().fg(AMBER))));
+    }
+
+    let input_widget = Paragraph::new(lines)
     .block(
         Block::default()
-            .borders(Borders::TOP)
+            .borders(borders(app, Borders::TOP))
             .border_style(Style::default().fg(DIM_GRAY))
     );
 
     frame.render_widget(input_widget, area);
 
     if !app.is_loading {
-        let cursor_x = area.x + 2 + app.input.len() as u16;
+        let cursor_x = area.x + 2 + app.input[..app.input_cursor()].chars().count() as u16;
         let cursor_y = area.y + 1;
         if cursor_x < area.x + area.width - 1 {
             frame.set_cursor_position((cursor_x, cursor_y));
@@ -186,24 +676,86 @@ fn render_input(frame: &mut Frame, app: &App, area: Rect) {
     }
 }
 
+/// Ctrl+R in place of the input: the query, then the match with the
+/// query highlighted in it
+fn render_history_search(frame: &mut Frame, query: &str, found: Option<Match>, area: Rect) {
+    let label = if found.is_some() || query.is_empty() { "(reverse-i-search)" } else { "(failed reverse-i-search)" };
+    let prompt = format!("{}`{}': ", label, query);
+    let mut spans = vec![Span::styled(prompt.clone(), Style::default().fg(DIM_GRAY))];
+    if let Some((entry, range)) = found {
+        // Multi-line entries shown on one line; same byte length, so `range` still fits
+        let entry = entry.replace('\n', " ");
+        spans.push(Span::styled(entry[..range.start].to_string(), Style::default().fg(MUTED_WHITE)));
+        spans.push(Span::styled(
+            entry[range.clone()].to_string(),
+            Style::default().fg(Color::Black).bg(CYAN).add_modifier(Modifier::BOLD),
+        ));
+        spans.push(Span::styled(entry[range.end..].to_string(), Style::default().fg(MUTED_WHITE)));
+    }
+    let lines = vec![
+        Line::from(spans),
+        Line::from(Span::styled("  Ctrl+R older · Enter edit · Esc cancel", Style::default().fg(AMBER))),
+    ];
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::default().borders(Borders::TOP).border_style(Style::default().fg(DIM_GRAY))),
+        area,
+    );
+    let cursor_x = area.x + prompt.chars().count() as u16 - 3;
+    if cursor_x < area.x + area.width - 1 {
+        frame.set_cursor_position((cursor_x, area.y + 1));
+    }
+}
+
 fn render_status_bar(frame: &mut Frame, app: &App, area: Rect) {
-    let status_parts: Vec<Span> = vec![
-        if let Some(email) = &app.user_email {
-            Span::styled(email.as_str(), Style::default().fg(DIM_GRAY))
+    let dim = Style::default().fg(DIM_GRAY);
+    let mut segments = vec![
+        if let Some(name) = app.user_name.as_ref().or(app.user_email.as_ref()) {
+            Segment::user(name, dim, 80)
+        } else if app.capabilities.is_guest() {
+            Segment::new("guest", Style::default().fg(CYAN), 80).required()
         } else {
-            Span::styled("not logged in", Style::default().fg(DIM_GRAY))
+            Segment::new("not logged in", dim, 80).required()
         },
-        Span::styled(" · ", Style::default().fg(DIM_GRAY)),
-        Span::styled("esc to exit", Style::default().fg(DIM_GRAY)),
-        Span::styled(" · ", Style::default().fg(DIM_GRAY)),
-        Span::styled("tab for commands", Style::default().fg(DIM_GRAY)),
+        Segment::new("esc to exit", dim, 10),
+        Segment::new("tab for commands", dim, 20).or("tab"),
     ];
 
-    let status_widget = Paragraph::new(Line::from(status_parts));
-    frame.render_widget(status_widget, area);
+    if app.stats.tokens_used > 0 {
+        let count = format::count(app.stats.tokens_used);
+        segments.push(Segment::new(format!("{} tokens", count), dim, 40).or(count));
+    }
+
+    // Database indicator, only shown when connected to a database directly
+    if let Some(db) = &app.db {
+        let (label, short, color) = match db.health() {
+            DbHealth::Connected => ("db ●", "●", SOFT_GREEN),
+            DbHealth::Connecting => ("db connecting…", "○", DIM_GRAY),
+            DbHealth::Reconnecting => ("db reconnecting…", "◌", SOFT_RED),
+        };
+        segments.push(Segment::new(label, Style::default().fg(color), 70).or(short));
+    }
+
+    // Accessible mode posts notices to the transcript instead
+    if let Some(notice) = app.notice(std::time::Instant::now()).filter(|_| !app.accessible) {
+        segments.push(Segment::new(notice, Style::default().fg(CYAN), 60));
+    }
+
+    if app.insecure_jwt_secret {
+        segments.push(
+            Segment::new(
+                "⚠ default JWT secret",
+                Style::default().fg(Color::Black).bg(SOFT_RED).add_modifier(Modifier::BOLD),
+                90,
+            )
+            .or("⚠ JWT"),
+        );
+    }
+
+    let line = status_line::compose(&segments, dim, area.width as usize);
+    frame.render_widget(Paragraph::new(line), area);
 }
 
-fn render_suggestions(frame: &mut Frame, app: &App, area: Rect) {
+fn render_suggestions(frame: &mut Frame, app: &mut App, area: Rect) {
     if area.height < 2 {
         return; // Not enough space
     }
@@ -224,7 +776,11 @@ fn render_suggestions(frame: &mut Frame, app: &App, area: Rect) {
                 Style::default().fg(MUTED_WHITE)
             };
             
-            let prefix = if is_selected { " ▶ " } else { "   " };
+            let prefix = match (is_selected, app.accessible) {
+                (true, true) => " > ",
+                (true, false) => " ▶ ",
+                (false, _) => "   ",
+            };
             Line::from(vec![
                 Span::raw(prefix),
                 Span::styled(suggestion, style),
@@ -232,16 +788,187 @@ fn render_suggestions(frame: &mut Frame, app: &App, area: Rect) {
         })
         .collect();
     
+    let title = if app.accessible {
+        " Suggestions, Up and Down to move, Tab to select "
+    } else {
+        " Suggestions (↑↓ to navigate, Tab to select) "
+    };
     let block = Block::default()
-        .borders(Borders::ALL)
+        .borders(borders(app, Borders::ALL))
         .border_style(Style::default().fg(CYAN))
         .title(Span::styled(
-            " Suggestions (↑↓ to navigate, Tab to select) ",
+            title,
             Style::default().fg(CYAN).add_modifier(Modifier::BOLD),
         ));
     
+    let rows = suggestions.len().min(area.height.saturating_sub(2) as usize);
+    for i in 0..rows {
+        let rect = Rect::new(area.x + 1, area.y + 1 + i as u16, area.width.saturating_sub(2), 1);
+        app.hits.suggestions.push((rect, i));
+    }
+    
     let paragraph = Paragraph::new(suggestions)
         .block(block);
     
     frame.render_widget(paragraph, area);
 }
+
+/// Centered /stats panel drawn over the chat; any key closes it
+fn render_stats(frame: &mut Frame, app: &App) {
+    let stats = &app.stats;
+    let area = centered_rect(frame.area(), 52, 16);
+
+    let label = |text: &'static str| Span::styled(text, Style::default().fg(DIM_GRAY));
+    let value = |text: String| Span::styled(text, Style::default().fg(MUTED_WHITE));
+    let received = app.messages.iter().filter(|m| m.role == MessageRole::Assistant).count();
+    let latency = stats
+        .average_latency()
+        .map(|d| format!("{:.1}s", d.as_secs_f64()))
+        .unwrap_or_else(|| "-".to_string());
+
+    let mut lines = vec![
+        Line::from(vec![label("Messages      "), value(format!("{} sent · {} received", stats.messages_sent, received))]),
+        Line::from(vec![label("Tokens        "), value(format::grouped(stats.tokens_used))]),
+        Line::from(vec![
+            label("Circuits      "),
+            value(format!(
+                "{} generated · {} saved · {} run",
+                stats.circuits_generated, stats.circuits_saved, stats.circuits_run
+            )),
+        ]),
+        Line::from(vec![label("AI latency    "), value(format!("{} avg", latency))]),
+        Line::from(vec![label("Uptime        "), value(format::duration(stats.uptime()))]),
+    ];
+    if let Some(totals) = &stats.all_time {
+        let summary = if totals.is_empty() {
+            "no usage recorded".to_string()
+        } else {
+            totals
+                .iter()
+                .map(|(kind, count)| format!("{} {}", format::grouped(*count as u64), kind.replace('_', " ")))
+                .collect::<Vec<_>>()
+                .join(" · ")
+        };
+        lines.push(Line::from(vec![label("All time      "), value(summary)]));
+    }
+
+    let block = Block::default()
+        .borders(borders(app, Borders::ALL))
+        .border_style(Style::default().fg(CYAN))
+        .title(Span::styled(" Session stats ", Style::default().fg(CYAN).add_modifier(Modifier::BOLD)))
+        .title_bottom(Span::styled(" any key to close ", Style::default().fg(DIM_GRAY)));
+    let inner = block.inner(area);
+    frame.render_widget(Clear, area);
+    frame.render_widget(block, area);
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(lines.len() as u16 + 1),
+            Constraint::Length(1),
+            Constraint::Min(1),
+        ])
+        .split(inner);
+
+    frame.render_widget(Paragraph::new(lines).wrap(Wrap { trim: true }), rows[0]);
+    // The sparkline is drawn in block characters; the average above says it in words
+    if app.accessible {
+        return;
+    }
+    frame.render_widget(Paragraph::new(label("AI latency (recent replies)")), rows[1]);
+    let history = stats.latency_history_ms();
+    frame.render_widget(
+        Sparkline::default()
+            .data(&history)
+            .style(Style::default().fg(SOFT_BLUE)),
+        rows[2],
+    );
+}
+
+/// Ctrl+P palette: the query, then the best matches with their usage; or
+/// the arguments being typed for a command, under its usage
+fn render_palette(frame: &mut Frame, app: &App) {
+    let Some(palette) = &app.palette else {
+        return;
+    };
+    let area = centered_rect(frame.area(), 76, palette::VISIBLE as u16 + 4);
+    let keys = if palette.arguments().is_some() {
+        " Enter run · Esc back "
+    } else {
+        " ↑↓ move · Enter pick · Esc close "
+    };
+    let block = Block::default()
+        .borders(borders(app, Borders::ALL))
+        .border_style(Style::default().fg(CYAN))
+        .title(Span::styled(" Commands ", Style::default().fg(CYAN).add_modifier(Modifier::BOLD)))
+        .title_bottom(Span::styled(keys, Style::default().fg(DIM_GRAY)));
+    let inner = block.inner(area);
+    frame.render_widget(Clear, area);
+    frame.render_widget(block, area);
+
+    if let Some(arguments) = palette.arguments() {
+        let lines = vec![
+            Line::from(Span::styled(format!("Usage: {}", arguments.usage), Style::default().fg(DIM_GRAY))),
+            Line::from(""),
+            Line::from(vec![
+                Span::styled(if app.accessible { "> " } else { "› " }, Style::default().fg(CYAN)),
+                Span::styled(format!("{} ", arguments.command), Style::default().fg(CYAN)),
+                Span::styled(palette.query(), Style::default().fg(MUTED_WHITE)),
+            ]),
+        ];
+        frame.render_widget(Paragraph::new(lines), inner);
+        return;
+    }
+
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled(if app.accessible { "> " } else { "› " }, Style::default().fg(CYAN)),
+            Span::styled(palette.query(), Style::default().fg(MUTED_WHITE)),
+        ]),
+        Line::from(""),
+    ];
+    let matches = palette.matches();
+    if matches.is_empty() {
+        lines.push(Line::from(Span::styled("  No matches", Style::default().fg(DIM_GRAY))));
+    }
+    // Keep the selected row in view
+    let first = palette.selected().saturating_sub(palette::VISIBLE - 1);
+    let width = inner.width as usize;
+    for (i, item) in matches.iter().enumerate().skip(first).take(palette::VISIBLE) {
+        let selected = i == palette.selected();
+        let (name, detail) = match item.kind {
+            ItemKind::Command => (item.text.clone(), item.detail.as_str()),
+            ItemKind::Recent => (item.text.clone(), "recent command"),
+            ItemKind::Prompt => (item.text.lines().next().unwrap_or_default().to_string(), "recent prompt"),
+        };
+        let name = status_line::truncate(&name, width.saturating_sub(4).min(40));
+        let detail = status_line::truncate(detail, width.saturating_sub(name.chars().count() + 5));
+        let style = if selected {
+            Style::default().fg(Color::Black).bg(CYAN).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(MUTED_WHITE)
+        };
+        lines.push(Line::from(vec![
+            Span::raw(match (selected, app.accessible) {
+                (true, true) => " > ",
+                (true, false) => " ▶ ",
+                (false, _) => "   ",
+            }),
+            Span::styled(name, style),
+            Span::styled(format!("  {}", detail), Style::default().fg(DIM_GRAY)),
+        ]));
+    }
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+/// A `width` x `height` rect centered in `area`, shrunk to fit
+fn centered_rect(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
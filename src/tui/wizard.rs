@@ -0,0 +1,379 @@
+// Interactive first-run setup wizard
+//
+// A small state machine driven by the input box: `submit` validates the answer
+// for the current step and either advances or explains what was wrong. It never
+// touches `App` or the network - the caller applies the collected settings,
+// and passes in any IBM Quantum accounts Qiskit has saved so the quantum step
+// can offer to import one.
+
+use crate::config::qiskit::{self, QiskitAccount};
+
+pub const AI_PROVIDERS: [&str; 3] = ["deepseek", "openai", "anthropic"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WizardStep {
+    Provider,
+    AiKey,
+    QuantumKey,
+    Account,
+    Email,
+    Username,
+    Password,
+    Authenticating,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AccountChoice {
+    Login,
+    Register,
+    Skip,
+}
+
+/// What the caller should do after a step was accepted
+#[derive(Debug, Clone, PartialEq)]
+pub enum WizardAction {
+    /// Show the next prompt
+    Continue,
+    /// All answers collected - apply settings, then authenticate if requested
+    Finish,
+}
+
+#[derive(Debug, Clone)]
+pub struct SetupWizard {
+    pub step: WizardStep,
+    pub provider: String,
+    pub ai_key: Option<String>,
+    pub quantum_key: Option<String>,
+    /// Channel and instance of an imported Qiskit account
+    pub quantum_channel: Option<String>,
+    pub quantum_instance: Option<String>,
+    /// Accounts found in ~/.qiskit/qiskit-ibm.json
+    pub qiskit_accounts: Vec<QiskitAccount>,
+    pub account: AccountChoice,
+    pub email: String,
+    pub username: String,
+    pub password: String,
+}
+
+impl Default for SetupWizard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SetupWizard {
+    pub fn new() -> Self {
+        Self {
+            step: WizardStep::Provider,
+            provider: AI_PROVIDERS[0].to_string(),
+            ai_key: None,
+            quantum_key: None,
+            quantum_channel: None,
+            quantum_instance: None,
+            qiskit_accounts: Vec::new(),
+            account: AccountChoice::Skip,
+            email: String::new(),
+            username: String::new(),
+            password: String::new(),
+        }
+    }
+
+    /// Step counter shown in prompts, e.g. "Step 2/4"
+    fn step_number(&self) -> usize {
+        match self.step {
+            WizardStep::Provider => 1,
+            WizardStep::AiKey => 2,
+            WizardStep::QuantumKey => 3,
+            _ => 4,
+        }
+    }
+
+    /// Prompt text for the current step
+    pub fn prompt(&self) -> String {
+        let header = format!("Setup · Step {}/4", self.step_number());
+        match self.step {
+            WizardStep::Provider => format!(
+                "{}\n\nChoose an AI provider: {}\nPress Enter to use {}.",
+                header,
+                AI_PROVIDERS.join(", "),
+                AI_PROVIDERS[0]
+            ),
+            WizardStep::AiKey => format!(
+                "{}\n\nEnter your {} API key (input is hidden).\nPress Enter to keep the built-in shared key.",
+                header, self.provider
+            ),
+            WizardStep::QuantumKey => format!(
+                "{}\n\nEnter your IBM Quantum token (optional, input is hidden).{}\nPress Enter to skip.",
+                header,
+                self.qiskit_offer()
+            ),
+            WizardStep::Account => format!(
+                "{}\n\nDo you want to login or register? (login / register / skip)",
+                header
+            ),
+            WizardStep::Email => "Email address:".to_string(),
+            WizardStep::Username => "Username:".to_string(),
+            WizardStep::Password => "Password (input is hidden):".to_string(),
+            WizardStep::Authenticating => "Authenticating...".to_string(),
+        }
+    }
+
+    /// Placeholder shown in the empty input box
+    pub fn hint(&self) -> &'static str {
+        match self.step {
+            WizardStep::Provider => "deepseek, openai or anthropic (Enter for default)",
+            WizardStep::AiKey => "Paste API key (Enter to skip)",
+            WizardStep::QuantumKey if !self.qiskit_accounts.is_empty() => {
+                "Paste IBM Quantum token, or type import (Enter to skip)"
+            }
+            WizardStep::QuantumKey => "Paste IBM Quantum token (Enter to skip)",
+            WizardStep::Account => "login, register or skip",
+            WizardStep::Email => "you@example.com",
+            WizardStep::Username => "Choose a username",
+            WizardStep::Password => "At least 8 characters",
+            WizardStep::Authenticating => "Please wait...",
+        }
+    }
+
+    /// Whether the current step collects a secret that must be masked
+    pub fn is_secret(&self) -> bool {
+        matches!(
+            self.step,
+            WizardStep::AiKey | WizardStep::QuantumKey | WizardStep::Password
+        )
+    }
+
+    /// Validate and record the answer for the current step
+    pub fn submit(&mut self, input: &str) -> Result<WizardAction, String> {
+        // A secret is taken exactly as typed; a stray space is the user's to see
+        let input = if self.is_secret() { input } else { input.trim() };
+
+        match self.step {
+            WizardStep::Provider => {
+                let provider = if input.is_empty() {
+                    AI_PROVIDERS[0].to_string()
+                } else {
+                    input.to_lowercase()
+                };
+                if !AI_PROVIDERS.contains(&provider.as_str()) {
+                    return Err(format!(
+                        "Unknown provider '{}'. Choose one of: {}",
+                        provider,
+                        AI_PROVIDERS.join(", ")
+                    ));
+                }
+                self.provider = provider;
+                self.step = WizardStep::AiKey;
+            }
+            WizardStep::AiKey => {
+                if !input.is_empty() {
+                    validate_api_key(&self.provider, input)?;
+                    self.ai_key = Some(input.to_string());
+                }
+                self.step = WizardStep::QuantumKey;
+            }
+            WizardStep::QuantumKey => {
+                if let Some(name) = input.strip_prefix("import").filter(|rest| rest.is_empty() || rest.starts_with(' ')) {
+                    let name = Some(name.trim()).filter(|n| !n.is_empty());
+                    let account = qiskit::select(&self.qiskit_accounts, name)?;
+                    self.quantum_key = Some(account.token.clone());
+                    self.quantum_channel = Some(account.channel.clone());
+                    self.quantum_instance = account.instance.clone();
+                } else if !input.is_empty() {
+                    validate_quantum_token(input)?;
+                    self.quantum_key = Some(input.to_string());
+                }
+                self.step = WizardStep::Account;
+            }
+            WizardStep::Account => {
+                self.account = match input.to_lowercase().as_str() {
+                    "login" | "l" => AccountChoice::Login,
+                    "register" | "r" => AccountChoice::Register,
+                    "skip" | "s" | "" => AccountChoice::Skip,
+                    other => {
+                        return Err(format!(
+                            "Unknown choice '{}'. Type login, register or skip.",
+                            other
+                        ))
+                    }
+                };
+                if self.account == AccountChoice::Skip {
+                    return Ok(WizardAction::Finish);
+                }
+                self.step = WizardStep::Email;
+            }
+            WizardStep::Email => {
+                if !input.contains('@') || input.contains(char::is_whitespace) {
+                    return Err("Please enter a valid email address.".to_string());
+                }
+                self.email = input.to_string();
+                self.step = if self.account == AccountChoice::Register {
+                    WizardStep::Username
+                } else {
+                    WizardStep::Password
+                };
+            }
+            WizardStep::Username => {
+                if input.len() < 3 || input.contains(char::is_whitespace) {
+                    return Err(
+                        "Username must be at least 3 characters with no spaces.".to_string()
+                    );
+                }
+                self.username = input.to_string();
+                self.step = WizardStep::Password;
+            }
+            WizardStep::Password => {
+                if self.account == AccountChoice::Register && input.len() < 8 {
+                    return Err("Password must be at least 8 characters.".to_string());
+                }
+                if input.is_empty() {
+                    return Err("Password cannot be empty.".to_string());
+                }
+                self.password = input.to_string();
+                self.step = WizardStep::Authenticating;
+                return Ok(WizardAction::Finish);
+            }
+            WizardStep::Authenticating => {
+                return Err("Still authenticating, please wait...".to_string());
+            }
+        }
+
+        Ok(WizardAction::Continue)
+    }
+
+    /// How the quantum step mentions Qiskit's saved accounts
+    fn qiskit_offer(&self) -> String {
+        match &self.qiskit_accounts[..] {
+            [] => String::new(),
+            [only] => format!("\nQiskit has an account saved: {}. Type import to use it.", only.describe()),
+            accounts => {
+                let names: Vec<&str> = accounts.iter().map(|a| a.name.as_str()).collect();
+                format!(
+                    "\nQiskit has several accounts saved ({}). Type import <name> to use one.",
+                    names.join(", ")
+                )
+            }
+        }
+    }
+
+    /// Authentication failed - go back to the account choice so the user can retry
+    pub fn auth_failed(&mut self) {
+        self.password.clear();
+        self.step = WizardStep::Account;
+    }
+}
+
+/// Basic shape checks for AI provider keys
+pub fn validate_api_key(provider: &str, key: &str) -> Result<(), String> {
+    if key.contains(char::is_whitespace) {
+        return Err("API key must not contain spaces.".to_string());
+    }
+    match provider {
+        "openai" if !key.starts_with("sk-") => {
+            Err("OpenAI keys start with 'sk-'.".to_string())
+        }
+        "anthropic" if !key.starts_with("sk-ant-") => {
+            Err("Anthropic keys start with 'sk-ant-'.".to_string())
+        }
+        _ if key.len() < 20 => Err("API key looks too short.".to_string()),
+        _ => Ok(()),
+    }
+}
+
+/// Basic shape checks for IBM Quantum tokens
+pub fn validate_quantum_token(token: &str) -> Result<(), String> {
+    if token.contains(char::is_whitespace) {
+        return Err("Token must not contain spaces.".to_string());
+    }
+    if token.len() < 20 {
+        return Err("IBM Quantum token looks too short.".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skip_everything() {
+        let mut wizard = SetupWizard::new();
+        assert_eq!(wizard.submit(""), Ok(WizardAction::Continue));
+        assert_eq!(wizard.submit(""), Ok(WizardAction::Continue));
+        assert_eq!(wizard.submit(""), Ok(WizardAction::Continue));
+        assert_eq!(wizard.submit("skip"), Ok(WizardAction::Finish));
+        assert_eq!(wizard.provider, "deepseek");
+        assert!(wizard.ai_key.is_none());
+        assert!(wizard.quantum_key.is_none());
+    }
+
+    #[test]
+    fn test_invalid_answers_do_not_advance() {
+        let mut wizard = SetupWizard::new();
+        assert!(wizard.submit("gpt").is_err());
+        assert_eq!(wizard.step, WizardStep::Provider);
+
+        wizard.submit("openai").unwrap();
+        assert!(wizard.submit("not-a-key-at-all-xxxxxxxx").is_err());
+        assert_eq!(wizard.step, WizardStep::AiKey);
+        assert!(wizard.is_secret());
+    }
+
+    #[test]
+    fn test_only_plain_answers_are_trimmed() {
+        let mut wizard = SetupWizard::new();
+        wizard.submit(" deepseek ").unwrap();
+        wizard.submit("").unwrap();
+        wizard.submit("").unwrap();
+        wizard.submit(" register").unwrap();
+        wizard.submit("  you@example.com ").unwrap();
+        wizard.submit(" quantum_fan ").unwrap();
+        assert_eq!((wizard.email.as_str(), wizard.username.as_str()), ("you@example.com", "quantum_fan"));
+        assert_eq!(wizard.submit("  correct horse  "), Ok(WizardAction::Finish));
+        assert_eq!(wizard.password, "  correct horse  ");
+    }
+
+    #[test]
+    fn test_import_qiskit_account() {
+        let account = |name: &str, token: &str| QiskitAccount {
+            name: name.to_string(),
+            channel: "ibm_quantum".to_string(),
+            token: token.to_string(),
+            instance: Some("ibm-q/open/main".to_string()),
+            is_default: false,
+        };
+        let mut wizard = SetupWizard::new();
+        wizard.qiskit_accounts = vec![account("home", "home-token-0123456789"), account("work", "work-token-0123456789")];
+        wizard.submit("").unwrap();
+        wizard.submit("").unwrap();
+        assert!(wizard.prompt().contains("(home, work). Type import <name>"));
+        assert!(wizard.submit("import").unwrap_err().contains("choose one of"));
+        assert!(wizard.submit("import lab").is_err());
+        assert_eq!(wizard.step, WizardStep::QuantumKey);
+
+        assert_eq!(wizard.submit("import work"), Ok(WizardAction::Continue));
+        assert_eq!(wizard.quantum_key.as_deref(), Some("work-token-0123456789"));
+        assert_eq!(wizard.quantum_instance.as_deref(), Some("ibm-q/open/main"));
+        assert_eq!(wizard.step, WizardStep::Account);
+    }
+
+    #[test]
+    fn test_register_flow() {
+        let mut wizard = SetupWizard::new();
+        wizard.submit("").unwrap();
+        wizard.submit("").unwrap();
+        wizard.submit("").unwrap();
+        wizard.submit("register").unwrap();
+        assert!(wizard.submit("not-an-email").is_err());
+        wizard.submit("ada@example.com").unwrap();
+        assert_eq!(wizard.step, WizardStep::Username);
+        wizard.submit("ada").unwrap();
+        assert!(wizard.submit("short").is_err());
+        assert_eq!(wizard.submit("correct horse"), Ok(WizardAction::Finish));
+        assert_eq!(wizard.step, WizardStep::Authenticating);
+
+        wizard.auth_failed();
+        assert_eq!(wizard.step, WizardStep::Account);
+        assert!(wizard.password.is_empty());
+    }
+}
@@ -0,0 +1,17 @@
+#!/bin/bash
+# Check that the crate builds without a database
+#
+# Runs `cargo check` with DATABASE_URL unset and SQLX_OFFLINE=true, so any
+# compile-time checked sqlx query (query!, query_as!, ...) fails the build
+# instead of silently connecting to a local database.
+
+set -e
+
+cd "$(dirname "$0")"
+
+echo "Checking build without DATABASE_URL..."
+echo ""
+
+env -u DATABASE_URL SQLX_OFFLINE=true cargo check --all-targets --quiet
+
+echo "✅ Builds without a database"
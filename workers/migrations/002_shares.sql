@@ -0,0 +1,15 @@
+-- Conversations and code blocks published with /share
+CREATE TABLE IF NOT EXISTS shares (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    visibility TEXT NOT NULL CHECK(visibility IN ('private', 'unlisted')),
+    -- JSON: { code } or { messages: [{ role, content }] }, redacted before storing
+    content TEXT NOT NULL,
+    created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+    -- NULL keeps the share until it is deleted
+    expires_at INTEGER,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_shares_user_id ON shares(user_id);
+CREATE INDEX IF NOT EXISTS idx_shares_expires_at ON shares(expires_at);
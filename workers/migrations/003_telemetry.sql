@@ -0,0 +1,10 @@
+-- Opt-in usage counters from the CLI (/telemetry on), summed per day.
+-- No user, session or address is stored: only counter names and totals.
+CREATE TABLE IF NOT EXISTS telemetry_counts (
+    day INTEGER NOT NULL,
+    version TEXT NOT NULL,
+    kind TEXT NOT NULL CHECK(kind IN ('commands', 'errors', 'latency')),
+    name TEXT NOT NULL,
+    count INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (day, version, kind, name)
+);
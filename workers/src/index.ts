@@ -4,6 +4,8 @@ import { Env, Variables } from './types';
 import auth from './routes/auth';
 import ai from './routes/ai';
 import quantum from './routes/quantum';
+import share from './routes/share';
+import telemetry from './routes/telemetry';
 
 /**
  * QHub API - TypeScript Backend on Cloudflare Workers
@@ -44,7 +46,9 @@ app.get('/', (c) => {
     endpoints: {
       auth: '/auth',
       ai: '/ai',
-      quantum: '/quantum'
+      quantum: '/quantum',
+      share: '/share',
+      telemetry: '/telemetry'
     },
     documentation: 'https://github.com/your-org/qhub-cli'
   });
@@ -71,6 +75,8 @@ app.get('/version', (c) => {
 app.route('/auth', auth);
 app.route('/ai', ai);
 app.route('/quantum', quantum);
+app.route('/share', share);
+app.route('/telemetry', telemetry);
 
 // 404 handler for unknown routes
 app.notFound((c) => {
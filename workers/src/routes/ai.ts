@@ -2,6 +2,7 @@ import { Hono } from 'hono';
 import { Env, ChatRequest, ChatResponse, User, Conversation, Message, Variables } from '../types';
 import { generateId, now } from '../utils';
 import { authMiddleware } from '../middleware/auth';
+import { checkModel } from '../tiers';
 
 const ai = new Hono<{ Bindings: Env; Variables: Variables }>();
 
@@ -17,14 +18,14 @@ const USAGE_LIMITS: Record<string, number> = {
  * Send a message and get AI response
  * 
  * Requires authentication
- * Body: { message, conversation_id? }
+ * Body: { message, conversation_id?, model? }
  * Returns: { response, conversation_id, tokens_used }
  */
 ai.post('/chat', authMiddleware, async (c) => {
   try {
     const user = c.get('user') as User;
     const body = await c.req.json<ChatRequest>();
-    const { message, conversation_id } = body;
+    const { message, conversation_id, model } = body;
 
     // Validate input
     if (!message || message.trim().length === 0) {
@@ -35,6 +36,12 @@ ai.post('/chat', authMiddleware, async (c) => {
       return c.json({ error: 'Message too long (max 4000 characters)' }, 400);
     }
 
+    // Models beyond the tier are refused before anything is stored
+    const modelError = model ? checkModel(user.tier, model) : null;
+    if (modelError) {
+      return c.json({ error: modelError }, 403);
+    }
+
     // Check usage limits
     const today = Math.floor(Date.now() / 86400000); // Days since epoch
     const usageLimit = USAGE_LIMITS[user.tier] || USAGE_LIMITS.free;
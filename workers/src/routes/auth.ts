@@ -1,5 +1,5 @@
 import { Hono } from 'hono';
-import { Env, RegisterRequest, LoginRequest, AuthResponse, User, Variables } from '../types';
+import { Env, RegisterRequest, LoginRequest, UpdateProfileRequest, AuthResponse, User, Variables } from '../types';
 import { 
   generateId, 
   hashPassword, 
@@ -7,7 +7,8 @@ import {
   generateToken, 
   hashToken,
   now,
-  isValidEmail 
+  isValidEmail,
+  validateDisplayName
 } from '../utils';
 import { authMiddleware } from '../middleware/auth';
 
@@ -17,7 +18,7 @@ const auth = new Hono<{ Bindings: Env; Variables: Variables }>();
  * POST /auth/register
  * Register a new user account
  * 
- * Body: { email, password, username? }
+ * Body: { email, password, username?, display_name? }
  * Returns: { token, user, expires_at }
  */
 auth.post('/register', async (c) => {
@@ -25,6 +26,15 @@ auth.post('/register', async (c) => {
     const body = await c.req.json<RegisterRequest>();
     const { email, password, username } = body;
 
+    let displayName: string | null = null;
+    if (body.display_name !== undefined) {
+      const checked = validateDisplayName(body.display_name);
+      if ('error' in checked) {
+        return c.json({ error: checked.error }, 400);
+      }
+      displayName = checked.name;
+    }
+
     // Validate input
     if (!email || !password) {
       return c.json({ error: 'Email and password are required' }, 400);
@@ -67,13 +77,14 @@ auth.post('/register', async (c) => {
 
     await c.env.DB.prepare(
       `INSERT INTO users (
-        id, email, username, password_hash, tier, 
+        id, email, username, display_name, password_hash, tier, 
         created_at, updated_at, is_active, email_verified
-      ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)`
+      ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)`
     ).bind(
       userId,
       email.toLowerCase(),
       username || null,
+      displayName,
       passwordHash,
       'free',
       timestamp,
@@ -119,6 +130,7 @@ auth.post('/register', async (c) => {
         id: userId,
         email: email.toLowerCase(),
         username: username || null,
+        display_name: displayName,
         tier: 'free'
       },
       expires_at: expiresAt
@@ -206,7 +218,10 @@ auth.post('/login', async (c) => {
         id: user.id,
         email: user.email,
         username: user.username,
-        tier: user.tier
+        display_name: user.display_name,
+        tier: user.tier,
+        // Read before this login updated it: the previous one
+        last_login_at: user.last_login_at
       },
       expires_at: expiresAt
     };
@@ -272,7 +287,7 @@ auth.post('/logout-all', authMiddleware, async (c) => {
  * GET /auth/verify
  * Verify current token and return user info (requires authentication)
  * 
- * Returns: { user: { id, email, username, tier } }
+ * Returns: { user: { id, email, username, display_name, tier, last_login_at } }
  */
 auth.get('/verify', authMiddleware, async (c) => {
   try {
@@ -283,9 +298,11 @@ auth.get('/verify', authMiddleware, async (c) => {
         id: user.id,
         email: user.email,
         username: user.username,
+        display_name: user.display_name,
         tier: user.tier,
         email_verified: user.email_verified === 1,
-        created_at: user.created_at
+        created_at: user.created_at,
+        last_login_at: user.last_login_at
       }
     });
   } catch (error) {
@@ -294,6 +311,42 @@ auth.get('/verify', authMiddleware, async (c) => {
   }
 });
 
+/**
+ * PATCH /auth/profile
+ * Change the current user's profile (requires authentication)
+ * 
+ * Body: { display_name }
+ * Returns: { user: { id, email, username, display_name, tier } }
+ */
+auth.patch('/profile', authMiddleware, async (c) => {
+  try {
+    const user = c.get('user') as User;
+    const body = await c.req.json<UpdateProfileRequest>();
+
+    const checked = validateDisplayName(body.display_name ?? '');
+    if ('error' in checked) {
+      return c.json({ error: checked.error }, 400);
+    }
+
+    await c.env.DB.prepare(
+      'UPDATE users SET display_name = ?, updated_at = ? WHERE id = ?'
+    ).bind(checked.name, now(), user.id).run();
+
+    return c.json({
+      user: {
+        id: user.id,
+        email: user.email,
+        username: user.username,
+        display_name: checked.name,
+        tier: user.tier
+      }
+    });
+  } catch (error) {
+    console.error('Profile update error:', error);
+    return c.json({ error: 'Profile update failed' }, 500);
+  }
+});
+
 /**
  * GET /auth/sessions
  * List all active sessions for current user (requires authentication)
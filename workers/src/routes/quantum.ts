@@ -2,6 +2,7 @@ import { Hono } from 'hono';
 import { Env, QuantumJobRequest, QuantumJobResponse, User, QuantumJob, Variables } from '../types';
 import { generateId, now } from '../utils';
 import { authMiddleware } from '../middleware/auth';
+import { checkJob } from '../tiers';
 
 const quantum = new Hono<{ Bindings: Env; Variables: Variables }>();
 
@@ -17,14 +18,14 @@ const JOB_LIMITS: Record<string, number> = {
  * Submit a new quantum circuit job
  * 
  * Requires authentication
- * Body: { circuit_code, backend?, name? }
+ * Body: { circuit_code, backend?, name?, shots? }
  * Returns: { job_id, status, created_at }
  */
 quantum.post('/submit', authMiddleware, async (c) => {
   try {
     const user = c.get('user') as User;
     const body = await c.req.json<QuantumJobRequest>();
-    const { circuit_code, backend, name } = body;
+    const { circuit_code, backend, name, shots } = body;
 
     // Validate input
     if (!circuit_code || circuit_code.trim().length === 0) {
@@ -35,6 +36,12 @@ quantum.post('/submit', authMiddleware, async (c) => {
       return c.json({ error: 'Circuit code too large (max 50KB)' }, 400);
     }
 
+    // Hardware backends and large jobs depend on the tier
+    const tierError = checkJob(user.tier, backend || 'qiskit_aer_simulator', shots || 1024);
+    if (tierError) {
+      return c.json({ error: tierError }, 403);
+    }
+
     // Check concurrent job limits
     const jobLimit = JOB_LIMITS[user.tier] || JOB_LIMITS.free;
     const activeJobs = await c.env.DB.prepare(
@@ -0,0 +1,174 @@
+import { Hono } from 'hono';
+import { Env, Share, ShareRequest, ShareResponse, User, Variables } from '../types';
+import { generateId, now } from '../utils';
+import { authMiddleware, optionalAuthMiddleware } from '../middleware/auth';
+
+const share = new Hono<{ Bindings: Env; Variables: Variables }>();
+
+// Longest expiry a share can ask for; the CLI offers the same range
+const MAX_EXPIRY_DAYS = 90;
+
+// Upload size limit (characters of code or of all messages together)
+const MAX_SHARE_CHARS = 200000;
+
+const REDACTED = '[REDACTED]';
+
+// Token shapes the CLI also redacts; a second pass in case an older or
+// third-party client skipped it, so no share is ever stored with them
+const SECRET_PATTERNS = [
+  /\bsk-[A-Za-z0-9_\-]{16,}/g,
+  /\bAKIA[0-9A-Z]{16}\b/g,
+  /\bgh[pousr]_[A-Za-z0-9]{30,}/g,
+  /\bbearer\s+[A-Za-z0-9_\-\.=]{16,}/gi,
+];
+
+function redact(text: string): string {
+  return SECRET_PATTERNS.reduce((clean, pattern) => clean.replace(pattern, REDACTED), text);
+}
+
+/**
+ * POST /share
+ * Publish a conversation or a code block
+ *
+ * Requires authentication
+ * Body: { visibility, expires_in_days, code? , messages? }
+ * Returns: { id, url, expires_at }
+ */
+share.post('/', authMiddleware, async (c) => {
+  try {
+    const user = c.get('user') as User;
+    const body = await c.req.json<ShareRequest>();
+    const { visibility, expires_in_days, code, messages } = body;
+
+    // Validate input
+    if (visibility !== 'private' && visibility !== 'unlisted') {
+      return c.json({ error: 'Visibility must be private or unlisted' }, 400);
+    }
+
+    if (expires_in_days !== null && expires_in_days !== undefined) {
+      if (!Number.isInteger(expires_in_days) || expires_in_days < 1 || expires_in_days > MAX_EXPIRY_DAYS) {
+        return c.json({ error: `Expiry must be between 1 and ${MAX_EXPIRY_DAYS} days, or null for none` }, 400);
+      }
+    }
+
+    let content: string;
+    let size: number;
+    if (typeof code === 'string' && !messages?.length) {
+      if (code.trim().length === 0) {
+        return c.json({ error: 'Code is required' }, 400);
+      }
+      content = JSON.stringify({ code: redact(code) });
+      size = code.length;
+    } else if (Array.isArray(messages) && messages.length > 0 && code === undefined) {
+      const valid = messages.every(m =>
+        (m?.role === 'user' || m?.role === 'assistant') && typeof m.content === 'string'
+      );
+      if (!valid) {
+        return c.json({ error: 'Messages must be user or assistant messages with text content' }, 400);
+      }
+      content = JSON.stringify({
+        messages: messages.map(m => ({ role: m.role, content: redact(m.content) }))
+      });
+      size = messages.reduce((total, m) => total + m.content.length, 0);
+    } else {
+      return c.json({ error: 'Send either code or messages' }, 400);
+    }
+
+    if (size > MAX_SHARE_CHARS) {
+      return c.json({ error: `Share too large (max ${MAX_SHARE_CHARS} characters)` }, 400);
+    }
+
+    const shareId = generateId();
+    const timestamp = now();
+    const expiresAt = expires_in_days ? timestamp + expires_in_days * 86400 : null;
+
+    await c.env.DB.prepare(
+      `INSERT INTO shares (id, user_id, visibility, content, created_at, expires_at)
+       VALUES (?, ?, ?, ?, ?, ?)`
+    ).bind(shareId, user.id, visibility, content, timestamp, expiresAt).run();
+
+    const response: ShareResponse = {
+      id: shareId,
+      url: `${new URL(c.req.url).origin}/share/${shareId}`,
+      expires_at: expiresAt
+    };
+
+    return c.json(response, 201);
+  } catch (error) {
+    console.error('Create share error:', error);
+    return c.json({ error: 'Failed to create share' }, 500);
+  }
+});
+
+/**
+ * GET /share/:id
+ * Read a share
+ *
+ * Unlisted shares are open to anyone with the link; private ones only to
+ * their owner. Expired shares are deleted and reported as not found.
+ * Returns: { id, visibility, created_at, expires_at, code? , messages? }
+ */
+share.get('/:id', optionalAuthMiddleware, async (c) => {
+  try {
+    const user = c.get('user') as User | undefined;
+    const shareId = c.req.param('id');
+
+    const row = await c.env.DB.prepare(
+      'SELECT * FROM shares WHERE id = ?'
+    ).bind(shareId).first<Share>();
+
+    if (row && row.expires_at !== null && row.expires_at <= now()) {
+      await c.env.DB.prepare('DELETE FROM shares WHERE id = ?').bind(shareId).run();
+      return c.json({ error: 'Share not found' }, 404);
+    }
+
+    // A private share looks the same as a missing one to everyone else
+    if (!row || (row.visibility === 'private' && row.user_id !== user?.id)) {
+      return c.json({ error: 'Share not found' }, 404);
+    }
+
+    return c.json({
+      id: row.id,
+      visibility: row.visibility,
+      created_at: row.created_at,
+      expires_at: row.expires_at,
+      ...JSON.parse(row.content)
+    });
+  } catch (error) {
+    console.error('Get share error:', error);
+    return c.json({ error: 'Failed to get share' }, 500);
+  }
+});
+
+/**
+ * DELETE /share/:id
+ * Delete one of your shares
+ *
+ * Requires authentication
+ */
+share.delete('/:id', authMiddleware, async (c) => {
+  try {
+    const user = c.get('user') as User;
+    const shareId = c.req.param('id');
+
+    // Verify ownership
+    const row = await c.env.DB.prepare(
+      'SELECT id FROM shares WHERE id = ? AND user_id = ?'
+    ).bind(shareId, user.id).first();
+
+    if (!row) {
+      return c.json({ error: 'Share not found' }, 404);
+    }
+
+    await c.env.DB.prepare(
+      'DELETE FROM shares WHERE id = ?'
+    ).bind(shareId).run();
+
+    return c.json({ message: 'Share deleted' });
+  } catch (error) {
+    console.error('Delete share error:', error);
+    return c.json({ error: 'Failed to delete share' }, 500);
+  }
+});
+
+export default share;
@@ -0,0 +1,70 @@
+import { Hono } from 'hono';
+import { Env, TelemetryBatch, Variables } from '../types';
+import { now } from '../utils';
+
+const telemetry = new Hono<{ Bindings: Env; Variables: Variables }>();
+
+const KINDS = ['commands', 'errors', 'latency'] as const;
+
+// Counter names are fixed strings in the CLI ("model", "rate_limit", "1-3s");
+// anything else is refused rather than stored
+const NAME_PATTERN = /^[A-Za-z0-9_<>+.\-]{1,32}$/;
+const VERSION_PATTERN = /^[0-9A-Za-z.\-+]{1,32}$/;
+
+// Limits per batch, well above what one client collects between uploads
+const MAX_NAMES = 100;
+const MAX_COUNT = 10000;
+
+/**
+ * POST /telemetry
+ * Add a batch of anonymous usage counters
+ *
+ * No authentication: batches are deliberately not tied to an account
+ * Body: { version, commands?, errors?, latency? }
+ * Returns: 204
+ */
+telemetry.post('/', async (c) => {
+  try {
+    const body = await c.req.json<TelemetryBatch>();
+
+    // Validate input
+    if (typeof body?.version !== 'string' || !VERSION_PATTERN.test(body.version)) {
+      return c.json({ error: 'A client version is required' }, 400);
+    }
+
+    const rows: { kind: string; name: string; count: number }[] = [];
+    for (const kind of KINDS) {
+      const counts = body[kind] ?? {};
+      if (typeof counts !== 'object' || Array.isArray(counts)) {
+        return c.json({ error: `${kind} must be an object of counts` }, 400);
+      }
+      for (const [name, count] of Object.entries(counts)) {
+        if (!NAME_PATTERN.test(name) || !Number.isInteger(count) || count < 1 || count > MAX_COUNT) {
+          return c.json({ error: `Invalid ${kind} counter: ${name.slice(0, 32)}` }, 400);
+        }
+        rows.push({ kind, name, count });
+      }
+    }
+
+    if (rows.length > MAX_NAMES) {
+      return c.json({ error: `Too many counters (max ${MAX_NAMES})` }, 400);
+    }
+
+    // Days since epoch, so nothing finer than a date is kept
+    const day = Math.floor(now() / 86400);
+    if (rows.length > 0) {
+      await c.env.DB.batch(rows.map(row => c.env.DB.prepare(
+        `INSERT INTO telemetry_counts (day, version, kind, name, count)
+         VALUES (?, ?, ?, ?, ?)
+         ON CONFLICT (day, version, kind, name) DO UPDATE SET count = count + excluded.count`
+      ).bind(day, body.version, row.kind, row.name, row.count)));
+    }
+
+    return c.body(null, 204);
+  } catch (error) {
+    console.error('Telemetry error:', error);
+    return c.json({ error: 'Failed to record telemetry' }, 500);
+  }
+});
+
+export default telemetry;
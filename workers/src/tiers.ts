@@ -0,0 +1,48 @@
+// Tier capabilities, kept in step with src/config/tiers.rs in the CLI
+
+export interface TierCapabilities {
+  hardware: boolean;
+  // null allows any model
+  models: string[] | null;
+  maxShots: number;
+}
+
+const SMALL_MODELS = ['deepseek-chat', 'gpt-4o-mini', 'gpt-3.5-turbo', 'claude-3-haiku'];
+
+const TIER_CAPABILITIES: Record<string, TierCapabilities> = {
+  free: { hardware: false, models: SMALL_MODELS, maxShots: 4096 },
+  pro: { hardware: true, models: null, maxShots: 100000 },
+  enterprise: { hardware: true, models: null, maxShots: 1000000 }
+};
+
+// Capabilities for a tier, free when it is unknown
+export function tierCapabilities(tier: string): TierCapabilities {
+  return TIER_CAPABILITIES[tier?.toLowerCase()] || TIER_CAPABILITIES.free;
+}
+
+// Error text for a capability the tier does not include
+export function requiresPro(capability: string): string {
+  return `${capability} requires Pro — /upgrade`;
+}
+
+// Null when the model is allowed, the error otherwise
+export function checkModel(tier: string, model: string): string | null {
+  const allowed = tierCapabilities(tier).models;
+  const name = model.split('/').pop()!.toLowerCase();
+  if (allowed && !allowed.includes(name)) {
+    return requiresPro(`Model ${model}`);
+  }
+  return null;
+}
+
+// Null when the job fits the tier, the error otherwise
+export function checkJob(tier: string, backend: string, shots: number): string | null {
+  const caps = tierCapabilities(tier);
+  if (!caps.hardware && !backend.includes('simulator')) {
+    return requiresPro(`Hardware backend ${backend}`);
+  }
+  if (shots > caps.maxShots) {
+    return requiresPro(`${shots} shots per job (the ${tier} limit is ${caps.maxShots})`);
+  }
+  return null;
+}
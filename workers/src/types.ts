@@ -71,11 +71,25 @@ export interface QuantumJob {
   completed_at: number | null;
 }
 
+export interface Share {
+  id: string;
+  user_id: string;
+  visibility: 'private' | 'unlisted';
+  content: string;
+  created_at: number;
+  expires_at: number | null;
+}
+
 // API Request/Response types
 export interface RegisterRequest {
   email: string;
   password: string;
   username?: string;
+  display_name?: string;
+}
+
+export interface UpdateProfileRequest {
+  display_name: string;
 }
 
 export interface LoginRequest {
@@ -89,7 +103,9 @@ export interface AuthResponse {
     id: string;
     email: string;
     username: string | null;
+    display_name: string | null;
     tier: string;
+    last_login_at?: number | null;
   };
   expires_at: number;
 }
@@ -97,6 +113,7 @@ export interface AuthResponse {
 export interface ChatRequest {
   message: string;
   conversation_id?: string;
+  model?: string;
 }
 
 export interface ChatResponse {
@@ -105,10 +122,31 @@ export interface ChatResponse {
   tokens_used: number;
 }
 
+export interface ShareRequest {
+  visibility: 'private' | 'unlisted';
+  expires_in_days: number | null;
+  code?: string;
+  messages?: { role: string; content: string }[];
+}
+
+export interface ShareResponse {
+  id: string;
+  url: string;
+  expires_at: number | null;
+}
+
+export interface TelemetryBatch {
+  version: string;
+  commands?: Record<string, number>;
+  errors?: Record<string, number>;
+  latency?: Record<string, number>;
+}
+
 export interface QuantumJobRequest {
   circuit_code: string;
   backend?: string;
   name?: string;
+  shots?: number;
 }
 
 export interface QuantumJobResponse {
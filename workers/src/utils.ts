@@ -78,6 +78,24 @@ export function isValidEmail(email: string): boolean {
   return emailRegex.test(email);
 }
 
+// Longest display name, in characters (matches the CLI)
+export const MAX_DISPLAY_NAME = 64;
+
+// Trimmed display name, or an error message
+export function validateDisplayName(name: string): { name: string } | { error: string } {
+  const trimmed = name.trim();
+  if (!trimmed) {
+    return { error: 'Display name cannot be empty' };
+  }
+  if ([...trimmed].length > MAX_DISPLAY_NAME) {
+    return { error: `Display name must be at most ${MAX_DISPLAY_NAME} characters` };
+  }
+  if (/[\u0000-\u001f\u007f-\u009f]/.test(trimmed)) {
+    return { error: 'Display name cannot contain control characters' };
+  }
+  return { name: trimmed };
+}
+
 // Error response helper
 export function errorResponse(message: string, status: number = 400) {
   return Response.json({ error: message }, { status });